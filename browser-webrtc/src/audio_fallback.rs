@@ -0,0 +1,156 @@
+use core::time::Duration;
+
+use wasm_bindgen::JsValue;
+
+use crate::stats::{stat_bool, stat_f64, stat_string};
+
+/// Conditions of the automatic switch to audio only and back, used by senders.
+///
+/// The outgoing bitrate is estimated by the browser from the connection stats,
+/// the estimate may stay low while only audio is sent, so the video is restored
+/// only after the estimate is above `restore_bitrate` for `restore_after`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AudioFallbackPolicy {
+    /// Minimum estimated outgoing bitrate in bits per second required to keep the video.
+    pub min_bitrate: u32,
+    /// Time the bitrate stays below the minimum or the video encoding stalls
+    /// before the video is stopped.
+    pub fallback_after: Duration,
+    /// Estimated outgoing bitrate in bits per second required to restore the video.
+    pub restore_bitrate: u32,
+    pub restore_after: Duration,
+}
+
+impl Default for AudioFallbackPolicy {
+    fn default() -> Self {
+        Self {
+            min_bitrate: 100_000,
+            fallback_after: Duration::from_secs(10),
+            restore_bitrate: 300_000,
+            restore_after: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Reason of the switch to audio only.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AudioFallbackReason {
+    /// The estimated outgoing bitrate in bits per second stayed below the minimum.
+    LowBandwidth { available_bitrate: u32 },
+    /// No video frames were encoded while the video was sent.
+    VideoEncodingStalled,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum AudioFallbackChange {
+    Fallback(AudioFallbackReason),
+    Restore,
+}
+
+/// Outgoing video metrics of a single stats report.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct AudioFallbackSample {
+    available_bitrate: Option<f64>,
+    /// Encoded frames of all outgoing video streams, `None` if no video is sent.
+    frames_encoded: Option<f64>,
+}
+
+impl AudioFallbackSample {
+    fn from_stats(stats: &[JsValue]) -> Self {
+        let mut sample = Self::default();
+        for stat in stats {
+            match stat_string(stat, "type").as_deref() {
+                Some("outbound-rtp") if stat_string(stat, "kind").as_deref() == Some("video") => {
+                    if let Some(frames_encoded) = stat_f64(stat, "framesEncoded") {
+                        *sample.frames_encoded.get_or_insert(0.0) += frames_encoded;
+                    }
+                }
+                Some("candidate-pair") if stat_bool(stat, "nominated") == Some(true) => {
+                    if let Some(bitrate) = stat_f64(stat, "availableOutgoingBitrate") {
+                        sample.available_bitrate = Some(bitrate);
+                    }
+                }
+                _ => {}
+            }
+        }
+        sample
+    }
+}
+
+/// Tracks how long the fallback or the restore conditions hold.
+#[derive(Clone, Debug)]
+pub(crate) struct AudioFallbackMonitor {
+    policy: AudioFallbackPolicy,
+    is_audio_only: bool,
+    prev_frames_encoded: Option<f64>,
+    /// Time in milliseconds since the change conditions hold.
+    pending_since: Option<f64>,
+}
+
+impl AudioFallbackMonitor {
+    pub(crate) fn new(policy: AudioFallbackPolicy) -> Self {
+        Self {
+            policy,
+            is_audio_only: false,
+            prev_frames_encoded: None,
+            pending_since: None,
+        }
+    }
+
+    pub(crate) fn policy(&self) -> AudioFallbackPolicy {
+        self.policy
+    }
+
+    pub(crate) fn is_audio_only(&self) -> bool {
+        self.is_audio_only
+    }
+
+    /// Returns the change if the conditions held long enough, `now` is in milliseconds.
+    pub(crate) fn update(&mut self, stats: &[JsValue], now: f64) -> Option<AudioFallbackChange> {
+        let sample = AudioFallbackSample::from_stats(stats);
+        let frames_encoded = sample.frames_encoded.filter(|_| !self.is_audio_only);
+        let prev_frames_encoded = core::mem::replace(&mut self.prev_frames_encoded, frames_encoded);
+
+        let change = if self.is_audio_only {
+            sample
+                .available_bitrate
+                .filter(|&bitrate| bitrate >= f64::from(self.policy.restore_bitrate))
+                .map(|_| AudioFallbackChange::Restore)
+        } else {
+            // Connections without video are never downgraded.
+            match (sample.available_bitrate, sample.frames_encoded) {
+                (Some(bitrate), Some(_)) if bitrate < f64::from(self.policy.min_bitrate) => Some(
+                    AudioFallbackChange::Fallback(AudioFallbackReason::LowBandwidth {
+                        available_bitrate: bitrate as u32,
+                    }),
+                ),
+                (_, Some(frames_encoded)) if prev_frames_encoded == Some(frames_encoded) => Some(
+                    AudioFallbackChange::Fallback(AudioFallbackReason::VideoEncodingStalled),
+                ),
+                _ => None,
+            }
+        };
+
+        let change = match change {
+            Some(change) => change,
+            None => {
+                self.pending_since = None;
+                return None;
+            }
+        };
+        let since = *self.pending_since.get_or_insert(now);
+        let delay = if self.is_audio_only {
+            self.policy.restore_after
+        } else {
+            self.policy.fallback_after
+        };
+        if now - since < delay.as_secs_f64() * 1000.0 {
+            return None;
+        }
+
+        self.pending_since = None;
+        self.prev_frames_encoded = None;
+        self.is_audio_only = matches!(change, AudioFallbackChange::Fallback(_));
+        Some(change)
+    }
+}
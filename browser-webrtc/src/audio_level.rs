@@ -0,0 +1,90 @@
+use core::cell::RefCell;
+
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{AnalyserNode, AudioContext, MediaStream, MediaStreamAudioSourceNode};
+
+/// Number of samples analysed by every `AudioLevelMeter::level` call.
+const AUDIO_LEVEL_FFT_SIZE: u32 = 1024;
+
+/// Measures the current level of audio tracks of a media stream, e.g. for a microphone test.
+#[derive(Debug)]
+pub struct AudioLevelMeter {
+    js_context: AudioContext,
+    js_source: MediaStreamAudioSourceNode,
+    js_analyser: AnalyserNode,
+    samples: RefCell<Vec<f32>>,
+}
+
+impl AudioLevelMeter {
+    pub fn new(media_stream: &MediaStream) -> Result<Self, AudioLevelMeterError> {
+        use web_sys::AudioNode;
+
+        log::trace!("browser_webrtc::AudioLevelMeter::new");
+
+        if media_stream.get_audio_tracks().length() == 0 {
+            return Err(AudioLevelMeterError::NoAudioTracks);
+        }
+
+        let js_context = AudioContext::new().map_err(AudioLevelMeterError::NewAudioContextError)?;
+        let js_source = js_context
+            .create_media_stream_source(media_stream)
+            .map_err(AudioLevelMeterError::CreateSourceError)?;
+        let js_analyser = js_context
+            .create_analyser()
+            .map_err(AudioLevelMeterError::CreateAnalyserError)?;
+        js_analyser.set_fft_size(AUDIO_LEVEL_FFT_SIZE);
+        let _: AudioNode = js_source
+            .connect_with_audio_node(&js_analyser)
+            .map_err(AudioLevelMeterError::ConnectError)?;
+
+        Ok(Self {
+            js_context,
+            js_source,
+            js_analyser,
+            samples: RefCell::new(vec![0.0; AUDIO_LEVEL_FFT_SIZE as usize]),
+        })
+    }
+
+    /// Returns the root mean square of the latest samples, from `0.0` (silence) to `1.0`.
+    pub fn level(&self) -> f64 {
+        let mut samples = self.samples.borrow_mut();
+        self.js_analyser.get_float_time_domain_data(&mut samples);
+        let sum: f64 = samples
+            .iter()
+            .map(|&sample| f64::from(sample) * f64::from(sample))
+            .sum();
+        (sum / samples.len() as f64).sqrt().min(1.0)
+    }
+
+    pub(crate) fn js_context(&self) -> &AudioContext {
+        &self.js_context
+    }
+
+    pub(crate) fn js_analyser(&self) -> &AnalyserNode {
+        &self.js_analyser
+    }
+}
+
+impl Drop for AudioLevelMeter {
+    fn drop(&mut self) {
+        log::trace!("browser_webrtc::AudioLevelMeter::drop");
+
+        let _: Option<()> = self.js_source.disconnect().ok();
+        let _: Option<_> = self.js_context.close().ok();
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AudioLevelMeterError {
+    #[error("media stream has no audio tracks")]
+    NoAudioTracks,
+    #[error("new AudioContext error: {0:?}")]
+    NewAudioContextError(JsValue),
+    #[error("createMediaStreamSource error: {0:?}")]
+    CreateSourceError(JsValue),
+    #[error("createAnalyser error: {0:?}")]
+    CreateAnalyserError(JsValue),
+    #[error("audio node connect error: {0:?}")]
+    ConnectError(JsValue),
+}
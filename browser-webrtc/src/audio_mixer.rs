@@ -0,0 +1,90 @@
+use core::cell::RefCell;
+
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    AudioContext, MediaStream, MediaStreamAudioDestinationNode, MediaStreamAudioSourceNode,
+    MediaStreamTrack,
+};
+
+/// Mixes audio tracks of several media streams into a single audio track.
+#[derive(Debug)]
+pub struct AudioMixer {
+    js_context: AudioContext,
+    js_destination: MediaStreamAudioDestinationNode,
+    js_sources: RefCell<Vec<MediaStreamAudioSourceNode>>,
+}
+
+impl AudioMixer {
+    pub fn new() -> Result<Self, AudioMixerError> {
+        log::trace!("browser_webrtc::AudioMixer::new");
+
+        let js_context = AudioContext::new().map_err(AudioMixerError::NewAudioContextError)?;
+        let js_destination = js_context
+            .create_media_stream_destination()
+            .map_err(AudioMixerError::CreateDestinationError)?;
+
+        Ok(Self {
+            js_context,
+            js_destination,
+            js_sources: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Adds audio tracks of the media stream to the mix,
+    /// media streams without audio tracks are ignored.
+    pub fn add_media_stream(&self, media_stream: &MediaStream) -> Result<(), AudioMixerError> {
+        use web_sys::AudioNode;
+
+        if media_stream.get_audio_tracks().length() == 0 {
+            return Ok(());
+        }
+
+        let js_source = self
+            .js_context
+            .create_media_stream_source(media_stream)
+            .map_err(AudioMixerError::CreateSourceError)?;
+        let _: AudioNode = js_source
+            .connect_with_audio_node(&self.js_destination)
+            .map_err(AudioMixerError::ConnectError)?;
+        self.js_sources.borrow_mut().push(js_source);
+        Ok(())
+    }
+
+    /// Media stream with the single mixed audio track.
+    pub fn media_stream(&self) -> MediaStream {
+        self.js_destination.stream()
+    }
+
+    pub fn audio_track(&self) -> Option<MediaStreamTrack> {
+        use wasm_bindgen::JsCast;
+
+        self.media_stream()
+            .get_audio_tracks()
+            .iter()
+            .find_map(|track| track.dyn_into().ok())
+    }
+}
+
+impl Drop for AudioMixer {
+    fn drop(&mut self) {
+        log::trace!("browser_webrtc::AudioMixer::drop");
+
+        for js_source in self.js_sources.borrow().iter() {
+            let _: Option<()> = js_source.disconnect().ok();
+        }
+        let _: Option<_> = self.js_context.close().ok();
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AudioMixerError {
+    #[error("new AudioContext error: {0:?}")]
+    NewAudioContextError(JsValue),
+    #[error("createMediaStreamDestination error: {0:?}")]
+    CreateDestinationError(JsValue),
+    #[error("createMediaStreamSource error: {0:?}")]
+    CreateSourceError(JsValue),
+    #[error("audio node connect error: {0:?}")]
+    ConnectError(JsValue),
+}
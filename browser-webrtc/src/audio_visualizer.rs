@@ -0,0 +1,226 @@
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+
+use async_std::sync::Arc;
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, MediaStream};
+
+use crate::{AudioLevelMeter, AudioLevelMeterError};
+
+/// Time between the redraws of the visualization canvas.
+const AUDIO_VISUALIZER_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Number of spectrum bars, the frequency bins are grouped into them.
+const AUDIO_VISUALIZER_SPECTRUM_BARS: usize = 32;
+
+const AUDIO_VISUALIZER_BACKGROUND: &str = "#000";
+const AUDIO_VISUALIZER_FOREGROUND: &str = "#4caf50";
+
+/// Canvas drawing the waveform or the spectrum of the audio tracks of a media stream,
+/// so that audio-only streams have visible activity.
+///
+/// The canvas is redrawn periodically until the visualizer is dropped.
+/// Remote streams should also be played by a media element, e.g. a `MediaView`,
+/// otherwise some browsers provide no samples for them.
+#[derive(Debug)]
+pub struct AudioVisualizer {
+    media_stream: MediaStream,
+    meter: AudioLevelMeter,
+    canvas: HtmlCanvasElement,
+    style: Cell<AudioVisualizerStyle>,
+    samples: RefCell<Vec<f32>>,
+    bins: RefCell<Vec<u8>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AudioVisualizerStyle {
+    /// Samples of the latest time window.
+    Waveform,
+    /// Levels of the frequency bands.
+    Spectrum,
+}
+
+impl AudioVisualizer {
+    pub fn new(
+        media_stream: MediaStream,
+        style: AudioVisualizerStyle,
+    ) -> Result<Arc<Self>, NewAudioVisualizerError> {
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+        use web_sys::window;
+
+        use crate::sleep;
+
+        log::trace!("browser_webrtc::AudioVisualizer::new");
+
+        let meter = AudioLevelMeter::new(&media_stream)?;
+        let document = window()
+            .ok_or(NewAudioVisualizerError::WindowIsUndefined)?
+            .document()
+            .ok_or(NewAudioVisualizerError::DocumentIsUndefined)?;
+        let canvas: HtmlCanvasElement = document
+            .create_element("canvas")
+            .map_err(NewAudioVisualizerError::CanvasElementCreateError)?
+            .dyn_into()
+            .unwrap();
+        let _: Option<_> = canvas.set_attribute("class", "audio-visualizer").ok();
+
+        let analyser = meter.js_analyser();
+        let samples = vec![0.0; analyser.fft_size() as usize];
+        let bins = vec![0; analyser.frequency_bin_count() as usize];
+        let visualizer = Arc::new(Self {
+            media_stream,
+            meter,
+            canvas,
+            style: Cell::new(style),
+            samples: RefCell::new(samples),
+            bins: RefCell::new(bins),
+        });
+
+        let visualizer_weak = Arc::downgrade(&visualizer);
+        spawn_local(async move {
+            loop {
+                match visualizer_weak.upgrade() {
+                    Some(visualizer) => {
+                        if let Err(err) = visualizer.draw() {
+                            log::warn!("{}", err);
+                            return;
+                        }
+                    }
+                    None => return,
+                }
+                sleep(AUDIO_VISUALIZER_FRAME_INTERVAL).await;
+            }
+        });
+
+        Ok(visualizer)
+    }
+
+    pub fn media_stream(&self) -> &MediaStream {
+        &self.media_stream
+    }
+
+    /// Canvas to be placed in the document, it is sized to its displayed size on every redraw.
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+
+    pub fn style(&self) -> AudioVisualizerStyle {
+        self.style.get()
+    }
+
+    pub fn set_style(&self, style: AudioVisualizerStyle) {
+        self.style.set(style);
+    }
+
+    /// Resumes the audio analysis suspended by the browser autoplay policy.
+    ///
+    /// Must be called from a user gesture event handler (e.g. click).
+    pub fn resume(&self) {
+        let _: Option<_> = self.meter.js_context().resume().ok();
+    }
+
+    /// Draws the latest samples, it is called periodically by the visualizer itself.
+    pub fn draw(&self) -> Result<(), AudioVisualizerDrawError> {
+        use wasm_bindgen::JsCast;
+
+        let width = self.canvas.client_width().max(0) as u32;
+        let height = self.canvas.client_height().max(0) as u32;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+
+        let context: CanvasRenderingContext2d = self
+            .canvas
+            .get_context("2d")
+            .map_err(AudioVisualizerDrawError::GetContextError)?
+            .and_then(|context| context.dyn_into().ok())
+            .ok_or(AudioVisualizerDrawError::ContextIsUnavailable)?;
+
+        let (width, height) = (f64::from(width), f64::from(height));
+        context.set_fill_style_str(AUDIO_VISUALIZER_BACKGROUND);
+        context.fill_rect(0.0, 0.0, width, height);
+        match self.style.get() {
+            AudioVisualizerStyle::Waveform => self.draw_waveform(&context, width, height),
+            AudioVisualizerStyle::Spectrum => self.draw_spectrum(&context, width, height),
+        }
+        Ok(())
+    }
+
+    fn draw_waveform(&self, context: &CanvasRenderingContext2d, width: f64, height: f64) {
+        let mut samples = self.samples.borrow_mut();
+        self.meter
+            .js_analyser()
+            .get_float_time_domain_data(&mut samples);
+        let step = width / samples.len().saturating_sub(1).max(1) as f64;
+        context.set_stroke_style_str(AUDIO_VISUALIZER_FOREGROUND);
+        context.set_line_width(2.0);
+        context.begin_path();
+        for (index, &sample) in samples.iter().enumerate() {
+            let x = step * index as f64;
+            let y = (1.0 - f64::from(sample).clamp(-1.0, 1.0)) * height / 2.0;
+            if index == 0 {
+                context.move_to(x, y);
+            } else {
+                context.line_to(x, y);
+            }
+        }
+        context.stroke();
+    }
+
+    fn draw_spectrum(&self, context: &CanvasRenderingContext2d, width: f64, height: f64) {
+        let mut bins = self.bins.borrow_mut();
+        self.meter.js_analyser().get_byte_frequency_data(&mut bins);
+        let bins_per_bar = (bins.len() / AUDIO_VISUALIZER_SPECTRUM_BARS).max(1);
+        let bar_width = width / AUDIO_VISUALIZER_SPECTRUM_BARS as f64;
+        context.set_fill_style_str(AUDIO_VISUALIZER_FOREGROUND);
+        for (index, bar) in bins
+            .chunks(bins_per_bar)
+            .take(AUDIO_VISUALIZER_SPECTRUM_BARS)
+            .enumerate()
+        {
+            let level = bar.iter().map(|&bin| f64::from(bin)).sum::<f64>()
+                / (bar.len() as f64 * f64::from(u8::MAX));
+            let bar_height = level * height;
+            context.fill_rect(
+                bar_width * index as f64 + 1.0,
+                height - bar_height,
+                (bar_width - 2.0).max(1.0),
+                bar_height,
+            );
+        }
+    }
+}
+
+impl PartialEq for AudioVisualizer {
+    fn eq(&self, other: &Self) -> bool {
+        self.canvas == other.canvas
+    }
+}
+
+impl Eq for AudioVisualizer {}
+
+#[derive(Error, Debug)]
+pub enum NewAudioVisualizerError {
+    #[error(transparent)]
+    AudioLevelMeterError(#[from] AudioLevelMeterError),
+    #[error("JavaScript window is undefined")]
+    WindowIsUndefined,
+    #[error("JavaScript document is undefined")]
+    DocumentIsUndefined,
+    #[error("failed to create canvas element: {0:?}")]
+    CanvasElementCreateError(JsValue),
+}
+
+#[derive(Error, Debug)]
+pub enum AudioVisualizerDrawError {
+    #[error("canvas get context error: {0:?}")]
+    GetContextError(JsValue),
+    #[error("canvas 2d context is unavailable")]
+    ContextIsUnavailable,
+}
@@ -0,0 +1,53 @@
+use js_sys::{global, Reflect};
+use wasm_bindgen::JsValue;
+
+/// Optional browser features used by the crate.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Capabilities {
+    /// Audio output device selection with `HTMLMediaElement.setSinkId`.
+    pub set_sink_id: bool,
+    /// Insertable streams for encoded media frames.
+    pub encoded_transform: bool,
+    /// Screen sharing with `MediaDevices.getDisplayMedia`.
+    pub display_media: bool,
+    /// Receiver jitter buffer control with `RTCRtpReceiver.jitterBufferTarget`.
+    pub jitter_buffer_target: bool,
+    /// Sending multiple encodings with `RTCPeerConnection.addTransceiver`.
+    ///
+    /// Browsers do not expose simulcast support directly,
+    /// so it is assumed if the required APIs are present.
+    pub simulcast: bool,
+}
+
+/// Probes the running browser for the optional features,
+/// so that unsupported options can be hidden up front.
+pub fn capabilities() -> Capabilities {
+    let global = global();
+    let media_devices = Reflect::get(&global, &JsValue::from_str("navigator"))
+        .and_then(|navigator| Reflect::get(&navigator, &JsValue::from_str("mediaDevices")))
+        .unwrap_or(JsValue::UNDEFINED);
+
+    Capabilities {
+        set_sink_id: has_prototype_property(&global, "HTMLMediaElement", "setSinkId"),
+        encoded_transform: has_prototype_property(&global, "RTCRtpSender", "createEncodedStreams"),
+        display_media: has_property(&media_devices, "getDisplayMedia"),
+        jitter_buffer_target: has_prototype_property(
+            &global,
+            "RTCRtpReceiver",
+            "jitterBufferTarget",
+        ),
+        simulcast: has_prototype_property(&global, "RTCPeerConnection", "addTransceiver")
+            && has_prototype_property(&global, "RTCRtpSender", "setParameters"),
+    }
+}
+
+fn has_prototype_property(global: &JsValue, class: &str, name: &str) -> bool {
+    Reflect::get(global, &JsValue::from_str(class))
+        .and_then(|class| Reflect::get(&class, &JsValue::from_str("prototype")))
+        .map(|prototype| has_property(&prototype, name))
+        .unwrap_or(false)
+}
+
+fn has_property(object: &JsValue, name: &str) -> bool {
+    object.is_object() && Reflect::has(object, &JsValue::from_str(name)).unwrap_or(false)
+}
@@ -0,0 +1,176 @@
+use core::time::Duration;
+
+use async_std::sync::Arc;
+use thiserror::Error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+use web_sys::{Event, SpeechRecognition, SpeechRecognitionEvent};
+
+use crate::Sender;
+
+/// Header of the text message with a caption cue,
+/// followed by the `id\tduration_ms\tis_final\tlanguage` line and the cue text.
+pub(crate) const CAPTION_CUE_HEADER: &str = "caption-cue";
+
+/// Display duration of the recognized speech cues.
+const SPEECH_CUE_DURATION: Duration = Duration::from_secs(4);
+
+/// Timed text cue of the sender captions, shown by the receiver when it is received.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CaptionCue {
+    /// Interim cues are replaced by the next cues with the same id.
+    pub id: u32,
+    /// BCP 47 language tag, see `Sender::set_caption_languages`.
+    pub language: String,
+    pub text: String,
+    /// Time to show the cue for.
+    pub duration: Duration,
+    /// The text of an interim cue may still change, e.g. while the speech is recognized.
+    pub is_final: bool,
+}
+
+impl CaptionCue {
+    pub(crate) fn encode(&self) -> String {
+        format!(
+            "{}\n{}\t{}\t{}\t{}\n{}",
+            CAPTION_CUE_HEADER,
+            self.id,
+            self.duration.as_millis(),
+            u8::from(self.is_final),
+            self.language.replace(['\t', '\n'], " "),
+            self.text
+        )
+    }
+
+    /// Decodes the cue from the lines following the header.
+    pub(crate) fn decode(mut lines: core::str::Split<'_, char>) -> Option<Self> {
+        let mut fields = lines.next()?.split('\t');
+        let id = fields.next()?.parse().ok()?;
+        let duration = Duration::from_millis(fields.next()?.parse().ok()?);
+        let is_final = match fields.next()? {
+            "0" => false,
+            "1" => true,
+            _ => return None,
+        };
+        let language = fields.next()?.to_owned();
+        let text = lines.collect::<Vec<_>>().join("\n");
+        Some(Self {
+            id,
+            language,
+            text,
+            duration,
+            is_final,
+        })
+    }
+}
+
+/// Publishes the captions of the speech recognized with the Web Speech API,
+/// the recognition is stopped on drop.
+///
+/// Browsers recognize the speech of the default microphone,
+/// the recognition restarts when the browser ends it after a silence.
+#[derive(Debug)]
+pub struct SpeechCaptions {
+    js_recognition: SpeechRecognition,
+    _js_result_handler: Closure<dyn FnMut(SpeechRecognitionEvent)>,
+    _js_end_handler: Closure<dyn FnMut(Event)>,
+}
+
+impl SpeechCaptions {
+    pub fn start(sender: &Arc<Sender>, language: &str) -> Result<Self, SpeechCaptionsError> {
+        use crate::closure_1;
+        use core::cell::Cell;
+        use std::rc::Rc;
+        use wasm_bindgen::JsCast;
+
+        let js_recognition =
+            SpeechRecognition::new().map_err(SpeechCaptionsError::RecognitionIsUnsupported)?;
+        js_recognition.set_lang(language);
+        js_recognition.set_interim_results(true);
+        let _: Result<(), _> = js_recognition.set_continuous(true);
+
+        // Every recognition result is a cue, the result indexes start from zero
+        // after every restart, so the ids of the next session start after the used ones.
+        let next_id = Rc::new(Cell::new(0));
+        let result_offset = Rc::new(Cell::new(0));
+        let js_result_handler = {
+            let sender = Arc::downgrade(sender);
+            let language = language.to_owned();
+            let next_id = Rc::clone(&next_id);
+            let result_offset = Rc::clone(&result_offset);
+            closure_1(move |ev: SpeechRecognitionEvent| {
+                let (sender, results) = match (sender.upgrade(), ev.results()) {
+                    (Some(sender), Some(results)) => (sender, results),
+                    _ => return,
+                };
+                for index in ev.result_index()..results.length() {
+                    let result = match results.get(index) {
+                        Some(result) => result,
+                        None => continue,
+                    };
+                    let text = match result.get(0) {
+                        Some(alternative) => alternative.transcript(),
+                        None => continue,
+                    };
+                    let id = result_offset.get() + index;
+                    next_id.set(next_id.get().max(id + 1));
+                    let cue = CaptionCue {
+                        id,
+                        language: language.clone(),
+                        text: text.trim().to_owned(),
+                        duration: SPEECH_CUE_DURATION,
+                        is_final: result.is_final(),
+                    };
+                    if let Err(err) = sender.send_caption(&cue) {
+                        log::debug!("caption is not sent: {}", err);
+                    }
+                }
+            })
+        };
+
+        let js_end_handler = {
+            let js_recognition = js_recognition.clone();
+            closure_1(move |_: Event| {
+                result_offset.set(next_id.get());
+                if let Err(err) = js_recognition.start() {
+                    log::warn!("speech recognition restart error: {:?}", err);
+                }
+            })
+        };
+        js_recognition.set_onresult(Some(js_result_handler.as_ref().unchecked_ref()));
+        js_recognition.set_onend(Some(js_end_handler.as_ref().unchecked_ref()));
+        js_recognition
+            .start()
+            .map_err(SpeechCaptionsError::RecognitionStartError)?;
+
+        Ok(Self {
+            js_recognition,
+            _js_result_handler: js_result_handler,
+            _js_end_handler: js_end_handler,
+        })
+    }
+}
+
+impl Drop for SpeechCaptions {
+    fn drop(&mut self) {
+        self.js_recognition.set_onresult(None);
+        self.js_recognition.set_onend(None);
+        self.js_recognition.abort();
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CaptionSendError {
+    #[error("control channel is not open yet")]
+    ChannelIsNotOpen,
+    #[error("caption send error: {0:?}")]
+    SendError(JsValue),
+}
+
+#[derive(Error, Debug)]
+pub enum SpeechCaptionsError {
+    #[error("speech recognition is not supported: {0:?}")]
+    RecognitionIsUnsupported(JsValue),
+    #[error("speech recognition start error: {0:?}")]
+    RecognitionStartError(JsValue),
+}
@@ -1,12 +1,19 @@
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::convert::{FromWasmAbi, ReturnWasmAbi};
 
+use crate::live_objects::{LiveObject, LiveObjectKind};
+
 pub fn closure_0<F, R>(func: F) -> Closure<dyn FnMut() -> R>
 where
     F: 'static + FnMut() -> R,
     R: 'static + ReturnWasmAbi,
 {
-    let handler: Box<dyn FnMut() -> R> = Box::new(func);
+    let mut func = func;
+    let live_object = LiveObject::new(LiveObjectKind::JsClosure);
+    let handler: Box<dyn FnMut() -> R> = Box::new(move || {
+        let _: &LiveObject = &live_object;
+        func()
+    });
     Closure::wrap(handler)
 }
 
@@ -16,6 +23,27 @@ where
     R: 'static + ReturnWasmAbi,
     T1: 'static + FromWasmAbi,
 {
-    let handler: Box<dyn FnMut(T1) -> R> = Box::new(func);
+    let mut func = func;
+    let live_object = LiveObject::new(LiveObjectKind::JsClosure);
+    let handler: Box<dyn FnMut(T1) -> R> = Box::new(move |arg1| {
+        let _: &LiveObject = &live_object;
+        func(arg1)
+    });
+    Closure::wrap(handler)
+}
+
+pub fn closure_2<F, R, T1, T2>(func: F) -> Closure<dyn FnMut(T1, T2) -> R>
+where
+    F: 'static + FnMut(T1, T2) -> R,
+    R: 'static + ReturnWasmAbi,
+    T1: 'static + FromWasmAbi,
+    T2: 'static + FromWasmAbi,
+{
+    let mut func = func;
+    let live_object = LiveObject::new(LiveObjectKind::JsClosure);
+    let handler: Box<dyn FnMut(T1, T2) -> R> = Box::new(move |arg1, arg2| {
+        let _: &LiveObject = &live_object;
+        func(arg1, arg2)
+    });
     Closure::wrap(handler)
 }
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use signaling_protocol::{CodecError, WireCodec};
+
+/// Message sent through the data channels, each one is sent as a single bincode-encoded
+/// data channel message, so that the text is not mixed up with the binary data.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DataChannelMessage {
+    Text(String),
+    Binary(Vec<u8>),
+    File {
+        name: String,
+        mime_type: String,
+        data: Vec<u8>,
+    },
+}
+
+impl DataChannelMessage {
+    pub fn encode(&self) -> Result<Vec<u8>, CodecError> {
+        WireCodec::Bincode.encode(self)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, CodecError> {
+        WireCodec::Bincode.decode(data)
+    }
+}
+
+/// File sent by `FileSender`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct FileInfo {
+    pub name: String,
+    pub mime_type: String,
+    /// Size in bytes.
+    pub size: u64,
+}
+
+/// Message of the file transfer data channels,
+/// the header is followed by the file chunks in order.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) enum FileTransferMessage {
+    Header(FileInfo),
+    Chunk(Vec<u8>),
+}
+
+impl FileTransferMessage {
+    pub(crate) fn encode(&self) -> Result<Vec<u8>, CodecError> {
+        WireCodec::Bincode.encode(self)
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Result<Self, CodecError> {
+        WireCodec::Bincode.decode(data)
+    }
+}
@@ -1,11 +1,15 @@
 use core::cell::RefCell;
 
 use async_std::sync::Arc;
+use signaling_protocol::{CloseReason, CodecError};
 use thiserror::Error;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsValue;
-use web_sys::{MessageEvent, RtcDataChannel};
+use web_sys::{Event, MessageEvent, RtcDataChannel};
 
+use crate::data_message::DataChannelMessage;
+use crate::live_objects::{LiveObject, LiveObjectKind};
+use crate::peer_connection::JsHandler;
 use crate::{BoxAsyncFn2, BoxAsyncFn2Wrapper, Receiver};
 
 #[derive(Debug)]
@@ -34,8 +38,10 @@ impl DataReceiverBuilder {
 pub struct DataReceiver {
     receiver: Arc<Receiver>,
     handler: BoxAsyncFn2Wrapper<Arc<DataReceiver>, DataReceiverEvent, ()>,
+    _live_object: LiveObject,
     js_channel: RtcDataChannel,
     js_message_handler: RefCell<Option<Closure<dyn FnMut(MessageEvent)>>>,
+    js_close_handler: JsHandler<Event>,
 }
 
 impl DataReceiver {
@@ -53,11 +59,14 @@ impl DataReceiver {
         let data_channel = Arc::new(Self {
             receiver,
             handler: BoxAsyncFn2Wrapper(handler),
+            _live_object: LiveObject::new(LiveObjectKind::DataReceiver),
             js_channel: js_channel,
             js_message_handler: RefCell::new(None),
+            js_close_handler: RefCell::new(None),
         });
 
         data_channel.init_message_handler();
+        data_channel.init_close_handler();
 
         data_channel
     }
@@ -70,8 +79,9 @@ impl DataReceiver {
         let js_message_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |ev: MessageEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_message_event(ev).await })
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_message_event(ev).await });
+                }
             })
         };
         self.js_channel
@@ -80,6 +90,25 @@ impl DataReceiver {
         debug_assert!(prev_handler.is_none());
     }
 
+    fn init_close_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_close_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_close_event().await });
+                }
+            })
+        };
+        self.js_channel
+            .set_onclose(Some(js_close_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.js_close_handler.replace(Some(js_close_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
     async fn handler(self: &Arc<Self>, ev: DataReceiverEvent) {
         self.handler.0(Arc::clone(self), ev).await
     }
@@ -88,6 +117,15 @@ impl DataReceiver {
         self.handler(DataReceiverEvent::Error(err)).await
     }
 
+    /// Channels closed while the receiver is open are closed by the remote peer.
+    async fn on_close_event(self: &Arc<Self>) {
+        let reason = self
+            .receiver
+            .close_reason()
+            .unwrap_or(CloseReason::RemoteClosed);
+        self.handler(DataReceiverEvent::Closed(reason)).await;
+    }
+
     async fn on_message_event(self: &Arc<Self>, ev: MessageEvent) {
         match self.clone().handle_message_event(ev).await {
             Ok(()) => {}
@@ -107,8 +145,23 @@ impl DataReceiver {
             .dyn_into()
             .map_err(DataReceiverError::NonArrayData)?;
         let data = Uint8Array::new(&array_buffer).to_vec();
-
-        self.handler(DataReceiverEvent::Message(data)).await;
+        let message =
+            DataChannelMessage::decode(&data).map_err(DataReceiverError::MessageDecodeError)?;
+
+        let ev = match message {
+            DataChannelMessage::Text(text) => DataReceiverEvent::TextMessage(text),
+            DataChannelMessage::Binary(data) => DataReceiverEvent::Message(data),
+            DataChannelMessage::File {
+                name,
+                mime_type,
+                data,
+            } => DataReceiverEvent::FileMessage {
+                name,
+                mime_type,
+                data,
+            },
+        };
+        self.handler(ev).await;
         Ok(())
     }
 }
@@ -118,13 +171,22 @@ impl Drop for DataReceiver {
         log::trace!("browser_webrtc::DataReceiver::drop");
 
         self.js_channel.set_onmessage(None);
+        self.js_channel.set_onclose(None);
         self.js_channel.close();
     }
 }
 
 #[derive(Debug)]
 pub enum DataReceiverEvent {
+    /// Binary data sent by `DataSender::send`.
     Message(Vec<u8>),
+    TextMessage(String),
+    FileMessage {
+        name: String,
+        mime_type: String,
+        data: Vec<u8>,
+    },
+    Closed(CloseReason),
     Error(DataReceiverError),
 }
 
@@ -132,4 +194,6 @@ pub enum DataReceiverEvent {
 pub enum DataReceiverError {
     #[error("non-array data received: {0:?}")]
     NonArrayData(JsValue),
+    #[error("message decode error: {0}")]
+    MessageDecodeError(CodecError),
 }
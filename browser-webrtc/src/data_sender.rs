@@ -1,20 +1,29 @@
 use core::cell::RefCell;
 
 use async_std::sync::Arc;
+use signaling_protocol::{CloseReason, CodecError};
 use thiserror::Error;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsValue;
 use web_sys::{Event, RtcDataChannel, RtcPeerConnection};
 
+use crate::data_message::DataChannelMessage;
+use crate::live_objects::{LiveObject, LiveObjectKind};
+use crate::peer_connection::JsHandler;
 use crate::{BoxAsyncFn2, BoxAsyncFn2Wrapper, Sender};
 
 #[derive(Debug)]
 pub struct DataSender {
     sender: Arc<Sender>,
     handler: BoxAsyncFn2Wrapper<Arc<DataSender>, DataSenderEvent, ()>,
+    _live_object: LiveObject,
     js_channel: RtcDataChannel,
     js_open_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
     js_error_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+    js_buffered_amount_low_handler: JsHandler<Event>,
+    js_close_handler: JsHandler<Event>,
+    buffered_amount_low_sender: async_std::channel::Sender<()>,
+    buffered_amount_low_receiver: async_std::channel::Receiver<()>,
 }
 
 impl DataSender {
@@ -28,16 +37,26 @@ impl DataSender {
 
         let js_channel = js_connection.create_data_channel(name.as_ref());
 
+        // Wake-ups are coalesced, the buffered amount is checked again after each one.
+        let (buffered_amount_low_sender, buffered_amount_low_receiver) =
+            async_std::channel::bounded(1);
         let data_channel = Arc::new(Self {
             sender,
             handler: BoxAsyncFn2Wrapper(handler),
+            _live_object: LiveObject::new(LiveObjectKind::DataSender),
             js_channel: js_channel,
             js_open_handler: RefCell::new(None),
             js_error_handler: RefCell::new(None),
+            js_buffered_amount_low_handler: RefCell::new(None),
+            js_close_handler: RefCell::new(None),
+            buffered_amount_low_sender,
+            buffered_amount_low_receiver,
         });
 
         data_channel.init_open_handler();
         data_channel.init_error_handler();
+        data_channel.init_buffered_amount_low_handler();
+        data_channel.init_close_handler();
 
         data_channel
     }
@@ -50,8 +69,9 @@ impl DataSender {
         let js_open_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |_: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_open_event().await })
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_open_event().await });
+                }
             })
         };
         self.js_channel
@@ -68,16 +88,61 @@ impl DataSender {
         let js_error_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_error_event(ev).await })
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_error_event(ev).await });
+                }
             })
         };
         self.js_channel
-            .set_onopen(Some(js_error_handler.as_ref().unchecked_ref()));
+            .set_onerror(Some(js_error_handler.as_ref().unchecked_ref()));
         let prev_handler = self.js_error_handler.replace(Some(js_error_handler));
         debug_assert!(prev_handler.is_none());
     }
 
+    fn init_buffered_amount_low_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_buffered_amount_low_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    let _: Result<(), _> = self_arc.buffered_amount_low_sender.try_send(());
+                    spawn_local(async move {
+                        self_arc.handler(DataSenderEvent::BufferedAmountLow).await
+                    });
+                }
+            })
+        };
+        self.js_channel.set_onbufferedamountlow(Some(
+            js_buffered_amount_low_handler.as_ref().unchecked_ref(),
+        ));
+        let prev_handler = self
+            .js_buffered_amount_low_handler
+            .replace(Some(js_buffered_amount_low_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    fn init_close_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_close_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_close_event().await });
+                }
+            })
+        };
+        self.js_channel
+            .set_onclose(Some(js_close_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.js_close_handler.replace(Some(js_close_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
     async fn handler(self: &Arc<Self>, ev: DataSenderEvent) {
         self.handler.0(Arc::clone(self), ev).await
     }
@@ -90,16 +155,98 @@ impl DataSender {
         self.handler(DataSenderEvent::Open).await;
     }
 
+    /// Channels closed while the sender is open are closed by the remote peer.
+    async fn on_close_event(self: &Arc<Self>) {
+        // Wakes up the pending `send_when_ready` calls, so that they fail.
+        let _: Result<(), _> = self.buffered_amount_low_sender.try_send(());
+        let reason = self
+            .sender
+            .close_reason()
+            .unwrap_or(CloseReason::RemoteClosed);
+        self.handler(DataSenderEvent::Closed(reason)).await;
+    }
+
     async fn on_error_event(self: &Arc<Self>, ev: Event) {
         use js_sys::Reflect;
-        let error = Reflect::get(&ev, &JsValue::from_str("error")).unwrap();
+        let error = Reflect::get(&ev, &JsValue::from_str("error")).unwrap_or_else(|_| ev.into());
         self.error(DataSenderError::RtcDataChannelError(error))
             .await;
     }
 
+    /// Maximum message size negotiated by the connection,
+    /// `DEFAULT_MAX_MESSAGE_SIZE` until the limits are known.
+    pub fn max_message_size(&self) -> Option<u32> {
+        use crate::DEFAULT_MAX_MESSAGE_SIZE;
+
+        match self.sender.sctp_limits() {
+            Some(limits) => limits.max_message_size,
+            None => Some(DEFAULT_MAX_MESSAGE_SIZE),
+        }
+    }
+
+    /// Number of bytes queued in the data channel and not sent yet,
+    /// the sending should be paused while it keeps growing.
+    pub fn buffered_amount(&self) -> u32 {
+        self.js_channel.buffered_amount()
+    }
+
+    /// Sets the queued bytes threshold, `DataSenderEvent::BufferedAmountLow`
+    /// is emitted when the buffered amount drops to it.
+    pub fn set_buffered_amount_low_threshold(&self, threshold: u32) {
+        self.js_channel.set_buffered_amount_low_threshold(threshold);
+    }
+
+    /// Sends the message once the buffered amount drops to the low threshold,
+    /// so that the data channel buffer does not keep growing with large payloads.
+    pub async fn send_when_ready(
+        &self,
+        message: &DataChannelMessage,
+    ) -> Result<(), DataSenderSendError> {
+        use web_sys::RtcDataChannelState;
+
+        loop {
+            match self.js_channel.ready_state() {
+                RtcDataChannelState::Closing | RtcDataChannelState::Closed => {
+                    return Err(DataSenderSendError::ChannelIsClosed)
+                }
+                _ => {}
+            }
+            if self.js_channel.buffered_amount() <= self.js_channel.buffered_amount_low_threshold()
+            {
+                return self.send_message(message);
+            }
+            self.buffered_amount_low_receiver
+                .recv()
+                .await
+                .map_err(|_| DataSenderSendError::ChannelIsClosed)?;
+        }
+    }
+
+    /// Sends the binary data, see `send_message`.
     pub fn send(&self, data: &[u8]) -> Result<(), DataSenderSendError> {
+        self.send_message(&DataChannelMessage::Binary(data.to_vec()))
+    }
+
+    /// Sends the text, it is received as is by `DataReceiverEvent::TextMessage`.
+    pub fn send_text(&self, text: &str) -> Result<(), DataSenderSendError> {
+        self.send_message(&DataChannelMessage::Text(text.to_owned()))
+    }
+
+    /// Sends the message, messages exceeding the maximum message size when encoded are rejected.
+    pub fn send_message(&self, message: &DataChannelMessage) -> Result<(), DataSenderSendError> {
+        let data = message
+            .encode()
+            .map_err(DataSenderSendError::MessageEncodeError)?;
+        if let Some(max_size) = self.max_message_size() {
+            if data.len() > max_size as usize {
+                return Err(DataSenderSendError::MessageIsTooLarge {
+                    size: data.len(),
+                    max_size,
+                });
+            }
+        }
         self.js_channel
-            .send_with_u8_array(data)
+            .send_with_u8_array(&data)
             .map_err(DataSenderSendError::RtcDataChannelSendError)
     }
 }
@@ -110,6 +257,8 @@ impl Drop for DataSender {
 
         self.js_channel.set_onopen(None);
         self.js_channel.set_onerror(None);
+        self.js_channel.set_onbufferedamountlow(None);
+        self.js_channel.set_onclose(None);
         self.js_channel.close();
     }
 }
@@ -117,6 +266,9 @@ impl Drop for DataSender {
 #[derive(Debug)]
 pub enum DataSenderEvent {
     Open,
+    /// The buffered amount dropped to the threshold, the sending can be resumed.
+    BufferedAmountLow,
+    Closed(CloseReason),
     Error(DataSenderError),
 }
 
@@ -130,4 +282,10 @@ pub enum DataSenderError {
 pub enum DataSenderSendError {
     #[error("RtcDataChannel send error: {0:?}")]
     RtcDataChannelSendError(JsValue),
+    #[error("message of {size} bytes exceeds the maximum message size of {max_size} bytes")]
+    MessageIsTooLarge { size: usize, max_size: u32 },
+    #[error("message encode error: {0}")]
+    MessageEncodeError(CodecError),
+    #[error("data channel is closed")]
+    ChannelIsClosed,
 }
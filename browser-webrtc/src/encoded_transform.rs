@@ -0,0 +1,175 @@
+use core::cell::RefCell;
+use core::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+use thiserror::Error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+use web_sys::RtcPeerConnection;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EncodedFrameKind {
+    Audio,
+    Video,
+}
+
+/// Transformation of encoded media frames, e.g. an E2EE cipher.
+///
+/// Called synchronously for every encoded frame sent or received by the connection.
+#[derive(Clone)]
+pub struct EncodedFrameTransform(pub Rc<dyn Fn(EncodedFrameKind, Vec<u8>) -> Vec<u8>>);
+
+impl EncodedFrameTransform {
+    pub fn new<F>(func: F) -> Self
+    where
+        F: 'static + Fn(EncodedFrameKind, Vec<u8>) -> Vec<u8>,
+    {
+        Self(Rc::new(func))
+    }
+}
+
+impl Debug for EncodedFrameTransform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("EncodedFrameTransform")
+            .field(&"...")
+            .finish()
+    }
+}
+
+/// Returns `true` if the browser supports insertable streams for encoded media frames.
+pub fn is_encoded_transform_supported() -> bool {
+    use js_sys::{global, Reflect};
+
+    Reflect::get(&global(), &JsValue::from_str("RTCRtpSender"))
+        .and_then(|class| Reflect::get(&class, &JsValue::from_str("prototype")))
+        .and_then(|prototype| Reflect::has(&prototype, &JsValue::from_str("createEncodedStreams")))
+        .unwrap_or(false)
+}
+
+/// Returns `true` if the connection was created with encoded insertable streams.
+///
+/// Browsers drop frames of such connections until encoded streams are piped.
+pub(crate) fn is_encoded_transform_enabled(js_connection: &RtcPeerConnection) -> bool {
+    use js_sys::Reflect;
+
+    Reflect::get(
+        &js_connection.get_configuration(),
+        &JsValue::from_str("encodedInsertableStreams"),
+    )
+    .map(|value| value.is_truthy())
+    .unwrap_or(false)
+}
+
+/// Frame transformation that can be set or replaced after encoded streams are piped,
+/// frames are passed unchanged while it is not set.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SharedEncodedFrameTransform(Rc<RefCell<Option<EncodedFrameTransform>>>);
+
+impl SharedEncodedFrameTransform {
+    pub(crate) fn set(&self, transform: Option<EncodedFrameTransform>) {
+        let _: Option<_> = self.0.replace(transform);
+    }
+
+    fn apply(&self, kind: EncodedFrameKind, data: Vec<u8>) -> Vec<u8> {
+        match self.0.borrow().as_ref() {
+            Some(transform) => transform.0(kind, data),
+            None => data,
+        }
+    }
+}
+
+/// Encoded frames stream of a `RTCRtpSender` or `RTCRtpReceiver`
+/// piped through the frame transformation.
+#[derive(Debug)]
+pub(crate) struct EncodedTransformPipe {
+    _js_transform_handler: Closure<dyn FnMut(JsValue, JsValue)>,
+}
+
+impl EncodedTransformPipe {
+    pub(crate) fn new(
+        js_sender_or_receiver: &JsValue,
+        transform: SharedEncodedFrameTransform,
+    ) -> Result<Self, EncodedTransformError> {
+        use crate::closure_2;
+        use js_sys::{global, Array, Function, Object, Reflect, Uint8Array};
+        use wasm_bindgen::JsCast;
+
+        if !is_encoded_transform_supported() {
+            return Err(EncodedTransformError::Unsupported);
+        }
+
+        let streams = js_call_method(js_sender_or_receiver, "createEncodedStreams", &Array::new())
+            .map_err(EncodedTransformError::CreateEncodedStreamsError)?;
+        let readable = js_property(&streams, "readable")
+            .map_err(EncodedTransformError::CreateEncodedStreamsError)?;
+        let writable = js_property(&streams, "writable")
+            .map_err(EncodedTransformError::CreateEncodedStreamsError)?;
+
+        let js_transform_handler = closure_2(move |frame: JsValue, controller: JsValue| {
+            // Only video frames have a type of "key", "delta" or "empty".
+            let kind = match Reflect::has(&frame, &JsValue::from_str("type")) {
+                Ok(true) => EncodedFrameKind::Video,
+                _ => EncodedFrameKind::Audio,
+            };
+            if let Ok(data) = js_property(&frame, "data") {
+                let data = Uint8Array::new(&data).to_vec();
+                let data = transform.apply(kind, data);
+                let data = Uint8Array::from(data.as_slice()).buffer();
+                let _: bool =
+                    Reflect::set(&frame, &JsValue::from_str("data"), &data).unwrap_or(false);
+            }
+            if let Err(err) = js_call_method(&controller, "enqueue", &Array::of1(&frame)) {
+                log::error!("encoded frame enqueue error: {:?}", err);
+            }
+        });
+
+        let transformer = Object::new();
+        let _: bool = Reflect::set(
+            &transformer,
+            &JsValue::from_str("transform"),
+            js_transform_handler.as_ref(),
+        )
+        .unwrap_or(false);
+        let transform_stream_class: Function = js_property(&global(), "TransformStream")
+            .and_then(JsCast::dyn_into)
+            .map_err(EncodedTransformError::NewTransformStreamError)?;
+        let transform_stream =
+            Reflect::construct(&transform_stream_class, &Array::of1(&transformer))
+                .map_err(EncodedTransformError::NewTransformStreamError)?;
+
+        let transformed = js_call_method(&readable, "pipeThrough", &Array::of1(&transform_stream))
+            .map_err(EncodedTransformError::PipeError)?;
+        let _: JsValue = js_call_method(&transformed, "pipeTo", &Array::of1(&writable))
+            .map_err(EncodedTransformError::PipeError)?;
+
+        Ok(Self {
+            _js_transform_handler: js_transform_handler,
+        })
+    }
+}
+
+fn js_property(object: &JsValue, name: &str) -> Result<JsValue, JsValue> {
+    use js_sys::Reflect;
+
+    Reflect::get(object, &JsValue::from_str(name))
+}
+
+fn js_call_method(object: &JsValue, name: &str, args: &js_sys::Array) -> Result<JsValue, JsValue> {
+    use js_sys::{Function, Reflect};
+    use wasm_bindgen::JsCast;
+
+    let method: Function = js_property(object, name)?.dyn_into()?;
+    Reflect::apply(&method, object, args)
+}
+
+#[derive(Error, Debug)]
+pub enum EncodedTransformError {
+    #[error("encoded transform is not supported by the browser")]
+    Unsupported,
+    #[error("createEncodedStreams error: {0:?}")]
+    CreateEncodedStreamsError(JsValue),
+    #[error("new TransformStream error: {0:?}")]
+    NewTransformStreamError(JsValue),
+    #[error("encoded streams pipe error: {0:?}")]
+    PipeError(JsValue),
+}
@@ -0,0 +1,123 @@
+use core::cell::{Cell, RefCell};
+use core::fmt::{Debug, Formatter, Result};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use async_std::sync::Arc;
+
+use crate::BoxAsyncFn2;
+
+/// Number of the last events replayed to late subscribers by default.
+pub const DEFAULT_EVENT_HISTORY_LEN: usize = 32;
+
+type Subscriber<T> = Rc<dyn Fn(&T)>;
+
+/// Delivers the events of a single object, e.g. a `Sender`, to multiple subscribers.
+///
+/// The last events are kept and replayed to every new subscriber,
+/// so views created after the negotiation started still see the early events.
+pub struct EventBus<T> {
+    history_len: usize,
+    history: RefCell<VecDeque<Rc<T>>>,
+    next_subscription_id: Cell<u32>,
+    subscribers: RefCell<Vec<(EventSubscriptionId, Subscriber<T>)>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EventSubscriptionId(u32);
+
+impl<T: 'static> EventBus<T> {
+    pub fn new(history_len: usize) -> Arc<Self> {
+        Arc::new(Self {
+            history_len,
+            history: RefCell::new(VecDeque::with_capacity(history_len)),
+            next_subscription_id: Cell::new(0),
+            subscribers: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Returns the handler that publishes the events of the object it is passed to.
+    pub fn handler<O: 'static>(self: &Arc<Self>) -> BoxAsyncFn2<O, T, ()> {
+        let self_weak = Arc::downgrade(self);
+        Box::new(move |_, ev| {
+            if let Some(self_arc) = self_weak.upgrade() {
+                self_arc.publish(ev);
+            }
+            Box::pin(async {})
+        })
+    }
+
+    pub fn publish(&self, ev: T) {
+        let ev = Rc::new(ev);
+        {
+            let mut history = self.history.borrow_mut();
+            if history.len() >= self.history_len {
+                let _: Option<_> = history.pop_front();
+            }
+            if self.history_len > 0 {
+                history.push_back(Rc::clone(&ev));
+            }
+        }
+
+        // Subscribers are cloned, so that they can subscribe and unsubscribe while notified.
+        let subscribers: Vec<_> = self
+            .subscribers
+            .borrow()
+            .iter()
+            .map(|(_, subscriber)| Rc::clone(subscriber))
+            .collect();
+        for subscriber in subscribers {
+            subscriber(&ev);
+        }
+    }
+
+    /// Calls the subscriber with the kept events and then with every published one.
+    pub fn subscribe_all(&self, subscriber: impl Fn(&T) + 'static) -> EventSubscriptionId {
+        let history: Vec<_> = self.history.borrow().iter().map(Rc::clone).collect();
+        for ev in history {
+            subscriber(&ev);
+        }
+
+        let id = EventSubscriptionId(self.next_subscription_id.get());
+        self.next_subscription_id.set(id.0.wrapping_add(1));
+        self.subscribers
+            .borrow_mut()
+            .push((id, Rc::new(subscriber)));
+        id
+    }
+
+    /// Calls the subscriber only with the events selected by the filter,
+    /// e.g. `|ev| match ev { SenderEvent::QualityChanged(quality) => Some(*quality), _ => None }`.
+    pub fn subscribe<U>(
+        &self,
+        filter: impl Fn(&T) -> Option<U> + 'static,
+        subscriber: impl Fn(U) + 'static,
+    ) -> EventSubscriptionId {
+        self.subscribe_all(move |ev| {
+            if let Some(value) = filter(ev) {
+                subscriber(value)
+            }
+        })
+    }
+
+    pub fn unsubscribe(&self, id: EventSubscriptionId) {
+        self.subscribers
+            .borrow_mut()
+            .retain(|(subscription_id, _)| *subscription_id != id);
+    }
+
+    /// Drops the kept events, e.g. when the object is restarted.
+    pub fn clear_history(&self) {
+        self.history.borrow_mut().clear();
+    }
+}
+
+impl<T> Debug for EventBus<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.debug_struct("EventBus")
+            .field("history_len", &self.history_len)
+            .field("history", &self.history.borrow().len())
+            .field("subscribers", &self.subscribers.borrow().len())
+            .finish()
+    }
+}
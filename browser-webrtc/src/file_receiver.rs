@@ -0,0 +1,270 @@
+use core::cell::{Cell, RefCell};
+
+use async_std::sync::Arc;
+use js_sys::Array;
+use signaling_protocol::{CloseReason, CodecError};
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{Blob, Event, MessageEvent, RtcDataChannel};
+
+use crate::data_message::{FileInfo, FileTransferMessage};
+use crate::live_objects::{LiveObject, LiveObjectKind};
+use crate::peer_connection::JsHandler;
+use crate::{BoxAsyncFn2, BoxAsyncFn2Wrapper, FileTransferProgress, Receiver};
+
+#[derive(Debug)]
+pub struct FileReceiverBuilder {
+    receiver: Arc<Receiver>,
+    js_channel: RtcDataChannel,
+}
+
+impl FileReceiverBuilder {
+    pub fn new(receiver: Arc<Receiver>, js_channel: RtcDataChannel) -> Self {
+        Self {
+            receiver,
+            js_channel,
+        }
+    }
+
+    pub fn build_with_handler(
+        self,
+        handler: BoxAsyncFn2<Arc<FileReceiver>, FileReceiverEvent, ()>,
+    ) -> Arc<FileReceiver> {
+        FileReceiver::new(self.receiver, self.js_channel, handler)
+    }
+}
+
+/// Reassembles a file sent by `FileSender` from its chunks.
+#[derive(Debug)]
+pub struct FileReceiver {
+    receiver: Arc<Receiver>,
+    handler: BoxAsyncFn2Wrapper<Arc<FileReceiver>, FileReceiverEvent, ()>,
+    _live_object: LiveObject,
+    js_channel: RtcDataChannel,
+    info: RefCell<Option<FileInfo>>,
+    started_at: Cell<f64>,
+    received: Cell<u64>,
+    /// Received chunks as `Uint8Array`s, joined into a `Blob` once the file is complete.
+    js_chunks: Array,
+    is_completed: Cell<bool>,
+    js_message_handler: JsHandler<MessageEvent>,
+    js_close_handler: JsHandler<Event>,
+}
+
+impl FileReceiver {
+    pub fn new(
+        receiver: Arc<Receiver>,
+        js_channel: RtcDataChannel,
+        handler: BoxAsyncFn2<Arc<Self>, FileReceiverEvent, ()>,
+    ) -> Arc<Self> {
+        log::trace!("browser_webrtc::FileReceiver::new");
+
+        use web_sys::RtcDataChannelType;
+
+        js_channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+
+        let file_receiver = Arc::new(Self {
+            receiver,
+            handler: BoxAsyncFn2Wrapper(handler),
+            _live_object: LiveObject::new(LiveObjectKind::FileReceiver),
+            js_channel,
+            info: RefCell::new(None),
+            started_at: Cell::new(0.0),
+            received: Cell::new(0),
+            js_chunks: Array::new(),
+            is_completed: Cell::new(false),
+            js_message_handler: RefCell::new(None),
+            js_close_handler: RefCell::new(None),
+        });
+
+        file_receiver.init_message_handler();
+        file_receiver.init_close_handler();
+
+        file_receiver
+    }
+
+    fn init_message_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_message_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |ev: MessageEvent| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_message_event(ev).await });
+                }
+            })
+        };
+        self.js_channel
+            .set_onmessage(Some(js_message_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.js_message_handler.replace(Some(js_message_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    fn init_close_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_close_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_close_event().await });
+                }
+            })
+        };
+        self.js_channel
+            .set_onclose(Some(js_close_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.js_close_handler.replace(Some(js_close_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    async fn handler(self: &Arc<Self>, ev: FileReceiverEvent) {
+        self.handler.0(Arc::clone(self), ev).await
+    }
+
+    async fn error(self: &Arc<Self>, err: FileReceiverError) {
+        self.handler(FileReceiverEvent::Error(err)).await
+    }
+
+    /// Channels closed while the receiver is open are closed by the remote peer.
+    async fn on_close_event(self: &Arc<Self>) {
+        if !self.is_completed.get() {
+            let size = self.info.borrow().as_ref().map(|info| info.size);
+            self.error(FileReceiverError::FileIsIncomplete {
+                received: self.received.get(),
+                size,
+            })
+            .await;
+        }
+        let reason = self
+            .receiver
+            .close_reason()
+            .unwrap_or(CloseReason::RemoteClosed);
+        self.handler(FileReceiverEvent::Closed(reason)).await;
+    }
+
+    async fn on_message_event(self: &Arc<Self>, ev: MessageEvent) {
+        match self.clone().handle_message_event(ev).await {
+            Ok(()) => {}
+            Err(err) => self.error(err).await,
+        }
+    }
+
+    async fn handle_message_event(
+        self: &Arc<Self>,
+        ev: MessageEvent,
+    ) -> Result<(), FileReceiverError> {
+        use js_sys::{ArrayBuffer, Uint8Array};
+        use wasm_bindgen::JsCast;
+
+        let array_buffer: ArrayBuffer = ev
+            .data()
+            .dyn_into()
+            .map_err(FileReceiverError::NonArrayData)?;
+        let data = Uint8Array::new(&array_buffer).to_vec();
+
+        match FileTransferMessage::decode(&data).map_err(FileReceiverError::DecodeError)? {
+            FileTransferMessage::Header(info) => {
+                if self.info.borrow().is_some() {
+                    return Err(FileReceiverError::UnexpectedHeader);
+                }
+                self.started_at.set(js_sys::Date::now());
+                let _: Option<_> = self.info.replace(Some(info.clone()));
+                self.handler(FileReceiverEvent::Started(info)).await;
+                // Empty files have no chunks.
+                self.complete_if_received().await
+            }
+            FileTransferMessage::Chunk(chunk) => {
+                let size = match self.info.borrow().as_ref() {
+                    Some(info) => info.size,
+                    None => return Err(FileReceiverError::ChunkBeforeHeader),
+                };
+                let received = self.received.get() + chunk.len() as u64;
+                if received > size {
+                    return Err(FileReceiverError::FileSizeExceeded { received, size });
+                }
+                self.received.set(received);
+                let _: u32 = self.js_chunks.push(&Uint8Array::from(&chunk[..]));
+                self.handler(FileReceiverEvent::Progress(FileTransferProgress::new(
+                    received,
+                    size,
+                    self.started_at.get(),
+                )))
+                .await;
+                self.complete_if_received().await
+            }
+        }
+    }
+
+    async fn complete_if_received(self: &Arc<Self>) -> Result<(), FileReceiverError> {
+        use web_sys::BlobPropertyBag;
+
+        // Messages are handled concurrently, so the earlier chunks may see the file complete.
+        let mime_type = match self.info.borrow().as_ref() {
+            Some(info) if info.size == self.received.get() && !self.is_completed.get() => {
+                info.mime_type.clone()
+            }
+            _ => return Ok(()),
+        };
+        let options = BlobPropertyBag::new();
+        options.set_type(&mime_type);
+        let file = Blob::new_with_u8_array_sequence_and_options(&self.js_chunks, &options)
+            .map_err(FileReceiverError::BlobError)?;
+        self.js_chunks.set_length(0);
+        self.is_completed.set(true);
+        self.handler(FileReceiverEvent::Completed(file)).await;
+        // The sender keeps its channel until it is closed by the receiver.
+        self.js_channel.close();
+        Ok(())
+    }
+
+    /// File info, `None` until the header is received.
+    pub fn info(&self) -> Option<FileInfo> {
+        self.info.borrow().clone()
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.is_completed.get()
+    }
+}
+
+impl Drop for FileReceiver {
+    fn drop(&mut self) {
+        log::trace!("browser_webrtc::FileReceiver::drop");
+
+        self.js_channel.set_onmessage(None);
+        self.js_channel.set_onclose(None);
+        self.js_channel.close();
+    }
+}
+
+#[derive(Debug)]
+pub enum FileReceiverEvent {
+    Started(FileInfo),
+    Progress(FileTransferProgress),
+    /// The file is received, the data channel is closed after that.
+    Completed(Blob),
+    Closed(CloseReason),
+    Error(FileReceiverError),
+}
+
+#[derive(Error, Debug)]
+pub enum FileReceiverError {
+    #[error("non-array data received: {0:?}")]
+    NonArrayData(JsValue),
+    #[error("file transfer message decode error: {0}")]
+    DecodeError(CodecError),
+    #[error("file header is received twice")]
+    UnexpectedHeader,
+    #[error("file chunk is received before the header")]
+    ChunkBeforeHeader,
+    #[error("received {received} bytes exceed the file size of {size} bytes")]
+    FileSizeExceeded { received: u64, size: u64 },
+    #[error("file is incomplete, {received} of {size:?} bytes received")]
+    FileIsIncomplete { received: u64, size: Option<u64> },
+    #[error("Blob error: {0:?}")]
+    BlobError(JsValue),
+}
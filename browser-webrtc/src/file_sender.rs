@@ -0,0 +1,333 @@
+use core::cell::{Cell, RefCell};
+
+use async_std::sync::Arc;
+use signaling_protocol::{CloseReason, CodecError};
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{Blob, Event, RtcDataChannel, RtcPeerConnection};
+
+use crate::data_message::{FileInfo, FileTransferMessage};
+use crate::live_objects::{LiveObject, LiveObjectKind};
+use crate::peer_connection::JsHandler;
+use crate::{BoxAsyncFn2, BoxAsyncFn2Wrapper, Sender};
+
+/// Label of the data channels created by `FileSender`, one channel per file.
+pub(crate) const FILE_DATA_CHANNEL_LABEL: &str = "browser-webrtc-file";
+
+/// Size of the file chunks, safely below the message size limits of all browsers.
+pub const FILE_CHUNK_SIZE: u32 = 16 * 1024;
+
+/// Chunks are not sent while more than this many bytes are queued in the data channel,
+/// the sending is resumed when the queue drops to `FILE_BUFFERED_AMOUNT_LOW`.
+pub const MAX_FILE_BUFFERED_AMOUNT: u32 = 1024 * 1024;
+pub const FILE_BUFFERED_AMOUNT_LOW: u32 = 256 * 1024;
+
+/// Progress of a file transfer, reported after every chunk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileTransferProgress {
+    pub transferred: u64,
+    pub size: u64,
+    /// Average throughput since the transfer started, in bytes per second.
+    pub throughput: f64,
+}
+
+impl FileTransferProgress {
+    pub(crate) fn new(transferred: u64, size: u64, started_at: f64) -> Self {
+        let elapsed = (js_sys::Date::now() - started_at) / 1000.0;
+        Self {
+            transferred,
+            size,
+            throughput: if elapsed > 0.0 {
+                transferred as f64 / elapsed
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Sends a file through its own data channel in chunks,
+/// the chunks are queued only while the data channel buffer has room for them.
+#[derive(Debug)]
+pub struct FileSender {
+    sender: Arc<Sender>,
+    handler: BoxAsyncFn2Wrapper<Arc<FileSender>, FileSenderEvent, ()>,
+    _live_object: LiveObject,
+    js_channel: RtcDataChannel,
+    file: Blob,
+    info: FileInfo,
+    is_completed: Cell<bool>,
+    buffered_amount_low_sender: async_std::channel::Sender<()>,
+    buffered_amount_low_receiver: async_std::channel::Receiver<()>,
+    js_open_handler: JsHandler<Event>,
+    js_buffered_amount_low_handler: JsHandler<Event>,
+    js_error_handler: JsHandler<Event>,
+    js_close_handler: JsHandler<Event>,
+}
+
+impl FileSender {
+    pub fn new(
+        sender: Arc<Sender>,
+        js_connection: RtcPeerConnection,
+        file: Blob,
+        name: String,
+        handler: BoxAsyncFn2<Arc<Self>, FileSenderEvent, ()>,
+    ) -> Arc<Self> {
+        log::trace!("browser_webrtc::FileSender::new");
+
+        let js_channel = js_connection.create_data_channel(FILE_DATA_CHANNEL_LABEL);
+        js_channel.set_buffered_amount_low_threshold(FILE_BUFFERED_AMOUNT_LOW);
+        let info = FileInfo {
+            name,
+            mime_type: file.type_(),
+            size: file.size() as u64,
+        };
+
+        // Wake-ups are coalesced, the buffered amount is checked again after each one.
+        let (buffered_amount_low_sender, buffered_amount_low_receiver) =
+            async_std::channel::bounded(1);
+        let file_sender = Arc::new(Self {
+            sender,
+            handler: BoxAsyncFn2Wrapper(handler),
+            _live_object: LiveObject::new(LiveObjectKind::FileSender),
+            js_channel,
+            file,
+            info,
+            is_completed: Cell::new(false),
+            buffered_amount_low_sender,
+            buffered_amount_low_receiver,
+            js_open_handler: RefCell::new(None),
+            js_buffered_amount_low_handler: RefCell::new(None),
+            js_error_handler: RefCell::new(None),
+            js_close_handler: RefCell::new(None),
+        });
+
+        file_sender.init_open_handler();
+        file_sender.init_buffered_amount_low_handler();
+        file_sender.init_error_handler();
+        file_sender.init_close_handler();
+
+        file_sender
+    }
+
+    fn init_open_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_open_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_open_event().await });
+                }
+            })
+        };
+        self.js_channel
+            .set_onopen(Some(js_open_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.js_open_handler.replace(Some(js_open_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    fn init_buffered_amount_low_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+
+        let js_buffered_amount_low_handler = {
+            let buffered_amount_low_sender = self.buffered_amount_low_sender.clone();
+            closure_1(move |_: Event| {
+                let _: Result<(), _> = buffered_amount_low_sender.try_send(());
+            })
+        };
+        self.js_channel.set_onbufferedamountlow(Some(
+            js_buffered_amount_low_handler.as_ref().unchecked_ref(),
+        ));
+        let prev_handler = self
+            .js_buffered_amount_low_handler
+            .replace(Some(js_buffered_amount_low_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    fn init_error_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_error_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |ev: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_error_event(ev).await });
+                }
+            })
+        };
+        self.js_channel
+            .set_onerror(Some(js_error_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.js_error_handler.replace(Some(js_error_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    fn init_close_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_close_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_close_event().await });
+                }
+            })
+        };
+        self.js_channel
+            .set_onclose(Some(js_close_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.js_close_handler.replace(Some(js_close_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    async fn handler(self: &Arc<Self>, ev: FileSenderEvent) {
+        self.handler.0(Arc::clone(self), ev).await
+    }
+
+    async fn error(self: &Arc<Self>, err: FileSenderError) {
+        self.handler(FileSenderEvent::Error(err)).await
+    }
+
+    async fn on_open_event(self: &Arc<Self>) {
+        self.handler(FileSenderEvent::Open).await;
+        match self.send_chunks().await {
+            Ok(()) => {}
+            Err(err) => self.error(err).await,
+        }
+    }
+
+    async fn send_chunks(self: &Arc<Self>) -> Result<(), FileSenderError> {
+        use js_sys::Uint8Array;
+        use wasm_bindgen_futures::JsFuture;
+
+        let started_at = js_sys::Date::now();
+        self.send_message(&FileTransferMessage::Header(self.info.clone()))?;
+
+        let mut offset = 0;
+        while offset < self.info.size {
+            self.wait_buffered_amount_low().await?;
+            let end = (offset + u64::from(FILE_CHUNK_SIZE)).min(self.info.size);
+            let chunk = self
+                .file
+                .slice_with_f64_and_f64(offset as f64, end as f64)
+                .map_err(FileSenderError::FileReadError)?;
+            let chunk = JsFuture::from(chunk.array_buffer())
+                .await
+                .map_err(FileSenderError::FileReadError)?;
+            self.send_message(&FileTransferMessage::Chunk(
+                Uint8Array::new(&chunk).to_vec(),
+            ))?;
+            offset = end;
+            self.handler(FileSenderEvent::Progress(FileTransferProgress::new(
+                offset,
+                self.info.size,
+                started_at,
+            )))
+            .await;
+        }
+
+        self.is_completed.set(true);
+        self.handler(FileSenderEvent::Completed).await;
+        Ok(())
+    }
+
+    /// Waits until the data channel buffer has room for the next chunk.
+    async fn wait_buffered_amount_low(&self) -> Result<(), FileSenderError> {
+        use web_sys::RtcDataChannelState;
+
+        loop {
+            if self.js_channel.ready_state() != RtcDataChannelState::Open {
+                return Err(FileSenderError::ChannelIsClosed);
+            }
+            if self.js_channel.buffered_amount() <= MAX_FILE_BUFFERED_AMOUNT {
+                return Ok(());
+            }
+            self.buffered_amount_low_receiver
+                .recv()
+                .await
+                .map_err(|_| FileSenderError::ChannelIsClosed)?;
+        }
+    }
+
+    fn send_message(&self, message: &FileTransferMessage) -> Result<(), FileSenderError> {
+        let data = message.encode().map_err(FileSenderError::EncodeError)?;
+        self.js_channel
+            .send_with_u8_array(&data)
+            .map_err(FileSenderError::RtcDataChannelSendError)
+    }
+
+    /// Channels closed while the sender is open are closed by the remote peer.
+    async fn on_close_event(self: &Arc<Self>) {
+        // Wakes up the chunks sending, so that it stops.
+        let _: Result<(), _> = self.buffered_amount_low_sender.try_send(());
+        let reason = self
+            .sender
+            .close_reason()
+            .unwrap_or(CloseReason::RemoteClosed);
+        self.handler(FileSenderEvent::Closed(reason)).await;
+    }
+
+    async fn on_error_event(self: &Arc<Self>, ev: Event) {
+        use js_sys::Reflect;
+        let error = Reflect::get(&ev, &JsValue::from_str("error")).unwrap_or_else(|_| ev.into());
+        self.error(FileSenderError::RtcDataChannelError(error))
+            .await;
+    }
+
+    pub fn info(&self) -> &FileInfo {
+        &self.info
+    }
+
+    /// Returns `true` once all chunks are queued in the data channel.
+    pub fn is_completed(&self) -> bool {
+        self.is_completed.get()
+    }
+
+    /// Stops the transfer, the receiver reports the file as incomplete.
+    pub fn cancel(&self) {
+        self.js_channel.close();
+    }
+}
+
+impl Drop for FileSender {
+    fn drop(&mut self) {
+        log::trace!("browser_webrtc::FileSender::drop");
+
+        self.js_channel.set_onopen(None);
+        self.js_channel.set_onbufferedamountlow(None);
+        self.js_channel.set_onerror(None);
+        self.js_channel.set_onclose(None);
+        self.js_channel.close();
+    }
+}
+
+#[derive(Debug)]
+pub enum FileSenderEvent {
+    Open,
+    Progress(FileTransferProgress),
+    /// All chunks are queued in the data channel, the sender should be kept
+    /// until the receiver reports the file as received.
+    Completed,
+    Closed(CloseReason),
+    Error(FileSenderError),
+}
+
+#[derive(Error, Debug)]
+pub enum FileSenderError {
+    #[error("RtcDataChannel error: {0:?}")]
+    RtcDataChannelError(JsValue),
+    #[error("RtcDataChannel send error: {0:?}")]
+    RtcDataChannelSendError(JsValue),
+    #[error("file read error: {0:?}")]
+    FileReadError(JsValue),
+    #[error("file chunk encode error: {0}")]
+    EncodeError(CodecError),
+    #[error("data channel is closed before the file is sent")]
+    ChannelIsClosed,
+}
@@ -0,0 +1,98 @@
+use core::future::Future;
+
+use async_std::sync::Arc;
+
+use crate::{BoxAsyncFn2, Receiver, ReceiverEvent, Sender, SenderEvent, Server, ServerEvent};
+
+/// Server events handler implemented by the application state,
+/// an alternative to the boxed closure handlers.
+///
+/// Closures returning futures implement it as well, so existing handlers keep working.
+#[allow(async_fn_in_trait)]
+pub trait ServerHandler: 'static {
+    async fn on_server_event(&self, server: Arc<Server>, ev: ServerEvent);
+
+    /// Converts the handler to the boxed closure accepted by `Server::new`.
+    fn boxed(self) -> BoxAsyncFn2<Arc<Server>, ServerEvent, ()>
+    where
+        Self: Sized,
+    {
+        use std::rc::Rc;
+
+        let handler = Rc::new(self);
+        Box::new(move |server, ev| {
+            let handler = Rc::clone(&handler);
+            Box::pin(async move { handler.on_server_event(server, ev).await })
+        })
+    }
+}
+
+/// Sender events handler, see `ServerHandler`.
+#[allow(async_fn_in_trait)]
+pub trait SenderHandler: 'static {
+    async fn on_sender_event(&self, sender: Arc<Sender>, ev: SenderEvent);
+
+    /// Converts the handler to the boxed closure accepted by `Server::open_channel`.
+    fn boxed(self) -> BoxAsyncFn2<Arc<Sender>, SenderEvent, ()>
+    where
+        Self: Sized,
+    {
+        use std::rc::Rc;
+
+        let handler = Rc::new(self);
+        Box::new(move |sender, ev| {
+            let handler = Rc::clone(&handler);
+            Box::pin(async move { handler.on_sender_event(sender, ev).await })
+        })
+    }
+}
+
+/// Receiver events handler, see `ServerHandler`.
+#[allow(async_fn_in_trait)]
+pub trait ReceiverHandler: 'static {
+    async fn on_receiver_event(&self, receiver: Arc<Receiver>, ev: ReceiverEvent);
+
+    /// Converts the handler to the boxed closure accepted by `Server::join_channel`.
+    fn boxed(self) -> BoxAsyncFn2<Arc<Receiver>, ReceiverEvent, ()>
+    where
+        Self: Sized,
+    {
+        use std::rc::Rc;
+
+        let handler = Rc::new(self);
+        Box::new(move |receiver, ev| {
+            let handler = Rc::clone(&handler);
+            Box::pin(async move { handler.on_receiver_event(receiver, ev).await })
+        })
+    }
+}
+
+impl<F, R> ServerHandler for F
+where
+    F: 'static + Fn(Arc<Server>, ServerEvent) -> R,
+    R: Future<Output = ()>,
+{
+    async fn on_server_event(&self, server: Arc<Server>, ev: ServerEvent) {
+        self(server, ev).await
+    }
+}
+
+impl<F, R> SenderHandler for F
+where
+    F: 'static + Fn(Arc<Sender>, SenderEvent) -> R,
+    R: Future<Output = ()>,
+{
+    async fn on_sender_event(&self, sender: Arc<Sender>, ev: SenderEvent) {
+        self(sender, ev).await
+    }
+}
+
+impl<F, R> ReceiverHandler for F
+where
+    F: 'static + Fn(Arc<Receiver>, ReceiverEvent) -> R,
+    R: Future<Output = ()>,
+{
+    async fn on_receiver_event(&self, receiver: Arc<Receiver>, ev: ReceiverEvent) {
+        self(receiver, ev).await
+    }
+}
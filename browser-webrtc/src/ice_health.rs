@@ -0,0 +1,247 @@
+use core::cell::RefCell;
+use core::time::Duration;
+use std::rc::Rc;
+
+use thiserror::Error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+use web_sys::{RtcConfiguration, RtcPeerConnectionIceEvent};
+
+use crate::peer_connection::PeerConnectionCore;
+
+/// Default time given to every ICE server to produce its candidates.
+pub const DEFAULT_ICE_SERVERS_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between the candidates gathering completion checks.
+const GATHERING_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IceServerKind {
+    Stun,
+    Turn,
+}
+
+/// Result of a single ICE server URL check.
+#[derive(Debug)]
+pub struct IceServerHealth {
+    pub url: String,
+    pub kind: IceServerKind,
+    /// Time to the first server reflexive candidate.
+    ///
+    /// Hosts with public addresses may get no such candidates from working STUN servers.
+    pub srflx_time: Option<Duration>,
+    /// Time to the first relay candidate.
+    pub relay_time: Option<Duration>,
+    /// The check could not be started, e.g. because of the malformed URL.
+    pub error: Option<IceServerCheckError>,
+}
+
+impl IceServerKind {
+    fn from_url(url: &str) -> Self {
+        if url.starts_with("turn:") || url.starts_with("turns:") {
+            Self::Turn
+        } else {
+            Self::Stun
+        }
+    }
+}
+
+impl IceServerHealth {
+    /// Time to the first candidate of the type the server is expected to produce.
+    pub fn response_time(&self) -> Option<Duration> {
+        match self.kind {
+            IceServerKind::Stun => self.srflx_time,
+            IceServerKind::Turn => self.relay_time,
+        }
+    }
+
+    pub fn is_reachable(&self) -> bool {
+        self.error.is_none() && self.response_time().is_some()
+    }
+}
+
+/// Checks every ICE server URL of the configuration with a separate throwaway peer connection.
+///
+/// The servers are checked concurrently, so the check takes at most `timeout`.
+pub async fn check_ice_servers(
+    rtc_configuration: &RtcConfiguration,
+    timeout: Duration,
+) -> Vec<IceServerHealth> {
+    use crate::sleep;
+
+    let mut probes = Vec::new();
+    for (ice_server, url) in ice_server_urls(rtc_configuration) {
+        probes.push(IceServerProbe::start(&ice_server, url).await);
+    }
+
+    let started_at = js_sys::Date::now();
+    while probes.iter().any(IceServerProbe::is_gathering)
+        && js_sys::Date::now() - started_at < timeout.as_secs_f64() * 1000.0
+    {
+        sleep(GATHERING_POLL_INTERVAL).await;
+    }
+
+    probes.into_iter().map(IceServerProbe::finish).collect()
+}
+
+/// Splits the configured ICE servers into single URL entries with the same credentials.
+fn ice_server_urls(rtc_configuration: &RtcConfiguration) -> Vec<(JsValue, String)> {
+    use js_sys::{Array, Object, Reflect};
+    use wasm_bindgen::JsCast;
+
+    let ice_servers = Reflect::get(rtc_configuration, &JsValue::from_str("iceServers"))
+        .unwrap_or(JsValue::UNDEFINED);
+    if !Array::is_array(&ice_servers) {
+        return Vec::new();
+    }
+
+    let mut entries = Vec::new();
+    for ice_server in Array::from(&ice_servers).iter() {
+        let urls =
+            Reflect::get(&ice_server, &JsValue::from_str("urls")).unwrap_or(JsValue::UNDEFINED);
+        let urls: Vec<_> = if Array::is_array(&urls) {
+            Array::from(&urls)
+                .iter()
+                .filter_map(|url| url.as_string())
+                .collect()
+        } else {
+            urls.as_string().into_iter().collect()
+        };
+        for url in urls {
+            let entry = Object::assign(&Object::new(), ice_server.unchecked_ref());
+            let _: bool =
+                Reflect::set(&entry, &JsValue::from_str("urls"), &JsValue::from_str(&url))
+                    .unwrap_or(false);
+            entries.push((entry.into(), url));
+        }
+    }
+    entries
+}
+
+type Candidates = Rc<RefCell<(Option<Duration>, Option<Duration>)>>;
+
+#[derive(Debug)]
+struct IceServerProbe {
+    url: String,
+    core: Result<PeerConnectionCore, IceServerCheckError>,
+    candidates: Candidates,
+    _js_ice_candidate_handler: Option<Closure<dyn FnMut(RtcPeerConnectionIceEvent)>>,
+}
+
+impl IceServerProbe {
+    async fn start(ice_server: &JsValue, url: String) -> Self {
+        use crate::closure_1;
+        use crate::ice_timeline::IceCandidateKind;
+        use js_sys::Array;
+        use wasm_bindgen::JsCast;
+
+        let candidates = Candidates::default();
+        let mut rtc_configuration = RtcConfiguration::new();
+        let _: &mut _ = rtc_configuration.ice_servers(&Array::of1(ice_server));
+        let core = match PeerConnectionCore::new(Some(rtc_configuration)) {
+            Ok(core) => core,
+            Err(err) => {
+                return Self {
+                    url,
+                    core: Err(IceServerCheckError::NewRtcPeerConnectionError(err)),
+                    candidates,
+                    _js_ice_candidate_handler: None,
+                }
+            }
+        };
+
+        let started_at = js_sys::Date::now();
+        let js_ice_candidate_handler = closure_1({
+            let candidates = Rc::clone(&candidates);
+            move |ev: RtcPeerConnectionIceEvent| {
+                let candidate = match ev.candidate() {
+                    Some(candidate) => candidate.candidate(),
+                    None => return,
+                };
+                let elapsed =
+                    Duration::from_secs_f64((js_sys::Date::now() - started_at).max(0.0) / 1000.0);
+                let mut candidates = candidates.borrow_mut();
+                let (srflx_time, relay_time) = &mut *candidates;
+                match IceCandidateKind::from_candidate(&candidate)
+                    .candidate_type
+                    .as_deref()
+                {
+                    Some("srflx") => *srflx_time = srflx_time.or(Some(elapsed)),
+                    Some("relay") => *relay_time = relay_time.or(Some(elapsed)),
+                    _ => {}
+                }
+            }
+        });
+        core.js_connection()
+            .set_onicecandidate(Some(js_ice_candidate_handler.as_ref().unchecked_ref()));
+
+        let core = match Self::gather(&core).await {
+            Ok(()) => Ok(core),
+            Err(err) => {
+                core.close();
+                Err(err)
+            }
+        };
+        Self {
+            url,
+            core,
+            candidates,
+            _js_ice_candidate_handler: Some(js_ice_candidate_handler),
+        }
+    }
+
+    /// Sets the local offer with a data section, which starts the candidates gathering.
+    async fn gather(core: &PeerConnectionCore) -> Result<(), IceServerCheckError> {
+        use webrtc_core::{RtcEngine, SdpType};
+
+        let _ = core
+            .js_connection()
+            .create_data_channel("ice-servers-check");
+        let engine = core.engine();
+        let offer = engine
+            .create_offer(false)
+            .await
+            .map_err(IceServerCheckError::CreateOfferError)?;
+        engine
+            .set_local_description(SdpType::Offer, &offer)
+            .await
+            .map_err(IceServerCheckError::SetLocalDescriptionError)
+    }
+
+    fn is_gathering(&self) -> bool {
+        use web_sys::RtcIceGatheringState;
+
+        match &self.core {
+            Ok(core) => core.ice_gathering_state() != RtcIceGatheringState::Complete,
+            Err(_) => false,
+        }
+    }
+
+    fn finish(self) -> IceServerHealth {
+        let (srflx_time, relay_time) = *self.candidates.borrow();
+        let error = match self.core {
+            Ok(core) => {
+                core.close();
+                None
+            }
+            Err(err) => Some(err),
+        };
+        IceServerHealth {
+            kind: IceServerKind::from_url(&self.url),
+            url: self.url,
+            srflx_time,
+            relay_time,
+            error,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IceServerCheckError {
+    #[error("new RtcPeerConnection error: {0:?}")]
+    NewRtcPeerConnectionError(JsValue),
+    #[error("create offer error: {0:?}")]
+    CreateOfferError(JsValue),
+    #[error("set local description error: {0:?}")]
+    SetLocalDescriptionError(JsValue),
+}
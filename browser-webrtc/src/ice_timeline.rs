@@ -0,0 +1,276 @@
+use core::cell::RefCell;
+use core::fmt;
+use core::time::Duration;
+use std::collections::HashMap;
+
+use signaling_protocol::IceCandidate;
+use wasm_bindgen::JsValue;
+use web_sys::RtcIceConnectionState;
+
+use crate::peer_connection::PeerConnectionEvent;
+use crate::stats::{stat_bool, stat_string};
+
+/// Maximum number of recorded entries, the following entries are dropped.
+pub const MAX_ICE_TIMELINE_LEN: usize = 512;
+
+/// ICE progress of a peer connection since it was created.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IceTimeline {
+    pub entries: Vec<IceTimelineEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IceTimelineEntry {
+    /// Time since the peer connection was created.
+    pub elapsed: Duration,
+    pub event: IceTimelineEvent,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IceTimelineEvent {
+    LocalCandidateAdded(IceCandidateKind),
+    LocalCandidatesGathered,
+    RemoteCandidateAdded(IceCandidateKind),
+    ConnectionStateChanged(RtcIceConnectionState),
+    /// The first candidate pair check is started.
+    ChecksStarted,
+    PairTried(IceCandidatePair),
+    PairSucceeded(IceCandidatePair),
+    PairFailed(IceCandidatePair),
+    PairNominated(IceCandidatePair),
+}
+
+/// Candidate type and transport, addresses are not recorded.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct IceCandidateKind {
+    /// `host`, `srflx`, `prflx` or `relay`.
+    pub candidate_type: Option<String>,
+    pub protocol: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct IceCandidatePair {
+    /// Stats identifier of the pair.
+    pub id: String,
+    pub local: IceCandidateKind,
+    pub remote: IceCandidateKind,
+}
+
+impl IceTimeline {
+    /// Time from the connection creation to the first nominated pair.
+    pub fn time_to_nomination(&self) -> Option<Duration> {
+        self.entries
+            .iter()
+            .find(|entry| matches!(entry.event, IceTimelineEvent::PairNominated(_)))
+            .map(|entry| entry.elapsed)
+    }
+}
+
+impl fmt::Display for IceTimeline {
+    /// Formats the timeline as a plain text dump, one entry per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "+{:>8.3}s {}", entry.elapsed.as_secs_f64(), entry.event)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for IceTimelineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LocalCandidateAdded(kind) => write!(f, "local candidate {}", kind),
+            Self::LocalCandidatesGathered => write!(f, "local candidates gathered"),
+            Self::RemoteCandidateAdded(kind) => write!(f, "remote candidate {}", kind),
+            Self::ConnectionStateChanged(state) => write!(f, "connection state {:?}", state),
+            Self::ChecksStarted => write!(f, "checks started"),
+            Self::PairTried(pair) => write!(f, "pair tried {}", pair),
+            Self::PairSucceeded(pair) => write!(f, "pair succeeded {}", pair),
+            Self::PairFailed(pair) => write!(f, "pair failed {}", pair),
+            Self::PairNominated(pair) => write!(f, "pair nominated {}", pair),
+        }
+    }
+}
+
+impl fmt::Display for IceCandidateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            self.candidate_type.as_deref().unwrap_or("?"),
+            self.protocol.as_deref().unwrap_or("?")
+        )
+    }
+}
+
+impl fmt::Display for IceCandidatePair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} -> {}", self.id, self.local, self.remote)
+    }
+}
+
+impl IceCandidateKind {
+    /// Parses the kind from the `candidate:` attribute value.
+    pub(crate) fn from_candidate(candidate: &str) -> Self {
+        let fields: Vec<_> = candidate.split_whitespace().collect();
+        let candidate_type = fields
+            .iter()
+            .position(|field| *field == "typ")
+            .and_then(|index| fields.get(index + 1))
+            .map(|candidate_type| (*candidate_type).to_owned());
+        Self {
+            candidate_type,
+            protocol: fields.get(2).map(|protocol| protocol.to_lowercase()),
+        }
+    }
+
+    fn from_stat(stat: &JsValue) -> Self {
+        Self {
+            candidate_type: stat_string(stat, "candidateType"),
+            protocol: stat_string(stat, "protocol"),
+        }
+    }
+}
+
+/// Candidate pair state as seen in the previous stats sample.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct PairProgress {
+    is_tried: bool,
+    is_finished: bool,
+    is_nominated: bool,
+}
+
+/// Records the ICE timeline from the connection events and the stats deltas.
+#[derive(Debug)]
+pub(crate) struct IceTimelineRecorder {
+    created_at: f64,
+    entries: RefCell<Vec<IceTimelineEntry>>,
+    pairs: RefCell<HashMap<String, PairProgress>>,
+}
+
+impl IceTimelineRecorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            created_at: now(),
+            entries: RefCell::new(Vec::new()),
+            pairs: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn timeline(&self) -> IceTimeline {
+        IceTimeline {
+            entries: self.entries.borrow().clone(),
+        }
+    }
+
+    fn push(&self, event: IceTimelineEvent) {
+        let mut entries = self.entries.borrow_mut();
+        if entries.len() < MAX_ICE_TIMELINE_LEN {
+            let elapsed = Duration::from_secs_f64((now() - self.created_at).max(0.0) / 1000.0);
+            entries.push(IceTimelineEntry { elapsed, event });
+        }
+    }
+
+    pub(crate) fn on_event(&self, ev: &PeerConnectionEvent) {
+        match ev {
+            PeerConnectionEvent::LocalIceCandidate(candidate) => {
+                self.push(IceTimelineEvent::LocalCandidateAdded(
+                    IceCandidateKind::from_candidate(&candidate.candidate),
+                ))
+            }
+            PeerConnectionEvent::LocalIceCandidatesGathered => {
+                self.push(IceTimelineEvent::LocalCandidatesGathered)
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn on_remote_ice_candidate(&self, candidate: &IceCandidate) {
+        self.push(IceTimelineEvent::RemoteCandidateAdded(
+            IceCandidateKind::from_candidate(&candidate.candidate),
+        ))
+    }
+
+    pub(crate) fn on_ice_connection_state(&self, state: RtcIceConnectionState) {
+        let is_changed = !matches!(
+            self.entries.borrow().iter().rev().find_map(|entry| match entry.event {
+                IceTimelineEvent::ConnectionStateChanged(state) => Some(state),
+                _ => None,
+            }),
+            Some(prev_state) if prev_state == state
+        );
+        if is_changed {
+            self.push(IceTimelineEvent::ConnectionStateChanged(state))
+        }
+    }
+
+    /// Records the candidate pairs progress since the previous stats sample.
+    pub(crate) fn update(&self, stats: &[JsValue]) {
+        let candidates: HashMap<_, _> = stats
+            .iter()
+            .filter(|stat| {
+                matches!(
+                    stat_string(stat, "type").as_deref(),
+                    Some("local-candidate" | "remote-candidate")
+                )
+            })
+            .filter_map(|stat| Some((stat_string(stat, "id")?, IceCandidateKind::from_stat(stat))))
+            .collect();
+        let candidate_kind = |stat: &JsValue, name| {
+            stat_string(stat, name)
+                .and_then(|id| candidates.get(&id).cloned())
+                .unwrap_or_default()
+        };
+
+        let mut pairs = self.pairs.borrow_mut();
+        for stat in stats {
+            if stat_string(stat, "type").as_deref() != Some("candidate-pair") {
+                continue;
+            }
+            let id = match stat_string(stat, "id") {
+                Some(id) => id,
+                None => continue,
+            };
+            let state = stat_string(stat, "state");
+            let is_nominated = stat_bool(stat, "nominated").unwrap_or(false);
+            let progress = PairProgress {
+                is_tried: matches!(
+                    state.as_deref(),
+                    Some("in-progress" | "succeeded" | "failed")
+                ),
+                is_finished: matches!(state.as_deref(), Some("succeeded" | "failed")),
+                is_nominated,
+            };
+
+            let is_first_pair_tried = !pairs.values().any(|prev| prev.is_tried);
+            let prev = pairs.insert(id.clone(), progress).unwrap_or_default();
+            if prev == progress {
+                continue;
+            }
+            let pair = IceCandidatePair {
+                id,
+                local: candidate_kind(stat, "localCandidateId"),
+                remote: candidate_kind(stat, "remoteCandidateId"),
+            };
+            if progress.is_tried && !prev.is_tried {
+                if is_first_pair_tried {
+                    self.push(IceTimelineEvent::ChecksStarted);
+                }
+                self.push(IceTimelineEvent::PairTried(pair.clone()));
+            }
+            if progress.is_finished && !prev.is_finished {
+                self.push(match state.as_deref() {
+                    Some("succeeded") => IceTimelineEvent::PairSucceeded(pair.clone()),
+                    _ => IceTimelineEvent::PairFailed(pair.clone()),
+                });
+            }
+            if progress.is_nominated && !prev.is_nominated {
+                self.push(IceTimelineEvent::PairNominated(pair));
+            }
+        }
+    }
+}
+
+fn now() -> f64 {
+    js_sys::Date::now()
+}
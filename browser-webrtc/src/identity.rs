@@ -0,0 +1,320 @@
+use core::cell::{Cell, RefCell};
+
+use js_sys::{Uint8Array, JSON};
+use signaling_protocol::{PublicKey, SessionDescription, SIGNED_PAYLOAD_PREFIX};
+use thiserror::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CryptoKey, CryptoKeyPair, RtcPeerConnection};
+
+use crate::webcrypto::{
+    bytes, call, from_hex, key_fingerprint, object, subtle, to_hex, usages, WebCryptoError,
+};
+use crate::{Persistence, PersistenceError};
+
+const NAMED_CURVE: &str = "P-256";
+const PRIVATE_KEY_STORAGE_KEY: &str = "identity.private_key";
+const PUBLIC_KEY_STORAGE_KEY: &str = "identity.public_key";
+/// Binds the signatures to their purpose, so they can not be reused in another protocol.
+const SIGNATURE_CONTEXT: &[u8] = b"browser-webrtc signed session description\n";
+const DTLS_FINGERPRINT_ATTRIBUTE: &str = "a=fingerprint:";
+
+/// Long-lived ECDSA P-256 key pair identifying the peer across sessions.
+#[derive(Clone, Debug)]
+pub struct IdentityKeyPair {
+    js_private_key: CryptoKey,
+    public_key: PublicKey,
+    fingerprint: String,
+}
+
+impl IdentityKeyPair {
+    /// Loads the identity key pair from the persistence,
+    /// a new key pair is generated and stored on the first use.
+    pub async fn load_or_generate(persistence: &Persistence) -> Result<Self, IdentityError> {
+        let private_key = persistence.get(PRIVATE_KEY_STORAGE_KEY)?;
+        let public_key = persistence.get(PUBLIC_KEY_STORAGE_KEY)?;
+        if let (Some(private_key), Some(public_key)) = (private_key, public_key) {
+            match Self::load(&private_key, &public_key).await {
+                Ok(key_pair) => return Ok(key_pair),
+                Err(err) => log::warn!("stored identity key is replaced: {}", err),
+            }
+        }
+
+        let (key_pair, private_key) = Self::generate().await?;
+        persistence.set(PRIVATE_KEY_STORAGE_KEY, &private_key)?;
+        persistence.set(PUBLIC_KEY_STORAGE_KEY, &to_hex(&key_pair.public_key.0))?;
+        Ok(key_pair)
+    }
+
+    /// Generates a new key pair, the private key is returned as a JWK to be persisted.
+    async fn generate() -> Result<(Self, String), IdentityError> {
+        let subtle = subtle()?;
+        let key_pair: CryptoKeyPair =
+            call(subtle.generate_key_with_object(&algorithm(), true, &usages(&["sign", "verify"])))
+                .await?
+                .unchecked_into();
+
+        let js_private_key = key_pair.get_private_key();
+        let private_key = call(subtle.export_key("jwk", &js_private_key)).await?;
+        let private_key = JSON::stringify(&private_key)
+            .map_err(WebCryptoError::JsError)?
+            .into();
+        let public_key = PublicKey(bytes(
+            &call(subtle.export_key("raw", &key_pair.get_public_key())).await?,
+        ));
+        let fingerprint = key_fingerprint(&public_key).await?;
+        Ok((
+            Self {
+                js_private_key,
+                public_key,
+                fingerprint,
+            },
+            private_key,
+        ))
+    }
+
+    async fn load(private_key: &str, public_key: &str) -> Result<Self, IdentityError> {
+        let public_key = PublicKey(from_hex(public_key).ok_or(IdentityError::InvalidStoredKey)?);
+        let private_key = JSON::parse(private_key).map_err(|_| IdentityError::InvalidStoredKey)?;
+        let js_private_key: CryptoKey = call(subtle()?.import_key_with_object(
+            "jwk",
+            private_key.unchecked_ref(),
+            &algorithm(),
+            false,
+            &usages(&["sign"]),
+        ))
+        .await
+        .map_err(|_| IdentityError::InvalidStoredKey)?
+        .unchecked_into();
+        let fingerprint = key_fingerprint(&public_key).await?;
+        Ok(Self {
+            js_private_key,
+            public_key,
+            fingerprint,
+        })
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// SHA-256 fingerprint of the public key for out-of-band verification.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    async fn sign_sdp(&self, sdp: SessionDescription) -> Result<SessionDescription, IdentityError> {
+        let signature = bytes(
+            &call(subtle()?.sign_with_object_and_u8_array(
+                &signature_params(),
+                &self.js_private_key,
+                &signed_data(&sdp.0),
+            ))
+            .await?,
+        );
+        Ok(SessionDescription(format!(
+            "{}{}:{}:{}",
+            SIGNED_PAYLOAD_PREFIX,
+            to_hex(&self.public_key.0),
+            to_hex(&signature),
+            sdp.0
+        )))
+    }
+}
+
+/// Remote peer whose identity signature and DTLS fingerprint binding were verified.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VerifiedPeer {
+    pub public_key: PublicKey,
+    /// SHA-256 fingerprint of the identity key for out-of-band verification.
+    pub fingerprint: String,
+    /// DTLS certificate fingerprints signed by the identity key.
+    pub dtls_fingerprints: Vec<String>,
+}
+
+/// Signs local and verifies remote session descriptions of a peer connection.
+#[derive(Debug, Default)]
+pub(crate) struct PeerVerification {
+    identity_key_pair: RefCell<Option<IdentityKeyPair>>,
+    is_verified_peer_required: Cell<bool>,
+    verified_peer: RefCell<Option<VerifiedPeer>>,
+}
+
+impl PeerVerification {
+    pub(crate) fn identity_key_pair(&self) -> Option<IdentityKeyPair> {
+        self.identity_key_pair.borrow().clone()
+    }
+
+    pub(crate) fn set_identity_key_pair(&self, key_pair: IdentityKeyPair) {
+        let _: Option<_> = self.identity_key_pair.replace(Some(key_pair));
+    }
+
+    pub(crate) fn set_verified_peer_required(&self, is_required: bool) {
+        self.is_verified_peer_required.set(is_required);
+    }
+
+    pub(crate) fn verified_peer(&self) -> Option<VerifiedPeer> {
+        self.verified_peer.borrow().clone()
+    }
+
+    /// Signs the local description if the identity key pair is set.
+    pub(crate) async fn sign(
+        &self,
+        sdp: SessionDescription,
+    ) -> Result<SessionDescription, IdentityError> {
+        let key_pair = self.identity_key_pair();
+        match key_pair {
+            Some(key_pair) => key_pair.sign_sdp(sdp).await,
+            None => Ok(sdp),
+        }
+    }
+
+    /// Verifies the signature of the remote description and strips it.
+    ///
+    /// The returned peer is not trusted until its DTLS fingerprints
+    /// are checked against the applied remote description with `bind`.
+    pub(crate) async fn verify(
+        &self,
+        sdp: SessionDescription,
+    ) -> Result<(SessionDescription, Option<VerifiedPeer>), VerificationError> {
+        let signed = match sdp.0.strip_prefix(SIGNED_PAYLOAD_PREFIX) {
+            Some(signed) => signed,
+            None if self.is_verified_peer_required.get() => {
+                return Err(VerificationError::UnsignedSessionDescription)
+            }
+            None => return Ok((sdp, None)),
+        };
+        let mut parts = signed.splitn(3, ':');
+        let (public_key, signature, sdp) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(public_key), Some(signature), Some(sdp)) => (public_key, signature, sdp),
+            _ => return Err(VerificationError::InvalidSignedPayload),
+        };
+        let public_key =
+            PublicKey(from_hex(public_key).ok_or(VerificationError::InvalidSignedPayload)?);
+        let signature = from_hex(signature).ok_or(VerificationError::InvalidSignedPayload)?;
+
+        if let Some(verified_peer) = self.verified_peer.borrow().as_ref() {
+            if verified_peer.public_key != public_key {
+                return Err(VerificationError::PeerIdentityChanged);
+            }
+        }
+
+        let subtle = subtle()?;
+        let js_public_key: CryptoKey = call(subtle.import_key_with_object(
+            "raw",
+            &Uint8Array::from(&public_key.0[..]),
+            &algorithm(),
+            true,
+            &usages(&["verify"]),
+        ))
+        .await
+        .map_err(|_| VerificationError::InvalidIdentityKey)?
+        .unchecked_into();
+        let is_valid = call(subtle.verify_with_object_and_u8_array_and_u8_array(
+            &signature_params(),
+            &js_public_key,
+            &signature,
+            &signed_data(sdp),
+        ))
+        .await?
+        .as_bool()
+        .unwrap_or(false);
+        if !is_valid {
+            return Err(VerificationError::SignatureMismatch);
+        }
+
+        let dtls_fingerprints = dtls_fingerprints(sdp);
+        if dtls_fingerprints.is_empty() {
+            return Err(VerificationError::MissingDtlsFingerprint);
+        }
+        let peer = VerifiedPeer {
+            fingerprint: key_fingerprint(&public_key).await?,
+            public_key,
+            dtls_fingerprints,
+        };
+        Ok((SessionDescription(sdp.to_owned()), Some(peer)))
+    }
+
+    /// Checks that the applied remote description uses the signed DTLS fingerprints,
+    /// returns the peer if it is verified for the first time.
+    pub(crate) fn bind(
+        &self,
+        js_connection: &RtcPeerConnection,
+        peer: VerifiedPeer,
+    ) -> Result<Option<VerifiedPeer>, VerificationError> {
+        let remote_fingerprints = js_connection
+            .remote_description()
+            .map(|description| dtls_fingerprints(&description.sdp()))
+            .unwrap_or_default();
+        if remote_fingerprints != peer.dtls_fingerprints {
+            return Err(VerificationError::DtlsFingerprintMismatch);
+        }
+
+        let previous = self.verified_peer.replace(Some(peer.clone()));
+        Ok(match previous {
+            Some(previous) if previous == peer => None,
+            _ => Some(peer),
+        })
+    }
+}
+
+fn algorithm() -> js_sys::Object {
+    object(&[
+        ("name", JsValue::from_str("ECDSA")),
+        ("namedCurve", JsValue::from_str(NAMED_CURVE)),
+    ])
+}
+
+fn signature_params() -> js_sys::Object {
+    object(&[
+        ("name", JsValue::from_str("ECDSA")),
+        ("hash", JsValue::from_str("SHA-256")),
+    ])
+}
+
+fn signed_data(sdp: &str) -> Vec<u8> {
+    let mut data = SIGNATURE_CONTEXT.to_vec();
+    data.extend_from_slice(sdp.as_bytes());
+    data
+}
+
+/// Returns the sorted unique DTLS fingerprints of the session description.
+fn dtls_fingerprints(sdp: &str) -> Vec<String> {
+    let mut fingerprints: Vec<String> = sdp
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(DTLS_FINGERPRINT_ATTRIBUTE))
+        .map(|fingerprint| fingerprint.trim().to_ascii_lowercase())
+        .collect();
+    fingerprints.sort();
+    fingerprints.dedup();
+    fingerprints
+}
+
+#[derive(Error, Debug)]
+pub enum IdentityError {
+    #[error(transparent)]
+    WebCryptoError(#[from] WebCryptoError),
+    #[error(transparent)]
+    PersistenceError(#[from] PersistenceError),
+    #[error("stored identity key is invalid")]
+    InvalidStoredKey,
+}
+
+#[derive(Error, Debug)]
+pub enum VerificationError {
+    #[error(transparent)]
+    WebCryptoError(#[from] WebCryptoError),
+    #[error("session description is not signed but a verified peer is required")]
+    UnsignedSessionDescription,
+    #[error("invalid signed session description")]
+    InvalidSignedPayload,
+    #[error("invalid peer identity key")]
+    InvalidIdentityKey,
+    #[error("session description signature mismatch")]
+    SignatureMismatch,
+    #[error("signed session description has no DTLS fingerprint")]
+    MissingDtlsFingerprint,
+    #[error("DTLS fingerprints of the remote description differ from the signed ones")]
+    DtlsFingerprintMismatch,
+    #[error("peer identity key differs from the already verified one")]
+    PeerIdentityChanged,
+}
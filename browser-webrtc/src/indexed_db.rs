@@ -0,0 +1,138 @@
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode};
+
+/// Version of the databases opened by `IndexedDbStore`, each has a single object store.
+const INDEXED_DB_VERSION: u32 = 1;
+
+/// Object store with auto-incremented keys in the browser IndexedDB,
+/// for records that outgrow the `Persistence` key-value storage.
+///
+/// Records are JavaScript values stored with the structured clone algorithm.
+#[derive(Clone, Debug)]
+pub struct IndexedDbStore {
+    js_database: IdbDatabase,
+    store_name: String,
+}
+
+impl IndexedDbStore {
+    /// Opens the database, the object store is created on the first open.
+    pub async fn open(database_name: &str, store_name: &str) -> Result<Self, IndexedDbError> {
+        use wasm_bindgen::JsCast;
+        use web_sys::{Event, IdbObjectStoreParameters};
+
+        use crate::closure::closure_1;
+
+        let js_factory = web_sys::window()
+            .ok_or(IndexedDbError::IndexedDbIsNotAvailable)?
+            .indexed_db()
+            .map_err(IndexedDbError::JsError)?
+            .ok_or(IndexedDbError::IndexedDbIsNotAvailable)?;
+        let js_open_request = js_factory
+            .open_with_u32(database_name, INDEXED_DB_VERSION)
+            .map_err(IndexedDbError::JsError)?;
+
+        let js_upgrade_handler = closure_1({
+            let js_open_request = js_open_request.clone();
+            let store_name = store_name.to_owned();
+            move |_: Event| {
+                let js_database: IdbDatabase = match js_open_request.result() {
+                    Ok(js_database) => js_database.unchecked_into(),
+                    Err(err) => return log::error!("IndexedDB upgrade error: {:?}", err),
+                };
+                if !js_database.object_store_names().contains(&store_name) {
+                    let parameters = IdbObjectStoreParameters::new();
+                    parameters.set_auto_increment(true);
+                    if let Err(err) = js_database
+                        .create_object_store_with_optional_parameters(&store_name, &parameters)
+                    {
+                        log::error!("IndexedDB object store create error: {:?}", err);
+                    }
+                }
+            }
+        });
+        js_open_request.set_onupgradeneeded(Some(js_upgrade_handler.as_ref().unchecked_ref()));
+        let result = request_result(&js_open_request).await;
+        js_open_request.set_onupgradeneeded(None);
+
+        Ok(Self {
+            js_database: result?.unchecked_into(),
+            store_name: store_name.to_owned(),
+        })
+    }
+
+    pub async fn add(&self, record: &JsValue) -> Result<(), IndexedDbError> {
+        let js_request = self
+            .js_object_store(IdbTransactionMode::Readwrite)?
+            .add(record)
+            .map_err(IndexedDbError::JsError)?;
+        let _: JsValue = request_result(&js_request).await?;
+        Ok(())
+    }
+
+    /// Returns all records in the key order, i.e. in the order they were added.
+    pub async fn get_all(&self) -> Result<Vec<JsValue>, IndexedDbError> {
+        use wasm_bindgen::JsCast;
+
+        let js_request = self
+            .js_object_store(IdbTransactionMode::Readonly)?
+            .get_all()
+            .map_err(IndexedDbError::JsError)?;
+        let records: js_sys::Array = request_result(&js_request).await?.unchecked_into();
+        Ok(records.iter().collect())
+    }
+
+    pub async fn clear(&self) -> Result<(), IndexedDbError> {
+        let js_request = self
+            .js_object_store(IdbTransactionMode::Readwrite)?
+            .clear()
+            .map_err(IndexedDbError::JsError)?;
+        let _: JsValue = request_result(&js_request).await?;
+        Ok(())
+    }
+
+    fn js_object_store(
+        &self,
+        mode: IdbTransactionMode,
+    ) -> Result<web_sys::IdbObjectStore, IndexedDbError> {
+        self.js_database
+            .transaction_with_str_and_mode(&self.store_name, mode)
+            .and_then(|js_transaction| js_transaction.object_store(&self.store_name))
+            .map_err(IndexedDbError::JsError)
+    }
+}
+
+/// Waits for the request to succeed or fail, its own callbacks resolve the promise.
+async fn request_result(js_request: &IdbRequest) -> Result<JsValue, IndexedDbError> {
+    use js_sys::Promise;
+    use wasm_bindgen_futures::JsFuture;
+
+    let promise = Promise::new(&mut |resolve, reject| {
+        js_request.set_onsuccess(Some(&resolve));
+        js_request.set_onerror(Some(&reject));
+    });
+    let result = JsFuture::from(promise).await;
+    js_request.set_onsuccess(None);
+    js_request.set_onerror(None);
+    match result {
+        Ok(_) => js_request.result().map_err(IndexedDbError::JsError),
+        Err(_) => Err(IndexedDbError::RequestError(
+            js_request
+                .error()
+                .ok()
+                .flatten()
+                .map(|error| error.message())
+                .unwrap_or_default(),
+        )),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IndexedDbError {
+    #[error("IndexedDB is not available")]
+    IndexedDbIsNotAvailable,
+    #[error("IndexedDB request failed: {0}")]
+    RequestError(String),
+    #[error("IndexedDB error: {0:?}")]
+    JsError(JsValue),
+}
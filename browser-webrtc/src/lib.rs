@@ -12,44 +12,169 @@
 
 /*
     Possible future improvements:
-    - Extract common functionality from Sender and Receiver, DataSender and DataReceiver
+    - Extract common functionality from DataSender and DataReceiver
       in order to reduce duplicate code.
-    - Use traits with async function instead of handlers.
 */
 
+mod audio_fallback;
+mod audio_level;
+mod audio_mixer;
+mod audio_visualizer;
 mod boxfn;
+mod capabilities;
+mod captions;
 mod closure;
+mod data_message;
 mod data_receiver;
 mod data_sender;
+#[cfg(feature = "encoded-transform")]
+mod encoded_transform;
+mod event_bus;
+mod file_receiver;
+mod file_sender;
+mod handler;
+mod ice_health;
+mod ice_timeline;
+mod identity;
+mod indexed_db;
+mod live_objects;
 mod local_media;
 mod media_receiver;
 mod media_sender;
 mod media_view;
+mod network;
+mod notifier;
+mod opus;
+mod peer_connection;
+mod peer_state;
+mod permissions;
+mod persistence;
+mod protocol_violation;
+mod quality;
 mod receiver;
+mod reconnect;
+mod recording_uploader;
+mod registry;
+mod request_policy;
 mod rtc_configuration;
+mod rtc_engine;
+mod rtp_parameters;
+mod sctp;
+mod sealed;
 mod sender;
 mod server;
+mod server_snapshot;
+mod session_metrics;
+mod session_policy;
+mod snapshot;
+mod stats;
+mod telemetry;
+mod timer;
+mod visibility;
+mod warm_connection;
+mod webcrypto;
 mod websocket;
 
+pub use audio_fallback::{AudioFallbackPolicy, AudioFallbackReason};
+pub use audio_level::{AudioLevelMeter, AudioLevelMeterError};
+pub use audio_mixer::{AudioMixer, AudioMixerError};
+pub use audio_visualizer::{
+    AudioVisualizer, AudioVisualizerDrawError, AudioVisualizerStyle, NewAudioVisualizerError,
+};
 pub use boxfn::{BoxAsyncFn2, BoxAsyncFn2Wrapper};
-pub use closure::{closure_0, closure_1};
+pub use capabilities::{capabilities, Capabilities};
+pub use captions::{CaptionCue, CaptionSendError, SpeechCaptions, SpeechCaptionsError};
+pub use closure::{closure_0, closure_1, closure_2};
+pub use data_message::{DataChannelMessage, FileInfo};
 pub use data_receiver::{DataReceiver, DataReceiverBuilder, DataReceiverError, DataReceiverEvent};
 pub use data_sender::{DataSender, DataSenderError, DataSenderEvent, DataSenderSendError};
-pub use local_media::LocalMedia;
+#[cfg(feature = "encoded-transform")]
+pub use encoded_transform::{
+    is_encoded_transform_supported, EncodedFrameKind, EncodedFrameTransform, EncodedTransformError,
+};
+pub use event_bus::{EventBus, EventSubscriptionId, DEFAULT_EVENT_HISTORY_LEN};
+pub use file_receiver::{FileReceiver, FileReceiverBuilder, FileReceiverError, FileReceiverEvent};
+pub use file_sender::{
+    FileSender, FileSenderError, FileSenderEvent, FileTransferProgress, FILE_BUFFERED_AMOUNT_LOW,
+    FILE_CHUNK_SIZE, MAX_FILE_BUFFERED_AMOUNT,
+};
+pub use handler::{ReceiverHandler, SenderHandler, ServerHandler};
+pub use ice_health::{
+    check_ice_servers, IceServerCheckError, IceServerHealth, IceServerKind,
+    DEFAULT_ICE_SERVERS_CHECK_TIMEOUT,
+};
+pub use ice_timeline::{
+    IceCandidateKind, IceCandidatePair, IceTimeline, IceTimelineEntry, IceTimelineEvent,
+    MAX_ICE_TIMELINE_LEN,
+};
+pub use identity::{IdentityError, IdentityKeyPair, VerificationError, VerifiedPeer};
+pub use indexed_db::{IndexedDbError, IndexedDbStore};
+#[cfg(feature = "live-objects")]
+pub use live_objects::{
+    live_object_counts, log_live_objects_on_unload, LiveObjectCounts, LiveObjectKind,
+};
+pub use local_media::{
+    CaptureDowngrade, CaptureFallback, DisplayAudioSource, DisplayMediaAudio, LocalMedia,
+    LocalMediaError, LocalMediaEvent, LocalMediaWatcher, MediaDevice, TrackConstraints,
+    VideoSettings, DEFAULT_CAPTURE_FALLBACKS,
+};
 pub use media_receiver::{
     MediaReceiver, MediaReceiverBuilder, MediaReceiverError, MediaReceiverEvent,
 };
-pub use media_sender::MediaSender;
-pub use media_view::{MediaView, MediaViewAudio, NewMediaViewError};
+pub use media_sender::{MediaSender, MediaSenderError, MediaSenderEvent};
+pub use media_view::{
+    MediaView, MediaViewAudio, MediaViewCaptionError, MediaViewError, MediaViewEvent,
+    MediaViewHudError, MediaViewPlayError, NewMediaViewError,
+};
+pub use network::NetworkChange;
+pub use notifier::{Notifier, NotifierError, NotifierEvent};
+pub use opus::OpusParameters;
+pub use peer_state::{PeerState, CONTROL_DATA_CHANNEL_LABEL};
+pub use permissions::{
+    MediaPermissionKind, MediaPermissions, MediaPermissionsError, MediaPermissionsEvent,
+};
+pub use persistence::{Persistence, PersistenceError};
+pub use protocol_violation::{
+    ProtocolViolation, ProtocolViolationDiagnostics, ProtocolViolationPolicy,
+};
+pub use quality::{ConnectionQuality, MAX_QUALITY_SCORE};
 pub use receiver::{NewReceiverError, Receiver, ReceiverEvent, ReceiverSendError};
+pub use reconnect::ReconnectPolicy;
+pub use recording_uploader::{
+    RecordingOptions, RecordingUploader, RecordingUploaderError, DEFAULT_RECORDING_TIME_SLICE,
+};
+pub use registry::{GlobalReceiverId, GlobalSenderId, PeerRegistry, PeerRegistryError, ServerId};
+pub use request_policy::{IncomingRequestDecision, IncomingRequestPolicy};
 pub use rtc_configuration::{default_rtc_configuration, RtcConfigurationExt};
-pub use sender::{NewSenderError, Sender, SenderEvent, SenderSendError};
+pub use rtc_engine::BrowserRtcEngine;
+pub use rtp_parameters::{
+    DegradationPreference, KeyFrameRequestMethod, RtpParameterSupport, RtpParametersError,
+    RtpPriority, RtpSendPriority, RtpSendPrioritySupport, VideoQuality,
+};
+pub use sctp::{SctpLimits, DEFAULT_MAX_MESSAGE_SIZE};
+pub use sealed::{SealingError, SealingKeyPair};
+pub use sender::{NegotiationTimeout, NewSenderError, Sender, SenderEvent, SenderSendError};
 pub use server::{
-    NewServerError, Server, ServerEvent, ServerJoinChannelError, ServerOpenChannelError,
+    AcceptChannelRequestError, ChannelAccess, ChannelReservation, Delivery, DeliveryError,
+    JoinError, NewServerError, OpenError, ReserveChannelError, Server, ServerEvent,
+    ServerJoinChannelError, ServerOpenChannelError, WebSocketState,
 };
+pub use server_snapshot::{PeerCounts, ReceiverSnapshot, SenderSnapshot, ServerSnapshot};
+pub use session_metrics::{SessionUsage, TrafficBytes};
+pub use session_policy::{SessionEndReason, SessionPolicy};
+pub use snapshot::{MediaSnapshot, MediaSnapshotError, MediaSnapshotFormat};
+pub use stats::{InboundVideoStats, KeyFrameRequestStats, StatsError};
+pub use telemetry::{ServerTelemetry, Telemetry, TelemetrySource, TELEMETRY_BATCH_DELAY};
+pub use timer::sleep;
+pub use visibility::{BackgroundPolicy, PageVisibility, VideoThrottle};
+pub use warm_connection::{WarmConnection, WarmConnectionError};
+pub use web_sys::{MediaDeviceKind, NotificationPermission};
+pub use webcrypto::{key_fingerprint, WebCryptoError};
 pub use websocket::{
-    parse_websocket_server_message, send_websocket_client_message, WebSocketClientMessageSendError,
+    parse_websocket_server_message, send_websocket_client_message,
+    serialize_websocket_client_message, WebSocketClientMessageSendError,
     WebSocketServerMessageParseError,
 };
 
 pub use signaling_protocol;
+pub use webrtc_core;
@@ -0,0 +1,119 @@
+#[cfg(feature = "live-objects")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Kind of the objects counted by the live objects registry.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LiveObjectKind {
+    Sender,
+    Receiver,
+    DataSender,
+    DataReceiver,
+    MediaReceiver,
+    FileSender,
+    FileReceiver,
+    /// JS closures created with `closure_0`, `closure_1` or `closure_2`.
+    JsClosure,
+}
+
+/// Numbers of live objects, e.g. a number that keeps growing after peers are closed
+/// points to a reference cycle between the objects and their JS handlers.
+#[cfg(feature = "live-objects")]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LiveObjectCounts {
+    pub senders: usize,
+    pub receivers: usize,
+    pub data_senders: usize,
+    pub data_receivers: usize,
+    pub media_receivers: usize,
+    pub file_senders: usize,
+    pub file_receivers: usize,
+    pub js_closures: usize,
+}
+
+#[cfg(feature = "live-objects")]
+static LIVE_OBJECTS: [AtomicUsize; 8] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+#[cfg(feature = "live-objects")]
+impl LiveObjectKind {
+    fn counter(self) -> &'static AtomicUsize {
+        &LIVE_OBJECTS[self as usize]
+    }
+}
+
+/// Returns the numbers of the currently live objects.
+#[cfg(feature = "live-objects")]
+pub fn live_object_counts() -> LiveObjectCounts {
+    let count = |kind: LiveObjectKind| kind.counter().load(Ordering::Relaxed);
+    LiveObjectCounts {
+        senders: count(LiveObjectKind::Sender),
+        receivers: count(LiveObjectKind::Receiver),
+        data_senders: count(LiveObjectKind::DataSender),
+        data_receivers: count(LiveObjectKind::DataReceiver),
+        media_receivers: count(LiveObjectKind::MediaReceiver),
+        file_senders: count(LiveObjectKind::FileSender),
+        file_receivers: count(LiveObjectKind::FileReceiver),
+        js_closures: count(LiveObjectKind::JsClosure),
+    }
+}
+
+/// Logs the live object counts when the page is unloaded.
+///
+/// The listener is never removed, so it should be installed once per page.
+#[cfg(feature = "live-objects")]
+pub fn log_live_objects_on_unload() {
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+    use web_sys::{window, Event};
+
+    let window = match window() {
+        Some(window) => window,
+        None => return,
+    };
+    // Not created with `closure_1`, so that the listener is not counted itself.
+    let handler: Box<dyn FnMut(Event)> = Box::new(|_| {
+        log::info!("live objects on page unload: {:?}", live_object_counts());
+    });
+    let js_unload_handler = Closure::wrap(handler);
+    match window
+        .add_event_listener_with_callback("pagehide", js_unload_handler.as_ref().unchecked_ref())
+    {
+        Ok(()) => js_unload_handler.forget(),
+        Err(err) => log::warn!("page unload listener install error: {:?}", err),
+    }
+}
+
+/// Counts the owning object while it is alive, does nothing without the `live-objects` feature.
+#[derive(Debug)]
+pub(crate) struct LiveObject {
+    #[cfg(feature = "live-objects")]
+    kind: LiveObjectKind,
+}
+
+impl LiveObject {
+    #[cfg(feature = "live-objects")]
+    pub(crate) fn new(kind: LiveObjectKind) -> Self {
+        let _: usize = kind.counter().fetch_add(1, Ordering::Relaxed);
+        Self { kind }
+    }
+
+    #[cfg(not(feature = "live-objects"))]
+    pub(crate) fn new(_: LiveObjectKind) -> Self {
+        Self {}
+    }
+}
+
+#[cfg(feature = "live-objects")]
+impl Drop for LiveObject {
+    fn drop(&mut self) {
+        let _: usize = self.kind.counter().fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -1,57 +1,622 @@
-use web_sys::{MediaStream, MediaStreamConstraints};
+use core::cell::RefCell;
+use std::rc::Rc;
+
+use async_std::sync::Arc;
+use thiserror::Error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+use web_sys::{Event, MediaDeviceKind, MediaStream, MediaStreamConstraints, MediaStreamTrack};
+
+use crate::{AudioMixer, AudioMixerError, BoxAsyncFn2, BoxAsyncFn2Wrapper};
+
+/// Capture fallback ladder used by default, see `LocalMedia::with_fallbacks`.
+pub const DEFAULT_CAPTURE_FALLBACKS: &[CaptureFallback] = &[
+    CaptureFallback::DropExactConstraints,
+    CaptureFallback::LowerResolution {
+        width: 640,
+        height: 360,
+    },
+    CaptureFallback::AudioOnly,
+];
 
 #[derive(Clone, Debug)]
 pub struct LocalMedia {
     js_media_stream: MediaStream,
+    js_mixed_tracks: Vec<MediaStreamTrack>,
+    audio_mixer: Option<Rc<AudioMixer>>,
+    is_display_media: bool,
+}
+
+/// Requested audio capture of a shared screen, window or browser tab.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DisplayMediaAudio {
+    Disable,
+    /// Audio of a shared browser tab.
+    Tab,
+    /// Audio of a shared browser tab or the whole system audio of a shared screen.
+    System,
+}
+
+/// Audio source actually granted by the user and the browser.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DisplayAudioSource {
+    Tab,
+    System,
+    Window,
+    Unknown,
+}
+
+/// Events of the local media tracks, see `LocalMedia::watch`.
+#[derive(Debug)]
+pub enum LocalMediaEvent {
+    /// The track is ended by the browser, e.g. its device is disconnected,
+    /// the tracks stopped by the application are not reported.
+    TrackEnded(MediaStreamTrack),
+    /// The user stopped sharing the screen, window or browser tab with the browser controls.
+    DisplayMediaStopped,
+}
+
+/// Listens to the `ended` events of the local media tracks, the listeners are removed on drop.
+#[derive(Debug)]
+pub struct LocalMediaWatcher {
+    handler: BoxAsyncFn2Wrapper<Arc<LocalMediaWatcher>, LocalMediaEvent, ()>,
+    is_display_media: bool,
+    ended_listeners: RefCell<Vec<TrackEndedListener>>,
+}
+
+#[derive(Debug)]
+struct TrackEndedListener {
+    js_track: MediaStreamTrack,
+    js_ended_handler: Closure<dyn FnMut(Event)>,
+}
+
+/// Media input or output device, labels are empty until a media permission is granted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MediaDevice {
+    pub device_id: String,
+    pub group_id: String,
+    pub kind: MediaDeviceKind,
+    pub label: String,
+}
+
+/// Capture constraints of a single track kind, unset values are chosen by the browser.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TrackConstraints {
+    /// Exact device id, see `LocalMedia::devices`.
+    pub device_id: Option<String>,
+    /// Ideal video frame width, ignored for audio.
+    pub width: Option<u32>,
+    /// Ideal video frame height, ignored for audio.
+    pub height: Option<u32>,
+    /// Ideal video frame rate, ignored for audio.
+    pub frame_rate: Option<u32>,
+}
+
+impl TrackConstraints {
+    fn to_js_value(&self) -> JsValue {
+        use js_sys::{Object, Reflect};
+
+        let constraints = Object::new();
+        let set = |name: &str, kind: &str, value: JsValue| {
+            let value_constraint = Object::new();
+            let _: bool =
+                Reflect::set(&value_constraint, &JsValue::from_str(kind), &value).unwrap_or(false);
+            let _: bool = Reflect::set(&constraints, &JsValue::from_str(name), &value_constraint)
+                .unwrap_or(false);
+        };
+        if let Some(device_id) = &self.device_id {
+            set("deviceId", "exact", JsValue::from_str(device_id));
+        }
+        if let Some(width) = self.width {
+            set("width", "ideal", JsValue::from(width));
+        }
+        if let Some(height) = self.height {
+            set("height", "ideal", JsValue::from(height));
+        }
+        if let Some(frame_rate) = self.frame_rate {
+            set("frameRate", "ideal", JsValue::from(frame_rate));
+        }
+        constraints.into()
+    }
+}
+
+/// Relaxation of the capture constraints tried after the previous capture attempt failed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CaptureFallback {
+    /// Drops the exact device ids, so that any device of the kind may be captured.
+    DropExactConstraints,
+    /// Requests the lower video resolution and drops the requested frame rate.
+    LowerResolution { width: u32, height: u32 },
+    /// Drops the video, if the audio is requested too.
+    AudioOnly,
+}
+
+/// Capture constraints relaxation made after the failed capture attempt.
+#[derive(Debug)]
+pub struct CaptureDowngrade {
+    pub fallback: CaptureFallback,
+    /// Error of the previous capture attempt.
+    pub reason: LocalMediaError,
+}
+
+impl CaptureFallback {
+    /// Relaxes the constraints, returns `false` if the fallback changes nothing.
+    fn apply(
+        self,
+        video: &mut Option<TrackConstraints>,
+        audio: &mut Option<TrackConstraints>,
+    ) -> bool {
+        match self {
+            Self::DropExactConstraints => {
+                let mut is_changed = false;
+                for constraints in video.iter_mut().chain(audio.iter_mut()) {
+                    is_changed |= constraints.device_id.take().is_some();
+                }
+                is_changed
+            }
+            Self::LowerResolution { width, height } => match video {
+                Some(video) => {
+                    let is_lower = video.width.is_none_or(|prev| width < prev)
+                        || video.height.is_none_or(|prev| height < prev);
+                    if is_lower {
+                        video.width = Some(width);
+                        video.height = Some(height);
+                        video.frame_rate = None;
+                    }
+                    is_lower
+                }
+                None => false,
+            },
+            Self::AudioOnly => {
+                if video.is_some() && audio.is_some() {
+                    *video = None;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Settings of a captured video track actually applied by the browser.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VideoSettings {
+    pub device_id: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
 }
 
 impl LocalMedia {
-    pub async fn new(constraints: MediaStreamConstraints) -> Self {
+    /// Lists the available media devices.
+    pub async fn devices() -> Result<Vec<MediaDevice>, LocalMediaError> {
+        use js_sys::Array;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::{window, MediaDeviceInfo};
+
+        let window = window().ok_or(LocalMediaError::WindowIsUndefined)?;
+        let media_devices = window
+            .navigator()
+            .media_devices()
+            .map_err(LocalMediaError::MediaDevicesIsUndefined)?;
+        let devices_promise = media_devices
+            .enumerate_devices()
+            .map_err(LocalMediaError::EnumerateDevicesError)?;
+        let devices: Array = JsFuture::from(devices_promise)
+            .await
+            .map_err(LocalMediaError::EnumerateDevicesError)?
+            .dyn_into()
+            .map_err(LocalMediaError::EnumerateDevicesError)?;
+
+        Ok(devices
+            .iter()
+            .filter_map(|device| device.dyn_into::<MediaDeviceInfo>().ok())
+            .map(|device| MediaDevice {
+                device_id: device.device_id(),
+                group_id: device.group_id(),
+                kind: device.kind(),
+                label: device.label(),
+            })
+            .collect())
+    }
+
+    /// Captures the tracks of the kinds with constraints, at least one kind must be requested.
+    pub async fn with_constraints(
+        video: Option<&TrackConstraints>,
+        audio: Option<&TrackConstraints>,
+    ) -> Result<Self, LocalMediaError> {
+        let constraints = MediaStreamConstraints::new();
+        if let Some(video) = video {
+            constraints.set_video(&video.to_js_value());
+        }
+        if let Some(audio) = audio {
+            constraints.set_audio(&audio.to_js_value());
+        }
+        Self::new(constraints).await
+    }
+
+    /// Captures the tracks like `with_constraints`, but if the capture fails
+    /// because of the unsatisfiable constraints or a busy device,
+    /// the constraints are relaxed with the fallbacks in order and the capture is retried.
+    ///
+    /// Fallbacks that change nothing are skipped,
+    /// every applied one is reported to `on_downgrade` before the retry.
+    pub async fn with_fallbacks(
+        video: Option<&TrackConstraints>,
+        audio: Option<&TrackConstraints>,
+        fallbacks: &[CaptureFallback],
+        mut on_downgrade: impl FnMut(CaptureDowngrade),
+    ) -> Result<Self, LocalMediaError> {
+        let mut video = video.cloned();
+        let mut audio = audio.cloned();
+        let mut fallbacks = fallbacks.iter();
+        loop {
+            let err = match Self::with_constraints(video.as_ref(), audio.as_ref()).await {
+                Ok(media) => return Ok(media),
+                Err(err) if err.is_recoverable_by_fallback() => err,
+                Err(err) => return Err(err),
+            };
+            let fallback = loop {
+                match fallbacks.next() {
+                    Some(fallback) if fallback.apply(&mut video, &mut audio) => break *fallback,
+                    Some(_) => {}
+                    None => return Err(err),
+                }
+            };
+            on_downgrade(CaptureDowngrade {
+                fallback,
+                reason: err,
+            });
+        }
+    }
+
+    pub async fn new(constraints: MediaStreamConstraints) -> Result<Self, LocalMediaError> {
         use wasm_bindgen::JsCast;
         use wasm_bindgen_futures::JsFuture;
         use web_sys::window;
 
-        let window = window().unwrap();
+        let window = window().ok_or(LocalMediaError::WindowIsUndefined)?;
         let navigator = window.navigator();
-        let media_devices = navigator.media_devices().unwrap();
+        let media_devices = navigator
+            .media_devices()
+            .map_err(LocalMediaError::MediaDevicesIsUndefined)?;
         let media_stream_promise = media_devices
             .get_user_media_with_constraints(&constraints)
-            .unwrap();
+            .map_err(LocalMediaError::from_get_user_media_error)?;
         let js_media_stream: MediaStream = JsFuture::from(media_stream_promise)
             .await
-            .unwrap()
+            .map_err(LocalMediaError::from_get_user_media_error)?
             .dyn_into()
-            .unwrap();
+            .map_err(LocalMediaError::InvalidMediaStream)?;
 
-        Self { js_media_stream }
+        Ok(Self::from_media_stream(js_media_stream))
     }
 
-    pub async fn with_video() -> Self {
-        use wasm_bindgen::JsValue;
+    fn from_media_stream(js_media_stream: MediaStream) -> Self {
+        Self {
+            js_media_stream,
+            js_mixed_tracks: Vec::new(),
+            audio_mixer: None,
+            is_display_media: false,
+        }
+    }
 
+    pub async fn with_video() -> Result<Self, LocalMediaError> {
         let mut constraints = MediaStreamConstraints::new();
         let _: &mut _ = constraints.video(&JsValue::TRUE);
         Self::new(constraints).await
     }
 
-    pub async fn with_audio() -> Self {
-        use wasm_bindgen::JsValue;
-
+    pub async fn with_audio() -> Result<Self, LocalMediaError> {
         let mut constraints = MediaStreamConstraints::new();
         let _: &mut _ = constraints.audio(&JsValue::TRUE);
         Self::new(constraints).await
     }
 
-    pub async fn with_video_and_audio() -> Self {
-        use wasm_bindgen::JsValue;
-
+    pub async fn with_video_and_audio() -> Result<Self, LocalMediaError> {
         let mut constraints = MediaStreamConstraints::new();
         let _: &mut _ = constraints.video(&JsValue::TRUE);
         let _: &mut _ = constraints.audio(&JsValue::TRUE);
         Self::new(constraints).await
     }
 
+    /// Captures a screen, window or browser tab selected by the user.
+    ///
+    /// Browsers may grant no audio or audio of another source than requested,
+    /// use `display_audio_source` to get the granted one.
+    pub async fn with_display_media(audio: DisplayMediaAudio) -> Result<Self, LocalMediaError> {
+        use js_sys::{Object, Reflect};
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::{window, DisplayMediaStreamConstraints};
+
+        let mut constraints = DisplayMediaStreamConstraints::new();
+        let _: &mut _ = constraints.video(&JsValue::TRUE);
+        let system_audio = match audio {
+            DisplayMediaAudio::Disable => None,
+            DisplayMediaAudio::Tab => Some("exclude"),
+            DisplayMediaAudio::System => Some("include"),
+        };
+        if let Some(system_audio) = system_audio {
+            let _: &mut _ = constraints.audio(&JsValue::TRUE);
+            let constraints: &Object = constraints.as_ref();
+            let _: bool = Reflect::set(
+                constraints,
+                &JsValue::from_str("systemAudio"),
+                &JsValue::from_str(system_audio),
+            )
+            .unwrap_or(false);
+        }
+
+        let window = window().ok_or(LocalMediaError::WindowIsUndefined)?;
+        let media_devices = window
+            .navigator()
+            .media_devices()
+            .map_err(LocalMediaError::MediaDevicesIsUndefined)?;
+        let media_stream_promise = media_devices
+            .get_display_media_with_constraints(&constraints)
+            .map_err(LocalMediaError::from_get_user_media_error)?;
+        let js_media_stream: MediaStream = JsFuture::from(media_stream_promise)
+            .await
+            .map_err(LocalMediaError::from_get_user_media_error)?
+            .dyn_into()
+            .map_err(LocalMediaError::InvalidMediaStream)?;
+
+        Ok(Self {
+            is_display_media: true,
+            ..Self::from_media_stream(js_media_stream)
+        })
+    }
+
+    /// Returns `true` for a captured screen, window or browser tab,
+    /// including its media mixed with other media.
+    pub fn is_display_media(&self) -> bool {
+        self.is_display_media
+    }
+
+    /// Reports the tracks ended by the browser or the user to the handler
+    /// while the returned watcher is kept.
+    ///
+    /// The listeners are added alongside the ones of `MediaSender`, both are notified.
+    pub fn watch(
+        &self,
+        handler: BoxAsyncFn2<Arc<LocalMediaWatcher>, LocalMediaEvent, ()>,
+    ) -> Arc<LocalMediaWatcher> {
+        let watcher = Arc::new(LocalMediaWatcher {
+            handler: BoxAsyncFn2Wrapper(handler),
+            is_display_media: self.is_display_media,
+            ended_listeners: RefCell::new(Vec::new()),
+        });
+        for track in self.all_tracks() {
+            watcher.init_ended_handler(track);
+        }
+        watcher
+    }
+
+    /// Returns the source of captured display audio,
+    /// or `None` if there is no captured audio or the media is not a display media.
+    pub fn display_audio_source(&self) -> Option<DisplayAudioSource> {
+        use js_sys::Reflect;
+
+        if self.js_media_stream.get_audio_tracks().length() == 0 {
+            return None;
+        }
+        let video_track = self
+            .tracks()
+            .into_iter()
+            .find(|track| track.kind() == "video")?;
+        let display_surface = Reflect::get(
+            &video_track.get_settings(),
+            &JsValue::from_str("displaySurface"),
+        )
+        .ok()?
+        .as_string()?;
+        Some(match display_surface.as_str() {
+            "browser" => DisplayAudioSource::Tab,
+            "monitor" => DisplayAudioSource::System,
+            "window" => DisplayAudioSource::Window,
+            _ => DisplayAudioSource::Unknown,
+        })
+    }
+
+    /// Returns media with video tracks of this media
+    /// and a single audio track mixed from audio tracks of both media.
+    ///
+    /// Stopping the returned media also stops tracks of both media.
+    pub fn with_mixed_audio(&self, other: &Self) -> Result<Self, LocalMediaError> {
+        let audio_mixer = AudioMixer::new()?;
+        audio_mixer.add_media_stream(&self.js_media_stream)?;
+        audio_mixer.add_media_stream(&other.js_media_stream)?;
+
+        let js_media_stream = MediaStream::new().map_err(LocalMediaError::NewMediaStreamError)?;
+        for track in self.tracks().iter().filter(|track| track.kind() == "video") {
+            js_media_stream.add_track(track);
+        }
+        if let Some(track) = audio_mixer.audio_track() {
+            js_media_stream.add_track(&track);
+        }
+
+        let js_mixed_tracks = self
+            .all_tracks()
+            .into_iter()
+            .chain(other.all_tracks())
+            .filter(|track| track.kind() == "audio")
+            .collect();
+
+        Ok(Self {
+            js_media_stream,
+            js_mixed_tracks,
+            audio_mixer: Some(Rc::new(audio_mixer)),
+            is_display_media: self.is_display_media,
+        })
+    }
+
+    /// Returns the settings of the first video track, `None` if there is no video.
+    pub fn video_settings(&self) -> Option<VideoSettings> {
+        use core::convert::TryFrom;
+
+        let video_track = self
+            .tracks()
+            .into_iter()
+            .find(|track| track.kind() == "video")?;
+        let settings = video_track.get_settings();
+        Some(VideoSettings {
+            device_id: settings.get_device_id(),
+            width: settings
+                .get_width()
+                .and_then(|width| u32::try_from(width).ok()),
+            height: settings
+                .get_height()
+                .and_then(|height| u32::try_from(height).ok()),
+            frame_rate: settings.get_frame_rate(),
+        })
+    }
+
+    pub fn audio_mixer(&self) -> Option<&AudioMixer> {
+        self.audio_mixer.as_deref()
+    }
+
     pub fn media_stream(&self) -> &MediaStream {
         &self.js_media_stream
     }
+
+    pub fn stop(&self) {
+        for track in self.all_tracks() {
+            track.stop();
+        }
+    }
+
+    pub fn stop_tracks_of_kind(&self, kind: &str) {
+        for track in self
+            .all_tracks()
+            .iter()
+            .filter(|track| track.kind() == kind)
+        {
+            track.stop();
+        }
+    }
+
+    fn all_tracks(&self) -> Vec<MediaStreamTrack> {
+        let mut tracks = self.tracks();
+        tracks.extend(self.js_mixed_tracks.iter().cloned());
+        tracks
+    }
+
+    pub fn tracks(&self) -> Vec<MediaStreamTrack> {
+        use wasm_bindgen::JsCast;
+
+        self.js_media_stream
+            .get_tracks()
+            .iter()
+            .filter_map(|track| track.dyn_into().ok())
+            .collect()
+    }
+}
+
+impl LocalMediaWatcher {
+    fn init_ended_handler(self: &Arc<Self>, track: MediaStreamTrack) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_ended_handler = {
+            let self_weak = Arc::downgrade(self);
+            let track = track.clone();
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    let track = track.clone();
+                    spawn_local(async move { self_arc.on_track_ended_event(track).await })
+                }
+            })
+        };
+        let _: Result<(), _> = track
+            .add_event_listener_with_callback("ended", js_ended_handler.as_ref().unchecked_ref());
+        self.ended_listeners.borrow_mut().push(TrackEndedListener {
+            js_track: track,
+            js_ended_handler,
+        });
+    }
+
+    async fn on_track_ended_event(self: &Arc<Self>, track: MediaStreamTrack) {
+        log::trace!("browser_webrtc::LocalMediaWatcher::on_track_ended_event");
+
+        // Browsers end the display video track when the user stops sharing.
+        let ev = if self.is_display_media && track.kind() == "video" {
+            LocalMediaEvent::DisplayMediaStopped
+        } else {
+            LocalMediaEvent::TrackEnded(track)
+        };
+        self.handler.0(Arc::clone(self), ev).await
+    }
+}
+
+impl Drop for TrackEndedListener {
+    fn drop(&mut self) {
+        use wasm_bindgen::JsCast;
+
+        let _: Result<(), _> = self.js_track.remove_event_listener_with_callback(
+            "ended",
+            self.js_ended_handler.as_ref().unchecked_ref(),
+        );
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LocalMediaError {
+    #[error("JavaScript window is undefined")]
+    WindowIsUndefined,
+    #[error("navigator.mediaDevices is undefined: {0:?}")]
+    MediaDevicesIsUndefined(JsValue),
+    #[error("permission to use media devices was denied: {0}")]
+    PermissionDenied(String),
+    #[error("requested media devices were not found: {0}")]
+    NotFound(String),
+    #[error("media devices could not be read: {0}")]
+    NotReadable(String),
+    #[error("media constraints could not be satisfied: {0}")]
+    Overconstrained(String),
+    #[error("enumerateDevices error: {0:?}")]
+    EnumerateDevicesError(JsValue),
+    #[error("getUserMedia error: {0:?}")]
+    GetUserMediaError(JsValue),
+    #[error("invalid MediaStream received: {0:?}")]
+    InvalidMediaStream(JsValue),
+    #[error("new MediaStream error: {0:?}")]
+    NewMediaStreamError(JsValue),
+    #[error(transparent)]
+    AudioMixerError(#[from] AudioMixerError),
+}
+
+impl LocalMediaError {
+    /// Returns `true` if the capture may succeed with the relaxed constraints.
+    pub fn is_recoverable_by_fallback(&self) -> bool {
+        matches!(self, Self::NotReadable(_) | Self::Overconstrained(_))
+    }
+
+    fn from_get_user_media_error(err: JsValue) -> Self {
+        use js_sys::Reflect;
+
+        let property = |name: &str| {
+            Reflect::get(&err, &JsValue::from_str(name))
+                .ok()
+                .and_then(|value| value.as_string())
+        };
+
+        let message = property("message").unwrap_or_default();
+        match property("name").as_deref() {
+            Some("NotAllowedError") | Some("PermissionDeniedError") | Some("SecurityError") => {
+                Self::PermissionDenied(message)
+            }
+            Some("NotFoundError") | Some("DevicesNotFoundError") => Self::NotFound(message),
+            Some("NotReadableError") | Some("TrackStartError") | Some("AbortError") => {
+                Self::NotReadable(message)
+            }
+            Some("OverconstrainedError") | Some("ConstraintNotSatisfiedError") => {
+                Self::Overconstrained(message)
+            }
+            _ => Self::GetUserMediaError(err),
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 
 use async_std::sync::Arc;
 use thiserror::Error;
@@ -6,7 +6,11 @@ use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsValue;
 use web_sys::{MediaStream, MediaStreamTrack, TrackEvent};
 
-use crate::{BoxAsyncFn2, BoxAsyncFn2Wrapper, Receiver};
+use crate::live_objects::{LiveObject, LiveObjectKind};
+use crate::{
+    BoxAsyncFn2, BoxAsyncFn2Wrapper, InboundVideoStats, MediaSnapshot, MediaSnapshotError,
+    MediaSnapshotFormat, Receiver, StatsError,
+};
 
 #[derive(Debug)]
 pub struct MediaReceiverBuilder {
@@ -34,9 +38,11 @@ impl MediaReceiverBuilder {
 pub struct MediaReceiver {
     receiver: Arc<Receiver>,
     handler: BoxAsyncFn2Wrapper<Arc<MediaReceiver>, MediaReceiverEvent, ()>,
+    _live_object: LiveObject,
     js_media_stream: MediaStream,
     js_add_track_handler: RefCell<Option<Closure<dyn FnMut(TrackEvent)>>>,
     js_remove_track_handler: RefCell<Option<Closure<dyn FnMut(TrackEvent)>>>,
+    is_paused: Cell<bool>,
 }
 
 impl MediaReceiver {
@@ -50,9 +56,11 @@ impl MediaReceiver {
         let data_channel = Arc::new(Self {
             receiver,
             handler: BoxAsyncFn2Wrapper(handler),
+            _live_object: LiveObject::new(LiveObjectKind::MediaReceiver),
             js_media_stream,
             js_add_track_handler: RefCell::new(None),
             js_remove_track_handler: RefCell::new(None),
+            is_paused: Cell::new(false),
         });
 
         data_channel.init_add_track_handler();
@@ -65,6 +73,69 @@ impl MediaReceiver {
         &self.js_media_stream
     }
 
+    /// Label of the stream set by the sender, `None` until it is received.
+    pub fn label(&self) -> Option<String> {
+        self.receiver.stream_label(&self.js_media_stream.id())
+    }
+
+    /// Temporarily stops rendering the received tracks, e.g. in a backgrounded tab.
+    ///
+    /// Only the local tracks are disabled, changing the transceiver direction instead
+    /// would require a renegotiation, so the sender keeps sending and `resume` is instant.
+    /// The media view showing the stream should be paused too.
+    pub fn pause(&self) {
+        self.is_paused.set(true);
+        self.set_tracks_enabled(false);
+    }
+
+    pub fn resume(&self) {
+        self.is_paused.set(false);
+        self.set_tracks_enabled(true);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.get()
+    }
+
+    fn set_tracks_enabled(&self, is_enabled: bool) {
+        use wasm_bindgen::JsCast;
+
+        for track in self.js_media_stream.get_tracks().iter() {
+            if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                track.set_enabled(is_enabled);
+            }
+        }
+    }
+
+    /// Captures the current video frame, e.g. for a channel thumbnail.
+    pub async fn capture_snapshot(
+        &self,
+        max_width: u32,
+        format: MediaSnapshotFormat,
+    ) -> Result<MediaSnapshot, MediaSnapshotError> {
+        MediaSnapshot::capture_media_stream(&self.js_media_stream, max_width, format).await
+    }
+
+    /// Returns stats of the received video,
+    /// rates are computed relative to the previous sample if specified.
+    pub async fn video_stats(
+        &self,
+        prev: Option<&InboundVideoStats>,
+    ) -> Result<Option<InboundVideoStats>, StatsError> {
+        use wasm_bindgen::JsCast;
+
+        let track: Option<MediaStreamTrack> = self
+            .js_media_stream
+            .get_video_tracks()
+            .get(0)
+            .dyn_into()
+            .ok();
+        match track {
+            Some(track) => self.receiver.inbound_video_stats(&track, prev).await,
+            None => Ok(None),
+        }
+    }
+
     fn init_add_track_handler(self: &Arc<Self>) {
         use crate::closure_1;
         use wasm_bindgen::JsCast;
@@ -73,8 +144,9 @@ impl MediaReceiver {
         let js_add_track_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |ev: TrackEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_add_track_event(ev).await })
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_add_track_event(ev).await });
+                }
             })
         };
         self.js_media_stream
@@ -93,8 +165,9 @@ impl MediaReceiver {
         let js_remove_track_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |ev: TrackEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_remove_track_event(ev).await })
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_remove_track_event(ev).await });
+                }
             })
         };
         self.js_media_stream
@@ -126,10 +199,13 @@ impl MediaReceiver {
     ) -> Result<(), MediaReceiverError> {
         use wasm_bindgen::JsCast;
 
-        let track = ev
+        let track: MediaStreamTrack = ev
             .track()
             .and_then(|track| track.dyn_into().ok())
             .ok_or_else(|| MediaReceiverError::InvalidAddTrackValue(ev.track().map(Into::into)))?;
+        if self.is_paused() {
+            track.set_enabled(false);
+        }
         self.handler(MediaReceiverEvent::AddTrack(track)).await;
         Ok(())
     }
@@ -1,14 +1,43 @@
+use core::cell::{Cell, RefCell};
+
 use async_std::sync::Arc;
-use web_sys::{MediaStream, RtcPeerConnection, RtcRtpSender};
+use thiserror::Error;
+use wasm_bindgen::closure::Closure;
+use web_sys::{Event, MediaStream, MediaStreamTrack, RtcPeerConnection, RtcRtpSender};
 
-use crate::Sender;
+#[cfg(feature = "encoded-transform")]
+use crate::{
+    encoded_transform::{EncodedTransformPipe, SharedEncodedFrameTransform},
+    EncodedFrameTransform, EncodedTransformError,
+};
+use crate::{
+    BoxAsyncFn2, BoxAsyncFn2Wrapper, KeyFrameRequestMethod, KeyFrameRequestStats,
+    RtpParametersError, RtpSendPriority, RtpSendPrioritySupport, Sender, StatsError, VideoQuality,
+};
 
 #[derive(Debug)]
 pub struct MediaSender {
     sender: Arc<Sender>,
+    handler: BoxAsyncFn2Wrapper<Arc<MediaSender>, MediaSenderEvent, ()>,
     js_connection: RtcPeerConnection,
     js_media_stream: MediaStream,
-    js_rtc_rtp_senders: Vec<RtcRtpSender>,
+    js_tracks: RefCell<Vec<MediaSenderTrack>>,
+    label: RefCell<Option<String>>,
+    send_priority: RefCell<RtpSendPriority>,
+    video_quality: Cell<VideoQuality>,
+    /// Video tracks are kept, but not sent, see `AudioFallbackPolicy`.
+    is_video_suspended: Cell<bool>,
+    #[cfg(feature = "encoded-transform")]
+    encoded_transform: SharedEncodedFrameTransform,
+}
+
+#[derive(Debug)]
+struct MediaSenderTrack {
+    js_track: MediaStreamTrack,
+    js_rtc_rtp_sender: RtcRtpSender,
+    js_ended_handler: Closure<dyn FnMut(Event)>,
+    #[cfg(feature = "encoded-transform")]
+    _js_encoded_transform_pipe: Option<EncodedTransformPipe>,
 }
 
 impl MediaSender {
@@ -16,40 +45,344 @@ impl MediaSender {
         sender: Arc<Sender>,
         js_connection: RtcPeerConnection,
         js_media_stream: MediaStream,
+        handler: BoxAsyncFn2<Arc<Self>, MediaSenderEvent, ()>,
     ) -> Arc<Self> {
         log::trace!("browser_webrtc::MediaSender::new");
 
         use wasm_bindgen::JsCast;
-        use web_sys::MediaStreamTrack;
-
-        let tracks = js_media_stream.get_tracks();
-        let js_rtc_rtp_senders = tracks
-            .iter()
-            .map(|track| {
-                let track: MediaStreamTrack = track.dyn_into().unwrap();
-                js_connection.add_track_0(&track, &js_media_stream)
-            })
-            .collect();
 
-        Arc::new(Self {
+        let media_sender = Arc::new(Self {
             sender,
+            handler: BoxAsyncFn2Wrapper(handler),
             js_connection,
             js_media_stream,
-            js_rtc_rtp_senders,
-        })
+            js_tracks: RefCell::new(Vec::new()),
+            label: RefCell::new(None),
+            send_priority: RefCell::new(RtpSendPriority::default()),
+            video_quality: Cell::new(VideoQuality::default()),
+            is_video_suspended: Cell::new(false),
+            #[cfg(feature = "encoded-transform")]
+            encoded_transform: SharedEncodedFrameTransform::default(),
+        });
+
+        for track in media_sender.js_media_stream.get_tracks().iter() {
+            let track: MediaStreamTrack = track.dyn_into().unwrap();
+            media_sender.add_connection_track(track);
+        }
+
+        media_sender
     }
 
     pub fn media_stream(&self) -> &MediaStream {
         &self.js_media_stream
     }
+
+    /// Label of the stream sent to the receiver, e.g. to tell a camera from a screen.
+    pub fn label(&self) -> Option<String> {
+        self.label.borrow().clone()
+    }
+
+    /// Sets the stream label, the receiver reads it with `MediaReceiver::label`.
+    pub fn set_label(&self, label: Option<String>) {
+        let _: Option<_> = self.label.replace(label);
+        self.sender.update_stream_labels();
+    }
+
+    /// RTP senders of the stream tracks currently added to the connection.
+    pub fn rtc_rtp_senders(&self) -> Vec<RtcRtpSender> {
+        self.js_tracks
+            .borrow()
+            .iter()
+            .map(|track| track.js_rtc_rtp_sender.clone())
+            .collect()
+    }
+
+    /// Removes all tracks of the stream from the connection,
+    /// called when the stream is removed from the sender.
+    pub(crate) fn remove_all_tracks(&self) {
+        self.remove_tracks(|_| true);
+    }
+
+    /// Adds a new track to the media stream and to the connection,
+    /// e.g. a replacement for a track of an unplugged device.
+    pub fn add_track(self: &Arc<Self>, track: MediaStreamTrack) {
+        self.js_media_stream.add_track(&track);
+        self.add_connection_track(track);
+    }
+
+    fn add_connection_track(self: &Arc<Self>, track: MediaStreamTrack) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_rtc_rtp_sender = self
+            .js_connection
+            .add_track_0(&track, &self.js_media_stream);
+
+        let js_ended_handler = {
+            let self_weak = Arc::downgrade(self);
+            let track = track.clone();
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    let track = track.clone();
+                    spawn_local(async move { self_arc.on_track_ended_event(track).await })
+                }
+            })
+        };
+        track.set_onended(Some(js_ended_handler.as_ref().unchecked_ref()));
+
+        let send_priority = *self.send_priority.borrow();
+        if !send_priority.is_empty() {
+            let self_arc = Arc::clone(self);
+            let js_rtc_rtp_sender = js_rtc_rtp_sender.clone();
+            spawn_local(async move {
+                if let Err(err) = send_priority.apply(&js_rtc_rtp_sender).await {
+                    self_arc.error(MediaSenderError::from(err)).await
+                }
+            });
+        }
+
+        let video_quality = self.video_quality.get();
+        if track.kind() == "video" && !video_quality.is_full() {
+            let self_arc = Arc::clone(self);
+            let js_rtc_rtp_sender = js_rtc_rtp_sender.clone();
+            spawn_local(async move {
+                if let Err(err) = video_quality.apply(&js_rtc_rtp_sender).await {
+                    self_arc.error(MediaSenderError::from(err)).await
+                }
+            });
+        }
+
+        if track.kind() == "video" && self.is_video_suspended.get() {
+            let self_arc = Arc::clone(self);
+            let js_rtc_rtp_sender = js_rtc_rtp_sender.clone();
+            spawn_local(async move {
+                if let Err(err) = replace_track(&js_rtc_rtp_sender, None).await {
+                    self_arc.error(err).await
+                }
+            });
+        }
+
+        #[cfg(feature = "encoded-transform")]
+        let js_encoded_transform_pipe = self.pipe_encoded_transform(&js_rtc_rtp_sender);
+
+        self.js_tracks.borrow_mut().push(MediaSenderTrack {
+            js_track: track,
+            js_rtc_rtp_sender,
+            js_ended_handler,
+            #[cfg(feature = "encoded-transform")]
+            _js_encoded_transform_pipe: js_encoded_transform_pipe,
+        });
+    }
+
+    #[cfg(feature = "encoded-transform")]
+    fn pipe_encoded_transform(
+        self: &Arc<Self>,
+        js_rtc_rtp_sender: &RtcRtpSender,
+    ) -> Option<EncodedTransformPipe> {
+        use crate::encoded_transform::is_encoded_transform_enabled;
+        use wasm_bindgen_futures::spawn_local;
+
+        if !is_encoded_transform_enabled(&self.js_connection) {
+            return None;
+        }
+        match EncodedTransformPipe::new(js_rtc_rtp_sender, self.encoded_transform.clone()) {
+            Ok(pipe) => Some(pipe),
+            Err(err) => {
+                let self_arc = Arc::clone(self);
+                spawn_local(async move { self_arc.error(MediaSenderError::from(err)).await });
+                None
+            }
+        }
+    }
+
+    /// Sets the transformation of encoded frames of all tracks of this media sender.
+    ///
+    /// Requires the connection to be created with
+    /// `RtcConfigurationExt::with_encoded_insertable_streams`.
+    #[cfg(feature = "encoded-transform")]
+    pub fn set_encoded_transform(&self, transform: Option<EncodedFrameTransform>) {
+        self.encoded_transform.set(transform);
+    }
+
+    /// Sets sending priorities of all current and future tracks of this media sender.
+    ///
+    /// Parameters not supported by the browser are skipped
+    /// and reported as unsupported in the returned value.
+    pub async fn set_send_priority(
+        &self,
+        send_priority: RtpSendPriority,
+    ) -> Result<RtpSendPrioritySupport, RtpParametersError> {
+        let _: RtpSendPriority = self.send_priority.replace(send_priority);
+
+        let js_rtc_rtp_senders = self.rtc_rtp_senders();
+
+        let mut support: Option<RtpSendPrioritySupport> = None;
+        for js_rtc_rtp_sender in js_rtc_rtp_senders {
+            let track_support = send_priority.apply(&js_rtc_rtp_sender).await?;
+            support = Some(match support {
+                Some(support) => support.min(track_support),
+                None => track_support,
+            });
+        }
+        Ok(support.unwrap_or_else(RtpSendPrioritySupport::unsupported))
+    }
+
+    pub fn video_quality(&self) -> VideoQuality {
+        self.video_quality.get()
+    }
+
+    /// Sets the sending quality of all current and future video tracks of this media sender.
+    pub async fn set_video_quality(&self, quality: VideoQuality) -> Result<(), RtpParametersError> {
+        self.video_quality.set(quality);
+        for js_rtc_rtp_sender in self.js_rtc_rtp_senders_of_kind("video") {
+            quality.apply(&js_rtc_rtp_sender).await?;
+        }
+        Ok(())
+    }
+
+    pub fn is_video_suspended(&self) -> bool {
+        self.is_video_suspended.get()
+    }
+
+    /// Stops or resumes sending of all current and future video tracks of this media sender,
+    /// the tracks are kept, so no renegotiation is needed to resume them.
+    pub(crate) async fn set_video_suspended(
+        &self,
+        is_suspended: bool,
+    ) -> Result<(), MediaSenderError> {
+        if self.is_video_suspended.replace(is_suspended) == is_suspended {
+            return Ok(());
+        }
+        let tracks: Vec<_> = self
+            .js_tracks
+            .borrow()
+            .iter()
+            .filter(|track| track.js_track.kind() == "video")
+            .map(|track| (track.js_track.clone(), track.js_rtc_rtp_sender.clone()))
+            .collect();
+        for (js_track, js_rtc_rtp_sender) in tracks {
+            let js_track = Some(&js_track).filter(|_| !is_suspended);
+            replace_track(&js_rtc_rtp_sender, js_track).await?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_tracks_of_kind(&self, kind: &str) {
+        self.remove_tracks(|track| track.kind() == kind);
+    }
+
+    fn remove_tracks<F: Fn(&MediaStreamTrack) -> bool>(&self, predicate: F) {
+        let tracks = core::mem::take(&mut *self.js_tracks.borrow_mut());
+        let (removed, kept): (Vec<_>, Vec<_>) = tracks
+            .into_iter()
+            .partition(|track| predicate(&track.js_track));
+        *self.js_tracks.borrow_mut() = kept;
+        for track in removed {
+            self.js_media_stream.remove_track(&track.js_track);
+            self.remove_connection_track(track);
+        }
+    }
+
+    fn remove_connection_track(&self, track: MediaSenderTrack) {
+        let MediaSenderTrack {
+            js_track,
+            js_rtc_rtp_sender,
+            js_ended_handler,
+            ..
+        } = track;
+        js_track.set_onended(None);
+        self.js_connection.remove_track(&js_rtc_rtp_sender);
+        drop(js_ended_handler);
+    }
+
+    fn js_rtc_rtp_senders_of_kind(&self, kind: &str) -> Vec<RtcRtpSender> {
+        self.js_tracks
+            .borrow()
+            .iter()
+            .filter(|track| track.js_track.kind() == kind)
+            .map(|track| track.js_rtc_rtp_sender.clone())
+            .collect()
+    }
+
+    /// Requests the generation of a keyframe for all video tracks,
+    /// e.g. to avoid waiting for a clean frame after a receiver joins.
+    ///
+    /// Returns `None` if there are no video tracks.
+    pub async fn request_key_frame(
+        &self,
+    ) -> Result<Option<KeyFrameRequestMethod>, RtpParametersError> {
+        use crate::rtp_parameters::request_key_frame;
+
+        let mut method = None;
+        for js_rtc_rtp_sender in self.js_rtc_rtp_senders_of_kind("video") {
+            method = Some(request_key_frame(&js_rtc_rtp_sender).await?);
+        }
+        Ok(method)
+    }
+
+    /// Returns the number of keyframe requests received for all video tracks.
+    pub async fn key_frame_request_stats(&self) -> Result<KeyFrameRequestStats, StatsError> {
+        use crate::stats::stats_report_values;
+
+        let mut stats = Vec::new();
+        for js_rtc_rtp_sender in self.js_rtc_rtp_senders_of_kind("video") {
+            stats.extend(stats_report_values(js_rtc_rtp_sender.get_stats()).await?);
+        }
+        Ok(KeyFrameRequestStats::from_outbound_rtp_stats(&stats))
+    }
+
+    async fn handler(self: &Arc<Self>, ev: MediaSenderEvent) {
+        self.handler.0(Arc::clone(self), ev).await
+    }
+
+    async fn error(self: &Arc<Self>, err: MediaSenderError) {
+        self.handler(MediaSenderEvent::Error(err)).await
+    }
+
+    async fn on_track_ended_event(self: &Arc<Self>, track: MediaStreamTrack) {
+        log::trace!("browser_webrtc::MediaSender::on_track_ended_event");
+
+        self.remove_tracks(|other| other == &track);
+        self.handler(MediaSenderEvent::TrackEnded(track)).await
+    }
 }
 
 impl Drop for MediaSender {
     fn drop(&mut self) {
         log::trace!("browser_webrtc::MediaSender::drop");
 
-        for sender in self.js_rtc_rtp_senders.iter() {
-            self.js_connection.remove_track(&sender);
+        for track in core::mem::take(self.js_tracks.get_mut()) {
+            self.remove_connection_track(track);
         }
     }
 }
+
+async fn replace_track(
+    js_rtc_rtp_sender: &RtcRtpSender,
+    js_track: Option<&MediaStreamTrack>,
+) -> Result<(), MediaSenderError> {
+    use wasm_bindgen_futures::JsFuture;
+
+    let _: wasm_bindgen::JsValue = JsFuture::from(js_rtc_rtp_sender.replace_track(js_track))
+        .await
+        .map_err(MediaSenderError::ReplaceTrackError)?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum MediaSenderEvent {
+    TrackEnded(MediaStreamTrack),
+    Error(MediaSenderError),
+}
+
+#[derive(Error, Debug)]
+pub enum MediaSenderError {
+    #[error("replace track error: {0:?}")]
+    ReplaceTrackError(wasm_bindgen::JsValue),
+    #[error(transparent)]
+    RtpParametersError(#[from] RtpParametersError),
+    #[cfg(feature = "encoded-transform")]
+    #[error(transparent)]
+    EncodedTransformError(#[from] EncodedTransformError),
+}
@@ -1,11 +1,34 @@
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+
 use async_std::sync::Arc;
 use thiserror::Error;
 use wasm_bindgen::JsValue;
-use web_sys::{HtmlVideoElement, MediaStream};
+use web_sys::{Event, HtmlCanvasElement, HtmlVideoElement, MediaStream, TextTrack, VttCue};
+
+use crate::peer_connection::JsHandler;
+use crate::{
+    BoxAsyncFn2, BoxAsyncFn2Wrapper, CaptionCue, InboundVideoStats, MediaSnapshot,
+    MediaSnapshotError, MediaSnapshotFormat,
+};
+
+const HUD_FONT_SIZE: f64 = 12.0;
+const HUD_LINE_HEIGHT: f64 = 14.0;
 
 #[derive(Debug)]
 pub struct MediaView {
     pub video: HtmlVideoElement,
+    hud: HtmlCanvasElement,
+    is_hud_enabled: Cell<bool>,
+    audio: MediaViewAudio,
+    is_first_frame_rendered: Cell<bool>,
+    is_first_audio_played: Cell<bool>,
+    caption_track: RefCell<Option<TextTrack>>,
+    /// The shown cue, replaced by the next cues with the same id.
+    caption_cue: RefCell<Option<(u32, VttCue)>>,
+    js_first_frame_handler: JsHandler<JsValue>,
+    js_audio_state_handler: JsHandler<Event>,
+    handler: BoxAsyncFn2Wrapper<Arc<MediaView>, MediaViewEvent, ()>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -18,8 +41,10 @@ impl MediaView {
     pub fn new(
         media_stream: MediaStream,
         audio: MediaViewAudio,
+        handler: BoxAsyncFn2<Arc<Self>, MediaViewEvent, ()>,
     ) -> Result<Arc<Self>, NewMediaViewError> {
         use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
         use web_sys::window;
 
         let window = window().ok_or(NewMediaViewError::WindowIsUndefined)?;
@@ -28,7 +53,7 @@ impl MediaView {
             .ok_or(NewMediaViewError::DocumentIsUndefined)?;
         let video: HtmlVideoElement = document
             .create_element("video")
-            .map_err(|err| NewMediaViewError::VideoElementCreateError(err))?
+            .map_err(NewMediaViewError::VideoElementCreateError)?
             .dyn_into()
             .unwrap();
 
@@ -37,17 +62,417 @@ impl MediaView {
 
         video.set_src_object(Some(&media_stream));
 
+        let hud: HtmlCanvasElement = document
+            .create_element("canvas")
+            .map_err(NewMediaViewError::CanvasElementCreateError)?
+            .dyn_into()
+            .unwrap();
+        let _: Option<_> = hud.set_attribute("class", "hud").ok();
+        hud.set_hidden(true);
+
         match audio {
             MediaViewAudio::Enable => video.set_muted(false),
             MediaViewAudio::Disable => video.set_muted(true),
         }
 
-        Ok(Arc::new(Self { video }))
+        let media_view = Arc::new(Self {
+            video,
+            hud,
+            is_hud_enabled: Cell::new(false),
+            audio,
+            is_first_frame_rendered: Cell::new(false),
+            is_first_audio_played: Cell::new(false),
+            caption_track: RefCell::new(None),
+            caption_cue: RefCell::new(None),
+            js_first_frame_handler: RefCell::new(None),
+            js_audio_state_handler: RefCell::new(None),
+            handler: BoxAsyncFn2Wrapper(handler),
+        });
+        media_view.init_first_frame_handler();
+        media_view.init_audio_state_handler();
+
+        spawn_local({
+            let media_view = Arc::clone(&media_view);
+            async move { media_view.start_playback().await }
+        });
+
+        Ok(media_view)
     }
 
     pub fn view(&self) -> &HtmlVideoElement {
         &self.video
     }
+
+    /// Returns `true` after the first video frame is presented.
+    pub fn is_first_frame_rendered(&self) -> bool {
+        self.is_first_frame_rendered.get()
+    }
+
+    /// Returns `true` after the audio is first played unmuted.
+    pub fn is_first_audio_played(&self) -> bool {
+        self.is_first_audio_played.get()
+    }
+
+    /// Detects the first presented frame with `requestVideoFrameCallback` if supported,
+    /// otherwise with the `loadeddata` event.
+    fn init_first_frame_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use js_sys::{Function, Reflect};
+        use wasm_bindgen::JsCast;
+
+        let self_weak = Arc::downgrade(self);
+        let js_first_frame_handler = closure_1(move |_: JsValue| {
+            if let Some(self_arc) = self_weak.upgrade() {
+                self_arc.on_first_frame()
+            }
+        });
+        let request_video_frame_callback =
+            Reflect::get(&self.video, &JsValue::from_str("requestVideoFrameCallback"))
+                .ok()
+                .and_then(|function| function.dyn_into::<Function>().ok());
+        match request_video_frame_callback {
+            Some(request_video_frame_callback) => {
+                let _: Option<JsValue> = request_video_frame_callback
+                    .call1(&self.video, js_first_frame_handler.as_ref())
+                    .ok();
+            }
+            None => self
+                .video
+                .set_onloadeddata(Some(js_first_frame_handler.as_ref().unchecked_ref())),
+        }
+        let _: Option<_> = self
+            .js_first_frame_handler
+            .replace(Some(js_first_frame_handler));
+    }
+
+    fn on_first_frame(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        if self.is_first_frame_rendered.replace(true) {
+            return;
+        }
+        self.video.set_onloadeddata(None);
+        let self_arc = Arc::clone(self);
+        spawn_local(async move {
+            let (width, height) = self_arc.video_size().unwrap_or((0, 0));
+            self_arc
+                .handler(MediaViewEvent::FirstFrameRendered { width, height })
+                .await
+        });
+    }
+
+    /// Detects the first unmuted playback of the stream audio tracks,
+    /// the audio is unmuted later if it is blocked by the autoplay policy.
+    fn init_audio_state_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+
+        if self.audio == MediaViewAudio::Disable {
+            return;
+        }
+        let self_weak = Arc::downgrade(self);
+        let js_audio_state_handler = closure_1(move |_: Event| {
+            if let Some(self_arc) = self_weak.upgrade() {
+                self_arc.on_audio_state_change()
+            }
+        });
+        self.video
+            .set_onplaying(Some(js_audio_state_handler.as_ref().unchecked_ref()));
+        self.video
+            .set_onvolumechange(Some(js_audio_state_handler.as_ref().unchecked_ref()));
+        let _: Option<_> = self
+            .js_audio_state_handler
+            .replace(Some(js_audio_state_handler));
+    }
+
+    fn on_audio_state_change(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        let has_audio_tracks = self
+            .video
+            .src_object()
+            .is_some_and(|media_stream| media_stream.get_audio_tracks().length() > 0);
+        if self.video.paused()
+            || self.video.muted()
+            || !has_audio_tracks
+            || self.is_first_audio_played.replace(true)
+        {
+            return;
+        }
+        self.video.set_onplaying(None);
+        self.video.set_onvolumechange(None);
+        let self_arc = Arc::clone(self);
+        spawn_local(async move { self_arc.handler(MediaViewEvent::FirstAudioPlayed).await });
+    }
+
+    /// Canvas with the video statistics overlay,
+    /// it should be placed over the video element.
+    pub fn hud(&self) -> &HtmlCanvasElement {
+        &self.hud
+    }
+
+    pub fn is_hud_enabled(&self) -> bool {
+        self.is_hud_enabled.get()
+    }
+
+    pub fn set_hud_enabled(&self, is_hud_enabled: bool) {
+        self.is_hud_enabled.set(is_hud_enabled);
+        self.hud.set_hidden(!is_hud_enabled);
+    }
+
+    /// Draws the video statistics on the overlay sized to the displayed video.
+    pub fn update_hud(&self, stats: &InboundVideoStats) -> Result<(), MediaViewHudError> {
+        use wasm_bindgen::JsCast;
+        use web_sys::CanvasRenderingContext2d;
+
+        fn format_ms(duration: Option<Duration>) -> String {
+            duration.map_or_else(
+                || "-".to_owned(),
+                |duration| format!("{:.0} ms", duration.as_secs_f64() * 1000.0),
+            )
+        }
+
+        let width = self.video.client_width().max(0) as u32;
+        let height = self.video.client_height().max(0) as u32;
+        if self.hud.width() != width || self.hud.height() != height {
+            self.hud.set_width(width);
+            self.hud.set_height(height);
+        }
+
+        let context: CanvasRenderingContext2d = self
+            .hud
+            .get_context("2d")
+            .map_err(MediaViewHudError::GetContextError)?
+            .and_then(|context| context.dyn_into().ok())
+            .ok_or(MediaViewHudError::ContextIsUnavailable)?;
+
+        let lines = [
+            match stats.bitrate {
+                Some(bitrate) => format!("bitrate: {:.0} kbps", bitrate / 1000.0),
+                None => "bitrate: -".to_owned(),
+            },
+            match stats.frames_per_second {
+                Some(frames_per_second) => format!("framerate: {:.0} fps", frames_per_second),
+                None => "framerate: -".to_owned(),
+            },
+            match (stats.frame_width, stats.frame_height) {
+                (Some(width), Some(height)) => format!("resolution: {}x{}", width, height),
+                _ => "resolution: -".to_owned(),
+            },
+            format!("jitter: {}", format_ms(stats.jitter)),
+            format!("latency: ~{}", format_ms(stats.estimated_latency())),
+        ];
+
+        context.clear_rect(0.0, 0.0, f64::from(width), f64::from(height));
+        context.set_fill_style_str("rgba(0, 0, 0, 0.5)");
+        context.fill_rect(
+            0.0,
+            0.0,
+            160.0,
+            HUD_LINE_HEIGHT * lines.len() as f64 + HUD_LINE_HEIGHT / 2.0,
+        );
+        context.set_fill_style_str("#fff");
+        context.set_font(&format!("{}px monospace", HUD_FONT_SIZE));
+        for (index, line) in lines.iter().enumerate() {
+            context
+                .fill_text(line, 4.0, HUD_LINE_HEIGHT * (index + 1) as f64)
+                .map_err(MediaViewHudError::DrawTextError)?;
+        }
+        Ok(())
+    }
+
+    /// Shows the caption cue over the video from the current playback time for its duration,
+    /// the interim cue with the same id is replaced.
+    pub fn show_caption(&self, cue: &CaptionCue) -> Result<(), MediaViewCaptionError> {
+        use web_sys::{TextTrackKind, TextTrackMode};
+
+        let mut caption_track = self.caption_track.borrow_mut();
+        let track = match caption_track.as_ref() {
+            Some(track) if track.language() == cue.language => track,
+            _ => {
+                if let Some(prev_track) = caption_track.take() {
+                    prev_track.set_mode(TextTrackMode::Disabled);
+                }
+                let _: Option<_> = self.caption_cue.take();
+                let track = self.video.add_text_track_with_label_and_language(
+                    TextTrackKind::Captions,
+                    "captions",
+                    &cue.language,
+                );
+                track.set_mode(TextTrackMode::Showing);
+                caption_track.insert(track)
+            }
+        };
+
+        let mut caption_cue = self.caption_cue.borrow_mut();
+        let start_time = match caption_cue.take() {
+            Some((id, prev_cue)) => {
+                let _: Option<_> = track.remove_cue(&prev_cue).ok();
+                if id == cue.id {
+                    prev_cue.start_time()
+                } else {
+                    self.video.current_time()
+                }
+            }
+            None => self.video.current_time(),
+        };
+        let end_time = self.video.current_time() + cue.duration.as_secs_f64();
+        let js_cue = VttCue::new(start_time, end_time, &cue.text)
+            .map_err(MediaViewCaptionError::CueCreateError)?;
+        track.add_cue(&js_cue);
+        *caption_cue = Some((cue.id, js_cue));
+        Ok(())
+    }
+
+    /// Size of the rendered video frames, `None` until the first frame is decoded.
+    pub fn video_size(&self) -> Option<(u32, u32)> {
+        match (self.video.video_width(), self.video.video_height()) {
+            (0, _) | (_, 0) => None,
+            size => Some(size),
+        }
+    }
+
+    /// Estimates the rendered frame rate by counting the frames decoded during the duration,
+    /// returns `None` if no frames were decoded.
+    pub async fn measure_frame_rate(&self, duration: Duration) -> Option<f64> {
+        use crate::sleep;
+
+        let started_at = self.video.get_video_playback_quality().total_video_frames();
+        sleep(duration).await;
+        let frames = self
+            .video
+            .get_video_playback_quality()
+            .total_video_frames()
+            .saturating_sub(started_at);
+        if frames == 0 {
+            None
+        } else {
+            Some(f64::from(frames) / duration.as_secs_f64())
+        }
+    }
+
+    pub async fn capture_snapshot(
+        &self,
+        max_width: u32,
+        format: MediaSnapshotFormat,
+    ) -> Result<MediaSnapshot, MediaSnapshotError> {
+        MediaSnapshot::capture_video_with_format(&self.video, max_width, format).await
+    }
+
+    /// Pauses the video rendering until `resume` is called.
+    pub fn pause(&self) -> Result<(), MediaViewError> {
+        self.video.pause().map_err(MediaViewError::PauseError)
+    }
+
+    /// Resumes the video rendering paused with `pause`.
+    pub fn resume(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        let media_view = Arc::clone(self);
+        spawn_local(async move { media_view.start_playback().await });
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.video.paused()
+    }
+
+    /// Resumes playback blocked by the browser autoplay policy.
+    ///
+    /// Must be called from a user gesture event handler (e.g. click),
+    /// otherwise the browser will block the playback again.
+    pub fn resume_playback(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        if self.audio == MediaViewAudio::Enable {
+            self.video.set_muted(false);
+        }
+        let play_result = self.video.play();
+        let media_view = Arc::clone(self);
+        spawn_local(async move {
+            match media_view.await_play(play_result).await {
+                Ok(()) => media_view.handler(MediaViewEvent::PlaybackStarted).await,
+                Err(MediaViewPlayError::NotAllowed) => {
+                    media_view.handler(MediaViewEvent::PlaybackBlocked).await
+                }
+                Err(err) => media_view.error(MediaViewError::PlayError(err)).await,
+            }
+        });
+    }
+
+    async fn start_playback(self: &Arc<Self>) {
+        match self.await_play(self.video.play()).await {
+            Ok(()) => self.handler(MediaViewEvent::PlaybackStarted).await,
+            Err(MediaViewPlayError::NotAllowed) => match self.audio {
+                // Muted playback is allowed by autoplay policies,
+                // so the video is started and only the audio waits for a user gesture.
+                MediaViewAudio::Enable => {
+                    self.video.set_muted(true);
+                    match self.await_play(self.video.play()).await {
+                        Ok(()) => self.handler(MediaViewEvent::AudioBlocked).await,
+                        Err(MediaViewPlayError::NotAllowed) => {
+                            self.handler(MediaViewEvent::PlaybackBlocked).await
+                        }
+                        Err(err) => self.error(MediaViewError::PlayError(err)).await,
+                    }
+                }
+                MediaViewAudio::Disable => self.handler(MediaViewEvent::PlaybackBlocked).await,
+            },
+            Err(err) => self.error(MediaViewError::PlayError(err)).await,
+        }
+    }
+
+    async fn await_play(
+        &self,
+        play_result: Result<js_sys::Promise, JsValue>,
+    ) -> Result<(), MediaViewPlayError> {
+        use js_sys::Reflect;
+        use wasm_bindgen_futures::JsFuture;
+
+        let promise = play_result.map_err(MediaViewPlayError::PlayFailed)?;
+        match JsFuture::from(promise).await {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                let name = Reflect::get(&err, &JsValue::from_str("name"))
+                    .ok()
+                    .and_then(|name| name.as_string());
+                match name.as_deref() {
+                    Some("NotAllowedError") => Err(MediaViewPlayError::NotAllowed),
+                    _ => Err(MediaViewPlayError::PlayFailed(err)),
+                }
+            }
+        }
+    }
+
+    async fn handler(self: &Arc<Self>, ev: MediaViewEvent) {
+        self.handler.0(Arc::clone(self), ev).await
+    }
+
+    async fn error(self: &Arc<Self>, err: MediaViewError) {
+        self.handler(MediaViewEvent::Error(err)).await
+    }
+}
+
+impl PartialEq for MediaView {
+    fn eq(&self, other: &Self) -> bool {
+        self.video == other.video
+    }
+}
+
+impl Eq for MediaView {}
+
+#[derive(Debug)]
+pub enum MediaViewEvent {
+    PlaybackStarted,
+    /// The first video frame is presented, so the video is actually visible.
+    FirstFrameRendered {
+        width: u32,
+        height: u32,
+    },
+    /// The stream audio is first played unmuted.
+    FirstAudioPlayed,
+    AudioBlocked,
+    PlaybackBlocked,
+    Error(MediaViewError),
 }
 
 #[derive(Error, Debug)]
@@ -58,4 +483,38 @@ pub enum NewMediaViewError {
     DocumentIsUndefined,
     #[error("failed to create video element: {0:?}")]
     VideoElementCreateError(JsValue),
+    #[error("failed to create canvas element: {0:?}")]
+    CanvasElementCreateError(JsValue),
+}
+
+#[derive(Error, Debug)]
+pub enum MediaViewHudError {
+    #[error("canvas get context error: {0:?}")]
+    GetContextError(JsValue),
+    #[error("canvas 2d context is unavailable")]
+    ContextIsUnavailable,
+    #[error("canvas draw text error: {0:?}")]
+    DrawTextError(JsValue),
+}
+
+#[derive(Error, Debug)]
+pub enum MediaViewCaptionError {
+    #[error("caption cue create error: {0:?}")]
+    CueCreateError(JsValue),
+}
+
+#[derive(Error, Debug)]
+pub enum MediaViewError {
+    #[error(transparent)]
+    PlayError(MediaViewPlayError),
+    #[error("pause error: {0:?}")]
+    PauseError(JsValue),
+}
+
+#[derive(Error, Debug)]
+pub enum MediaViewPlayError {
+    #[error("playback is not allowed by the browser autoplay policy")]
+    NotAllowed,
+    #[error("play error: {0:?}")]
+    PlayFailed(JsValue),
 }
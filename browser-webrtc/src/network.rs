@@ -0,0 +1,75 @@
+use std::rc::Rc;
+
+use wasm_bindgen::closure::Closure;
+use web_sys::Event;
+
+/// Network change detected by a connection, online and ICE failures trigger an ICE restart.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NetworkChange {
+    Online,
+    Offline,
+    IceFailed,
+}
+
+/// Window `online` and `offline` event listeners, removed on drop.
+#[derive(Debug)]
+pub(crate) struct NetworkChangeListener {
+    js_online_handler: Closure<dyn FnMut(Event)>,
+    js_offline_handler: Closure<dyn FnMut(Event)>,
+}
+
+impl NetworkChangeListener {
+    pub(crate) fn new<F>(handler: F) -> Option<Self>
+    where
+        F: 'static + Fn(NetworkChange),
+    {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use web_sys::window;
+
+        let window = window()?;
+        let handler = Rc::new(handler);
+        let js_online_handler = {
+            let handler = Rc::clone(&handler);
+            closure_1(move |_: Event| handler(NetworkChange::Online))
+        };
+        let js_offline_handler = closure_1(move |_: Event| handler(NetworkChange::Offline));
+
+        window
+            .add_event_listener_with_callback("online", js_online_handler.as_ref().unchecked_ref())
+            .ok()?;
+        window
+            .add_event_listener_with_callback(
+                "offline",
+                js_offline_handler.as_ref().unchecked_ref(),
+            )
+            .ok()?;
+
+        Some(Self {
+            js_online_handler,
+            js_offline_handler,
+        })
+    }
+}
+
+impl Drop for NetworkChangeListener {
+    fn drop(&mut self) {
+        use wasm_bindgen::JsCast;
+        use web_sys::window;
+
+        if let Some(window) = window() {
+            let _: Option<()> = window
+                .remove_event_listener_with_callback(
+                    "online",
+                    self.js_online_handler.as_ref().unchecked_ref(),
+                )
+                .ok();
+            let _: Option<()> = window
+                .remove_event_listener_with_callback(
+                    "offline",
+                    self.js_offline_handler.as_ref().unchecked_ref(),
+                )
+                .ok();
+        }
+    }
+}
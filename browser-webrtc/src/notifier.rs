@@ -0,0 +1,144 @@
+use core::cell::Cell;
+
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{Notification, NotificationPermission};
+
+use crate::PageVisibility;
+
+/// Notable event the user can be notified about while the page is hidden.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum NotifierEvent {
+    /// A receiver connected to the sender channel.
+    ReceiverJoined {
+        channel: String,
+    },
+    ChatMessage {
+        text: String,
+    },
+}
+
+impl NotifierEvent {
+    fn title(&self) -> &'static str {
+        match self {
+            Self::ReceiverJoined { .. } => "Receiver joined",
+            Self::ChatMessage { .. } => "New message",
+        }
+    }
+
+    fn body(&self) -> &str {
+        match self {
+            Self::ReceiverJoined { channel } => channel,
+            Self::ChatMessage { text } => text,
+        }
+    }
+
+    /// Notifications with the same tag replace each other instead of piling up.
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::ReceiverJoined { .. } => "browser-webrtc.receiver-joined",
+            Self::ChatMessage { .. } => "browser-webrtc.chat-message",
+        }
+    }
+}
+
+/// Raises browser notifications for notable events while the page is hidden.
+///
+/// Notifications are disabled until enabled with `set_enabled`,
+/// which also asks the user for the permission if it is not yet decided.
+#[derive(Debug, Default)]
+pub struct Notifier {
+    is_enabled: Cell<bool>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_supported() -> bool {
+        use js_sys::Reflect;
+        use web_sys::window;
+
+        window()
+            .and_then(|window| Reflect::has(&window, &JsValue::from_str("Notification")).ok())
+            .unwrap_or(false)
+    }
+
+    /// Current notification permission, `None` if notifications are not supported.
+    pub fn permission() -> Option<NotificationPermission> {
+        if Self::is_supported() {
+            Some(Notification::permission())
+        } else {
+            None
+        }
+    }
+
+    /// Asks the user for the notification permission if it is not yet granted or denied.
+    pub async fn request_permission() -> Result<NotificationPermission, NotifierError> {
+        use wasm_bindgen_futures::JsFuture;
+
+        match Self::permission() {
+            None => return Err(NotifierError::NotificationIsNotSupported),
+            Some(NotificationPermission::Default) => {}
+            Some(permission) => return Ok(permission),
+        }
+        let permission = JsFuture::from(
+            Notification::request_permission().map_err(NotifierError::RequestPermissionError)?,
+        )
+        .await
+        .map_err(NotifierError::RequestPermissionError)?;
+        Ok(NotificationPermission::from_js_value(&permission)
+            .unwrap_or(NotificationPermission::Default))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled.get()
+    }
+
+    /// Enables notifications, returns the resulting permission.
+    ///
+    /// Browsers only show the permission prompt in response to a user gesture,
+    /// so this should be called from an input handler.
+    pub async fn set_enabled(
+        &self,
+        is_enabled: bool,
+    ) -> Result<Option<NotificationPermission>, NotifierError> {
+        self.is_enabled.set(is_enabled);
+        if is_enabled {
+            Self::request_permission().await.map(Some)
+        } else {
+            Ok(Self::permission())
+        }
+    }
+
+    /// Shows the notification if notifications are enabled and permitted
+    /// and the page is hidden, returns `None` otherwise.
+    pub fn notify(&self, ev: &NotifierEvent) -> Result<Option<Notification>, NotifierError> {
+        use web_sys::NotificationOptions;
+
+        if !self.is_enabled.get()
+            || Self::permission() != Some(NotificationPermission::Granted)
+            || !PageVisibility::current().is_hidden()
+        {
+            return Ok(None);
+        }
+
+        let options = NotificationOptions::new();
+        options.set_body(ev.body());
+        options.set_tag(ev.tag());
+        Notification::new_with_options(ev.title(), &options)
+            .map(Some)
+            .map_err(NotifierError::NewNotificationError)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum NotifierError {
+    #[error("Notification API is not supported")]
+    NotificationIsNotSupported,
+    #[error("notification permission request error: {0:?}")]
+    RequestPermissionError(JsValue),
+    #[error("new Notification error: {0:?}")]
+    NewNotificationError(JsValue),
+}
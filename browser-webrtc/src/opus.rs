@@ -0,0 +1,197 @@
+const OPUS_RTPMAP_CODEC: &str = "opus/48000";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OpusParameters {
+    pub stereo: bool,
+    pub max_average_bitrate: Option<u32>,
+    pub use_dtx: bool,
+}
+
+impl OpusParameters {
+    /// Stereo audio with the maximum Opus bitrate and without discontinuous transmission,
+    /// suitable for music streaming.
+    pub fn high_quality() -> Self {
+        Self {
+            stereo: true,
+            max_average_bitrate: Some(510_000),
+            use_dtx: false,
+        }
+    }
+
+    fn fmtp_parameters(&self) -> Vec<(&'static str, String)> {
+        let flag = |value: bool| if value { "1" } else { "0" }.to_owned();
+
+        let mut parameters = vec![
+            ("stereo", flag(self.stereo)),
+            ("sprop-stereo", flag(self.stereo)),
+            ("usedtx", flag(self.use_dtx)),
+        ];
+        if let Some(max_average_bitrate) = self.max_average_bitrate {
+            parameters.push(("maxaveragebitrate", max_average_bitrate.to_string()));
+        }
+        parameters
+    }
+
+    /// Returns the session description with these parameters set
+    /// in the `a=fmtp` lines of all Opus payload types.
+    pub fn apply_to_sdp(&self, sdp: &str) -> String {
+        let payload_types: Vec<&str> = sdp
+            .lines()
+            .filter_map(|line| line.strip_prefix("a=rtpmap:"))
+            .filter_map(|rtpmap| {
+                let (payload_type, codec) = rtpmap.split_once(' ')?;
+                if codec.to_ascii_lowercase().starts_with(OPUS_RTPMAP_CODEC) {
+                    Some(payload_type)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if payload_types.is_empty() {
+            return sdp.to_owned();
+        }
+
+        let mut lines = Vec::new();
+        for line in sdp.lines() {
+            let fmtp_payload_type = line
+                .strip_prefix("a=fmtp:")
+                .and_then(|fmtp| fmtp.split(' ').next());
+            let rtpmap_payload_type = line
+                .strip_prefix("a=rtpmap:")
+                .and_then(|rtpmap| rtpmap.split(' ').next());
+
+            match (fmtp_payload_type, rtpmap_payload_type) {
+                (Some(payload_type), _) if payload_types.contains(&payload_type) => {
+                    lines.push(self.apply_to_fmtp(line, payload_type));
+                }
+                (_, Some(payload_type)) if payload_types.contains(&payload_type) => {
+                    lines.push(line.to_owned());
+                    let has_fmtp = sdp
+                        .lines()
+                        .any(|line| line.starts_with(&format!("a=fmtp:{} ", payload_type)));
+                    if !has_fmtp {
+                        lines.push(
+                            self.apply_to_fmtp(&format!("a=fmtp:{} ", payload_type), payload_type),
+                        );
+                    }
+                }
+                _ => lines.push(line.to_owned()),
+            }
+        }
+
+        let mut sdp_result = lines.join("\r\n");
+        sdp_result.push_str("\r\n");
+        sdp_result
+    }
+
+    fn apply_to_fmtp(&self, line: &str, payload_type: &str) -> String {
+        let prefix = format!("a=fmtp:{} ", payload_type);
+        let parameters = line.strip_prefix(&prefix).unwrap_or_default();
+        let new_parameters = self.fmtp_parameters();
+
+        let mut parameters: Vec<String> = parameters
+            .split(';')
+            .map(str::trim)
+            .filter(|parameter| !parameter.is_empty())
+            .filter(|parameter| {
+                let name = parameter.split('=').next().unwrap_or_default();
+                !new_parameters.iter().any(|(new_name, _)| *new_name == name)
+            })
+            .map(str::to_owned)
+            .collect();
+        parameters.extend(
+            new_parameters
+                .into_iter()
+                .map(|(name, value)| format!("{}={}", name, value)),
+        );
+
+        format!("{}{}", prefix, parameters.join(";"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpusParameters;
+
+    const PARAMETERS: OpusParameters = OpusParameters {
+        stereo: true,
+        max_average_bitrate: Some(128_000),
+        use_dtx: false,
+    };
+    const APPLIED: &str = "stereo=1;sprop-stereo=1;usedtx=0;maxaveragebitrate=128000";
+
+    fn sdp(lines: &[&str]) -> String {
+        lines.iter().map(|line| format!("{}\r\n", line)).collect()
+    }
+
+    #[test]
+    fn existing_opus_fmtp_is_updated() {
+        let sdp = sdp(&[
+            "m=audio 9 UDP/TLS/RTP/SAVPF 111 0",
+            "a=rtpmap:111 opus/48000/2",
+            "a=fmtp:111 minptime=10;stereo=0;useinbandfec=1",
+            "a=rtpmap:0 PCMU/8000",
+        ]);
+        assert_eq!(
+            PARAMETERS.apply_to_sdp(&sdp),
+            format!(
+                "m=audio 9 UDP/TLS/RTP/SAVPF 111 0\r\n\
+                 a=rtpmap:111 opus/48000/2\r\n\
+                 a=fmtp:111 minptime=10;useinbandfec=1;{}\r\n\
+                 a=rtpmap:0 PCMU/8000\r\n",
+                APPLIED
+            )
+        );
+    }
+
+    #[test]
+    fn missing_opus_fmtp_is_added_after_rtpmap() {
+        let sdp = sdp(&[
+            "m=audio 9 UDP/TLS/RTP/SAVPF 111",
+            "a=rtpmap:111 opus/48000/2",
+        ]);
+        assert_eq!(
+            PARAMETERS.apply_to_sdp(&sdp),
+            format!(
+                "m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+                 a=rtpmap:111 opus/48000/2\r\n\
+                 a=fmtp:111 {}\r\n",
+                APPLIED
+            )
+        );
+    }
+
+    #[test]
+    fn all_opus_payload_types_are_updated() {
+        let sdp = sdp(&[
+            "m=audio 9 UDP/TLS/RTP/SAVPF 111 109",
+            "a=rtpmap:111 opus/48000/2",
+            "a=fmtp:111 minptime=10",
+            "a=rtpmap:109 OPUS/48000/2",
+        ]);
+        assert_eq!(
+            PARAMETERS.apply_to_sdp(&sdp),
+            format!(
+                "m=audio 9 UDP/TLS/RTP/SAVPF 111 109\r\n\
+                 a=rtpmap:111 opus/48000/2\r\n\
+                 a=fmtp:111 minptime=10;{0}\r\n\
+                 a=rtpmap:109 OPUS/48000/2\r\n\
+                 a=fmtp:109 {0}\r\n",
+                APPLIED
+            )
+        );
+    }
+
+    #[test]
+    fn sdp_without_opus_is_unchanged() {
+        let sdp = sdp(&[
+            "v=0",
+            "m=audio 9 UDP/TLS/RTP/SAVPF 0",
+            "a=rtpmap:0 PCMU/8000",
+            "a=fmtp:0 foo=1",
+        ]);
+        assert_eq!(PARAMETERS.apply_to_sdp(&sdp), sdp);
+        let sdp = sdp.trim_end();
+        assert_eq!(PARAMETERS.apply_to_sdp(sdp), sdp);
+    }
+}
@@ -0,0 +1,389 @@
+use core::cell::{Cell, RefCell};
+
+use async_std::sync::Arc;
+use signaling_protocol::{IceCandidate, PeerCapabilities};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::convert::FromWasmAbi;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    Event, RtcConfiguration, RtcIceConnectionState, RtcIceGatheringState, RtcPeerConnection,
+    RtcPeerConnectionIceEvent, RtcSignalingState,
+};
+use webrtc_core::{
+    CandidatePreference, IceCandidateRecovery, IceCandidateRecoveryAction, LocalCandidates,
+    NegotiationState, RemoteCandidates,
+};
+
+use crate::ice_timeline::IceTimelineRecorder;
+use crate::network::NetworkChangeListener;
+use crate::visibility::VisibilityChangeListener;
+use crate::{BrowserRtcEngine, IceTimeline, NetworkChange, PageVisibility};
+
+/// Peer connection events forwarded to the sender or receiver.
+#[derive(Debug)]
+pub(crate) enum PeerConnectionEvent {
+    LocalIceCandidate(IceCandidate),
+    /// All local candidates are gathered.
+    LocalIceCandidatesGathered,
+    NegotiationNeeded,
+    IceConnectionStateChange,
+    IceGatheringStateChange,
+    SignalingStateChange,
+    NetworkChanged(NetworkChange),
+    VisibilityChanged(PageVisibility),
+    /// A remote candidate could not be added even after all retries.
+    IceCandidateRetriesExhausted {
+        action: IceCandidateRecoveryAction,
+        err: JsValue,
+    },
+}
+
+/// Role-specific layer on top of the shared peer connection, i.e. `Sender` or `Receiver`.
+pub(crate) trait PeerConnectionRole: 'static {
+    fn core(&self) -> &PeerConnectionCore;
+
+    async fn on_peer_connection_event(self: &Arc<Self>, ev: PeerConnectionEvent);
+}
+
+pub(crate) type JsHandler<T> = RefCell<Option<Closure<dyn FnMut(T)>>>;
+
+/// Owns the `RtcPeerConnection`, its event handlers and the candidates handling.
+#[derive(Debug)]
+pub(crate) struct PeerConnectionCore {
+    js_connection: RtcPeerConnection,
+    engine: BrowserRtcEngine,
+    local_candidates: LocalCandidates,
+    candidate_preference: Cell<CandidatePreference>,
+    peer_capabilities: Cell<PeerCapabilities>,
+    remote_candidates: RemoteCandidates,
+    ice_candidate_recovery: IceCandidateRecovery,
+    ice_timeline: IceTimelineRecorder,
+    js_ice_candidate_handler: JsHandler<RtcPeerConnectionIceEvent>,
+    js_negotiation_needed_handler: JsHandler<Event>,
+    js_ice_connection_state_change_handler: JsHandler<Event>,
+    js_ice_gathering_state_change_handler: JsHandler<Event>,
+    js_signaling_state_change_handler: JsHandler<Event>,
+    network_change_listener: RefCell<Option<NetworkChangeListener>>,
+    visibility_change_listener: RefCell<Option<VisibilityChangeListener>>,
+}
+
+impl PeerConnectionCore {
+    pub(crate) fn new(rtc_configuration: Option<RtcConfiguration>) -> Result<Self, JsValue> {
+        let js_connection = match rtc_configuration {
+            Some(config) => RtcPeerConnection::new_with_configuration(&config),
+            None => RtcPeerConnection::new(),
+        }?;
+
+        Ok(Self {
+            engine: BrowserRtcEngine::new(js_connection.clone()),
+            js_connection,
+            local_candidates: LocalCandidates::default(),
+            candidate_preference: Cell::new(CandidatePreference::default()),
+            peer_capabilities: Cell::new(PeerCapabilities::default()),
+            remote_candidates: RemoteCandidates::default(),
+            ice_candidate_recovery: IceCandidateRecovery::default(),
+            ice_timeline: IceTimelineRecorder::new(),
+            js_ice_candidate_handler: RefCell::new(None),
+            js_negotiation_needed_handler: RefCell::new(None),
+            js_ice_connection_state_change_handler: RefCell::new(None),
+            js_ice_gathering_state_change_handler: RefCell::new(None),
+            js_signaling_state_change_handler: RefCell::new(None),
+            network_change_listener: RefCell::new(None),
+            visibility_change_listener: RefCell::new(None),
+        })
+    }
+
+    /// Forwards the connection, network and visibility events to the role,
+    /// the negotiation needed handler is initialized separately.
+    pub(crate) fn init_handlers<R: PeerConnectionRole>(&self, role: &Arc<R>) {
+        use wasm_bindgen::JsCast;
+
+        let js_ice_candidate_handler = event_closure(role, |ev: RtcPeerConnectionIceEvent| {
+            // The empty candidate marks the end of the gathering.
+            ev.candidate()
+                .map(|candidate| match candidate.candidate().as_ref() {
+                    "" => PeerConnectionEvent::LocalIceCandidatesGathered,
+                    candidate_str => PeerConnectionEvent::LocalIceCandidate(IceCandidate {
+                        candidate: candidate_str.to_owned(),
+                        sdp_mid: candidate.sdp_mid(),
+                        sdp_m_line_index: candidate.sdp_m_line_index(),
+                    }),
+                })
+        });
+        self.js_connection
+            .set_onicecandidate(Some(js_ice_candidate_handler.as_ref().unchecked_ref()));
+        let prev_handler = self
+            .js_ice_candidate_handler
+            .replace(Some(js_ice_candidate_handler));
+        debug_assert!(prev_handler.is_none());
+
+        let js_ice_connection_state_change_handler = event_closure(role, |_: Event| {
+            Some(PeerConnectionEvent::IceConnectionStateChange)
+        });
+        self.js_connection.set_oniceconnectionstatechange(Some(
+            js_ice_connection_state_change_handler
+                .as_ref()
+                .unchecked_ref(),
+        ));
+        let prev_handler = self
+            .js_ice_connection_state_change_handler
+            .replace(Some(js_ice_connection_state_change_handler));
+        debug_assert!(prev_handler.is_none());
+
+        let js_ice_gathering_state_change_handler = event_closure(role, |_: Event| {
+            Some(PeerConnectionEvent::IceGatheringStateChange)
+        });
+        self.js_connection.set_onicegatheringstatechange(Some(
+            js_ice_gathering_state_change_handler
+                .as_ref()
+                .unchecked_ref(),
+        ));
+        let prev_handler = self
+            .js_ice_gathering_state_change_handler
+            .replace(Some(js_ice_gathering_state_change_handler));
+        debug_assert!(prev_handler.is_none());
+
+        let js_signaling_state_change_handler = event_closure(role, |_: Event| {
+            Some(PeerConnectionEvent::SignalingStateChange)
+        });
+        self.js_connection.set_onsignalingstatechange(Some(
+            js_signaling_state_change_handler.as_ref().unchecked_ref(),
+        ));
+        let prev_handler = self
+            .js_signaling_state_change_handler
+            .replace(Some(js_signaling_state_change_handler));
+        debug_assert!(prev_handler.is_none());
+
+        let network_change_listener = {
+            let role_weak = Arc::downgrade(role);
+            NetworkChangeListener::new(move |change| {
+                forward_event(&role_weak, PeerConnectionEvent::NetworkChanged(change))
+            })
+        };
+        let prev_listener = self
+            .network_change_listener
+            .replace(network_change_listener);
+        debug_assert!(prev_listener.is_none());
+
+        let visibility_change_listener = {
+            let role_weak = Arc::downgrade(role);
+            VisibilityChangeListener::new(move |visibility| {
+                forward_event(
+                    &role_weak,
+                    PeerConnectionEvent::VisibilityChanged(visibility),
+                )
+            })
+        };
+        let prev_listener = self
+            .visibility_change_listener
+            .replace(visibility_change_listener);
+        debug_assert!(prev_listener.is_none());
+    }
+
+    /// Initialized after the first negotiation, later calls do nothing.
+    pub(crate) fn init_negotiation_needed_handler<R: PeerConnectionRole>(&self, role: &Arc<R>) {
+        use wasm_bindgen::JsCast;
+
+        if self.js_negotiation_needed_handler.borrow().is_some() {
+            return;
+        }
+
+        let js_negotiation_needed_handler = event_closure(role, |_: Event| {
+            Some(PeerConnectionEvent::NegotiationNeeded)
+        });
+        self.js_connection
+            .set_onnegotiationneeded(Some(js_negotiation_needed_handler.as_ref().unchecked_ref()));
+        let prev_handler = self
+            .js_negotiation_needed_handler
+            .replace(Some(js_negotiation_needed_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    pub(crate) fn js_connection(&self) -> &RtcPeerConnection {
+        &self.js_connection
+    }
+
+    pub(crate) fn engine(&self) -> &BrowserRtcEngine {
+        &self.engine
+    }
+
+    /// Local candidates held until the server confirms the channel.
+    pub(crate) fn local_candidates(&self) -> &LocalCandidates {
+        &self.local_candidates
+    }
+
+    pub(crate) fn candidate_preference(&self) -> CandidatePreference {
+        self.candidate_preference.get()
+    }
+
+    /// Returns the capabilities advertised to the remote peer with the preference.
+    pub(crate) fn set_candidate_preference(
+        &self,
+        preference: CandidatePreference,
+    ) -> PeerCapabilities {
+        self.candidate_preference.set(preference);
+        PeerCapabilities {
+            is_on_lan: preference.is_on_lan,
+        }
+    }
+
+    pub(crate) fn on_peer_capabilities(&self, capabilities: PeerCapabilities) {
+        self.peer_capabilities.set(capabilities);
+    }
+
+    /// Applies the candidate preference to the local candidate before it is sent,
+    /// returns `None` if it is not sent.
+    pub(crate) fn prefer_local_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Option<IceCandidate> {
+        self.candidate_preference
+            .get()
+            .apply(ice_candidate, self.peer_capabilities.get().is_on_lan)
+    }
+
+    pub(crate) fn ice_connection_state(&self) -> RtcIceConnectionState {
+        self.js_connection.ice_connection_state()
+    }
+
+    pub(crate) fn ice_timeline(&self) -> IceTimeline {
+        self.ice_timeline.timeline()
+    }
+
+    /// Records the candidate pairs progress from the connection stats.
+    pub(crate) fn update_ice_timeline(&self, stats: &[JsValue]) {
+        self.ice_timeline.update(stats)
+    }
+
+    pub(crate) fn ice_gathering_state(&self) -> RtcIceGatheringState {
+        self.js_connection.ice_gathering_state()
+    }
+
+    pub(crate) fn signaling_state(&self) -> RtcSignalingState {
+        self.js_connection.signaling_state()
+    }
+
+    pub(crate) fn negotiation_state(&self) -> NegotiationState {
+        match self.js_connection.signaling_state() {
+            RtcSignalingState::Stable => NegotiationState::Stable,
+            RtcSignalingState::HaveLocalOffer | RtcSignalingState::HaveRemotePranswer => {
+                NegotiationState::HaveLocalOffer
+            }
+            RtcSignalingState::Closed => NegotiationState::Closed,
+            _ => NegotiationState::HaveRemoteOffer,
+        }
+    }
+
+    /// Adds the remote candidate, it is buffered until the remote description is applied.
+    pub(crate) async fn on_remote_ice_candidate<R: PeerConnectionRole>(
+        &self,
+        role: &Arc<R>,
+        ice_candidate: IceCandidate,
+    ) {
+        self.ice_timeline.on_remote_ice_candidate(&ice_candidate);
+        if let Some(ice_candidate) = self.remote_candidates.on_received(ice_candidate) {
+            self.add_ice_candidate(role, ice_candidate).await;
+        }
+    }
+
+    /// Adds the remote candidates received before the remote description,
+    /// called once the remote description is applied.
+    pub(crate) async fn on_remote_description<R: PeerConnectionRole>(&self, role: &Arc<R>) {
+        self.ice_candidate_recovery.reset();
+        for ice_candidate in self.remote_candidates.on_remote_description() {
+            self.add_ice_candidate(role, ice_candidate).await;
+        }
+    }
+
+    /// Adds the remote candidate, it is recovered in the background if adding fails.
+    async fn add_ice_candidate<R: PeerConnectionRole>(
+        &self,
+        role: &Arc<R>,
+        ice_candidate: IceCandidate,
+    ) {
+        use wasm_bindgen_futures::spawn_local;
+        use webrtc_core::RtcEngine;
+
+        let sequence = self.ice_candidate_recovery.on_received();
+        if let Err(err) = self.engine.add_ice_candidate(&ice_candidate).await {
+            log::warn!("add ice candidate error, retrying: {:?}", err);
+            let role = Arc::clone(role);
+            spawn_local(async move {
+                role.core()
+                    .recover_ice_candidate(&role, ice_candidate, sequence, err)
+                    .await
+            });
+        }
+    }
+
+    /// Retries adding the remote candidate once the connection state settles,
+    /// the role is notified when the retries are exhausted.
+    async fn recover_ice_candidate<R: PeerConnectionRole>(
+        &self,
+        role: &Arc<R>,
+        ice_candidate: IceCandidate,
+        sequence: u32,
+        mut err: JsValue,
+    ) {
+        use crate::sleep;
+        use webrtc_core::{RtcEngine, ICE_CANDIDATE_RETRY_INTERVAL, MAX_ICE_CANDIDATE_RETRIES};
+
+        for _ in 0..MAX_ICE_CANDIDATE_RETRIES {
+            sleep(ICE_CANDIDATE_RETRY_INTERVAL).await;
+            if self.js_connection.remote_description().is_none() {
+                continue;
+            }
+            match self.engine.add_ice_candidate(&ice_candidate).await {
+                Ok(()) => return,
+                Err(retry_err) => err = retry_err,
+            }
+        }
+
+        let action = self.ice_candidate_recovery.on_retries_exhausted(sequence);
+        role.on_peer_connection_event(PeerConnectionEvent::IceCandidateRetriesExhausted {
+            action,
+            err,
+        })
+        .await
+    }
+
+    /// Detaches the handlers and closes the connection.
+    pub(crate) fn close(&self) {
+        self.js_connection.set_onnegotiationneeded(None);
+        self.js_connection.set_onicecandidate(None);
+        drop(self.network_change_listener.take());
+        drop(self.visibility_change_listener.take());
+        self.js_connection.close();
+    }
+}
+
+fn event_closure<R: PeerConnectionRole, T: 'static + FromWasmAbi>(
+    role: &Arc<R>,
+    to_event: fn(T) -> Option<PeerConnectionEvent>,
+) -> Closure<dyn FnMut(T)> {
+    use crate::closure_1;
+
+    let role_weak = Arc::downgrade(role);
+    closure_1(move |ev: T| {
+        if let Some(ev) = to_event(ev) {
+            forward_event(&role_weak, ev)
+        }
+    })
+}
+
+fn forward_event<R: PeerConnectionRole>(
+    role_weak: &async_std::sync::Weak<R>,
+    ev: PeerConnectionEvent,
+) {
+    use wasm_bindgen_futures::spawn_local;
+
+    if let Some(role) = role_weak.upgrade() {
+        let core = role.core();
+        core.ice_timeline.on_event(&ev);
+        if let PeerConnectionEvent::IceConnectionStateChange = ev {
+            core.ice_timeline
+                .on_ice_connection_state(core.ice_connection_state());
+        }
+        spawn_local(async move { role.on_peer_connection_event(ev).await });
+    }
+}
@@ -0,0 +1,315 @@
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use wasm_bindgen::closure::Closure;
+use web_sys::{Event, MessageEvent, RtcDataChannel};
+
+use crate::captions::CAPTION_CUE_HEADER;
+use crate::{CaptionCue, CaptionSendError};
+
+/// Label of the data channel reserved for the peer state exchange,
+/// it is not reported as a regular data channel.
+pub const CONTROL_DATA_CHANNEL_LABEL: &str = "browser-webrtc-control";
+
+/// Local state changes made within the interval are sent as a single update.
+const PEER_STATE_DEBOUNCE_INTERVAL: Duration = Duration::from_millis(250);
+
+const IS_TYPING_FLAG: u8 = 0x01;
+const IS_AWAY_FLAG: u8 = 0x02;
+const IS_MUTED_FLAG: u8 = 0x04;
+const IS_SCREEN_SHARING_FLAG: u8 = 0x08;
+
+/// Header of the text message with the media stream labels,
+/// followed by a `stream_id\tlabel` line per labeled stream.
+const STREAM_LABELS_HEADER: &str = "stream-labels";
+
+/// Header of the text message with the id of the media stream removed by the sender.
+const STREAM_REMOVED_HEADER: &str = "stream-removed";
+
+/// Header of the text message with `1` if the sender switched to audio only or `0` otherwise.
+const AUDIO_ONLY_HEADER: &str = "audio-only";
+
+/// Peer state flags exchanged over the control data channel.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct PeerState {
+    pub is_typing: bool,
+    pub is_away: bool,
+    pub is_muted: bool,
+    pub is_screen_sharing: bool,
+}
+
+impl PeerState {
+    fn to_flags(self) -> u8 {
+        let mut flags = 0;
+        for (is_set, flag) in [
+            (self.is_typing, IS_TYPING_FLAG),
+            (self.is_away, IS_AWAY_FLAG),
+            (self.is_muted, IS_MUTED_FLAG),
+            (self.is_screen_sharing, IS_SCREEN_SHARING_FLAG),
+        ] {
+            if is_set {
+                flags |= flag;
+            }
+        }
+        flags
+    }
+
+    /// Unknown flags are ignored for compatibility with newer peers.
+    fn from_flags(flags: u8) -> Self {
+        Self {
+            is_typing: flags & IS_TYPING_FLAG != 0,
+            is_away: flags & IS_AWAY_FLAG != 0,
+            is_muted: flags & IS_MUTED_FLAG != 0,
+            is_screen_sharing: flags & IS_SCREEN_SHARING_FLAG != 0,
+        }
+    }
+}
+
+/// Message received over the control data channel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ControlMessage {
+    PeerState(PeerState),
+    /// Labels of the sent media streams by the stream id.
+    StreamLabels(HashMap<String, String>),
+    /// Id of the media stream removed by the sender.
+    StreamRemoved(String),
+    /// The sender stopped or restored its video, see `AudioFallbackPolicy`.
+    AudioOnly(bool),
+    Caption(CaptionCue),
+}
+
+/// Encodes the stream labels as a text message,
+/// peer states are binary so older peers ignore it.
+fn encode_stream_labels(labels: &HashMap<String, String>) -> String {
+    let mut labels: Vec<_> = labels.iter().collect();
+    labels.sort();
+    labels
+        .into_iter()
+        .fold(STREAM_LABELS_HEADER.to_owned(), |message, (id, label)| {
+            let label = label.replace(['\t', '\n'], " ");
+            message + "\n" + id + "\t" + &label
+        })
+}
+
+fn decode_text_message(message: &str) -> Option<ControlMessage> {
+    let mut lines = message.split('\n');
+    match lines.next()? {
+        STREAM_LABELS_HEADER => Some(ControlMessage::StreamLabels(
+            lines
+                .filter_map(|line| {
+                    let (id, label) = line.split_once('\t')?;
+                    Some((id.to_owned(), label.to_owned()))
+                })
+                .collect(),
+        )),
+        STREAM_REMOVED_HEADER => Some(ControlMessage::StreamRemoved(lines.next()?.to_owned())),
+        AUDIO_ONLY_HEADER => match lines.next()? {
+            "0" => Some(ControlMessage::AudioOnly(false)),
+            "1" => Some(ControlMessage::AudioOnly(true)),
+            _ => None,
+        },
+        CAPTION_CUE_HEADER => CaptionCue::decode(lines).map(ControlMessage::Caption),
+        _ => None,
+    }
+}
+
+/// Exchanges peer states and stream labels over the control data channel, closed on drop.
+#[derive(Debug)]
+pub(crate) struct ControlChannel {
+    js_channel: RtcDataChannel,
+    local_state: Cell<PeerState>,
+    sent_state: Cell<Option<PeerState>>,
+    stream_labels: RefCell<Option<String>>,
+    sent_stream_labels: RefCell<Option<String>>,
+    is_audio_only: Cell<bool>,
+    sent_is_audio_only: Cell<bool>,
+    is_send_scheduled: Cell<bool>,
+    js_open_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+    js_message_handler: RefCell<Option<Closure<dyn FnMut(MessageEvent)>>>,
+}
+
+impl ControlChannel {
+    /// Creates the control channel of the connection, the handler is called on remote
+    /// peer state and stream labels changes.
+    pub(crate) fn new<F>(js_channel: RtcDataChannel, handler: F) -> Rc<Self>
+    where
+        F: 'static + Fn(ControlMessage),
+    {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use web_sys::RtcDataChannelType;
+
+        js_channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+
+        let control_channel = Rc::new(Self {
+            js_channel,
+            local_state: Cell::new(PeerState::default()),
+            sent_state: Cell::new(None),
+            stream_labels: RefCell::new(None),
+            sent_stream_labels: RefCell::new(None),
+            is_audio_only: Cell::new(false),
+            sent_is_audio_only: Cell::new(false),
+            is_send_scheduled: Cell::new(false),
+            js_open_handler: RefCell::new(None),
+            js_message_handler: RefCell::new(None),
+        });
+
+        let js_open_handler = {
+            let self_weak = Rc::downgrade(&control_channel);
+            closure_1(move |_: Event| {
+                if let Some(self_rc) = self_weak.upgrade() {
+                    self_rc.flush()
+                }
+            })
+        };
+        let js_message_handler = closure_1(move |ev: MessageEvent| {
+            use js_sys::{ArrayBuffer, Uint8Array};
+
+            let data = ev.data();
+            if let Some(message) = data.as_string() {
+                match decode_text_message(&message) {
+                    Some(message) => handler(message),
+                    None => log::warn!("unknown control message received: {:?}", message),
+                }
+                return;
+            }
+            match data.dyn_into::<ArrayBuffer>() {
+                Ok(array_buffer) => match Uint8Array::new(&array_buffer).to_vec().first() {
+                    Some(&flags) => {
+                        handler(ControlMessage::PeerState(PeerState::from_flags(flags)))
+                    }
+                    None => log::warn!("empty peer state received"),
+                },
+                Err(data) => log::warn!("non-array peer state received: {:?}", data),
+            }
+        });
+        control_channel
+            .js_channel
+            .set_onopen(Some(js_open_handler.as_ref().unchecked_ref()));
+        control_channel
+            .js_channel
+            .set_onmessage(Some(js_message_handler.as_ref().unchecked_ref()));
+        let _: Option<_> = control_channel
+            .js_open_handler
+            .replace(Some(js_open_handler));
+        let _: Option<_> = control_channel
+            .js_message_handler
+            .replace(Some(js_message_handler));
+
+        control_channel
+    }
+
+    pub(crate) fn local_state(&self) -> PeerState {
+        self.local_state.get()
+    }
+
+    /// Sets the local peer state, it is sent to the remote peer after the debounce interval.
+    pub(crate) fn set_local_state(self: &Rc<Self>, state: PeerState) {
+        use crate::sleep;
+        use wasm_bindgen_futures::spawn_local;
+
+        self.local_state.set(state);
+        if self.is_send_scheduled.replace(true) {
+            return;
+        }
+        let self_weak: Weak<Self> = Rc::downgrade(self);
+        spawn_local(async move {
+            sleep(PEER_STATE_DEBOUNCE_INTERVAL).await;
+            if let Some(self_rc) = self_weak.upgrade() {
+                self_rc.is_send_scheduled.set(false);
+                self_rc.flush();
+            }
+        });
+    }
+
+    /// Sets the labels of the sent media streams, they are sent once the channel is open.
+    pub(crate) fn set_stream_labels(&self, labels: &HashMap<String, String>) {
+        let _: Option<_> = self
+            .stream_labels
+            .replace(Some(encode_stream_labels(labels)));
+        self.flush();
+    }
+
+    /// Sets the audio only state of the sender, it is sent once the channel is open.
+    pub(crate) fn set_audio_only(&self, is_audio_only: bool) {
+        self.is_audio_only.set(is_audio_only);
+        self.flush();
+    }
+
+    /// Notifies the receiver that the stream is removed, so that it is not
+    /// shown until the renegotiation ends, skipped if the channel is not open yet.
+    pub(crate) fn send_stream_removed(&self, stream_id: &str) {
+        use web_sys::RtcDataChannelState;
+
+        if self.js_channel.ready_state() != RtcDataChannelState::Open {
+            return;
+        }
+        let message = format!("{}\n{}", STREAM_REMOVED_HEADER, stream_id);
+        if let Err(err) = self.js_channel.send_with_str(&message) {
+            log::warn!("stream removed send error: {:?}", err);
+        }
+    }
+
+    /// Sends the caption cue, the cues are not queued until the channel is open.
+    pub(crate) fn send_caption(&self, cue: &CaptionCue) -> Result<(), CaptionSendError> {
+        use web_sys::RtcDataChannelState;
+
+        if self.js_channel.ready_state() != RtcDataChannelState::Open {
+            return Err(CaptionSendError::ChannelIsNotOpen);
+        }
+        self.js_channel
+            .send_with_str(&cue.encode())
+            .map_err(CaptionSendError::SendError)
+    }
+
+    /// Sends the local peer state, the stream labels and the audio only state
+    /// if they have changed since they were sent last time.
+    fn flush(&self) {
+        use web_sys::RtcDataChannelState;
+
+        if self.js_channel.ready_state() != RtcDataChannelState::Open {
+            return;
+        }
+
+        let state = self.local_state.get();
+        if self.sent_state.get() != Some(state) {
+            match self.js_channel.send_with_u8_array(&[state.to_flags()]) {
+                Ok(()) => self.sent_state.set(Some(state)),
+                Err(err) => log::warn!("peer state send error: {:?}", err),
+            }
+        }
+
+        let stream_labels = self.stream_labels.borrow().clone();
+        if let Some(stream_labels) = stream_labels {
+            if self.sent_stream_labels.borrow().as_ref() != Some(&stream_labels) {
+                match self.js_channel.send_with_str(&stream_labels) {
+                    Ok(()) => {
+                        let _: Option<_> = self.sent_stream_labels.replace(Some(stream_labels));
+                    }
+                    Err(err) => log::warn!("stream labels send error: {:?}", err),
+                }
+            }
+        }
+
+        let is_audio_only = self.is_audio_only.get();
+        if self.sent_is_audio_only.get() != is_audio_only {
+            let message = format!("{}\n{}", AUDIO_ONLY_HEADER, u8::from(is_audio_only));
+            match self.js_channel.send_with_str(&message) {
+                Ok(()) => self.sent_is_audio_only.set(is_audio_only),
+                Err(err) => log::warn!("audio only state send error: {:?}", err),
+            }
+        }
+    }
+}
+
+impl Drop for ControlChannel {
+    fn drop(&mut self) {
+        log::trace!("browser_webrtc::ControlChannel::drop");
+
+        self.js_channel.set_onopen(None);
+        self.js_channel.set_onmessage(None);
+        self.js_channel.close();
+    }
+}
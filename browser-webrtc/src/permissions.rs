@@ -0,0 +1,185 @@
+use core::cell::RefCell;
+
+use async_std::sync::Arc;
+use thiserror::Error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+use web_sys::{Event, PermissionState, PermissionStatus};
+
+use crate::{BoxAsyncFn2, BoxAsyncFn2Wrapper};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MediaPermissionKind {
+    Camera,
+    Microphone,
+}
+
+impl MediaPermissionKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Camera => "camera",
+            Self::Microphone => "microphone",
+        }
+    }
+
+    pub fn track_kind(self) -> &'static str {
+        match self {
+            Self::Camera => "video",
+            Self::Microphone => "audio",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MediaPermissions {
+    handler: BoxAsyncFn2Wrapper<Arc<MediaPermissions>, MediaPermissionsEvent, ()>,
+    js_camera_status: Option<PermissionStatus>,
+    js_microphone_status: Option<PermissionStatus>,
+    js_camera_change_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+    js_microphone_change_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+}
+
+impl MediaPermissions {
+    pub async fn query(
+        handler: BoxAsyncFn2<Arc<Self>, MediaPermissionsEvent, ()>,
+    ) -> Result<Arc<Self>, MediaPermissionsError> {
+        log::trace!("browser_webrtc::MediaPermissions::query");
+
+        use web_sys::window;
+
+        let window = window().ok_or(MediaPermissionsError::WindowIsUndefined)?;
+        let permissions = window
+            .navigator()
+            .permissions()
+            .map_err(MediaPermissionsError::PermissionsIsUndefined)?;
+
+        // Browsers that do not know the permission name reject the query,
+        // in that case the state is reported as unknown.
+        let js_camera_status = Self::query_status(&permissions, MediaPermissionKind::Camera).await;
+        let js_microphone_status =
+            Self::query_status(&permissions, MediaPermissionKind::Microphone).await;
+
+        let media_permissions = Arc::new(Self {
+            handler: BoxAsyncFn2Wrapper(handler),
+            js_camera_status,
+            js_microphone_status,
+            js_camera_change_handler: RefCell::new(None),
+            js_microphone_change_handler: RefCell::new(None),
+        });
+
+        media_permissions.init_change_handler(MediaPermissionKind::Camera);
+        media_permissions.init_change_handler(MediaPermissionKind::Microphone);
+
+        Ok(media_permissions)
+    }
+
+    async fn query_status(
+        permissions: &web_sys::Permissions,
+        kind: MediaPermissionKind,
+    ) -> Option<PermissionStatus> {
+        use js_sys::{Object, Reflect};
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        let descriptor = Object::new();
+        let _: bool = Reflect::set(
+            &descriptor,
+            &JsValue::from_str("name"),
+            &JsValue::from_str(kind.name()),
+        )
+        .ok()?;
+        let status = JsFuture::from(permissions.query(&descriptor).ok()?)
+            .await
+            .ok()?;
+        status.dyn_into().ok()
+    }
+
+    fn status(&self, kind: MediaPermissionKind) -> Option<&PermissionStatus> {
+        match kind {
+            MediaPermissionKind::Camera => self.js_camera_status.as_ref(),
+            MediaPermissionKind::Microphone => self.js_microphone_status.as_ref(),
+        }
+    }
+
+    fn change_handler(
+        &self,
+        kind: MediaPermissionKind,
+    ) -> &RefCell<Option<Closure<dyn FnMut(Event)>>> {
+        match kind {
+            MediaPermissionKind::Camera => &self.js_camera_change_handler,
+            MediaPermissionKind::Microphone => &self.js_microphone_change_handler,
+        }
+    }
+
+    fn init_change_handler(self: &Arc<Self>, kind: MediaPermissionKind) {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::spawn_local;
+
+        let status = match self.status(kind) {
+            Some(status) => status,
+            None => return,
+        };
+
+        let js_change_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_change_event(kind).await })
+                }
+            })
+        };
+        status.set_onchange(Some(js_change_handler.as_ref().unchecked_ref()));
+        let prev_handler = self.change_handler(kind).replace(Some(js_change_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    async fn handler(self: &Arc<Self>, ev: MediaPermissionsEvent) {
+        self.handler.0(Arc::clone(self), ev).await
+    }
+
+    async fn on_change_event(self: &Arc<Self>, kind: MediaPermissionKind) {
+        if let Some(state) = self.state(kind) {
+            self.handler(MediaPermissionsEvent::StateChange { kind, state })
+                .await
+        }
+    }
+
+    pub fn state(&self, kind: MediaPermissionKind) -> Option<PermissionState> {
+        self.status(kind).map(|status| status.state())
+    }
+
+    pub fn is_granted(&self, kind: MediaPermissionKind) -> bool {
+        self.state(kind) == Some(PermissionState::Granted)
+    }
+}
+
+impl Drop for MediaPermissions {
+    fn drop(&mut self) {
+        log::trace!("browser_webrtc::MediaPermissions::drop");
+
+        for status in [&self.js_camera_status, &self.js_microphone_status]
+            .iter()
+            .copied()
+            .flatten()
+        {
+            status.set_onchange(None);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MediaPermissionsEvent {
+    StateChange {
+        kind: MediaPermissionKind,
+        state: PermissionState,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum MediaPermissionsError {
+    #[error("JavaScript window is undefined")]
+    WindowIsUndefined,
+    #[error("navigator.permissions is undefined: {0:?}")]
+    PermissionsIsUndefined(JsValue),
+}
@@ -0,0 +1,53 @@
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::Storage;
+
+const KEY_PREFIX: &str = "browser-webrtc.";
+
+/// Key-value persistence backed by the browser `localStorage`,
+/// keys are prefixed to avoid collisions with the application data.
+#[derive(Clone, Debug)]
+pub struct Persistence {
+    js_storage: Storage,
+}
+
+impl Persistence {
+    pub fn local() -> Result<Self, PersistenceError> {
+        let js_storage = web_sys::window()
+            .ok_or(PersistenceError::StorageIsNotAvailable)?
+            .local_storage()
+            .map_err(PersistenceError::JsError)?
+            .ok_or(PersistenceError::StorageIsNotAvailable)?;
+        Ok(Self { js_storage })
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, PersistenceError> {
+        self.js_storage
+            .get_item(&Self::storage_key(key))
+            .map_err(PersistenceError::JsError)
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> Result<(), PersistenceError> {
+        self.js_storage
+            .set_item(&Self::storage_key(key), value)
+            .map_err(PersistenceError::JsError)
+    }
+
+    pub fn remove(&self, key: &str) -> Result<(), PersistenceError> {
+        self.js_storage
+            .remove_item(&Self::storage_key(key))
+            .map_err(PersistenceError::JsError)
+    }
+
+    fn storage_key(key: &str) -> String {
+        format!("{}{}", KEY_PREFIX, key)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PersistenceError {
+    #[error("local storage is not available")]
+    StorageIsNotAvailable,
+    #[error("local storage error: {0:?}")]
+    JsError(JsValue),
+}
@@ -0,0 +1,32 @@
+use signaling_protocol::{ServerReceiverErrorMessage, ServerSenderErrorMessage};
+use thiserror::Error;
+use web_sys::{RtcIceConnectionState, RtcSignalingState};
+
+/// Handling of server messages that the session cannot expect in its current state.
+///
+/// The library never panics on them.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ProtocolViolationPolicy {
+    /// The violation is fatal, the peer connection is closed
+    /// and a `Closed` event with diagnostics is emitted.
+    Strict,
+    /// The violation is logged and the session continues.
+    #[default]
+    Lenient,
+}
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum ProtocolViolation {
+    #[error("unexpected server sender error: {0}")]
+    UnexpectedSenderError(ServerSenderErrorMessage),
+    #[error("unexpected server receiver error: {0}")]
+    UnexpectedReceiverError(ServerReceiverErrorMessage),
+}
+
+/// The violation that closed the session and the connection state at that moment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtocolViolationDiagnostics {
+    pub violation: ProtocolViolation,
+    pub ice_connection_state: RtcIceConnectionState,
+    pub signaling_state: RtcSignalingState,
+}
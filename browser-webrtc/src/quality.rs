@@ -0,0 +1,172 @@
+use core::time::Duration;
+
+use wasm_bindgen::JsValue;
+
+use crate::stats::{stat_f64, stat_string};
+
+/// Interval between connection quality samples.
+pub(crate) const QUALITY_UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+/// Number of consecutive samples required to change the score by one point,
+/// larger changes are applied immediately.
+const QUALITY_HYSTERESIS_SAMPLES: u32 = 3;
+
+pub const MAX_QUALITY_SCORE: u8 = 5;
+
+/// Connection quality aggregated from the stats of all streams of the connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConnectionQuality {
+    /// Score from `0` (unusable) to `MAX_QUALITY_SCORE` (excellent),
+    /// it is determined by the worst of the metrics.
+    pub score: u8,
+    /// Fraction of the packets lost since the previous sample.
+    pub packet_loss: Option<f64>,
+    pub round_trip_time: Option<Duration>,
+    pub jitter: Option<Duration>,
+    /// Received video freezes since the previous sample.
+    pub freeze_count: u32,
+}
+
+impl ConnectionQuality {
+    fn from_sample(sample: &QualitySample, prev: Option<&QualitySample>) -> Option<Self> {
+        let packet_loss = match prev {
+            Some(prev) if sample.packets_received > prev.packets_received => {
+                let lost = (sample.packets_lost - prev.packets_lost).max(0.0);
+                let received = sample.packets_received - prev.packets_received;
+                Some(lost / (lost + received))
+            }
+            _ => None,
+        }
+        .or(sample.remote_fraction_lost);
+        let freeze_count = prev
+            .map(|prev| sample.freeze_count.saturating_sub(prev.freeze_count))
+            .unwrap_or(0);
+
+        let scores = [
+            packet_loss.map(|loss| score(loss, &[0.005, 0.02, 0.05, 0.1, 0.2])),
+            sample
+                .round_trip_time
+                .map(|rtt| score(rtt.as_secs_f64(), &[0.1, 0.2, 0.3, 0.5, 1.0])),
+            sample
+                .jitter
+                .map(|jitter| score(jitter.as_secs_f64(), &[0.02, 0.04, 0.06, 0.1, 0.2])),
+            prev.map(|_| score(freeze_count as f64, &[0.5, 0.5, 1.5, 1.5, 3.5])),
+        ];
+        let score = scores.iter().flatten().min().copied()?;
+
+        Some(Self {
+            score,
+            packet_loss,
+            round_trip_time: sample.round_trip_time,
+            jitter: sample.jitter,
+            freeze_count,
+        })
+    }
+}
+
+/// Maps the metric to the score using the ascending thresholds of the scores from 5 to 1.
+fn score(value: f64, thresholds: &[f64; MAX_QUALITY_SCORE as usize]) -> u8 {
+    let exceeded = thresholds
+        .iter()
+        .take_while(|threshold| value >= **threshold)
+        .count();
+    MAX_QUALITY_SCORE - exceeded as u8
+}
+
+/// Cumulative counters and the current metrics of a single stats report.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct QualitySample {
+    packets_lost: f64,
+    packets_received: f64,
+    freeze_count: u32,
+    /// Loss reported by the remote receivers, used by the sending side.
+    remote_fraction_lost: Option<f64>,
+    round_trip_time: Option<Duration>,
+    jitter: Option<Duration>,
+}
+
+impl QualitySample {
+    fn from_stats(stats: &[JsValue]) -> Self {
+        let mut sample = Self::default();
+        let mut max_jitter: Option<f64> = None;
+        for stat in stats {
+            match stat_string(stat, "type").as_deref() {
+                Some("inbound-rtp") => {
+                    sample.packets_lost += stat_f64(stat, "packetsLost").unwrap_or(0.0);
+                    sample.packets_received += stat_f64(stat, "packetsReceived").unwrap_or(0.0);
+                    sample.freeze_count += stat_f64(stat, "freezeCount").unwrap_or(0.0) as u32;
+                    max_jitter = max(max_jitter, stat_f64(stat, "jitter"));
+                }
+                Some("remote-inbound-rtp") => {
+                    sample.remote_fraction_lost =
+                        max(sample.remote_fraction_lost, stat_f64(stat, "fractionLost"));
+                    max_jitter = max(max_jitter, stat_f64(stat, "jitter"));
+                }
+                Some("candidate-pair")
+                    if stat_string(stat, "state").as_deref() == Some("succeeded") =>
+                {
+                    if let Some(rtt) = stat_f64(stat, "currentRoundTripTime") {
+                        sample.round_trip_time = Some(Duration::from_secs_f64(rtt));
+                    }
+                }
+                _ => {}
+            }
+        }
+        sample.jitter = max_jitter.map(Duration::from_secs_f64);
+        sample
+    }
+}
+
+fn max(lhs: Option<f64>, rhs: Option<f64>) -> Option<f64> {
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => Some(lhs.max(rhs)),
+        (lhs, rhs) => lhs.or(rhs),
+    }
+}
+
+/// Samples the connection quality and smooths the score changes.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct QualityMonitor {
+    prev_sample: Option<QualitySample>,
+    quality: Option<ConnectionQuality>,
+    pending_score: Option<(u8, u32)>,
+}
+
+impl QualityMonitor {
+    /// Returns the new quality if the score is changed.
+    pub(crate) fn update(&mut self, stats: &[JsValue]) -> Option<ConnectionQuality> {
+        let sample = QualitySample::from_stats(stats);
+        let quality = ConnectionQuality::from_sample(&sample, self.prev_sample.as_ref());
+        self.prev_sample = Some(sample);
+        let quality = quality?;
+
+        let current_score = match self.quality {
+            Some(current) => current.score,
+            None => {
+                self.quality = Some(quality);
+                return Some(quality);
+            }
+        };
+        if quality.score == current_score {
+            self.pending_score = None;
+            self.quality = Some(quality);
+            return None;
+        }
+
+        let samples = match self.pending_score {
+            Some((score, samples)) if score == quality.score => samples + 1,
+            _ => 1,
+        };
+        if quality.score.abs_diff(current_score) > 1 || samples >= QUALITY_HYSTERESIS_SAMPLES {
+            self.pending_score = None;
+            self.quality = Some(quality);
+            Some(quality)
+        } else {
+            self.pending_score = Some((quality.score, samples));
+            None
+        }
+    }
+
+    pub(crate) fn quality(&self) -> Option<ConnectionQuality> {
+        self.quality
+    }
+}
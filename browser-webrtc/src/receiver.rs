@@ -1,51 +1,110 @@
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::sync::atomic::AtomicBool;
+use core::time::Duration;
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
 use async_std::sync::Arc;
 use js_sys::Set;
 use signaling_protocol::{
-    ChannelId, ClientMessage, ClientReceiverMessage, ServerReceiverErrorMessage,
-    ServerReceiverMessage, SessionDescription, SessionReceiverId,
+    CallMilestone, ChannelId, ChannelSecret, ClientMessage, ClientReceiverMessage, CloseReason,
+    ErrorCode, IceCandidate, Language, PeerCapabilities, PeerNetwork, PublicKey, QualityRequest,
+    ServerReceiverErrorMessage, ServerReceiverMessage, SessionDescription, SessionReceiverId,
+    SessionSummary, Topic,
 };
 use thiserror::Error;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsValue;
 use web_sys::{
-    Event, MediaStream, RtcConfiguration, RtcDataChannelEvent, RtcIceCandidate,
-    RtcIceCandidateInit, RtcIceConnectionState, RtcIceGatheringState, RtcPeerConnection,
-    RtcPeerConnectionIceEvent, RtcSignalingState, RtcTrackEvent, WebSocket,
+    Event, MediaStream, MediaStreamTrack, RtcConfiguration, RtcDataChannel, RtcDataChannelEvent,
+    RtcIceConnectionState, RtcIceGatheringState, RtcSignalingState, RtcTrackEvent,
+};
+use webrtc_core::{
+    CandidatePreference, LocalCandidate, NegotiationRole, PerfectNegotiation, SessionTeardown,
 };
 
+use crate::identity::PeerVerification;
+use crate::live_objects::{LiveObject, LiveObjectKind};
+use crate::peer_connection::{
+    JsHandler, PeerConnectionCore, PeerConnectionEvent, PeerConnectionRole,
+};
+use crate::peer_state::{ControlChannel, ControlMessage};
+use crate::quality::QualityMonitor;
+use crate::sealed::SealedSession;
+use crate::session_metrics::{SessionMetrics, SessionUsage};
+use crate::session_policy::SessionLimiter;
+use crate::telemetry::MilestoneTracker;
+#[cfg(feature = "encoded-transform")]
+use crate::{
+    encoded_transform::{EncodedTransformPipe, SharedEncodedFrameTransform},
+    EncodedFrameTransform, EncodedTransformError,
+};
 use crate::{
-    send_websocket_client_message, BoxAsyncFn2, BoxAsyncFn2Wrapper, DataReceiverBuilder,
-    MediaReceiverBuilder, Server, WebSocketClientMessageSendError,
+    BackgroundPolicy, BoxAsyncFn2, BoxAsyncFn2Wrapper, CaptionCue, ConnectionQuality,
+    DataReceiverBuilder, Delivery, DeliveryError, FileReceiverBuilder, GlobalReceiverId,
+    IceTimeline, IdentityError, IdentityKeyPair, InboundVideoStats, JoinError,
+    MediaReceiverBuilder, MediaSnapshot, NetworkChange, PageVisibility, PeerState,
+    ProtocolViolation, ProtocolViolationDiagnostics, SctpLimits, SealingError, SealingKeyPair,
+    Server, SessionEndReason, SessionPolicy, StatsError, TelemetrySource, VerificationError,
+    VerifiedPeer, WebSocketClientMessageSendError,
 };
 
+type JoinResultSender = async_std::channel::Sender<Result<(), JoinError>>;
+
 #[derive(Debug)]
 pub struct Receiver {
     server: Arc<Server>,
     receiver_id: SessionReceiverId,
+    channel_id: RefCell<ChannelId>,
+    /// Sent again when the channel is rejoined on the reconnected server.
+    credential: Option<ChannelSecret>,
+    /// The receiver state published to the server, sent again when the channel is rejoined.
+    capabilities: Cell<Option<PeerCapabilities>>,
+    topics: RefCell<HashSet<Topic>>,
     handler: BoxAsyncFn2Wrapper<Arc<Receiver>, ReceiverEvent, ()>,
-    js_connection: RtcPeerConnection,
-    js_websocket: WebSocket,
-    js_ice_candidate_handler: RefCell<Option<Closure<dyn FnMut(RtcPeerConnectionIceEvent)>>>,
-    js_negotiation_needed_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+    _live_object: LiveObject,
+    core: PeerConnectionCore,
     js_data_channel_handler: RefCell<Option<Closure<dyn FnMut(RtcDataChannelEvent)>>>,
     js_track_handler: RefCell<Option<Closure<dyn FnMut(RtcTrackEvent)>>>,
-    js_ice_connection_state_change_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
-    js_ice_gathering_state_change: RefCell<Option<Closure<dyn FnMut(Event)>>>,
-    js_signaling_state_change_change: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+    page_visibility: Cell<PageVisibility>,
+    background_policy: Cell<BackgroundPolicy>,
+    /// Video tracks disabled by the background policy, enabled again once the page is shown.
+    js_background_paused_tracks: RefCell<Vec<MediaStreamTrack>>,
+    sctp_limits: Cell<Option<SctpLimits>>,
+    quality_monitor: RefCell<QualityMonitor>,
+    session_metrics: SessionMetrics,
+    milestones: MilestoneTracker,
+    is_summary_reported: Cell<bool>,
+    session_limiter: SessionLimiter,
+    join_result_sender: JoinResultSender,
+    join_result_receiver: async_std::channel::Receiver<Result<(), JoinError>>,
+    is_join_awaited: Cell<bool>,
+    control_channel: RefCell<Option<Rc<ControlChannel>>>,
+    local_peer_state: Cell<PeerState>,
+    sealing_key_pair: RefCell<Option<SealingKeyPair>>,
+    sealed_session: RefCell<Option<SealedSession>>,
+    peer_verification: PeerVerification,
+    teardown: SessionTeardown,
     js_media_streams: Set,
     js_media_tracks: Set,
+    stream_labels: RefCell<HashMap<String, String>>,
+    js_track_state_handler: JsHandler<Event>,
     is_started: AtomicBool,
+    /// The receiver is the polite peer, its offers colliding with the sender ones are rolled back.
+    perfect_negotiation: PerfectNegotiation,
+    #[cfg(feature = "encoded-transform")]
+    encoded_transform: SharedEncodedFrameTransform,
+    #[cfg(feature = "encoded-transform")]
+    js_encoded_transform_pipes: RefCell<Vec<EncodedTransformPipe>>,
 }
 
 impl Receiver {
     pub fn new(
-        js_websocket: WebSocket,
         server: Arc<Server>,
         receiver_id: SessionReceiverId,
         channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
         handler: BoxAsyncFn2<Arc<Self>, ReceiverEvent, ()>,
         rtc_configuration: Option<RtcConfiguration>,
     ) -> Result<Arc<Self>, NewReceiverError> {
@@ -53,64 +112,69 @@ impl Receiver {
 
         let message = ClientMessage::ReceiverMessage {
             receiver_id,
-            message: ClientReceiverMessage::JoinChannel { channel_id },
+            message: ClientReceiverMessage::JoinChannel {
+                channel_id: channel_id.clone(),
+                credential: credential.clone(),
+            },
         };
-        send_websocket_client_message(&js_websocket, message)?;
+        server.send_message(message)?;
 
-        let js_connection = match rtc_configuration {
-            Some(config) => RtcPeerConnection::new_with_configuration(&config),
-            None => RtcPeerConnection::new(),
-        }
-        .map_err(NewReceiverError::NewRtcPeerConnectionError)?;
+        let core = PeerConnectionCore::new(rtc_configuration)
+            .map_err(NewReceiverError::NewRtcPeerConnectionError)?;
 
+        // The result is buffered, so the join can be awaited after it is confirmed.
+        let (join_result_sender, join_result_receiver) = async_std::channel::bounded(1);
         let receiver = Arc::new(Self {
             server,
             receiver_id,
+            channel_id: RefCell::new(channel_id),
+            credential,
+            capabilities: Cell::new(None),
+            topics: RefCell::new(HashSet::new()),
             handler: BoxAsyncFn2Wrapper(handler),
-            js_connection: js_connection.clone(),
-            js_websocket,
-            js_ice_candidate_handler: RefCell::new(None),
-            js_negotiation_needed_handler: RefCell::new(None),
+            _live_object: LiveObject::new(LiveObjectKind::Receiver),
+            core,
             js_data_channel_handler: RefCell::new(None),
             js_track_handler: RefCell::new(None),
-            js_ice_connection_state_change_handler: RefCell::new(None),
-            js_ice_gathering_state_change: RefCell::new(None),
-            js_signaling_state_change_change: RefCell::new(None),
+            page_visibility: Cell::new(PageVisibility::current()),
+            background_policy: Cell::new(BackgroundPolicy::default()),
+            js_background_paused_tracks: RefCell::new(Vec::new()),
+            sctp_limits: Cell::new(None),
+            quality_monitor: RefCell::new(QualityMonitor::default()),
+            session_metrics: SessionMetrics::new(),
+            milestones: MilestoneTracker::new(TelemetrySource::Receiver(receiver_id)),
+            is_summary_reported: Cell::new(false),
+            session_limiter: SessionLimiter::default(),
+            join_result_sender,
+            join_result_receiver,
+            is_join_awaited: Cell::new(false),
+            control_channel: RefCell::new(None),
+            local_peer_state: Cell::new(PeerState::default()),
+            sealing_key_pair: RefCell::new(None),
+            sealed_session: RefCell::new(None),
+            peer_verification: PeerVerification::default(),
+            teardown: SessionTeardown::default(),
             js_media_streams: Set::new(&JsValue::UNDEFINED),
             js_media_tracks: Set::new(&JsValue::UNDEFINED),
+            stream_labels: RefCell::new(HashMap::new()),
+            js_track_state_handler: RefCell::new(None),
             is_started: AtomicBool::new(false),
+            perfect_negotiation: PerfectNegotiation::new(NegotiationRole::Polite),
+            #[cfg(feature = "encoded-transform")]
+            encoded_transform: SharedEncodedFrameTransform::default(),
+            #[cfg(feature = "encoded-transform")]
+            js_encoded_transform_pipes: RefCell::new(Vec::new()),
         });
 
-        receiver.init_icecandidate_handler();
+        receiver.core.init_handlers(&receiver);
         receiver.init_data_channel_handler();
         receiver.init_track_handler();
-        receiver.init_ice_connection_state_change_handler();
-        receiver.init_ice_gathering_state_change_handler();
-        receiver.init_signaling_state_change_handler();
+        receiver.init_track_state_handler();
+        receiver.init_stats_monitor();
 
         Ok(receiver)
     }
 
-    fn init_icecandidate_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
-
-        let js_ice_candidate_handler = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: RtcPeerConnectionIceEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_ice_candidate_event(ev).await });
-            })
-        };
-        self.js_connection
-            .set_onicecandidate(Some(js_ice_candidate_handler.as_ref().unchecked_ref()));
-        let prev_handler = self
-            .js_ice_candidate_handler
-            .replace(Some(js_ice_candidate_handler));
-        debug_assert!(prev_handler.is_none());
-    }
-
     fn init_data_channel_handler(self: &Arc<Self>) {
         use crate::closure_1;
         use wasm_bindgen::JsCast;
@@ -119,11 +183,13 @@ impl Receiver {
         let js_data_channel_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |ev: RtcDataChannelEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_data_channel_event(ev).await });
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_data_channel_event(ev).await });
+                }
             })
         };
-        self.js_connection
+        self.core
+            .js_connection()
             .set_ondatachannel(Some(js_data_channel_handler.as_ref().unchecked_ref()));
         let prev_handler = self
             .js_data_channel_handler
@@ -139,119 +205,208 @@ impl Receiver {
         let js_track_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |ev: RtcTrackEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_track_event(ev).await });
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_track_event(ev).await });
+                }
             })
         };
-        self.js_connection
+        self.core
+            .js_connection()
             .set_ontrack(Some(js_track_handler.as_ref().unchecked_ref()));
         let prev_handler = self.js_track_handler.replace(Some(js_track_handler));
         debug_assert!(prev_handler.is_none());
     }
 
-    fn init_negotiation_needed_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
+    fn send_acked_message(
+        &self,
+        message: ClientReceiverMessage,
+    ) -> Result<Delivery, ReceiverSendError> {
+        let message = ClientMessage::ReceiverMessage {
+            receiver_id: self.receiver_id,
+            message,
+        };
+        let (delivery, len) = self.server.queue_acked_message(message)?;
+        self.session_metrics.add_signaling_sent(len);
+        Ok(delivery)
+    }
 
-        let js_negotiation_needed_handler = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_negotiation_needed_event(ev).await });
-            })
+    fn send_message(&self, message: ClientReceiverMessage) -> Result<(), ReceiverSendError> {
+        let message = ClientMessage::ReceiverMessage {
+            receiver_id: self.receiver_id,
+            message,
         };
-        self.js_connection
-            .set_onnegotiationneeded(Some(js_negotiation_needed_handler.as_ref().unchecked_ref()));
-        let prev_handler = self
-            .js_negotiation_needed_handler
-            .replace(Some(js_negotiation_needed_handler));
-        debug_assert!(prev_handler.is_none());
+        let len = self.server.queue_message(message)?;
+        self.session_metrics.add_signaling_sent(len);
+        Ok(())
     }
 
-    fn init_ice_connection_state_change_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
+    async fn handler(self: &Arc<Self>, ev: ReceiverEvent) {
+        self.handler.0(Arc::clone(self), ev).await
+    }
 
-        let js_ice_connection_state_change_handler = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_ice_connection_state_change(ev).await });
-            })
-        };
-        self.js_connection.set_oniceconnectionstatechange(Some(
-            js_ice_connection_state_change_handler
-                .as_ref()
-                .unchecked_ref(),
-        ));
-        let prev_handler = self
-            .js_ice_connection_state_change_handler
-            .replace(Some(js_ice_connection_state_change_handler));
-        debug_assert!(prev_handler.is_none());
+    async fn error(self: &Arc<Self>, err: ReceiverError) {
+        self.handler(ReceiverEvent::Error(err)).await
     }
 
-    fn init_ice_gathering_state_change_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
+    async fn on_protocol_violation(self: &Arc<Self>, violation: ProtocolViolation) {
+        use crate::ProtocolViolationPolicy;
 
-        let js_ice_gathering_state_change = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_ice_gathering_state_change(ev).await });
-            })
-        };
-        self.js_connection.set_onicegatheringstatechange(Some(
-            js_ice_gathering_state_change.as_ref().unchecked_ref(),
-        ));
-        let prev_handler = self
-            .js_ice_gathering_state_change
-            .replace(Some(js_ice_gathering_state_change));
-        debug_assert!(prev_handler.is_none());
+        match self.server.protocol_violation_policy() {
+            ProtocolViolationPolicy::Lenient => {
+                log::warn!("receiver protocol violation ignored: {}", violation);
+            }
+            ProtocolViolationPolicy::Strict => {
+                let diagnostics = ProtocolViolationDiagnostics {
+                    violation,
+                    ice_connection_state: self.ice_connection_state(),
+                    signaling_state: self.signaling_state(),
+                };
+                log::error!(
+                    "receiver {:?} closed on protocol violation: {:?}",
+                    self.receiver_id,
+                    diagnostics
+                );
+                self.close_with(CloseReason::PROTOCOL_VIOLATION, Some(diagnostics))
+                    .await;
+            }
+        }
     }
 
-    fn init_signaling_state_change_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
+    /// Resolves when the server confirms or rejects the join,
+    /// the join errors are not reported in `ReceiverEvent::Error` after that.
+    pub async fn joined(&self, timeout: Duration) -> Result<(), JoinError> {
         use wasm_bindgen_futures::spawn_local;
 
-        let js_signaling_state_change_change = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_signaling_state_change(ev).await });
-            })
-        };
-        self.js_connection.set_onsignalingstatechange(Some(
-            js_signaling_state_change_change.as_ref().unchecked_ref(),
-        ));
-        let prev_handler = self
-            .js_signaling_state_change_change
-            .replace(Some(js_signaling_state_change_change));
-        debug_assert!(prev_handler.is_none());
+        use crate::sleep;
+
+        if self.is_join_awaited.replace(true) {
+            return Err(JoinError::AlreadyAwaited);
+        }
+        let join_result_sender = self.join_result_sender.clone();
+        spawn_local(async move {
+            sleep(timeout).await;
+            let _: Result<(), _> = join_result_sender.try_send(Err(JoinError::TimedOut(timeout)));
+        });
+        self.join_result_receiver
+            .recv()
+            .await
+            .unwrap_or(Err(JoinError::TimedOut(timeout)))
     }
 
-    fn send_message(&self, message: ClientReceiverMessage) -> Result<(), ReceiverSendError> {
-        let message = ClientMessage::ReceiverMessage {
-            receiver_id: self.receiver_id,
-            message,
-        };
-        send_websocket_client_message(&self.js_websocket, message)?;
-        Ok(())
+    async fn on_join_rejected(
+        self: &Arc<Self>,
+        err: ServerReceiverErrorMessage,
+    ) -> Result<(), ReceiverError> {
+        // The server has no session to exit.
+        self.teardown.skip_close_message();
+        let _: Result<(), _> = self
+            .join_result_sender
+            .try_send(Err(JoinError::Rejected(err.clone())));
+        if self.is_join_awaited.get() {
+            return Ok(());
+        }
+        match err {
+            ServerReceiverErrorMessage::ChannelIsNotExist(channel_id) => {
+                Err(ReceiverError::ChannelIsNotExist(channel_id))
+            }
+            ServerReceiverErrorMessage::ChannelIsAlreadyOccupied(channel_id) => {
+                Err(ReceiverError::ChannelIsAlreadyOccupied(channel_id))
+            }
+            ServerReceiverErrorMessage::InvalidChannelCredentials(channel_id) => {
+                Err(ReceiverError::InvalidChannelCredentials(channel_id))
+            }
+            err => {
+                self.on_protocol_violation(ProtocolViolation::UnexpectedReceiverError(err))
+                    .await;
+                Ok(())
+            }
+        }
     }
 
-    async fn handler(self: &Arc<Self>, ev: ReceiverEvent) {
-        self.handler.0(Arc::clone(self), ev).await
+    /// Returns `true` if the receiver is closed by a protocol violation in strict mode,
+    /// by the session policy or by the channel sender, server messages are ignored after that.
+    pub fn is_closed(&self) -> bool {
+        self.teardown.is_closed()
     }
 
-    async fn error(self: &Arc<Self>, err: ReceiverError) {
-        self.handler(ReceiverEvent::Error(err)).await
+    /// Joins the channel again on the reconnected server with its current id,
+    /// returns `false` if the receiver is closed.
+    pub(crate) fn rejoin_channel(&self) -> Result<bool, ReceiverSendError> {
+        if self.is_closed() {
+            return Ok(false);
+        }
+        let channel_id = self.channel_id.borrow().clone();
+        self.core.local_candidates().embargo();
+        self.send_message(ClientReceiverMessage::JoinChannel {
+            channel_id,
+            credential: self.credential.clone(),
+        })?;
+        if let Some(capabilities) = self.capabilities.get() {
+            self.send_message(ClientReceiverMessage::PublishCapabilities(capabilities))?;
+        }
+        for topic in self.topics.borrow().iter() {
+            self.send_message(ClientReceiverMessage::SubscribeTopic(topic.clone()))?;
+        }
+        Ok(true)
+    }
+
+    /// Why the receiver was closed, `None` while it is open.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.teardown.reason()
     }
 
-    pub(crate) async fn on_server_message(self: &Arc<Self>, message: ServerReceiverMessage) {
+    /// Closes the connection and exits the channel, the sender is notified.
+    ///
+    /// Closing an already closed receiver does nothing,
+    /// the receiver may be dropped right after that.
+    pub async fn close(self: &Arc<Self>) {
+        self.close_with(CloseReason::LocalClosed, None).await
+    }
+
+    async fn close_with(
+        self: &Arc<Self>,
+        reason: CloseReason,
+        diagnostics: Option<ProtocolViolationDiagnostics>,
+    ) {
+        if self.close_once(reason) {
+            self.handler(ReceiverEvent::Closed {
+                reason,
+                diagnostics,
+            })
+            .await;
+        }
+    }
+
+    /// Closes the connection and exits the channel, returns `false` if the receiver
+    /// is already closed, server messages and later close calls are ignored after that.
+    fn close_once(&self, reason: CloseReason) -> bool {
+        if !self.teardown.close(reason) {
+            return false;
+        }
+        self.core.js_connection().close();
+        self.exit_channel();
+        true
+    }
+
+    async fn on_channel_closed(self: &Arc<Self>, reason: CloseReason) {
+        log::info!(
+            "receiver {:?} channel closed: {:?}",
+            self.receiver_id,
+            reason
+        );
+        self.close_with(reason, None).await;
+    }
+
+    pub(crate) async fn on_server_message(
+        self: &Arc<Self>,
+        message: ServerReceiverMessage,
+        len: usize,
+    ) {
+        self.session_metrics.add_signaling_received(len);
+        if self.is_closed() {
+            log::debug!("receiver is closed, server message ignored: {:?}", message);
+            return;
+        }
         match self.clone().handle_server_message(message).await {
             Ok(()) => {}
             Err(err) => self.error(err).await,
@@ -262,88 +417,137 @@ impl Receiver {
         self: &Arc<Self>,
         message: ServerReceiverMessage,
     ) -> Result<(), ReceiverError> {
-        use wasm_bindgen_futures::JsFuture;
         use ServerReceiverMessage as Msg;
 
         match message {
             Msg::JoinChannelSuccess => {
+                let _: Result<(), _> = self.join_result_sender.try_send(Ok(()));
+                self.release_local_candidates().await;
                 self.handler(ReceiverEvent::JoinChannelSuccess).await;
                 Ok(())
             }
             Msg::ChannelOffer(sdp) => {
-                self.receive_offer_and_send_answer(sdp).await?;
+                let sdp = self.open_sdp(sdp).await?;
+                let (sdp, peer) = self.peer_verification.verify(sdp).await?;
+                self.receive_offer_and_send_answer(sdp, peer).await?;
+                Ok(())
+            }
+            Msg::ChannelAnswer(sdp) => {
+                let sdp = self.open_sdp(sdp).await?;
+                let (sdp, _) = self.peer_verification.verify(sdp).await?;
+                self.receive_answer(sdp).await?;
                 Ok(())
             }
             Msg::IceCandidate(ice_candidate) => {
-                let mut candidate = RtcIceCandidateInit::new(&ice_candidate.candidate);
-                let _: &mut _ = candidate
-                    .sdp_mid(ice_candidate.sdp_mid.as_deref())
-                    .sdp_m_line_index(ice_candidate.sdp_m_line_index);
-                let candidate = RtcIceCandidate::new(&candidate)
-                    .map_err(ReceiverError::NewRtcIceCandidateError)?;
-
-                let ice_candidate_result = JsFuture::from(
-                    self.js_connection
-                        .add_ice_candidate_with_opt_rtc_ice_candidate(Some(&candidate)),
-                )
-                .await;
-                match ice_candidate_result {
-                    Ok(_) => {}
-                    Err(err) => self.error(ReceiverError::AddIceCandidateError(err)).await,
-                };
-
+                let ice_candidate = self.open_ice_candidate(ice_candidate).await?;
+                self.core.on_remote_ice_candidate(self, ice_candidate).await;
                 Ok(())
             }
             Msg::AllIceCandidatesSent => Ok(()),
+            Msg::IceCandidatesRequested => self.resend_ice_candidates().await,
             Msg::BinaryData(data) => {
                 self.handler(ReceiverEvent::BinaryData(data)).await;
                 Ok(())
             }
+            Msg::TopicData { topic, data } => {
+                self.handler(ReceiverEvent::TopicData { topic, data }).await;
+                Ok(())
+            }
+            Msg::PeerPublicKey(public_key) => self.on_peer_public_key(public_key).await,
+            Msg::PeerCapabilities(capabilities) => {
+                self.core.on_peer_capabilities(capabilities);
+                Ok(())
+            }
+            Msg::PeerNetwork(peer_network) => {
+                self.handler(ReceiverEvent::PeerNetwork(peer_network)).await;
+                Ok(())
+            }
+            Msg::ChannelClosed(reason) => {
+                self.on_channel_closed(reason).await;
+                Ok(())
+            }
+            Msg::ChannelRenamed(channel_id) => {
+                let _: ChannelId = self.channel_id.replace(channel_id.clone());
+                self.handler(ReceiverEvent::ChannelRenamed(channel_id))
+                    .await;
+                Ok(())
+            }
+            Msg::ChannelSnapshot(snapshot) => {
+                self.handler(ReceiverEvent::ChannelSnapshot(MediaSnapshot::from_png(
+                    snapshot,
+                )))
+                .await;
+                Ok(())
+            }
             Msg::Error(err) => match err {
-                ServerReceiverErrorMessage::ChannelIsNotExist(channel_id) => {
-                    Err(ReceiverError::ChannelIsNotExist(channel_id))
+                ServerReceiverErrorMessage::ChannelIsNotExist(_)
+                | ServerReceiverErrorMessage::ChannelIsAlreadyOccupied(_)
+                | ServerReceiverErrorMessage::InvalidChannelCredentials(_) => {
+                    self.on_join_rejected(err).await
+                }
+                ServerReceiverErrorMessage::NegotiationTimedOut(channel_id) => {
+                    Err(ReceiverError::NegotiationTimedOut(channel_id))
                 }
-                ServerReceiverErrorMessage::ChannelIsAlreadyOccupied(channel_id) => {
-                    Err(ReceiverError::ChannelIsAlreadyOccupied(channel_id))
+                err => {
+                    self.on_protocol_violation(ProtocolViolation::UnexpectedReceiverError(err))
+                        .await;
+                    Ok(())
                 }
-                _ => panic!("invalid SessionReceiverId used"),
             },
         }
     }
 
-    async fn on_ice_candidate_event(self: &Arc<Self>, ev: RtcPeerConnectionIceEvent) {
-        log::trace!("browser_webrtc::Receiver::on_ice_candidate_event");
+    async fn send_ice_candidate(&self, ice_candidate: IceCandidate) -> Result<(), ReceiverError> {
+        let ice_candidate = match self.core.prefer_local_ice_candidate(ice_candidate) {
+            Some(ice_candidate) => ice_candidate,
+            None => return Ok(()),
+        };
+        let ice_candidate = self.seal_ice_candidate(ice_candidate).await?;
+        self.send_acked_message(ClientReceiverMessage::IceCandidate(ice_candidate))
+            .map_err(ReceiverError::IceCandidateSendError)?
+            .delivered()
+            .await
+            .map_err(ReceiverError::IceCandidateDeliveryError)
+    }
 
-        match self.handle_ice_candidate_event(ev).await {
-            Ok(()) => {}
-            Err(err) => self.error(err).await,
+    /// Sends the local candidate unless the server has not confirmed the channel yet.
+    async fn on_local_candidate(self: &Arc<Self>, candidate: LocalCandidate) {
+        if let Some(candidate) = self.core.local_candidates().on_gathered(candidate) {
+            if let Err(err) = self.send_local_candidate(candidate).await {
+                self.error(err).await
+            }
         }
     }
 
-    async fn handle_ice_candidate_event(
-        &self,
-        ev: RtcPeerConnectionIceEvent,
-    ) -> Result<(), ReceiverError> {
-        use signaling_protocol::IceCandidate;
-
-        if let Some(candidate) = ev.candidate() {
-            let candidate_str = candidate.candidate();
-            let message = match candidate_str.as_ref() {
-                "" => ClientReceiverMessage::AllIceCandidatesSent,
-                _ => {
-                    let ice_candidate = IceCandidate {
-                        candidate: candidate_str,
-                        sdp_mid: candidate.sdp_mid(),
-                        sdp_m_line_index: candidate.sdp_m_line_index(),
-                    };
-                    ClientReceiverMessage::IceCandidate(ice_candidate)
-                }
-            };
-            let message = ClientMessage::ReceiverMessage {
-                receiver_id: self.receiver_id,
-                message,
-            };
-            send_websocket_client_message(&self.js_websocket, message)
+    /// Sends the local candidates held until the server confirmed the channel.
+    async fn release_local_candidates(self: &Arc<Self>) {
+        while let Some(candidate) = self.core.local_candidates().release_next() {
+            if let Err(err) = self.send_local_candidate(candidate).await {
+                self.error(err).await
+            }
+        }
+    }
+
+    async fn send_local_candidate(&self, candidate: LocalCandidate) -> Result<(), ReceiverError> {
+        match candidate {
+            LocalCandidate::IceCandidate(ice_candidate) => {
+                self.send_ice_candidate(ice_candidate).await
+            }
+            LocalCandidate::AllSent => self
+                .send_message(ClientReceiverMessage::AllIceCandidatesSent)
+                .map_err(ReceiverError::IceCandidateSendError),
+        }
+    }
+
+    /// Re-sends the gathered local candidates on the sender request.
+    async fn resend_ice_candidates(&self) -> Result<(), ReceiverError> {
+        use web_sys::RtcIceGatheringState;
+
+        for ice_candidate in self.core.engine().local_ice_candidates() {
+            self.send_ice_candidate(ice_candidate).await?;
+        }
+        if self.ice_gathering_state() == RtcIceGatheringState::Complete {
+            self.send_message(ClientReceiverMessage::AllIceCandidatesSent)
                 .map_err(ReceiverError::IceCandidateSendError)?;
         }
         Ok(())
@@ -352,11 +556,285 @@ impl Receiver {
     async fn on_data_channel_event(self: &Arc<Self>, ev: RtcDataChannelEvent) {
         log::trace!("browser_webrtc::Receiver::on_data_channel_event");
 
-        let data_receiver = DataReceiverBuilder::new(Arc::clone(&self), ev.channel());
+        use crate::file_sender::FILE_DATA_CHANNEL_LABEL;
+        use crate::CONTROL_DATA_CHANNEL_LABEL;
+
+        let js_channel = ev.channel();
+        if js_channel.label() == CONTROL_DATA_CHANNEL_LABEL {
+            self.init_control_channel(js_channel);
+            return;
+        }
+        if js_channel.label() == FILE_DATA_CHANNEL_LABEL {
+            let file_receiver = FileReceiverBuilder::new(Arc::clone(self), js_channel);
+            self.handler(ReceiverEvent::FileReceiver(file_receiver))
+                .await;
+            return;
+        }
+        let data_receiver = DataReceiverBuilder::new(Arc::clone(&self), js_channel);
         self.handler(ReceiverEvent::DataReceiver(data_receiver))
             .await
     }
 
+    fn init_control_channel(self: &Arc<Self>, js_channel: RtcDataChannel) {
+        use wasm_bindgen_futures::spawn_local;
+
+        let control_channel = {
+            let self_weak = Arc::downgrade(self);
+            ControlChannel::new(js_channel, move |message| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_control_message(message).await });
+                }
+            })
+        };
+        control_channel.set_local_state(self.local_peer_state.get());
+        // The channel is replaced if the sender renegotiates it.
+        drop(self.control_channel.replace(Some(control_channel)));
+    }
+
+    async fn on_control_message(self: &Arc<Self>, message: ControlMessage) {
+        match message {
+            ControlMessage::PeerState(state) => {
+                self.handler(ReceiverEvent::PeerStateChanged(state)).await
+            }
+            ControlMessage::StreamLabels(labels) => {
+                let prev_labels = self.stream_labels.replace(labels.clone());
+                if prev_labels != labels {
+                    self.handler(ReceiverEvent::StreamLabelsChanged(labels))
+                        .await
+                }
+            }
+            ControlMessage::AudioOnly(is_audio_only) => {
+                self.handler(ReceiverEvent::PeerAudioOnlyChanged(is_audio_only))
+                    .await
+            }
+            ControlMessage::StreamRemoved(stream_id) => {
+                let stream = self
+                    .media_streams()
+                    .into_iter()
+                    .find(|stream| stream.id() == stream_id);
+                if let Some(stream) = stream {
+                    self.remove_media_stream(stream).await
+                }
+            }
+            ControlMessage::Caption(cue) => self.handler(ReceiverEvent::Caption(cue)).await,
+        }
+    }
+
+    fn media_streams(&self) -> Vec<MediaStream> {
+        use wasm_bindgen::JsCast;
+
+        let mut streams = Vec::new();
+        self.js_media_streams.for_each(&mut |stream, _, _| {
+            if let Ok(stream) = stream.dyn_into() {
+                streams.push(stream);
+            }
+        });
+        streams
+    }
+
+    /// Watches the received track, the streams are checked when it is muted or ended,
+    /// since removed remote tracks are muted and removed from their streams.
+    fn watch_track(&self, track: &MediaStreamTrack) {
+        use wasm_bindgen::JsCast;
+
+        if let Some(handler) = self.js_track_state_handler.borrow().as_ref() {
+            track.set_onmute(Some(handler.as_ref().unchecked_ref()));
+            track.set_onended(Some(handler.as_ref().unchecked_ref()));
+        }
+    }
+
+    fn init_track_state_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_track_state_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.remove_ended_media_streams().await });
+                }
+            })
+        };
+        let prev_handler = self
+            .js_track_state_handler
+            .replace(Some(js_track_state_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    /// Removes the streams without live tracks.
+    async fn remove_ended_media_streams(self: &Arc<Self>) {
+        use wasm_bindgen::JsCast;
+        use web_sys::MediaStreamTrackState;
+
+        for stream in self.media_streams() {
+            let is_ended = stream.get_tracks().iter().all(|track| {
+                track.dyn_into::<MediaStreamTrack>().map_or(true, |track| {
+                    track.ready_state() == MediaStreamTrackState::Ended
+                })
+            });
+            if is_ended {
+                self.remove_media_stream(stream).await;
+            }
+        }
+    }
+
+    async fn remove_media_stream(self: &Arc<Self>, stream: MediaStream) {
+        use wasm_bindgen::JsCast;
+
+        if !self.js_media_streams.delete(&stream) {
+            return;
+        }
+        for track in stream.get_tracks().iter() {
+            let _: bool = self.js_media_tracks.delete(&track);
+            if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                track.set_onmute(None);
+                track.set_onended(None);
+            }
+        }
+        let _: Option<_> = self.stream_labels.borrow_mut().remove(&stream.id());
+        self.handler(ReceiverEvent::MediaStreamRemoved(stream))
+            .await
+    }
+
+    /// Label of the received media stream set by the sender, e.g. a camera or a screen.
+    pub fn stream_label(&self, stream_id: &str) -> Option<String> {
+        self.stream_labels.borrow().get(stream_id).cloned()
+    }
+
+    /// Returns stats of the received video track,
+    /// `None` if the track is not received yet.
+    pub(crate) async fn inbound_video_stats(
+        &self,
+        track: &MediaStreamTrack,
+        prev: Option<&InboundVideoStats>,
+    ) -> Result<Option<InboundVideoStats>, StatsError> {
+        use crate::stats::stats_report_values;
+
+        let stats = stats_report_values(
+            self.core
+                .js_connection()
+                .get_stats_with_selector(Some(track)),
+        )
+        .await?;
+        Ok(InboundVideoStats::from_stats(&stats, prev))
+    }
+
+    /// Key pair generated when the sender enables sealed signaling.
+    pub fn sealing_key_pair(&self) -> Option<SealingKeyPair> {
+        self.sealing_key_pair.borrow().clone()
+    }
+
+    /// Fingerprint of the sender key, `None` if sealed signaling is not used.
+    pub fn peer_fingerprint(&self) -> Option<String> {
+        self.sealed_session
+            .borrow()
+            .as_ref()
+            .map(|session| session.peer_fingerprint().to_owned())
+    }
+
+    /// Signs the session descriptions with the long-lived identity key,
+    /// so that the remote peer can verify them.
+    pub fn set_identity_key_pair(&self, key_pair: IdentityKeyPair) {
+        self.peer_verification.set_identity_key_pair(key_pair)
+    }
+
+    pub fn identity_key_pair(&self) -> Option<IdentityKeyPair> {
+        self.peer_verification.identity_key_pair()
+    }
+
+    /// Rejects unsigned remote session descriptions when enabled.
+    pub fn set_verified_peer_required(&self, is_required: bool) {
+        self.peer_verification
+            .set_verified_peer_required(is_required)
+    }
+
+    /// The sender whose signed session description was verified, `None` until then.
+    pub fn verified_peer(&self) -> Option<VerifiedPeer> {
+        self.peer_verification.verified_peer()
+    }
+
+    /// Responds to the sender key with a generated key,
+    /// the sender sends its sealed offer once it receives the key.
+    async fn on_peer_public_key(
+        self: &Arc<Self>,
+        public_key: PublicKey,
+    ) -> Result<(), ReceiverError> {
+        let key_pair = match self.sealing_key_pair() {
+            Some(key_pair) => key_pair,
+            None => {
+                let key_pair = SealingKeyPair::generate().await?;
+                let _: Option<_> = self.sealing_key_pair.replace(Some(key_pair.clone()));
+                key_pair
+            }
+        };
+        let session = key_pair.seal_with(&public_key).await?;
+        let fingerprint = session.peer_fingerprint().to_owned();
+        let _: Option<_> = self.sealed_session.replace(Some(session));
+        self.send_message(ClientReceiverMessage::PublishPublicKey(
+            key_pair.public_key().clone(),
+        ))
+        .map_err(ReceiverError::PublicKeySendError)?;
+        let local_fingerprint = key_pair.fingerprint().to_owned();
+        self.handler(ReceiverEvent::PeerKeyReceived {
+            fingerprint,
+            local_fingerprint,
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn seal_sdp(&self, sdp: SessionDescription) -> Result<SessionDescription, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.seal_sdp(sdp).await,
+            None => Ok(sdp),
+        }
+    }
+
+    async fn open_sdp(&self, sdp: SessionDescription) -> Result<SessionDescription, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.open_sdp(sdp).await,
+            None => Ok(sdp),
+        }
+    }
+
+    async fn seal_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<IceCandidate, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.seal_ice_candidate(ice_candidate).await,
+            None => Ok(ice_candidate),
+        }
+    }
+
+    async fn open_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<IceCandidate, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.open_ice_candidate(ice_candidate).await,
+            None => Ok(ice_candidate),
+        }
+    }
+
+    /// Local peer state sent to the sender.
+    pub fn peer_state(&self) -> PeerState {
+        self.local_peer_state.get()
+    }
+
+    /// Sets the local peer state, frequent changes are debounced.
+    /// The state is sent once the sender control channel is received.
+    pub fn set_peer_state(&self, state: PeerState) {
+        self.local_peer_state.set(state);
+        if let Some(control_channel) = self.control_channel.borrow().as_ref() {
+            control_channel.set_local_state(state);
+        }
+    }
+
     async fn on_track_event(self: &Arc<Self>, ev: RtcTrackEvent) {
         log::trace!("browser_webrtc::Receiver::on_track_event");
 
@@ -369,6 +847,9 @@ impl Receiver {
     async fn handle_track_event(self: &Arc<Self>, ev: RtcTrackEvent) -> Result<(), ReceiverError> {
         use wasm_bindgen::JsCast;
 
+        #[cfg(feature = "encoded-transform")]
+        self.pipe_encoded_transform(&ev)?;
+
         if ev.streams().iter().count() == 0 {
             if self.js_media_tracks.has(&ev.track()) {
                 return Ok(());
@@ -376,6 +857,7 @@ impl Receiver {
             let track = ev.track();
             let stream = MediaStream::new().map_err(ReceiverError::NewMediaStreamFailed)?;
             stream.add_track(&track);
+            self.watch_track(&track);
             let _: Set = self.js_media_streams.add(&stream);
             let _: Set = self.js_media_tracks.add(&track);
 
@@ -383,6 +865,10 @@ impl Receiver {
             self.handler(ReceiverEvent::MediaReceiver(media_receiver))
                 .await;
         } else {
+            // Tracks of the same stream arrive in separate events,
+            // the stream is reported once and the following tracks are only recorded.
+            let _: Set = self.js_media_tracks.add(&ev.track());
+            self.watch_track(&ev.track());
             for stream in ev.streams().iter() {
                 if self.js_media_streams.has(&stream) {
                     continue;
@@ -406,33 +892,185 @@ impl Receiver {
                 }
             }
         }
+        self.update_background_video();
         Ok(())
     }
 
-    async fn on_negotiation_needed_event(self: &Arc<Self>, ev: Event) {
+    #[cfg(feature = "encoded-transform")]
+    fn pipe_encoded_transform(&self, ev: &RtcTrackEvent) -> Result<(), ReceiverError> {
+        use crate::encoded_transform::is_encoded_transform_enabled;
+
+        if is_encoded_transform_enabled(self.core.js_connection()) {
+            let pipe = EncodedTransformPipe::new(&ev.receiver(), self.encoded_transform.clone())?;
+            self.js_encoded_transform_pipes.borrow_mut().push(pipe);
+        }
+        Ok(())
+    }
+
+    pub fn server(&self) -> &Arc<Server> {
+        &self.server
+    }
+
+    pub fn receiver_id(&self) -> SessionReceiverId {
+        self.receiver_id
+    }
+
+    /// Id of the joined channel, changed when the sender renames the channel.
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id.borrow().clone()
+    }
+
+    /// Receiver identifier unique across all servers of the page.
+    pub fn global_id(&self) -> GlobalReceiverId {
+        GlobalReceiverId {
+            server_id: self.server.server_id(),
+            receiver_id: self.receiver_id,
+        }
+    }
+
+    /// Sets the transformation of encoded frames of all received tracks.
+    ///
+    /// Requires the connection to be created with
+    /// `RtcConfigurationExt::with_encoded_insertable_streams`.
+    #[cfg(feature = "encoded-transform")]
+    pub fn set_encoded_transform(&self, transform: Option<EncodedFrameTransform>) {
+        self.encoded_transform.set(transform);
+    }
+
+    async fn on_negotiation_needed_event(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Sender::on_negotiation_needed_event");
 
-        match self.handle_negotiation_needed_event(ev).await {
+        match self.handle_negotiation_needed_event().await {
             Ok(()) => {}
             Err(err) => self.error(err).await,
         }
     }
 
-    async fn handle_negotiation_needed_event(&self, _: Event) -> Result<(), ReceiverError> {
-        self.send_answer().await?;
+    async fn handle_negotiation_needed_event(&self) -> Result<(), ReceiverError> {
+        self.send_offer().await?;
         Ok(())
     }
 
-    async fn on_ice_connection_state_change(self: &Arc<Self>, _: Event) {
+    async fn on_ice_connection_state_change(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Receiver::on_ice_connection_state_change");
 
-        self.handler(ReceiverEvent::IceConnectionStateChange(
-            self.ice_connection_state(),
-        ))
-        .await
+        let state = self.ice_connection_state();
+        self.session_metrics.set_state(state);
+        self.report_ice_milestone(state);
+        self.handler(ReceiverEvent::IceConnectionStateChange(state))
+            .await;
+        if matches!(
+            state,
+            RtcIceConnectionState::Connected | RtcIceConnectionState::Completed
+        ) {
+            self.update_sctp_limits().await
+        }
+        if state == RtcIceConnectionState::Failed {
+            self.on_network_change(NetworkChange::IceFailed).await
+        }
+    }
+
+    fn report_milestone(&self, milestone: CallMilestone) {
+        self.milestones
+            .report(&self.server, milestone, self.session_metrics.elapsed())
+    }
+
+    fn report_ice_milestone(&self, state: RtcIceConnectionState) {
+        match state {
+            RtcIceConnectionState::Connected | RtcIceConnectionState::Completed => {
+                self.report_milestone(CallMilestone::IceConnected)
+            }
+            RtcIceConnectionState::Failed
+                if !self.milestones.is_reported(CallMilestone::IceConnected) =>
+            {
+                self.report_milestone(CallMilestone::SetupFailed)
+            }
+            _ => {}
+        }
+    }
+
+    async fn on_network_change(self: &Arc<Self>, change: NetworkChange) {
+        log::trace!("browser_webrtc::Receiver::on_network_change");
+
+        self.handler(ReceiverEvent::NetworkChanged(change)).await;
+        match change {
+            NetworkChange::Online | NetworkChange::IceFailed => {
+                if let Err(err) = self.request_ice_restart() {
+                    self.error(ReceiverError::IceRestartRequestError(err)).await
+                }
+            }
+            NetworkChange::Offline => {}
+        }
+    }
+
+    async fn on_visibility_change(self: &Arc<Self>, visibility: PageVisibility) {
+        log::trace!("browser_webrtc::Receiver::on_visibility_change");
+
+        if self.page_visibility.replace(visibility) == visibility {
+            return;
+        }
+        self.update_background_video();
+        self.handler(ReceiverEvent::VisibilityChanged(visibility))
+            .await;
+    }
+
+    pub fn background_policy(&self) -> BackgroundPolicy {
+        self.background_policy.get()
+    }
+
+    /// Sets the media adjustments applied while the page is hidden,
+    /// the policy is applied immediately if the page is already hidden.
+    pub fn set_background_policy(&self, policy: BackgroundPolicy) {
+        self.background_policy.set(policy);
+        self.update_background_video();
+    }
+
+    pub fn candidate_preference(&self) -> CandidatePreference {
+        self.core.candidate_preference()
+    }
+
+    /// Sets the preference applied to the local candidates sent from now on,
+    /// whether the peer is on a LAN is advertised to the remote peer.
+    pub fn set_candidate_preference(
+        &self,
+        preference: CandidatePreference,
+    ) -> Result<(), ReceiverSendError> {
+        let capabilities = self.core.set_candidate_preference(preference);
+        self.send_message(ClientReceiverMessage::PublishCapabilities(capabilities))?;
+        self.capabilities.set(Some(capabilities));
+        Ok(())
+    }
+
+    pub fn page_visibility(&self) -> PageVisibility {
+        self.page_visibility.get()
+    }
+
+    /// Disables the enabled received video tracks while the page is hidden
+    /// and the policy requires it, otherwise enables the tracks disabled before.
+    ///
+    /// Audio tracks are kept alive and tracks paused by the user are left as is.
+    fn update_background_video(&self) {
+        use wasm_bindgen::JsCast;
+
+        let mut paused_tracks = self.js_background_paused_tracks.borrow_mut();
+        if self.page_visibility.get().is_hidden() && self.background_policy.get().pause_remote_video
+        {
+            self.js_media_tracks.for_each(&mut |track, _, _| {
+                if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                    if track.kind() == "video" && track.enabled() {
+                        track.set_enabled(false);
+                        paused_tracks.push(track);
+                    }
+                }
+            });
+        } else {
+            for track in paused_tracks.drain(..) {
+                track.set_enabled(true);
+            }
+        }
     }
 
-    async fn on_ice_gathering_state_change(self: &Arc<Self>, _: Event) {
+    async fn on_ice_gathering_state_change(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Receiver::on_ice_gathering_state_change");
 
         self.handler(ReceiverEvent::IceGatheringStateChange(
@@ -441,7 +1079,7 @@ impl Receiver {
         .await
     }
 
-    async fn on_signaling_state_change(self: &Arc<Self>, _: Event) {
+    async fn on_signaling_state_change(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Receiver::on_signaling_state_change");
 
         self.handler(ReceiverEvent::RtcSignalingStateChange(
@@ -450,41 +1088,338 @@ impl Receiver {
         .await
     }
 
+    /// Requests the sender to adjust the video quality, e.g. on a limited bandwidth.
+    pub fn request_quality(&self, request: QualityRequest) -> Result<(), ReceiverSendError> {
+        self.send_message(ClientReceiverMessage::RequestQuality(request))
+    }
+
+    /// Subscribes to the sender data tagged with the topic, it is received in `TopicData`.
+    pub fn subscribe_topic(&self, topic: Topic) -> Result<(), ReceiverSendError> {
+        self.send_message(ClientReceiverMessage::SubscribeTopic(topic.clone()))?;
+        let _: bool = self.topics.borrow_mut().insert(topic);
+        Ok(())
+    }
+
+    pub fn unsubscribe_topic(&self, topic: Topic) -> Result<(), ReceiverSendError> {
+        let _: bool = self.topics.borrow_mut().remove(&topic);
+        self.send_message(ClientReceiverMessage::UnsubscribeTopic(topic))
+    }
+
+    /// Requests the sender to restart ICE with fresh candidates.
+    ///
+    /// Does nothing if the connection was not negotiated yet.
+    pub fn request_ice_restart(&self) -> Result<(), ReceiverSendError> {
+        if self.core.js_connection().remote_description().is_none() {
+            return Ok(());
+        }
+        self.send_message(ClientReceiverMessage::RequestIceRestart)
+    }
+
+    /// Reports the SCTP limits once they become known after the connection is established.
+    async fn update_sctp_limits(self: &Arc<Self>) {
+        if self.sctp_limits.get().is_some() {
+            return;
+        }
+        if let Some(limits) = SctpLimits::from_connection(self.core.js_connection()) {
+            self.sctp_limits.set(Some(limits));
+            self.handler(ReceiverEvent::SctpLimitsKnown(limits)).await
+        }
+    }
+
+    /// Periodically samples the connection stats for the session metrics,
+    /// emits `UsageUpdated` on every sample
+    /// and `QualityChanged` when the smoothed score changes.
+    fn init_stats_monitor(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        use crate::quality::QUALITY_UPDATE_INTERVAL;
+        use crate::sleep;
+
+        let self_weak = Arc::downgrade(self);
+        spawn_local(async move {
+            loop {
+                sleep(QUALITY_UPDATE_INTERVAL).await;
+                match self_weak.upgrade() {
+                    Some(self_arc) => self_arc.update_stats().await,
+                    None => return,
+                }
+            }
+        });
+    }
+
+    async fn update_stats(self: &Arc<Self>) {
+        use crate::stats::stats_report_values;
+
+        let stats = match stats_report_values(self.core.js_connection().get_stats()).await {
+            Ok(stats) => stats,
+            Err(err) => {
+                log::warn!("{}", err);
+                return;
+            }
+        };
+        self.session_metrics.update_traffic(&stats);
+        self.handler(ReceiverEvent::UsageUpdated(self.session_metrics.usage()))
+            .await;
+        self.core.update_ice_timeline(&stats);
+        if !self
+            .milestones
+            .is_reported(CallMilestone::FirstFrameDecoded)
+            && is_frame_decoded(&stats)
+        {
+            self.report_milestone(CallMilestone::FirstFrameDecoded);
+        }
+        if self.check_session_limits().await {
+            return;
+        }
+
+        if !matches!(
+            self.ice_connection_state(),
+            RtcIceConnectionState::Connected | RtcIceConnectionState::Completed
+        ) {
+            return;
+        }
+        let quality = self.quality_monitor.borrow_mut().update(&stats);
+        if let Some(quality) = quality {
+            self.handler(ReceiverEvent::QualityChanged(quality)).await
+        }
+    }
+
+    /// Candidates and candidate pairs progress of the connection,
+    /// pairs are sampled from the connection stats.
+    pub fn ice_timeline(&self) -> IceTimeline {
+        self.core.ice_timeline()
+    }
+
+    /// Smoothed connection quality, `None` until the first stats sample.
+    pub fn quality(&self) -> Option<ConnectionQuality> {
+        self.quality_monitor.borrow().quality()
+    }
+
+    /// Session metrics of the connection,
+    /// traffic totals are as of the latest periodic stats sample.
+    pub fn summary(&self) -> SessionSummary {
+        self.session_metrics.summary()
+    }
+
+    /// Cumulative bytes of the session by the traffic kind,
+    /// media and data bytes are as of the latest periodic stats sample.
+    pub fn usage(&self) -> SessionUsage {
+        self.session_metrics.usage()
+    }
+
+    /// Limits the session length and inactivity, checked on every periodic stats sample.
+    pub fn set_session_policy(&self, policy: SessionPolicy) {
+        self.session_limiter.set_policy(policy);
+    }
+
+    pub fn session_policy(&self) -> SessionPolicy {
+        self.session_limiter.policy()
+    }
+
+    /// Returns `true` if the session is ended by the session policy.
+    async fn check_session_limits(self: &Arc<Self>) -> bool {
+        use crate::session_policy::SessionLimitAction;
+
+        let action = self.session_limiter.check(
+            self.session_metrics.elapsed(),
+            self.session_metrics.inactive_for(),
+        );
+        match action {
+            None => false,
+            Some(SessionLimitAction::Warn { reason, remaining }) => {
+                self.handler(ReceiverEvent::SessionEnding { reason, remaining })
+                    .await;
+                false
+            }
+            Some(SessionLimitAction::End(reason)) => {
+                log::info!(
+                    "receiver {:?} session ended: {:?}",
+                    self.receiver_id,
+                    reason
+                );
+                if self.close_once(CloseReason::Timeout) {
+                    self.handler(ReceiverEvent::SessionEnded(reason)).await;
+                    self.handler(ReceiverEvent::Closed {
+                        reason: CloseReason::Timeout,
+                        diagnostics: None,
+                    })
+                    .await;
+                }
+                true
+            }
+        }
+    }
+
+    /// Reports the session summary when enabled and exits the channel on the server once,
+    /// the close reason is relayed to the sender.
+    ///
+    /// Nothing is sent over a closing WebSocket, the server releases
+    /// the sessions of a disconnected client by itself.
+    fn exit_channel(&self) {
+        use crate::WebSocketState;
+
+        let is_websocket_open = matches!(
+            self.server.websocket_state(),
+            WebSocketState::Connecting | WebSocketState::Open
+        );
+        let reason = match self.teardown.take_close_message(is_websocket_open) {
+            Some(reason) => reason,
+            None => return,
+        };
+        if self.is_summary_reported.get() {
+            let summary = self.session_metrics.summary();
+            let _: Option<()> = self
+                .send_message(ClientReceiverMessage::ReportSessionSummary(summary))
+                .ok();
+        }
+        let _: Option<()> = self
+            .send_message(ClientReceiverMessage::ExitChannel(reason))
+            .ok();
+    }
+
+    /// Reports the session summary to the server on close when enabled,
+    /// so that operators can collect aggregate statistics.
+    pub fn set_summary_reporting(&self, is_enabled: bool) {
+        self.is_summary_reported.set(is_enabled);
+    }
+
+    /// Data channel limits negotiated by the connection,
+    /// `None` until the connection is established.
+    pub fn sctp_limits(&self) -> Option<SctpLimits> {
+        self.sctp_limits.get()
+    }
+
     pub fn ice_connection_state(&self) -> RtcIceConnectionState {
-        self.js_connection.ice_connection_state()
+        self.core.ice_connection_state()
     }
 
     pub fn ice_gathering_state(&self) -> RtcIceGatheringState {
-        self.js_connection.ice_gathering_state()
+        self.core.ice_gathering_state()
     }
 
     pub fn signaling_state(&self) -> RtcSignalingState {
-        self.js_connection.signaling_state()
+        self.core.signaling_state()
     }
 
     async fn receive_offer_and_send_answer(
         self: &Arc<Self>,
         remote_sdp: SessionDescription,
+        peer: Option<VerifiedPeer>,
     ) -> Result<(), ReceiveReceiveOfferAndSendAnswerError> {
         log::trace!("browser_webrtc::Receiver::receive_offer_and_send_answer");
 
-        use wasm_bindgen_futures::JsFuture;
-        use web_sys::{RtcSdpType, RtcSessionDescriptionInit};
+        use webrtc_core::{OfferCollision, RtcEngine, SdpType};
 
         use ReceiveReceiveOfferAndSendAnswerError as Event;
 
-        let mut remote_description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
-        let _: &mut _ = remote_description.sdp(&remote_sdp.0);
+        match self
+            .perfect_negotiation
+            .on_remote_offer(self.core.negotiation_state())
+        {
+            // The pending local offer is not set yet if it is still being created,
+            // setting it then fails and it is offered again after this negotiation.
+            OfferCollision::Rollback
+                if self.signaling_state() == RtcSignalingState::HaveLocalOffer =>
+            {
+                self.core
+                    .engine()
+                    .set_local_description(SdpType::Rollback, &SessionDescription(String::new()))
+                    .await
+                    .map_err(Event::RollbackError)?;
+            }
+            OfferCollision::None | OfferCollision::Rollback | OfferCollision::Ignore => {}
+        }
 
-        let _: JsValue = JsFuture::from(
-            self.js_connection
-                .set_remote_description(&remote_description),
-        )
-        .await
-        .map_err(Event::SetRemoteDescriptionError)?;
+        self.core
+            .engine()
+            .set_remote_description(SdpType::Offer, &remote_sdp)
+            .await
+            .map_err(Event::SetRemoteDescriptionError)?;
+        self.core.on_remote_description(self).await;
+        // Streams of the tracks removed by the offer are emptied.
+        self.remove_ended_media_streams().await;
+
+        if let Some(peer) = peer {
+            if let Some(peer) = self
+                .peer_verification
+                .bind(self.core.js_connection(), peer)?
+            {
+                self.handler(ReceiverEvent::VerifiedPeer(peer)).await;
+            }
+        }
 
         self.send_answer().await?;
-        self.init_negotiation_needed_handler();
+        self.core.init_negotiation_needed_handler(self);
+
+        Ok(())
+    }
+
+    /// Offers the changes of the connection made after the first negotiation,
+    /// e.g. the tracks added to it.
+    async fn send_offer(&self) -> Result<(), ReceiverSendOfferError> {
+        log::trace!("browser_webrtc::Receiver::send_offer");
+
+        if self.signaling_state() != RtcSignalingState::Stable {
+            // The negotiation needed event is fired again once the signaling is stable.
+            return Ok(());
+        }
+
+        self.perfect_negotiation.set_making_offer(true);
+        let sdp = self.set_local_offer().await;
+        self.perfect_negotiation.set_making_offer(false);
+        let sdp = match sdp {
+            Ok(sdp) => sdp,
+            // The offer is superseded by the colliding sender offer.
+            Err(_) if self.signaling_state() == RtcSignalingState::HaveRemoteOffer => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        let sdp = self.peer_verification.sign(sdp).await?;
+        let sdp = self.seal_sdp(sdp).await?;
+        self.send_acked_message(ClientReceiverMessage::SendOffer(sdp))?
+            .delivered()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_local_offer(&self) -> Result<SessionDescription, ReceiverSendOfferError> {
+        use js_sys::Reflect;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::RtcSessionDescriptionInit;
+
+        use ReceiverSendOfferError as Event;
+
+        let offer = JsFuture::from(self.core.js_connection().create_offer())
+            .await
+            .map_err(Event::CreateOfferError)?;
+
+        let offer: &RtcSessionDescriptionInit = offer.as_ref().unchecked_ref();
+
+        let _: JsValue = JsFuture::from(self.core.js_connection().set_local_description(offer))
+            .await
+            .map_err(Event::SetLocalDescriptionError)?;
+
+        Reflect::get(offer, &JsValue::from_str("sdp"))
+            .ok()
+            .and_then(|sdp| sdp.as_string())
+            .map(SessionDescription)
+            .ok_or(Event::OfferSdpIsMissing)
+    }
+
+    async fn receive_answer(
+        self: &Arc<Self>,
+        remote_sdp: SessionDescription,
+    ) -> Result<(), ReceiverReceiveAnswerError> {
+        use webrtc_core::{RtcEngine, SdpType};
+
+        self.core
+            .engine()
+            .set_remote_description(SdpType::Answer, &remote_sdp)
+            .await
+            .map_err(ReceiverReceiveAnswerError::SetRemoteDescriptionError)?;
+        self.core.on_remote_description(self).await;
 
         Ok(())
     }
@@ -499,41 +1434,87 @@ impl Receiver {
 
         use ReceiveReceiveOfferAndSendAnswerError as Event;
 
-        let offer = JsFuture::from(self.js_connection.create_answer())
+        let offer = JsFuture::from(self.core.js_connection().create_answer())
             .await
             .map_err(Event::CreateAnswerError)?;
 
         let offer: &RtcSessionDescriptionInit = offer.as_ref().unchecked_ref();
 
-        let _: JsValue = JsFuture::from(self.js_connection.set_local_description(&offer))
+        let _: JsValue = JsFuture::from(self.core.js_connection().set_local_description(&offer))
             .await
             .map_err(Event::SetLocalDescriptionError)?;
 
         let local_sdp = Reflect::get(&offer, &JsValue::from_str("sdp"))
-            .unwrap()
-            .as_string()
-            .unwrap();
-
-        self.send_message(ClientReceiverMessage::SendAnswer(SessionDescription(
-            local_sdp,
-        )))?;
+            .ok()
+            .and_then(|sdp| sdp.as_string())
+            .ok_or(Event::AnswerSdpIsMissing)?;
+
+        let sdp = self
+            .peer_verification
+            .sign(SessionDescription(local_sdp))
+            .await?;
+        let sdp = self.seal_sdp(sdp).await?;
+        self.send_acked_message(ClientReceiverMessage::SendAnswer(sdp))?
+            .delivered()
+            .await?;
 
         Ok(())
     }
 }
 
+impl PeerConnectionRole for Receiver {
+    fn core(&self) -> &PeerConnectionCore {
+        &self.core
+    }
+
+    async fn on_peer_connection_event(self: &Arc<Self>, ev: PeerConnectionEvent) {
+        use webrtc_core::IceCandidateRecoveryAction;
+        use PeerConnectionEvent as Ev;
+
+        match ev {
+            Ev::LocalIceCandidate(ice_candidate) => {
+                self.on_local_candidate(LocalCandidate::IceCandidate(ice_candidate))
+                    .await
+            }
+            Ev::LocalIceCandidatesGathered => {
+                self.on_local_candidate(LocalCandidate::AllSent).await
+            }
+            Ev::NegotiationNeeded => self.on_negotiation_needed_event().await,
+            Ev::IceConnectionStateChange => self.on_ice_connection_state_change().await,
+            Ev::IceGatheringStateChange => self.on_ice_gathering_state_change().await,
+            Ev::SignalingStateChange => self.on_signaling_state_change().await,
+            Ev::NetworkChanged(change) => self.on_network_change(change).await,
+            Ev::VisibilityChanged(visibility) => self.on_visibility_change(visibility).await,
+            Ev::IceCandidateRetriesExhausted { action, err } => match action {
+                IceCandidateRecoveryAction::RequestResend => {
+                    log::warn!("ice candidate retries exhausted, requesting candidates again");
+                    if let Err(err) = self.send_message(ClientReceiverMessage::RequestIceCandidates)
+                    {
+                        self.error(ReceiverError::IceCandidatesRequestError(err))
+                            .await
+                    }
+                }
+                IceCandidateRecoveryAction::AwaitResend => {}
+                IceCandidateRecoveryAction::Fail => {
+                    self.error(ReceiverError::AddIceCandidateError(err)).await
+                }
+            },
+        }
+    }
+}
+
 impl Drop for Receiver {
     fn drop(&mut self) {
         use wasm_bindgen_futures::spawn_local;
 
         log::trace!("browser_webrtc::Receiver::drop");
 
-        self.js_connection.set_onicecandidate(None);
-        self.js_connection.close();
+        self.core.close();
+        drop(self.control_channel.take());
 
         let server = Arc::clone(&self.server);
         let receiver_id = self.receiver_id;
-        let _: Option<()> = self.send_message(ClientReceiverMessage::ExitChannel).ok();
+        self.exit_channel();
         spawn_local(async move { server.on_receiver_dropped(receiver_id).await });
     }
 }
@@ -542,12 +1523,64 @@ impl Drop for Receiver {
 pub enum ReceiverEvent {
     ServerMessage(ServerReceiverMessage),
     DataReceiver(DataReceiverBuilder),
+    /// A file is sent by the sender with `Sender::send_file`.
+    FileReceiver(FileReceiverBuilder),
     MediaReceiver(MediaReceiverBuilder),
     IceConnectionStateChange(RtcIceConnectionState),
     IceGatheringStateChange(RtcIceGatheringState),
     RtcSignalingStateChange(RtcSignalingState),
+    NetworkChanged(NetworkChange),
+    /// The page is hidden or shown, the background policy is already applied.
+    VisibilityChanged(PageVisibility),
+    SctpLimitsKnown(SctpLimits),
+    /// The smoothed connection quality score is changed.
+    QualityChanged(ConnectionQuality),
+    /// The session usage is sampled, emitted periodically.
+    UsageUpdated(SessionUsage),
+    PeerStateChanged(PeerState),
+    /// The sender stopped or restored its video because of the poor connection,
+    /// see `AudioFallbackPolicy`.
+    PeerAudioOnlyChanged(bool),
+    /// All tracks of the stream are removed or ended, its views can be removed.
+    MediaStreamRemoved(MediaStream),
+    /// Labels of the received media streams by the stream id, see `Receiver::stream_label`.
+    StreamLabelsChanged(HashMap<String, String>),
+    /// Caption cue sent by the sender, see `MediaView::show_caption`.
+    Caption(CaptionCue),
+    /// The sender enabled sealed signaling,
+    /// the fingerprint of its key can be verified out-of-band.
+    PeerKeyReceived {
+        fingerprint: String,
+        local_fingerprint: String,
+    },
+    /// The sender identity signature and DTLS fingerprint binding are verified.
+    VerifiedPeer(VerifiedPeer),
+    /// Requested by the sender, sent before the offer.
+    PeerNetwork(PeerNetwork),
     JoinChannelSuccess,
     BinaryData(Vec<u8>),
+    /// Data of a subscribed topic relayed by the server.
+    TopicData {
+        topic: Topic,
+        data: Vec<u8>,
+    },
+    ChannelSnapshot(MediaSnapshot),
+    /// The sender renamed the channel, the session is not affected.
+    ChannelRenamed(ChannelId),
+    /// The receiver is closed, this is the last event of the receiver.
+    /// The diagnostics are set if it is closed by a protocol violation,
+    /// see `ProtocolViolationPolicy::Strict`.
+    Closed {
+        reason: CloseReason,
+        diagnostics: Option<ProtocolViolationDiagnostics>,
+    },
+    /// The session will be ended by the session policy unless activity resumes.
+    SessionEnding {
+        reason: SessionEndReason,
+        remaining: Duration,
+    },
+    /// The session is ended by the session policy, the channel is exited.
+    SessionEnded(SessionEndReason),
     Error(ReceiverError),
 }
 
@@ -556,21 +1589,43 @@ pub enum ReceiverError {
     //#[error("client message send error: {0}")]
     //SendError(#[from] WebSocketClientMessageSendError),
     #[error("client message send error: {0}")]
-    IceCandidateSendError(WebSocketClientMessageSendError),
-    #[error("channel id is not exist: {0:?}")]
+    IceCandidateSendError(ReceiverSendError),
+    #[error("ice candidate delivery error: {0}")]
+    IceCandidateDeliveryError(DeliveryError),
+    #[error("{}", ErrorCode::ChannelIsNotExist.describe(Some(.0), Language::English))]
     ChannelIsNotExist(ChannelId),
-    #[error("channel id is already occupied: {0:?}")]
+    #[error("{}", ErrorCode::ChannelIsAlreadyOccupied.describe(Some(.0), Language::English))]
     ChannelIsAlreadyOccupied(ChannelId),
-    #[error("new RtcIceCandidate error: {}", 0.0)]
-    NewRtcIceCandidateError(JsValue),
+    /// The channel is opened with a secret, the UI can ask for it and join again.
+    #[error("{}", ErrorCode::InvalidChannelCredentials.describe(Some(.0), Language::English))]
+    InvalidChannelCredentials(ChannelId),
+    #[error("{}", ErrorCode::NegotiationTimedOut.describe(Some(.0), Language::English))]
+    NegotiationTimedOut(ChannelId),
     #[error("add ice candidate error: {}", 0.0)]
     AddIceCandidateError(JsValue),
+    #[error("ice candidates request error: {0}")]
+    IceCandidatesRequestError(ReceiverSendError),
     #[error(transparent)]
     ReceiveReceiveOfferAndSendAnswer(#[from] ReceiveReceiveOfferAndSendAnswerError),
+    #[error(transparent)]
+    SendOfferError(#[from] ReceiverSendOfferError),
+    #[error(transparent)]
+    ReceiveAnswerError(#[from] ReceiverReceiveAnswerError),
     #[error("inalid MediaStream received in track event: {}", 0.0)]
     InvalidTrackEventMediaStream(JsValue),
     #[error("new MediaStream error: {}", 0.0)]
     NewMediaStreamFailed(JsValue),
+    #[error("ICE restart request error: {0}")]
+    IceRestartRequestError(ReceiverSendError),
+    #[error("public key send error: {0}")]
+    PublicKeySendError(ReceiverSendError),
+    #[error(transparent)]
+    SealingError(#[from] SealingError),
+    #[error("sender verification failed: {0}")]
+    VerificationError(#[from] VerificationError),
+    #[cfg(feature = "encoded-transform")]
+    #[error(transparent)]
+    EncodedTransformError(#[from] EncodedTransformError),
 }
 
 #[derive(Error, Debug)]
@@ -583,14 +1638,50 @@ pub enum NewReceiverError {
 
 #[derive(Error, Debug)]
 pub enum ReceiveReceiveOfferAndSendAnswerError {
+    #[error("rollback of the colliding local offer error: {0:?}")]
+    RollbackError(JsValue),
     #[error("set_remote_description error: {0:?}")]
     SetRemoteDescriptionError(JsValue),
     #[error("create_answer error: {0:?}")]
     CreateAnswerError(JsValue),
     #[error("set_local_description error: {0:?}")]
     SetLocalDescriptionError(JsValue),
+    #[error("created answer has no sdp")]
+    AnswerSdpIsMissing,
     #[error("answer send error: {0}")]
     SendError(#[from] ReceiverSendError),
+    #[error("answer delivery error: {0}")]
+    DeliveryError(#[from] DeliveryError),
+    #[error(transparent)]
+    SealingError(#[from] SealingError),
+    #[error("sender verification failed: {0}")]
+    VerificationError(#[from] VerificationError),
+    #[error("answer signing error: {0}")]
+    IdentityError(#[from] IdentityError),
+}
+
+#[derive(Error, Debug)]
+pub enum ReceiverSendOfferError {
+    #[error("create_offer error: {0:?}")]
+    CreateOfferError(JsValue),
+    #[error("set_local_description error: {0:?}")]
+    SetLocalDescriptionError(JsValue),
+    #[error("created offer has no sdp")]
+    OfferSdpIsMissing,
+    #[error("offer send error: {0}")]
+    SendError(#[from] ReceiverSendError),
+    #[error("offer delivery error: {0}")]
+    DeliveryError(#[from] DeliveryError),
+    #[error(transparent)]
+    SealingError(#[from] SealingError),
+    #[error("offer signing error: {0}")]
+    IdentityError(#[from] IdentityError),
+}
+
+#[derive(Error, Debug)]
+pub enum ReceiverReceiveAnswerError {
+    #[error("set_remote_description error: {0:?}")]
+    SetRemoteDescriptionError(JsValue),
 }
 
 #[derive(Error, Debug)]
@@ -604,3 +1695,13 @@ pub enum ReceiverIceCandidateError {
     #[error("client message send error: {0}")]
     SendError(#[from] WebSocketClientMessageSendError),
 }
+
+/// Returns `true` if any inbound video stream has decoded frames.
+fn is_frame_decoded(stats: &[JsValue]) -> bool {
+    use crate::stats::{stat_f64, stat_string};
+
+    stats.iter().any(|stat| {
+        stat_string(stat, "type").as_deref() == Some("inbound-rtp")
+            && stat_f64(stat, "framesDecoded").unwrap_or(0.0) > 0.0
+    })
+}
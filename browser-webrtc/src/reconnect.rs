@@ -0,0 +1,42 @@
+use core::time::Duration;
+
+/// Reconnection of a `Server` whose signaling WebSocket is closed.
+///
+/// Attempts are delayed with exponential backoff, each attempt tries the endpoints
+/// in order of priority. Live senders and receivers open and join their channels again
+/// once the connection is restored.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt, doubled after every failed attempt.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Attempts before giving up, `None` to retry until the server is dropped.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before the attempt, attempts are numbered from `1`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1_u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    pub fn is_exhausted(&self, attempts: u32) -> bool {
+        self.max_attempts
+            .is_some_and(|max_attempts| attempts >= max_attempts)
+    }
+}
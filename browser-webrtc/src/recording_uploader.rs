@@ -0,0 +1,187 @@
+use core::time::Duration;
+
+use async_std::sync::Arc;
+use thiserror::Error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+use web_sys::{Blob, BlobEvent, Event, MediaRecorder, MediaStream};
+
+use crate::{Sender, SenderSendError};
+
+/// The server rejects the client frames of 4 MiB and more,
+/// the chunks of a second are far below that at the usual bitrates.
+pub const DEFAULT_RECORDING_TIME_SLICE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingOptions {
+    /// Container and codecs, e.g. `video/webm;codecs=vp8,opus`,
+    /// the browser default is used if not set.
+    pub mime_type: Option<String>,
+    /// Total audio and video bitrate in bits per second.
+    pub bits_per_second: Option<u32>,
+    /// Duration of the media in every uploaded chunk.
+    pub time_slice: Duration,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        Self {
+            mime_type: None,
+            bits_per_second: None,
+            time_slice: DEFAULT_RECORDING_TIME_SLICE,
+        }
+    }
+}
+
+/// Records the media stream with `MediaRecorder` and uploads the chunks
+/// to the signaling server, which stores them per channel.
+///
+/// The server reports the result in `SenderEvent::RecordingStarted`,
+/// `SenderEvent::RecordingStopped` or `SenderEvent::RecordingFailed`,
+/// the uploader should be stopped once the recording failed.
+///
+/// The recorder is stopped on drop, the remaining chunks are still uploaded.
+#[derive(Debug)]
+pub struct RecordingUploader {
+    js_recorder: MediaRecorder,
+}
+
+/// Recorder event handlers, kept by the upload task until the recorder is stopped.
+struct RecorderHandlers {
+    _js_data_available_handler: Closure<dyn FnMut(BlobEvent)>,
+    _js_stop_handler: Closure<dyn FnMut(Event)>,
+}
+
+enum Upload {
+    Chunk(Blob),
+    Stop,
+}
+
+impl RecordingUploader {
+    pub fn start(
+        sender: &Arc<Sender>,
+        stream: &MediaStream,
+        options: RecordingOptions,
+    ) -> Result<Self, RecordingUploaderError> {
+        use crate::closure_1;
+        use core::convert::TryFrom;
+        use wasm_bindgen::JsCast;
+        use web_sys::MediaRecorderOptions;
+
+        let recorder_options = MediaRecorderOptions::new();
+        if let Some(mime_type) = &options.mime_type {
+            if !MediaRecorder::is_type_supported(mime_type) {
+                return Err(RecordingUploaderError::UnsupportedMimeType(
+                    mime_type.clone(),
+                ));
+            }
+            recorder_options.set_mime_type(mime_type);
+        }
+        if let Some(bits_per_second) = options.bits_per_second {
+            recorder_options.set_bits_per_second(bits_per_second);
+        }
+        let js_recorder = MediaRecorder::new_with_media_stream_and_media_recorder_options(
+            stream,
+            &recorder_options,
+        )
+        .map_err(RecordingUploaderError::RecorderCreateError)?;
+
+        // Chunks are converted to bytes asynchronously,
+        // the queue keeps them in order and sends the stop after the last one.
+        let (upload_sender, upload_receiver) = async_std::channel::unbounded();
+        let js_data_available_handler = {
+            let upload_sender = upload_sender.clone();
+            closure_1(move |ev: BlobEvent| {
+                if let Some(blob) = ev.data().filter(|blob| blob.size() > 0.0) {
+                    let _: Result<(), _> = upload_sender.try_send(Upload::Chunk(blob));
+                }
+            })
+        };
+        let js_stop_handler = closure_1(move |_: Event| {
+            let _: Result<(), _> = upload_sender.try_send(Upload::Stop);
+            let _: bool = upload_sender.close();
+        });
+        js_recorder.set_ondataavailable(Some(js_data_available_handler.as_ref().unchecked_ref()));
+        js_recorder.set_onstop(Some(js_stop_handler.as_ref().unchecked_ref()));
+
+        let mime_type = options.mime_type.unwrap_or_else(|| js_recorder.mime_type());
+        sender.start_recording(mime_type)?;
+        let time_slice = i32::try_from(options.time_slice.as_millis()).unwrap_or(i32::MAX);
+        if let Err(err) = js_recorder.start_with_time_slice(time_slice) {
+            let _: Result<(), _> = sender.stop_recording();
+            return Err(RecordingUploaderError::RecorderStartError(err));
+        }
+        let handlers = RecorderHandlers {
+            _js_data_available_handler: js_data_available_handler,
+            _js_stop_handler: js_stop_handler,
+        };
+        spawn_uploads(Arc::downgrade(sender), upload_receiver, handlers);
+
+        Ok(Self { js_recorder })
+    }
+
+    /// Actual container and codecs of the recording.
+    pub fn mime_type(&self) -> String {
+        self.js_recorder.mime_type()
+    }
+
+    /// Stops the recorder, the server is asked to stop the recording
+    /// once the remaining chunks are uploaded.
+    pub fn stop(&self) {
+        let _: Result<(), _> = self.js_recorder.stop();
+    }
+}
+
+impl Drop for RecordingUploader {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn spawn_uploads(
+    sender: async_std::sync::Weak<Sender>,
+    uploads: async_std::channel::Receiver<Upload>,
+    handlers: RecorderHandlers,
+) {
+    use js_sys::Uint8Array;
+    use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+    // The queue is drained even if the uploads fail,
+    // the handlers must outlive the recorder events.
+    spawn_local(async move {
+        let _handlers = handlers;
+        let mut is_failed = false;
+        while let Ok(upload) = uploads.recv().await {
+            let sender = match sender.upgrade() {
+                Some(sender) if !is_failed => sender,
+                _ => continue,
+            };
+            let result = match upload {
+                Upload::Chunk(blob) => match JsFuture::from(blob.array_buffer()).await {
+                    Ok(buffer) => sender.send_recording_chunk(Uint8Array::new(&buffer).to_vec()),
+                    Err(err) => {
+                        log::error!("recording chunk read error: {:?}", err);
+                        Ok(())
+                    }
+                },
+                Upload::Stop => sender.stop_recording(),
+            };
+            if let Err(err) = result {
+                log::error!("recording upload error: {}", err);
+                is_failed = true;
+            }
+        }
+    });
+}
+
+#[derive(Error, Debug)]
+pub enum RecordingUploaderError {
+    #[error("recording MIME type is not supported: {0}")]
+    UnsupportedMimeType(String),
+    #[error("MediaRecorder create error: {0:?}")]
+    RecorderCreateError(JsValue),
+    #[error("MediaRecorder start error: {0:?}")]
+    RecorderStartError(JsValue),
+    #[error(transparent)]
+    SendError(#[from] SenderSendError),
+}
@@ -0,0 +1,163 @@
+use core::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use async_std::sync::{Arc, Weak};
+use signaling_protocol::{SessionReceiverId, SessionSenderId};
+use thiserror::Error;
+
+use crate::{Receiver, Sender, Server};
+
+thread_local! {
+    static GLOBAL_PEER_REGISTRY: Arc<PeerRegistry> = Arc::new(PeerRegistry::new());
+}
+
+/// Server identifier unique within the page.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ServerId(pub u32);
+
+/// Sender identifier unique across all servers of the page.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct GlobalSenderId {
+    pub server_id: ServerId,
+    pub sender_id: SessionSenderId,
+}
+
+/// Receiver identifier unique across all servers of the page.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct GlobalReceiverId {
+    pub server_id: ServerId,
+    pub receiver_id: SessionReceiverId,
+}
+
+/// Registry of all live servers, senders and receivers of the page.
+///
+/// Session sender and receiver ids are only unique within their server,
+/// the registry scopes them by the server id so that peers of concurrent servers
+/// can be enumerated and looked up without being mixed up.
+#[derive(Debug)]
+pub struct PeerRegistry {
+    next_server_id: Cell<u32>,
+    servers: RefCell<HashMap<ServerId, Weak<Server>>>,
+    senders: RefCell<HashMap<GlobalSenderId, Weak<Sender>>>,
+    receivers: RefCell<HashMap<GlobalReceiverId, Weak<Receiver>>>,
+}
+
+impl PeerRegistry {
+    fn new() -> Self {
+        Self {
+            next_server_id: Cell::new(0),
+            servers: RefCell::new(HashMap::new()),
+            senders: RefCell::new(HashMap::new()),
+            receivers: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Registry shared by all servers of the page.
+    pub fn global() -> Arc<Self> {
+        GLOBAL_PEER_REGISTRY.with(Arc::clone)
+    }
+
+    pub fn servers(&self) -> Vec<Arc<Server>> {
+        upgrade_all(&self.servers.borrow())
+    }
+
+    pub fn senders(&self) -> Vec<Arc<Sender>> {
+        upgrade_all(&self.senders.borrow())
+    }
+
+    pub fn receivers(&self) -> Vec<Arc<Receiver>> {
+        upgrade_all(&self.receivers.borrow())
+    }
+
+    pub fn server(&self, server_id: ServerId) -> Option<Arc<Server>> {
+        self.servers.borrow().get(&server_id)?.upgrade()
+    }
+
+    pub fn sender(&self, id: GlobalSenderId) -> Option<Arc<Sender>> {
+        self.senders.borrow().get(&id)?.upgrade()
+    }
+
+    pub fn receiver(&self, id: GlobalReceiverId) -> Option<Arc<Receiver>> {
+        self.receivers.borrow().get(&id)?.upgrade()
+    }
+
+    pub(crate) fn next_server_id(&self) -> ServerId {
+        let server_id = self.next_server_id.get();
+        self.next_server_id.set(server_id.wrapping_add(1));
+        ServerId(server_id)
+    }
+
+    pub(crate) fn register_server(&self, server: &Arc<Server>) {
+        let prev_server = self
+            .servers
+            .borrow_mut()
+            .insert(server.server_id(), Arc::downgrade(server));
+        debug_assert!(prev_server.is_none());
+    }
+
+    pub(crate) fn unregister_server(&self, server_id: ServerId) {
+        let _: Option<_> = self.servers.borrow_mut().remove(&server_id);
+    }
+
+    /// Registers the sender of the server,
+    /// senders created by another server are rejected.
+    pub(crate) fn register_sender(
+        &self,
+        server: &Arc<Server>,
+        sender: &Arc<Sender>,
+    ) -> Result<(), PeerRegistryError> {
+        let id = sender.global_id();
+        if !Arc::ptr_eq(sender.server(), server) {
+            return Err(PeerRegistryError::ForeignSender(id));
+        }
+        let mut senders = self.senders.borrow_mut();
+        if senders.get(&id).and_then(Weak::upgrade).is_some() {
+            return Err(PeerRegistryError::SenderIsAlreadyRegistered(id));
+        }
+        let _: Option<_> = senders.insert(id, Arc::downgrade(sender));
+        Ok(())
+    }
+
+    /// Registers the receiver of the server,
+    /// receivers created by another server are rejected.
+    pub(crate) fn register_receiver(
+        &self,
+        server: &Arc<Server>,
+        receiver: &Arc<Receiver>,
+    ) -> Result<(), PeerRegistryError> {
+        let id = receiver.global_id();
+        if !Arc::ptr_eq(receiver.server(), server) {
+            return Err(PeerRegistryError::ForeignReceiver(id));
+        }
+        let mut receivers = self.receivers.borrow_mut();
+        if receivers.get(&id).and_then(Weak::upgrade).is_some() {
+            return Err(PeerRegistryError::ReceiverIsAlreadyRegistered(id));
+        }
+        let _: Option<_> = receivers.insert(id, Arc::downgrade(receiver));
+        Ok(())
+    }
+
+    pub(crate) fn unregister_sender(&self, id: GlobalSenderId) {
+        let _: Option<_> = self.senders.borrow_mut().remove(&id);
+    }
+
+    pub(crate) fn unregister_receiver(&self, id: GlobalReceiverId) {
+        let _: Option<_> = self.receivers.borrow_mut().remove(&id);
+    }
+}
+
+fn upgrade_all<K, V>(peers: &HashMap<K, Weak<V>>) -> Vec<Arc<V>> {
+    peers.values().filter_map(Weak::upgrade).collect()
+}
+
+#[derive(Clone, Copy, Error, Debug)]
+pub enum PeerRegistryError {
+    #[error("sender `{0:?}` belongs to another server")]
+    ForeignSender(GlobalSenderId),
+    #[error("receiver `{0:?}` belongs to another server")]
+    ForeignReceiver(GlobalReceiverId),
+    #[error("sender `{0:?}` is already registered")]
+    SenderIsAlreadyRegistered(GlobalSenderId),
+    #[error("receiver `{0:?}` is already registered")]
+    ReceiverIsAlreadyRegistered(GlobalReceiverId),
+}
@@ -0,0 +1,41 @@
+use core::time::Duration;
+use std::collections::BTreeSet;
+
+use signaling_protocol::{ChannelRequestDeclineReason, PeerId};
+
+/// Handling of the incoming channel requests, enforced by the `Server`
+/// before they are reported to the application.
+///
+/// Everything is disabled by default, so every request rings until it is answered.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct IncomingRequestPolicy {
+    /// Declines every request with `ChannelRequestDeclineReason::DoNotDisturb`,
+    /// including the requests of the auto-accepted peers.
+    pub do_not_disturb: bool,
+    /// Requests from these peer ids are reported with `ServerEvent::IncomingRequestAutoAccepted`
+    /// instead of ringing. Peer ids are not verified by the server,
+    /// so the opened session should still verify the remote identity.
+    pub auto_accept: BTreeSet<PeerId>,
+    /// Ringing requests not answered within this time
+    /// are declined with `ChannelRequestDeclineReason::NoAnswer`.
+    pub ring_timeout: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IncomingRequestDecision {
+    Ring,
+    AutoAccept,
+    Decline(ChannelRequestDeclineReason),
+}
+
+impl IncomingRequestPolicy {
+    pub fn decide(&self, from: Option<&PeerId>) -> IncomingRequestDecision {
+        if self.do_not_disturb {
+            IncomingRequestDecision::Decline(ChannelRequestDeclineReason::DoNotDisturb)
+        } else if from.is_some_and(|peer_id| self.auto_accept.contains(peer_id)) {
+            IncomingRequestDecision::AutoAccept
+        } else {
+            IncomingRequestDecision::Ring
+        }
+    }
+}
@@ -6,6 +6,17 @@ pub fn default_rtc_configuration() -> RtcConfiguration {
 
 pub trait RtcConfigurationExt {
     fn with_google_stun_server(self) -> Self;
+
+    /// Removes the STUN and TURN servers, so that only the host candidates are gathered
+    /// and the same-network peers connect without waiting for the external servers.
+    ///
+    /// Combine with `CandidatePreference::is_host_only` and `Sender::request_peer_network`
+    /// to check that the peers are eligible for it.
+    fn with_lan_only(self) -> Self;
+
+    /// Allows encoded transforms to be set on senders and receivers of the connection.
+    #[cfg(feature = "encoded-transform")]
+    fn with_encoded_insertable_streams(self) -> Self;
 }
 
 impl RtcConfigurationExt for RtcConfiguration {
@@ -24,4 +35,27 @@ impl RtcConfigurationExt for RtcConfiguration {
 
         self
     }
+
+    fn with_lan_only(self) -> Self {
+        use js_sys::Array;
+
+        self.set_ice_servers(&Array::new());
+
+        self
+    }
+
+    #[cfg(feature = "encoded-transform")]
+    fn with_encoded_insertable_streams(self) -> Self {
+        use js_sys::Reflect;
+        use wasm_bindgen::JsValue;
+
+        let _: bool = Reflect::set(
+            &self,
+            &JsValue::from_str("encodedInsertableStreams"),
+            &JsValue::TRUE,
+        )
+        .unwrap_or(false);
+
+        self
+    }
 }
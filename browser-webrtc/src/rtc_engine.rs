@@ -0,0 +1,121 @@
+use signaling_protocol::{IceCandidate, SessionDescription};
+use wasm_bindgen::JsValue;
+use web_sys::RtcPeerConnection;
+use webrtc_core::{RtcEngine, SdpType};
+
+/// Browser `RTCPeerConnection` as the shared negotiation engine.
+#[derive(Clone, Debug)]
+pub struct BrowserRtcEngine {
+    js_connection: RtcPeerConnection,
+}
+
+impl BrowserRtcEngine {
+    pub fn new(js_connection: RtcPeerConnection) -> Self {
+        Self { js_connection }
+    }
+
+    pub fn js_connection(&self) -> &RtcPeerConnection {
+        &self.js_connection
+    }
+
+    /// Returns the candidates gathered so far for the current local description.
+    pub fn local_ice_candidates(&self) -> Vec<IceCandidate> {
+        use webrtc_core::sdp_ice_candidates;
+
+        match self.js_connection.local_description() {
+            Some(description) => sdp_ice_candidates(&description.sdp()),
+            None => Vec::new(),
+        }
+    }
+
+    async fn set_description(
+        &self,
+        is_local: bool,
+        sdp_type: SdpType,
+        sdp: &SessionDescription,
+    ) -> Result<(), JsValue> {
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::{RtcSdpType, RtcSessionDescriptionInit};
+
+        let mut description = RtcSessionDescriptionInit::new(match sdp_type {
+            SdpType::Offer => RtcSdpType::Offer,
+            SdpType::Answer => RtcSdpType::Answer,
+            SdpType::Rollback => RtcSdpType::Rollback,
+        });
+        let _: &mut _ = description.sdp(&sdp.0);
+        let promise = if is_local {
+            self.js_connection.set_local_description(&description)
+        } else {
+            self.js_connection.set_remote_description(&description)
+        };
+        let _: JsValue = JsFuture::from(promise).await?;
+        Ok(())
+    }
+}
+
+impl RtcEngine for BrowserRtcEngine {
+    type Error = JsValue;
+
+    async fn create_offer(&self, is_ice_restart: bool) -> Result<SessionDescription, JsValue> {
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::RtcOfferOptions;
+
+        let mut offer_options = RtcOfferOptions::new();
+        let _: &mut _ = offer_options.ice_restart(is_ice_restart);
+        let offer = JsFuture::from(
+            self.js_connection
+                .create_offer_with_rtc_offer_options(&offer_options),
+        )
+        .await?;
+        description_sdp(&offer)
+    }
+
+    async fn create_answer(&self) -> Result<SessionDescription, JsValue> {
+        use wasm_bindgen_futures::JsFuture;
+
+        let answer = JsFuture::from(self.js_connection.create_answer()).await?;
+        description_sdp(&answer)
+    }
+
+    async fn set_local_description(
+        &self,
+        sdp_type: SdpType,
+        sdp: &SessionDescription,
+    ) -> Result<(), JsValue> {
+        self.set_description(true, sdp_type, sdp).await
+    }
+
+    async fn set_remote_description(
+        &self,
+        sdp_type: SdpType,
+        sdp: &SessionDescription,
+    ) -> Result<(), JsValue> {
+        self.set_description(false, sdp_type, sdp).await
+    }
+
+    async fn add_ice_candidate(&self, ice_candidate: &IceCandidate) -> Result<(), JsValue> {
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::{RtcIceCandidate, RtcIceCandidateInit};
+
+        let mut candidate = RtcIceCandidateInit::new(&ice_candidate.candidate);
+        let _: &mut _ = candidate
+            .sdp_mid(ice_candidate.sdp_mid.as_deref())
+            .sdp_m_line_index(ice_candidate.sdp_m_line_index);
+        let candidate = RtcIceCandidate::new(&candidate)?;
+        let _: JsValue = JsFuture::from(
+            self.js_connection
+                .add_ice_candidate_with_opt_rtc_ice_candidate(Some(&candidate)),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn description_sdp(description: &JsValue) -> Result<SessionDescription, JsValue> {
+    use js_sys::Reflect;
+
+    Reflect::get(description, &JsValue::from_str("sdp"))?
+        .as_string()
+        .map(SessionDescription)
+        .ok_or_else(|| JsValue::from_str("session description sdp is missing"))
+}
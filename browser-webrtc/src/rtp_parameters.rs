@@ -0,0 +1,349 @@
+use signaling_protocol::QualityRequest;
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::RtcRtpSender;
+
+use crate::VideoThrottle;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RtpPriority {
+    VeryLow,
+    Low,
+    Medium,
+    High,
+}
+
+impl RtpPriority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::VeryLow => "very-low",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DegradationPreference {
+    MaintainFramerate,
+    MaintainResolution,
+    Balanced,
+}
+
+impl DegradationPreference {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MaintainFramerate => "maintain-framerate",
+            Self::MaintainResolution => "maintain-resolution",
+            Self::Balanced => "balanced",
+        }
+    }
+}
+
+/// Sending priorities of a media track, unset values are left as chosen by the browser.
+///
+/// `priority` and `network_priority` are applied to all encodings of the track,
+/// `network_priority` is used by browsers to set DSCP marking of outgoing packets.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RtpSendPriority {
+    pub priority: Option<RtpPriority>,
+    pub network_priority: Option<RtpPriority>,
+    pub degradation_preference: Option<DegradationPreference>,
+}
+
+impl RtpSendPriority {
+    /// Priority for latency-critical streams, e.g. screen sharing.
+    pub fn latency_critical() -> Self {
+        Self {
+            priority: Some(RtpPriority::High),
+            network_priority: Some(RtpPriority::High),
+            degradation_preference: Some(DegradationPreference::MaintainResolution),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) async fn apply(
+        &self,
+        js_rtc_rtp_sender: &RtcRtpSender,
+    ) -> Result<RtpSendPrioritySupport, RtpParametersError> {
+        use js_sys::{Array, Reflect};
+        use wasm_bindgen_futures::JsFuture;
+
+        let parameters = js_rtc_rtp_sender.get_parameters();
+        let encodings: Vec<JsValue> = Reflect::get(&parameters, &JsValue::from_str("encodings"))
+            .ok()
+            .filter(Array::is_array)
+            .map(|encodings| Array::from(&encodings).iter().collect())
+            .unwrap_or_default();
+
+        // Browsers report only the parameters they support,
+        // so missing properties are treated as unsupported and are not set.
+        let set = |object: &JsValue, name: &str, value: Option<&str>| match value {
+            Some(value) => {
+                if Reflect::has(object, &JsValue::from_str(name)).unwrap_or(false) {
+                    let _: bool =
+                        Reflect::set(object, &JsValue::from_str(name), &JsValue::from_str(value))
+                            .unwrap_or(false);
+                    RtpParameterSupport::Applied
+                } else {
+                    RtpParameterSupport::Unsupported
+                }
+            }
+            None => RtpParameterSupport::Unset,
+        };
+        let set_encodings = |name: &str, value: Option<&str>| {
+            encodings
+                .iter()
+                .map(|encoding| set(encoding, name, value))
+                .min()
+                .unwrap_or(match value {
+                    Some(_) => RtpParameterSupport::Unsupported,
+                    None => RtpParameterSupport::Unset,
+                })
+        };
+
+        let support = RtpSendPrioritySupport {
+            priority: set_encodings("priority", self.priority.map(RtpPriority::as_str)),
+            network_priority: set_encodings(
+                "networkPriority",
+                self.network_priority.map(RtpPriority::as_str),
+            ),
+            degradation_preference: set(
+                &parameters,
+                "degradationPreference",
+                self.degradation_preference
+                    .map(DegradationPreference::as_str),
+            ),
+        };
+
+        if support.is_any_applied() {
+            let _: JsValue =
+                JsFuture::from(js_rtc_rtp_sender.set_parameters_with_parameters(&parameters))
+                    .await
+                    .map_err(RtpParametersError::SetParametersError)?;
+        }
+
+        Ok(support)
+    }
+}
+
+/// Video sending quality adjusted on receivers quality requests.
+///
+/// Simulcast encodings above the selected layer are deactivated,
+/// a single encoding is downscaled twice for every layer below the highest one instead.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct VideoQuality {
+    /// Number of layers below the highest one, zero is the full quality.
+    pub layer_reduction: u8,
+    /// Maximum bitrate of every encoding in bits per second.
+    pub max_bitrate: Option<u32>,
+    pub max_framerate: Option<u32>,
+}
+
+impl VideoQuality {
+    pub const MAX_LAYER_REDUCTION: u8 = 2;
+
+    pub fn with_request(self, request: QualityRequest) -> Self {
+        match request {
+            QualityRequest::LowerLayer => Self {
+                layer_reduction: (self.layer_reduction + 1).min(Self::MAX_LAYER_REDUCTION),
+                ..self
+            },
+            QualityRequest::HigherLayer => Self {
+                layer_reduction: self.layer_reduction.saturating_sub(1),
+                ..self
+            },
+            QualityRequest::TargetBitrate(max_bitrate) => Self {
+                max_bitrate,
+                ..self
+            },
+        }
+    }
+
+    /// Applies the throttle limits on top of the requested quality.
+    pub fn throttled(self, throttle: VideoThrottle) -> Self {
+        Self {
+            max_bitrate: Some(
+                self.max_bitrate
+                    .map_or(throttle.max_bitrate, |max_bitrate| {
+                        max_bitrate.min(throttle.max_bitrate)
+                    }),
+            ),
+            max_framerate: Some(
+                self.max_framerate
+                    .map_or(throttle.max_framerate, |max_framerate| {
+                        max_framerate.min(throttle.max_framerate)
+                    }),
+            ),
+            ..self
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) async fn apply(
+        &self,
+        js_rtc_rtp_sender: &RtcRtpSender,
+    ) -> Result<(), RtpParametersError> {
+        use js_sys::{Array, Object, Reflect};
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        let parameters = js_rtc_rtp_sender.get_parameters();
+        let encodings: Vec<JsValue> = Reflect::get(&parameters, &JsValue::from_str("encodings"))
+            .ok()
+            .filter(Array::is_array)
+            .map(|encodings| Array::from(&encodings).iter().collect())
+            .unwrap_or_default();
+        if encodings.is_empty() {
+            return Ok(());
+        }
+
+        // Simulcast encodings are expected to be ordered from the lowest to the highest layer.
+        let active_count = encodings
+            .len()
+            .saturating_sub(usize::from(self.layer_reduction))
+            .max(1);
+        let set = |encoding: &JsValue, name: &str, value: JsValue| {
+            let _: bool = Reflect::set(encoding, &JsValue::from_str(name), &value).unwrap_or(false);
+        };
+        let set_or_delete = |encoding: &JsValue, name: &str, value: Option<u32>| match value {
+            Some(value) => set(encoding, name, JsValue::from_f64(f64::from(value))),
+            None => {
+                let _: bool = Reflect::delete_property(
+                    encoding.unchecked_ref::<Object>(),
+                    &JsValue::from_str(name),
+                )
+                .unwrap_or(false);
+            }
+        };
+        for (index, encoding) in encodings.iter().enumerate() {
+            if encodings.len() > 1 {
+                set(encoding, "active", JsValue::from_bool(index < active_count));
+            } else {
+                let scale = f64::from(1_u32 << self.layer_reduction);
+                set(encoding, "scaleResolutionDownBy", JsValue::from_f64(scale));
+            }
+            set_or_delete(encoding, "maxBitrate", self.max_bitrate);
+            set_or_delete(encoding, "maxFramerate", self.max_framerate);
+        }
+
+        let _: JsValue =
+            JsFuture::from(js_rtc_rtp_sender.set_parameters_with_parameters(&parameters))
+                .await
+                .map_err(RtpParametersError::SetParametersError)?;
+        Ok(())
+    }
+}
+
+/// The way a keyframe generation was requested.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyFrameRequestMethod {
+    /// The browser supports `RTCRtpSender.generateKeyFrame`.
+    GenerateKeyFrame,
+    /// Encodings were deactivated and activated again,
+    /// encoders start with a keyframe after reactivation.
+    EncodingReactivation,
+}
+
+pub(crate) async fn request_key_frame(
+    js_rtc_rtp_sender: &RtcRtpSender,
+) -> Result<KeyFrameRequestMethod, RtpParametersError> {
+    use js_sys::{Array, Function, Reflect};
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let generate_key_frame: Option<Function> =
+        Reflect::get(js_rtc_rtp_sender, &JsValue::from_str("generateKeyFrame"))
+            .ok()
+            .and_then(|method| method.dyn_into().ok());
+    if let Some(generate_key_frame) = generate_key_frame {
+        let promise: js_sys::Promise = generate_key_frame
+            .call0(js_rtc_rtp_sender)
+            .and_then(JsCast::dyn_into)
+            .map_err(RtpParametersError::GenerateKeyFrameError)?;
+        let _: JsValue = JsFuture::from(promise)
+            .await
+            .map_err(RtpParametersError::GenerateKeyFrameError)?;
+        return Ok(KeyFrameRequestMethod::GenerateKeyFrame);
+    }
+
+    for active in [false, true].iter() {
+        let parameters = js_rtc_rtp_sender.get_parameters();
+        if let Ok(encodings) = Reflect::get(&parameters, &JsValue::from_str("encodings")) {
+            for encoding in Array::from(&encodings).iter() {
+                let _: bool = Reflect::set(
+                    &encoding,
+                    &JsValue::from_str("active"),
+                    &JsValue::from_bool(*active),
+                )
+                .unwrap_or(false);
+            }
+        }
+        let _: JsValue =
+            JsFuture::from(js_rtc_rtp_sender.set_parameters_with_parameters(&parameters))
+                .await
+                .map_err(RtpParametersError::SetParametersError)?;
+    }
+    Ok(KeyFrameRequestMethod::EncodingReactivation)
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RtpParameterSupport {
+    Unsupported,
+    Unset,
+    Applied,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RtpSendPrioritySupport {
+    pub priority: RtpParameterSupport,
+    pub network_priority: RtpParameterSupport,
+    pub degradation_preference: RtpParameterSupport,
+}
+
+impl RtpSendPrioritySupport {
+    pub(crate) fn unsupported() -> Self {
+        Self {
+            priority: RtpParameterSupport::Unsupported,
+            network_priority: RtpParameterSupport::Unsupported,
+            degradation_preference: RtpParameterSupport::Unsupported,
+        }
+    }
+
+    pub(crate) fn min(self, other: Self) -> Self {
+        Self {
+            priority: self.priority.min(other.priority),
+            network_priority: self.network_priority.min(other.network_priority),
+            degradation_preference: self
+                .degradation_preference
+                .min(other.degradation_preference),
+        }
+    }
+
+    pub fn is_any_applied(&self) -> bool {
+        self.priority == RtpParameterSupport::Applied
+            || self.network_priority == RtpParameterSupport::Applied
+            || self.degradation_preference == RtpParameterSupport::Applied
+    }
+
+    pub fn is_any_unsupported(&self) -> bool {
+        self.priority == RtpParameterSupport::Unsupported
+            || self.network_priority == RtpParameterSupport::Unsupported
+            || self.degradation_preference == RtpParameterSupport::Unsupported
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RtpParametersError {
+    #[error("setParameters error: {0:?}")]
+    SetParametersError(JsValue),
+    #[error("generateKeyFrame error: {0:?}")]
+    GenerateKeyFrameError(JsValue),
+}
@@ -0,0 +1,41 @@
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+use web_sys::RtcPeerConnection;
+
+/// Message size that is safe to send before the SCTP limits are negotiated.
+pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 16 * 1024;
+
+/// Data channel limits negotiated by the SCTP transport of the connection.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SctpLimits {
+    /// Maximum data channel message size in bytes, `None` if it is unlimited.
+    pub max_message_size: Option<u32>,
+    /// Maximum number of simultaneously open data channels,
+    /// `None` if the browser does not report it.
+    pub max_channels: Option<u16>,
+}
+
+impl SctpLimits {
+    /// Reads the limits from `RTCPeerConnection.sctp`,
+    /// returns `None` if the SCTP transport is not established yet.
+    pub(crate) fn from_connection(js_connection: &RtcPeerConnection) -> Option<Self> {
+        let js_sctp = Reflect::get(js_connection, &JsValue::from_str("sctp")).ok()?;
+        if js_sctp.is_null() || js_sctp.is_undefined() {
+            return None;
+        }
+        let max_message_size = Reflect::get(&js_sctp, &JsValue::from_str("maxMessageSize"))
+            .ok()?
+            .as_f64()?;
+        let max_channels = Reflect::get(&js_sctp, &JsValue::from_str("maxChannels"))
+            .ok()
+            .and_then(|value| value.as_f64());
+        Some(Self {
+            max_message_size: if max_message_size.is_finite() {
+                Some(max_message_size.min(u32::MAX.into()) as u32)
+            } else {
+                None
+            },
+            max_channels: max_channels.map(|value| value.min(u16::MAX.into()) as u16),
+        })
+    }
+}
@@ -0,0 +1,222 @@
+use js_sys::{Object, Uint8Array};
+use signaling_protocol::{
+    CodecError, IceCandidate, PublicKey, SessionDescription, WireCodec, SEALED_PAYLOAD_PREFIX,
+};
+use thiserror::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CryptoKey, CryptoKeyPair};
+
+use crate::webcrypto::{
+    bytes, call, fill_random, from_hex, key_fingerprint, object, subtle, to_hex, usages,
+    WebCryptoError,
+};
+
+const NAMED_CURVE: &str = "P-256";
+const NONCE_LEN: usize = 12;
+/// Binds the ciphertexts to their purpose, so they can not be reused in another protocol.
+const ADDITIONAL_DATA: &[u8] = b"browser-webrtc sealed signaling";
+
+/// Long-lived or per-session key pair used to seal signaling payloads.
+#[derive(Clone, Debug)]
+pub struct SealingKeyPair {
+    js_private_key: CryptoKey,
+    public_key: PublicKey,
+    fingerprint: String,
+}
+
+impl SealingKeyPair {
+    /// Generates a new ECDH P-256 key pair, the private key is not extractable.
+    pub async fn generate() -> Result<Self, SealingError> {
+        let algorithm = object(&[
+            ("name", JsValue::from_str("ECDH")),
+            ("namedCurve", JsValue::from_str(NAMED_CURVE)),
+        ]);
+        let key_pair: CryptoKeyPair =
+            call(subtle()?.generate_key_with_object(&algorithm, false, &usages(&["deriveKey"])))
+                .await?
+                .unchecked_into();
+
+        let js_public_key = key_pair.get_public_key();
+        let public_key = PublicKey(bytes(
+            &call(subtle()?.export_key("raw", &js_public_key)).await?,
+        ));
+        let fingerprint = key_fingerprint(&public_key).await?;
+        Ok(Self {
+            js_private_key: key_pair.get_private_key(),
+            public_key,
+            fingerprint,
+        })
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// SHA-256 fingerprint of the public key for out-of-band verification.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// Derives the shared key with the remote peer.
+    pub(crate) async fn seal_with(
+        &self,
+        peer_key: &PublicKey,
+    ) -> Result<SealedSession, SealingError> {
+        let subtle = subtle()?;
+        let algorithm = object(&[
+            ("name", JsValue::from_str("ECDH")),
+            ("namedCurve", JsValue::from_str(NAMED_CURVE)),
+        ]);
+        let js_peer_key: CryptoKey = call(subtle.import_key_with_object(
+            "raw",
+            &Uint8Array::from(&peer_key.0[..]),
+            &algorithm,
+            true,
+            &usages(&[]),
+        ))
+        .await
+        .map_err(|_| SealingError::InvalidPeerKey)?
+        .unchecked_into();
+
+        let derive_algorithm = object(&[
+            ("name", JsValue::from_str("ECDH")),
+            ("public", js_peer_key.into()),
+        ]);
+        let key_type = object(&[
+            ("name", JsValue::from_str("AES-GCM")),
+            ("length", JsValue::from_f64(256.0)),
+        ]);
+        let js_key: CryptoKey = call(subtle.derive_key_with_object_and_object(
+            &derive_algorithm,
+            &self.js_private_key,
+            &key_type,
+            false,
+            &usages(&["encrypt", "decrypt"]),
+        ))
+        .await?
+        .unchecked_into();
+
+        Ok(SealedSession {
+            js_key,
+            peer_fingerprint: key_fingerprint(peer_key).await?,
+        })
+    }
+}
+
+/// Shared key of the local and the remote peer.
+#[derive(Clone, Debug)]
+pub(crate) struct SealedSession {
+    js_key: CryptoKey,
+    peer_fingerprint: String,
+}
+
+impl SealedSession {
+    pub(crate) fn peer_fingerprint(&self) -> &str {
+        &self.peer_fingerprint
+    }
+
+    pub(crate) async fn seal_sdp(
+        &self,
+        sdp: SessionDescription,
+    ) -> Result<SessionDescription, SealingError> {
+        Ok(SessionDescription(self.seal(sdp.0.as_bytes()).await?))
+    }
+
+    pub(crate) async fn open_sdp(
+        &self,
+        sdp: SessionDescription,
+    ) -> Result<SessionDescription, SealingError> {
+        let data = self.open(&sdp.0).await?;
+        Ok(SessionDescription(
+            String::from_utf8(data).map_err(|_| SealingError::InvalidPayload)?,
+        ))
+    }
+
+    /// Seals the whole candidate, so that its mid and line index are hidden too.
+    pub(crate) async fn seal_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<IceCandidate, SealingError> {
+        let data = WireCodec::DEFAULT.encode(&ice_candidate)?;
+        Ok(IceCandidate {
+            candidate: self.seal(&data).await?,
+            sdp_mid: None,
+            sdp_m_line_index: None,
+        })
+    }
+
+    pub(crate) async fn open_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<IceCandidate, SealingError> {
+        let data = self.open(&ice_candidate.candidate).await?;
+        Ok(WireCodec::DEFAULT.decode(&data)?)
+    }
+
+    async fn seal(&self, plaintext: &[u8]) -> Result<String, SealingError> {
+        let mut nonce = [0; NONCE_LEN];
+        fill_random(&mut nonce)?;
+
+        let ciphertext = bytes(
+            &call(subtle()?.encrypt_with_object_and_u8_array(
+                &cipher_params(&nonce),
+                &self.js_key,
+                plaintext,
+            ))
+            .await?,
+        );
+
+        Ok(format!(
+            "{}{}{}",
+            SEALED_PAYLOAD_PREFIX,
+            to_hex(&nonce),
+            to_hex(&ciphertext)
+        ))
+    }
+
+    /// Opens the sealed payload, unsealed or tampered payloads are rejected.
+    async fn open(&self, sealed: &str) -> Result<Vec<u8>, SealingError> {
+        let hex = sealed
+            .strip_prefix(SEALED_PAYLOAD_PREFIX)
+            .ok_or(SealingError::PayloadIsNotSealed)?;
+        let data = from_hex(hex)
+            .filter(|data| data.len() >= NONCE_LEN)
+            .ok_or(SealingError::InvalidPayload)?;
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+
+        let plaintext = call(subtle()?.decrypt_with_object_and_u8_array(
+            &cipher_params(nonce),
+            &self.js_key,
+            ciphertext,
+        ))
+        .await
+        .map_err(|_| SealingError::DecryptionFailed)?;
+        Ok(bytes(&plaintext))
+    }
+}
+
+fn cipher_params(nonce: &[u8]) -> Object {
+    object(&[
+        ("name", JsValue::from_str("AES-GCM")),
+        ("iv", Uint8Array::from(nonce).into()),
+        ("additionalData", Uint8Array::from(ADDITIONAL_DATA).into()),
+    ])
+}
+
+#[derive(Error, Debug)]
+pub enum SealingError {
+    #[error(transparent)]
+    WebCryptoError(#[from] WebCryptoError),
+    #[error("invalid peer public key")]
+    InvalidPeerKey,
+    #[error("peer public key is not received yet")]
+    PeerKeyIsNotReceived,
+    #[error("signaling payload is not sealed")]
+    PayloadIsNotSealed,
+    #[error("invalid sealed payload")]
+    InvalidPayload,
+    #[error("sealed payload decryption failed, it may have been tampered with")]
+    DecryptionFailed,
+    #[error(transparent)]
+    CodecError(#[from] CodecError),
+}
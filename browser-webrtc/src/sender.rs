@@ -1,121 +1,534 @@
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::sync::atomic::AtomicBool;
+use core::time::Duration;
 
-use async_std::sync::Arc;
+use std::rc::Rc;
+
+use async_std::sync::{Arc, Weak};
 use signaling_protocol::{
-    ChannelId, ClientMessage, ClientSenderMessage, NetworkMode, ServerSenderErrorMessage,
-    ServerSenderMessage, SessionDescription, SessionSenderId,
+    CallMilestone, ChannelId, ChannelPreview, ClientMessage, ClientSenderMessage, CloseReason,
+    ErrorCode, IceCandidate, IntendedMedia, Language, NetworkMode, PeerCapabilities, PeerNetwork,
+    PublicKey, QualityRequest, ReceiverInfo, RecordingErrorMessage, ResumeToken,
+    ServerSenderErrorMessage, ServerSenderMessage, SessionDescription, SessionSenderId,
+    SessionSummary, ThrottleNotice, Topic,
 };
 use thiserror::Error;
-use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsValue;
 use web_sys::{
-    Event, MediaStream, RtcConfiguration, RtcIceCandidate, RtcIceCandidateInit,
-    RtcIceConnectionState, RtcIceGatheringState, RtcPeerConnection, RtcPeerConnectionIceEvent,
-    RtcSignalingState, WebSocket,
+    Blob, MediaStream, RtcDataChannel, RtcIceConnectionState, RtcIceGatheringState,
+    RtcSignalingState,
+};
+use webrtc_core::{
+    CandidatePreference, LocalCandidate, NegotiationRole, PerfectNegotiation, SessionTeardown,
 };
 
+use crate::audio_fallback::AudioFallbackMonitor;
+use crate::identity::PeerVerification;
+use crate::live_objects::{LiveObject, LiveObjectKind};
+use crate::peer_connection::{PeerConnectionCore, PeerConnectionEvent, PeerConnectionRole};
+use crate::peer_state::{ControlChannel, ControlMessage};
+use crate::quality::QualityMonitor;
+use crate::sealed::SealedSession;
+use crate::session_metrics::{SessionMetrics, SessionUsage};
+use crate::session_policy::SessionLimiter;
+use crate::telemetry::MilestoneTracker;
 use crate::{
-    send_websocket_client_message, BoxAsyncFn2, BoxAsyncFn2Wrapper, DataSender, DataSenderEvent,
-    MediaSender, Server, WebSocketClientMessageSendError,
+    AudioFallbackPolicy, AudioFallbackReason, BackgroundPolicy, BoxAsyncFn2, BoxAsyncFn2Wrapper,
+    CaptionCue, CaptionSendError, ChannelAccess, ConnectionQuality, DataSender, DataSenderEvent,
+    Delivery, DeliveryError, FileSender, FileSenderEvent, GlobalSenderId, IceTimeline,
+    IdentityError, IdentityKeyPair, MediaSender, MediaSenderError, MediaSenderEvent, MediaSnapshot,
+    NetworkChange, OpenError, OpusParameters, PageVisibility, PeerState, ProtocolViolation,
+    ProtocolViolationDiagnostics, RtpParametersError, SctpLimits, SealingError, SealingKeyPair,
+    Server, SessionEndReason, SessionPolicy, TelemetrySource, VerificationError, VerifiedPeer,
+    VideoQuality, WarmConnection, WarmConnectionError, WebSocketClientMessageSendError,
 };
 
+/// Answer waiting policy for offers relayed to a joined receiver.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NegotiationTimeout {
+    pub answer_timeout: Duration,
+    /// Number of times the offer is re-sent before the negotiation is considered failed.
+    pub max_retries: u32,
+}
+
+impl Default for NegotiationTimeout {
+    fn default() -> Self {
+        Self {
+            answer_timeout: Duration::from_secs(10),
+            max_retries: 2,
+        }
+    }
+}
+
+type OpenResultSender = async_std::channel::Sender<Result<(), OpenError>>;
+
 #[derive(Debug)]
 pub struct Sender {
     server: Arc<Server>,
     sender_id: SessionSenderId,
+    channel_id: RefCell<Option<ChannelId>>,
+    /// Options the channel is opened with, reused to open it again after a reconnect.
+    open_options: RefCell<Option<(NetworkMode, ChannelAccess)>>,
+    /// Lets the channel reopened after a reconnect replace its stale server connection.
+    resume_token: Option<ResumeToken>,
+    /// The channel state published to the server, sent again when the channel is reopened.
+    capabilities: Cell<Option<PeerCapabilities>>,
+    preview: RefCell<Option<ChannelPreview>>,
+    scheduled_start: Cell<Option<Duration>>,
+    is_peer_network_requested: Cell<bool>,
     handler: BoxAsyncFn2Wrapper<Arc<Sender>, SenderEvent, ()>,
-    js_connection: RtcPeerConnection,
-    js_websocket: WebSocket,
-    js_ice_candidate_handler: RefCell<Option<Closure<dyn FnMut(RtcPeerConnectionIceEvent)>>>,
-    js_negotiation_needed_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
-    js_ice_connection_state_change_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
-    js_ice_gathering_state_change: RefCell<Option<Closure<dyn FnMut(Event)>>>,
-    js_signaling_state_change_change: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+    _live_object: LiveObject,
+    core: PeerConnectionCore,
+    page_visibility: Cell<PageVisibility>,
+    background_policy: Cell<BackgroundPolicy>,
+    sctp_limits: Cell<Option<SctpLimits>>,
+    quality_monitor: RefCell<QualityMonitor>,
+    audio_fallback: RefCell<Option<AudioFallbackMonitor>>,
+    session_metrics: SessionMetrics,
+    milestones: MilestoneTracker,
+    is_summary_reported: Cell<bool>,
+    session_limiter: SessionLimiter,
+    open_result_sender: OpenResultSender,
+    open_result_receiver: async_std::channel::Receiver<Result<(), OpenError>>,
+    is_open_awaited: Cell<bool>,
+    control_channel: RefCell<Option<Rc<ControlChannel>>>,
+    sealing_key_pair: RefCell<Option<SealingKeyPair>>,
+    sealed_session: RefCell<Option<SealedSession>>,
+    is_offer_deferred: Cell<bool>,
+    peer_verification: PeerVerification,
+    teardown: SessionTeardown,
+    opus_parameters: RefCell<Option<OpusParameters>>,
+    media_senders: RefCell<Vec<Weak<MediaSender>>>,
+    video_quality: Cell<VideoQuality>,
+    intended_media: Cell<Option<IntendedMedia>>,
+    caption_languages: RefCell<Vec<String>>,
+    is_quality_automatic: AtomicBool,
+    negotiation_timeout: Cell<Option<NegotiationTimeout>>,
+    negotiation_generation: Cell<u32>,
+    offer_retries: Cell<u32>,
     is_started: AtomicBool,
+    /// The local offer prepared by the connection warm-up is not sent yet.
+    is_offer_warm: Cell<bool>,
+    /// The sender is the impolite peer, the receiver offers colliding with its own are ignored.
+    perfect_negotiation: PerfectNegotiation,
 }
 
 impl Sender {
+    /// Creates the sender on top of the connection, the channel is opened separately
+    /// with `open_channel`, so the connection can be warmed up before it.
     pub fn new(
-        js_websocket: WebSocket,
         server: Arc<Server>,
         sender_id: SessionSenderId,
-        channel_id: ChannelId,
-        network_mode: NetworkMode,
         handler: BoxAsyncFn2<Arc<Self>, SenderEvent, ()>,
-        rtc_configuration: Option<RtcConfiguration>,
-    ) -> Result<Arc<Self>, NewSenderError> {
+        connection: WarmConnection,
+    ) -> Arc<Self> {
         log::trace!("browser_webrtc::Sender::new");
 
-        let message = ClientMessage::SenderMessage {
-            sender_id,
-            message: ClientSenderMessage::OpenChannel {
-                channel_id,
-                network_mode,
-            },
-        };
-        send_websocket_client_message(&js_websocket, message)?;
-
-        let js_connection = match rtc_configuration {
-            Some(config) => RtcPeerConnection::new_with_configuration(&config),
-            None => RtcPeerConnection::new(),
-        }
-        .map_err(NewSenderError::NewRtcPeerConnectionError)?;
+        let (core, js_control_channel, is_warmed_up) = connection.into_parts();
 
+        // The result is buffered, so the channel can be awaited after it is confirmed.
+        let (open_result_sender, open_result_receiver) = async_std::channel::bounded(1);
         let sender = Arc::new(Self {
             server,
             sender_id,
+            channel_id: RefCell::new(None),
+            open_options: RefCell::new(None),
+            resume_token: new_resume_token(),
+            capabilities: Cell::new(None),
+            preview: RefCell::new(None),
+            scheduled_start: Cell::new(None),
+            is_peer_network_requested: Cell::new(false),
             handler: BoxAsyncFn2Wrapper(handler),
-            js_connection: js_connection.clone(),
-            js_websocket,
-            js_ice_candidate_handler: RefCell::new(None),
-            js_negotiation_needed_handler: RefCell::new(None),
-            js_ice_connection_state_change_handler: RefCell::new(None),
-            js_ice_gathering_state_change: RefCell::new(None),
-            js_signaling_state_change_change: RefCell::new(None),
+            _live_object: LiveObject::new(LiveObjectKind::Sender),
+            core,
+            page_visibility: Cell::new(PageVisibility::current()),
+            background_policy: Cell::new(BackgroundPolicy::default()),
+            sctp_limits: Cell::new(None),
+            quality_monitor: RefCell::new(QualityMonitor::default()),
+            audio_fallback: RefCell::new(None),
+            session_metrics: SessionMetrics::new(),
+            milestones: MilestoneTracker::new(TelemetrySource::Sender(sender_id)),
+            is_summary_reported: Cell::new(false),
+            session_limiter: SessionLimiter::default(),
+            open_result_sender,
+            open_result_receiver,
+            is_open_awaited: Cell::new(false),
+            control_channel: RefCell::new(None),
+            sealing_key_pair: RefCell::new(None),
+            sealed_session: RefCell::new(None),
+            is_offer_deferred: Cell::new(false),
+            peer_verification: PeerVerification::default(),
+            teardown: SessionTeardown::default(),
+            opus_parameters: RefCell::new(None),
+            media_senders: RefCell::new(Vec::new()),
+            video_quality: Cell::new(VideoQuality::default()),
+            intended_media: Cell::new(None),
+            caption_languages: RefCell::new(Vec::new()),
+            is_quality_automatic: AtomicBool::new(false),
+            negotiation_timeout: Cell::new(Some(NegotiationTimeout::default())),
+            negotiation_generation: Cell::new(0),
+            offer_retries: Cell::new(0),
             is_started: AtomicBool::new(false),
+            is_offer_warm: Cell::new(is_warmed_up),
+            perfect_negotiation: PerfectNegotiation::new(NegotiationRole::Impolite),
         });
 
-        sender.init_icecandidate_handler();
-        sender.init_ice_connection_state_change_handler();
-        sender.init_ice_gathering_state_change_handler();
-        sender.init_signaling_state_change_handler();
+        sender.core.init_handlers(&sender);
+        sender.init_stats_monitor();
+        sender.init_control_channel(js_control_channel);
 
-        Ok(sender)
+        sender
     }
 
-    fn init_icecandidate_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
+    /// Sends the open channel message, the result is reported in sender events.
+    pub fn open_channel(
+        &self,
+        channel_id: ChannelId,
+        access: ChannelAccess,
+        network_mode: NetworkMode,
+    ) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::OpenChannel {
+            channel_id: channel_id.clone(),
+            network_mode,
+            claim_token: access.claim_token.clone(),
+            secret: access.secret.clone(),
+            resume_token: self.resume_token.clone(),
+        })?;
+        let _: Option<_> = self.channel_id.replace(Some(channel_id));
+        let _: Option<_> = self.open_options.replace(Some((network_mode, access)));
+        Ok(())
+    }
 
-        let js_ice_candidate_handler = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: RtcPeerConnectionIceEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_ice_candidate_event(ev).await });
-            })
-        };
-        self.js_connection
-            .set_onicecandidate(Some(js_ice_candidate_handler.as_ref().unchecked_ref()));
-        let prev_handler = self
-            .js_ice_candidate_handler
-            .replace(Some(js_ice_candidate_handler));
-        debug_assert!(prev_handler.is_none());
+    /// Opens the channel again on the reconnected server with its current id,
+    /// returns `false` if it is not opened or the sender is closed.
+    pub(crate) fn reopen_channel(&self) -> Result<bool, SenderSendError> {
+        let channel_id = self.channel_id.borrow().clone();
+        let open_options = self.open_options.borrow().clone();
+        match (channel_id, open_options) {
+            (Some(channel_id), Some((network_mode, access))) if !self.is_closed() => {
+                self.core.local_candidates().embargo();
+                self.send_message(ClientSenderMessage::OpenChannel {
+                    channel_id,
+                    network_mode,
+                    claim_token: access.claim_token,
+                    secret: access.secret,
+                    resume_token: self.resume_token.clone(),
+                })?;
+                self.republish_channel_state()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Sends the published channel state again, the reopened channel starts without it.
+    fn republish_channel_state(&self) -> Result<(), SenderSendError> {
+        use core::sync::atomic::Ordering;
+
+        if let Some(key_pair) = self.sealing_key_pair.borrow().as_ref() {
+            self.send_message(ClientSenderMessage::PublishPublicKey(
+                key_pair.public_key().clone(),
+            ))?;
+        }
+        if let Some(capabilities) = self.capabilities.get() {
+            self.send_message(ClientSenderMessage::PublishCapabilities(capabilities))?;
+        }
+        if let Some(preview) = self.preview.borrow().clone() {
+            self.send_message(ClientSenderMessage::PublishPreview(preview))?;
+        }
+        // The channel is listed as live once it is started, the schedule is outdated then.
+        let scheduled_start = self.scheduled_start.get();
+        if scheduled_start.is_some() && !self.is_started.load(Ordering::Relaxed) {
+            self.send_message(ClientSenderMessage::ScheduleStart(scheduled_start))?;
+        }
+        if self.is_peer_network_requested.get() {
+            self.send_message(ClientSenderMessage::RequestPeerNetwork)?;
+        }
+        Ok(())
+    }
+
+    /// Id of the channel, `None` until it is opened with `open_channel`.
+    pub fn channel_id(&self) -> Option<ChannelId> {
+        self.channel_id.borrow().clone()
     }
 
     #[must_use]
+    pub fn server(&self) -> &Arc<Server> {
+        &self.server
+    }
+
+    pub fn sender_id(&self) -> SessionSenderId {
+        self.sender_id
+    }
+
+    /// Sender identifier unique across all servers of the page.
+    pub fn global_id(&self) -> GlobalSenderId {
+        GlobalSenderId {
+            server_id: self.server.server_id(),
+            sender_id: self.sender_id,
+        }
+    }
+
     pub fn add_data_channel<T: AsRef<str>>(
         self: &Arc<Self>,
         name: T,
         handler: BoxAsyncFn2<Arc<DataSender>, DataSenderEvent, ()>,
     ) -> Arc<DataSender> {
-        DataSender::new(Arc::clone(self), self.js_connection.clone(), name, handler)
+        DataSender::new(
+            Arc::clone(self),
+            self.core.js_connection().clone(),
+            name,
+            handler,
+        )
+    }
+
+    /// Sends the file in its own data channel, see `FileSender`.
+    pub fn send_file(
+        self: &Arc<Self>,
+        file: Blob,
+        name: String,
+        handler: BoxAsyncFn2<Arc<FileSender>, FileSenderEvent, ()>,
+    ) -> Arc<FileSender> {
+        FileSender::new(
+            Arc::clone(self),
+            self.core.js_connection().clone(),
+            file,
+            name,
+            handler,
+        )
     }
 
     #[must_use]
-    pub fn add_media_stream(self: &Arc<Self>, media_stream: MediaStream) -> Arc<MediaSender> {
-        MediaSender::new(Arc::clone(self), self.js_connection.clone(), media_stream)
+    pub fn add_media_stream(
+        self: &Arc<Self>,
+        media_stream: MediaStream,
+        handler: BoxAsyncFn2<Arc<MediaSender>, MediaSenderEvent, ()>,
+    ) -> Arc<MediaSender> {
+        let media_sender = MediaSender::new(
+            Arc::clone(self),
+            self.core.js_connection().clone(),
+            media_stream,
+            handler,
+        );
+        if self.is_audio_only() {
+            use wasm_bindgen_futures::spawn_local;
+
+            let self_arc = Arc::clone(self);
+            let media_sender = Arc::clone(&media_sender);
+            spawn_local(async move {
+                if let Err(err) = media_sender.set_video_suspended(true).await {
+                    self_arc.error(SenderError::AudioFallbackError(err)).await
+                }
+            });
+        }
+        let mut media_senders = self.media_senders.borrow_mut();
+        media_senders.retain(|media_sender| media_sender.strong_count() > 0);
+        media_senders.push(Arc::downgrade(&media_sender));
+        // The warm offer has no media sections.
+        self.is_offer_warm.set(false);
+        media_sender
+    }
+
+    /// Removes the stream tracks from the connection, other streams are kept as is.
+    pub fn remove_media_stream(&self, media_sender: &MediaSender) {
+        media_sender.remove_all_tracks();
+        self.media_senders.borrow_mut().retain(|other| {
+            other.strong_count() > 0 && !core::ptr::eq(other.as_ptr(), media_sender)
+        });
+        if let Some(control_channel) = self.control_channel.borrow().as_ref() {
+            control_channel.send_stream_removed(&media_sender.media_stream().id());
+        }
+        self.update_stream_labels();
+    }
+
+    /// Media senders of the streams added to the connection.
+    pub fn media_senders(&self) -> Vec<Arc<MediaSender>> {
+        self.media_senders
+            .borrow()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    /// Sends the labels of the added streams to the receiver.
+    pub(crate) fn update_stream_labels(&self) {
+        let labels = self
+            .media_senders()
+            .iter()
+            .filter_map(|media_sender| {
+                let label = media_sender.label()?;
+                Some((media_sender.media_stream().id(), label))
+            })
+            .collect();
+        if let Some(control_channel) = self.control_channel.borrow().as_ref() {
+            control_channel.set_stream_labels(&labels);
+        }
+    }
+
+    /// Enables adjusting of video encodings on receivers quality requests,
+    /// requests are reported in `SenderEvent::QualityRequested` regardless of this setting.
+    pub fn set_quality_automatic(&self, is_quality_automatic: bool) {
+        use core::sync::atomic::Ordering;
+
+        self.is_quality_automatic
+            .store(is_quality_automatic, Ordering::Relaxed);
+    }
+
+    /// Adjusts video encodings of all media senders according to the receiver request.
+    pub async fn apply_quality_request(
+        &self,
+        request: QualityRequest,
+    ) -> Result<VideoQuality, RtpParametersError> {
+        let quality = self.video_quality.get().with_request(request);
+        self.video_quality.set(quality);
+        self.apply_video_quality().await?;
+        Ok(quality)
+    }
+
+    /// Applies the requested video quality to all media senders,
+    /// throttled according to the background policy while the page is hidden.
+    async fn apply_video_quality(&self) -> Result<(), RtpParametersError> {
+        let quality = self.video_quality.get();
+        let quality = match self.background_policy.get().throttle_outgoing_video {
+            Some(throttle) if self.page_visibility.get().is_hidden() => quality.throttled(throttle),
+            _ => quality,
+        };
+
+        let media_senders: Vec<_> = self
+            .media_senders
+            .borrow()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for media_sender in media_senders {
+            media_sender.set_video_quality(quality).await?;
+        }
+        Ok(())
+    }
+
+    pub fn audio_fallback_policy(&self) -> Option<AudioFallbackPolicy> {
+        self.audio_fallback
+            .borrow()
+            .as_ref()
+            .map(AudioFallbackMonitor::policy)
+    }
+
+    /// Enables the automatic switch to audio only when the video can not be sent,
+    /// `None` disables it and restores the video if it is stopped.
+    pub async fn set_audio_fallback_policy(
+        &self,
+        policy: Option<AudioFallbackPolicy>,
+    ) -> Result<(), MediaSenderError> {
+        let was_audio_only = self.is_audio_only();
+        let _: Option<_> = self
+            .audio_fallback
+            .replace(policy.map(AudioFallbackMonitor::new));
+        if was_audio_only {
+            self.apply_audio_only(false).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the video is stopped by the audio fallback policy.
+    pub fn is_audio_only(&self) -> bool {
+        self.audio_fallback
+            .borrow()
+            .as_ref()
+            .is_some_and(AudioFallbackMonitor::is_audio_only)
+    }
+
+    /// Suspends or resumes the video of all media senders and notifies the receiver.
+    async fn apply_audio_only(&self, is_audio_only: bool) -> Result<(), MediaSenderError> {
+        if let Some(control_channel) = self.control_channel.borrow().as_ref() {
+            control_channel.set_audio_only(is_audio_only);
+        }
+        for media_sender in self.media_senders() {
+            media_sender.set_video_suspended(is_audio_only).await?;
+        }
+        Ok(())
+    }
+
+    async fn update_audio_fallback(self: &Arc<Self>, stats: &[JsValue]) {
+        use crate::audio_fallback::AudioFallbackChange;
+
+        let change = match self.audio_fallback.borrow_mut().as_mut() {
+            Some(audio_fallback) => audio_fallback.update(stats, js_sys::Date::now()),
+            None => return,
+        };
+        let (is_audio_only, ev) = match change {
+            Some(AudioFallbackChange::Fallback(reason)) => {
+                (true, SenderEvent::AudioOnlyFallback(reason))
+            }
+            Some(AudioFallbackChange::Restore) => (false, SenderEvent::VideoRestored),
+            None => return,
+        };
+        if let Err(err) = self.apply_audio_only(is_audio_only).await {
+            self.error(SenderError::AudioFallbackError(err)).await
+        }
+        self.handler(ev).await
+    }
+
+    pub fn background_policy(&self) -> BackgroundPolicy {
+        self.background_policy.get()
+    }
+
+    /// Sets the media adjustments applied while the page is hidden,
+    /// the policy is applied immediately if the page is already hidden.
+    pub async fn set_background_policy(
+        &self,
+        policy: BackgroundPolicy,
+    ) -> Result<(), RtpParametersError> {
+        let prev_policy = self.background_policy.replace(policy);
+        if prev_policy != policy && self.page_visibility.get().is_hidden() {
+            self.apply_video_quality().await?;
+        }
+        Ok(())
+    }
+
+    pub fn page_visibility(&self) -> PageVisibility {
+        self.page_visibility.get()
+    }
+
+    /// Sets Opus parameters applied to the offer and the answer
+    /// starting from the next negotiation.
+    /// Sets the answer timeout for relayed offers, `None` waits for the answer forever.
+    pub fn set_negotiation_timeout(&self, negotiation_timeout: Option<NegotiationTimeout>) {
+        self.negotiation_timeout.set(negotiation_timeout);
+    }
+
+    pub fn set_opus_parameters(&self, opus_parameters: Option<OpusParameters>) {
+        let _: Option<_> = self.opus_parameters.replace(opus_parameters);
+    }
+
+    pub fn candidate_preference(&self) -> CandidatePreference {
+        self.core.candidate_preference()
+    }
+
+    /// Sets the preference applied to the local candidates sent from now on,
+    /// whether the peer is on a LAN is advertised to the remote peer.
+    pub fn set_candidate_preference(
+        &self,
+        preference: CandidatePreference,
+    ) -> Result<(), SenderSendError> {
+        let capabilities = self.core.set_candidate_preference(preference);
+        self.send_message(ClientSenderMessage::PublishCapabilities(capabilities))?;
+        self.capabilities.set(Some(capabilities));
+        Ok(())
+    }
+
+    /// Asks the server whether the sender and the receiver are likely on the same network,
+    /// the answer is reported to both of them with the `PeerNetwork` event once joined.
+    pub fn request_peer_network(&self) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::RequestPeerNetwork)?;
+        self.is_peer_network_requested.set(true);
+        Ok(())
+    }
+
+    fn transform_sdp(&self, sdp: String) -> String {
+        match self.opus_parameters.borrow().as_ref() {
+            Some(opus_parameters) => opus_parameters.apply_to_sdp(&sdp),
+            None => sdp,
+        }
     }
 
     pub async fn start(self: &Arc<Self>) -> Result<(), SenderStartError> {
@@ -123,96 +536,188 @@ impl Sender {
 
         if self.is_started.swap(true, Ordering::Relaxed) {
             Err(SenderStartError::AlreadyStarted)
+        } else if self.sealing_key_pair.borrow().is_some() && self.sealed_session.borrow().is_none()
+        {
+            // The offer can only be sealed when the receiver key is received.
+            self.is_offer_deferred.set(true);
+            Ok(())
         } else {
             self.send_offer().await?;
-            self.init_negotiation_needed_handler();
+            self.core.init_negotiation_needed_handler(self);
             Ok(())
         }
     }
 
-    fn init_negotiation_needed_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
+    /// Enables sealed signaling, must be called before `start`.
+    ///
+    /// The offer is deferred until a receiver joins and responds with its key,
+    /// then session descriptions and candidates are sealed end-to-end.
+    pub fn enable_sealed_signaling(&self, key_pair: SealingKeyPair) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::PublishPublicKey(
+            key_pair.public_key().clone(),
+        ))?;
+        let _: Option<_> = self.sealing_key_pair.replace(Some(key_pair));
+        Ok(())
+    }
 
-        let js_negotiation_needed_handler = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_negotiation_needed_event(ev).await });
-            })
-        };
-        self.js_connection
-            .set_onnegotiationneeded(Some(js_negotiation_needed_handler.as_ref().unchecked_ref()));
-        let prev_handler = self
-            .js_negotiation_needed_handler
-            .replace(Some(js_negotiation_needed_handler));
-        debug_assert!(prev_handler.is_none());
+    pub fn sealing_key_pair(&self) -> Option<SealingKeyPair> {
+        self.sealing_key_pair.borrow().clone()
     }
 
-    fn init_ice_connection_state_change_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
+    /// Fingerprint of the receiver key, `None` until it is received.
+    pub fn peer_fingerprint(&self) -> Option<String> {
+        self.sealed_session
+            .borrow()
+            .as_ref()
+            .map(|session| session.peer_fingerprint().to_owned())
+    }
 
-        let js_ice_connection_state_change_handler = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_ice_connection_state_change(ev).await });
-            })
-        };
-        self.js_connection.set_oniceconnectionstatechange(Some(
-            js_ice_connection_state_change_handler
-                .as_ref()
-                .unchecked_ref(),
-        ));
-        let prev_handler = self
-            .js_ice_connection_state_change_handler
-            .replace(Some(js_ice_connection_state_change_handler));
-        debug_assert!(prev_handler.is_none());
-    }
-
-    fn init_ice_gathering_state_change_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
+    /// Signs the session descriptions with the long-lived identity key,
+    /// so that the remote peer can verify them.
+    pub fn set_identity_key_pair(&self, key_pair: IdentityKeyPair) {
+        self.peer_verification.set_identity_key_pair(key_pair)
+    }
+
+    pub fn identity_key_pair(&self) -> Option<IdentityKeyPair> {
+        self.peer_verification.identity_key_pair()
+    }
+
+    /// Rejects unsigned remote session descriptions when enabled.
+    pub fn set_verified_peer_required(&self, is_required: bool) {
+        self.peer_verification
+            .set_verified_peer_required(is_required)
+    }
+
+    /// The receiver whose signed session description was verified, `None` until then.
+    pub fn verified_peer(&self) -> Option<VerifiedPeer> {
+        self.peer_verification.verified_peer()
+    }
+
+    async fn on_peer_public_key(
+        self: &Arc<Self>,
+        public_key: PublicKey,
+    ) -> Result<(), SenderError> {
+        let key_pair = self
+            .sealing_key_pair()
+            .ok_or(SenderError::UnexpectedPeerPublicKey)?;
+        let session = key_pair.seal_with(&public_key).await?;
+        let fingerprint = session.peer_fingerprint().to_owned();
+        let _: Option<_> = self.sealed_session.replace(Some(session));
+        let local_fingerprint = key_pair.fingerprint().to_owned();
+        self.handler(SenderEvent::PeerKeyReceived {
+            fingerprint,
+            local_fingerprint,
+        })
+        .await;
+
+        if self.is_offer_deferred.replace(false) {
+            self.send_offer().await?;
+            self.core.init_negotiation_needed_handler(self);
+        } else if self.signaling_state() == RtcSignalingState::HaveLocalOffer {
+            // The pending offer was sealed for the previous receiver.
+            self.resend_local_offer().await?;
+        }
+        Ok(())
+    }
+
+    async fn seal_sdp(&self, sdp: SessionDescription) -> Result<SessionDescription, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.seal_sdp(sdp).await,
+            None => Ok(sdp),
+        }
+    }
+
+    /// Opens the sealed receiver payload,
+    /// unsealed payloads are rejected once sealed signaling is enabled.
+    async fn open_sdp(&self, sdp: SessionDescription) -> Result<SessionDescription, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.open_sdp(sdp).await,
+            None if self.sealing_key_pair.borrow().is_some() => {
+                Err(SealingError::PeerKeyIsNotReceived)
+            }
+            None => Ok(sdp),
+        }
+    }
+
+    async fn seal_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<IceCandidate, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.seal_ice_candidate(ice_candidate).await,
+            None => Ok(ice_candidate),
+        }
+    }
+
+    async fn open_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<IceCandidate, SealingError> {
+        let session = self.sealed_session.borrow().clone();
+        match session {
+            Some(session) => session.open_ice_candidate(ice_candidate).await,
+            None if self.sealing_key_pair.borrow().is_some() => {
+                Err(SealingError::PeerKeyIsNotReceived)
+            }
+            None => Ok(ice_candidate),
+        }
+    }
+
+    fn init_control_channel(self: &Arc<Self>, js_channel: RtcDataChannel) {
         use wasm_bindgen_futures::spawn_local;
 
-        let js_ice_gathering_state_change = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_ice_gathering_state_change(ev).await });
+        let control_channel = {
+            let self_weak = Arc::downgrade(self);
+            ControlChannel::new(js_channel, move |message| match message {
+                ControlMessage::PeerState(state) => {
+                    if let Some(self_arc) = self_weak.upgrade() {
+                        spawn_local(async move {
+                            self_arc.handler(SenderEvent::PeerStateChanged(state)).await
+                        });
+                    }
+                }
+                ControlMessage::StreamLabels(_)
+                | ControlMessage::StreamRemoved(_)
+                | ControlMessage::AudioOnly(_)
+                | ControlMessage::Caption(_) => {
+                    log::warn!("sender control messages received from the receiver are ignored")
+                }
             })
         };
-        self.js_connection.set_onicegatheringstatechange(Some(
-            js_ice_gathering_state_change.as_ref().unchecked_ref(),
-        ));
-        let prev_handler = self
-            .js_ice_gathering_state_change
-            .replace(Some(js_ice_gathering_state_change));
-        debug_assert!(prev_handler.is_none());
+        let prev_channel = self.control_channel.replace(Some(control_channel));
+        debug_assert!(prev_channel.is_none());
     }
 
-    fn init_signaling_state_change_handler(self: &Arc<Self>) {
-        use crate::closure_1;
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::spawn_local;
+    /// Local peer state sent to the receiver.
+    pub fn peer_state(&self) -> PeerState {
+        self.control_channel
+            .borrow()
+            .as_ref()
+            .map(|control_channel| control_channel.local_state())
+            .unwrap_or_default()
+    }
 
-        let js_signaling_state_change_change = {
-            let self_weak = Arc::downgrade(&self);
-            closure_1(move |ev: Event| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_signaling_state_change(ev).await });
-            })
+    /// Sets the local peer state, frequent changes are debounced.
+    pub fn set_peer_state(&self, state: PeerState) {
+        if let Some(control_channel) = self.control_channel.borrow().as_ref() {
+            control_channel.set_local_state(state);
+        }
+    }
+
+    fn send_acked_message(
+        &self,
+        message: ClientSenderMessage,
+    ) -> Result<Delivery, SenderSendError> {
+        let message = ClientMessage::SenderMessage {
+            sender_id: self.sender_id,
+            message,
         };
-        self.js_connection.set_onsignalingstatechange(Some(
-            js_signaling_state_change_change.as_ref().unchecked_ref(),
-        ));
-        let prev_handler = self
-            .js_signaling_state_change_change
-            .replace(Some(js_signaling_state_change_change));
-        debug_assert!(prev_handler.is_none());
+        let (delivery, len) = self.server.queue_acked_message(message)?;
+        self.session_metrics.add_signaling_sent(len);
+        Ok(delivery)
     }
 
     fn send_message(&self, message: ClientSenderMessage) -> Result<(), SenderSendError> {
@@ -220,7 +725,8 @@ impl Sender {
             sender_id: self.sender_id,
             message,
         };
-        send_websocket_client_message(&self.js_websocket, message)?;
+        let len = self.server.queue_message(message)?;
+        self.session_metrics.add_signaling_sent(len);
         Ok(())
     }
 
@@ -232,7 +738,132 @@ impl Sender {
         self.handler(SenderEvent::Error(err)).await
     }
 
-    pub(crate) async fn on_server_message(self: &Arc<Self>, message: ServerSenderMessage) {
+    async fn on_protocol_violation(self: &Arc<Self>, violation: ProtocolViolation) {
+        use crate::ProtocolViolationPolicy;
+
+        match self.server.protocol_violation_policy() {
+            ProtocolViolationPolicy::Lenient => {
+                log::warn!("sender protocol violation ignored: {}", violation);
+            }
+            ProtocolViolationPolicy::Strict => {
+                let diagnostics = ProtocolViolationDiagnostics {
+                    violation,
+                    ice_connection_state: self.ice_connection_state(),
+                    signaling_state: self.signaling_state(),
+                };
+                log::error!(
+                    "sender {:?} closed on protocol violation: {:?}",
+                    self.sender_id,
+                    diagnostics
+                );
+                self.close_with(CloseReason::PROTOCOL_VIOLATION, Some(diagnostics))
+                    .await;
+            }
+        }
+    }
+
+    /// Resolves when the server confirms or rejects the channel,
+    /// the open errors are not reported in `SenderEvent::Error` after that.
+    pub async fn opened(&self, timeout: Duration) -> Result<(), OpenError> {
+        use wasm_bindgen_futures::spawn_local;
+
+        use crate::sleep;
+
+        if self.is_open_awaited.replace(true) {
+            return Err(OpenError::AlreadyAwaited);
+        }
+        let open_result_sender = self.open_result_sender.clone();
+        spawn_local(async move {
+            sleep(timeout).await;
+            let _: Result<(), _> = open_result_sender.try_send(Err(OpenError::TimedOut(timeout)));
+        });
+        self.open_result_receiver
+            .recv()
+            .await
+            .unwrap_or(Err(OpenError::TimedOut(timeout)))
+    }
+
+    async fn on_open_rejected(
+        self: &Arc<Self>,
+        err: ServerSenderErrorMessage,
+    ) -> Result<(), SenderError> {
+        // The server has no channel to close.
+        self.teardown.skip_close_message();
+        let _: Result<(), _> = self
+            .open_result_sender
+            .try_send(Err(OpenError::Rejected(err.clone())));
+        if self.is_open_awaited.get() {
+            return Ok(());
+        }
+        match err {
+            ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(channel_id) => {
+                Err(SenderError::ChannelIdIsAlreadyUsed(channel_id))
+            }
+            ServerSenderErrorMessage::ChannelIsReserved(channel_id) => {
+                Err(SenderError::ChannelIsReserved(channel_id))
+            }
+            err => {
+                self.on_protocol_violation(ProtocolViolation::UnexpectedSenderError(err))
+                    .await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns `true` if the sender is closed by a protocol violation in strict mode
+    /// or by the session policy, server messages are ignored after that.
+    pub fn is_closed(&self) -> bool {
+        self.teardown.is_closed()
+    }
+
+    /// Why the sender was closed, `None` while it is open.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.teardown.reason()
+    }
+
+    /// Closes the connection and the channel, the receiver is notified.
+    ///
+    /// Closing an already closed sender does nothing,
+    /// the sender may be dropped right after that.
+    pub async fn close(self: &Arc<Self>) {
+        self.close_with(CloseReason::LocalClosed, None).await
+    }
+
+    async fn close_with(
+        self: &Arc<Self>,
+        reason: CloseReason,
+        diagnostics: Option<ProtocolViolationDiagnostics>,
+    ) {
+        if self.close_once(reason) {
+            self.handler(SenderEvent::Closed {
+                reason,
+                diagnostics,
+            })
+            .await;
+        }
+    }
+
+    /// Closes the connection and the channel, returns `false` if the sender is already closed,
+    /// server messages and later close calls are ignored after that.
+    fn close_once(&self, reason: CloseReason) -> bool {
+        if !self.teardown.close(reason) {
+            return false;
+        }
+        self.core.js_connection().close();
+        self.close_channel();
+        true
+    }
+
+    pub(crate) async fn on_server_message(
+        self: &Arc<Self>,
+        message: ServerSenderMessage,
+        len: usize,
+    ) {
+        self.session_metrics.add_signaling_received(len);
+        if self.is_closed() {
+            log::debug!("sender is closed, server message ignored: {:?}", message);
+            return;
+        }
         match self.clone().handle_server_message(message).await {
             Ok(()) => {}
             Err(err) => self.error(err).await,
@@ -243,110 +874,283 @@ impl Sender {
         self: &Arc<Self>,
         message: ServerSenderMessage,
     ) -> Result<(), SenderError> {
-        use wasm_bindgen_futures::JsFuture;
         use ServerSenderMessage as Msg;
 
         match message {
             Msg::OpenChannelSuccess => {
+                let _: Result<(), _> = self.open_result_sender.try_send(Ok(()));
+                self.release_local_candidates().await;
                 self.handler(SenderEvent::OpenChannelSuccess).await;
                 Ok(())
             }
+            Msg::ReceiverJoined { receiver_info } => {
+                self.handler(SenderEvent::ReceiverJoined(receiver_info))
+                    .await;
+                Ok(())
+            }
+            Msg::OfferRelayed => {
+                self.watch_answer();
+                Ok(())
+            }
+            Msg::ChannelOffer(sdp) => {
+                let sdp = self.open_sdp(sdp).await?;
+                let (sdp, _) = self.peer_verification.verify(sdp).await?;
+                self.receive_offer_and_send_answer(sdp).await?;
+                Ok(())
+            }
             Msg::ChannelAnswer(sdp) => {
+                self.negotiation_generation
+                    .set(self.negotiation_generation.get().wrapping_add(1));
+                self.offer_retries.set(0);
+                let sdp = self.open_sdp(sdp).await?;
+                let (sdp, peer) = self.peer_verification.verify(sdp).await?;
                 self.receive_answer(sdp).await?;
+                if let Some(peer) = peer {
+                    if let Some(peer) = self
+                        .peer_verification
+                        .bind(self.core.js_connection(), peer)?
+                    {
+                        self.handler(SenderEvent::VerifiedPeer(peer)).await;
+                    }
+                }
                 Ok(())
             }
             Msg::IceCandidate(ice_candidate) => {
-                let mut candidate = RtcIceCandidateInit::new(&ice_candidate.candidate);
-                let _: &mut _ = candidate
-                    .sdp_mid(ice_candidate.sdp_mid.as_deref())
-                    .sdp_m_line_index(ice_candidate.sdp_m_line_index);
-                let candidate = RtcIceCandidate::new(&candidate)
-                    .map_err(SenderError::NewRtcIceCandidateError)?;
-
-                let ice_candidate_result = JsFuture::from(
-                    self.js_connection
-                        .add_ice_candidate_with_opt_rtc_ice_candidate(Some(&candidate)),
-                )
-                .await;
-                match ice_candidate_result {
-                    Ok(_) => {}
-                    Err(err) => self.error(SenderError::AddIceCandidateError(err)).await,
-                };
-
+                let ice_candidate = self.open_ice_candidate(ice_candidate).await?;
+                self.core.on_remote_ice_candidate(self, ice_candidate).await;
                 Ok(())
             }
             Msg::AllIceCandidatesSent => Ok(()),
+            Msg::IceCandidatesRequested => self.resend_ice_candidates().await,
+            Msg::IceRestartRequested => {
+                self.restart_ice().await?;
+                Ok(())
+            }
+            Msg::PeerPublicKey(public_key) => self.on_peer_public_key(public_key).await,
+            Msg::PeerCapabilities(capabilities) => {
+                self.core.on_peer_capabilities(capabilities);
+                Ok(())
+            }
+            Msg::PeerNetwork(peer_network) => {
+                self.handler(SenderEvent::PeerNetwork(peer_network)).await;
+                Ok(())
+            }
+            Msg::ReceiverLeft(reason) => {
+                log::info!("sender {:?} receiver left: {:?}", self.sender_id, reason);
+                self.handler(SenderEvent::ReceiverLeft(reason)).await;
+                Ok(())
+            }
+            Msg::ChannelRenamed(channel_id) => {
+                let _: Option<_> = self.channel_id.replace(Some(channel_id.clone()));
+                self.handler(SenderEvent::ChannelRenamed(channel_id)).await;
+                Ok(())
+            }
+            Msg::RenameChannelFailed(err) => {
+                self.handler(SenderEvent::RenameChannelFailed(err)).await;
+                Ok(())
+            }
+            Msg::Throttled(notice) => {
+                log::warn!("sender {:?} relay throttled: {:?}", self.sender_id, notice);
+                self.handler(SenderEvent::Throttled(notice)).await;
+                Ok(())
+            }
+            Msg::BinaryDataEcho(data) => {
+                self.handler(SenderEvent::BinaryDataEcho(data)).await;
+                Ok(())
+            }
+            Msg::RecordingStarted => {
+                self.handler(SenderEvent::RecordingStarted).await;
+                Ok(())
+            }
+            Msg::RecordingStopped { size } => {
+                self.handler(SenderEvent::RecordingStopped { size }).await;
+                Ok(())
+            }
+            Msg::RecordingFailed(err) => {
+                log::warn!("sender {:?} recording failed: {:?}", self.sender_id, err);
+                self.handler(SenderEvent::RecordingFailed(err)).await;
+                Ok(())
+            }
+            Msg::QualityRequested(request) => {
+                use core::sync::atomic::Ordering;
+
+                self.handler(SenderEvent::QualityRequested(request)).await;
+                if self.is_quality_automatic.load(Ordering::Relaxed) {
+                    let _: VideoQuality = self.apply_quality_request(request).await?;
+                }
+                Ok(())
+            }
             Msg::Error(err) => match err {
-                ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(channel_id) => {
-                    Err(SenderError::ChannelIdIsAlreadyUsed(channel_id))
+                ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(_)
+                | ServerSenderErrorMessage::ChannelIsReserved(_) => {
+                    self.on_open_rejected(err).await
+                }
+                err => {
+                    self.on_protocol_violation(ProtocolViolation::UnexpectedSenderError(err))
+                        .await;
+                    Ok(())
                 }
-                _ => panic!("invalid SessionSenderId used"),
             },
         }
     }
 
-    async fn on_ice_candidate_event(self: &Arc<Self>, ev: RtcPeerConnectionIceEvent) {
-        log::trace!("browser_webrtc::Sender::on_ice_candidate_event");
+    async fn send_ice_candidate(&self, ice_candidate: IceCandidate) -> Result<(), SenderError> {
+        let ice_candidate = match self.core.prefer_local_ice_candidate(ice_candidate) {
+            Some(ice_candidate) => ice_candidate,
+            None => return Ok(()),
+        };
+        let ice_candidate = self.seal_ice_candidate(ice_candidate).await?;
+        self.send_acked_message(ClientSenderMessage::IceCandidate(ice_candidate))
+            .map_err(SenderError::IceCandidateSendError)?
+            .delivered()
+            .await
+            .map_err(SenderError::IceCandidateDeliveryError)
+    }
 
-        match self.handle_ice_candidate_event(ev).await {
-            Ok(()) => {}
-            Err(err) => self.error(err).await,
+    /// Sends the local candidate unless the server has not confirmed the channel yet.
+    async fn on_local_candidate(self: &Arc<Self>, candidate: LocalCandidate) {
+        if let Some(candidate) = self.core.local_candidates().on_gathered(candidate) {
+            if let Err(err) = self.send_local_candidate(candidate).await {
+                self.error(err).await
+            }
         }
     }
 
-    async fn handle_ice_candidate_event(
-        &self,
-        ev: RtcPeerConnectionIceEvent,
-    ) -> Result<(), SenderError> {
-        use signaling_protocol::IceCandidate;
-
-        if let Some(candidate) = ev.candidate() {
-            let candidate_str = candidate.candidate();
-            let message = match candidate_str.as_ref() {
-                "" => ClientSenderMessage::AllIceCandidatesSent,
-                _ => {
-                    let ice_candidate = IceCandidate {
-                        candidate: candidate_str,
-                        sdp_mid: candidate.sdp_mid(),
-                        sdp_m_line_index: candidate.sdp_m_line_index(),
-                    };
-                    ClientSenderMessage::IceCandidate(ice_candidate)
-                }
-            };
-            let message = ClientMessage::SenderMessage {
-                sender_id: self.sender_id,
-                message,
-            };
-            send_websocket_client_message(&self.js_websocket, message)
+    /// Sends the local candidates held until the server confirmed the channel.
+    async fn release_local_candidates(self: &Arc<Self>) {
+        while let Some(candidate) = self.core.local_candidates().release_next() {
+            if let Err(err) = self.send_local_candidate(candidate).await {
+                self.error(err).await
+            }
+        }
+    }
+
+    async fn send_local_candidate(&self, candidate: LocalCandidate) -> Result<(), SenderError> {
+        match candidate {
+            LocalCandidate::IceCandidate(ice_candidate) => {
+                self.send_ice_candidate(ice_candidate).await
+            }
+            LocalCandidate::AllSent => self
+                .send_message(ClientSenderMessage::AllIceCandidatesSent)
+                .map_err(SenderError::IceCandidateSendError),
+        }
+    }
+
+    /// Re-sends the gathered local candidates on the receiver request.
+    async fn resend_ice_candidates(&self) -> Result<(), SenderError> {
+        use web_sys::RtcIceGatheringState;
+
+        for ice_candidate in self.core.engine().local_ice_candidates() {
+            self.send_ice_candidate(ice_candidate).await?;
+        }
+        if self.ice_gathering_state() == RtcIceGatheringState::Complete {
+            self.send_message(ClientSenderMessage::AllIceCandidatesSent)
                 .map_err(SenderError::IceCandidateSendError)?;
         }
         Ok(())
     }
 
-    async fn on_negotiation_needed_event(self: &Arc<Self>, ev: Event) {
+    async fn on_negotiation_needed_event(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Sender::on_negotiation_needed_event");
 
-        match self.handle_negotiation_needed_event(ev).await {
+        match self.handle_negotiation_needed_event().await {
             Ok(()) => {}
             Err(err) => self.error(err).await,
         }
     }
 
-    async fn handle_negotiation_needed_event(&self, _: Event) -> Result<(), SenderError> {
+    async fn handle_negotiation_needed_event(&self) -> Result<(), SenderError> {
         self.send_offer().await?;
         Ok(())
     }
 
-    async fn on_ice_connection_state_change(self: &Arc<Self>, _: Event) {
+    async fn on_ice_connection_state_change(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Receiver::on_ice_connection_state_change");
 
-        self.handler(SenderEvent::IceConnectionStateChange(
-            self.ice_connection_state(),
-        ))
-        .await
+        let state = self.ice_connection_state();
+        self.session_metrics.set_state(state);
+        self.report_ice_milestone(state);
+        self.handler(SenderEvent::IceConnectionStateChange(state))
+            .await;
+        if matches!(
+            state,
+            RtcIceConnectionState::Connected | RtcIceConnectionState::Completed
+        ) {
+            self.update_sctp_limits().await
+        }
+        if state == RtcIceConnectionState::Failed {
+            self.on_network_change(NetworkChange::IceFailed).await
+        }
+    }
+
+    fn report_milestone(&self, milestone: CallMilestone) {
+        self.milestones
+            .report(&self.server, milestone, self.session_metrics.elapsed())
+    }
+
+    fn report_ice_milestone(&self, state: RtcIceConnectionState) {
+        match state {
+            RtcIceConnectionState::Connected | RtcIceConnectionState::Completed => {
+                self.report_milestone(CallMilestone::IceConnected)
+            }
+            RtcIceConnectionState::Failed
+                if !self.milestones.is_reported(CallMilestone::IceConnected) =>
+            {
+                self.report_milestone(CallMilestone::SetupFailed)
+            }
+            _ => {}
+        }
+    }
+
+    async fn on_network_change(self: &Arc<Self>, change: NetworkChange) {
+        log::trace!("browser_webrtc::Sender::on_network_change");
+
+        self.handler(SenderEvent::NetworkChanged(change)).await;
+        match change {
+            NetworkChange::Online | NetworkChange::IceFailed => {
+                if let Err(err) = self.restart_ice().await {
+                    self.error(err.into()).await
+                }
+            }
+            NetworkChange::Offline => {}
+        }
+    }
+
+    async fn on_visibility_change(self: &Arc<Self>, visibility: PageVisibility) {
+        log::trace!("browser_webrtc::Sender::on_visibility_change");
+
+        if self.page_visibility.replace(visibility) == visibility {
+            return;
+        }
+        if self
+            .background_policy
+            .get()
+            .throttle_outgoing_video
+            .is_some()
+        {
+            if let Err(err) = self.apply_video_quality().await {
+                self.error(err.into()).await
+            }
+        }
+        self.handler(SenderEvent::VisibilityChanged(visibility))
+            .await;
+    }
+
+    /// Restarts ICE with fresh candidates, e.g. after a network change.
+    ///
+    /// Does nothing if the sender is not started or a negotiation is in progress.
+    pub async fn restart_ice(&self) -> Result<(), SenderSendOfferError> {
+        use core::sync::atomic::Ordering;
+
+        if !self.is_started.load(Ordering::Relaxed)
+            || self.signaling_state() != RtcSignalingState::Stable
+        {
+            return Ok(());
+        }
+        self.send_offer_with_ice_restart(true).await
     }
 
-    async fn on_ice_gathering_state_change(self: &Arc<Self>, _: Event) {
+    async fn on_ice_gathering_state_change(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Receiver::on_ice_gathering_state_change");
 
         self.handler(SenderEvent::IceGatheringStateChange(
@@ -355,69 +1159,406 @@ impl Sender {
         .await
     }
 
-    async fn on_signaling_state_change(self: &Arc<Self>, _: Event) {
+    async fn on_signaling_state_change(self: &Arc<Self>) {
         log::trace!("browser_webrtc::Receiver::on_signaling_state_change");
 
         self.handler(SenderEvent::RtcSignalingStateChange(self.signaling_state()))
             .await
     }
 
+    /// Reports the SCTP limits once they become known after the connection is established.
+    async fn update_sctp_limits(self: &Arc<Self>) {
+        if self.sctp_limits.get().is_some() {
+            return;
+        }
+        if let Some(limits) = SctpLimits::from_connection(self.core.js_connection()) {
+            self.sctp_limits.set(Some(limits));
+            self.handler(SenderEvent::SctpLimitsKnown(limits)).await
+        }
+    }
+
+    /// Periodically samples the connection stats for the session metrics,
+    /// emits `UsageUpdated` on every sample
+    /// and `QualityChanged` when the smoothed score changes.
+    fn init_stats_monitor(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        use crate::quality::QUALITY_UPDATE_INTERVAL;
+        use crate::sleep;
+
+        let self_weak = Arc::downgrade(self);
+        spawn_local(async move {
+            loop {
+                sleep(QUALITY_UPDATE_INTERVAL).await;
+                match self_weak.upgrade() {
+                    Some(self_arc) => self_arc.update_stats().await,
+                    None => return,
+                }
+            }
+        });
+    }
+
+    async fn update_stats(self: &Arc<Self>) {
+        use crate::stats::stats_report_values;
+
+        let stats = match stats_report_values(self.core.js_connection().get_stats()).await {
+            Ok(stats) => stats,
+            Err(err) => {
+                log::warn!("{}", err);
+                return;
+            }
+        };
+        self.session_metrics.update_traffic(&stats);
+        self.handler(SenderEvent::UsageUpdated(self.session_metrics.usage()))
+            .await;
+        self.core.update_ice_timeline(&stats);
+        if self.check_session_limits().await {
+            return;
+        }
+
+        if !matches!(
+            self.ice_connection_state(),
+            RtcIceConnectionState::Connected | RtcIceConnectionState::Completed
+        ) {
+            return;
+        }
+        let quality = self.quality_monitor.borrow_mut().update(&stats);
+        if let Some(quality) = quality {
+            self.handler(SenderEvent::QualityChanged(quality)).await
+        }
+        self.update_audio_fallback(&stats).await;
+    }
+
+    /// Candidates and candidate pairs progress of the connection,
+    /// pairs are sampled from the connection stats.
+    pub fn ice_timeline(&self) -> IceTimeline {
+        self.core.ice_timeline()
+    }
+
+    /// Smoothed connection quality, `None` until the first stats sample.
+    pub fn quality(&self) -> Option<ConnectionQuality> {
+        self.quality_monitor.borrow().quality()
+    }
+
+    /// Session metrics of the connection,
+    /// traffic totals are as of the latest periodic stats sample.
+    pub fn summary(&self) -> SessionSummary {
+        self.session_metrics.summary()
+    }
+
+    /// Cumulative bytes of the session by the traffic kind,
+    /// media and data bytes are as of the latest periodic stats sample.
+    pub fn usage(&self) -> SessionUsage {
+        self.session_metrics.usage()
+    }
+
+    /// Limits the session length and inactivity, checked on every periodic stats sample.
+    pub fn set_session_policy(&self, policy: SessionPolicy) {
+        self.session_limiter.set_policy(policy);
+    }
+
+    pub fn session_policy(&self) -> SessionPolicy {
+        self.session_limiter.policy()
+    }
+
+    /// Returns `true` if the session is ended by the session policy.
+    async fn check_session_limits(self: &Arc<Self>) -> bool {
+        use crate::session_policy::SessionLimitAction;
+
+        let action = self.session_limiter.check(
+            self.session_metrics.elapsed(),
+            self.session_metrics.inactive_for(),
+        );
+        match action {
+            None => false,
+            Some(SessionLimitAction::Warn { reason, remaining }) => {
+                self.handler(SenderEvent::SessionEnding { reason, remaining })
+                    .await;
+                false
+            }
+            Some(SessionLimitAction::End(reason)) => {
+                log::info!("sender {:?} session ended: {:?}", self.sender_id, reason);
+                if self.close_once(CloseReason::Timeout) {
+                    self.handler(SenderEvent::SessionEnded(reason)).await;
+                    self.handler(SenderEvent::Closed {
+                        reason: CloseReason::Timeout,
+                        diagnostics: None,
+                    })
+                    .await;
+                }
+                true
+            }
+        }
+    }
+
+    /// Reports the session summary when enabled and closes the channel on the server once,
+    /// the close reason is relayed to the receiver.
+    ///
+    /// Nothing is sent over a closing WebSocket, the server closes the channels
+    /// of a disconnected client by itself.
+    fn close_channel(&self) {
+        use crate::WebSocketState;
+
+        let is_websocket_open = matches!(
+            self.server.websocket_state(),
+            WebSocketState::Connecting | WebSocketState::Open
+        );
+        let reason = match self.teardown.take_close_message(is_websocket_open) {
+            Some(reason) => reason,
+            None => return,
+        };
+        if self.is_summary_reported.get() {
+            let summary = self.session_metrics.summary();
+            let _: Option<()> = self
+                .send_message(ClientSenderMessage::ReportSessionSummary(summary))
+                .ok();
+        }
+        let _: Option<()> = self
+            .send_message(ClientSenderMessage::CloseChannel(reason))
+            .ok();
+    }
+
+    /// Reports the session summary to the server on close when enabled,
+    /// so that operators can collect aggregate statistics.
+    pub fn set_summary_reporting(&self, is_enabled: bool) {
+        self.is_summary_reported.set(is_enabled);
+    }
+
+    /// Data channel limits negotiated by the connection,
+    /// `None` until the connection is established.
+    pub fn sctp_limits(&self) -> Option<SctpLimits> {
+        self.sctp_limits.get()
+    }
+
     pub fn ice_connection_state(&self) -> RtcIceConnectionState {
-        self.js_connection.ice_connection_state()
+        self.core.ice_connection_state()
     }
 
     pub fn ice_gathering_state(&self) -> RtcIceGatheringState {
-        self.js_connection.ice_gathering_state()
+        self.core.ice_gathering_state()
     }
 
     pub fn signaling_state(&self) -> RtcSignalingState {
-        self.js_connection.signaling_state()
+        self.core.signaling_state()
     }
 
     async fn send_offer(&self) -> Result<(), SenderSendOfferError> {
+        self.send_offer_with_ice_restart(false).await
+    }
+
+    async fn send_offer_with_ice_restart(
+        &self,
+        is_ice_restart: bool,
+    ) -> Result<(), SenderSendOfferError> {
         log::trace!("browser_webrtc::Sender::send_offer");
 
+        if self.is_offer_warm.replace(false)
+            && !is_ice_restart
+            && self.signaling_state() == RtcSignalingState::HaveLocalOffer
+        {
+            // The warm offer already has the candidates gathered so far.
+            if let Some(local_description) = self.core.js_connection().local_description() {
+                let sdp = SessionDescription(local_description.sdp());
+                return self.send_signed_offer(sdp, false).await;
+            }
+        }
+
+        self.perfect_negotiation.set_making_offer(true);
+        let sdp = self.set_local_offer(is_ice_restart).await;
+        self.perfect_negotiation.set_making_offer(false);
+
+        self.send_signed_offer(SessionDescription(sdp?), is_ice_restart)
+            .await
+    }
+
+    async fn set_local_offer(&self, is_ice_restart: bool) -> Result<String, SenderSendOfferError> {
         use js_sys::Reflect;
         use wasm_bindgen::JsCast;
         use wasm_bindgen_futures::JsFuture;
-        use web_sys::RtcSessionDescriptionInit;
-
-        let offer = JsFuture::from(self.js_connection.create_offer())
-            .await
-            .map_err(SenderSendOfferError::CreateOfferError)?;
+        use web_sys::{RtcOfferOptions, RtcSdpType, RtcSessionDescriptionInit};
+
+        let mut offer_options = RtcOfferOptions::new();
+        let _: &mut _ = offer_options.ice_restart(is_ice_restart);
+        let offer = JsFuture::from(
+            self.core
+                .js_connection()
+                .create_offer_with_rtc_offer_options(&offer_options),
+        )
+        .await
+        .map_err(SenderSendOfferError::CreateOfferError)?;
 
         let offer: &RtcSessionDescriptionInit = offer.as_ref().unchecked_ref();
 
-        let _: JsValue = JsFuture::from(self.js_connection.set_local_description(offer))
+        let sdp = Reflect::get(&offer, &JsValue::from_str("sdp"))
+            .ok()
+            .and_then(|sdp| sdp.as_string())
+            .ok_or(SenderSendOfferError::OfferSdpIsMissing)?;
+        let sdp = self.transform_sdp(sdp);
+
+        let mut offer = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+        let _: &mut _ = offer.sdp(&sdp);
+
+        let _: JsValue = JsFuture::from(self.core.js_connection().set_local_description(&offer))
             .await
             .map_err(SenderSendOfferError::SetLocalDescriptionError)?;
 
-        let sdp = Reflect::get(&offer, &JsValue::from_str("sdp"))
-            .unwrap()
-            .as_string()
-            .unwrap();
+        Ok(sdp)
+    }
 
-        self.send_message(ClientSenderMessage::SendOffer(SessionDescription(sdp)))?;
+    async fn send_signed_offer(
+        &self,
+        sdp: SessionDescription,
+        is_ice_restart: bool,
+    ) -> Result<(), SenderSendOfferError> {
+        let sdp = self.peer_verification.sign(sdp).await?;
+        let sdp = self.seal_sdp(sdp).await?;
+        self.send_acked_message(if is_ice_restart {
+            ClientSenderMessage::SendIceRestartOffer(sdp)
+        } else {
+            ClientSenderMessage::SendOffer(sdp)
+        })?
+        .delivered()
+        .await?;
+        self.report_milestone(CallMilestone::OfferSent);
 
         Ok(())
     }
 
-    async fn receive_answer(
+    fn watch_answer(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        let negotiation_timeout = match self.negotiation_timeout.get() {
+            Some(negotiation_timeout) => negotiation_timeout,
+            None => return,
+        };
+        let generation = self.negotiation_generation.get().wrapping_add(1);
+        self.negotiation_generation.set(generation);
+
+        let self_weak = Arc::downgrade(self);
+        spawn_local(async move {
+            use crate::sleep;
+
+            sleep(negotiation_timeout.answer_timeout).await;
+            if let Some(self_arc) = self_weak.upgrade() {
+                if let Err(err) = self_arc
+                    .on_answer_timeout(generation, negotiation_timeout)
+                    .await
+                {
+                    self_arc.error(err).await
+                }
+            }
+        });
+    }
+
+    async fn on_answer_timeout(
         &self,
+        generation: u32,
+        negotiation_timeout: NegotiationTimeout,
+    ) -> Result<(), SenderError> {
+        log::trace!("browser_webrtc::Sender::on_answer_timeout");
+
+        if generation != self.negotiation_generation.get()
+            || self.signaling_state() != RtcSignalingState::HaveLocalOffer
+        {
+            return Ok(());
+        }
+
+        let retries = self.offer_retries.get();
+        if retries >= negotiation_timeout.max_retries {
+            self.offer_retries.set(0);
+            return Err(SenderError::NegotiationTimedOut { retries });
+        }
+        self.offer_retries.set(retries + 1);
+
+        self.resend_local_offer().await?;
+        Ok(())
+    }
+
+    /// Re-sends the pending local offer as is, the server relays it to the receiver again.
+    async fn resend_local_offer(&self) -> Result<(), SenderSendOfferError> {
+        let sdp = match self.core.js_connection().local_description() {
+            Some(local_description) => local_description.sdp(),
+            None => return Ok(()),
+        };
+        let sdp = self.peer_verification.sign(SessionDescription(sdp)).await?;
+        let sdp = self.seal_sdp(sdp).await?;
+        self.send_message(ClientSenderMessage::SendOffer(sdp))?;
+        Ok(())
+    }
+
+    async fn receive_answer(
+        self: &Arc<Self>,
         remote_sdp: SessionDescription,
     ) -> Result<(), SenderReceiveAnswerError> {
+        use webrtc_core::{RtcEngine, SdpType};
+
+        let sdp = SessionDescription(self.transform_sdp(remote_sdp.0));
+        self.core
+            .engine()
+            .set_remote_description(SdpType::Answer, &sdp)
+            .await
+            .map_err(SenderReceiveAnswerError::SetRemoteDescriptionError)?;
+        self.report_milestone(CallMilestone::AnswerReceived);
+        self.core.on_remote_description(self).await;
+
+        Ok(())
+    }
+
+    /// Answers the renegotiation offer of the receiver unless it collides with the local offer,
+    /// the receiver rolls back its offer and answers the local one then.
+    async fn receive_offer_and_send_answer(
+        self: &Arc<Self>,
+        remote_sdp: SessionDescription,
+    ) -> Result<(), SenderReceiveOfferError> {
+        log::trace!("browser_webrtc::Sender::receive_offer_and_send_answer");
+
+        use js_sys::Reflect;
+        use wasm_bindgen::JsCast;
         use wasm_bindgen_futures::JsFuture;
-        use web_sys::{RtcSdpType, RtcSessionDescriptionInit};
+        use web_sys::RtcSessionDescriptionInit;
+        use webrtc_core::{OfferCollision, RtcEngine, SdpType};
+
+        use SenderReceiveOfferError as Event;
+
+        match self
+            .perfect_negotiation
+            .on_remote_offer(self.core.negotiation_state())
+        {
+            OfferCollision::None | OfferCollision::Rollback => {}
+            OfferCollision::Ignore => {
+                log::info!("sender {:?} colliding offer ignored", self.sender_id);
+                return Ok(());
+            }
+        }
 
-        let mut remote_description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
-        let _: &mut _ = remote_description.sdp(&remote_sdp.0);
+        let sdp = SessionDescription(self.transform_sdp(remote_sdp.0));
+        self.core
+            .engine()
+            .set_remote_description(SdpType::Offer, &sdp)
+            .await
+            .map_err(Event::SetRemoteDescriptionError)?;
+        self.core.on_remote_description(self).await;
 
-        let _: JsValue = JsFuture::from(
-            self.js_connection
-                .set_remote_description(&remote_description),
-        )
-        .await
-        .map_err(SenderReceiveAnswerError::SetRemoteDescriptionError)?;
+        let answer = JsFuture::from(self.core.js_connection().create_answer())
+            .await
+            .map_err(Event::CreateAnswerError)?;
+
+        let answer: &RtcSessionDescriptionInit = answer.as_ref().unchecked_ref();
+
+        let _: JsValue = JsFuture::from(self.core.js_connection().set_local_description(answer))
+            .await
+            .map_err(Event::SetLocalDescriptionError)?;
+
+        let sdp = Reflect::get(answer, &JsValue::from_str("sdp"))
+            .ok()
+            .and_then(|sdp| sdp.as_string())
+            .ok_or(Event::AnswerSdpIsMissing)?;
+
+        let sdp = self.peer_verification.sign(SessionDescription(sdp)).await?;
+        let sdp = self.seal_sdp(sdp).await?;
+        self.send_acked_message(ClientSenderMessage::SendAnswer(sdp))?
+            .delivered()
+            .await?;
 
         Ok(())
     }
@@ -425,6 +1566,135 @@ impl Sender {
     pub fn send_binary_data(&self, data: Vec<u8>) -> Result<(), SenderSendError> {
         self.send_message(ClientSenderMessage::SendBinaryData(data))
     }
+
+    /// Sends the data through the server only to the receivers subscribed to the topic.
+    pub fn send_topic_data(&self, topic: Topic, data: Vec<u8>) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::SendTopicData { topic, data })
+    }
+
+    /// Sends the channel poster frame to the server,
+    /// it is shown to receivers before they join the channel.
+    pub fn send_snapshot(&self, snapshot: &MediaSnapshot) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::SendSnapshot(snapshot.data().to_vec()))
+    }
+
+    /// Publishes the channel preview shown in the open channels list,
+    /// the thumbnail is expected to be a small JPEG snapshot.
+    pub fn publish_preview(
+        &self,
+        thumbnail: Option<&MediaSnapshot>,
+        caption: String,
+    ) -> Result<(), SenderSendError> {
+        let preview = ChannelPreview {
+            thumbnail: thumbnail.map(|thumbnail| thumbnail.data().to_vec()),
+            caption,
+            intended_media: self.intended_media.get(),
+            caption_languages: self.caption_languages.borrow().clone(),
+        };
+        self.send_message(ClientSenderMessage::PublishPreview(preview.clone()))?;
+        let _: Option<_> = self.preview.replace(Some(preview));
+        Ok(())
+    }
+
+    /// Moves the open channel to a new id, e.g. when the display name follows the content.
+    /// The result is reported in `SenderEvent::ChannelRenamed`
+    /// or `SenderEvent::RenameChannelFailed`, the session is not affected.
+    pub fn rename_channel(&self, channel_id: ChannelId) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::RenameChannel(channel_id))
+    }
+
+    /// Lists the channel as scheduled to start at the time since the UNIX epoch,
+    /// the server lists it as live once the first offer is sent.
+    pub fn schedule_start(&self, scheduled_start: Option<Duration>) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::ScheduleStart(scheduled_start))?;
+        self.scheduled_start.set(scheduled_start);
+        Ok(())
+    }
+
+    /// Starts storing the uploaded recording chunks on the server,
+    /// the result is reported in `SenderEvent::RecordingStarted`
+    /// or `SenderEvent::RecordingFailed`. See `RecordingUploader`.
+    pub fn start_recording(&self, mime_type: String) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::StartRecording { mime_type })
+    }
+
+    pub fn send_recording_chunk(&self, chunk: Vec<u8>) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::SendRecordingChunk(chunk))
+    }
+
+    /// The stored size is reported in `SenderEvent::RecordingStopped`.
+    pub fn stop_recording(&self) -> Result<(), SenderSendError> {
+        self.send_message(ClientSenderMessage::StopRecording)
+    }
+
+    /// Sets the media advertised in the published previews,
+    /// e.g. while the local media capture is deferred until a receiver joins.
+    ///
+    /// Media streams can be added at any time, late ones are renegotiated with the receiver.
+    pub fn set_intended_media(&self, intended_media: Option<IntendedMedia>) {
+        self.intended_media.set(intended_media);
+    }
+
+    pub fn intended_media(&self) -> Option<IntendedMedia> {
+        self.intended_media.get()
+    }
+
+    /// Sets the BCP 47 language tags of the captions advertised in the published previews.
+    pub fn set_caption_languages(&self, caption_languages: Vec<String>) {
+        *self.caption_languages.borrow_mut() = caption_languages;
+    }
+
+    pub fn caption_languages(&self) -> Vec<String> {
+        self.caption_languages.borrow().clone()
+    }
+
+    /// Sends the caption cue to the receiver over the control channel, see `SpeechCaptions`.
+    pub fn send_caption(&self, cue: &CaptionCue) -> Result<(), CaptionSendError> {
+        match self.control_channel.borrow().as_ref() {
+            Some(control_channel) => control_channel.send_caption(cue),
+            None => Err(CaptionSendError::ChannelIsNotOpen),
+        }
+    }
+}
+
+impl PeerConnectionRole for Sender {
+    fn core(&self) -> &PeerConnectionCore {
+        &self.core
+    }
+
+    async fn on_peer_connection_event(self: &Arc<Self>, ev: PeerConnectionEvent) {
+        use webrtc_core::IceCandidateRecoveryAction;
+        use PeerConnectionEvent as Ev;
+
+        match ev {
+            Ev::LocalIceCandidate(ice_candidate) => {
+                self.on_local_candidate(LocalCandidate::IceCandidate(ice_candidate))
+                    .await
+            }
+            Ev::LocalIceCandidatesGathered => {
+                self.on_local_candidate(LocalCandidate::AllSent).await
+            }
+            Ev::NegotiationNeeded => self.on_negotiation_needed_event().await,
+            Ev::IceConnectionStateChange => self.on_ice_connection_state_change().await,
+            Ev::IceGatheringStateChange => self.on_ice_gathering_state_change().await,
+            Ev::SignalingStateChange => self.on_signaling_state_change().await,
+            Ev::NetworkChanged(change) => self.on_network_change(change).await,
+            Ev::VisibilityChanged(visibility) => self.on_visibility_change(visibility).await,
+            Ev::IceCandidateRetriesExhausted { action, err } => match action {
+                IceCandidateRecoveryAction::RequestResend => {
+                    log::warn!("ice candidate retries exhausted, requesting candidates again");
+                    if let Err(err) = self.send_message(ClientSenderMessage::RequestIceCandidates) {
+                        self.error(SenderError::IceCandidatesRequestError(err))
+                            .await
+                    }
+                }
+                IceCandidateRecoveryAction::AwaitResend => {}
+                IceCandidateRecoveryAction::Fail => {
+                    self.error(SenderError::AddIceCandidateError(err)).await
+                }
+            },
+        }
+    }
 }
 
 impl Drop for Sender {
@@ -433,23 +1703,94 @@ impl Drop for Sender {
 
         log::trace!("browser_webrtc::Sender::drop");
 
-        self.js_connection.set_onnegotiationneeded(None);
-        self.js_connection.set_onicecandidate(None);
-        self.js_connection.close();
+        drop(self.control_channel.take());
+        self.core.close();
 
         let server = Arc::clone(&self.server);
         let sender_id = self.sender_id;
-        let _: Option<()> = self.send_message(ClientSenderMessage::CloseChannel).ok();
+        self.close_channel();
         spawn_local(async move { server.on_sender_dropped(sender_id).await });
     }
 }
 
+/// Returns 16 random bytes as hex, `None` if the Web Crypto API is not available,
+/// the channel is then not resumed after a reconnect.
+fn new_resume_token() -> Option<ResumeToken> {
+    let mut bytes = [0; 16];
+    match crate::webcrypto::fill_random(&mut bytes) {
+        Ok(()) => Some(ResumeToken(crate::webcrypto::to_hex(&bytes))),
+        Err(err) => {
+            log::warn!("resume token is not generated: {}", err);
+            None
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SenderEvent {
     OpenChannelSuccess,
+    /// A receiver joined the channel, the connection is being negotiated.
+    ReceiverJoined(ReceiverInfo),
+    /// The receiver exited the channel, disconnected or did not answer in time,
+    /// the channel stays open for another receiver.
+    ReceiverLeft(CloseReason),
+    /// The channel is moved to the new id, the joined receivers are notified.
+    ChannelRenamed(ChannelId),
+    /// The channel keeps its id, the requested one is already used or reserved.
+    RenameChannelFailed(ServerSenderErrorMessage),
     IceConnectionStateChange(RtcIceConnectionState),
     IceGatheringStateChange(RtcIceGatheringState),
     RtcSignalingStateChange(RtcSignalingState),
+    NetworkChanged(NetworkChange),
+    /// The page is hidden or shown, the background policy is already applied.
+    VisibilityChanged(PageVisibility),
+    SctpLimitsKnown(SctpLimits),
+    /// The smoothed connection quality score is changed.
+    QualityChanged(ConnectionQuality),
+    /// The session usage is sampled, emitted periodically.
+    UsageUpdated(SessionUsage),
+    QualityRequested(QualityRequest),
+    /// The binary data relayed by the server in the client-server mode
+    /// exceeds the relay throughput caps and is partially dropped.
+    Throttled(ThrottleNotice),
+    /// The binary data sent to a loopback channel is echoed back by the server.
+    BinaryDataEcho(Vec<u8>),
+    /// The server stores the uploaded recording chunks.
+    RecordingStarted,
+    /// The recording is stored on the server, the size is in bytes.
+    RecordingStopped {
+        size: u64,
+    },
+    /// The recording is not started or is stopped by the server, e.g. on the storage quota.
+    RecordingFailed(RecordingErrorMessage),
+    /// The video is stopped by the audio fallback policy, the receiver is notified.
+    AudioOnlyFallback(AudioFallbackReason),
+    /// The video stopped by the audio fallback policy is sent again.
+    VideoRestored,
+    PeerStateChanged(PeerState),
+    /// The receiver key is received, its fingerprint can be verified out-of-band.
+    PeerKeyReceived {
+        fingerprint: String,
+        local_fingerprint: String,
+    },
+    /// The receiver identity signature and DTLS fingerprint binding are verified.
+    VerifiedPeer(VerifiedPeer),
+    /// Requested with `Sender::request_peer_network`.
+    PeerNetwork(PeerNetwork),
+    /// The sender is closed, this is the last event of the sender.
+    /// The diagnostics are set if it is closed by a protocol violation,
+    /// see `ProtocolViolationPolicy::Strict`.
+    Closed {
+        reason: CloseReason,
+        diagnostics: Option<ProtocolViolationDiagnostics>,
+    },
+    /// The session will be ended by the session policy unless activity resumes.
+    SessionEnding {
+        reason: SessionEndReason,
+        remaining: Duration,
+    },
+    /// The session is ended by the session policy, the channel is closed.
+    SessionEnded(SessionEndReason),
     Error(SenderError),
 }
 
@@ -458,25 +1799,43 @@ pub enum SenderError {
     //#[error("client message send error: {0}")]
     //SendError(#[from] WebSocketClientMessageSendError),
     #[error("client message send error: {0}")]
-    IceCandidateSendError(WebSocketClientMessageSendError),
-    #[error("channel id is already used: {0:?}")]
+    IceCandidateSendError(SenderSendError),
+    #[error("ice candidate delivery error: {0}")]
+    IceCandidateDeliveryError(DeliveryError),
+    #[error("{}", ErrorCode::ChannelIdIsAlreadyUsed.describe(Some(.0), Language::English))]
     ChannelIdIsAlreadyUsed(ChannelId),
-    #[error("new RtcIceCandidate error: {}", 0.0)]
-    NewRtcIceCandidateError(JsValue),
+    #[error("{}", ErrorCode::ChannelIsReserved.describe(Some(.0), Language::English))]
+    ChannelIsReserved(ChannelId),
     #[error("add ice candidate error: {}", 0.0)]
     AddIceCandidateError(JsValue),
+    #[error("ice candidates request error: {0}")]
+    IceCandidatesRequestError(SenderSendError),
     #[error(transparent)]
     SendOfferError(#[from] SenderSendOfferError),
     #[error(transparent)]
     ReceiveAnswerError(#[from] SenderReceiveAnswerError),
+    #[error(transparent)]
+    ReceiveOfferError(#[from] SenderReceiveOfferError),
+    #[error(transparent)]
+    RtpParametersError(#[from] RtpParametersError),
+    #[error("offer was not answered after {retries} retries")]
+    NegotiationTimedOut { retries: u32 },
+    #[error(transparent)]
+    SealingError(#[from] SealingError),
+    #[error("peer public key received without sealed signaling enabled")]
+    UnexpectedPeerPublicKey,
+    #[error("receiver verification failed: {0}")]
+    VerificationError(#[from] VerificationError),
+    #[error("audio fallback error: {0}")]
+    AudioFallbackError(MediaSenderError),
 }
 
 #[derive(Error, Debug)]
 pub enum NewSenderError {
     #[error(transparent)]
-    SendError(#[from] WebSocketClientMessageSendError),
-    #[error("new RtcPeerConnection error: {0:?}")]
-    NewRtcPeerConnectionError(JsValue),
+    SendError(#[from] SenderSendError),
+    #[error(transparent)]
+    WarmConnectionError(#[from] WarmConnectionError),
 }
 
 #[derive(Error, Debug)]
@@ -493,8 +1852,16 @@ pub enum SenderSendOfferError {
     CreateOfferError(JsValue),
     #[error("set_local_description error: {0:?}")]
     SetLocalDescriptionError(JsValue),
+    #[error("created offer has no sdp")]
+    OfferSdpIsMissing,
     #[error("offer send error: {0}")]
     SendError(#[from] SenderSendError),
+    #[error("offer delivery error: {0}")]
+    DeliveryError(#[from] DeliveryError),
+    #[error(transparent)]
+    SealingError(#[from] SealingError),
+    #[error("offer signing error: {0}")]
+    IdentityError(#[from] IdentityError),
 }
 
 #[derive(Error, Debug)]
@@ -503,6 +1870,26 @@ pub enum SenderReceiveAnswerError {
     SetRemoteDescriptionError(JsValue),
 }
 
+#[derive(Error, Debug)]
+pub enum SenderReceiveOfferError {
+    #[error("set_remote_description error: {0:?}")]
+    SetRemoteDescriptionError(JsValue),
+    #[error("create_answer error: {0:?}")]
+    CreateAnswerError(JsValue),
+    #[error("set_local_description error: {0:?}")]
+    SetLocalDescriptionError(JsValue),
+    #[error("created answer has no sdp")]
+    AnswerSdpIsMissing,
+    #[error("answer send error: {0}")]
+    SendError(#[from] SenderSendError),
+    #[error("answer delivery error: {0}")]
+    DeliveryError(#[from] DeliveryError),
+    #[error(transparent)]
+    SealingError(#[from] SealingError),
+    #[error("answer signing error: {0}")]
+    IdentityError(#[from] IdentityError),
+}
+
 #[derive(Error, Debug)]
 pub enum SenderSendError {
     #[error(transparent)]
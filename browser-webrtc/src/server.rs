@@ -1,171 +1,915 @@
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::sync::atomic::AtomicU32;
-use std::collections::HashMap;
+use core::time::Duration;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
 
 use async_std::sync::{Arc, RwLock, Weak};
 use signaling_protocol::{
-    ChannelId, NetworkMode, ServerMessage, SessionReceiverId, SessionSenderId,
+    AckId, ChannelId, ChannelInfo, ChannelPreview, ChannelRequestDeclineReason,
+    ChannelRequestErrorMessage, ChannelRequestId, ChannelSecret, ClaimToken, ClientMessage,
+    MilestoneReport, NackReason, NetworkMode, PeerId, ReservationErrorMessage, ServerMessage,
+    ServerReceiverErrorMessage, ServerSenderErrorMessage, SessionReceiverId, SessionSenderId,
+    WireCodec,
 };
 use thiserror::Error;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsValue;
-use web_sys::{MessageEvent, RtcConfiguration, WebSocket};
+use web_sys::{CloseEvent, Event, MessageEvent, RtcConfiguration, WebSocket};
 
 use crate::{
-    parse_websocket_server_message, BoxAsyncFn2, BoxAsyncFn2Wrapper, NewReceiverError,
-    NewSenderError, Receiver, ReceiverEvent, Sender, SenderEvent, WebSocketServerMessageParseError,
+    parse_websocket_server_message, serialize_websocket_client_message, BoxAsyncFn2,
+    BoxAsyncFn2Wrapper, GlobalReceiverId, GlobalSenderId, IncomingRequestPolicy, MediaSnapshot,
+    NewReceiverError, NewSenderError, PeerCounts, PeerRegistry, PeerRegistryError,
+    ProtocolViolationPolicy, Receiver, ReceiverEvent, ReceiverSnapshot, ReconnectPolicy, Sender,
+    SenderEvent, SenderSnapshot, ServerId, ServerSnapshot, Telemetry, TelemetrySource,
+    WarmConnection, WebSocketClientMessageSendError, WebSocketServerMessageParseError,
 };
 
+/// Maximum number of messages waiting for the WebSocket to open.
+const MAX_OUTGOING_QUEUE_LEN: usize = 1024;
+
+/// Interval of the keepalive pings, well below the default server idle timeout.
+const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+type AckResultSender = async_std::channel::Sender<Result<(), NackReason>>;
+type ReservationResultSender =
+    async_std::channel::Sender<Result<ChannelReservation, ReservationErrorMessage>>;
+
 #[derive(Debug)]
 pub struct Server {
+    server_id: ServerId,
+    registry: Arc<PeerRegistry>,
     senders: RwLock<HashMap<SessionSenderId, Weak<Sender>>>,
     receivers: RwLock<HashMap<SessionReceiverId, Weak<Receiver>>>,
     handler: BoxAsyncFn2Wrapper<Arc<Server>, ServerEvent, ()>,
-    next_sender_id: AtomicU32,
-    next_receiver_id: AtomicU32,
-    js_websocket: WebSocket,
+    next_ack_id: AtomicU32,
+    pending_acks: RefCell<HashMap<AckId, AckResultSender>>,
+    pending_reservations: RefCell<HashMap<ChannelId, ReservationResultSender>>,
+    next_channel_request_id: Cell<u64>,
+    incoming_request_policy: RefCell<IncomingRequestPolicy>,
+    ringing_requests: RefCell<HashSet<ChannelRequestId>>,
+    open_channel_ids: RefCell<Vec<ChannelId>>,
+    is_channel_list_paused: Cell<bool>,
+    protocol_violation_policy: Cell<ProtocolViolationPolicy>,
+    telemetry: RefCell<Option<Rc<dyn Telemetry>>>,
+    /// Time to connect to the active endpoint, including the failed endpoints.
+    connection_time: Duration,
+    endpoints: Vec<String>,
+    active_endpoint: Cell<usize>,
+    js_websocket: RefCell<WebSocket>,
+    codec: Cell<WireCodec>,
+    reconnect_policy: Cell<Option<ReconnectPolicy>>,
+    keepalive_interval: Cell<Option<Duration>>,
+    /// Time of the latest received message in milliseconds since the Unix epoch.
+    last_message_at: Cell<f64>,
+    /// Time of the latest keepalive ping sent by the current connection.
+    last_ping_at: Cell<Option<f64>>,
+    /// Peer ids registered again after a reconnect.
+    registered_peers: RefCell<Vec<PeerId>>,
+    outgoing_queue: RefCell<VecDeque<Vec<u8>>>,
     js_message_handler: RefCell<Option<Closure<dyn FnMut(MessageEvent)>>>,
-    //js_close_handler: RefCell<Option<Closure<dyn FnMut(CloseEvent)>>>,
+    js_open_handler: RefCell<Option<Closure<dyn FnMut(Event)>>>,
+    js_close_handler: RefCell<Option<Closure<dyn FnMut(CloseEvent)>>>,
 }
 
 impl Server {
-    pub async fn new<Url>(
-        url: Url,
+    /// Connects to the first available signaling server of the prioritized list.
+    ///
+    /// Endpoints that fail to connect are reported with `ServerEvent::EndpointFailed`
+    /// and the connected one with `ServerEvent::EndpointActive`.
+    pub async fn new<Urls, Url>(
+        urls: Urls,
         handler: BoxAsyncFn2<Arc<Self>, ServerEvent, ()>,
     ) -> Result<Arc<Self>, NewServerError>
     where
+        Urls: IntoIterator<Item = Url>,
         Url: AsRef<str>,
     {
         log::trace!("browser_webrtc::Server::new");
 
-        use js_sys::Promise;
-        use wasm_bindgen_futures::JsFuture;
-        use web_sys::BinaryType;
+        let endpoints: Vec<String> = urls
+            .into_iter()
+            .map(|url| url.as_ref().to_owned())
+            .collect();
 
-        let js_websocket =
-            WebSocket::new(url.as_ref()).map_err(NewServerError::NewWebSocketError)?;
-        js_websocket.set_binary_type(BinaryType::Arraybuffer);
+        let started_at = js_sys::Date::now();
+        let mut failed_endpoints = Vec::new();
+        let mut connected = None;
+        for (index, url) in endpoints.iter().enumerate() {
+            match Self::connect(url).await {
+                Ok((js_websocket, codec)) => {
+                    connected = Some((index, js_websocket, codec));
+                    break;
+                }
+                Err(err) => {
+                    log::warn!("signaling server {} connect error: {}", url, err);
+                    failed_endpoints.push((url.clone(), err));
+                }
+            }
+        }
+        let (active_endpoint, js_websocket, codec) = match connected {
+            Some(connected) => connected,
+            None if endpoints.is_empty() => return Err(NewServerError::NoEndpoints),
+            None => return Err(NewServerError::AllEndpointsFailed(failed_endpoints)),
+        };
+        let connection_time =
+            Duration::from_secs_f64((js_sys::Date::now() - started_at).max(0.0) / 1000.0);
 
+        let registry = PeerRegistry::global();
         let server = Arc::new(Self {
+            server_id: registry.next_server_id(),
+            registry,
             senders: RwLock::new(HashMap::new()),
             receivers: RwLock::new(HashMap::new()),
             handler: BoxAsyncFn2Wrapper(handler),
-            next_sender_id: AtomicU32::new(0),
-            next_receiver_id: AtomicU32::new(0),
-            js_websocket: js_websocket.clone(),
+            next_ack_id: AtomicU32::new(0),
+            pending_acks: RefCell::new(HashMap::new()),
+            pending_reservations: RefCell::new(HashMap::new()),
+            next_channel_request_id: Cell::new(0),
+            incoming_request_policy: RefCell::new(IncomingRequestPolicy::default()),
+            ringing_requests: RefCell::new(HashSet::new()),
+            open_channel_ids: RefCell::new(Vec::new()),
+            is_channel_list_paused: Cell::new(false),
+            protocol_violation_policy: Cell::new(ProtocolViolationPolicy::default()),
+            telemetry: RefCell::new(None),
+            connection_time,
+            endpoints,
+            active_endpoint: Cell::new(active_endpoint),
+            js_websocket: RefCell::new(js_websocket),
+            codec: Cell::new(codec),
+            reconnect_policy: Cell::new(Some(ReconnectPolicy::default())),
+            keepalive_interval: Cell::new(Some(DEFAULT_KEEPALIVE_INTERVAL)),
+            last_message_at: Cell::new(js_sys::Date::now()),
+            last_ping_at: Cell::new(None),
+            registered_peers: RefCell::new(Vec::new()),
+            outgoing_queue: RefCell::new(VecDeque::new()),
             js_message_handler: RefCell::new(None),
-            //js_close_handler: RefCell::new(None),
+            js_open_handler: RefCell::new(None),
+            js_close_handler: RefCell::new(None),
         });
 
+        server.registry.register_server(&server);
         server.init_message_handler();
+        server.init_open_handler();
+        server.init_close_handler();
+        server.attach_handlers();
+        server.init_keepalive();
 
-        /*let js_close_handler = {
-            let server = Arc::clone(&server);
-            closure_1(move |ev: CloseEvent| {
-                let server = Arc::clone(&server);
-                spawn_local(async move { server.on_close_event(ev).await })
+        for (url, error) in failed_endpoints {
+            server
+                .handler(ServerEvent::EndpointFailed { url, error })
+                .await;
+        }
+        server
+            .handler(ServerEvent::EndpointActive {
+                index: server.active_endpoint.get(),
+                url: server.active_endpoint().to_owned(),
             })
-        };
-        js_websocket.set_onmessage(Some(js_close_handler.as_ref().unchecked_ref()));
-        let prev_handler = server.js_close_handler.replace(Some(js_close_handler));
-        debug_assert!(prev_handler.is_none());*/
+            .await;
+
+        Ok(server)
+    }
+
+    /// Connects to the server offering all enabled codecs as WebSocket subprotocols.
+    async fn connect(url: &str) -> Result<(WebSocket, WireCodec), NewServerError> {
+        use js_sys::{Array, Promise};
+        use wasm_bindgen::JsValue;
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::BinaryType;
+
+        let subprotocols: Array = WireCodec::supported()
+            .into_iter()
+            .map(|codec| JsValue::from_str(codec.subprotocol()))
+            .collect();
+        let js_websocket = WebSocket::new_with_str_sequence(url, &subprotocols)
+            .map_err(NewServerError::NewWebSocketError)?;
+        js_websocket.set_binary_type(BinaryType::Arraybuffer);
 
         let web_socket_opened = Promise::new(&mut |resolve, reject| {
             js_websocket.set_onopen(Some(&resolve));
             js_websocket.set_onerror(Some(&reject));
         });
-        let _: JsValue = JsFuture::from(web_socket_opened)
-            .await
-            .map_err(NewServerError::WebSocketError)?;
+        match JsFuture::from(web_socket_opened).await {
+            Ok(_) => {
+                let subprotocol = js_websocket.protocol();
+                let codec = if subprotocol.is_empty() {
+                    Some(WireCodec::DEFAULT)
+                } else {
+                    WireCodec::from_subprotocol(&subprotocol)
+                };
+                match codec {
+                    Some(codec) => Ok((js_websocket, codec)),
+                    None => {
+                        let _: Option<_> = js_websocket.close().ok();
+                        Err(NewServerError::UnsupportedSubprotocol(subprotocol))
+                    }
+                }
+            }
+            Err(err) => {
+                js_websocket.set_onopen(None);
+                js_websocket.set_onerror(None);
+                let _: Option<_> = js_websocket.close().ok();
+                Err(NewServerError::WebSocketError(err))
+            }
+        }
+    }
 
-        Ok(server)
+    /// Server identifier unique within the page.
+    pub fn server_id(&self) -> ServerId {
+        self.server_id
+    }
+
+    /// Registry of the peers of all servers of the page.
+    pub fn registry(&self) -> &Arc<PeerRegistry> {
+        &self.registry
+    }
+
+    /// Prioritized signaling server URLs passed to `Server::new`.
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    /// URL of the connected signaling server, the last connected one while reconnecting.
+    pub fn active_endpoint(&self) -> &str {
+        &self.endpoints[self.active_endpoint.get()]
+    }
+
+    /// Codec negotiated with the signaling server.
+    pub fn codec(&self) -> WireCodec {
+        self.codec.get()
+    }
+
+    /// Reconnection of the closed signaling WebSocket, `None` if it is disabled.
+    pub fn reconnect_policy(&self) -> Option<ReconnectPolicy> {
+        self.reconnect_policy.get()
+    }
+
+    pub fn set_reconnect_policy(&self, policy: Option<ReconnectPolicy>) {
+        self.reconnect_policy.set(policy);
+    }
+
+    /// Interval of the keepalive pings, `None` if they are disabled.
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval.get()
+    }
+
+    /// The connection is reported lost if nothing is received for the interval after a ping.
+    pub fn set_keepalive_interval(&self, interval: Option<Duration>) {
+        self.keepalive_interval.set(interval);
+        self.last_ping_at.set(None);
+    }
+
+    /// Policy of the senders and receivers of this server for unexpected server messages.
+    pub fn protocol_violation_policy(&self) -> ProtocolViolationPolicy {
+        self.protocol_violation_policy.get()
+    }
+
+    pub fn set_protocol_violation_policy(&self, policy: ProtocolViolationPolicy) {
+        self.protocol_violation_policy.set(policy);
+    }
+
+    pub fn incoming_request_policy(&self) -> IncomingRequestPolicy {
+        self.incoming_request_policy.borrow().clone()
+    }
+
+    /// Sets the handling of the incoming channel requests received afterwards,
+    /// already ringing requests keep their ring timeout.
+    pub fn set_incoming_request_policy(&self, policy: IncomingRequestPolicy) {
+        *self.incoming_request_policy.borrow_mut() = policy;
+    }
+
+    /// Sets the receiver of the call setup milestones of this server and its peers,
+    /// the signaling connection milestone is reported immediately.
+    pub fn set_telemetry(&self, telemetry: Option<Rc<dyn Telemetry>>) {
+        use signaling_protocol::CallMilestone;
+
+        if let Some(telemetry) = &telemetry {
+            telemetry.on_milestone(
+                TelemetrySource::Server,
+                MilestoneReport {
+                    milestone: CallMilestone::SignalingConnected,
+                    elapsed: self.connection_time,
+                },
+            );
+        }
+        let _: Option<_> = self.telemetry.replace(telemetry);
+    }
+
+    pub(crate) fn report_milestone(&self, source: TelemetrySource, report: MilestoneReport) {
+        let telemetry = self.telemetry.borrow().clone();
+        if let Some(telemetry) = telemetry {
+            telemetry.on_milestone(source, report);
+        }
+    }
+
+    /// Current state of the signaling server WebSocket.
+    pub fn websocket_state(&self) -> WebSocketState {
+        WebSocketState::from_ready_state(self.js_websocket.borrow().ready_state())
     }
 
     fn init_message_handler(self: &Arc<Self>) {
         use crate::closure_1;
-        use wasm_bindgen::JsCast;
         use wasm_bindgen_futures::spawn_local;
 
         let js_message_handler = {
             let self_weak = Arc::downgrade(&self);
             closure_1(move |ev: MessageEvent| {
-                let self_arc = self_weak.upgrade().unwrap();
-                spawn_local(async move { self_arc.on_message_event(ev).await })
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_message_event(ev).await });
+                }
             })
         };
-        self.js_websocket
-            .set_onmessage(Some(js_message_handler.as_ref().unchecked_ref()));
         let prev_handler = self.js_message_handler.replace(Some(js_message_handler));
         debug_assert!(prev_handler.is_none());
     }
 
+    fn init_open_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_open_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |_: Event| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_open_event().await })
+                }
+            })
+        };
+        let prev_handler = self.js_open_handler.replace(Some(js_open_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    fn init_close_handler(self: &Arc<Self>) {
+        use crate::closure_1;
+        use wasm_bindgen_futures::spawn_local;
+
+        let js_close_handler = {
+            let self_weak = Arc::downgrade(self);
+            closure_1(move |ev: CloseEvent| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    spawn_local(async move { self_arc.on_close_event(ev).await })
+                }
+            })
+        };
+        let prev_handler = self.js_close_handler.replace(Some(js_close_handler));
+        debug_assert!(prev_handler.is_none());
+    }
+
+    /// Sets the event handlers on the current WebSocket, the initial or the reconnected one.
+    fn attach_handlers(&self) {
+        use wasm_bindgen::JsCast;
+
+        let js_websocket = self.js_websocket.borrow();
+        if let Some(handler) = self.js_message_handler.borrow().as_ref() {
+            js_websocket.set_onmessage(Some(handler.as_ref().unchecked_ref()));
+        }
+        if let Some(handler) = self.js_open_handler.borrow().as_ref() {
+            js_websocket.set_onopen(Some(handler.as_ref().unchecked_ref()));
+        }
+        if let Some(handler) = self.js_close_handler.borrow().as_ref() {
+            js_websocket.set_onclose(Some(handler.as_ref().unchecked_ref()));
+        }
+    }
+
+    /// Ids of the channels listed in the latest `ServerEvent::OpenChannelsChanged`.
+    pub fn open_channel_ids(&self) -> Vec<ChannelId> {
+        self.open_channel_ids.borrow().clone()
+    }
+
+    /// Sends the message or queues it until the WebSocket is open.
+    pub fn send_message(
+        &self,
+        message: ClientMessage,
+    ) -> Result<(), WebSocketClientMessageSendError> {
+        let _: usize = self.queue_message(message)?;
+        Ok(())
+    }
+
+    /// Sends the message or queues it until the WebSocket is open,
+    /// returns the encoded message size in bytes.
+    pub(crate) fn queue_message(
+        &self,
+        message: ClientMessage,
+    ) -> Result<usize, WebSocketClientMessageSendError> {
+        let data = serialize_websocket_client_message(&message, self.codec.get())?;
+        let mut outgoing_queue = self.outgoing_queue.borrow_mut();
+        if outgoing_queue.len() >= MAX_OUTGOING_QUEUE_LEN {
+            return Err(WebSocketClientMessageSendError::QueueIsFull(
+                outgoing_queue.len(),
+            ));
+        }
+        let len = data.len();
+        outgoing_queue.push_back(data);
+        drop(outgoing_queue);
+        let _: usize = self.flush_outgoing_queue();
+        Ok(len)
+    }
+
+    /// Number of messages waiting for the WebSocket to open.
+    pub fn outgoing_queue_len(&self) -> usize {
+        self.outgoing_queue.borrow().len()
+    }
+
+    /// Sends the queued messages in order while the WebSocket is open,
+    /// returns the number of messages sent.
+    fn flush_outgoing_queue(&self) -> usize {
+        let mut outgoing_queue = self.outgoing_queue.borrow_mut();
+        let js_websocket = self.js_websocket.borrow();
+        let mut sent = 0;
+        while let Some(data) = outgoing_queue.front() {
+            if js_websocket.ready_state() != WebSocket::OPEN {
+                break;
+            }
+            match js_websocket.send_with_u8_array(data) {
+                Ok(()) => {
+                    let _: Option<_> = outgoing_queue.pop_front();
+                    sent += 1;
+                }
+                Err(err) => {
+                    log::warn!(
+                        "WebSocket send error, message is queued for retry: {:?}",
+                        err
+                    );
+                    break;
+                }
+            }
+        }
+        sent
+    }
+
+    async fn on_open_event(self: &Arc<Self>) {
+        self.handler(ServerEvent::WebSocketStateChanged(self.websocket_state()))
+            .await;
+        let sent = self.flush_outgoing_queue();
+        self.handler(ServerEvent::OutgoingQueueFlushed {
+            sent,
+            remaining: self.outgoing_queue_len(),
+        })
+        .await
+    }
+
+    /// Opens the channel with the claim token of the reserved channel id
+    /// and the secret required from the receivers, see `ChannelAccess`.
     pub async fn open_channel(
         self: &Arc<Self>,
         channel_id: ChannelId,
+        access: ChannelAccess,
         network_mode: NetworkMode,
         rtc_configuration: Option<RtcConfiguration>,
         handler: BoxAsyncFn2<Arc<Sender>, SenderEvent, ()>,
     ) -> Result<Arc<Sender>, ServerOpenChannelError> {
-        use core::sync::atomic::Ordering;
+        let connection = WarmConnection::new(rtc_configuration).map_err(NewSenderError::from)?;
+        self.open_warm_channel(channel_id, access, network_mode, connection, handler)
+            .await
+    }
 
-        let sender_id = SessionSenderId(self.next_sender_id.fetch_add(1, Ordering::Relaxed));
-        let sender = Sender::new(
-            self.js_websocket.clone(),
-            Arc::clone(self),
-            sender_id,
-            channel_id,
-            network_mode,
-            handler,
-            rtc_configuration,
-        )?;
+    /// Opens the channel on top of the connection created in advance,
+    /// the offer is sent without waiting for it if the connection is warmed up.
+    pub async fn open_warm_channel(
+        self: &Arc<Self>,
+        channel_id: ChannelId,
+        access: ChannelAccess,
+        network_mode: NetworkMode,
+        connection: WarmConnection,
+        handler: BoxAsyncFn2<Arc<Sender>, SenderEvent, ()>,
+    ) -> Result<Arc<Sender>, ServerOpenChannelError> {
+        let sender_id = {
+            let senders = self.senders.read().await;
+            loop {
+                let sender_id = SessionSenderId(random_session_id());
+                if !senders.contains_key(&sender_id) {
+                    break sender_id;
+                }
+            }
+        };
+        let sender = Sender::new(Arc::clone(self), sender_id, handler, connection);
+        sender
+            .open_channel(channel_id, access, network_mode)
+            .map_err(NewSenderError::from)?;
 
+        self.registry.register_sender(self, &sender)?;
         let mut senders = self.senders.write().await;
         let prev_sender = senders.insert(sender_id, Arc::downgrade(&sender));
         debug_assert!(prev_sender.is_none());
+        drop(senders);
+        self.on_peer_counts_changed().await;
 
         Ok(sender)
     }
 
+    /// Opens the channel and resolves only after the server confirms it,
+    /// unlike `open_channel` which reports the open errors later in sender events.
+    pub async fn open_channel_confirmed(
+        self: &Arc<Self>,
+        channel_id: ChannelId,
+        access: ChannelAccess,
+        network_mode: NetworkMode,
+        rtc_configuration: Option<RtcConfiguration>,
+        handler: BoxAsyncFn2<Arc<Sender>, SenderEvent, ()>,
+        timeout: Duration,
+    ) -> Result<Arc<Sender>, OpenError> {
+        let sender = self
+            .open_channel(channel_id, access, network_mode, rtc_configuration, handler)
+            .await?;
+        sender.opened(timeout).await?;
+        Ok(sender)
+    }
+
+    /// Opens the channel on top of the connection created in advance
+    /// and resolves only after the server confirms it.
+    pub async fn open_warm_channel_confirmed(
+        self: &Arc<Self>,
+        channel_id: ChannelId,
+        access: ChannelAccess,
+        network_mode: NetworkMode,
+        connection: WarmConnection,
+        handler: BoxAsyncFn2<Arc<Sender>, SenderEvent, ()>,
+        timeout: Duration,
+    ) -> Result<Arc<Sender>, OpenError> {
+        let sender = self
+            .open_warm_channel(channel_id, access, network_mode, connection, handler)
+            .await?;
+        sender.opened(timeout).await?;
+        Ok(sender)
+    }
+
+    /// Joins the channel, the credential is required if the channel is opened with a secret.
     pub async fn join_channel(
         self: &Arc<Self>,
         channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
         rtc_configuration: Option<RtcConfiguration>,
         handler: BoxAsyncFn2<Arc<Receiver>, ReceiverEvent, ()>,
     ) -> Result<Arc<Receiver>, ServerJoinChannelError> {
-        use core::sync::atomic::Ordering;
-
-        let receiver_id = SessionReceiverId(self.next_receiver_id.fetch_add(1, Ordering::Relaxed));
+        let receiver_id = {
+            let receivers = self.receivers.read().await;
+            loop {
+                let receiver_id = SessionReceiverId(random_session_id());
+                if !receivers.contains_key(&receiver_id) {
+                    break receiver_id;
+                }
+            }
+        };
         let receiver = Receiver::new(
-            self.js_websocket.clone(),
             Arc::clone(self),
             receiver_id,
             channel_id,
+            credential,
             handler,
             rtc_configuration,
         )?;
 
+        self.registry.register_receiver(self, &receiver)?;
         let mut receivers = self.receivers.write().await;
         let prev_receiver = receivers.insert(receiver_id, Arc::downgrade(&receiver));
         debug_assert!(prev_receiver.is_none());
+        drop(receivers);
+        self.on_peer_counts_changed().await;
 
         Ok(receiver)
     }
 
+    /// Joins the channel and resolves only after the server confirms the join,
+    /// unlike `join_channel` which reports the join errors later in receiver events.
+    pub async fn join_channel_confirmed(
+        self: &Arc<Self>,
+        channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
+        rtc_configuration: Option<RtcConfiguration>,
+        handler: BoxAsyncFn2<Arc<Receiver>, ReceiverEvent, ()>,
+        timeout: Duration,
+    ) -> Result<Arc<Receiver>, JoinError> {
+        let receiver = self
+            .join_channel(channel_id, credential, rtc_configuration, handler)
+            .await?;
+        receiver.joined(timeout).await?;
+        Ok(receiver)
+    }
+
+    /// Reserves the channel id before the channel is opened, e.g. to share a join link
+    /// before the capture is started, the channel is opened with the returned claim token.
+    pub async fn reserve_channel(
+        &self,
+        channel_id: ChannelId,
+    ) -> Result<ChannelReservation, ReserveChannelError> {
+        use async_std::channel::bounded;
+        use std::collections::hash_map::Entry;
+
+        let (result_sender, result_receiver) = bounded(1);
+        match self
+            .pending_reservations
+            .borrow_mut()
+            .entry(channel_id.clone())
+        {
+            Entry::Occupied(_) => return Err(ReserveChannelError::AlreadyPending),
+            Entry::Vacant(entry) => drop(entry.insert(result_sender)),
+        }
+        let message = ClientMessage::ReserveChannel {
+            channel_id: channel_id.clone(),
+        };
+        if let Err(err) = self.send_message(message) {
+            drop(self.pending_reservations.borrow_mut().remove(&channel_id));
+            return Err(err.into());
+        }
+        match result_receiver.recv().await {
+            Ok(Ok(reservation)) => Ok(reservation),
+            Ok(Err(reason)) => Err(ReserveChannelError::Rejected(reason)),
+            Err(_) => Err(ReserveChannelError::Cancelled),
+        }
+    }
+
+    /// Makes this client reachable by the channel requests addressed to the peer id,
+    /// e.g. the fingerprint of its identity key, until it disconnects.
+    ///
+    /// A registration failure is reported with `ServerEvent::PeerRegistrationFailed`.
+    pub fn register_peer(&self, peer_id: PeerId) -> Result<(), WebSocketClientMessageSendError> {
+        self.send_message(ClientMessage::RegisterPeer(peer_id.clone()))?;
+        let mut registered_peers = self.registered_peers.borrow_mut();
+        if !registered_peers.contains(&peer_id) {
+            registered_peers.push(peer_id);
+        }
+        Ok(())
+    }
+
+    /// Asks the registered peer to open a channel toward this client.
+    ///
+    /// The outcome is reported with `ServerEvent::ChannelRequestAccepted`,
+    /// `ChannelRequestDeclined` or `ChannelRequestFailed` with the returned request id,
+    /// the accepted channel is joined with `join_channel`.
+    pub fn request_channel(
+        &self,
+        peer_id: PeerId,
+    ) -> Result<ChannelRequestId, WebSocketClientMessageSendError> {
+        let request_id = ChannelRequestId(self.next_channel_request_id.get());
+        self.next_channel_request_id
+            .set(request_id.0.wrapping_add(1));
+        self.send_message(ClientMessage::RequestChannel {
+            request_id,
+            peer_id,
+        })?;
+        Ok(request_id)
+    }
+
+    /// Opens the channel for the `ServerEvent::IncomingRequest`
+    /// or `ServerEvent::IncomingRequestAutoAccepted` and accepts the request,
+    /// the requesting client is told to join the channel.
+    pub async fn accept_channel_request(
+        self: &Arc<Self>,
+        request_id: ChannelRequestId,
+        channel_id: ChannelId,
+        network_mode: NetworkMode,
+        rtc_configuration: Option<RtcConfiguration>,
+        handler: BoxAsyncFn2<Arc<Sender>, SenderEvent, ()>,
+    ) -> Result<Arc<Sender>, AcceptChannelRequestError> {
+        let _: bool = self.ringing_requests.borrow_mut().remove(&request_id);
+        let sender = self
+            .open_channel(
+                channel_id.clone(),
+                ChannelAccess::default(),
+                network_mode,
+                rtc_configuration,
+                handler,
+            )
+            .await?;
+        // Sent after the open message, so the channel is open when the request is accepted.
+        self.send_message(ClientMessage::AcceptChannelRequest {
+            request_id,
+            channel_id,
+        })?;
+        Ok(sender)
+    }
+
+    pub fn decline_channel_request(
+        &self,
+        request_id: ChannelRequestId,
+        reason: ChannelRequestDeclineReason,
+    ) -> Result<(), WebSocketClientMessageSendError> {
+        let _: bool = self.ringing_requests.borrow_mut().remove(&request_id);
+        self.send_message(ClientMessage::DeclineChannelRequest { request_id, reason })
+    }
+
+    /// Applies the incoming request policy, ringing requests are reported to the application.
+    async fn on_channel_requested(
+        self: &Arc<Self>,
+        request_id: ChannelRequestId,
+        from: Option<PeerId>,
+    ) {
+        use crate::IncomingRequestDecision;
+
+        let decision = self.incoming_request_policy.borrow().decide(from.as_ref());
+        match decision {
+            IncomingRequestDecision::Ring => {
+                let _: bool = self.ringing_requests.borrow_mut().insert(request_id);
+                let ring_timeout = self.incoming_request_policy.borrow().ring_timeout;
+                if let Some(ring_timeout) = ring_timeout {
+                    self.init_ring_timeout(request_id, from.clone(), ring_timeout);
+                }
+                self.handler(ServerEvent::IncomingRequest { request_id, from })
+                    .await;
+            }
+            IncomingRequestDecision::AutoAccept => {
+                self.handler(ServerEvent::IncomingRequestAutoAccepted { request_id, from })
+                    .await;
+            }
+            IncomingRequestDecision::Decline(reason) => {
+                self.auto_decline_channel_request(request_id, from, reason)
+                    .await;
+            }
+        }
+    }
+
+    fn init_ring_timeout(
+        self: &Arc<Self>,
+        request_id: ChannelRequestId,
+        from: Option<PeerId>,
+        ring_timeout: Duration,
+    ) {
+        use wasm_bindgen_futures::spawn_local;
+
+        use crate::sleep;
+
+        let self_weak = Arc::downgrade(self);
+        spawn_local(async move {
+            sleep(ring_timeout).await;
+            if let Some(self_arc) = self_weak.upgrade() {
+                if self_arc.ringing_requests.borrow().contains(&request_id) {
+                    self_arc
+                        .auto_decline_channel_request(
+                            request_id,
+                            from,
+                            ChannelRequestDeclineReason::NoAnswer,
+                        )
+                        .await;
+                }
+            }
+        });
+    }
+
+    async fn auto_decline_channel_request(
+        self: &Arc<Self>,
+        request_id: ChannelRequestId,
+        from: Option<PeerId>,
+        reason: ChannelRequestDeclineReason,
+    ) {
+        if let Err(err) = self.decline_channel_request(request_id, reason) {
+            log::error!("{}", err);
+        }
+        self.handler(ServerEvent::IncomingRequestDeclined {
+            request_id,
+            from,
+            reason,
+        })
+        .await;
+    }
+
+    /// Requests the channel poster frame,
+    /// it is received in `ServerEvent::ChannelSnapshot` if the channel sender has sent one.
+    /// The credential is required if the channel has a secret.
+    pub fn request_channel_snapshot(
+        &self,
+        channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
+    ) -> Result<(), WebSocketClientMessageSendError> {
+        self.send_message(ClientMessage::RequestChannelSnapshot {
+            channel_id,
+            credential,
+        })
+    }
+
+    /// Stops the `ServerEvent::OpenChannelsChanged` updates, e.g. while a call is active
+    /// and the channel list is not shown. Does nothing if they are already paused.
+    pub fn pause_channel_list(&self) -> Result<(), WebSocketClientMessageSendError> {
+        if !self.is_channel_list_paused.get() {
+            self.send_message(ClientMessage::PauseChannelList)?;
+            self.is_channel_list_paused.set(true);
+        }
+        Ok(())
+    }
+
+    /// Resumes the open channel list updates paused with `pause_channel_list`,
+    /// the full list is received at once.
+    pub fn resume_channel_list(&self) -> Result<(), WebSocketClientMessageSendError> {
+        if self.is_channel_list_paused.get() {
+            self.send_message(ClientMessage::ResumeChannelList)?;
+            self.is_channel_list_paused.set(false);
+        }
+        Ok(())
+    }
+
+    pub fn is_channel_list_paused(&self) -> bool {
+        self.is_channel_list_paused.get()
+    }
+
+    /// Sends the message which delivery to the peer is confirmed by the server.
+    pub fn send_acked_message(
+        &self,
+        message: ClientMessage,
+    ) -> Result<Delivery, WebSocketClientMessageSendError> {
+        Ok(self.queue_acked_message(message)?.0)
+    }
+
+    /// Sends the message with the delivery confirmation,
+    /// returns the encoded message size in bytes along with the delivery.
+    pub(crate) fn queue_acked_message(
+        &self,
+        message: ClientMessage,
+    ) -> Result<(Delivery, usize), WebSocketClientMessageSendError> {
+        use async_std::channel::bounded;
+        use core::sync::atomic::Ordering;
+
+        let ack_id = AckId(self.next_ack_id.fetch_add(1, Ordering::Relaxed));
+        let (result_sender, result_receiver) = bounded(1);
+        let len = self.queue_message(ClientMessage::WithAck {
+            ack_id,
+            message: Box::new(message),
+        })?;
+        let prev_ack = self.pending_acks.borrow_mut().insert(ack_id, result_sender);
+        debug_assert!(prev_ack.is_none());
+        Ok((Delivery(result_receiver), len))
+    }
+
     pub(crate) async fn on_sender_dropped(self: &Arc<Self>, sender_id: SessionSenderId) {
+        self.registry.unregister_sender(GlobalSenderId {
+            server_id: self.server_id,
+            sender_id,
+        });
         let mut senders = self.senders.write().await;
         let sender = senders.remove(&sender_id);
+        drop(senders);
         if sender.is_none() {
             self.error(ServerError::SenderWasAlreadyRemoved(sender_id))
                 .await
+        } else {
+            self.on_peer_counts_changed().await
         }
     }
 
     pub(crate) async fn on_receiver_dropped(self: &Arc<Self>, receiver_id: SessionReceiverId) {
+        self.registry.unregister_receiver(GlobalReceiverId {
+            server_id: self.server_id,
+            receiver_id,
+        });
         let mut receivers = self.receivers.write().await;
         let receiver = receivers.remove(&receiver_id);
+        drop(receivers);
         if receiver.is_none() {
             self.error(ServerError::ReceiverWasAlreadyRemoved(receiver_id))
                 .await
+        } else {
+            self.on_peer_counts_changed().await
+        }
+    }
+
+    async fn on_peer_counts_changed(self: &Arc<Self>) {
+        let counts = self.peer_counts().await;
+        self.handler(ServerEvent::PeerCountsChanged(counts)).await
+    }
+
+    /// Numbers of the live senders and receivers of this server.
+    pub async fn peer_counts(&self) -> PeerCounts {
+        PeerCounts {
+            senders: self.senders().await.len(),
+            receivers: self.receivers().await.len(),
+        }
+    }
+
+    async fn senders(&self) -> Vec<Arc<Sender>> {
+        let senders = self.senders.read().await;
+        senders.values().filter_map(Weak::upgrade).collect()
+    }
+
+    async fn receivers(&self) -> Vec<Arc<Receiver>> {
+        let receivers = self.receivers.read().await;
+        receivers.values().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Serializable summary of the server and the states of its senders and receivers.
+    pub async fn snapshot(&self) -> ServerSnapshot {
+        let senders: Vec<_> = self
+            .senders()
+            .await
+            .iter()
+            .map(|sender| SenderSnapshot::new(sender))
+            .collect();
+        let receivers: Vec<_> = self
+            .receivers()
+            .await
+            .iter()
+            .map(|receiver| ReceiverSnapshot::new(receiver))
+            .collect();
+        ServerSnapshot {
+            server_id: self.server_id.0,
+            active_endpoint: self.active_endpoint().to_owned(),
+            websocket_state: format!("{:?}", self.websocket_state()),
+            outgoing_queue_len: self.outgoing_queue_len(),
+            open_channel_ids: self.open_channel_ids(),
+            peer_counts: PeerCounts {
+                senders: senders.len(),
+                receivers: receivers.len(),
+            },
+            senders,
+            receivers,
         }
     }
 
@@ -178,6 +922,7 @@ impl Server {
     }
 
     async fn on_message_event(self: &Arc<Self>, ev: MessageEvent) {
+        self.last_message_at.set(js_sys::Date::now());
         match self.handle_socket_message(ev).await {
             Ok(()) => {}
             Err(err) => self.error(err).await,
@@ -185,10 +930,97 @@ impl Server {
     }
 
     async fn handle_socket_message(self: &Arc<Self>, ev: MessageEvent) -> Result<(), ServerError> {
-        match parse_websocket_server_message(ev) {
+        use js_sys::ArrayBuffer;
+        use wasm_bindgen::JsCast;
+
+        let len = ev
+            .data()
+            .dyn_ref::<ArrayBuffer>()
+            .map_or(0, |data| data.byte_length() as usize);
+        match parse_websocket_server_message(ev, self.codec.get()) {
             Ok(msg) => match msg {
-                ServerMessage::OpenChannelIdsChanged(ids) => {
-                    self.handler(ServerEvent::OpenChannelIdsChanged(ids)).await;
+                ServerMessage::OpenChannelsChanged(channels) => {
+                    let (opened, closed) = self.update_open_channel_ids(&channels);
+                    self.handler(ServerEvent::OpenChannelsChanged(channels))
+                        .await;
+                    if !opened.is_empty() || !closed.is_empty() {
+                        self.handler(ServerEvent::OpenChannelsDiff { opened, closed })
+                            .await;
+                    }
+                    Ok(())
+                }
+                ServerMessage::ChannelSnapshot {
+                    channel_id,
+                    snapshot,
+                } => {
+                    self.handler(ServerEvent::ChannelSnapshot {
+                        channel_id,
+                        snapshot: MediaSnapshot::from_png(snapshot),
+                    })
+                    .await;
+                    Ok(())
+                }
+                ServerMessage::ChannelReserved {
+                    channel_id,
+                    claim_token,
+                    valid_for,
+                } => {
+                    let reservation = ChannelReservation {
+                        channel_id: channel_id.clone(),
+                        claim_token,
+                        valid_for,
+                    };
+                    self.resolve_reservation(channel_id, Ok(reservation))
+                }
+                ServerMessage::ChannelReservationFailed { channel_id, reason } => {
+                    self.resolve_reservation(channel_id, Err(reason))
+                }
+                ServerMessage::PeerRegistrationFailed { peer_id, reason } => {
+                    self.handler(ServerEvent::PeerRegistrationFailed { peer_id, reason })
+                        .await;
+                    Ok(())
+                }
+                ServerMessage::ChannelRequested { request_id, from } => {
+                    self.on_channel_requested(request_id, from).await;
+                    Ok(())
+                }
+                ServerMessage::ChannelRequestAccepted {
+                    request_id,
+                    channel_id,
+                } => {
+                    self.handler(ServerEvent::ChannelRequestAccepted {
+                        request_id,
+                        channel_id,
+                    })
+                    .await;
+                    Ok(())
+                }
+                ServerMessage::ChannelRequestDeclined { request_id, reason } => {
+                    self.handler(ServerEvent::ChannelRequestDeclined { request_id, reason })
+                        .await;
+                    Ok(())
+                }
+                ServerMessage::ChannelRequestFailed { request_id, reason } => {
+                    self.handler(ServerEvent::ChannelRequestFailed { request_id, reason })
+                        .await;
+                    Ok(())
+                }
+                ServerMessage::ServerShutdown => {
+                    self.handler(ServerEvent::ServerShutdown).await;
+                    Ok(())
+                }
+                ServerMessage::Ack(ack_id) => self.resolve_ack(ack_id, Ok(())),
+                ServerMessage::Nack { ack_id, reason } => self.resolve_ack(ack_id, Err(reason)),
+                ServerMessage::Pong => Ok(()),
+                ServerMessage::ChannelPreviewChanged {
+                    channel_id,
+                    preview,
+                } => {
+                    self.handler(ServerEvent::ChannelPreviewChanged {
+                        channel_id,
+                        preview,
+                    })
+                    .await;
                     Ok(())
                 }
                 ServerMessage::SenderMessage { sender_id, message } => {
@@ -197,7 +1029,7 @@ impl Server {
                         Some(sender) => match sender.upgrade() {
                             Some(sender) => {
                                 drop(senders);
-                                sender.on_server_message(message).await;
+                                sender.on_server_message(message, len).await;
                                 Ok(())
                             }
                             None => Err(ServerError::SenderWasDropped(sender_id)),
@@ -214,7 +1046,7 @@ impl Server {
                         Some(receiver) => match receiver.upgrade() {
                             Some(receiver) => {
                                 drop(receivers);
-                                receiver.on_server_message(message).await;
+                                receiver.on_server_message(message, len).await;
                                 Ok(())
                             }
                             None => Err(ServerError::ReceiverWasDropped(receiver_id)),
@@ -223,40 +1055,326 @@ impl Server {
                     }
                 }
             },
-            Err(err) => Err(ServerError::ParseError(err.into())),
+            Err(WebSocketServerMessageParseError::DeserializationFailed(err))
+                if err.is_unknown_type() =>
+            {
+                log::debug!("server message of a newer protocol is skipped: {}", err);
+                Ok(())
+            }
+            Err(err) => Err(ServerError::ParseError(err)),
         }
     }
 
-    /*async fn on_close_event(self: &Arc<Self>, ev: CloseEvent) {
-        match self.handle_close_event(ev).await {
-            Ok(()) => {}
-            Err(err) => self.error(err).await,
+    /// Stores the listed channel ids and returns the opened and the closed ones
+    /// since the previous list.
+    fn update_open_channel_ids(
+        &self,
+        channels: &[ChannelInfo],
+    ) -> (Vec<ChannelId>, Vec<ChannelId>) {
+        let channel_ids: Vec<ChannelId> = channels
+            .iter()
+            .map(|channel| channel.channel_id.clone())
+            .collect();
+        let prev_channel_ids = self.open_channel_ids.replace(channel_ids.clone());
+        let opened = channel_ids
+            .iter()
+            .filter(|channel_id| !prev_channel_ids.contains(channel_id))
+            .cloned()
+            .collect();
+        let closed = prev_channel_ids
+            .into_iter()
+            .filter(|channel_id| !channel_ids.contains(channel_id))
+            .collect();
+        (opened, closed)
+    }
+
+    fn resolve_reservation(
+        &self,
+        channel_id: ChannelId,
+        result: Result<ChannelReservation, ReservationErrorMessage>,
+    ) -> Result<(), ServerError> {
+        let result_sender = self
+            .pending_reservations
+            .borrow_mut()
+            .remove(&channel_id)
+            .ok_or(ServerError::ReservationDoesNotExist(channel_id))?;
+        let _: Result<(), _> = result_sender.try_send(result);
+        Ok(())
+    }
+
+    fn resolve_ack(
+        &self,
+        ack_id: AckId,
+        result: Result<(), NackReason>,
+    ) -> Result<(), ServerError> {
+        let result_sender = self
+            .pending_acks
+            .borrow_mut()
+            .remove(&ack_id)
+            .ok_or(ServerError::AckDoesNotExist(ack_id))?;
+        // The delivery may be already dropped if nobody waits for it.
+        let _: Result<(), _> = result_sender.try_send(result);
+        Ok(())
+    }
+
+    async fn on_close_event(self: &Arc<Self>, ev: CloseEvent) {
+        self.on_disconnected(ServerEvent::WebSocketClosed {
+            code: ev.code(),
+            reason: ev.reason(),
+            was_clean: ev.was_clean(),
+        })
+        .await;
+    }
+
+    fn init_keepalive(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        spawn_local(Self::keepalive(Arc::downgrade(self)));
+    }
+
+    /// Pings the server while the WebSocket is open until the server is dropped.
+    ///
+    /// Only the messages received after a ping are awaited, so that throttled timers
+    /// of a background tab do not make an idle connection look lost.
+    async fn keepalive(self_weak: Weak<Self>) {
+        use crate::sleep;
+
+        loop {
+            let interval = match self_weak.upgrade() {
+                Some(self_arc) => self_arc
+                    .keepalive_interval
+                    .get()
+                    .unwrap_or(DEFAULT_KEEPALIVE_INTERVAL),
+                None => return,
+            };
+            sleep(interval).await;
+
+            let self_arc = match self_weak.upgrade() {
+                Some(self_arc) => self_arc,
+                None => return,
+            };
+            let interval = match self_arc.keepalive_interval.get() {
+                Some(interval) => interval,
+                None => continue,
+            };
+            if self_arc.js_websocket.borrow().ready_state() != WebSocket::OPEN {
+                continue;
+            }
+            let now = js_sys::Date::now();
+            let last_message_at = self_arc.last_message_at.get();
+            let is_lost = self_arc.last_ping_at.get().is_some_and(|ping_at| {
+                last_message_at < ping_at && now - ping_at >= interval.as_secs_f64() * 1000.0
+            });
+            if is_lost {
+                let silence = Duration::from_secs_f64((now - last_message_at).max(0.0) / 1000.0);
+                self_arc.on_connection_lost(silence).await;
+                continue;
+            }
+            match self_arc.send_message(ClientMessage::Ping) {
+                Ok(()) => self_arc.last_ping_at.set(Some(now)),
+                Err(err) => log::error!("{}", err),
+            }
         }
     }
 
-    async fn handle_close_event(self: &Arc<Self>, ev: CloseEvent) -> Result<(), ServerError> {
-        /*match ev.code() {
-            1000 => {
-                self.handler(ServerEvent::WebSocketClosed);
-                Ok(())
+    /// Closes the half-open WebSocket without waiting for its close event,
+    /// it may not be reported until the underlying connection times out.
+    async fn on_connection_lost(self: &Arc<Self>, silence: Duration) {
+        let js_websocket = self.js_websocket.borrow().clone();
+        js_websocket.set_onclose(None);
+        let _: Option<_> = js_websocket.close().ok();
+        self.last_ping_at.set(None);
+        self.on_disconnected(ServerEvent::ConnectionLost { silence })
+            .await;
+    }
+
+    /// Reports the closed or lost connection and starts the reconnection.
+    async fn on_disconnected(self: &Arc<Self>, ev: ServerEvent) {
+        use wasm_bindgen_futures::spawn_local;
+
+        // Messages awaiting acknowledgment will never be confirmed by the closed connection.
+        self.pending_acks.borrow_mut().clear();
+        self.pending_reservations.borrow_mut().clear();
+        self.handler(ServerEvent::WebSocketStateChanged(self.websocket_state()))
+            .await;
+        self.handler(ev).await;
+
+        // The reconnection keeps only a weak reference, so that a dropped server stops it.
+        if self.reconnect_policy.get().is_some() {
+            spawn_local(Self::reconnect(Arc::downgrade(self)));
+        }
+    }
+
+    /// Connects again with backoff until it succeeds, the policy is exhausted or disabled,
+    /// or the server is dropped.
+    async fn reconnect(self_weak: Weak<Self>) {
+        use crate::sleep;
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let self_arc = match self_weak.upgrade() {
+                Some(self_arc) => self_arc,
+                None => return,
+            };
+            let policy = match self_arc.reconnect_policy.get() {
+                Some(policy) => policy,
+                None => return,
+            };
+            if policy.is_exhausted(attempt - 1) {
+                self_arc
+                    .handler(ServerEvent::ReconnectFailed {
+                        attempts: attempt - 1,
+                    })
+                    .await;
+                return;
             }
-            code => Err(NewServerError {
-                WebSocketCloseError,
-            }),
-        }*/
-        Ok(())
-    }*/
+            let delay = policy.delay(attempt);
+            self_arc
+                .handler(ServerEvent::Reconnecting { attempt, delay })
+                .await;
+            drop(self_arc);
+            sleep(delay).await;
+
+            let self_arc = match self_weak.upgrade() {
+                Some(self_arc) => self_arc,
+                None => return,
+            };
+            for (index, url) in self_arc.endpoints.iter().enumerate() {
+                match Self::connect(url).await {
+                    Ok((js_websocket, codec)) => {
+                        self_arc
+                            .on_reconnected(index, js_websocket, codec, attempt)
+                            .await;
+                        return;
+                    }
+                    Err(err) => log::warn!("signaling server {} reconnect error: {}", url, err),
+                }
+            }
+        }
+    }
+
+    /// Replaces the closed WebSocket and restores the server-side state of this client:
+    /// registered peer ids, the paused channel list, the open and the joined channels.
+    async fn on_reconnected(
+        self: &Arc<Self>,
+        index: usize,
+        js_websocket: WebSocket,
+        codec: WireCodec,
+        attempt: u32,
+    ) {
+        let _: WebSocket = self.js_websocket.replace(js_websocket);
+        self.active_endpoint.set(index);
+        self.last_message_at.set(js_sys::Date::now());
+        self.last_ping_at.set(None);
+        if self.codec.replace(codec) != codec {
+            let dropped = self.outgoing_queue.borrow_mut().drain(..).count();
+            if dropped > 0 {
+                log::warn!("{} queued messages are dropped, codec is changed", dropped);
+            }
+        }
+        self.attach_handlers();
+
+        // Queued messages may depend on the restored channels, so they are sent after them.
+        let queued = self.outgoing_queue.take();
+        for peer_id in self.registered_peers.borrow().clone() {
+            if let Err(err) = self.send_message(ClientMessage::RegisterPeer(peer_id)) {
+                log::error!("{}", err);
+            }
+        }
+        if self.is_channel_list_paused.get() {
+            if let Err(err) = self.send_message(ClientMessage::PauseChannelList) {
+                log::error!("{}", err);
+            }
+        }
+        let mut resumed_sessions = 0;
+        let senders: Vec<_> = self.senders.read().await.values().cloned().collect();
+        for sender in senders.iter().filter_map(Weak::upgrade) {
+            match sender.reopen_channel() {
+                Ok(true) => resumed_sessions += 1,
+                Ok(false) => {}
+                Err(err) => log::error!("{}", err),
+            }
+        }
+        let receivers: Vec<_> = self.receivers.read().await.values().cloned().collect();
+        for receiver in receivers.iter().filter_map(Weak::upgrade) {
+            match receiver.rejoin_channel() {
+                Ok(true) => resumed_sessions += 1,
+                Ok(false) => {}
+                Err(err) => log::error!("{}", err),
+            }
+        }
+        self.outgoing_queue.borrow_mut().extend(queued);
+        let _: usize = self.flush_outgoing_queue();
+
+        self.handler(ServerEvent::WebSocketStateChanged(self.websocket_state()))
+            .await;
+        self.handler(ServerEvent::Reconnected {
+            attempt,
+            url: self.active_endpoint().to_owned(),
+            resumed_sessions,
+        })
+        .await;
+    }
 }
 
 impl Drop for Server {
     fn drop(&mut self) {
         log::trace!("browser_webrtc::Server::drop");
 
-        self.js_websocket.set_onmessage(None);
-        let _: Option<_> = self.js_websocket.close().ok();
+        self.registry.unregister_server(self.server_id);
+
+        let js_websocket = self.js_websocket.get_mut();
+        js_websocket.set_onmessage(None);
+        js_websocket.set_onopen(None);
+        js_websocket.set_onclose(None);
+        let _: Option<_> = js_websocket.close().ok();
     }
 }
 
+/// Returns a random session id using the cryptographically strong random generator
+/// with a fallback to `Math.random` if it is unavailable.
+fn random_session_id() -> u64 {
+    use js_sys::Math;
+    use web_sys::window;
+
+    let mut bytes = [0; 8];
+    let is_filled = window()
+        .and_then(|window| window.crypto().ok())
+        .and_then(|crypto| crypto.get_random_values_with_u8_array(&mut bytes).ok())
+        .is_some();
+    if is_filled {
+        u64::from_le_bytes(bytes)
+    } else {
+        let high = (Math::random() * f64::from(u32::MAX)) as u64;
+        let low = (Math::random() * f64::from(u32::MAX)) as u64;
+        high << 32 | low
+    }
+}
+
+/// Pending delivery of a message sent with `Server::send_acked_message`.
+#[derive(Debug)]
+pub struct Delivery(async_std::channel::Receiver<Result<(), NackReason>>);
+
+impl Delivery {
+    /// Resolves when the server confirms or rejects the message delivery.
+    pub async fn delivered(self) -> Result<(), DeliveryError> {
+        match self.0.recv().await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(reason)) => Err(DeliveryError::Rejected(reason)),
+            Err(_) => Err(DeliveryError::Cancelled),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DeliveryError {
+    #[error("message delivery rejected: {0}")]
+    Rejected(NackReason),
+    #[error("message delivery cancelled, server connection is dropped")]
+    Cancelled,
+}
+
 #[derive(Error, Debug)]
 pub enum NewServerError {
     #[error("new WebSocket error: {0:?}")]
@@ -265,33 +1383,219 @@ pub enum NewServerError {
     WebSocketError(JsValue),
     #[error("WebSocket close error: {0:?}")]
     WebSocketCloseError(JsValue),
-    /*#[error("WebSocket close error with code {code}, reason: {reason}, was_clean: {was_clean}")]
-    WebSocketCloseError {
-        code: u16,
-        reason: String,
-        was_clean: bool,
-    },*/
+    #[error("WebSocket subprotocol `{0}` is not supported")]
+    UnsupportedSubprotocol(String),
+    #[error("no signaling server endpoints specified")]
+    NoEndpoints,
+    #[error("all signaling server endpoints failed: {0:?}")]
+    AllEndpointsFailed(Vec<(String, NewServerError)>),
 }
 
 #[derive(Error, Debug)]
 pub enum ServerOpenChannelError {
     #[error(transparent)]
     NewSenderError(#[from] NewSenderError),
+    #[error(transparent)]
+    PeerRegistryError(#[from] PeerRegistryError),
+}
+
+#[derive(Error, Debug)]
+pub enum AcceptChannelRequestError {
+    #[error(transparent)]
+    OpenChannelError(#[from] ServerOpenChannelError),
+    #[error(transparent)]
+    SendError(#[from] WebSocketClientMessageSendError),
 }
 
 #[derive(Error, Debug)]
 pub enum ServerJoinChannelError {
     #[error(transparent)]
     NewReceiverError(#[from] NewReceiverError),
+    #[error(transparent)]
+    PeerRegistryError(#[from] PeerRegistryError),
+}
+
+/// Credentials of the opened channel.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ChannelAccess {
+    /// Required if the channel id is reserved, see `Server::reserve_channel`.
+    pub claim_token: Option<ClaimToken>,
+    /// Required from the receivers to join the channel if set.
+    pub secret: Option<ChannelSecret>,
+}
+
+/// Reserved channel id, the reservation expires if the channel is not opened in `valid_for`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChannelReservation {
+    pub channel_id: ChannelId,
+    pub claim_token: ClaimToken,
+    pub valid_for: Duration,
+}
+
+#[derive(Error, Debug)]
+pub enum ReserveChannelError {
+    #[error(transparent)]
+    SendError(#[from] WebSocketClientMessageSendError),
+    #[error("channel reservation rejected: {0}")]
+    Rejected(ReservationErrorMessage),
+    #[error("channel reservation is already requested")]
+    AlreadyPending,
+    #[error("channel reservation cancelled, server connection is closed")]
+    Cancelled,
+}
+
+#[derive(Error, Debug)]
+pub enum OpenError {
+    #[error(transparent)]
+    OpenChannelError(#[from] ServerOpenChannelError),
+    #[error("open channel rejected: {0}")]
+    Rejected(ServerSenderErrorMessage),
+    #[error("open channel was not confirmed in {0:?}")]
+    TimedOut(Duration),
+    #[error("open channel result is already awaited")]
+    AlreadyAwaited,
+}
+
+#[derive(Error, Debug)]
+pub enum JoinError {
+    #[error(transparent)]
+    JoinChannelError(#[from] ServerJoinChannelError),
+    #[error("join channel rejected: {0}")]
+    Rejected(ServerReceiverErrorMessage),
+    #[error("join channel was not confirmed in {0:?}")]
+    TimedOut(Duration),
+    #[error("join channel result is already awaited")]
+    AlreadyAwaited,
 }
 
 #[derive(Debug)]
 pub enum ServerEvent {
-    OpenChannelIdsChanged(Vec<ChannelId>),
-    WebSocketClosed,
+    OpenChannelsChanged(Vec<ChannelInfo>),
+    /// Channels opened and closed since the previous `ServerEvent::OpenChannelsChanged`,
+    /// emitted after it if any.
+    OpenChannelsDiff {
+        opened: Vec<ChannelId>,
+        closed: Vec<ChannelId>,
+    },
+    ChannelSnapshot {
+        channel_id: ChannelId,
+        snapshot: MediaSnapshot,
+    },
+    /// The sender republished the preview of a listed channel,
+    /// the previews are not resent in `ServerEvent::OpenChannelsChanged` for that.
+    ChannelPreviewChanged {
+        channel_id: ChannelId,
+        preview: ChannelPreview,
+    },
+    EndpointFailed {
+        url: String,
+        error: NewServerError,
+    },
+    EndpointActive {
+        index: usize,
+        url: String,
+    },
+    /// Messages queued while the WebSocket was not open are sent after it is opened.
+    OutgoingQueueFlushed {
+        sent: usize,
+        remaining: usize,
+    },
+    WebSocketStateChanged(WebSocketState),
+    /// A sender or a receiver is added or dropped.
+    PeerCountsChanged(PeerCounts),
+    PeerRegistrationFailed {
+        peer_id: PeerId,
+        reason: ChannelRequestErrorMessage,
+    },
+    /// Another client asks to open a channel toward it, answered with
+    /// `Server::accept_channel_request` or `Server::decline_channel_request`.
+    /// `from` is the unverified peer id registered by the requesting client.
+    IncomingRequest {
+        request_id: ChannelRequestId,
+        from: Option<PeerId>,
+    },
+    /// The request from an auto-accepted peer of the `IncomingRequestPolicy`,
+    /// expected to be accepted with `Server::accept_channel_request` without ringing.
+    IncomingRequestAutoAccepted {
+        request_id: ChannelRequestId,
+        from: Option<PeerId>,
+    },
+    /// The request is declined by the `IncomingRequestPolicy`
+    /// because of do-not-disturb or the ring timeout.
+    IncomingRequestDeclined {
+        request_id: ChannelRequestId,
+        from: Option<PeerId>,
+        reason: ChannelRequestDeclineReason,
+    },
+    /// The requested peer opened the channel to be joined.
+    ChannelRequestAccepted {
+        request_id: ChannelRequestId,
+        channel_id: ChannelId,
+    },
+    ChannelRequestDeclined {
+        request_id: ChannelRequestId,
+        reason: ChannelRequestDeclineReason,
+    },
+    /// The request is not delivered or its answer is rejected,
+    /// the request id is the one of the request or of the answer respectively.
+    ChannelRequestFailed {
+        request_id: ChannelRequestId,
+        reason: ChannelRequestErrorMessage,
+    },
+    /// The signaling server is shutting down, its connection is closed right after.
+    ServerShutdown,
+    /// The signaling server connection is closed with the close code and reason,
+    /// it is reconnected afterwards if the `ReconnectPolicy` is set.
+    WebSocketClosed {
+        code: u16,
+        reason: String,
+        was_clean: bool,
+    },
+    /// Nothing was received for a keepalive interval after a ping,
+    /// the WebSocket is closed and reconnected as if the server closed it.
+    ConnectionLost {
+        /// Time since the latest received message.
+        silence: Duration,
+    },
+    /// The reconnect attempt starts after the delay.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    /// The connection is restored, the live senders and receivers
+    /// are opening and joining their channels again.
+    Reconnected {
+        attempt: u32,
+        url: String,
+        resumed_sessions: usize,
+    },
+    /// All attempts allowed by the `ReconnectPolicy` failed.
+    ReconnectFailed {
+        attempts: u32,
+    },
     Error(ServerError),
 }
 
+/// Signaling server WebSocket state as reported by its `readyState`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WebSocketState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+impl WebSocketState {
+    fn from_ready_state(ready_state: u16) -> Self {
+        match ready_state {
+            WebSocket::CONNECTING => Self::Connecting,
+            WebSocket::OPEN => Self::Open,
+            WebSocket::CLOSING => Self::Closing,
+            _ => Self::Closed,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ServerError {
     #[error("server message parse error: {0}")]
@@ -308,4 +1612,8 @@ pub enum ServerError {
     ReceiverWasDropped(SessionReceiverId),
     #[error("receiver `{}` was already removed", 0.0)]
     ReceiverWasAlreadyRemoved(SessionReceiverId),
+    #[error("acknowledgment `{}` does not exist", 0.0)]
+    AckDoesNotExist(AckId),
+    #[error("reservation of channel `{}` was not requested", 0.0)]
+    ReservationDoesNotExist(ChannelId),
 }
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use signaling_protocol::{ChannelId, SessionReceiverId, SessionSenderId};
+
+use crate::{Receiver, Sender};
+
+/// Numbers of the live senders and receivers of a server.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Serialize)]
+pub struct PeerCounts {
+    pub senders: usize,
+    pub receivers: usize,
+}
+
+/// Serializable summary of a server and its peers, e.g. for host application dashboards.
+///
+/// Browser enum states are stored as their names.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ServerSnapshot {
+    pub server_id: u32,
+    pub active_endpoint: String,
+    pub websocket_state: String,
+    pub outgoing_queue_len: usize,
+    pub open_channel_ids: Vec<ChannelId>,
+    pub peer_counts: PeerCounts,
+    pub senders: Vec<SenderSnapshot>,
+    pub receivers: Vec<ReceiverSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SenderSnapshot {
+    pub sender_id: SessionSenderId,
+    /// `None` until the channel open message is sent.
+    pub channel_id: Option<ChannelId>,
+    pub ice_connection_state: String,
+    pub ice_gathering_state: String,
+    pub signaling_state: String,
+    pub quality_score: Option<u8>,
+    pub media_stream_count: usize,
+    pub is_closed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReceiverSnapshot {
+    pub receiver_id: SessionReceiverId,
+    pub channel_id: ChannelId,
+    pub ice_connection_state: String,
+    pub ice_gathering_state: String,
+    pub signaling_state: String,
+    pub quality_score: Option<u8>,
+    pub is_closed: bool,
+}
+
+impl SenderSnapshot {
+    pub(crate) fn new(sender: &Sender) -> Self {
+        Self {
+            sender_id: sender.sender_id(),
+            channel_id: sender.channel_id(),
+            ice_connection_state: format!("{:?}", sender.ice_connection_state()),
+            ice_gathering_state: format!("{:?}", sender.ice_gathering_state()),
+            signaling_state: format!("{:?}", sender.signaling_state()),
+            quality_score: sender.quality().map(|quality| quality.score),
+            media_stream_count: sender.media_senders().len(),
+            is_closed: sender.is_closed(),
+        }
+    }
+}
+
+impl ReceiverSnapshot {
+    pub(crate) fn new(receiver: &Receiver) -> Self {
+        Self {
+            receiver_id: receiver.receiver_id(),
+            channel_id: receiver.channel_id(),
+            ice_connection_state: format!("{:?}", receiver.ice_connection_state()),
+            ice_gathering_state: format!("{:?}", receiver.ice_gathering_state()),
+            signaling_state: format!("{:?}", receiver.signaling_state()),
+            quality_score: receiver.quality().map(|quality| quality.score),
+            is_closed: receiver.is_closed(),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+
+use signaling_protocol::{SessionSummary, StateDuration};
+use wasm_bindgen::JsValue;
+use web_sys::RtcIceConnectionState;
+
+use crate::stats::{stat_f64, stat_string};
+
+/// Tracks the connection state durations and the traffic totals of a peer connection.
+#[derive(Debug)]
+pub(crate) struct SessionMetrics {
+    started_at: f64,
+    state: RefCell<(RtcIceConnectionState, f64)>,
+    state_durations: RefCell<Vec<StateDuration>>,
+    traffic: Cell<TrafficTotals>,
+    signaling: Cell<TrafficBytes>,
+    last_activity_at: Cell<f64>,
+}
+
+/// Cumulative bytes of a session split by the traffic kind.
+///
+/// Media and data bytes are payload and RTP header bytes as of the latest
+/// periodic stats sample, signaling bytes are the encoded server messages
+/// of the session as they are sent and received.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SessionUsage {
+    pub media: TrafficBytes,
+    pub data: TrafficBytes,
+    pub signaling: TrafficBytes,
+    /// All bytes of the peer connection transport,
+    /// including media and data with their packet overhead, RTCP and ICE checks.
+    pub transport: TrafficBytes,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TrafficBytes {
+    pub sent: u64,
+    pub received: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct TrafficTotals {
+    bytes_sent: u64,
+    bytes_received: u64,
+    media_bytes_sent: u64,
+    media_bytes_received: u64,
+    data_bytes_sent: u64,
+    data_bytes_received: u64,
+    frames_sent: u64,
+    frames_received: u64,
+    messages_sent: u64,
+    messages_received: u64,
+}
+
+impl SessionMetrics {
+    pub(crate) fn new() -> Self {
+        let now = now();
+        Self {
+            started_at: now,
+            state: RefCell::new((RtcIceConnectionState::New, now)),
+            state_durations: RefCell::new(Vec::new()),
+            traffic: Cell::new(TrafficTotals::default()),
+            signaling: Cell::new(TrafficBytes::default()),
+            last_activity_at: Cell::new(now),
+        }
+    }
+
+    pub(crate) fn set_state(&self, state: RtcIceConnectionState) {
+        let now = now();
+        let mut current = self.state.borrow_mut();
+        if current.0 != state {
+            add_duration(
+                &mut self.state_durations.borrow_mut(),
+                current.0,
+                now - current.1,
+            );
+            *current = (state, now);
+        }
+    }
+
+    /// Updates the traffic totals from the connection stats,
+    /// totals are never decreased when streams are removed.
+    pub(crate) fn update_traffic(&self, stats: &[JsValue]) {
+        let mut sample = TrafficTotals::default();
+        for stat in stats {
+            let value = |name| stat_f64(stat, name).unwrap_or(0.0) as u64;
+            match stat_string(stat, "type").as_deref() {
+                Some("transport") => {
+                    sample.bytes_sent += value("bytesSent");
+                    sample.bytes_received += value("bytesReceived");
+                }
+                Some("outbound-rtp") => {
+                    sample.media_bytes_sent += value("bytesSent") + value("headerBytesSent");
+                    sample.frames_sent += value("framesSent");
+                }
+                Some("inbound-rtp") => {
+                    sample.media_bytes_received +=
+                        value("bytesReceived") + value("headerBytesReceived");
+                    sample.frames_received += value("framesReceived");
+                }
+                Some("data-channel") => {
+                    sample.data_bytes_sent += value("bytesSent");
+                    sample.data_bytes_received += value("bytesReceived");
+                    sample.messages_sent += value("messagesSent");
+                    sample.messages_received += value("messagesReceived");
+                }
+                _ => {}
+            }
+        }
+        let prev = self.traffic.get();
+        let next = TrafficTotals {
+            bytes_sent: prev.bytes_sent.max(sample.bytes_sent),
+            bytes_received: prev.bytes_received.max(sample.bytes_received),
+            media_bytes_sent: prev.media_bytes_sent.max(sample.media_bytes_sent),
+            media_bytes_received: prev.media_bytes_received.max(sample.media_bytes_received),
+            data_bytes_sent: prev.data_bytes_sent.max(sample.data_bytes_sent),
+            data_bytes_received: prev.data_bytes_received.max(sample.data_bytes_received),
+            frames_sent: prev.frames_sent.max(sample.frames_sent),
+            frames_received: prev.frames_received.max(sample.frames_received),
+            messages_sent: prev.messages_sent.max(sample.messages_sent),
+            messages_received: prev.messages_received.max(sample.messages_received),
+        };
+        // Bytes are not counted as activity, they include keepalives and RTCP.
+        if (
+            next.frames_sent,
+            next.frames_received,
+            next.messages_sent,
+            next.messages_received,
+        ) != (
+            prev.frames_sent,
+            prev.frames_received,
+            prev.messages_sent,
+            prev.messages_received,
+        ) {
+            self.last_activity_at.set(now());
+        }
+        self.traffic.set(next);
+    }
+
+    pub(crate) fn add_signaling_sent(&self, bytes: usize) {
+        let mut signaling = self.signaling.get();
+        signaling.sent += bytes as u64;
+        self.signaling.set(signaling);
+    }
+
+    pub(crate) fn add_signaling_received(&self, bytes: usize) {
+        let mut signaling = self.signaling.get();
+        signaling.received += bytes as u64;
+        self.signaling.set(signaling);
+    }
+
+    /// Time since the peer connection was created.
+    pub(crate) fn elapsed(&self) -> Duration {
+        duration_ms(now() - self.started_at)
+    }
+
+    /// Time since media frames or data channel messages were last sent or received.
+    pub(crate) fn inactive_for(&self) -> Duration {
+        duration_ms(now() - self.last_activity_at.get())
+    }
+
+    pub(crate) fn summary(&self) -> SessionSummary {
+        let now = now();
+        let mut state_durations = self.state_durations.borrow().clone();
+        let (state, since) = *self.state.borrow();
+        add_duration(&mut state_durations, state, now - since);
+        let traffic = self.traffic.get();
+        SessionSummary {
+            duration: duration_ms(now - self.started_at),
+            state_durations,
+            bytes_sent: traffic.bytes_sent,
+            bytes_received: traffic.bytes_received,
+            frames_sent: traffic.frames_sent,
+            frames_received: traffic.frames_received,
+        }
+    }
+
+    pub(crate) fn usage(&self) -> SessionUsage {
+        let traffic = self.traffic.get();
+        SessionUsage {
+            media: TrafficBytes {
+                sent: traffic.media_bytes_sent,
+                received: traffic.media_bytes_received,
+            },
+            data: TrafficBytes {
+                sent: traffic.data_bytes_sent,
+                received: traffic.data_bytes_received,
+            },
+            signaling: self.signaling.get(),
+            transport: TrafficBytes {
+                sent: traffic.bytes_sent,
+                received: traffic.bytes_received,
+            },
+        }
+    }
+}
+
+impl SessionUsage {
+    /// Bytes of the peer connection transport and the signaling together.
+    pub fn total(&self) -> TrafficBytes {
+        TrafficBytes {
+            sent: self.transport.sent + self.signaling.sent,
+            received: self.transport.received + self.signaling.received,
+        }
+    }
+}
+
+fn add_duration(
+    state_durations: &mut Vec<StateDuration>,
+    state: RtcIceConnectionState,
+    duration: f64,
+) {
+    let state = format!("{:?}", state);
+    let duration = duration_ms(duration);
+    match state_durations
+        .iter_mut()
+        .find(|state_duration| state_duration.state == state)
+    {
+        Some(state_duration) => state_duration.duration += duration,
+        None => state_durations.push(StateDuration { state, duration }),
+    }
+}
+
+fn duration_ms(duration: f64) -> Duration {
+    Duration::from_secs_f64(duration.max(0.0) / 1000.0)
+}
+
+fn now() -> f64 {
+    js_sys::Date::now()
+}
@@ -0,0 +1,101 @@
+use core::cell::Cell;
+use core::time::Duration;
+
+/// Limits of a session, e.g. for kiosk or demo deployments that may leak sessions.
+///
+/// A `SessionEnding` event is emitted `warning_period` before a limit is reached,
+/// then the session is closed with a `SessionEnded` event. Everything is disabled by default.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SessionPolicy {
+    /// Maximum time since the peer connection was created.
+    pub max_duration: Option<Duration>,
+    /// Maximum time without sent or received media frames and data channel messages.
+    pub inactivity_timeout: Option<Duration>,
+    pub warning_period: Duration,
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        Self {
+            max_duration: None,
+            inactivity_timeout: None,
+            warning_period: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SessionEndReason {
+    MaxDurationReached,
+    Inactivity,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum SessionLimitAction {
+    Warn {
+        reason: SessionEndReason,
+        remaining: Duration,
+    },
+    End(SessionEndReason),
+}
+
+/// Checks the session policy limits on every stats sample.
+#[derive(Debug, Default)]
+pub(crate) struct SessionLimiter {
+    policy: Cell<SessionPolicy>,
+    is_duration_warned: Cell<bool>,
+    is_inactivity_warned: Cell<bool>,
+}
+
+impl SessionLimiter {
+    pub(crate) fn policy(&self) -> SessionPolicy {
+        self.policy.get()
+    }
+
+    pub(crate) fn set_policy(&self, policy: SessionPolicy) {
+        self.policy.set(policy);
+        self.is_duration_warned.set(false);
+        self.is_inactivity_warned.set(false);
+    }
+
+    pub(crate) fn check(
+        &self,
+        elapsed: Duration,
+        inactive_for: Duration,
+    ) -> Option<SessionLimitAction> {
+        let policy = self.policy.get();
+        let limits = [
+            (
+                SessionEndReason::MaxDurationReached,
+                policy.max_duration,
+                elapsed,
+                &self.is_duration_warned,
+            ),
+            (
+                SessionEndReason::Inactivity,
+                policy.inactivity_timeout,
+                inactive_for,
+                &self.is_inactivity_warned,
+            ),
+        ];
+        for (reason, limit, value, is_warned) in limits {
+            let limit = match limit {
+                Some(limit) => limit,
+                None => continue,
+            };
+            if value >= limit {
+                return Some(SessionLimitAction::End(reason));
+            }
+            let remaining = limit - value;
+            if remaining <= policy.warning_period {
+                if !is_warned.replace(true) {
+                    return Some(SessionLimitAction::Warn { reason, remaining });
+                }
+            } else {
+                // The activity is resumed after the warning.
+                is_warned.set(false);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,223 @@
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlVideoElement, MediaStream};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaSnapshotFormat {
+    Png,
+    /// Lossy encoding with the quality from 0.0 to 1.0, suitable for small thumbnails.
+    Jpeg {
+        quality: f64,
+    },
+}
+
+impl MediaSnapshotFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg { .. } => "image/jpeg",
+        }
+    }
+}
+
+/// Encoded image of a single video frame, e.g. a channel poster frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaSnapshot {
+    format: MediaSnapshotFormat,
+    data: Vec<u8>,
+}
+
+impl MediaSnapshot {
+    pub fn new(format: MediaSnapshotFormat, data: Vec<u8>) -> Self {
+        Self { format, data }
+    }
+
+    pub fn from_png(data: Vec<u8>) -> Self {
+        Self::new(MediaSnapshotFormat::Png, data)
+    }
+
+    /// Creates a snapshot from JPEG bytes, the quality is unknown and is reported as 1.0.
+    pub fn from_jpeg(data: Vec<u8>) -> Self {
+        Self::new(MediaSnapshotFormat::Jpeg { quality: 1.0 }, data)
+    }
+
+    /// Captures the current frame of the video element as PNG,
+    /// frames wider than `max_width` are downscaled preserving the aspect ratio.
+    pub async fn capture_video(
+        video: &HtmlVideoElement,
+        max_width: u32,
+    ) -> Result<Self, MediaSnapshotError> {
+        Self::capture_video_with_format(video, max_width, MediaSnapshotFormat::Png).await
+    }
+
+    pub async fn capture_video_with_format(
+        video: &HtmlVideoElement,
+        max_width: u32,
+        format: MediaSnapshotFormat,
+    ) -> Result<Self, MediaSnapshotError> {
+        use js_sys::{Promise, Uint8Array};
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::{Blob, CanvasRenderingContext2d, HtmlCanvasElement};
+
+        let (video_width, video_height) = (video.video_width(), video.video_height());
+        if video_width == 0 || video_height == 0 {
+            return Err(MediaSnapshotError::NoVideoFrame);
+        }
+        let scale = (f64::from(max_width) / f64::from(video_width)).min(1.0);
+        let width = (f64::from(video_width) * scale).round().max(1.0);
+        let height = (f64::from(video_height) * scale).round().max(1.0);
+
+        let canvas: HtmlCanvasElement = create_element("canvas")?
+            .dyn_into()
+            .map_err(|element| MediaSnapshotError::ElementCreateError(element.into()))?;
+        canvas.set_width(width as u32);
+        canvas.set_height(height as u32);
+
+        let context: CanvasRenderingContext2d = canvas
+            .get_context("2d")
+            .map_err(MediaSnapshotError::GetContextError)?
+            .and_then(|context| context.dyn_into().ok())
+            .ok_or(MediaSnapshotError::ContextIsUnavailable)?;
+        context
+            .draw_image_with_html_video_element_and_dw_and_dh(video, 0.0, 0.0, width, height)
+            .map_err(MediaSnapshotError::DrawImageError)?;
+
+        let blob = Promise::new(&mut |resolve, reject| {
+            let result = match format {
+                MediaSnapshotFormat::Png => canvas.to_blob_with_type(&resolve, format.mime_type()),
+                MediaSnapshotFormat::Jpeg { quality } => canvas
+                    .to_blob_with_type_and_encoder_options(
+                        &resolve,
+                        format.mime_type(),
+                        &JsValue::from_f64(quality),
+                    ),
+            };
+            if let Err(err) = result {
+                let _: JsValue = reject.call1(&JsValue::UNDEFINED, &err).unwrap_or_default();
+            }
+        });
+        let blob: Blob = JsFuture::from(blob)
+            .await
+            .map_err(MediaSnapshotError::EncodeError)?
+            .dyn_into()
+            .map_err(MediaSnapshotError::EncodeError)?;
+        let array_buffer = JsFuture::from(blob.array_buffer())
+            .await
+            .map_err(MediaSnapshotError::EncodeError)?;
+
+        Ok(Self::new(format, Uint8Array::new(&array_buffer).to_vec()))
+    }
+
+    /// Captures the current video frame of the media stream
+    /// using a temporary muted video element.
+    pub async fn capture_media_stream(
+        media_stream: &MediaStream,
+        max_width: u32,
+        format: MediaSnapshotFormat,
+    ) -> Result<Self, MediaSnapshotError> {
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        if media_stream.get_video_tracks().length() == 0 {
+            return Err(MediaSnapshotError::NoVideoFrame);
+        }
+
+        let video: HtmlVideoElement = create_element("video")?
+            .dyn_into()
+            .map_err(|element| MediaSnapshotError::ElementCreateError(element.into()))?;
+        video.set_muted(true);
+        let _: Option<_> = video.set_attribute("playsinline", "").ok();
+        video.set_src_object(Some(media_stream));
+
+        let play_result = match video.play() {
+            Ok(promise) => JsFuture::from(promise).await,
+            Err(err) => Err(err),
+        };
+        let snapshot = match play_result {
+            Ok(_) => Self::capture_video_with_format(&video, max_width, format).await,
+            Err(err) => Err(MediaSnapshotError::PlayError(err)),
+        };
+
+        let _: Option<()> = video.pause().ok();
+        video.set_src_object(None);
+        snapshot
+    }
+
+    pub fn format(&self) -> MediaSnapshotFormat {
+        self.format
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns `data:` URL of the image which can be used as `img` source.
+    pub fn data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.format.mime_type(),
+            base64_encode(&self.data)
+        )
+    }
+}
+
+fn create_element(name: &str) -> Result<web_sys::Element, MediaSnapshotError> {
+    use web_sys::window;
+
+    window()
+        .ok_or(MediaSnapshotError::WindowIsUndefined)?
+        .document()
+        .ok_or(MediaSnapshotError::DocumentIsUndefined)?
+        .create_element(name)
+        .map_err(MediaSnapshotError::ElementCreateError)
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let bits = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (bits >> (18 - 6 * index)) & 0x3f;
+                result.push(char::from(ALPHABET[sextet as usize]));
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[derive(Error, Debug)]
+pub enum MediaSnapshotError {
+    #[error("video has no frames to capture")]
+    NoVideoFrame,
+    #[error("JavaScript window is undefined")]
+    WindowIsUndefined,
+    #[error("JavaScript document is undefined")]
+    DocumentIsUndefined,
+    #[error("failed to create element: {0:?}")]
+    ElementCreateError(JsValue),
+    #[error("video play error: {0:?}")]
+    PlayError(JsValue),
+    #[error("canvas getContext error: {0:?}")]
+    GetContextError(JsValue),
+    #[error("canvas 2d context is unavailable")]
+    ContextIsUnavailable,
+    #[error("canvas drawImage error: {0:?}")]
+    DrawImageError(JsValue),
+    #[error("image encode error: {0:?}")]
+    EncodeError(JsValue),
+}
@@ -0,0 +1,151 @@
+use core::time::Duration;
+
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+
+/// Keyframe requests received from remote receivers.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct KeyFrameRequestStats {
+    /// Picture Loss Indication count.
+    pub pli_count: u32,
+    /// Full Intra Request count.
+    pub fir_count: u32,
+}
+
+impl KeyFrameRequestStats {
+    pub(crate) fn from_outbound_rtp_stats(stats: &[JsValue]) -> Self {
+        stats
+            .iter()
+            .filter(|stat| {
+                stat_string(stat, "type").as_deref() == Some("outbound-rtp")
+                    && stat_string(stat, "kind").as_deref() == Some("video")
+            })
+            .fold(Self::default(), |result, stat| Self {
+                pli_count: result.pli_count + stat_f64(stat, "pliCount").unwrap_or(0.0) as u32,
+                fir_count: result.fir_count + stat_f64(stat, "firCount").unwrap_or(0.0) as u32,
+            })
+    }
+}
+
+/// Received video statistics, rates are computed relative to the previous sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InboundVideoStats {
+    /// Stats timestamp in milliseconds.
+    pub timestamp: f64,
+    pub bytes_received: f64,
+    /// Bits per second since the previous sample.
+    pub bitrate: Option<f64>,
+    pub frames_per_second: Option<f64>,
+    pub frame_width: Option<u32>,
+    pub frame_height: Option<u32>,
+    pub jitter: Option<Duration>,
+    /// Average time a frame is held in the jitter buffer.
+    pub jitter_buffer_delay: Option<Duration>,
+    pub round_trip_time: Option<Duration>,
+}
+
+impl InboundVideoStats {
+    /// Returns stats of the first inbound video stream of the report, if any.
+    pub(crate) fn from_stats(stats: &[JsValue], prev: Option<&Self>) -> Option<Self> {
+        let inbound = stats.iter().find(|stat| {
+            stat_string(stat, "type").as_deref() == Some("inbound-rtp")
+                && stat_string(stat, "kind").as_deref() == Some("video")
+        })?;
+        let round_trip_time = stats
+            .iter()
+            .filter(|stat| stat_string(stat, "type").as_deref() == Some("candidate-pair"))
+            .filter(|stat| stat_string(stat, "state").as_deref() == Some("succeeded"))
+            .find_map(|stat| stat_f64(stat, "currentRoundTripTime"))
+            .map(Duration::from_secs_f64);
+
+        let timestamp = stat_f64(inbound, "timestamp").unwrap_or(0.0);
+        let bytes_received = stat_f64(inbound, "bytesReceived").unwrap_or(0.0);
+        let bitrate = prev
+            .filter(|prev| timestamp > prev.timestamp && bytes_received >= prev.bytes_received)
+            .map(|prev| {
+                (bytes_received - prev.bytes_received) * 8.0 * 1000.0 / (timestamp - prev.timestamp)
+            });
+        let jitter_buffer_delay = match (
+            stat_f64(inbound, "jitterBufferDelay"),
+            stat_f64(inbound, "jitterBufferEmittedCount"),
+        ) {
+            (Some(delay), Some(count)) if count > 0.0 => {
+                Some(Duration::from_secs_f64(delay / count))
+            }
+            _ => None,
+        };
+
+        Some(Self {
+            timestamp,
+            bytes_received,
+            bitrate,
+            frames_per_second: stat_f64(inbound, "framesPerSecond"),
+            frame_width: stat_f64(inbound, "frameWidth").map(|width| width as u32),
+            frame_height: stat_f64(inbound, "frameHeight").map(|height| height as u32),
+            jitter: stat_f64(inbound, "jitter").map(Duration::from_secs_f64),
+            jitter_buffer_delay,
+            round_trip_time,
+        })
+    }
+
+    /// Estimated end-to-end video latency.
+    ///
+    /// Peer clocks are not synchronized, so the one-way network delay is estimated
+    /// as a half of the round trip time, decoding and rendering time are not included.
+    pub fn estimated_latency(&self) -> Option<Duration> {
+        Some(self.round_trip_time? / 2 + self.jitter_buffer_delay?)
+    }
+}
+
+/// Resolves a `getStats` promise and returns all stats of the report.
+pub(crate) async fn stats_report_values(
+    promise: js_sys::Promise,
+) -> Result<Vec<JsValue>, StatsError> {
+    use js_sys::{try_iter, Function, Reflect};
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let report = JsFuture::from(promise)
+        .await
+        .map_err(StatsError::GetStatsError)?;
+    let values: Function = Reflect::get(&report, &JsValue::from_str("values"))
+        .and_then(JsCast::dyn_into)
+        .map_err(StatsError::InvalidStatsReport)?;
+    let values = values
+        .call0(&report)
+        .map_err(StatsError::InvalidStatsReport)?;
+    let values = try_iter(&values)
+        .map_err(StatsError::InvalidStatsReport)?
+        .ok_or_else(|| StatsError::InvalidStatsReport(values.clone()))?;
+    values
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(StatsError::InvalidStatsReport)
+}
+
+pub(crate) fn stat_f64(stat: &JsValue, name: &str) -> Option<f64> {
+    use js_sys::Reflect;
+
+    Reflect::get(stat, &JsValue::from_str(name)).ok()?.as_f64()
+}
+
+pub(crate) fn stat_bool(stat: &JsValue, name: &str) -> Option<bool> {
+    use js_sys::Reflect;
+
+    Reflect::get(stat, &JsValue::from_str(name)).ok()?.as_bool()
+}
+
+pub(crate) fn stat_string(stat: &JsValue, name: &str) -> Option<String> {
+    use js_sys::Reflect;
+
+    Reflect::get(stat, &JsValue::from_str(name))
+        .ok()?
+        .as_string()
+}
+
+#[derive(Error, Debug)]
+pub enum StatsError {
+    #[error("getStats error: {0:?}")]
+    GetStatsError(JsValue),
+    #[error("invalid stats report: {0:?}")]
+    InvalidStatsReport(JsValue),
+}
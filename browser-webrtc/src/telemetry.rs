@@ -0,0 +1,102 @@
+use core::cell::RefCell;
+use core::fmt;
+use core::time::Duration;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use async_std::sync::{Arc, Weak};
+use signaling_protocol::{CallMilestone, MilestoneReport, SessionReceiverId, SessionSenderId};
+
+use crate::Server;
+
+/// Delay of the milestones reporting, so that the milestones of a call setup are sent together.
+pub const TELEMETRY_BATCH_DELAY: Duration = Duration::from_secs(2);
+
+/// Receives the call setup milestones, e.g. to measure the setup times and success rate.
+///
+/// It is set with `Server::set_telemetry`.
+pub trait Telemetry: fmt::Debug {
+    fn on_milestone(&self, source: TelemetrySource, report: MilestoneReport);
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TelemetrySource {
+    /// Signaling milestones, elapsed since the server connection was started.
+    Server,
+    /// Sender milestones, elapsed since its peer connection was created.
+    Sender(SessionSenderId),
+    /// Receiver milestones, elapsed since its peer connection was created.
+    Receiver(SessionReceiverId),
+}
+
+/// Reference telemetry that batches the milestones and reports them to the signaling server.
+#[derive(Debug)]
+pub struct ServerTelemetry {
+    server: Weak<Server>,
+    pending: Rc<RefCell<Vec<MilestoneReport>>>,
+}
+
+impl ServerTelemetry {
+    pub fn new(server: &Arc<Server>) -> Rc<Self> {
+        Rc::new(Self {
+            server: Arc::downgrade(server),
+            pending: Rc::new(RefCell::new(Vec::new())),
+        })
+    }
+}
+
+impl Telemetry for ServerTelemetry {
+    fn on_milestone(&self, _: TelemetrySource, report: MilestoneReport) {
+        use signaling_protocol::ClientMessage;
+        use wasm_bindgen_futures::spawn_local;
+
+        let is_first = {
+            let mut pending = self.pending.borrow_mut();
+            pending.push(report);
+            pending.len() == 1
+        };
+        if !is_first {
+            return;
+        }
+
+        let server = Weak::clone(&self.server);
+        let pending = Rc::clone(&self.pending);
+        spawn_local(async move {
+            use crate::sleep;
+
+            sleep(TELEMETRY_BATCH_DELAY).await;
+            let reports = pending.replace(Vec::new());
+            if let Some(server) = server.upgrade() {
+                if let Err(err) = server.send_message(ClientMessage::ReportMilestones(reports)) {
+                    log::warn!("telemetry report error: {}", err);
+                }
+            }
+        });
+    }
+}
+
+/// Reports every milestone of a peer connection at most once.
+#[derive(Debug)]
+pub(crate) struct MilestoneTracker {
+    source: TelemetrySource,
+    reported: RefCell<HashSet<CallMilestone>>,
+}
+
+impl MilestoneTracker {
+    pub(crate) fn new(source: TelemetrySource) -> Self {
+        Self {
+            source,
+            reported: RefCell::new(HashSet::new()),
+        }
+    }
+
+    pub(crate) fn is_reported(&self, milestone: CallMilestone) -> bool {
+        self.reported.borrow().contains(&milestone)
+    }
+
+    pub(crate) fn report(&self, server: &Server, milestone: CallMilestone, elapsed: Duration) {
+        if self.reported.borrow_mut().insert(milestone) {
+            server.report_milestone(self.source, MilestoneReport { milestone, elapsed });
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use core::time::Duration;
+
+/// Resolves after the duration using `setTimeout`, resolves immediately without a window.
+pub async fn sleep(duration: Duration) {
+    use js_sys::Promise;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::window;
+
+    let window = match window() {
+        Some(window) => window,
+        None => return,
+    };
+    let timeout = Promise::new(&mut |resolve, _| {
+        let _: Option<i32> = window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                &resolve,
+                duration.as_millis().min(i32::MAX as u128) as i32,
+            )
+            .ok();
+    });
+    let _: Option<JsValue> = JsFuture::from(timeout).await.ok();
+}
@@ -0,0 +1,99 @@
+use wasm_bindgen::closure::Closure;
+use web_sys::Event;
+
+/// Page visibility, hidden pages are e.g. background tabs or minimized windows.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PageVisibility {
+    Visible,
+    Hidden,
+}
+
+impl PageVisibility {
+    pub fn current() -> Self {
+        use web_sys::window;
+
+        match window().and_then(|window| window.document()) {
+            Some(document) if document.hidden() => Self::Hidden,
+            _ => Self::Visible,
+        }
+    }
+
+    pub fn is_hidden(self) -> bool {
+        self == Self::Hidden
+    }
+}
+
+/// Media adjustments applied while the page is hidden, audio is never affected.
+///
+/// Everything is disabled by default.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BackgroundPolicy {
+    /// Stops rendering the received video tracks, used by receivers.
+    pub pause_remote_video: bool,
+    /// Limits the outgoing video encodings, used by senders.
+    pub throttle_outgoing_video: Option<VideoThrottle>,
+}
+
+/// Outgoing video limits of a hidden page.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct VideoThrottle {
+    pub max_framerate: u32,
+    /// Maximum bitrate of every encoding in bits per second.
+    pub max_bitrate: u32,
+}
+
+impl Default for VideoThrottle {
+    fn default() -> Self {
+        Self {
+            max_framerate: 5,
+            max_bitrate: 150_000,
+        }
+    }
+}
+
+/// Document `visibilitychange` event listener, removed on drop.
+#[derive(Debug)]
+pub(crate) struct VisibilityChangeListener {
+    js_visibility_change_handler: Closure<dyn FnMut(Event)>,
+}
+
+impl VisibilityChangeListener {
+    pub(crate) fn new<F>(handler: F) -> Option<Self>
+    where
+        F: 'static + Fn(PageVisibility),
+    {
+        use crate::closure_1;
+        use wasm_bindgen::JsCast;
+        use web_sys::window;
+
+        let document = window()?.document()?;
+        let js_visibility_change_handler =
+            closure_1(move |_: Event| handler(PageVisibility::current()));
+        document
+            .add_event_listener_with_callback(
+                "visibilitychange",
+                js_visibility_change_handler.as_ref().unchecked_ref(),
+            )
+            .ok()?;
+
+        Some(Self {
+            js_visibility_change_handler,
+        })
+    }
+}
+
+impl Drop for VisibilityChangeListener {
+    fn drop(&mut self) {
+        use wasm_bindgen::JsCast;
+        use web_sys::window;
+
+        if let Some(document) = window().and_then(|window| window.document()) {
+            let _: Option<()> = document
+                .remove_event_listener_with_callback(
+                    "visibilitychange",
+                    self.js_visibility_change_handler.as_ref().unchecked_ref(),
+                )
+                .ok();
+        }
+    }
+}
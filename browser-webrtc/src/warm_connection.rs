@@ -0,0 +1,99 @@
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use web_sys::{RtcConfiguration, RtcDataChannel, RtcIceGatheringState};
+
+use crate::peer_connection::PeerConnectionCore;
+
+/// Sender peer connection created before the channel is opened.
+///
+/// Warming it up prepares the offer and starts the candidates gathering,
+/// so that the offer is sent without waiting for them once the channel is opened.
+#[derive(Debug)]
+pub struct WarmConnection {
+    /// Taken when the connection is passed to the sender.
+    parts: Option<(PeerConnectionCore, RtcDataChannel)>,
+    is_warmed_up: bool,
+}
+
+impl WarmConnection {
+    pub fn new(rtc_configuration: Option<RtcConfiguration>) -> Result<Self, WarmConnectionError> {
+        use crate::CONTROL_DATA_CHANNEL_LABEL;
+
+        let core = PeerConnectionCore::new(rtc_configuration)
+            .map_err(WarmConnectionError::NewRtcPeerConnectionError)?;
+        // The control channel is created first, so that the offer has the data section.
+        let js_control_channel = core
+            .js_connection()
+            .create_data_channel(CONTROL_DATA_CHANNEL_LABEL);
+
+        Ok(Self {
+            parts: Some((core, js_control_channel)),
+            is_warmed_up: false,
+        })
+    }
+
+    /// Creates the connection and warms it up.
+    pub async fn warmed_up(
+        rtc_configuration: Option<RtcConfiguration>,
+    ) -> Result<Self, WarmConnectionError> {
+        let mut connection = Self::new(rtc_configuration)?;
+        connection.warm_up().await?;
+        Ok(connection)
+    }
+
+    /// Sets the local offer in advance, which starts the candidates gathering.
+    pub async fn warm_up(&mut self) -> Result<(), WarmConnectionError> {
+        use webrtc_core::{RtcEngine, SdpType};
+
+        if self.is_warmed_up {
+            return Ok(());
+        }
+        let engine = self.core().engine();
+        let offer = engine
+            .create_offer(false)
+            .await
+            .map_err(WarmConnectionError::CreateOfferError)?;
+        engine
+            .set_local_description(SdpType::Offer, &offer)
+            .await
+            .map_err(WarmConnectionError::SetLocalDescriptionError)?;
+        self.is_warmed_up = true;
+        Ok(())
+    }
+
+    pub fn is_warmed_up(&self) -> bool {
+        self.is_warmed_up
+    }
+
+    pub fn ice_gathering_state(&self) -> RtcIceGatheringState {
+        self.core().ice_gathering_state()
+    }
+
+    fn core(&self) -> &PeerConnectionCore {
+        &self.parts.as_ref().unwrap().0
+    }
+
+    pub(crate) fn into_parts(mut self) -> (PeerConnectionCore, RtcDataChannel, bool) {
+        let (core, js_control_channel) = self.parts.take().unwrap();
+        (core, js_control_channel, self.is_warmed_up)
+    }
+}
+
+impl Drop for WarmConnection {
+    fn drop(&mut self) {
+        // Connections that are never used are closed.
+        if let Some((core, _)) = self.parts.take() {
+            core.close();
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WarmConnectionError {
+    #[error("new RtcPeerConnection error: {0:?}")]
+    NewRtcPeerConnectionError(JsValue),
+    #[error("create offer error: {0:?}")]
+    CreateOfferError(JsValue),
+    #[error("set local description error: {0:?}")]
+    SetLocalDescriptionError(JsValue),
+}
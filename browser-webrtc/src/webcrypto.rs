@@ -0,0 +1,84 @@
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array};
+use signaling_protocol::PublicKey;
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::SubtleCrypto;
+
+/// Returns the colon-separated SHA-256 fingerprint of the public key.
+pub async fn key_fingerprint(public_key: &PublicKey) -> Result<String, WebCryptoError> {
+    let digest =
+        bytes(&call(subtle()?.digest_with_str_and_u8_array("SHA-256", &public_key.0)).await?);
+    Ok(digest
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+pub(crate) fn subtle() -> Result<SubtleCrypto, WebCryptoError> {
+    Ok(web_sys::window()
+        .ok_or(WebCryptoError::CryptoIsNotAvailable)?
+        .crypto()
+        .map_err(|_| WebCryptoError::CryptoIsNotAvailable)?
+        .subtle())
+}
+
+pub(crate) async fn call(promise: Result<Promise, JsValue>) -> Result<JsValue, WebCryptoError> {
+    JsFuture::from(promise.map_err(WebCryptoError::JsError)?)
+        .await
+        .map_err(WebCryptoError::JsError)
+}
+
+pub(crate) fn fill_random(data: &mut [u8]) -> Result<(), WebCryptoError> {
+    let _: Object = web_sys::window()
+        .ok_or(WebCryptoError::CryptoIsNotAvailable)?
+        .crypto()
+        .map_err(|_| WebCryptoError::CryptoIsNotAvailable)?
+        .get_random_values_with_u8_array(data)
+        .map_err(WebCryptoError::JsError)?;
+    Ok(())
+}
+
+pub(crate) fn object(entries: &[(&str, JsValue)]) -> Object {
+    let object = Object::new();
+    for (name, value) in entries {
+        let _: bool = Reflect::set(&object, &JsValue::from_str(name), value).unwrap_or(false);
+    }
+    object
+}
+
+pub(crate) fn usages(usages: &[&str]) -> JsValue {
+    usages
+        .iter()
+        .map(|usage| JsValue::from_str(usage))
+        .collect::<Array>()
+        .into()
+}
+
+pub(crate) fn bytes(array_buffer: &JsValue) -> Vec<u8> {
+    Uint8Array::new(array_buffer).to_vec()
+}
+
+pub(crate) fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes the hex string, the trailing odd digit makes the whole string invalid.
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum WebCryptoError {
+    #[error("WebCrypto is not available")]
+    CryptoIsNotAvailable,
+    #[error("WebCrypto error: {0:?}")]
+    JsError(JsValue),
+}
@@ -1,12 +1,12 @@
-use signaling_protocol::{ClientMessage, ServerMessage};
+use signaling_protocol::{ClientMessage, FrameError, ServerMessage, WireCodec};
 use thiserror::Error;
 use wasm_bindgen::JsValue;
 use web_sys::{MessageEvent, WebSocket};
 
 pub fn parse_websocket_server_message(
     ev: MessageEvent,
+    codec: WireCodec,
 ) -> Result<ServerMessage, WebSocketServerMessageParseError> {
-    use bincode::deserialize;
     use js_sys::{ArrayBuffer, Uint8Array};
     use wasm_bindgen::JsCast;
 
@@ -15,28 +15,34 @@ pub fn parse_websocket_server_message(
         .dyn_into()
         .map_err(WebSocketServerMessageParseError::NonArrayData)?;
     let data = Uint8Array::new(&array_buffer).to_vec();
-    Ok(deserialize(&data)?)
+    Ok(codec.decode_frame(&data, u32::MAX)?)
 }
 
 pub fn send_websocket_client_message(
     web_socket: &WebSocket,
     msg: ClientMessage,
+    codec: WireCodec,
 ) -> Result<(), WebSocketClientMessageSendError> {
-    use bincode::serialize;
-
-    let request: Vec<u8> = serialize(&msg)?;
+    let request = serialize_websocket_client_message(&msg, codec)?;
     web_socket
         .send_with_u8_array(&request)
         .map_err(WebSocketClientMessageSendError::WebSocketSendError)?;
     Ok(())
 }
 
+pub fn serialize_websocket_client_message(
+    msg: &ClientMessage,
+    codec: WireCodec,
+) -> Result<Vec<u8>, WebSocketClientMessageSendError> {
+    Ok(codec.encode_frame(msg)?)
+}
+
 #[derive(Error, Debug)]
 pub enum WebSocketServerMessageParseError {
     #[error("non-array websocket data received: {0:?}")]
     NonArrayData(JsValue),
     #[error("websocket data deserialization error: {0}")]
-    DeserializationFailed(#[from] bincode::Error),
+    DeserializationFailed(#[from] FrameError),
 }
 
 #[derive(Error, Debug)]
@@ -44,5 +50,7 @@ pub enum WebSocketClientMessageSendError {
     #[error("WebSocket send error: {0:?}")]
     WebSocketSendError(JsValue),
     #[error("ClientMessageData serialization error: {0}")]
-    SerializationFailed(#[from] bincode::Error),
+    SerializationFailed(#[from] FrameError),
+    #[error("outgoing message queue is full, {0} messages are waiting for the WebSocket")]
+    QueueIsFull(usize),
 }
@@ -1,7 +1,7 @@
 use crate::ServersListView;
 use sycamore::prelude::*;
 
-pub fn build_app_view() -> Template<DomNode> {
+pub fn build_app_view(cx: Scope<'_>) -> View<DomNode> {
     let servers_view = ServersListView::new();
-    servers_view.view()
+    servers_view.view(cx)
 }
@@ -0,0 +1,28 @@
+use async_std::sync::Arc;
+use browser_webrtc::{AudioVisualizer, AudioVisualizerStyle};
+use sycamore::prelude::*;
+use web_sys::MediaStream;
+
+/// Draws the audio of an audio-only stream for the lifetime of the scope,
+/// so that it does not look like a blank video.
+pub fn audio_visualizer_view(cx: Scope<'_>, media_stream: Option<MediaStream>) -> View<DomNode> {
+    let media_stream = match media_stream {
+        Some(media_stream)
+            if media_stream.get_video_tracks().length() == 0
+                && media_stream.get_audio_tracks().length() > 0 =>
+        {
+            media_stream
+        }
+        _ => return View::empty(),
+    };
+    match AudioVisualizer::new(media_stream, AudioVisualizerStyle::Spectrum) {
+        Ok(visualizer) => {
+            let visualizer: &Arc<AudioVisualizer> = create_ref(cx, visualizer);
+            View::new_node(DomNode::from_web_sys(visualizer.canvas().clone().into()))
+        }
+        Err(err) => {
+            log::warn!("{}", err);
+            View::empty()
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use sycamore::prelude::*;
+
+use crate::Observable;
+
+/// Binds the view-model observable to a sycamore signal for the lifetime of the scope.
+pub fn bind_signal<'a, T: Clone + 'static>(
+    cx: Scope<'a>,
+    observable: &'a Observable<T>,
+) -> &'a Signal<T> {
+    let signal = create_rc_signal(observable.get());
+    let subscription = observable.subscribe({
+        let signal = signal.clone();
+        move |value: &T| signal.set(value.clone())
+    });
+    on_cleanup(cx, move || observable.unsubscribe(subscription));
+    create_ref(cx, signal)
+}
@@ -0,0 +1,264 @@
+use core::cell::RefCell;
+use core::time::Duration;
+use std::rc::Rc;
+
+use browser_webrtc::signaling_protocol::ChannelId;
+use browser_webrtc::IndexedDbStore;
+use wasm_bindgen::JsValue;
+
+use crate::Observable;
+
+const CALL_HISTORY_DATABASE: &str = "client.call-history";
+const CALL_HISTORY_STORE: &str = "calls";
+
+thread_local! {
+    /// Call history shared by all senders and receivers of the page.
+    static CALL_HISTORY: Rc<CallHistory> = CallHistory::new();
+}
+
+pub fn call_history() -> Rc<CallHistory> {
+    CALL_HISTORY.with(Rc::clone)
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CallDirection {
+    /// The channel was opened by this client.
+    Outgoing,
+    /// The channel was joined by this client.
+    Incoming,
+}
+
+/// Finished session as stored in IndexedDB.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallRecord {
+    /// Fingerprint of the verified peer identity key.
+    pub peer: Option<String>,
+    pub channel_id: ChannelId,
+    pub direction: CallDirection,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: f64,
+    pub duration: Duration,
+    /// Latest connection quality score of the session.
+    pub quality_score: Option<u8>,
+    pub end_reason: String,
+}
+
+/// Calls loaded from IndexedDB followed by the calls recorded since, oldest first.
+///
+/// Calls are kept in memory only if IndexedDB is not available.
+#[derive(Debug)]
+pub struct CallHistory {
+    store: RefCell<Option<IndexedDbStore>>,
+    /// Calls recorded before the store is opened.
+    pending: RefCell<Vec<CallRecord>>,
+    calls: Observable<Vec<CallRecord>>,
+}
+
+/// Session being tracked, it becomes a `CallRecord` when it ends.
+#[derive(Debug)]
+pub struct CallTracker {
+    channel_id: RefCell<ChannelId>,
+    direction: CallDirection,
+    /// Peer verified by the signed descriptions, usually before the call is started.
+    peer: RefCell<Option<String>>,
+    call: RefCell<Option<ActiveCall>>,
+}
+
+#[derive(Debug)]
+struct ActiveCall {
+    started_at: f64,
+    quality_score: Option<u8>,
+}
+
+impl CallRecord {
+    fn to_js(&self) -> JsValue {
+        use js_sys::{Object, Reflect};
+
+        let object = Object::new();
+        let fields = [
+            (
+                "peer",
+                self.peer.as_deref().map_or(JsValue::NULL, JsValue::from),
+            ),
+            ("channelId", JsValue::from(self.channel_id.0.as_str())),
+            (
+                "direction",
+                JsValue::from(match self.direction {
+                    CallDirection::Outgoing => "outgoing",
+                    CallDirection::Incoming => "incoming",
+                }),
+            ),
+            ("startedAt", JsValue::from(self.started_at)),
+            (
+                "durationMs",
+                JsValue::from(self.duration.as_millis() as f64),
+            ),
+            (
+                "qualityScore",
+                self.quality_score.map_or(JsValue::NULL, JsValue::from),
+            ),
+            ("endReason", JsValue::from(self.end_reason.as_str())),
+        ];
+        for (key, value) in fields.iter() {
+            let _: bool = Reflect::set(&object, &JsValue::from(*key), value).unwrap_or(false);
+        }
+        object.into()
+    }
+
+    /// Returns `None` for the records not written by `to_js`.
+    fn from_js(value: &JsValue) -> Option<Self> {
+        use js_sys::Reflect;
+
+        let field = |key: &str| Reflect::get(value, &JsValue::from(key)).ok();
+        let direction = match field("direction")?.as_string()?.as_str() {
+            "outgoing" => CallDirection::Outgoing,
+            "incoming" => CallDirection::Incoming,
+            _ => return None,
+        };
+        Some(Self {
+            peer: field("peer")?.as_string(),
+            channel_id: ChannelId(field("channelId")?.as_string()?),
+            direction,
+            started_at: field("startedAt")?.as_f64()?,
+            duration: Duration::from_millis(field("durationMs")?.as_f64()?.max(0.0) as u64),
+            quality_score: field("qualityScore")?.as_f64().map(|score| score as u8),
+            end_reason: field("endReason")?.as_string()?,
+        })
+    }
+}
+
+impl CallHistory {
+    fn new() -> Rc<Self> {
+        use wasm_bindgen_futures::spawn_local;
+
+        let history = Rc::new(Self {
+            store: RefCell::new(None),
+            pending: RefCell::new(Vec::new()),
+            calls: Observable::new(Vec::new()),
+        });
+        spawn_local({
+            let history = Rc::clone(&history);
+            async move { history.load().await }
+        });
+        history
+    }
+
+    async fn load(&self) {
+        let store = match IndexedDbStore::open(CALL_HISTORY_DATABASE, CALL_HISTORY_STORE).await {
+            Ok(store) => store,
+            Err(err) => return log::warn!("call history is not persisted: {}", err),
+        };
+        match store.get_all().await {
+            Ok(records) => {
+                let mut loaded: Vec<_> = records.iter().filter_map(CallRecord::from_js).collect();
+                self.calls.modify(|calls| {
+                    loaded.append(calls);
+                    *calls = loaded;
+                });
+            }
+            Err(err) => log::error!("{}", err),
+        }
+        // Calls recorded from now on are added directly.
+        *self.store.borrow_mut() = Some(store.clone());
+        for record in self.pending.take() {
+            if let Err(err) = store.add(&record.to_js()).await {
+                log::error!("{}", err);
+            }
+        }
+    }
+
+    pub fn calls(&self) -> &Observable<Vec<CallRecord>> {
+        &self.calls
+    }
+
+    pub fn record(&self, record: CallRecord) {
+        use wasm_bindgen_futures::spawn_local;
+
+        self.calls.modify(|calls| calls.push(record.clone()));
+        match self.store.borrow().clone() {
+            Some(store) => spawn_local(async move {
+                if let Err(err) = store.add(&record.to_js()).await {
+                    log::error!("{}", err);
+                }
+            }),
+            None => self.pending.borrow_mut().push(record),
+        }
+    }
+
+    pub fn clear(&self) {
+        use wasm_bindgen_futures::spawn_local;
+
+        self.calls.set(Vec::new());
+        self.pending.borrow_mut().clear();
+        if let Some(store) = self.store.borrow().clone() {
+            spawn_local(async move {
+                if let Err(err) = store.clear().await {
+                    log::error!("{}", err);
+                }
+            });
+        }
+    }
+}
+
+impl CallTracker {
+    pub fn new(channel_id: ChannelId, direction: CallDirection) -> Self {
+        Self {
+            channel_id: RefCell::new(channel_id),
+            direction,
+            peer: RefCell::new(None),
+            call: RefCell::new(None),
+        }
+    }
+
+    /// Starts the call when the peer is connected, does nothing if it is already started.
+    pub fn start(&self) {
+        let mut call = self.call.borrow_mut();
+        if call.is_none() {
+            *call = Some(ActiveCall {
+                started_at: js_sys::Date::now(),
+                quality_score: None,
+            });
+        }
+    }
+
+    pub fn set_channel_id(&self, channel_id: ChannelId) {
+        *self.channel_id.borrow_mut() = channel_id;
+    }
+
+    pub fn set_peer(&self, fingerprint: String) {
+        *self.peer.borrow_mut() = Some(fingerprint);
+    }
+
+    pub fn set_quality_score(&self, score: u8) {
+        if let Some(call) = self.call.borrow_mut().as_mut() {
+            call.quality_score = Some(score);
+        }
+    }
+
+    /// Records the started call to the call history, does nothing if it is not started.
+    ///
+    /// The verified peer is forgotten, so that the next peer of the channel is verified anew.
+    pub fn finish(&self, end_reason: String) {
+        let peer = self.peer.borrow_mut().take();
+        let call = match self.call.borrow_mut().take() {
+            Some(call) => call,
+            None => return,
+        };
+        let elapsed_ms = (js_sys::Date::now() - call.started_at).max(0.0);
+        call_history().record(CallRecord {
+            peer,
+            channel_id: self.channel_id.borrow().clone(),
+            direction: self.direction,
+            started_at: call.started_at,
+            duration: Duration::from_millis(elapsed_ms as u64),
+            quality_score: call.quality_score,
+            end_reason,
+        });
+    }
+}
+
+impl Drop for CallTracker {
+    fn drop(&mut self) {
+        self.finish("Removed".to_owned());
+    }
+}
@@ -0,0 +1,133 @@
+use async_std::sync::{Arc, Weak};
+use sycamore::prelude::*;
+
+use crate::{
+    bind_signal, call_history, CallDirection, CallRecord, ReceiversListView, SendersListView,
+};
+
+/// Number of the latest calls shown in the history panel.
+const CALL_HISTORY_SHOWN_LEN: usize = 20;
+
+/// Latest calls of the page with shortcuts to open or join their channels again
+/// on this server.
+#[derive(Debug)]
+pub struct CallHistoryListView {
+    senders: Weak<SendersListView>,
+    receivers: Weak<ReceiversListView>,
+}
+
+impl CallHistoryListView {
+    pub fn new(senders: &Arc<SendersListView>, receivers: &Arc<ReceiversListView>) -> Arc<Self> {
+        log::trace!("client::CallHistoryListView::new");
+
+        Arc::new(Self {
+            senders: Arc::downgrade(senders),
+            receivers: Arc::downgrade(receivers),
+        })
+    }
+
+    /// Opens the channel of an outgoing call or joins the channel of an incoming one.
+    fn rejoin(&self, call: &CallRecord) {
+        match call.direction {
+            CallDirection::Outgoing => {
+                if let Some(senders) = self.senders.upgrade() {
+                    senders.reopen_channel(call.channel_id.clone());
+                }
+            }
+            CallDirection::Incoming => {
+                if let Some(receivers) = self.receivers.upgrade() {
+                    receivers.add_receiver(call.channel_id.clone(), None);
+                }
+            }
+        }
+    }
+
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
+        let history = create_ref(cx, call_history());
+        let calls_var = bind_signal(cx, history.calls());
+        let calls = create_memo(cx, move || {
+            calls_var
+                .get()
+                .iter()
+                .rev()
+                .take(CALL_HISTORY_SHOWN_LEN)
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+        let has_calls = create_selector(cx, || !calls.get().is_empty());
+
+        let self_arc = create_ref(cx, Arc::clone(self));
+
+        view! { cx,
+            div(class = "component") {
+                h1 {
+                    "Call history"
+                }
+                (if *has_calls.get() {
+                    View::empty()
+                } else {
+                    view! { cx,
+                        div {
+                            "No calls yet"
+                        }
+                    }
+                })
+                Indexed(
+                    iterable = calls,
+                    view = move |cx, call: CallRecord| {
+                        let description = describe_call(&call);
+                        let label = match call.direction {
+                            CallDirection::Outgoing => "Reopen",
+                            CallDirection::Incoming => "Rejoin",
+                        };
+                        view! { cx,
+                            div(class = "monospace") {
+                                button(on:click = move |_| self_arc.rejoin(&call)) {
+                                    (label)
+                                }
+                                " "
+                                (description)
+                            }
+                        }
+                    },
+                )
+                button(on:click = move |_| history.clear(), disabled = !*has_calls.get()) {
+                    "Clear history"
+                }
+            }
+        }
+    }
+}
+
+impl Drop for CallHistoryListView {
+    fn drop(&mut self) {
+        log::trace!("client::CallHistoryListView::drop");
+    }
+}
+
+fn describe_call(call: &CallRecord) -> String {
+    use js_sys::Date;
+    use wasm_bindgen::JsValue;
+
+    let started_at: String = Date::new(&JsValue::from(call.started_at))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into();
+    let direction = match call.direction {
+        CallDirection::Outgoing => "outgoing",
+        CallDirection::Incoming => "incoming",
+    };
+    let quality = match call.quality_score {
+        Some(score) => score.to_string(),
+        None => "unknown".to_owned(),
+    };
+    format!(
+        "{} {} {:?}, peer: {}, duration: {}s, quality: {}, ended: {}",
+        started_at,
+        direction,
+        call.channel_id.0,
+        call.peer.as_deref().unwrap_or("not verified"),
+        call.duration.as_secs(),
+        quality,
+        call.end_reason,
+    )
+}
@@ -0,0 +1,333 @@
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+
+use async_std::sync::{Arc, Weak};
+use browser_webrtc::{
+    AudioLevelMeter, LocalMedia, MediaDevice, MediaDeviceKind, MediaView, MediaViewAudio,
+    TrackConstraints,
+};
+use sycamore::prelude::*;
+
+const AUDIO_LEVEL_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+const FRAME_RATE_MEASURE_DURATION: Duration = Duration::from_secs(2);
+
+/// Pre-join device test, captures the selected devices into a local preview
+/// and measures the microphone level and the camera resolution and frame rate.
+#[derive(Debug)]
+pub struct DeviceTestView {
+    cameras_var: RcSignal<Vec<MediaDevice>>,
+    microphones_var: RcSignal<Vec<MediaDevice>>,
+    camera_id_var: RcSignal<String>,
+    microphone_id_var: RcSignal<String>,
+    local_media: RefCell<Option<LocalMedia>>,
+    media_view_var: RcSignal<Option<Arc<MediaView>>>,
+    audio_level_var: RcSignal<f64>,
+    video_state_var: RcSignal<String>,
+    state_var: RcSignal<String>,
+    is_passed_var: RcSignal<bool>,
+    test_generation: Cell<u32>,
+}
+
+impl DeviceTestView {
+    pub fn new() -> Arc<Self> {
+        use wasm_bindgen_futures::spawn_local;
+
+        log::trace!("client::DeviceTestView::new");
+
+        let device_test = Arc::new(Self {
+            cameras_var: create_rc_signal(Vec::new()),
+            microphones_var: create_rc_signal(Vec::new()),
+            camera_id_var: create_rc_signal(String::new()),
+            microphone_id_var: create_rc_signal(String::new()),
+            local_media: RefCell::new(None),
+            media_view_var: create_rc_signal(None),
+            audio_level_var: create_rc_signal(0.0),
+            video_state_var: create_rc_signal(String::new()),
+            state_var: create_rc_signal("not tested".to_owned()),
+            is_passed_var: create_rc_signal(false),
+            test_generation: Cell::new(0),
+        });
+
+        spawn_local({
+            let device_test = Arc::clone(&device_test);
+            async move { device_test.update_devices().await }
+        });
+
+        device_test
+    }
+
+    /// Returns `true` if the selected devices are captured and measured successfully.
+    pub fn is_passed_var(&self) -> &RcSignal<bool> {
+        &self.is_passed_var
+    }
+
+    /// Selected camera id, `None` for the browser default.
+    pub fn camera_id(&self) -> Option<String> {
+        Some(self.camera_id_var.get().as_ref().clone()).filter(|id| !id.is_empty())
+    }
+
+    /// Selected microphone id, `None` for the browser default.
+    pub fn microphone_id(&self) -> Option<String> {
+        Some(self.microphone_id_var.get().as_ref().clone()).filter(|id| !id.is_empty())
+    }
+
+    async fn update_devices(&self) {
+        match LocalMedia::devices().await {
+            Ok(devices) => {
+                let of_kind = |kind| {
+                    devices
+                        .iter()
+                        .filter(|device| device.kind == kind)
+                        .cloned()
+                        .collect()
+                };
+                self.cameras_var.set(of_kind(MediaDeviceKind::Videoinput));
+                self.microphones_var
+                    .set(of_kind(MediaDeviceKind::Audioinput));
+            }
+            Err(err) => log::warn!("{}", err),
+        }
+    }
+
+    /// Captures the selected devices and measures them, the previous test is stopped.
+    pub fn start(self: &Arc<Self>, should_use_video: bool, should_use_audio: bool) {
+        use wasm_bindgen_futures::spawn_local;
+
+        self.stop();
+        if !should_use_video && !should_use_audio {
+            self.state_var.set("no devices are selected".to_owned());
+            return;
+        }
+        self.state_var.set("testing".to_owned());
+
+        let generation = self.test_generation.get();
+        let self_arc = Arc::clone(self);
+        spawn_local(async move {
+            let video = TrackConstraints {
+                device_id: self_arc.camera_id(),
+                ..TrackConstraints::default()
+            };
+            let audio = TrackConstraints {
+                device_id: self_arc.microphone_id(),
+                ..TrackConstraints::default()
+            };
+            let media = LocalMedia::with_constraints(
+                Some(&video).filter(|_| should_use_video),
+                Some(&audio).filter(|_| should_use_audio),
+            )
+            .await;
+            match media {
+                Ok(media) if self_arc.test_generation.get() == generation => {
+                    self_arc.on_media_captured(media, generation).await
+                }
+                Ok(media) => media.stop(),
+                Err(err) => self_arc.state_var.set(format!("failed: {}", err)),
+            }
+        });
+    }
+
+    async fn on_media_captured(self: &Arc<Self>, media: LocalMedia, generation: u32) {
+        use wasm_bindgen_futures::spawn_local;
+
+        // Device labels are only available after a media permission is granted.
+        self.update_devices().await;
+
+        let media_stream = media.media_stream().clone();
+        let video_settings = media.video_settings();
+        let _: Option<_> = self.local_media.replace(Some(media));
+
+        if media_stream.get_audio_tracks().length() > 0 {
+            match AudioLevelMeter::new(&media_stream) {
+                Ok(meter) => spawn_local(Self::update_audio_level(
+                    Arc::downgrade(self),
+                    meter,
+                    generation,
+                )),
+                Err(err) => log::warn!("{}", err),
+            }
+        }
+
+        if let Some(video_settings) = video_settings {
+            let media_view = match MediaView::new(
+                media_stream,
+                MediaViewAudio::Disable,
+                Box::new(|_, ev| Box::pin(async move { log::debug!("{:?}", ev) })),
+            ) {
+                Ok(media_view) => media_view,
+                Err(err) => {
+                    self.state_var.set(format!("failed: {}", err));
+                    return;
+                }
+            };
+            self.media_view_var.set(Some(Arc::clone(&media_view)));
+            self.video_state_var.set("measuring".to_owned());
+
+            let frame_rate = media_view
+                .measure_frame_rate(FRAME_RATE_MEASURE_DURATION)
+                .await;
+            if self.test_generation.get() != generation {
+                return;
+            }
+            let (width, height) = media_view
+                .video_size()
+                .or_else(|| Some((video_settings.width?, video_settings.height?)))
+                .unwrap_or_default();
+            self.video_state_var.set(match frame_rate {
+                Some(frame_rate) => format!("{}x{}, {:.1} fps", width, height, frame_rate),
+                None => format!("{}x{}, no frames received", width, height),
+            });
+            if frame_rate.is_none() {
+                self.state_var.set("camera sends no frames".to_owned());
+                return;
+            }
+        }
+
+        self.state_var.set("devices are working".to_owned());
+        self.is_passed_var.set(true);
+    }
+
+    async fn update_audio_level(self_weak: Weak<Self>, meter: AudioLevelMeter, generation: u32) {
+        use browser_webrtc::sleep;
+
+        loop {
+            match self_weak.upgrade() {
+                Some(self_arc) if self_arc.test_generation.get() == generation => {
+                    self_arc.audio_level_var.set(meter.level())
+                }
+                _ => return,
+            }
+            sleep(AUDIO_LEVEL_UPDATE_INTERVAL).await;
+        }
+    }
+
+    /// Stops the tested devices, e.g. before they are captured by a sender.
+    pub fn stop(&self) {
+        self.test_generation
+            .set(self.test_generation.get().wrapping_add(1));
+        if let Some(media) = self.local_media.take() {
+            media.stop();
+        }
+        self.media_view_var.set(None);
+        self.audio_level_var.set(0.0);
+        self.video_state_var.set(String::new());
+        self.is_passed_var.set(false);
+    }
+
+    pub fn view<'a>(
+        self: &Arc<Self>,
+        cx: Scope<'a>,
+        should_use_video_var: &'a Signal<bool>,
+        should_use_audio_var: &'a Signal<bool>,
+    ) -> View<DomNode> {
+        let on_test_click = {
+            let self_arc = Arc::clone(self);
+            move |_| self_arc.start(*should_use_video_var.get(), *should_use_audio_var.get())
+        };
+        let on_stop_click = {
+            let self_arc = Arc::clone(self);
+            move |_| {
+                self_arc.stop();
+                self_arc.state_var.set("not tested".to_owned());
+            }
+        };
+
+        let camera_id_var: &'a Signal<String> = create_ref(cx, self.camera_id_var.clone());
+        let microphone_id_var: &'a Signal<String> = create_ref(cx, self.microphone_id_var.clone());
+        let audio_level_var = create_ref(cx, self.audio_level_var.clone());
+        let video_state_var = create_ref(cx, self.video_state_var.clone());
+        let state_var = create_ref(cx, self.state_var.clone());
+
+        let cameras_var = self.cameras_var.clone();
+        let cameras = create_memo(cx, move || cameras_var.get().as_ref().clone());
+        let microphones_var = self.microphones_var.clone();
+        let microphones = create_memo(cx, move || microphones_var.get().as_ref().clone());
+
+        let media_view_var = self.media_view_var.clone();
+        let media_view = create_memo(cx, move || media_view_var.get().as_ref().clone());
+
+        view! { cx,
+            div {
+                div {
+                    label {
+                        "camera: "
+                        select(bind:value = camera_id_var) {
+                            option(value = "") { "default" }
+                            Keyed(
+                                iterable = cameras,
+                                view = |cx, device| Self::device_option_view(cx, device),
+                                key = |device| device.device_id.clone(),
+                            )
+                        }
+                    }
+                    label {
+                        "microphone: "
+                        select(bind:value = microphone_id_var) {
+                            option(value = "") { "default" }
+                            Keyed(
+                                iterable = microphones,
+                                view = |cx, device| Self::device_option_view(cx, device),
+                                key = |device| device.device_id.clone(),
+                            )
+                        }
+                    }
+                    button(on:click = on_test_click) {
+                        "Test devices"
+                    }
+                    button(on:click = on_stop_click) {
+                        "Stop test"
+                    }
+                }
+                (match media_view.get().as_ref() {
+                    Some(media_view) => {
+                        let video =
+                            View::new_node(DomNode::from_web_sys(media_view.view().clone().into()));
+                        view! { cx,
+                            div(class = "video") {
+                                (video)
+                            }
+                        }
+                    }
+                    None => View::empty(),
+                })
+                div(class = "monospace") {
+                    "microphone level: "
+                    progress(max = "1", value = audio_level_var.get().to_string())
+                }
+                div(class = "monospace") {
+                    "camera: "
+                    (video_state_var.get())
+                }
+                div(class = "monospace") {
+                    "device test: "
+                    (state_var.get())
+                }
+            }
+        }
+    }
+
+    fn device_option_view(cx: Scope<'_>, device: MediaDevice) -> View<DomNode> {
+        let label = if device.label.is_empty() {
+            format!(
+                "device {}",
+                device.device_id.get(..8).unwrap_or(&device.device_id)
+            )
+        } else {
+            device.label
+        };
+        view! { cx,
+            option(value = device.device_id) {
+                (label)
+            }
+        }
+    }
+}
+
+impl Drop for DeviceTestView {
+    fn drop(&mut self) {
+        log::trace!("client::DeviceTestView::drop");
+
+        if let Some(media) = self.local_media.take() {
+            media.stop();
+        }
+    }
+}
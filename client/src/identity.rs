@@ -0,0 +1,7 @@
+use browser_webrtc::{IdentityError, IdentityKeyPair, Persistence};
+
+/// Loads the long-lived peer identity shared by all senders and receivers of the page.
+pub async fn load_identity() -> Result<IdentityKeyPair, IdentityError> {
+    let persistence = Persistence::local()?;
+    IdentityKeyPair::load_or_generate(&persistence).await
+}
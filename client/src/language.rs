@@ -0,0 +1,20 @@
+use browser_webrtc::signaling_protocol::Language;
+
+/// Language of the browser if the error catalog has it, English otherwise.
+pub fn ui_language() -> Language {
+    browser_language_tag()
+        .and_then(|tag| Language::from_tag(&tag))
+        .unwrap_or_default()
+}
+
+/// BCP 47 language tag of the browser, e.g. `en-US`.
+pub fn browser_language_tag() -> Option<String> {
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+    use web_sys::window;
+
+    window()
+        .and_then(|window| Reflect::get(&window, &JsValue::from_str("navigator")).ok())
+        .and_then(|navigator| Reflect::get(&navigator, &JsValue::from_str("language")).ok())
+        .and_then(|tag| tag.as_string())
+}
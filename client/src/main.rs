@@ -15,33 +15,65 @@
 static ALLOC: wee_alloc::WeeAlloc<'_> = wee_alloc::WeeAlloc::INIT;
 
 mod app;
+mod audio_visualizer;
+mod binding;
+mod call_history;
+mod call_history_list;
+mod device_test;
+mod identity;
+mod language;
+mod notifier;
+mod observable;
+mod peer_state;
+mod quality;
 mod receiver;
 mod receiver_builder;
+mod receiver_model;
 mod receivers_list;
+mod request_policy;
 mod sender;
 mod sender_builder;
+mod sender_model;
 mod senders_list;
 mod server;
 mod server_address;
 mod server_builder;
+mod server_model;
 mod servers_list;
-mod signal_ext;
+mod usage;
 
 use app::build_app_view;
+use audio_visualizer::audio_visualizer_view;
+use binding::bind_signal;
+use call_history::{call_history, CallDirection, CallRecord, CallTracker};
+use call_history_list::CallHistoryListView;
+use device_test::DeviceTestView;
+use identity::load_identity;
+use language::{browser_language_tag, ui_language};
+use notifier::{notify, set_notifications_enabled};
+use observable::Observable;
+use peer_state::describe_peer_state;
+use quality::{describe_quality, describe_quality_details};
 use receiver::ReceiverView;
 use receiver_builder::ReceiverBuilderView;
+use receiver_model::ReceiverModel;
 use receivers_list::ReceiversListView;
-use sender::SenderView;
-use sender_builder::SenderBuilderView;
+use request_policy::RequestPolicyView;
+use sender::{SenderMedia, SenderView};
+use sender_builder::{SenderBuilderView, SenderOptions};
+use sender_model::SenderModel;
 use senders_list::SendersListView;
 use server::ServerView;
 use server_address::default_server_address;
 use server_builder::ServerBuilderView;
+use server_model::{ChannelListState, ServerModel};
 use servers_list::ServersListView;
-use signal_ext::{SignalVecPush, SignalVecRemoveByPtrEq};
+use usage::{describe_usage, describe_usage_details};
 
 fn main() {
     console_error_panic_hook::set_once();
     console_log::init_with_level(log::Level::Debug).unwrap();
-    sycamore::render(|| build_app_view());
+    #[cfg(feature = "live-objects")]
+    browser_webrtc::log_live_objects_on_unload();
+    sycamore::render(build_app_view);
 }
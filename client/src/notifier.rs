@@ -0,0 +1,30 @@
+use std::rc::Rc;
+
+use browser_webrtc::{Notifier, NotifierEvent};
+
+thread_local! {
+    /// Notifier shared by all senders and receivers of the page.
+    static NOTIFIER: Rc<Notifier> = Rc::new(Notifier::new());
+}
+
+/// Enables notifications, returns `false` if they are not permitted.
+pub async fn set_notifications_enabled(is_enabled: bool) -> bool {
+    use browser_webrtc::NotificationPermission;
+
+    let notifier = NOTIFIER.with(Rc::clone);
+    match notifier.set_enabled(is_enabled).await {
+        Ok(permission) => !is_enabled || permission == Some(NotificationPermission::Granted),
+        Err(err) => {
+            log::error!("{}", err);
+            false
+        }
+    }
+}
+
+pub fn notify(ev: NotifierEvent) {
+    NOTIFIER.with(|notifier| {
+        if let Err(err) = notifier.notify(&ev) {
+            log::warn!("{}", err);
+        }
+    });
+}
@@ -0,0 +1,92 @@
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use std::rc::Rc;
+
+/// Value of a view-model that notifies its subscribers on every change.
+///
+/// It does not depend on a UI framework, views bind it to their own reactive primitives,
+/// e.g. with `bind_signal` for sycamore.
+pub struct Observable<T> {
+    value: RefCell<T>,
+    next_subscription_id: Cell<u32>,
+    subscribers: RefCell<Vec<(SubscriptionId, Rc<dyn Fn(&T)>)>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SubscriptionId(u32);
+
+impl<T> Observable<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: RefCell::new(value),
+            next_subscription_id: Cell::new(0),
+            subscribers: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.value.borrow().clone()
+    }
+
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.value.borrow())
+    }
+
+    pub fn set(&self, value: T) {
+        *self.value.borrow_mut() = value;
+        self.notify();
+    }
+
+    pub fn modify(&self, f: impl FnOnce(&mut T)) {
+        f(&mut self.value.borrow_mut());
+        self.notify();
+    }
+
+    /// Calls the subscriber on every change, the subscriber must not change this observable.
+    pub fn subscribe(&self, subscriber: impl Fn(&T) + 'static) -> SubscriptionId {
+        let id = SubscriptionId(self.next_subscription_id.get());
+        self.next_subscription_id.set(id.0.wrapping_add(1));
+        self.subscribers
+            .borrow_mut()
+            .push((id, Rc::new(subscriber)));
+        id
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        self.subscribers
+            .borrow_mut()
+            .retain(|(subscription_id, _)| *subscription_id != id);
+    }
+
+    fn notify(&self) {
+        // Subscribers are collected first, so they may subscribe or unsubscribe when notified.
+        let subscribers: Vec<_> = self
+            .subscribers
+            .borrow()
+            .iter()
+            .map(|(_, subscriber)| Rc::clone(subscriber))
+            .collect();
+        let value = self.value.borrow();
+        for subscriber in subscribers {
+            subscriber(&value);
+        }
+    }
+}
+
+impl<T: Default> Default for Observable<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Observable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observable")
+            .field("value", &self.value)
+            .field("subscribers", &self.subscribers.borrow().len())
+            .finish()
+    }
+}
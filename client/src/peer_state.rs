@@ -0,0 +1,20 @@
+use browser_webrtc::PeerState;
+
+/// Describes the remote peer state indicators.
+pub fn describe_peer_state(state: PeerState) -> String {
+    let indicators: Vec<_> = [
+        (state.is_typing, "typing"),
+        (state.is_away, "away"),
+        (state.is_muted, "muted"),
+        (state.is_screen_sharing, "sharing screen"),
+    ]
+    .iter()
+    .filter(|(is_set, _)| *is_set)
+    .map(|(_, name)| *name)
+    .collect();
+    if indicators.is_empty() {
+        "active".to_owned()
+    } else {
+        indicators.join(", ")
+    }
+}
@@ -0,0 +1,46 @@
+use browser_webrtc::{ConnectionQuality, MAX_QUALITY_SCORE};
+
+/// Renders the connection quality score as signal bars.
+pub fn describe_quality(quality: Option<ConnectionQuality>) -> String {
+    let quality = match quality {
+        Some(quality) => quality,
+        None => return "unknown".to_owned(),
+    };
+    let rating = match quality.score {
+        5 => "excellent",
+        4 => "good",
+        3 => "fair",
+        2 => "poor",
+        1 => "bad",
+        _ => "unusable",
+    };
+    format!(
+        "{}{} {}",
+        "▮".repeat(quality.score as usize),
+        "▯".repeat((MAX_QUALITY_SCORE - quality.score) as usize),
+        rating
+    )
+}
+
+/// Describes the network metrics behind the score,
+/// so that network issues can be told apart from application ones.
+pub fn describe_quality_details(quality: Option<ConnectionQuality>) -> String {
+    let quality = match quality {
+        Some(quality) => quality,
+        None => return String::new(),
+    };
+    let mut details = Vec::new();
+    if let Some(packet_loss) = quality.packet_loss {
+        details.push(format!("loss: {:.1}%", packet_loss * 100.0));
+    }
+    if let Some(round_trip_time) = quality.round_trip_time {
+        details.push(format!("rtt: {}ms", round_trip_time.as_millis()));
+    }
+    if let Some(jitter) = quality.jitter {
+        details.push(format!("jitter: {}ms", jitter.as_millis()));
+    }
+    if quality.freeze_count > 0 {
+        details.push(format!("freezes: {}", quality.freeze_count));
+    }
+    details.join(", ")
+}
@@ -1,31 +1,48 @@
-use core::cell::RefCell;
+use core::time::Duration;
 
 use async_std::sync::{Arc, Weak};
+use browser_webrtc::signaling_protocol::{QualityRequest, Topic, JPEG_FRAME_TOPIC};
 use browser_webrtc::{
-    DataReceiver, DataReceiverBuilder, DataReceiverEvent, MediaReceiver, MediaReceiverBuilder,
-    MediaReceiverEvent, MediaView, MediaViewAudio, Receiver,
+    CaptionCue, DataReceiver, DataReceiverBuilder, DataReceiverEvent, MediaReceiver,
+    MediaReceiverBuilder, MediaReceiverEvent, MediaView, MediaViewAudio, MediaViewEvent, PeerState,
+    Receiver,
 };
 use sycamore::prelude::*;
+use web_sys::MediaStream;
+
+use crate::audio_visualizer_view;
+
+const HUD_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub struct ReceiverView {
     receiver: Arc<Receiver>,
-    media_receivers_var: Signal<RefCell<Vec<Arc<MediaReceiver>>>>,
-    media_views_var: Signal<RefCell<Vec<Arc<MediaView>>>>,
-    data_receivers_var: Signal<RefCell<Vec<Arc<DataReceiver>>>>,
-    webrtc_binary_data_var: Signal<String>,
-    socket_binary_data_var: Signal<String>,
+    media_receivers_var: RcSignal<Vec<Arc<MediaReceiver>>>,
+    media_views_var: RcSignal<Vec<Arc<MediaView>>>,
+    data_receivers_var: RcSignal<Vec<Arc<DataReceiver>>>,
+    webrtc_binary_data_var: RcSignal<String>,
+    socket_binary_data_var: RcSignal<String>,
+    /// Object URL of the latest JPEG frame relayed by a headless sender, see `iot-sender`.
+    socket_frame_url_var: RcSignal<Option<String>>,
+    playback_blocked_var: RcSignal<bool>,
+    is_hud_enabled_var: RcSignal<bool>,
+    is_paused_var: RcSignal<bool>,
+    /// Set once the first video frame is rendered, not when ICE merely connects.
+    is_media_visible_var: RcSignal<bool>,
+    /// ICE timeline dump, filled on request.
+    ice_timeline_var: RcSignal<String>,
 }
 
 impl ReceiverView {
     pub fn new(receiver: Arc<Receiver>) -> Arc<Self> {
         log::trace!("client::ReceiverView::new");
 
-        let media_receivers_var = Signal::new(RefCell::new(Vec::new()));
-        let media_views_var = Signal::new(RefCell::new(Vec::new()));
-        let data_receivers_var = Signal::new(RefCell::new(Vec::new()));
-        let webrtc_binary_data_var = Signal::new(String::new());
-        let socket_binary_data_var = Signal::new(String::new());
+        let media_receivers_var = create_rc_signal(Vec::new());
+        let media_views_var = create_rc_signal(Vec::new());
+        let data_receivers_var = create_rc_signal(Vec::new());
+        let webrtc_binary_data_var = create_rc_signal(String::new());
+        let socket_binary_data_var = create_rc_signal(String::new());
+        let playback_blocked_var = create_rc_signal(false);
 
         Arc::new(Self {
             receiver,
@@ -34,54 +51,136 @@ impl ReceiverView {
             data_receivers_var,
             webrtc_binary_data_var,
             socket_binary_data_var,
+            socket_frame_url_var: create_rc_signal(None),
+            playback_blocked_var,
+            is_hud_enabled_var: create_rc_signal(false),
+            is_paused_var: create_rc_signal(false),
+            is_media_visible_var: create_rc_signal(false),
+            ice_timeline_var: create_rc_signal(String::new()),
         })
     }
 
     pub async fn on_media_receiver(self: &Arc<Self>, builder: MediaReceiverBuilder) {
         log::trace!("client::Receiver::add_media_receiver");
 
-        use crate::SignalVecPush;
         use log::error;
+        use wasm_bindgen_futures::spawn_local;
 
         let self_weak = Arc::downgrade(&self);
 
         let media_receiver = builder.build_with_handler(Box::new(move |_, ev| {
             let self_weak = Weak::clone(&self_weak);
             Box::pin(async move {
-                let self_arc = self_weak.upgrade().unwrap();
-                self_arc.on_media_receiver_event(ev).await
+                if let Some(self_arc) = self_weak.upgrade() {
+                    self_arc.on_media_receiver_event(ev).await
+                }
             })
         }));
 
+        let self_weak = Arc::downgrade(&self);
         let media_view = MediaView::new(
             media_receiver.media_stream().clone(),
             MediaViewAudio::Enable,
+            Box::new(move |_, ev| {
+                let self_weak = Weak::clone(&self_weak);
+                Box::pin(async move {
+                    if let Some(self_arc) = self_weak.upgrade() {
+                        self_arc.on_media_view_event(ev).await
+                    }
+                })
+            }),
         );
 
-        self.media_receivers_var.push(media_receiver);
+        if *self.is_paused_var.get() {
+            media_receiver.pause();
+        }
+        let media_receiver_weak = Arc::downgrade(&media_receiver);
+        self.media_receivers_var.modify().push(media_receiver);
 
         match media_view {
-            Ok(media_view) => self.media_views_var.push(media_view),
+            Ok(media_view) => {
+                media_view.set_hud_enabled(*self.is_hud_enabled_var.get());
+                if *self.is_paused_var.get() {
+                    if let Err(err) = media_view.pause() {
+                        error!("{}", err);
+                    }
+                }
+                spawn_local(Self::update_hud(
+                    media_receiver_weak,
+                    Arc::downgrade(&media_view),
+                ));
+                self.media_views_var.modify().push(media_view)
+            }
             Err(err) => error!("{}", err),
         }
     }
 
+    /// Removes the receiver and the view of the stream removed by the sender.
+    pub fn on_media_stream_removed(&self, media_stream: &MediaStream) {
+        self.media_receivers_var
+            .modify()
+            .retain(|media_receiver| media_receiver.media_stream() != media_stream);
+        self.media_views_var
+            .modify()
+            .retain(|media_view| media_view.video.src_object().as_ref() != Some(media_stream));
+    }
+
+    /// Shows the sender caption over all the received videos.
+    pub fn on_caption(&self, cue: &CaptionCue) {
+        use log::error;
+
+        for media_view in self.media_views_var.get().iter() {
+            if let Err(err) = media_view.show_caption(cue) {
+                error!("{}", err);
+            }
+        }
+    }
+
+    /// Periodically draws the received video stats on the media view overlay
+    /// until the media receiver or the view is dropped.
+    async fn update_hud(media_receiver: Weak<MediaReceiver>, media_view: Weak<MediaView>) {
+        use browser_webrtc::sleep;
+        use log::warn;
+
+        let mut prev_stats = None;
+        loop {
+            sleep(HUD_UPDATE_INTERVAL).await;
+            let (media_receiver, media_view) =
+                match (media_receiver.upgrade(), media_view.upgrade()) {
+                    (Some(media_receiver), Some(media_view)) => (media_receiver, media_view),
+                    _ => return,
+                };
+            // Stats are collected while the overlay is hidden to keep the bitrate up to date.
+            match media_receiver.video_stats(prev_stats.as_ref()).await {
+                Ok(Some(stats)) => {
+                    if media_view.is_hud_enabled() {
+                        if let Err(err) = media_view.update_hud(&stats) {
+                            warn!("{}", err);
+                        }
+                    }
+                    prev_stats = Some(stats);
+                }
+                Ok(None) => {}
+                Err(err) => warn!("{}", err),
+            }
+        }
+    }
+
     pub async fn on_data_receiver(self: &Arc<Self>, builder: DataReceiverBuilder) {
         log::trace!("client::Receiver::add_data_receiver");
 
-        use crate::SignalVecPush;
-
         let self_weak = Arc::downgrade(&self);
 
         let data_receiver = builder.build_with_handler(Box::new(move |_, ev| {
             let self_weak = Weak::clone(&self_weak);
             Box::pin(async move {
-                let self_arc = self_weak.upgrade().unwrap();
-                self_arc.on_data_receiver_event(ev).await
+                if let Some(self_arc) = self_weak.upgrade() {
+                    self_arc.on_data_receiver_event(ev).await
+                }
             })
         }));
 
-        self.data_receivers_var.push(data_receiver);
+        self.data_receivers_var.modify().push(data_receiver);
     }
 
     pub async fn on_socket_binary_data(self: &Arc<Self>, data: Vec<u8>) {
@@ -89,6 +188,35 @@ impl ReceiverView {
             .set(String::from_utf8_lossy(&data).to_string());
     }
 
+    /// Only the data of `JPEG_FRAME_TOPIC` is shown as video frames.
+    pub async fn on_topic_data(self: &Arc<Self>, topic: &Topic, data: Vec<u8>) {
+        if topic.0 == JPEG_FRAME_TOPIC {
+            self.on_socket_frame(&data);
+        } else {
+            self.on_socket_binary_data(data).await;
+        }
+    }
+
+    fn on_socket_frame(&self, data: &[u8]) {
+        use js_sys::{Array, Uint8Array};
+        use log::error;
+        use web_sys::{Blob, BlobPropertyBag, Url};
+
+        let options = BlobPropertyBag::new();
+        options.set_type("image/jpeg");
+        let parts = Array::of1(&Uint8Array::from(data));
+        let url = match Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+            .and_then(|frame| Url::create_object_url_with_blob(&frame))
+        {
+            Ok(url) => url,
+            Err(err) => return error!("JPEG frame error: {:?}", err),
+        };
+        if let Some(prev_url) = self.socket_frame_url_var.get_untracked().as_ref() {
+            let _: Result<(), _> = Url::revoke_object_url(prev_url);
+        }
+        self.socket_frame_url_var.set(Some(url));
+    }
+
     pub async fn on_media_receiver_event(self: &Arc<Self>, ev: MediaReceiverEvent) {
         use log::{debug, error};
         match ev {
@@ -97,64 +225,228 @@ impl ReceiverView {
         }
     }
 
+    pub async fn on_media_view_event(self: &Arc<Self>, ev: MediaViewEvent) {
+        use log::{debug, error};
+        match ev {
+            MediaViewEvent::PlaybackStarted => {}
+            MediaViewEvent::FirstFrameRendered { width, height } => {
+                debug!("first video frame rendered: {}x{}", width, height);
+                self.is_media_visible_var.set(true);
+            }
+            MediaViewEvent::FirstAudioPlayed => debug!("first audio played"),
+            MediaViewEvent::AudioBlocked | MediaViewEvent::PlaybackBlocked => {
+                self.playback_blocked_var.set(true)
+            }
+            MediaViewEvent::Error(err) => error!("{}", err),
+        }
+    }
+
+    pub fn set_peer_state(&self, state: PeerState) {
+        self.receiver.set_peer_state(state)
+    }
+
+    pub fn resume_playback(self: &Arc<Self>) {
+        self.playback_blocked_var.set(false);
+        for media_view in self.media_views_var.get().iter() {
+            media_view.resume_playback();
+        }
+    }
+
     pub async fn on_data_receiver_event(self: &Arc<Self>, ev: DataReceiverEvent) {
-        use log::error;
+        use log::{debug, error};
         match ev {
+            DataReceiverEvent::TextMessage(text) => {
+                use crate::notify;
+                use browser_webrtc::NotifierEvent;
+
+                notify(NotifierEvent::ChatMessage { text: text.clone() });
+                self.webrtc_binary_data_var.set(text);
+            }
             DataReceiverEvent::Message(data) => {
-                self.webrtc_binary_data_var
-                    .set(String::from_utf8_lossy(&data).to_string());
+                debug!("Binary data received: {} bytes", data.len())
             }
+            DataReceiverEvent::FileMessage {
+                name,
+                mime_type,
+                data,
+            } => debug!(
+                "File {} ({}) received: {} bytes",
+                name,
+                mime_type,
+                data.len()
+            ),
+            DataReceiverEvent::Closed(reason) => debug!("Data channel closed: {:?}", reason),
             DataReceiverEvent::Error(err) => error!("{}", err),
         }
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
+    fn request_quality(&self, request: QualityRequest) {
+        if let Err(err) = self.receiver.request_quality(request) {
+            log::error!("{}", err);
+        }
+    }
+
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
         let media_views_var = self.media_views_var.clone();
-        let webrtc_binary_data_var = self.webrtc_binary_data_var.clone();
-        let socket_binary_data_var = self.socket_binary_data_var.clone();
-
-        template! {
-            div() {
-                ({
-                    Template::new_fragment(
-                        media_views_var
-                            .get()
-                            .borrow()
-                            .iter()
-                            .map(|media_view| {
-                                let node_ref = NodeRef::new();
-                                let template = template! {
-                                    div(class = "video", ref = node_ref) {}
-                                };
-                                let node: DomNode = node_ref.get();
-                                let node = node.inner_element();
-                                let _: Option<_> = node.append_child(media_view.view()).ok();
-                                template
-                            })
-                            .collect(),
-                    )
-                })
+        let media_views = create_memo(cx, move || media_views_var.get().as_ref().clone());
+        let webrtc_binary_data_var = create_ref(cx, self.webrtc_binary_data_var.clone());
+        let socket_binary_data_var = create_ref(cx, self.socket_binary_data_var.clone());
+        let socket_frame_url_var = create_ref(cx, self.socket_frame_url_var.clone());
+        let playback_blocked_var = create_ref(cx, self.playback_blocked_var.clone());
+        let is_hud_enabled_var: &Signal<bool> = create_ref(cx, self.is_hud_enabled_var.clone());
+        let is_paused_var: &Signal<bool> = create_ref(cx, self.is_paused_var.clone());
+        let media_receivers_var = create_ref(cx, self.media_receivers_var.clone());
+        let is_media_visible_var = create_ref(cx, self.is_media_visible_var.clone());
+
+        create_effect(cx, move || {
+            let is_hud_enabled = *is_hud_enabled_var.get();
+            for media_view in media_views.get().iter() {
+                media_view.set_hud_enabled(is_hud_enabled);
+            }
+        });
+
+        create_effect(cx, move || {
+            let is_paused = *is_paused_var.get();
+            for media_receiver in media_receivers_var.get_untracked().iter() {
+                if is_paused {
+                    media_receiver.pause();
+                } else {
+                    media_receiver.resume();
+                }
             }
-            div() {
-                label() {
-                    div() {
-                        ("WebRtc DataChannel")
+            for media_view in media_views.get_untracked().iter() {
+                if is_paused == media_view.is_paused() {
+                    continue;
+                }
+                if is_paused {
+                    if let Err(err) = media_view.pause() {
+                        error!("{}", err);
+                    }
+                } else {
+                    media_view.resume();
+                }
+            }
+        });
+
+        let on_resume_playback_click = {
+            let self_arc = Arc::clone(self);
+            move |_| self_arc.resume_playback()
+        };
+
+        let on_lower_quality_click = {
+            let self_arc = Arc::clone(self);
+            move |_| self_arc.request_quality(QualityRequest::LowerLayer)
+        };
+        let on_higher_quality_click = {
+            let self_arc = Arc::clone(self);
+            move |_| self_arc.request_quality(QualityRequest::HigherLayer)
+        };
+        let ice_timeline_var = create_ref(cx, self.ice_timeline_var.clone());
+        let on_ice_timeline_click = {
+            let self_arc = Arc::clone(self);
+            move |_| {
+                self_arc
+                    .ice_timeline_var
+                    .set(self_arc.receiver.ice_timeline().to_string())
+            }
+        };
+
+        view! { cx,
+            (if *playback_blocked_var.get() {
+                let on_resume_playback_click = on_resume_playback_click.clone();
+                view! { cx,
+                    button(class = "overlay", on:click = on_resume_playback_click) {
+                        "Click to unmute/play"
+                    }
+                }
+            } else {
+                View::empty()
+            })
+            (if *is_media_visible_var.get() {
+                View::empty()
+            } else {
+                view! { cx,
+                    h2(class = "loading") {
+                        "connecting media..."
+                    }
+                }
+            })
+            div {
+                Keyed(
+                    iterable = media_views,
+                    view = |cx, media_view| {
+                        let video =
+                            View::new_node(DomNode::from_web_sys(media_view.view().clone().into()));
+                        let hud =
+                            View::new_node(DomNode::from_web_sys(media_view.hud().clone().into()));
+                        let audio_visualizer =
+                            audio_visualizer_view(cx, media_view.view().src_object());
+                        view! { cx,
+                            div(class = "video") {
+                                (video)
+                                (hud)
+                                (audio_visualizer)
+                            }
+                        }
+                    },
+                    key = |media_view| Arc::as_ptr(media_view) as usize,
+                )
+            }
+            (match socket_frame_url_var.get().as_ref() {
+                Some(url) => {
+                    let url = url.clone();
+                    view! { cx,
+                        div(class = "video") {
+                            img(src = url)
+                        }
+                    }
+                }
+                None => View::empty(),
+            })
+            div {
+                button(on:click = on_lower_quality_click) {
+                    "Lower quality"
+                }
+                button(on:click = on_higher_quality_click) {
+                    "Higher quality"
+                }
+                label {
+                    input(type = "checkbox", bind:checked = is_hud_enabled_var)
+                    "Stats overlay"
+                }
+                label {
+                    input(type = "checkbox", bind:checked = is_paused_var)
+                    "Pause video"
+                }
+            }
+            div {
+                label {
+                    div {
+                        "WebRtc DataChannel"
                     }
                     textarea(readonly = true) {
                         (webrtc_binary_data_var.get())
                     }
                 }
             }
-            div() {
-                label() {
-                    div() {
-                        ("WebSocket DataChannel")
+            div {
+                label {
+                    div {
+                        "WebSocket DataChannel"
                     }
                     textarea(readonly = true) {
                         (socket_binary_data_var.get())
                     }
                 }
             }
+            div {
+                button(on:click = on_ice_timeline_click) {
+                    "Dump ICE timeline"
+                }
+                textarea(class = "monospace", readonly = true) {
+                    (ice_timeline_var.get())
+                }
+            }
         }
     }
 }
@@ -162,5 +454,9 @@ impl ReceiverView {
 impl Drop for ReceiverView {
     fn drop(&mut self) {
         log::trace!("client::ReceiverView::drop");
+
+        if let Some(url) = self.socket_frame_url_var.get_untracked().as_ref() {
+            let _: Result<(), _> = web_sys::Url::revoke_object_url(url);
+        }
     }
 }
@@ -1,19 +1,28 @@
+use core::time::Duration;
+
 use async_std::sync::{Arc, Weak};
-use browser_webrtc::signaling_protocol::ChannelId;
-use browser_webrtc::{ReceiverEvent, Server};
+use browser_webrtc::signaling_protocol::{
+    ChannelId, ChannelSecret, ServerReceiverErrorMessage, Topic, JPEG_FRAME_TOPIC,
+};
+use browser_webrtc::{BackgroundPolicy, JoinError, PeerState, ReceiverEvent, Server};
 use sycamore::prelude::*;
 
-use crate::{ReceiverView, ReceiversListView};
+use crate::{
+    bind_signal, describe_quality, describe_quality_details, describe_usage,
+    describe_usage_details, load_identity, ReceiverModel, ReceiverView, ReceiversListView,
+};
+
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub struct ReceiverBuilderView {
-    receivers: Arc<ReceiversListView>,
+    receivers: Weak<ReceiversListView>,
     server: Weak<Server>,
-    receiver_var: Signal<Option<Result<Arc<ReceiverView>, anyhow::Error>>>,
-    ice_connection_state_var: Signal<String>,
-    ice_gathering_state_var: Signal<String>,
-    signaling_state_var: Signal<String>,
+    receiver_var: RcSignal<Option<Result<Arc<ReceiverView>, anyhow::Error>>>,
+    model: Arc<ReceiverModel>,
+    is_away_var: RcSignal<bool>,
     channel_id: ChannelId,
+    credential: Option<ChannelSecret>,
 }
 
 impl ReceiverBuilderView {
@@ -21,24 +30,22 @@ impl ReceiverBuilderView {
         receivers: Arc<ReceiversListView>,
         server: Arc<Server>,
         channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
     ) -> Arc<Self> {
         use wasm_bindgen_futures::spawn_local;
 
         log::trace!("client::ReceiverBuilderView::new");
 
-        let receiver_var = Signal::new(None);
-        let ice_connection_state_var = Signal::new(String::new());
-        let ice_gathering_state_var = Signal::new(String::new());
-        let signaling_state_var = Signal::new(String::new());
+        let receiver_var = create_rc_signal(None);
 
         let receiver = Arc::new(Self {
-            receivers,
+            receivers: Arc::downgrade(&receivers),
             server: Arc::downgrade(&server),
             receiver_var: receiver_var.clone(),
-            ice_connection_state_var,
-            ice_gathering_state_var,
-            signaling_state_var,
+            model: ReceiverModel::new(channel_id.clone()),
+            is_away_var: create_rc_signal(false),
             channel_id,
+            credential,
         });
 
         spawn_local({
@@ -53,6 +60,13 @@ impl ReceiverBuilderView {
         use browser_webrtc::{default_rtc_configuration, RtcConfigurationExt};
         use log::error;
 
+        // Answers are signed when the identity is available,
+        // it is loaded before joining so that the first answer is signed too.
+        let identity = load_identity()
+            .await
+            .map_err(|err| log::warn!("answers are not signed: {}", err))
+            .ok();
+
         let self_weak = Arc::downgrade(&self);
         let rtc_configuration = default_rtc_configuration().with_google_stun_server();
         let receiver = self
@@ -61,12 +75,14 @@ impl ReceiverBuilderView {
             .unwrap()
             .join_channel(
                 self.channel_id.clone(),
+                self.credential.clone(),
                 Some(rtc_configuration),
                 Box::new(move |_, ev| {
                     let self_weak = Weak::clone(&self_weak);
                     Box::pin(async move {
-                        let self_arc = self_weak.upgrade().unwrap();
-                        self_arc.on_event(ev).await
+                        if let Some(self_arc) = self_weak.upgrade() {
+                            self_arc.on_event(ev).await
+                        }
                     })
                 }),
             )
@@ -79,20 +95,50 @@ impl ReceiverBuilderView {
                 return Err(anyhow::Error::msg(err.to_string()));
             }
         };
+        if let Some(identity) = identity {
+            receiver.set_identity_key_pair(identity);
+        }
+        receiver.set_background_policy(BackgroundPolicy {
+            pause_remote_video: true,
+            ..BackgroundPolicy::default()
+        });
+        // Awaited after the identity is set, so that the offer relayed on join is answered signed.
+        if let Err(err) = receiver.joined(JOIN_TIMEOUT).await {
+            if let JoinError::Rejected(ServerReceiverErrorMessage::InvalidChannelCredentials(_)) =
+                err
+            {
+                self.ask_credential();
+            }
+            error!("{}", err);
+            return Err(anyhow::Error::msg(err.to_string()));
+        }
 
-        self.ice_connection_state_var
-            .set(format!("{:?}", receiver.ice_connection_state()));
-        self.ice_gathering_state_var
-            .set(format!("{:?}", receiver.ice_gathering_state()));
-        self.signaling_state_var
-            .set(format!("{:?}", receiver.signaling_state()));
+        self.model.on_joined(&receiver);
+        // Frames of the headless senders are relayed on their own topic.
+        if let Err(err) = receiver.subscribe_topic(Topic(JPEG_FRAME_TOPIC.to_owned())) {
+            error!("{}", err);
+        }
 
         let receiver_view = ReceiverView::new(receiver);
 
         Ok(receiver_view)
     }
 
-    fn receiver<'a>(self: &Arc<Self>) -> Option<Arc<ReceiverView>> {
+    /// Asks for the channel password and joins the channel again with it.
+    fn ask_credential(self: &Arc<Self>) {
+        let password = web_sys::window().and_then(|window| {
+            window
+                .prompt_with_message(&format!("Password of the channel {}:", self.channel_id.0))
+                .ok()
+                .flatten()
+        });
+        if let (Some(password), Some(receivers)) = (password, self.receivers.upgrade()) {
+            receivers.remove_receiver(self);
+            receivers.add_receiver(self.channel_id.clone(), Some(ChannelSecret(password)));
+        }
+    }
+
+    fn receiver(self: &Arc<Self>) -> Option<Arc<ReceiverView>> {
         self.receiver_var
             .get()
             .as_ref()
@@ -101,23 +147,30 @@ impl ReceiverBuilderView {
             .cloned()
     }
 
+    fn update_peer_state(self: &Arc<Self>) {
+        let state = PeerState {
+            is_away: *self.is_away_var.get(),
+            ..PeerState::default()
+        };
+        if let Some(receiver) = self.receiver() {
+            receiver.set_peer_state(state);
+        }
+    }
+
     async fn on_event(self: &Arc<Self>, ev: ReceiverEvent) {
-        use log::{debug, error};
+        use log::debug;
         match ev {
-            ReceiverEvent::IceConnectionStateChange(value) => {
-                self.ice_connection_state_var.set(format!("{:?}", value))
-            }
-            ReceiverEvent::IceGatheringStateChange(value) => {
-                self.ice_gathering_state_var.set(format!("{:?}", value))
-            }
-            ReceiverEvent::RtcSignalingStateChange(value) => {
-                self.signaling_state_var.set(format!("{:?}", value))
-            }
             ReceiverEvent::MediaReceiver(media_receiver_builder) => {
+                self.model.poster().set(None);
                 if let Some(receiver) = self.receiver() {
                     receiver.on_media_receiver(media_receiver_builder).await;
                 }
             }
+            ReceiverEvent::MediaStreamRemoved(media_stream) => {
+                if let Some(receiver) = self.receiver() {
+                    receiver.on_media_stream_removed(&media_stream);
+                }
+            }
             ReceiverEvent::DataReceiver(data_receiver_buidler) => {
                 if let Some(receiver) = self.receiver() {
                     receiver.on_data_receiver(data_receiver_buidler).await;
@@ -128,83 +181,164 @@ impl ReceiverBuilderView {
                     receiver.on_socket_binary_data(data).await;
                 }
             }
-            ReceiverEvent::Error(err) => error!("{}", err),
-            ev => debug!("Receiver event {:?}", ev),
+            ReceiverEvent::TopicData { topic, data } => {
+                debug!("Receiver topic {:?} data received", topic);
+                if let Some(receiver) = self.receiver() {
+                    receiver.on_topic_data(&topic, data).await;
+                }
+            }
+            ReceiverEvent::Caption(cue) => {
+                if let Some(receiver) = self.receiver() {
+                    receiver.on_caption(&cue);
+                }
+            }
+            ev => self.model.on_event(ev),
         }
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
+    pub fn channel_id(&self) -> &ChannelId {
+        &self.channel_id
+    }
+
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
         let receiver_var = self.receiver_var.clone();
-        let ice_connection_state_var = self.ice_connection_state_var.clone();
-        let ice_gathering_state_var = self.ice_gathering_state_var.clone();
-        let signaling_state_var = self.signaling_state_var.clone();
+        let model = create_ref(cx, Arc::clone(&self.model));
+        let ice_connection_state_var = bind_signal(cx, model.ice_connection_state());
+        let ice_gathering_state_var = bind_signal(cx, model.ice_gathering_state());
+        let signaling_state_var = bind_signal(cx, model.signaling_state());
+        let poster_var = bind_signal(cx, model.poster());
+        let is_away_var: &Signal<bool> = create_ref(cx, self.is_away_var.clone());
+        let peer_state_var = bind_signal(cx, model.peer_state());
+        let fingerprints_var = bind_signal(cx, model.fingerprints());
+        let verified_peer_var = bind_signal(cx, model.verified_peer());
+        let quality_var = bind_signal(cx, model.quality());
+        let usage_var = bind_signal(cx, model.usage());
+        let stream_labels_var = bind_signal(cx, model.stream_labels());
+        let sender_video_var = bind_signal(cx, model.sender_video());
 
-        let channel_id = self.channel_id.clone();
+        create_effect(cx, {
+            let self_weak = Arc::downgrade(self);
+            move || {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    self_arc.update_peer_state()
+                }
+            }
+        });
+
+        let channel_id = self.channel_id.0.clone();
 
         let on_close_click = {
             let self_weak = Arc::downgrade(self);
             move |_| {
-                let self_arc = self_weak.upgrade().unwrap();
-                self_arc.receivers.remove_receiver(&self_arc)
+                if let Some(self_arc) = self_weak.upgrade() {
+                    if let Some(receivers) = self_arc.receivers.upgrade() {
+                        receivers.remove_receiver(&self_arc)
+                    }
+                }
             }
         };
 
-        template! {
+        let receiver_view = View::new_dyn_scoped(cx, move |cx| match receiver_var.get().as_ref() {
+            Some(Ok(receiver)) => receiver.view(cx),
+            Some(Err(err)) => {
+                let err = err.to_string();
+                view! { cx,
+                    h2 {
+                        "error"
+                    }
+                    textarea(class = "error", readonly = true) {
+                        (err)
+                    }
+                }
+            }
+            None => view! { cx,
+                h2(class = "loading") {
+                    "loading..."
+                }
+            },
+        });
+
+        view! { cx,
             div(class = "component") {
-                h1() {
-                    ("Receiver")
+                h1 {
+                    "Receiver"
                 }
                 button(on:click = on_close_click, class = "close") {
-                    ("close")
+                    "close"
                 }
                 div(class = "monospace") {
-                    ("channel id: ")
-                    (channel_id.0)
+                    "channel id: "
+                    (channel_id)
+                }
+                div {
+                    label {
+                        input(type = "checkbox", bind:checked = is_away_var)
+                        "Away"
+                    }
                 }
                 div(class = "monospace") {
-                    ("ice_connection_state: ")
+                    "sender: "
+                    (peer_state_var.get())
+                }
+                div(class = "monospace", title = describe_quality_details(*quality_var.get())) {
+                    "quality: "
+                    (describe_quality(*quality_var.get()))
+                }
+                div(class = "monospace", title = describe_usage_details(*usage_var.get())) {
+                    "usage: "
+                    (describe_usage(*usage_var.get()))
+                }
+                div(class = "monospace") {
+                    "streams: "
+                    (stream_labels_var.get())
+                }
+                div(class = "monospace") {
+                    "sender video: "
+                    (sender_video_var.get())
+                }
+                div(class = "monospace") {
+                    "key fingerprints (local / sender): "
+                    (fingerprints_var.get())
+                }
+                div(class = "monospace") {
+                    "verified sender identity: "
+                    (verified_peer_var.get())
+                }
+                div(class = "monospace") {
+                    "ice_connection_state: "
                     (ice_connection_state_var.get())
                 }
                 div(class = "monospace") {
-                    ("ice_gathering_state: ")
+                    "ice_gathering_state: "
                     (ice_gathering_state_var.get())
                 }
                 div(class = "monospace") {
-                    ("signaling_state: ")
+                    "signaling_state: "
                     (signaling_state_var.get())
                 }
-                ({
-                    let receiver = receiver_var.get();
-
-                    match receiver.as_ref() {
-                        Some(Ok(receiver)) => {
-                            receiver.view()
-                        },
-                        Some(Err(err)) => {
-                            let err = err.to_string();
-                            template! {
-                                h2() {
-                                    ("error")
-                                }
-                                textarea(class = "error", readonly = true) {
-                                    (err)
-                                }
-                            }
-                        },
-                        None => {
-                            template! {
-                                h2(class = "loading") {
-                                    ("loading...")
-                                }
-                            }
+                (match poster_var.get().as_ref() {
+                    Some(poster) => {
+                        let poster = poster.clone();
+                        view! { cx,
+                            img(class = "snapshot", src = poster)
                         }
                     }
+                    None => View::empty(),
                 })
+                (receiver_view)
             }
         }
     }
 }
 
+impl PartialEq for ReceiverBuilderView {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self, other)
+    }
+}
+
+impl Eq for ReceiverBuilderView {}
+
 impl Drop for ReceiverBuilderView {
     fn drop(&mut self) {
         log::debug!("drop ReceiverBuilderView");
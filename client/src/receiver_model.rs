@@ -0,0 +1,186 @@
+use async_std::sync::Arc;
+use browser_webrtc::signaling_protocol::ChannelId;
+use browser_webrtc::{ConnectionQuality, Receiver, ReceiverEvent, SessionUsage};
+
+use crate::{describe_peer_state, CallDirection, CallTracker, Observable};
+
+/// Connection state of a receiver as shown to the user.
+#[derive(Debug)]
+pub struct ReceiverModel {
+    ice_connection_state: Observable<String>,
+    ice_gathering_state: Observable<String>,
+    signaling_state: Observable<String>,
+    poster: Observable<Option<String>>,
+    peer_state: Observable<String>,
+    fingerprints: Observable<String>,
+    verified_peer: Observable<String>,
+    quality: Observable<Option<ConnectionQuality>>,
+    usage: Observable<SessionUsage>,
+    stream_labels: Observable<String>,
+    sender_video: Observable<String>,
+    call: CallTracker,
+}
+
+impl ReceiverModel {
+    pub fn new(channel_id: ChannelId) -> Arc<Self> {
+        Arc::new(Self {
+            ice_connection_state: Observable::new(String::new()),
+            ice_gathering_state: Observable::new(String::new()),
+            signaling_state: Observable::new(String::new()),
+            poster: Observable::new(None),
+            peer_state: Observable::new("unknown".to_owned()),
+            fingerprints: Observable::new("not sealed".to_owned()),
+            verified_peer: Observable::new("not verified".to_owned()),
+            quality: Observable::new(None),
+            usage: Observable::new(SessionUsage::default()),
+            stream_labels: Observable::new("none".to_owned()),
+            sender_video: Observable::new("sent".to_owned()),
+            call: CallTracker::new(channel_id, CallDirection::Incoming),
+        })
+    }
+
+    pub fn ice_connection_state(&self) -> &Observable<String> {
+        &self.ice_connection_state
+    }
+
+    pub fn ice_gathering_state(&self) -> &Observable<String> {
+        &self.ice_gathering_state
+    }
+
+    pub fn signaling_state(&self) -> &Observable<String> {
+        &self.signaling_state
+    }
+
+    /// Data URL of the channel snapshot shown until the remote media is received.
+    pub fn poster(&self) -> &Observable<Option<String>> {
+        &self.poster
+    }
+
+    pub fn peer_state(&self) -> &Observable<String> {
+        &self.peer_state
+    }
+
+    pub fn fingerprints(&self) -> &Observable<String> {
+        &self.fingerprints
+    }
+
+    pub fn verified_peer(&self) -> &Observable<String> {
+        &self.verified_peer
+    }
+
+    pub fn quality(&self) -> &Observable<Option<ConnectionQuality>> {
+        &self.quality
+    }
+
+    pub fn usage(&self) -> &Observable<SessionUsage> {
+        &self.usage
+    }
+
+    /// Labels of the received streams, e.g. "camera, screen".
+    pub fn stream_labels(&self) -> &Observable<String> {
+        &self.stream_labels
+    }
+
+    /// Whether the sender stopped its video because of the poor connection.
+    pub fn sender_video(&self) -> &Observable<String> {
+        &self.sender_video
+    }
+
+    /// Reads the initial connection states of the joined receiver.
+    pub fn on_joined(&self, receiver: &Receiver) {
+        self.ice_connection_state
+            .set(format!("{:?}", receiver.ice_connection_state()));
+        self.ice_gathering_state
+            .set(format!("{:?}", receiver.ice_gathering_state()));
+        self.signaling_state
+            .set(format!("{:?}", receiver.signaling_state()));
+    }
+
+    /// Handles the state events, the received media and data are handled by the view.
+    pub fn on_event(&self, ev: ReceiverEvent) {
+        use log::{debug, error, info};
+
+        self.on_call_event(&ev);
+        match ev {
+            ReceiverEvent::IceConnectionStateChange(value) => {
+                self.ice_connection_state.set(format!("{:?}", value))
+            }
+            ReceiverEvent::IceGatheringStateChange(value) => {
+                self.ice_gathering_state.set(format!("{:?}", value))
+            }
+            ReceiverEvent::RtcSignalingStateChange(value) => {
+                self.signaling_state.set(format!("{:?}", value))
+            }
+            ReceiverEvent::ChannelSnapshot(snapshot) => self.poster.set(Some(snapshot.data_url())),
+            ReceiverEvent::PeerStateChanged(state) => {
+                self.peer_state.set(describe_peer_state(state))
+            }
+            ReceiverEvent::PeerKeyReceived {
+                fingerprint,
+                local_fingerprint,
+            } => self
+                .fingerprints
+                .set(format!("{} / {}", local_fingerprint, fingerprint)),
+            ReceiverEvent::VerifiedPeer(peer) => self.verified_peer.set(peer.fingerprint),
+            ReceiverEvent::QualityChanged(quality) => self.quality.set(Some(quality)),
+            ReceiverEvent::UsageUpdated(usage) => self.usage.set(usage),
+            ReceiverEvent::StreamLabelsChanged(labels) => {
+                let mut labels: Vec<_> = labels.into_values().collect();
+                labels.sort();
+                self.stream_labels.set(if labels.is_empty() {
+                    "none".to_owned()
+                } else {
+                    labels.join(", ")
+                })
+            }
+            ReceiverEvent::PeerAudioOnlyChanged(is_audio_only) => {
+                self.sender_video.set(if is_audio_only {
+                    "stopped, poor connection".to_owned()
+                } else {
+                    "sent".to_owned()
+                })
+            }
+            ReceiverEvent::Error(err) => error!("{}", err),
+            ReceiverEvent::Closed {
+                reason,
+                diagnostics: Some(diagnostics),
+            } => error!("Receiver closed: {:?}, {:?}", reason, diagnostics),
+            ReceiverEvent::Closed {
+                reason,
+                diagnostics: None,
+            } => info!("Receiver closed: {:?}", reason),
+            ReceiverEvent::SessionEnding { reason, remaining } => {
+                info!("Receiver session ends in {:?}: {:?}", remaining, reason)
+            }
+            ReceiverEvent::SessionEnded(reason) => info!("Receiver session ended: {:?}", reason),
+            ReceiverEvent::NetworkChanged(change) => {
+                info!("Receiver network changed: {:?}", change)
+            }
+            ReceiverEvent::VisibilityChanged(visibility) => {
+                info!("Receiver page visibility changed: {:?}", visibility)
+            }
+            ReceiverEvent::ChannelRenamed(channel_id) => {
+                info!("Receiver channel renamed: {:?}", channel_id)
+            }
+            ev => debug!("Receiver event {:?}", ev),
+        }
+    }
+
+    fn on_call_event(&self, ev: &ReceiverEvent) {
+        use web_sys::RtcIceConnectionState;
+
+        match ev {
+            ReceiverEvent::IceConnectionStateChange(RtcIceConnectionState::Connected) => {
+                self.call.start()
+            }
+            ReceiverEvent::VerifiedPeer(peer) => self.call.set_peer(peer.fingerprint.clone()),
+            ReceiverEvent::QualityChanged(quality) => self.call.set_quality_score(quality.score),
+            ReceiverEvent::ChannelRenamed(channel_id) => {
+                self.call.set_channel_id(channel_id.clone())
+            }
+            ReceiverEvent::Closed { reason, .. } => self.call.finish(format!("{:?}", reason)),
+            ReceiverEvent::SessionEnded(reason) => self.call.finish(format!("{:?}", reason)),
+            _ => {}
+        }
+    }
+}
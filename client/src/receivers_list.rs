@@ -1,99 +1,213 @@
+use core::time::Duration;
+
 use async_std::sync::{Arc, Weak};
-use browser_webrtc::signaling_protocol::ChannelId;
+use browser_webrtc::signaling_protocol::{ChannelId, ChannelSecret, ChannelStatus, IntendedMedia};
 use browser_webrtc::Server;
-use core::cell::RefCell;
 use sycamore::prelude::*;
 
-use crate::ReceiverBuilderView;
+use crate::{bind_signal, ChannelListState, ReceiverBuilderView, ServerModel};
 
 #[derive(Debug)]
 pub struct ReceiversListView {
     server: Weak<Server>,
-    channels_var: Signal<Vec<ChannelId>>,
-    receivers_var: Signal<RefCell<Vec<Arc<ReceiverBuilderView>>>>,
+    model: Arc<ServerModel>,
+    receivers_var: RcSignal<Vec<Arc<ReceiverBuilderView>>>,
 }
 
 impl ReceiversListView {
-    pub fn new(server: Arc<Server>, channels_var: Signal<Vec<ChannelId>>) -> Arc<Self> {
+    pub fn new(server: Arc<Server>, model: Arc<ServerModel>) -> Arc<Self> {
         log::trace!("client::ReceiversListView::new");
 
-        let receivers_var = Signal::new(RefCell::new(Vec::new()));
+        let receivers_var = create_rc_signal(Vec::new());
 
         Arc::new(Self {
             server: Arc::downgrade(&server),
-            channels_var,
+            model,
             receivers_var,
         })
     }
 
-    pub fn add_receiver(self: &Arc<Self>, channel_id: ChannelId) {
-        use crate::SignalVecPush;
-        let receiver =
-            ReceiverBuilderView::new(Arc::clone(self), self.server.upgrade().unwrap(), channel_id);
-        self.receivers_var.push(receiver);
+    /// Joins the channel, the credential is asked by the receiver if the channel requires it.
+    pub fn add_receiver(
+        self: &Arc<Self>,
+        channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
+    ) {
+        let server = match self.server.upgrade() {
+            Some(server) => server,
+            None => return,
+        };
+        let receiver = ReceiverBuilderView::new(Arc::clone(self), server, channel_id, credential);
+        self.receivers_var.modify().push(receiver);
+    }
+
+    pub fn request_channel_snapshot(self: &Arc<Self>, channel_id: ChannelId) {
+        if let Some(server) = self.server.upgrade() {
+            if let Err(err) = server.request_channel_snapshot(channel_id, None) {
+                log::error!("{}", err);
+            }
+        }
     }
 
     pub fn remove_receiver(self: &Arc<Self>, receiver: &Arc<ReceiverBuilderView>) {
-        use crate::SignalVecRemoveByPtrEq;
-        self.receivers_var.remove_by_ptr_eq(receiver);
+        self.receivers_var
+            .modify()
+            .retain(|other| !Arc::ptr_eq(receiver, other));
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
-        let channels_var = self.channels_var.clone();
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
+        let model = create_ref(cx, Arc::clone(&self.model));
+        let channels_var = bind_signal(cx, model.channels());
+        let channels = create_memo(cx, move || channels_var.get().as_ref().clone());
+        let has_channels = create_selector(cx, || !channels.get().is_empty());
+        let channel_previews_var = bind_signal(cx, model.channel_previews());
+        let channel_states_var = bind_signal(cx, model.channel_states());
+
         let receivers_var = self.receivers_var.clone();
+        let receivers = create_memo(cx, move || receivers_var.get().as_ref().clone());
+        let joined_channels = create_memo(cx, move || {
+            receivers
+                .get()
+                .iter()
+                .map(|receiver| receiver.channel_id())
+                .collect::<Vec<_>>()
+        });
 
-        let on_add_receiver_click = {
-            let self_arc = Arc::clone(self);
-            move |channel_id: ChannelId| {
-                let self_arc = Arc::clone(&self_arc);
-                move |_| self_arc.add_receiver(channel_id.clone())
-            }
-        };
+        let self_arc = create_ref(cx, Arc::clone(self));
 
-        template! {
+        view! { cx,
             div(class = "component") {
-                h1() {
-                    ("Receivers")
+                h1 {
+                    "Receivers"
                 }
-                div() {
-                    ({
-                        let channels = channels_var.get();
-                        if channels.is_empty() {
-                            template! {
-                                div() {
-                                    ("No open channels found")
-                                }
+                div {
+                    (if *has_channels.get() {
+                        View::empty()
+                    } else {
+                        view! { cx,
+                            div {
+                                "No open channels found"
                             }
-                        } else {
-                            let fragment = Template::new_fragment(
-                                channels
-                                    .iter()
-                                    .cloned()
-                                    .map(|channel| template! {
-                                        button(
-                                            on:click = on_add_receiver_click(channel.clone())
-                                        ) {
-                                            ("Join channel: ")
-                                            (channel.0)
-                                        }
-                                    })
-                                    .collect(),
-                            );
-                            fragment
                         }
                     })
+                    Keyed(
+                        iterable = channels,
+                        view = move |cx, channel: ChannelId| {
+                            let name = channel.0.clone();
+                            let snapshot_channel = channel.clone();
+                            let preview = create_memo(cx, {
+                                let channel = channel.clone();
+                                move || channel_previews_var.get().get(&channel).cloned()
+                            });
+                            let state = create_memo(cx, {
+                                let channel = channel.clone();
+                                move || channel_states_var.get().get(&channel).copied()
+                            });
+                            let class = create_memo(cx, move || match *state.get() {
+                                Some(ChannelListState::New) => "channel channel-new",
+                                Some(ChannelListState::Closed) => "channel channel-closed",
+                                Some(ChannelListState::Open) | None => "channel",
+                            });
+                            let is_join_disabled = create_memo(cx, {
+                                let channel = channel.clone();
+                                move || {
+                                    let is_ended = preview.get().as_ref().as_ref().map_or(
+                                        false,
+                                        |preview| preview.status == Some(ChannelStatus::Ended),
+                                    );
+                                    *state.get() == Some(ChannelListState::Closed)
+                                        || is_ended
+                                        || joined_channels.get().contains(&channel)
+                                }
+                            });
+                            view! { cx,
+                                div(class = *class.get()) {
+                                    button(
+                                        on:click = move |_| self_arc.add_receiver(channel.clone(), None),
+                                        disabled = *is_join_disabled.get(),
+                                    ) {
+                                        "Join channel: "
+                                        (name)
+                                    }
+                                    button(on:click = move |_| {
+                                        self_arc.request_channel_snapshot(snapshot_channel.clone())
+                                    }) {
+                                        "Preview"
+                                    }
+                                    (match preview.get().as_ref() {
+                                        Some(preview) => {
+                                            let caption = preview.caption.clone();
+                                            let status = match preview.status {
+                                                Some(status) => {
+                                                    let status = describe_channel_status(
+                                                        status,
+                                                        preview.scheduled_start,
+                                                    );
+                                                    view! { cx,
+                                                        div(class = "monospace") {
+                                                            "status: "
+                                                            (status)
+                                                        }
+                                                    }
+                                                }
+                                                None => View::empty(),
+                                            };
+                                            let intended_media = match preview.intended_media {
+                                                Some(media) => {
+                                                    let media = describe_intended_media(media);
+                                                    view! { cx,
+                                                        div(class = "monospace") {
+                                                            "on join: "
+                                                            (media)
+                                                        }
+                                                    }
+                                                }
+                                                None => View::empty(),
+                                            };
+                                            let caption_languages = if preview
+                                                .caption_languages
+                                                .is_empty()
+                                            {
+                                                View::empty()
+                                            } else {
+                                                let languages = preview.caption_languages.join(", ");
+                                                view! { cx,
+                                                    div(class = "monospace") {
+                                                        "captions: "
+                                                        (languages)
+                                                    }
+                                                }
+                                            };
+                                            let image = match preview.image_url.clone() {
+                                                Some(image_url) => view! { cx,
+                                                    img(class = "snapshot", src = image_url)
+                                                },
+                                                None => View::empty(),
+                                            };
+                                            view! { cx,
+                                                (image)
+                                                div(class = "monospace") {
+                                                    (caption)
+                                                }
+                                                (intended_media)
+                                                (caption_languages)
+                                                (status)
+                                            }
+                                        }
+                                        None => View::empty(),
+                                    })
+                                }
+                            }
+                        },
+                        key = |channel| channel.clone(),
+                    )
                 }
-                div() {
-                    ({
-                        Template::new_fragment(
-                            receivers_var
-                                .get()
-                                .borrow()
-                                .iter()
-                                .map(|receiver| receiver.view())
-                                .collect(),
-                        )
-                    })
+                div {
+                    Keyed(
+                        iterable = receivers,
+                        view = |cx, receiver| receiver.view(cx),
+                        key = |receiver| Arc::as_ptr(receiver) as usize,
+                    )
                 }
             }
         }
@@ -105,3 +219,28 @@ impl Drop for ReceiversListView {
         log::trace!("client::ReceiversListView::drop");
     }
 }
+
+fn describe_channel_status(status: ChannelStatus, scheduled_start: Option<Duration>) -> String {
+    match (status, scheduled_start) {
+        (ChannelStatus::Scheduled, Some(scheduled_start)) => {
+            let now = Duration::from_millis(js_sys::Date::now() as u64);
+            match scheduled_start.checked_sub(now) {
+                Some(remaining) => format!("scheduled, starts in {} min", remaining.as_secs() / 60),
+                None => "scheduled, starting soon".to_owned(),
+            }
+        }
+        (ChannelStatus::Scheduled, None) => "scheduled".to_owned(),
+        (ChannelStatus::Live, _) => "live".to_owned(),
+        (ChannelStatus::Ended, _) => "ended".to_owned(),
+        (ChannelStatus::Unknown, _) => "unknown".to_owned(),
+    }
+}
+
+fn describe_intended_media(media: IntendedMedia) -> &'static str {
+    match (media.video, media.audio) {
+        (true, true) => "video and audio",
+        (true, false) => "video only",
+        (false, true) => "audio only",
+        (false, false) => "data only",
+    }
+}
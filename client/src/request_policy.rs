@@ -0,0 +1,104 @@
+use core::time::Duration;
+
+use async_std::sync::{Arc, Weak};
+use browser_webrtc::signaling_protocol::PeerId;
+use browser_webrtc::{IncomingRequestPolicy, Server};
+use sycamore::prelude::*;
+
+const DEFAULT_RING_TIMEOUT_SECS: &str = "30";
+
+/// Settings of the incoming channel requests, applied to the server on every change.
+#[derive(Debug)]
+pub struct RequestPolicyView {
+    server: Weak<Server>,
+    do_not_disturb_var: RcSignal<bool>,
+    auto_accept_var: RcSignal<String>,
+    ring_timeout_var: RcSignal<String>,
+}
+
+impl RequestPolicyView {
+    pub fn new(server: Arc<Server>) -> Arc<Self> {
+        log::trace!("client::RequestPolicyView::new");
+
+        Arc::new(Self {
+            server: Arc::downgrade(&server),
+            do_not_disturb_var: create_rc_signal(false),
+            auto_accept_var: create_rc_signal(String::new()),
+            ring_timeout_var: create_rc_signal(DEFAULT_RING_TIMEOUT_SECS.to_owned()),
+        })
+    }
+
+    /// Policy of the current settings, peer ids are comma-separated
+    /// and an empty or invalid ring timeout disables it.
+    fn policy(&self) -> IncomingRequestPolicy {
+        IncomingRequestPolicy {
+            do_not_disturb: *self.do_not_disturb_var.get(),
+            auto_accept: self
+                .auto_accept_var
+                .get()
+                .split(',')
+                .map(str::trim)
+                .filter(|peer_id| !peer_id.is_empty())
+                .map(|peer_id| PeerId(peer_id.to_owned()))
+                .collect(),
+            ring_timeout: self
+                .ring_timeout_var
+                .get()
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&secs: &f64| secs.is_finite() && secs > 0.0)
+                .map(Duration::from_secs_f64),
+        }
+    }
+
+    pub fn view<'a>(self: &Arc<Self>, cx: Scope<'a>) -> View<DomNode> {
+        let do_not_disturb_var: &'a Signal<bool> = create_ref(cx, self.do_not_disturb_var.clone());
+        let auto_accept_var: &'a Signal<String> = create_ref(cx, self.auto_accept_var.clone());
+        let ring_timeout_var: &'a Signal<String> = create_ref(cx, self.ring_timeout_var.clone());
+
+        create_effect(cx, {
+            let self_weak = Arc::downgrade(self);
+            move || {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    let policy = self_arc.policy();
+                    if let Some(server) = self_arc.server.upgrade() {
+                        server.set_incoming_request_policy(policy);
+                    }
+                }
+            }
+        });
+
+        view! { cx,
+            div(class = "component") {
+                h1 {
+                    "Incoming requests"
+                }
+                div {
+                    label {
+                        input(type = "checkbox", bind:checked = do_not_disturb_var)
+                        "Do Not Disturb"
+                    }
+                }
+                div {
+                    label(title = "Comma-separated peer ids, not verified by the server") {
+                        "auto-accept: "
+                        input(type = "text", bind:value = auto_accept_var)
+                    }
+                }
+                div {
+                    label {
+                        "ring timeout, s: "
+                        input(type = "text", bind:value = ring_timeout_var)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RequestPolicyView {
+    fn drop(&mut self) {
+        log::trace!("client::RequestPolicyView::drop");
+    }
+}
@@ -1,40 +1,207 @@
+use core::cell::RefCell;
+
 use async_std::sync::Arc;
-use browser_webrtc::{DataSender, MediaSender, MediaView, Sender};
+use browser_webrtc::{
+    DataSender, LocalMedia, LocalMediaWatcher, MediaPermissionKind, MediaPermissions, MediaSender,
+    MediaView, PeerState, RecordingUploader, Sender, SpeechCaptions,
+};
 use sycamore::prelude::*;
+use web_sys::MediaStreamTrack;
+
+use crate::audio_visualizer_view;
+
+const PREVIEW_THUMBNAIL_MAX_WIDTH: u32 = 160;
+const PREVIEW_THUMBNAIL_QUALITY: f64 = 0.6;
 
 #[derive(Debug)]
 pub struct SenderView {
     sender: Arc<Sender>,
+    local_media: Option<LocalMedia>,
     media_sender: Option<Arc<MediaSender>>,
     media_view: Option<Arc<MediaView>>,
+    media_permissions: Option<Arc<MediaPermissions>>,
+    local_media_watcher: Option<Arc<LocalMediaWatcher>>,
     data_sender: Option<Arc<DataSender>>,
+    key_frame_state_var: RcSignal<String>,
+    /// Uploads the local media to the server while the recording is toggled on.
+    recording_uploader: RefCell<Option<RecordingUploader>>,
+    recording_state_var: RcSignal<String>,
+    /// Publishes the recognized speech captions while they are toggled on.
+    speech_captions: RefCell<Option<SpeechCaptions>>,
+    captions_state_var: RcSignal<String>,
+    /// ICE timeline dump, filled on request.
+    ice_timeline_var: RcSignal<String>,
+}
+
+/// Local media of a sender, empty while the capture is deferred.
+#[derive(Debug, Default)]
+pub struct SenderMedia {
+    pub local_media: Option<LocalMedia>,
+    pub media_sender: Option<Arc<MediaSender>>,
+    pub media_view: Option<Arc<MediaView>>,
+    pub media_permissions: Option<Arc<MediaPermissions>>,
+    /// Watches the shared screen to report the sharing stopped by the user.
+    pub local_media_watcher: Option<Arc<LocalMediaWatcher>>,
 }
 
 impl SenderView {
     pub fn new(
         sender: Arc<Sender>,
-        media_sender: Option<Arc<MediaSender>>,
-        media_view: Option<Arc<MediaView>>,
+        media: SenderMedia,
         data_sender: Option<Arc<DataSender>>,
     ) -> Arc<Self> {
         log::trace!("client::SenderView::new");
 
         Arc::new(Self {
             sender,
-            media_sender,
-            media_view,
+            local_media: media.local_media,
+            media_sender: media.media_sender,
+            media_view: media.media_view,
+            media_permissions: media.media_permissions,
+            local_media_watcher: media.local_media_watcher,
             data_sender,
+            key_frame_state_var: create_rc_signal(String::new()),
+            recording_uploader: RefCell::new(None),
+            recording_state_var: create_rc_signal(String::new()),
+            speech_captions: RefCell::new(None),
+            captions_state_var: create_rc_signal(String::new()),
+            ice_timeline_var: create_rc_signal(String::new()),
         })
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
+    /// Returns the view of the same sender with the deferred local media.
+    pub fn with_media(&self, media: SenderMedia) -> Arc<Self> {
+        Self::new(Arc::clone(&self.sender), media, self.data_sender.clone())
+    }
+
+    pub fn sender(&self) -> &Arc<Sender> {
+        &self.sender
+    }
+
+    pub fn set_peer_state(&self, state: PeerState) {
+        self.sender.set_peer_state(state)
+    }
+
+    pub fn stop_media(&self, kind: MediaPermissionKind) {
+        if let Some(local_media) = self.local_media.as_ref() {
+            local_media.stop_tracks_of_kind(kind.track_kind());
+        }
+        if let Some(media_sender) = self.media_sender.as_ref() {
+            media_sender.remove_tracks_of_kind(kind.track_kind());
+        }
+    }
+
+    pub fn add_media_track(&self, track: MediaStreamTrack) {
+        match self.media_sender.as_ref() {
+            Some(media_sender) => media_sender.add_track(track),
+            None => track.stop(),
+        }
+    }
+
+    pub async fn publish_preview(&self, caption: String) {
+        use browser_webrtc::{MediaSnapshotError, MediaSnapshotFormat};
+        use log::warn;
+
+        let format = MediaSnapshotFormat::Jpeg {
+            quality: PREVIEW_THUMBNAIL_QUALITY,
+        };
+        let thumbnail = match self.media_view.as_ref() {
+            Some(media_view) => match media_view
+                .capture_snapshot(PREVIEW_THUMBNAIL_MAX_WIDTH, format)
+                .await
+            {
+                Ok(thumbnail) => Some(thumbnail),
+                Err(MediaSnapshotError::NoVideoFrame) => None,
+                Err(err) => {
+                    warn!("{}", err);
+                    None
+                }
+            },
+            None => None,
+        };
+        if let Err(err) = self.sender.publish_preview(thumbnail.as_ref(), caption) {
+            warn!("{}", err);
+        }
+    }
+
+    async fn request_key_frame(self: Arc<Self>) {
+        let media_sender = match self.media_sender.as_ref() {
+            Some(media_sender) => media_sender,
+            None => return,
+        };
+
+        let method = match media_sender.request_key_frame().await {
+            Ok(Some(method)) => format!("{:?}", method),
+            Ok(None) => "no video".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        let stats = match media_sender.key_frame_request_stats().await {
+            Ok(stats) => format!(
+                "received PLI: {}, FIR: {}",
+                stats.pli_count, stats.fir_count
+            ),
+            Err(err) => err.to_string(),
+        };
+        self.key_frame_state_var
+            .set(format!("keyframe: {}, {}", method, stats));
+    }
+
+    /// Starts uploading the local media to the server or stops the started upload,
+    /// the server reports the stored recording size in `SenderEvent::RecordingStopped`.
+    fn toggle_recording(&self) {
+        use browser_webrtc::RecordingOptions;
+
+        let mut recording_uploader = self.recording_uploader.borrow_mut();
+        if let Some(uploader) = recording_uploader.take() {
+            uploader.stop();
+            self.recording_state_var.set(String::new());
+            return;
+        }
+        let local_media = match self.local_media.as_ref() {
+            Some(local_media) => local_media,
+            None => return,
+        };
+        match RecordingUploader::start(
+            &self.sender,
+            local_media.media_stream(),
+            RecordingOptions::default(),
+        ) {
+            Ok(uploader) => {
+                self.recording_state_var
+                    .set(format!("recording {}", uploader.mime_type()));
+                *recording_uploader = Some(uploader);
+            }
+            Err(err) => self.recording_state_var.set(err.to_string()),
+        }
+    }
+
+    /// Starts publishing the captions of the speech in the browser language or stops them,
+    /// the language is advertised in the next published previews.
+    fn toggle_captions(&self) {
+        use crate::browser_language_tag;
+
+        let mut speech_captions = self.speech_captions.borrow_mut();
+        if speech_captions.take().is_some() {
+            self.sender.set_caption_languages(Vec::new());
+            self.captions_state_var.set(String::new());
+            return;
+        }
+        let language = browser_language_tag().unwrap_or_else(|| "en-US".to_owned());
+        match SpeechCaptions::start(&self.sender, &language) {
+            Ok(captions) => {
+                self.sender.set_caption_languages(vec![language.clone()]);
+                self.captions_state_var
+                    .set(format!("captions {}", language));
+                *speech_captions = Some(captions);
+            }
+            Err(err) => self.captions_state_var.set(err.to_string()),
+        }
+    }
+
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
         use wasm_bindgen::JsCast;
         use web_sys::{Event, HtmlTextAreaElement};
 
-        let media_view = self.media_view.clone();
-        let node_ref = NodeRef::new();
-        let data_sender = self.data_sender.clone();
-
         let on_websocket_data_input = {
             let self_arc = Arc::clone(self);
             move |ev: Event| {
@@ -45,55 +212,112 @@ impl SenderView {
             }
         };
 
-        template! {
-            ({
-                if let Some(media_view) = media_view.as_ref() {
-                    let template = template! {
-                        div(class = "video", ref = node_ref) {}
-                    };
-                    let node: DomNode = node_ref.get();
-                    let node = node.inner_element();
-                    let _: Option<_> = node.append_child(media_view.view()).ok();
-                    template
-                } else {
-                    template! {}
+        let media_view = match self.media_view.as_ref() {
+            Some(media_view) => {
+                use wasm_bindgen_futures::spawn_local;
+
+                let video = View::new_node(DomNode::from_web_sys(media_view.view().clone().into()));
+                let audio_visualizer = audio_visualizer_view(cx, media_view.view().src_object());
+                let key_frame_state_var = create_ref(cx, self.key_frame_state_var.clone());
+                let on_key_frame_click = {
+                    let self_arc = Arc::clone(self);
+                    move |_| spawn_local(Arc::clone(&self_arc).request_key_frame())
+                };
+                let recording_state_var = create_ref(cx, self.recording_state_var.clone());
+                let on_recording_click = {
+                    let self_arc = Arc::clone(self);
+                    move |_| self_arc.toggle_recording()
+                };
+                let captions_state_var = create_ref(cx, self.captions_state_var.clone());
+                let on_captions_click = {
+                    let self_arc = Arc::clone(self);
+                    move |_| self_arc.toggle_captions()
+                };
+                view! { cx,
+                    div(class = "video") {
+                        (video)
+                        (audio_visualizer)
+                    }
+                    div {
+                        button(on:click = on_key_frame_click) {
+                            "Request keyframe"
+                        }
+                        span(class = "monospace") {
+                            (key_frame_state_var.get())
+                        }
+                    }
+                    div {
+                        button(on:click = on_recording_click) {
+                            "Toggle server recording"
+                        }
+                        span(class = "monospace") {
+                            (recording_state_var.get())
+                        }
+                    }
+                    div {
+                        button(on:click = on_captions_click) {
+                            "Toggle speech captions"
+                        }
+                        span(class = "monospace") {
+                            (captions_state_var.get())
+                        }
+                    }
                 }
-            })
-            ({
-                match data_sender.as_ref() {
-                    Some(data_sender) => {
-                        let on_webrtc_data_input = {
-                            let data_sender = data_sender.clone();
-                            move |ev: Event| {
-                                let target: HtmlTextAreaElement = ev.target().unwrap().dyn_into().unwrap();
-                                let _ = data_sender.send(target.value().as_bytes());
-                            }
-                        };
-
-                        template! {
-                            div() {
-                                label() {
-                                    div() {
-                                        ("WebRtc DataChannel")
-                                    }
-                                    textarea(
-                                        on:input = on_webrtc_data_input,
-                                    ) {}
-                                }
+            }
+            None => View::empty(),
+        };
+
+        let data_view = match self.data_sender.as_ref() {
+            Some(data_sender) => {
+                let on_webrtc_data_input = {
+                    let data_sender = Arc::clone(data_sender);
+                    move |ev: Event| {
+                        let target: HtmlTextAreaElement = ev.target().unwrap().dyn_into().unwrap();
+                        let _ = data_sender.send_text(&target.value());
+                    }
+                };
+
+                view! { cx,
+                    div {
+                        label {
+                            div {
+                                "WebRtc DataChannel"
                             }
+                            textarea(on:input = on_webrtc_data_input)
                         }
-                    },
-                    None => template! {},
+                    }
                 }
-            })
-            div() {
-                label() {
-                    div() {
-                        ("WebSocket DataChannel")
+            }
+            None => View::empty(),
+        };
+
+        let ice_timeline_var = create_ref(cx, self.ice_timeline_var.clone());
+        let on_ice_timeline_click = {
+            let self_arc = Arc::clone(self);
+            move |_| {
+                self_arc
+                    .ice_timeline_var
+                    .set(self_arc.sender.ice_timeline().to_string())
+            }
+        };
+
+        view! { cx,
+            (media_view)
+            (data_view)
+            div {
+                label {
+                    div {
+                        "WebSocket DataChannel"
                     }
-                    textarea(
-                        on:input = on_websocket_data_input,
-                    ) {}
+                    textarea(on:input = on_websocket_data_input)
+                }
+            }
+            div {
+                button(on:click = on_ice_timeline_click) {
+                    "Dump ICE timeline"
+                }
+                textarea(class = "monospace", readonly = true) {
+                    (ice_timeline_var.get())
                 }
             }
         }
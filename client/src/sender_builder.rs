@@ -1,25 +1,84 @@
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+
 use async_std::sync::{Arc, Weak};
-use browser_webrtc::signaling_protocol::{ChannelId, NetworkMode};
-use browser_webrtc::{DataSenderEvent, LocalMedia, MediaView, MediaViewAudio, SenderEvent, Server};
+use browser_webrtc::signaling_protocol::{ChannelId, IntendedMedia, NetworkMode};
+use browser_webrtc::{
+    AudioFallbackPolicy, BackgroundPolicy, BoxAsyncFn2, CaptureDowngrade, CaptureFallback,
+    ChannelAccess, DataSenderEvent, DisplayAudioSource, DisplayMediaAudio, EventBus,
+    EventSubscriptionId, LocalMedia, LocalMediaError, LocalMediaEvent, MediaPermissionKind,
+    MediaPermissions, MediaPermissionsEvent, MediaSenderEvent, MediaSnapshotError,
+    MediaSnapshotFormat, MediaView, MediaViewAudio, MediaViewEvent, OpusParameters, PeerState,
+    RtpSendPriority, SealingKeyPair, Sender, SenderEvent, Server, SessionPolicy, TrackConstraints,
+    VideoThrottle, WarmConnection,
+};
 use sycamore::prelude::*;
 
-use crate::{SenderView, SendersListView};
+use crate::{
+    bind_signal, describe_quality, describe_quality_details, describe_usage,
+    describe_usage_details, load_identity, SenderMedia, SenderModel, SenderView, SendersListView,
+};
 
 const DEFAULT_DATA_CHANNEL_NAME: &'static str = "default";
+const SNAPSHOT_MAX_WIDTH: u32 = 256;
+const PREVIEW_PUBLISH_INTERVAL: Duration = Duration::from_secs(5);
+const TYPING_TIMEOUT: Duration = Duration::from_secs(2);
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+const KIOSK_MAX_SESSION_DURATION: Duration = Duration::from_secs(30 * 60);
+const KIOSK_INACTIVITY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const SCHEDULED_START_DELAY: Duration = Duration::from_secs(5 * 60);
+const SENDER_EVENT_HISTORY_LEN: usize = 64;
 
 #[derive(Debug)]
 pub struct SenderBuilderView {
-    senders: Arc<SendersListView>,
+    senders: Weak<SendersListView>,
     server: Weak<Server>,
-    sender_var: Signal<Option<Result<Arc<SenderView>, anyhow::Error>>>,
-    ice_connection_state_var: Signal<String>,
-    ice_gathering_state_var: Signal<String>,
-    signaling_state_var: Signal<String>,
+    sender_var: RcSignal<Option<Result<Arc<SenderView>, anyhow::Error>>>,
+    model: Arc<SenderModel>,
+    /// Sender events kept for the views created after the channel is opened.
+    events: Arc<EventBus<SenderEvent>>,
+    should_reacquire_devices_var: RcSignal<bool>,
+    caption_var: RcSignal<String>,
+    is_away_var: RcSignal<bool>,
+    is_typing_var: RcSignal<bool>,
+    typing_generation: Cell<u32>,
+    is_capture_deferred: Cell<bool>,
+    has_receiver_joined: Cell<bool>,
+    /// Connection prepared before the sender is added, taken when the channel is opened.
+    warm_connection: RefCell<Option<WarmConnection>>,
     channel_id: ChannelId,
-    network_mode: NetworkMode,
-    should_use_video: bool,
-    should_use_audio: bool,
-    should_use_data_channel: bool,
+    options: SenderOptions,
+}
+
+#[derive(Clone, Debug)]
+pub struct SenderOptions {
+    pub network_mode: NetworkMode,
+    pub should_use_video: bool,
+    pub should_use_audio: bool,
+    pub should_use_hq_audio: bool,
+    pub should_share_screen: bool,
+    pub should_mix_microphone: bool,
+    pub should_use_data_channel: bool,
+    pub should_prioritize_latency: bool,
+    pub should_seal_signaling: bool,
+    pub should_verify_peer: bool,
+    pub should_report_summary: bool,
+    pub should_throttle_in_background: bool,
+    /// Stops the video while the connection can not carry it.
+    pub should_fallback_to_audio: bool,
+    /// Postpones the local media capture until a receiver joins.
+    pub should_defer_capture: bool,
+    /// Ends long or inactive sessions, e.g. for kiosk deployments.
+    pub should_limit_session: bool,
+    /// Lists the channel as an upcoming broadcast until the negotiation starts.
+    pub should_schedule_start: bool,
+    /// Camera to capture, `None` for the browser default.
+    pub video_device_id: Option<String>,
+    /// Microphone to capture, `None` for the browser default.
+    pub audio_device_id: Option<String>,
+    /// Token of the channel id reserved with `Server::reserve_channel`
+    /// and the password required from the receivers.
+    pub access: ChannelAccess,
 }
 
 impl SenderBuilderView {
@@ -27,37 +86,49 @@ impl SenderBuilderView {
         senders: Arc<SendersListView>,
         server: Arc<Server>,
         channel_id: ChannelId,
-        network_mode: NetworkMode,
-        should_use_video: bool,
-        should_use_audio: bool,
-        should_use_data_channel: bool,
+        warm_connection: Option<WarmConnection>,
+        options: SenderOptions,
     ) -> Arc<Self> {
         use wasm_bindgen_futures::spawn_local;
 
         log::trace!("client::SenderView::new");
 
-        let sender_var = Signal::new(None);
-        let ice_connection_state_var = Signal::new(String::new());
-        let ice_gathering_state_var = Signal::new(String::new());
-        let signaling_state_var = Signal::new(String::new());
+        let sender_var = create_rc_signal(None);
+
+        let model = SenderModel::new(channel_id.clone());
+        let events = EventBus::new(SENDER_EVENT_HISTORY_LEN);
+        let _: EventSubscriptionId = events.subscribe_all({
+            let model = Arc::clone(&model);
+            move |ev| model.on_event(ev)
+        });
 
         let sender = Arc::new(Self {
-            senders,
+            senders: Arc::downgrade(&senders),
             server: Arc::downgrade(&server),
             sender_var: sender_var.clone(),
-            ice_connection_state_var,
-            ice_gathering_state_var,
-            signaling_state_var,
+            model,
+            events,
+            should_reacquire_devices_var: create_rc_signal(true),
+            caption_var: create_rc_signal(String::new()),
+            is_away_var: create_rc_signal(false),
+            is_typing_var: create_rc_signal(false),
+            typing_generation: Cell::new(0),
+            is_capture_deferred: Cell::new(false),
+            has_receiver_joined: Cell::new(false),
+            warm_connection: RefCell::new(warm_connection),
             channel_id: channel_id.clone(),
-            network_mode,
-            should_use_video,
-            should_use_audio,
-            should_use_data_channel,
+            options,
         });
 
         spawn_local({
             let sender = Arc::clone(&sender);
-            async move { sender_var.set(Some(sender.init().await)) }
+            let sender_weak = Arc::downgrade(&sender);
+            async move {
+                sender_var.set(Some(Arc::clone(&sender).init().await));
+                sender.capture_deferred_media();
+                drop(sender);
+                Self::publish_previews(sender_weak).await
+            }
         });
 
         sender
@@ -68,24 +139,43 @@ impl SenderBuilderView {
         use log::error;
 
         let self_weak = Arc::downgrade(&self);
-        let rtc_configuration = default_rtc_configuration().with_google_stun_server();
-        let sender = self
-            .server
-            .upgrade()
-            .unwrap()
-            .open_channel(
-                self.channel_id.clone(),
-                self.network_mode,
-                Some(rtc_configuration),
-                Box::new(move |_, ev| {
-                    let self_weak = Weak::clone(&self_weak);
-                    Box::pin(async move {
-                        let self_arc = self_weak.upgrade().unwrap();
-                        self_arc.on_event(ev).await
-                    })
-                }),
-            )
-            .await;
+        let handler: BoxAsyncFn2<_, _, _> = Box::new(move |_, ev| {
+            let self_weak = Weak::clone(&self_weak);
+            Box::pin(async move {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    self_arc.on_event(ev).await
+                }
+            })
+        });
+        let server = self.server.upgrade().unwrap();
+        let warm_connection = self.warm_connection.take();
+        let sender = match warm_connection {
+            Some(connection) => {
+                server
+                    .open_warm_channel_confirmed(
+                        self.channel_id.clone(),
+                        self.options.access.clone(),
+                        self.options.network_mode,
+                        connection,
+                        handler,
+                        OPEN_TIMEOUT,
+                    )
+                    .await
+            }
+            None => {
+                let rtc_configuration = default_rtc_configuration().with_google_stun_server();
+                server
+                    .open_channel_confirmed(
+                        self.channel_id.clone(),
+                        self.options.access.clone(),
+                        self.options.network_mode,
+                        Some(rtc_configuration),
+                        handler,
+                        OPEN_TIMEOUT,
+                    )
+                    .await
+            }
+        };
 
         let sender = match sender {
             Ok(sender) => sender,
@@ -95,32 +185,36 @@ impl SenderBuilderView {
             }
         };
 
-        let media = match (self.should_use_video, self.should_use_audio) {
-            (true, true) => Some(LocalMedia::with_video_and_audio().await),
-            (true, false) => Some(LocalMedia::with_video().await),
-            (false, true) => Some(LocalMedia::with_audio().await),
-            (false, false) => None,
-        };
+        sender.set_quality_automatic(true);
 
-        let media_stream = media.as_ref().map(|media| media.media_stream());
-        let media_sender =
-            media_stream.map(|media_stream| sender.add_media_stream(media_stream.clone()));
-        let media_view = media_stream
-            .map(|media_stream| {
-                MediaView::new(media_stream.clone(), MediaViewAudio::Disable)
-                    .map_err(|err| anyhow::Error::msg(err.to_string()))
-            })
-            .transpose()?;
+        if self.options.should_use_hq_audio {
+            sender.set_opus_parameters(Some(OpusParameters::high_quality()));
+        }
+
+        let media = if self.options.should_defer_capture && self.has_media_options() {
+            sender.set_intended_media(Some(IntendedMedia {
+                video: self.options.should_use_video || self.options.should_share_screen,
+                audio: self.options.should_use_audio,
+            }));
+            self.is_capture_deferred.set(true);
+            self.model
+                .media_state()
+                .set("deferred until a receiver joins".to_owned());
+            SenderMedia::default()
+        } else {
+            self.add_local_media(&sender).await?
+        };
 
         let self_weak = Arc::downgrade(&self);
-        let data_sender = if self.should_use_data_channel {
+        let data_sender = if self.options.should_use_data_channel {
             Some(sender.add_data_channel(
                 DEFAULT_DATA_CHANNEL_NAME,
                 Box::new(move |_, ev| {
                     let self_weak = Weak::clone(&self_weak);
                     Box::pin(async move {
-                        let self_arc = self_weak.upgrade().unwrap();
-                        self_arc.on_datachannel_event(ev).await
+                        if let Some(self_arc) = self_weak.upgrade() {
+                            self_arc.on_datachannel_event(ev).await
+                        }
                     })
                 }),
             ))
@@ -128,6 +222,69 @@ impl SenderBuilderView {
             None
         };
 
+        if self.options.should_seal_signaling {
+            let key_pair = match SealingKeyPair::generate().await {
+                Ok(key_pair) => key_pair,
+                Err(err) => {
+                    error!("{}", err);
+                    return Err(anyhow::Error::msg(err.to_string()));
+                }
+            };
+            self.model
+                .fingerprints()
+                .set(format!("{} / waiting for receiver", key_pair.fingerprint()));
+            if let Err(err) = sender.enable_sealed_signaling(key_pair) {
+                error!("{}", err);
+                return Err(anyhow::Error::msg(err.to_string()));
+            }
+        }
+
+        if self.options.should_verify_peer {
+            match load_identity().await {
+                Ok(key_pair) => {
+                    sender.set_identity_key_pair(key_pair);
+                    sender.set_verified_peer_required(true);
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    return Err(anyhow::Error::msg(err.to_string()));
+                }
+            }
+        }
+
+        sender.set_summary_reporting(self.options.should_report_summary);
+        if self.options.should_limit_session {
+            sender.set_session_policy(SessionPolicy {
+                max_duration: Some(KIOSK_MAX_SESSION_DURATION),
+                inactivity_timeout: Some(KIOSK_INACTIVITY_TIMEOUT),
+                ..SessionPolicy::default()
+            });
+        }
+        if self.options.should_schedule_start {
+            let now = Duration::from_millis(js_sys::Date::now() as u64);
+            if let Err(err) = sender.schedule_start(Some(now + SCHEDULED_START_DELAY)) {
+                error!("{}", err);
+            }
+        }
+        if self.options.should_throttle_in_background {
+            let policy = BackgroundPolicy {
+                throttle_outgoing_video: Some(VideoThrottle::default()),
+                ..BackgroundPolicy::default()
+            };
+            if let Err(err) = sender.set_background_policy(policy).await {
+                error!("{}", err);
+            }
+        }
+
+        if self.options.should_fallback_to_audio {
+            if let Err(err) = sender
+                .set_audio_fallback_policy(Some(AudioFallbackPolicy::default()))
+                .await
+            {
+                error!("{}", err);
+            }
+        }
+
         match sender.start().await {
             Ok(()) => {}
             Err(err) => {
@@ -136,35 +293,494 @@ impl SenderBuilderView {
             }
         };
 
-        self.ice_connection_state_var
-            .set(format!("{:?}", sender.ice_connection_state()));
-        self.ice_gathering_state_var
-            .set(format!("{:?}", sender.ice_gathering_state()));
-        self.signaling_state_var
-            .set(format!("{:?}", sender.signaling_state()));
+        self.model.on_started(&sender);
 
-        let sender_view = SenderView::new(sender, media_sender, media_view, data_sender);
+        let sender_view = SenderView::new(sender, media, data_sender);
 
         Ok(sender_view)
     }
 
-    async fn on_event(self: &Arc<Self>, ev: SenderEvent) {
-        use log::{debug, error};
+    fn has_media_options(&self) -> bool {
+        self.options.should_use_video
+            || self.options.should_use_audio
+            || self.options.should_share_screen
+    }
+
+    /// Captures the local media and adds it to the sender.
+    async fn add_local_media(
+        self: &Arc<Self>,
+        sender: &Arc<Sender>,
+    ) -> Result<SenderMedia, anyhow::Error> {
+        let media = self.acquire_local_media().await;
+
+        let media_stream = media.as_ref().map(|media| media.media_stream());
+        let media_sender = media_stream.map(|media_stream| {
+            let self_weak = Arc::downgrade(self);
+            sender.add_media_stream(
+                media_stream.clone(),
+                Box::new(move |_, ev| {
+                    let self_weak = Weak::clone(&self_weak);
+                    Box::pin(async move {
+                        if let Some(self_arc) = self_weak.upgrade() {
+                            self_arc.on_media_sender_event(ev).await
+                        }
+                    })
+                }),
+            )
+        });
+        if let Some(media_sender) = media_sender.as_ref() {
+            let label = if self.options.should_share_screen {
+                "screen"
+            } else {
+                "camera"
+            };
+            media_sender.set_label(Some(label.to_owned()));
+        }
+        if let Some(media_sender) = media_sender
+            .as_ref()
+            .filter(|_| self.options.should_prioritize_latency)
+        {
+            match media_sender
+                .set_send_priority(RtpSendPriority::latency_critical())
+                .await
+            {
+                Ok(support) if support.is_any_unsupported() => {
+                    log::warn!("send priority is partially unsupported: {:?}", support)
+                }
+                Ok(_) => {}
+                Err(err) => log::warn!("{}", err),
+            }
+        }
+
+        let sender_weak = Arc::downgrade(sender);
+        let media_view = media_stream
+            .map(|media_stream| {
+                MediaView::new(
+                    media_stream.clone(),
+                    MediaViewAudio::Disable,
+                    Box::new(move |media_view, ev| {
+                        let sender_weak = Weak::clone(&sender_weak);
+                        Box::pin(async move {
+                            match (ev, sender_weak.upgrade()) {
+                                (MediaViewEvent::PlaybackStarted, Some(sender)) => {
+                                    Self::send_snapshot(&sender, &media_view).await
+                                }
+                                (ev, _) => log::debug!("{:?}", ev),
+                            }
+                        })
+                    }),
+                )
+                .map_err(|err| anyhow::Error::msg(err.to_string()))
+            })
+            .transpose()?;
+
+        let self_weak = Arc::downgrade(self);
+        let media_permissions = match media.as_ref() {
+            Some(_) => MediaPermissions::query(Box::new(move |_, ev| {
+                let self_weak = Weak::clone(&self_weak);
+                Box::pin(async move {
+                    if let Some(self_arc) = self_weak.upgrade() {
+                        self_arc.on_permissions_event(ev).await
+                    }
+                })
+            }))
+            .await
+            .map_err(|err| log::warn!("{}", err))
+            .ok(),
+            None => None,
+        };
+
+        let self_weak = Arc::downgrade(self);
+        let local_media_watcher =
+            media
+                .as_ref()
+                .filter(|media| media.is_display_media())
+                .map(|media| {
+                    media.watch(Box::new(move |_, ev| {
+                        let self_weak = Weak::clone(&self_weak);
+                        Box::pin(async move {
+                            if let Some(self_arc) = self_weak.upgrade() {
+                                self_arc.on_local_media_event(ev)
+                            }
+                        })
+                    }))
+                });
+
+        Ok(SenderMedia {
+            local_media: media,
+            media_sender,
+            media_view,
+            media_permissions,
+            local_media_watcher,
+        })
+    }
+
+    /// Captures the deferred local media once the sender is started and a receiver joined,
+    /// the added tracks are renegotiated with the receiver.
+    fn capture_deferred_media(self: &Arc<Self>) {
+        use wasm_bindgen_futures::spawn_local;
+
+        if !self.has_receiver_joined.get() {
+            return;
+        }
+        let sender_view = match self.sender() {
+            Some(sender_view) => sender_view,
+            None => return,
+        };
+        if !self.is_capture_deferred.replace(false) {
+            return;
+        }
+
+        let self_arc = Arc::clone(self);
+        spawn_local(async move {
+            let sender = Arc::clone(sender_view.sender());
+            let media = self_arc.add_local_media(&sender).await;
+            sender.set_intended_media(None);
+            self_arc
+                .sender_var
+                .set(Some(media.map(|media| sender_view.with_media(media))));
+        });
+    }
+
+    async fn acquire_local_media(self: &Arc<Self>) -> Option<LocalMedia> {
+        use log::warn;
+
+        if self.options.should_share_screen {
+            return self.acquire_display_media().await;
+        }
+
+        if !self.options.should_use_video && !self.options.should_use_audio {
+            self.model.media_state().set("data only".to_owned());
+            return None;
+        }
+
+        let mut downgrades = Vec::new();
+        let media = self
+            .capture_devices(
+                self.options.should_use_video,
+                self.options.should_use_audio,
+                |downgrade| {
+                    warn!(
+                        "{:?} after capture error: {}",
+                        downgrade.fallback, downgrade.reason
+                    );
+                    downgrades.push(describe_capture_fallback(downgrade.fallback));
+                },
+            )
+            .await;
+
+        let err = match media {
+            Ok(media) => {
+                let has_audio = media.tracks().iter().any(|track| track.kind() == "audio");
+                let state = match (media.video_settings().is_some(), has_audio) {
+                    (true, true) => "video and audio",
+                    (true, false) => "video only",
+                    _ => "audio only",
+                };
+                self.model.media_state().set(if downgrades.is_empty() {
+                    state.to_owned()
+                } else {
+                    format!("{}, {}", state, downgrades.join(", "))
+                });
+                return Some(media);
+            }
+            Err(err) => err,
+        };
+        warn!("{}", err);
+
+        self.model.media_state().set(format!(
+            "data only, {}: {}",
+            Self::local_media_error_summary(&err),
+            err
+        ));
+        None
+    }
+
+    /// Captures the selected camera and microphone,
+    /// relaxing the constraints down to the audio only if the devices can not be captured.
+    async fn capture_devices(
+        &self,
+        should_use_video: bool,
+        should_use_audio: bool,
+        on_downgrade: impl FnMut(CaptureDowngrade),
+    ) -> Result<LocalMedia, LocalMediaError> {
+        use browser_webrtc::DEFAULT_CAPTURE_FALLBACKS;
+
+        let video = TrackConstraints {
+            device_id: self.options.video_device_id.clone(),
+            ..TrackConstraints::default()
+        };
+        let audio = TrackConstraints {
+            device_id: self.options.audio_device_id.clone(),
+            ..TrackConstraints::default()
+        };
+        LocalMedia::with_fallbacks(
+            Some(&video).filter(|_| should_use_video),
+            Some(&audio).filter(|_| should_use_audio),
+            DEFAULT_CAPTURE_FALLBACKS,
+            on_downgrade,
+        )
+        .await
+    }
+
+    async fn acquire_display_media(self: &Arc<Self>) -> Option<LocalMedia> {
+        use log::warn;
+
+        let audio = if self.options.should_use_audio {
+            DisplayMediaAudio::System
+        } else {
+            DisplayMediaAudio::Disable
+        };
+        let media = match LocalMedia::with_display_media(audio).await {
+            Ok(media) => media,
+            Err(err) => {
+                warn!("{}", err);
+                self.model.media_state().set(format!(
+                    "data only, screen sharing failed, {}: {}",
+                    Self::local_media_error_summary(&err),
+                    err
+                ));
+                return None;
+            }
+        };
+
+        let audio_state = match media.display_audio_source() {
+            Some(DisplayAudioSource::Tab) => "with tab audio",
+            Some(DisplayAudioSource::System) => "with system audio",
+            Some(DisplayAudioSource::Window) => "with window audio",
+            Some(DisplayAudioSource::Unknown) => "with audio",
+            None => "without audio",
+        };
+
+        if !self.options.should_mix_microphone {
+            self.model
+                .media_state()
+                .set(format!("screen {}", audio_state));
+            return Some(media);
+        }
+
+        let mixed_media = match self
+            .capture_devices(false, true, |downgrade| warn!("{:?}", downgrade))
+            .await
+        {
+            Ok(microphone) => match media.with_mixed_audio(&microphone) {
+                Ok(mixed_media) => Ok(mixed_media),
+                Err(err) => {
+                    microphone.stop();
+                    Err(err)
+                }
+            },
+            Err(err) => Err(err),
+        };
+        match mixed_media {
+            Ok(mixed_media) => {
+                self.model
+                    .media_state()
+                    .set(format!("screen {} mixed with microphone", audio_state));
+                Some(mixed_media)
+            }
+            Err(err) => {
+                warn!("{}", err);
+                self.model.media_state().set(format!(
+                    "screen {}, microphone is unavailable: {}",
+                    audio_state, err
+                ));
+                Some(media)
+            }
+        }
+    }
+
+    /// Periodically publishes the channel preview until the sender is closed.
+    async fn publish_previews(self_weak: Weak<Self>) {
+        use browser_webrtc::sleep;
+
+        loop {
+            let (sender, caption) = match self_weak.upgrade() {
+                Some(self_arc) => match self_arc.sender() {
+                    Some(sender) => (sender, self_arc.caption_var.get().as_ref().clone()),
+                    None => return,
+                },
+                None => return,
+            };
+            sender.publish_preview(caption).await;
+            drop(sender);
+            sleep(PREVIEW_PUBLISH_INTERVAL).await;
+        }
+    }
+
+    async fn send_snapshot(sender: &Sender, media_view: &MediaView) {
+        use log::warn;
+
+        match media_view
+            .capture_snapshot(SNAPSHOT_MAX_WIDTH, MediaSnapshotFormat::Png)
+            .await
+        {
+            Ok(snapshot) => {
+                if let Err(err) = sender.send_snapshot(&snapshot) {
+                    warn!("{}", err);
+                }
+            }
+            Err(MediaSnapshotError::NoVideoFrame) => {}
+            Err(err) => warn!("{}", err),
+        }
+    }
+
+    /// Marks the local peer as typing until the caption is not edited for a while.
+    fn on_caption_input(self: &Arc<Self>) {
+        use browser_webrtc::sleep;
+        use wasm_bindgen_futures::spawn_local;
+
+        let generation = self.typing_generation.get().wrapping_add(1);
+        self.typing_generation.set(generation);
+        self.is_typing_var.set(true);
+
+        let self_weak = Arc::downgrade(self);
+        spawn_local(async move {
+            sleep(TYPING_TIMEOUT).await;
+            if let Some(self_arc) = self_weak.upgrade() {
+                if self_arc.typing_generation.get() == generation {
+                    self_arc.is_typing_var.set(false);
+                }
+            }
+        });
+    }
+
+    fn update_peer_state(self: &Arc<Self>) {
+        let state = PeerState {
+            is_typing: *self.is_typing_var.get(),
+            is_away: *self.is_away_var.get(),
+            is_muted: !self.options.should_use_audio,
+            is_screen_sharing: self.options.should_share_screen,
+        };
+        if let Some(sender) = self.sender() {
+            sender.set_peer_state(state);
+        }
+    }
+
+    fn local_media_error_summary(err: &LocalMediaError) -> &'static str {
+        match err {
+            LocalMediaError::PermissionDenied(_) => "permission denied",
+            LocalMediaError::NotFound(_) => "no device found",
+            LocalMediaError::NotReadable(_) => "device is busy",
+            LocalMediaError::Overconstrained(_) => "device is unsuitable",
+            _ => "media is unavailable",
+        }
+    }
+
+    fn sender(self: &Arc<Self>) -> Option<Arc<SenderView>> {
+        self.sender_var
+            .get()
+            .as_ref()
+            .as_ref()
+            .and_then(|sender| sender.as_ref().ok())
+            .cloned()
+    }
+
+    fn on_local_media_event(&self, ev: LocalMediaEvent) {
         match ev {
-            SenderEvent::Error(err) => error!("{}", err),
-            SenderEvent::IceConnectionStateChange(value) => {
-                self.ice_connection_state_var.set(format!("{:?}", value))
+            LocalMediaEvent::DisplayMediaStopped => self
+                .model
+                .media_state()
+                .set("screen sharing was stopped".to_owned()),
+            LocalMediaEvent::TrackEnded(track) => {
+                log::warn!("shared {} track ended", track.kind())
             }
-            SenderEvent::IceGatheringStateChange(value) => {
-                self.ice_gathering_state_var.set(format!("{:?}", value))
+        }
+    }
+
+    async fn on_permissions_event(self: &Arc<Self>, ev: MediaPermissionsEvent) {
+        use log::warn;
+        use web_sys::PermissionState;
+
+        match ev {
+            MediaPermissionsEvent::StateChange {
+                kind,
+                state: PermissionState::Denied,
+            } => {
+                warn!("{} permission was revoked", kind.name());
+                if let Some(sender) = self.sender() {
+                    sender.stop_media(kind);
+                }
+                self.model.media_state().set(
+                    match kind {
+                        MediaPermissionKind::Camera => {
+                            "video stopped, camera permission was revoked"
+                        }
+                        MediaPermissionKind::Microphone => {
+                            "audio stopped, microphone permission was revoked"
+                        }
+                    }
+                    .to_owned(),
+                );
             }
-            SenderEvent::RtcSignalingStateChange(value) => {
-                self.signaling_state_var.set(format!("{:?}", value))
+            MediaPermissionsEvent::StateChange { .. } => {}
+        }
+    }
+
+    async fn on_media_sender_event(self: &Arc<Self>, ev: MediaSenderEvent) {
+        use log::warn;
+
+        match ev {
+            MediaSenderEvent::Error(err) => warn!("{}", err),
+            MediaSenderEvent::TrackEnded(track) => {
+                let kind = track.kind();
+                warn!("{} track ended: {}", kind, track.label());
+                // Reported by the local media watcher.
+                if kind == "video" && self.options.should_share_screen {
+                    return;
+                }
+                if !*self.should_reacquire_devices_var.get() {
+                    self.model
+                        .media_state()
+                        .set(format!("{} stopped, device was disconnected", kind));
+                    return;
+                }
+
+                let on_downgrade = |downgrade: CaptureDowngrade| warn!("{:?}", downgrade);
+                let media = match kind.as_str() {
+                    "video" => self.capture_devices(true, false, on_downgrade).await,
+                    "audio" => self.capture_devices(false, true, on_downgrade).await,
+                    _ => return,
+                };
+                match (media, self.sender()) {
+                    (Ok(media), Some(sender)) => {
+                        for track in media.tracks() {
+                            sender.add_media_track(track);
+                        }
+                        self.model
+                            .media_state()
+                            .set(format!("{} device was replaced", kind));
+                    }
+                    (Ok(media), None) => media.stop(),
+                    (Err(err), _) => {
+                        warn!("{}", err);
+                        self.model.media_state().set(format!(
+                            "{} stopped, device was disconnected, {}: {}",
+                            kind,
+                            Self::local_media_error_summary(&err),
+                            err
+                        ));
+                    }
+                }
             }
-            ev => debug!("Sender event {:?}", ev),
         }
     }
 
+    async fn on_event(self: &Arc<Self>, ev: SenderEvent) {
+        if let SenderEvent::ReceiverJoined(_) = &ev {
+            use crate::notify;
+            use browser_webrtc::NotifierEvent;
+
+            self.has_receiver_joined.set(true);
+            self.capture_deferred_media();
+
+            notify(NotifierEvent::ReceiverJoined {
+                channel: self.channel_id.0.clone(),
+            });
+        }
+        self.events.publish(ev);
+    }
+
     pub async fn on_datachannel_event(self: &Arc<Self>, ev: DataSenderEvent) {
         use log::{debug, error};
         match ev {
@@ -173,97 +789,237 @@ impl SenderBuilderView {
         }
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
         let sender_var = self.sender_var.clone();
-        let ice_connection_state_var = self.ice_connection_state_var.clone();
-        let ice_gathering_state_var = self.ice_gathering_state_var.clone();
-        let signaling_state_var = self.signaling_state_var.clone();
+        let model = create_ref(cx, Arc::clone(&self.model));
+        let ice_connection_state_var = bind_signal(cx, model.ice_connection_state());
+        let ice_gathering_state_var = bind_signal(cx, model.ice_gathering_state());
+        let signaling_state_var = bind_signal(cx, model.signaling_state());
+        let media_state_var = bind_signal(cx, model.media_state());
+        let should_reacquire_devices_var: &Signal<bool> =
+            create_ref(cx, self.should_reacquire_devices_var.clone());
+        let caption_var: &Signal<String> = create_ref(cx, self.caption_var.clone());
+        let is_away_var: &Signal<bool> = create_ref(cx, self.is_away_var.clone());
+        let receiver_presence_var = bind_signal(cx, model.receiver_presence());
+        let peer_state_var = bind_signal(cx, model.peer_state());
+        let fingerprints_var = bind_signal(cx, model.fingerprints());
+        let verified_peer_var = bind_signal(cx, model.verified_peer());
+        let quality_var = bind_signal(cx, model.quality());
+        let usage_var = bind_signal(cx, model.usage());
+        let milestones_var = {
+            let milestones_var = create_rc_signal(Vec::new());
+            let subscription = self.events.subscribe(describe_milestone, {
+                let milestones_var = milestones_var.clone();
+                move |milestone| milestones_var.modify().push(milestone)
+            });
+            let events = Arc::clone(&self.events);
+            on_cleanup(cx, move || events.unsubscribe(subscription));
+            create_ref(cx, milestones_var)
+        };
+
+        create_effect(cx, {
+            let self_weak = Arc::downgrade(self);
+            move || {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    self_arc.update_peer_state()
+                }
+            }
+        });
+        let on_caption_input = {
+            let self_weak = Arc::downgrade(self);
+            move |_| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    self_arc.on_caption_input()
+                }
+            }
+        };
 
-        let channel_id = self.channel_id.clone();
-        let network_mode = self.network_mode;
-        let should_use_video = self.should_use_video;
-        let should_use_audio = self.should_use_audio;
-        let should_use_data_channel = self.should_use_data_channel;
+        let channel_id = self.channel_id.0.clone();
+        let network_mode = format!("{:?}", self.options.network_mode);
+        let should_use_video = match (
+            self.options.should_share_screen,
+            self.options.should_use_video,
+        ) {
+            (true, _) => "screen",
+            (false, true) => "yes",
+            (false, false) => "no",
+        };
+        let should_use_audio = match (
+            self.options.should_use_audio,
+            self.options.should_use_hq_audio,
+        ) {
+            (true, true) => "yes, high quality",
+            (true, false) => "yes",
+            (false, _) => "no",
+        };
+        let should_use_data_channel = if self.options.should_use_data_channel {
+            "yes"
+        } else {
+            "no"
+        };
 
         let on_close_click = {
-            let self_arc = Arc::clone(self);
-            move |_| self_arc.senders.remove_sender(&self_arc)
+            let self_weak = Arc::downgrade(self);
+            move |_| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    if let Some(senders) = self_arc.senders.upgrade() {
+                        senders.remove_sender(&self_arc)
+                    }
+                }
+            }
         };
 
-        template! {
+        let sender_view = View::new_dyn_scoped(cx, move |cx| match sender_var.get().as_ref() {
+            Some(Ok(sender)) => sender.view(cx),
+            Some(Err(err)) => {
+                let err = err.to_string();
+                view! { cx,
+                    h2 {
+                        "error"
+                    }
+                    textarea(class = "error", readonly = true) {
+                        (err)
+                    }
+                }
+            }
+            None => view! { cx,
+                h2(class = "loading") {
+                    "loading..."
+                }
+            },
+        });
+
+        view! { cx,
             div(class = "component") {
-                h1() {
-                    ("Sender")
+                h1 {
+                    "Sender"
                 }
                 button(on:click = on_close_click, class = "close") {
-                    ("close")
+                    "close"
+                }
+                div(class = "monospace") {
+                    "channel id: "
+                    (channel_id)
                 }
                 div(class = "monospace") {
-                    ("channel id: ")
-                    (channel_id.0)
+                    "network mode: "
+                    (network_mode)
                 }
                 div(class = "monospace") {
-                    ("network mode: ")
-                    (format!("{:?}", network_mode))
+                    "video: "
+                    (should_use_video)
                 }
                 div(class = "monospace") {
-                    ("video: ")
-                    (if should_use_video { "yes" } else { "no" })
+                    "audio: "
+                    (should_use_audio)
                 }
                 div(class = "monospace") {
-                    ("audio: ")
-                    (if should_use_audio { "yes" } else { "no" })
+                    "channel-data: "
+                    (should_use_data_channel)
                 }
                 div(class = "monospace") {
-                    ("channel-data: ")
-                    (if should_use_data_channel { "yes" } else { "no" })
+                    "media: "
+                    (media_state_var.get())
+                }
+                div {
+                    label {
+                        input(type = "checkbox", bind:checked = should_reacquire_devices_var)
+                        "Reacquire disconnected devices"
+                    }
+                }
+                div {
+                    label {
+                        "Preview caption: "
+                        input(type = "text", bind:value = caption_var, on:input = on_caption_input)
+                    }
+                }
+                div {
+                    label {
+                        input(type = "checkbox", bind:checked = is_away_var)
+                        "Away"
+                    }
                 }
                 div(class = "monospace") {
-                    ("ice_connection_state: ")
+                    "milestones: "
+                    (milestones_var.get().join(", "))
+                }
+                div(class = "monospace") {
+                    "receiver presence: "
+                    (receiver_presence_var.get())
+                }
+                div(class = "monospace") {
+                    "receiver: "
+                    (peer_state_var.get())
+                }
+                div(class = "monospace", title = describe_quality_details(*quality_var.get())) {
+                    "quality: "
+                    (describe_quality(*quality_var.get()))
+                }
+                div(class = "monospace", title = describe_usage_details(*usage_var.get())) {
+                    "usage: "
+                    (describe_usage(*usage_var.get()))
+                }
+                div(class = "monospace") {
+                    "key fingerprints (local / receiver): "
+                    (fingerprints_var.get())
+                }
+                div(class = "monospace") {
+                    "verified receiver identity: "
+                    (verified_peer_var.get())
+                }
+                div(class = "monospace") {
+                    "ice_connection_state: "
                     (ice_connection_state_var.get())
                 }
                 div(class = "monospace") {
-                    ("ice_gathering_state: ")
+                    "ice_gathering_state: "
                     (ice_gathering_state_var.get())
                 }
                 div(class = "monospace") {
-                    ("signaling_state: ")
+                    "signaling_state: "
                     (signaling_state_var.get())
                 }
-                ({
-                    let sender = sender_var.get();
-
-                    match sender.as_ref() {
-                        Some(Ok(sender)) => {
-                            sender.view()
-                        },
-                        Some(Err(err)) => {
-                            let err = err.to_string();
-                            template! {
-                                h2() {
-                                    ("error")
-                                }
-                                textarea(class = "error", readonly = true) {
-                                    (err)
-                                }
-                            }
-                        },
-                        None => {
-                            template! {
-                                h2(class = "loading") {
-                                    ("loading...")
-                                }
-                            }
-                        }
-                    }
-                })
+                (sender_view)
             }
         }
     }
 }
 
+impl PartialEq for SenderBuilderView {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self, other)
+    }
+}
+
+impl Eq for SenderBuilderView {}
+
 impl Drop for SenderBuilderView {
     fn drop(&mut self) {
         log::debug!("client::SenderBuilderView::drop");
     }
 }
+
+/// Describes the sender lifecycle events, the early ones are replayed to new views.
+fn describe_capture_fallback(fallback: CaptureFallback) -> String {
+    match fallback {
+        CaptureFallback::DropExactConstraints => "selected devices are unavailable".to_owned(),
+        CaptureFallback::LowerResolution { width, height } => {
+            format!("resolution lowered to {}x{}", width, height)
+        }
+        CaptureFallback::AudioOnly => "video is unavailable".to_owned(),
+    }
+}
+
+fn describe_milestone(ev: &SenderEvent) -> Option<String> {
+    match ev {
+        SenderEvent::OpenChannelSuccess => Some("opened".to_owned()),
+        SenderEvent::ReceiverJoined(receiver_info) => {
+            Some(format!("receiver #{} joined", receiver_info.connection_id))
+        }
+        SenderEvent::VerifiedPeer(_) => Some("receiver verified".to_owned()),
+        SenderEvent::SessionEnded(reason) => Some(format!("ended: {:?}", reason)),
+        SenderEvent::ReceiverLeft(reason) => Some(format!("receiver left: {:?}", reason)),
+        SenderEvent::Closed { reason, .. } => Some(format!("closed: {:?}", reason)),
+        _ => None,
+    }
+}
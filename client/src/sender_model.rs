@@ -0,0 +1,189 @@
+use async_std::sync::Arc;
+use browser_webrtc::signaling_protocol::ChannelId;
+use browser_webrtc::{ConnectionQuality, Sender, SenderEvent, SessionUsage};
+
+use crate::{describe_peer_state, ui_language, CallDirection, CallTracker, Observable};
+
+/// Connection state of a sender as shown to the user.
+#[derive(Debug)]
+pub struct SenderModel {
+    ice_connection_state: Observable<String>,
+    ice_gathering_state: Observable<String>,
+    signaling_state: Observable<String>,
+    media_state: Observable<String>,
+    receiver_presence: Observable<String>,
+    peer_state: Observable<String>,
+    fingerprints: Observable<String>,
+    verified_peer: Observable<String>,
+    quality: Observable<Option<ConnectionQuality>>,
+    usage: Observable<SessionUsage>,
+    /// Each connected receiver is recorded as a separate call.
+    call: CallTracker,
+}
+
+impl SenderModel {
+    pub fn new(channel_id: ChannelId) -> Arc<Self> {
+        Arc::new(Self {
+            ice_connection_state: Observable::new(String::new()),
+            ice_gathering_state: Observable::new(String::new()),
+            signaling_state: Observable::new(String::new()),
+            media_state: Observable::new(String::new()),
+            receiver_presence: Observable::new("none".to_owned()),
+            peer_state: Observable::new("unknown".to_owned()),
+            fingerprints: Observable::new("not sealed".to_owned()),
+            verified_peer: Observable::new("not verified".to_owned()),
+            quality: Observable::new(None),
+            usage: Observable::new(SessionUsage::default()),
+            call: CallTracker::new(channel_id, CallDirection::Outgoing),
+        })
+    }
+
+    pub fn ice_connection_state(&self) -> &Observable<String> {
+        &self.ice_connection_state
+    }
+
+    pub fn ice_gathering_state(&self) -> &Observable<String> {
+        &self.ice_gathering_state
+    }
+
+    pub fn signaling_state(&self) -> &Observable<String> {
+        &self.signaling_state
+    }
+
+    /// Description of the captured local media and its failures.
+    pub fn media_state(&self) -> &Observable<String> {
+        &self.media_state
+    }
+
+    pub fn receiver_presence(&self) -> &Observable<String> {
+        &self.receiver_presence
+    }
+
+    pub fn peer_state(&self) -> &Observable<String> {
+        &self.peer_state
+    }
+
+    pub fn fingerprints(&self) -> &Observable<String> {
+        &self.fingerprints
+    }
+
+    pub fn verified_peer(&self) -> &Observable<String> {
+        &self.verified_peer
+    }
+
+    pub fn quality(&self) -> &Observable<Option<ConnectionQuality>> {
+        &self.quality
+    }
+
+    pub fn usage(&self) -> &Observable<SessionUsage> {
+        &self.usage
+    }
+
+    /// Reads the initial connection states of the started sender.
+    pub fn on_started(&self, sender: &Sender) {
+        self.ice_connection_state
+            .set(format!("{:?}", sender.ice_connection_state()));
+        self.ice_gathering_state
+            .set(format!("{:?}", sender.ice_gathering_state()));
+        self.signaling_state
+            .set(format!("{:?}", sender.signaling_state()));
+    }
+
+    pub fn on_event(&self, ev: &SenderEvent) {
+        use browser_webrtc::signaling_protocol::CatalogedError;
+        use log::{debug, error, info, warn};
+
+        self.on_call_event(ev);
+        match ev {
+            SenderEvent::Error(err) => error!("{}", err),
+            SenderEvent::Closed {
+                reason,
+                diagnostics: Some(diagnostics),
+            } => error!("Sender closed: {:?}, {:?}", reason, diagnostics),
+            SenderEvent::Closed {
+                reason,
+                diagnostics: None,
+            } => info!("Sender closed: {:?}", reason),
+            SenderEvent::SessionEnding { reason, remaining } => {
+                info!("Sender session ends in {:?}: {:?}", remaining, reason)
+            }
+            SenderEvent::SessionEnded(reason) => info!("Sender session ended: {:?}", reason),
+            SenderEvent::IceConnectionStateChange(value) => {
+                use web_sys::RtcIceConnectionState;
+
+                if *value == RtcIceConnectionState::Connected {
+                    self.receiver_presence.set("connected".to_owned());
+                }
+                self.ice_connection_state.set(format!("{:?}", value))
+            }
+            SenderEvent::ReceiverJoined(receiver_info) => self
+                .receiver_presence
+                .set(format!("#{} connecting…", receiver_info.connection_id)),
+            SenderEvent::ReceiverLeft(reason) => {
+                self.receiver_presence.set(format!("left: {:?}", reason))
+            }
+            SenderEvent::IceGatheringStateChange(value) => {
+                self.ice_gathering_state.set(format!("{:?}", value))
+            }
+            SenderEvent::RtcSignalingStateChange(value) => {
+                self.signaling_state.set(format!("{:?}", value))
+            }
+            SenderEvent::NetworkChanged(change) => info!("Sender network changed: {:?}", change),
+            SenderEvent::VisibilityChanged(visibility) => {
+                info!("Sender page visibility changed: {:?}", visibility)
+            }
+            SenderEvent::PeerStateChanged(state) => {
+                self.peer_state.set(describe_peer_state(*state))
+            }
+            SenderEvent::PeerKeyReceived {
+                fingerprint,
+                local_fingerprint,
+            } => self
+                .fingerprints
+                .set(format!("{} / {}", local_fingerprint, fingerprint)),
+            SenderEvent::VerifiedPeer(peer) => self.verified_peer.set(peer.fingerprint.clone()),
+            SenderEvent::QualityChanged(quality) => self.quality.set(Some(*quality)),
+            SenderEvent::UsageUpdated(usage) => self.usage.set(*usage),
+            SenderEvent::AudioOnlyFallback(reason) => self
+                .media_state
+                .set(format!("audio only, video is stopped: {:?}", reason)),
+            SenderEvent::VideoRestored => self.media_state.set("video is restored".to_owned()),
+            SenderEvent::Throttled(notice) => warn!("Sender relay throttled: {:?}", notice),
+            SenderEvent::BinaryDataEcho(data) => {
+                info!("Sender data echoed: {}", String::from_utf8_lossy(data))
+            }
+            SenderEvent::ChannelRenamed(channel_id) => {
+                info!("Sender channel renamed: {:?}", channel_id)
+            }
+            SenderEvent::RenameChannelFailed(err) => warn!(
+                "Sender channel rename failed: {}",
+                err.describe(ui_language())
+            ),
+            SenderEvent::RecordingStopped { size } => {
+                info!("Sender recording stored on the server: {} bytes", size)
+            }
+            SenderEvent::RecordingFailed(err) => {
+                warn!("Sender recording failed: {}", err.describe(ui_language()))
+            }
+            ev => debug!("Sender event {:?}", ev),
+        }
+    }
+
+    fn on_call_event(&self, ev: &SenderEvent) {
+        use web_sys::RtcIceConnectionState;
+
+        match ev {
+            SenderEvent::IceConnectionStateChange(RtcIceConnectionState::Connected) => {
+                self.call.start()
+            }
+            SenderEvent::VerifiedPeer(peer) => self.call.set_peer(peer.fingerprint.clone()),
+            SenderEvent::QualityChanged(quality) => self.call.set_quality_score(quality.score),
+            SenderEvent::ChannelRenamed(channel_id) => self.call.set_channel_id(channel_id.clone()),
+            SenderEvent::ReceiverLeft(reason) | SenderEvent::Closed { reason, .. } => {
+                self.call.finish(format!("{:?}", reason))
+            }
+            SenderEvent::SessionEnded(reason) => self.call.finish(format!("{:?}", reason)),
+            _ => {}
+        }
+    }
+}
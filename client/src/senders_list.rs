@@ -1,45 +1,177 @@
 use core::cell::RefCell;
 
 use async_std::sync::{Arc, Weak};
-use browser_webrtc::signaling_protocol::{ChannelId, NetworkMode};
-use browser_webrtc::Server;
+use browser_webrtc::signaling_protocol::{ChannelId, ChannelSecret, NetworkMode};
+use browser_webrtc::{
+    Capabilities, ChannelAccess, ChannelReservation, MediaPermissionKind, MediaPermissions,
+    MediaPermissionsEvent, Server, WarmConnection,
+};
 use sycamore::prelude::*;
+use web_sys::PermissionState;
 
-use crate::SenderBuilderView;
+use crate::{DeviceTestView, SenderBuilderView, SenderOptions};
 
 const DEFAULT_NETWORK_MODE: NetworkMode = NetworkMode::PeerToPeer;
 
 #[derive(Debug)]
 pub struct SendersListView {
     server: Weak<Server>,
-    channel_name_var: Signal<ChannelId>,
-    network_mode_var: Signal<NetworkMode>,
-    should_use_video_var: Signal<bool>,
-    should_use_audio_var: Signal<bool>,
-    should_use_data_channel_var: Signal<bool>,
-    senders_var: Signal<RefCell<Vec<Arc<SenderBuilderView>>>>,
+    channel_name_var: RcSignal<String>,
+    /// Channel password, the channel is open to any receiver if it is empty.
+    channel_password_var: RcSignal<String>,
+    network_mode_var: RcSignal<NetworkMode>,
+    should_use_video_var: RcSignal<bool>,
+    should_use_audio_var: RcSignal<bool>,
+    should_use_hq_audio_var: RcSignal<bool>,
+    should_share_screen_var: RcSignal<bool>,
+    should_mix_microphone_var: RcSignal<bool>,
+    should_use_data_channel_var: RcSignal<bool>,
+    should_prioritize_latency_var: RcSignal<bool>,
+    should_seal_signaling_var: RcSignal<bool>,
+    should_verify_peer_var: RcSignal<bool>,
+    should_report_summary_var: RcSignal<bool>,
+    should_throttle_in_background_var: RcSignal<bool>,
+    should_fallback_to_audio_var: RcSignal<bool>,
+    should_defer_capture_var: RcSignal<bool>,
+    should_limit_session_var: RcSignal<bool>,
+    should_schedule_start_var: RcSignal<bool>,
+    reservation_var: RcSignal<Option<ChannelReservation>>,
+    reservation_state_var: RcSignal<String>,
+    device_test: Arc<DeviceTestView>,
+    senders_var: RcSignal<Vec<Arc<SenderBuilderView>>>,
+    camera_permission_var: RcSignal<Option<PermissionState>>,
+    microphone_permission_var: RcSignal<Option<PermissionState>>,
+    media_permissions: RefCell<Option<Arc<MediaPermissions>>>,
+    /// Connection warmed up for the next sender, so that its offer is ready in advance.
+    warm_connection: RefCell<Option<WarmConnection>>,
+    capabilities: Capabilities,
 }
 
 impl SendersListView {
     pub fn new(server: Arc<Server>) -> Arc<Self> {
+        use wasm_bindgen_futures::spawn_local;
+
         log::trace!("client::SendersListView::new");
 
-        let channel_name_var = Signal::new(ChannelId(Self::rand_channel_name()));
-        let network_mode_var = Signal::new(DEFAULT_NETWORK_MODE);
-        let senders_var = Signal::new(RefCell::new(Vec::new()));
-        let should_use_video_var = Signal::new(true);
-        let should_use_audio_var = Signal::new(true);
-        let should_use_data_channel_var = Signal::new(true);
+        let channel_name_var = create_rc_signal(Self::rand_channel_name());
+        let channel_password_var = create_rc_signal(String::new());
+        let network_mode_var = create_rc_signal(DEFAULT_NETWORK_MODE);
+        let senders_var = create_rc_signal(Vec::new());
+        let should_use_video_var = create_rc_signal(true);
+        let should_use_audio_var = create_rc_signal(true);
+        let should_use_hq_audio_var = create_rc_signal(false);
+        let should_share_screen_var = create_rc_signal(false);
+        let should_mix_microphone_var = create_rc_signal(false);
+        let should_use_data_channel_var = create_rc_signal(true);
+        let should_prioritize_latency_var = create_rc_signal(false);
+        let should_seal_signaling_var = create_rc_signal(false);
+        let should_verify_peer_var = create_rc_signal(false);
+        let should_report_summary_var = create_rc_signal(false);
+        let should_throttle_in_background_var = create_rc_signal(true);
+        let should_fallback_to_audio_var = create_rc_signal(true);
+        let should_defer_capture_var = create_rc_signal(false);
+        let should_limit_session_var = create_rc_signal(false);
+        let should_schedule_start_var = create_rc_signal(false);
 
-        Arc::new(Self {
+        let senders = Arc::new(Self {
             server: Arc::downgrade(&server),
             channel_name_var,
+            channel_password_var,
             network_mode_var,
             senders_var,
             should_use_video_var,
             should_use_audio_var,
+            should_use_hq_audio_var,
+            should_share_screen_var,
+            should_mix_microphone_var,
             should_use_data_channel_var,
-        })
+            should_prioritize_latency_var,
+            should_seal_signaling_var,
+            should_verify_peer_var,
+            should_report_summary_var,
+            should_throttle_in_background_var,
+            should_fallback_to_audio_var,
+            should_defer_capture_var,
+            should_limit_session_var,
+            should_schedule_start_var,
+            reservation_var: create_rc_signal(None),
+            reservation_state_var: create_rc_signal("none".to_owned()),
+            device_test: DeviceTestView::new(),
+            camera_permission_var: create_rc_signal(None),
+            microphone_permission_var: create_rc_signal(None),
+            media_permissions: RefCell::new(None),
+            warm_connection: RefCell::new(None),
+            capabilities: browser_webrtc::capabilities(),
+        });
+
+        spawn_local({
+            let senders = Arc::clone(&senders);
+            async move { senders.init_media_permissions().await }
+        });
+        senders.warm_up_connection();
+
+        senders
+    }
+
+    /// Prepares the connection of the next sender in the background.
+    fn warm_up_connection(self: &Arc<Self>) {
+        use browser_webrtc::{default_rtc_configuration, RtcConfigurationExt};
+        use wasm_bindgen_futures::spawn_local;
+
+        let self_weak = Arc::downgrade(self);
+        spawn_local(async move {
+            let rtc_configuration = default_rtc_configuration().with_google_stun_server();
+            match WarmConnection::warmed_up(Some(rtc_configuration)).await {
+                Ok(connection) => {
+                    if let Some(self_arc) = self_weak.upgrade() {
+                        let _: Option<_> = self_arc.warm_connection.replace(Some(connection));
+                    }
+                }
+                Err(err) => log::warn!("connection warm-up failed: {}", err),
+            }
+        });
+    }
+
+    async fn init_media_permissions(self: Arc<Self>) {
+        let self_weak = Arc::downgrade(&self);
+        let media_permissions = MediaPermissions::query(Box::new(move |_, ev| {
+            let self_weak = Weak::clone(&self_weak);
+            Box::pin(async move {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    self_arc.on_permissions_event(ev).await
+                }
+            })
+        }))
+        .await;
+
+        match media_permissions {
+            Ok(media_permissions) => {
+                self.camera_permission_var
+                    .set(media_permissions.state(MediaPermissionKind::Camera));
+                self.microphone_permission_var
+                    .set(media_permissions.state(MediaPermissionKind::Microphone));
+                let _: Option<_> = self.media_permissions.replace(Some(media_permissions));
+            }
+            Err(err) => log::warn!("{}", err),
+        }
+    }
+
+    async fn on_permissions_event(self: &Arc<Self>, ev: MediaPermissionsEvent) {
+        match ev {
+            MediaPermissionsEvent::StateChange { kind, state } => match kind {
+                MediaPermissionKind::Camera => self.camera_permission_var.set(Some(state)),
+                MediaPermissionKind::Microphone => self.microphone_permission_var.set(Some(state)),
+            },
+        }
+    }
+
+    fn permission_state_text(state: Option<PermissionState>) -> &'static str {
+        match state {
+            Some(PermissionState::Granted) => "granted",
+            Some(PermissionState::Prompt) => "permission needed, the browser will ask for it",
+            Some(PermissionState::Denied) => "permission denied, allow it in the browser settings",
+            _ => "unknown",
+        }
     }
 
     pub fn rand_channel_name() -> String {
@@ -49,139 +181,311 @@ impl SendersListView {
         channel_name.to_owned()
     }
 
+    /// Reserves the entered channel name, it is claimed by the next opened channel with this name.
+    pub async fn reserve_channel(self: Arc<Self>) {
+        let server = match self.server.upgrade() {
+            Some(server) => server,
+            None => return,
+        };
+        let channel_id = ChannelId(self.channel_name_var.get().as_ref().clone());
+        self.reservation_state_var
+            .set(format!("reserving {}", channel_id.0));
+        match server.reserve_channel(channel_id).await {
+            Ok(reservation) => {
+                self.reservation_state_var.set(format!(
+                    "{} reserved for {} s",
+                    reservation.channel_id.0,
+                    reservation.valid_for.as_secs()
+                ));
+                self.reservation_var.set(Some(reservation));
+            }
+            Err(err) => self.reservation_state_var.set(err.to_string()),
+        }
+    }
+
     pub fn add_sender(self: &Arc<Self>) {
-        use crate::SignalVecPush;
+        let server = match self.server.upgrade() {
+            Some(server) => server,
+            None => return,
+        };
+        let channel_id = ChannelId(self.channel_name_var.get().as_ref().clone());
+        let claim_token = match self.reservation_var.get().as_ref() {
+            Some(reservation) if reservation.channel_id == channel_id => {
+                Some(reservation.claim_token.clone())
+            }
+            _ => None,
+        };
+        if claim_token.is_some() {
+            self.reservation_var.set(None);
+            self.reservation_state_var.set("claimed".to_owned());
+        }
+        let password = self.channel_password_var.get();
+        let secret = if password.is_empty() {
+            None
+        } else {
+            Some(ChannelSecret(password.as_ref().clone()))
+        };
+        let warm_connection = self.warm_connection.take();
         let sender = SenderBuilderView::new(
             Arc::clone(self),
-            self.server.upgrade().unwrap(),
-            self.channel_name_var.get().as_ref().clone(),
-            *self.network_mode_var.get().as_ref(),
-            *self.should_use_video_var.get().as_ref(),
-            *self.should_use_audio_var.get().as_ref(),
-            *self.should_use_data_channel_var.get().as_ref(),
+            server,
+            channel_id,
+            warm_connection,
+            SenderOptions {
+                network_mode: *self.network_mode_var.get(),
+                should_use_video: *self.should_use_video_var.get(),
+                should_use_audio: *self.should_use_audio_var.get(),
+                should_use_hq_audio: *self.should_use_hq_audio_var.get(),
+                should_share_screen: *self.should_share_screen_var.get(),
+                should_mix_microphone: *self.should_mix_microphone_var.get(),
+                should_use_data_channel: *self.should_use_data_channel_var.get(),
+                should_prioritize_latency: *self.should_prioritize_latency_var.get(),
+                should_seal_signaling: *self.should_seal_signaling_var.get(),
+                should_verify_peer: *self.should_verify_peer_var.get(),
+                should_report_summary: *self.should_report_summary_var.get(),
+                should_throttle_in_background: *self.should_throttle_in_background_var.get(),
+                should_fallback_to_audio: *self.should_fallback_to_audio_var.get(),
+                should_defer_capture: *self.should_defer_capture_var.get(),
+                should_limit_session: *self.should_limit_session_var.get(),
+                should_schedule_start: *self.should_schedule_start_var.get(),
+                video_device_id: self.device_test.camera_id(),
+                audio_device_id: self.device_test.microphone_id(),
+                access: ChannelAccess {
+                    claim_token,
+                    secret,
+                },
+            },
         );
-        self.senders_var.push(sender);
-        self.channel_name_var
-            .set(ChannelId(Self::rand_channel_name()));
+        // The tested devices are released before the sender captures them.
+        self.device_test.stop();
+        self.senders_var.modify().push(sender);
+        self.channel_name_var.set(Self::rand_channel_name());
+        self.warm_up_connection();
     }
 
-    pub fn remove_sender(self: &Arc<Self>, sender: &Arc<SenderBuilderView>) {
-        use crate::SignalVecRemoveByPtrEq;
-        self.senders_var.remove_by_ptr_eq(sender);
+    /// Opens the channel with the current options, e.g. again from the call history.
+    pub fn reopen_channel(self: &Arc<Self>, channel_id: ChannelId) {
+        self.channel_name_var.set(channel_id.0);
+        self.add_sender();
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
-        use wasm_bindgen::JsCast;
-        use web_sys::{Event, HtmlInputElement};
+    pub fn remove_sender(self: &Arc<Self>, sender: &Arc<SenderBuilderView>) {
+        self.senders_var
+            .modify()
+            .retain(|other| !Arc::ptr_eq(sender, other));
+    }
 
+    pub fn view<'a>(self: &Arc<Self>, cx: Scope<'a>) -> View<DomNode> {
         let on_add_sender_click = {
             let self_arc = Arc::clone(self);
             move |_| self_arc.add_sender()
         };
+        let on_reserve_channel_click = {
+            use wasm_bindgen_futures::spawn_local;
 
-        let on_channel_name_change = {
             let self_arc = Arc::clone(self);
-            move |ev: Event| {
-                let target: HtmlInputElement = ev.target().unwrap().dyn_into().unwrap();
-                self_arc.channel_name_var.set(ChannelId(target.value()))
-            }
+            move |_| spawn_local(Arc::clone(&self_arc).reserve_channel())
         };
 
-        let self_arc = Arc::clone(self);
-        let channel_name_var = self.channel_name_var.clone();
-        let network_mode_var = self.network_mode_var.clone();
-        let should_use_video_var = self.should_use_video_var.clone();
-        let should_use_audio_var = self.should_use_audio_var.clone();
-        let should_use_data_channel_var = self.should_use_data_channel_var.clone();
+        let channel_name_var: &'a Signal<String> = create_ref(cx, self.channel_name_var.clone());
+        let channel_password_var: &'a Signal<String> =
+            create_ref(cx, self.channel_password_var.clone());
+        let network_mode_var: &'a Signal<NetworkMode> =
+            create_ref(cx, self.network_mode_var.clone());
+        let should_use_video_var: &'a Signal<bool> =
+            create_ref(cx, self.should_use_video_var.clone());
+        let should_use_audio_var: &'a Signal<bool> =
+            create_ref(cx, self.should_use_audio_var.clone());
+        let should_use_hq_audio_var: &'a Signal<bool> =
+            create_ref(cx, self.should_use_hq_audio_var.clone());
+        let should_share_screen_var: &'a Signal<bool> =
+            create_ref(cx, self.should_share_screen_var.clone());
+        let should_mix_microphone_var: &'a Signal<bool> =
+            create_ref(cx, self.should_mix_microphone_var.clone());
+        let should_use_data_channel_var: &'a Signal<bool> =
+            create_ref(cx, self.should_use_data_channel_var.clone());
+        let should_prioritize_latency_var: &'a Signal<bool> =
+            create_ref(cx, self.should_prioritize_latency_var.clone());
+        let should_seal_signaling_var: &'a Signal<bool> =
+            create_ref(cx, self.should_seal_signaling_var.clone());
+        let should_verify_peer_var: &'a Signal<bool> =
+            create_ref(cx, self.should_verify_peer_var.clone());
+        let should_report_summary_var: &'a Signal<bool> =
+            create_ref(cx, self.should_report_summary_var.clone());
+        let should_throttle_in_background_var: &'a Signal<bool> =
+            create_ref(cx, self.should_throttle_in_background_var.clone());
+        let should_fallback_to_audio_var: &'a Signal<bool> =
+            create_ref(cx, self.should_fallback_to_audio_var.clone());
+        let should_defer_capture_var: &'a Signal<bool> =
+            create_ref(cx, self.should_defer_capture_var.clone());
+        let should_limit_session_var: &'a Signal<bool> =
+            create_ref(cx, self.should_limit_session_var.clone());
+        let should_schedule_start_var: &'a Signal<bool> =
+            create_ref(cx, self.should_schedule_start_var.clone());
+
+        let is_peer_to_peer =
+            create_selector(cx, || *network_mode_var.get() == NetworkMode::PeerToPeer);
+        let is_client_server =
+            create_selector(cx, || *network_mode_var.get() == NetworkMode::ClientServer);
+        let is_loopback = create_selector(cx, || *network_mode_var.get() == NetworkMode::Loopback);
+
+        let reservation_state_var = create_ref(cx, self.reservation_state_var.clone());
+        let camera_permission_var = create_ref(cx, self.camera_permission_var.clone());
+        let microphone_permission_var = create_ref(cx, self.microphone_permission_var.clone());
+
+        let is_display_media_supported = self.capabilities.display_media;
+
+        let is_device_tested_var = create_ref(cx, self.device_test.is_passed_var().clone());
+        let is_device_test_required = create_selector(cx, || {
+            let should_use_camera = *should_use_video_var.get() && !*should_share_screen_var.get();
+            (should_use_camera || *should_use_audio_var.get()) && !*should_defer_capture_var.get()
+        });
+        let device_test_view =
+            self.device_test
+                .view(cx, should_use_video_var, should_use_audio_var);
+
         let senders_var = self.senders_var.clone();
+        let senders = create_memo(cx, move || senders_var.get().as_ref().clone());
 
-        template! {
+        view! { cx,
             div(class = "component") {
-                h1() {
-                    ("Senders")
+                h1 {
+                    "Senders"
                 }
-                div() {
-                    label() {
-                        ("channel name: ")
-                        input(
-                            type = "text",
-                            value = (channel_name_var.get().0),
-                            on:input = on_channel_name_change,
-                        )
+                div {
+                    label {
+                        "channel name: "
+                        input(type = "text", bind:value = channel_name_var)
+                    }
+                    button(on:click = on_reserve_channel_click) {
+                        "Reserve"
                     }
                 }
-                div() {
-                    ({
-                        let network_mode = network_mode_var.get().as_ref().clone();
-
-                        let on_set_network_mode_peer_to_peer = {
-                            let self_arc = Arc::clone(&self_arc);
-                            move |_| self_arc.network_mode_var.set(NetworkMode::PeerToPeer)
-                        };
-
-                        let on_set_network_mode_client_server = {
-                            let self_arc = Arc::clone(&self_arc);
-                            move |_| self_arc.network_mode_var.set(NetworkMode::ClientServer)
-                        };
-
-
-                        template! {
-                            label() {
-                                input(
-                                    type = "checkbox",
-                                    checked = network_mode == NetworkMode::PeerToPeer,
-                                    on:change = on_set_network_mode_peer_to_peer,
-                                )
-                                ("PeerToPeer")
-                            }
-                            label() {
-                                input(
-                                    type = "checkbox",
-                                    checked = network_mode == NetworkMode::ClientServer,
-                                    on: change = on_set_network_mode_client_server,
-                                )
-                                ("ClientServer")
-                            }
-                        }
-                    })
+                div {
+                    label(title = "Receivers are asked for it, empty for an open channel") {
+                        "channel password: "
+                        input(type = "password", bind:value = channel_password_var)
+                    }
                 }
-                div() {
-                    label() {
+                div(class = "monospace") {
+                    "reservation: "
+                    (reservation_state_var.get())
+                }
+                div {
+                    label {
                         input(
                             type = "checkbox",
-                            bind:checked = should_use_video_var
+                            checked = *is_peer_to_peer.get(),
+                            on:change = |_| network_mode_var.set(NetworkMode::PeerToPeer),
                         )
-                        ("Use Video")
+                        "PeerToPeer"
                     }
-                    label() {
+                    label {
                         input(
                             type = "checkbox",
-                            bind:checked = should_use_audio_var
+                            checked = *is_client_server.get(),
+                            on:change = |_| network_mode_var.set(NetworkMode::ClientServer),
                         )
-                        ("Use Audio")
+                        "ClientServer"
                     }
-                    label() {
+                    label(title = "Data sent through the server is also echoed back") {
                         input(
                             type = "checkbox",
-                            bind:checked = should_use_data_channel_var
+                            checked = *is_loopback.get(),
+                            on:change = |_| network_mode_var.set(NetworkMode::Loopback),
                         )
-                        ("Use DataChannel")
+                        "Loopback"
                     }
                 }
-                button(on:click = on_add_sender_click) {
-                    ("Open channel")
-                }
-                div() {
-                    ({
-                        Template::new_fragment(
-                            senders_var
-                                .get()
-                                .borrow()
-                                .iter()
-                                .map(|sender| sender.view())
-                                .collect(),
-                        )
+                div {
+                    label {
+                        input(type = "checkbox", bind:checked = should_use_video_var)
+                        "Use Video"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_use_audio_var)
+                        "Use Audio"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_use_hq_audio_var)
+                        "HQ Audio"
+                    }
+                    (if is_display_media_supported {
+                        view! { cx,
+                            label {
+                                input(type = "checkbox", bind:checked = should_share_screen_var)
+                                "Share Screen"
+                            }
+                            label {
+                                input(type = "checkbox", bind:checked = should_mix_microphone_var)
+                                "Mix Microphone"
+                            }
+                        }
+                    } else {
+                        View::empty()
                     })
+                    label {
+                        input(type = "checkbox", bind:checked = should_use_data_channel_var)
+                        "Use DataChannel"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_prioritize_latency_var)
+                        "Prioritize Latency"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_seal_signaling_var)
+                        "Sealed Signaling"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_verify_peer_var)
+                        "Verify Peer"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_report_summary_var)
+                        "Report Session Stats"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_throttle_in_background_var)
+                        "Throttle In Background"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_fallback_to_audio_var)
+                        "Audio Fallback"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_defer_capture_var)
+                        "Capture On Join"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_limit_session_var)
+                        "Kiosk Session Limits"
+                    }
+                    label {
+                        input(type = "checkbox", bind:checked = should_schedule_start_var)
+                        "Scheduled In 5 Minutes"
+                    }
+                }
+                div(class = "monospace") {
+                    "camera: "
+                    (Self::permission_state_text(*camera_permission_var.get()))
+                }
+                div(class = "monospace") {
+                    "microphone: "
+                    (Self::permission_state_text(*microphone_permission_var.get()))
+                }
+                (device_test_view)
+                button(
+                    on:click = on_add_sender_click,
+                    disabled = *is_device_test_required.get() && !*is_device_tested_var.get(),
+                ) {
+                    "Open channel"
+                }
+                div {
+                    Keyed(
+                        iterable = senders,
+                        view = |cx, sender| sender.view(cx),
+                        key = |sender| Arc::as_ptr(sender) as usize,
+                    )
                 }
             }
         }
@@ -1,40 +1,51 @@
 use async_std::sync::Arc;
-use browser_webrtc::signaling_protocol::ChannelId;
 use browser_webrtc::Server;
 use sycamore::prelude::*;
 
-use crate::{ReceiversListView, SendersListView};
+use crate::{
+    CallHistoryListView, ReceiversListView, RequestPolicyView, SendersListView, ServerModel,
+};
 
 #[derive(Debug)]
 pub struct ServerView {
     server: Arc<Server>,
-    channels_var: Signal<Vec<ChannelId>>,
+    model: Arc<ServerModel>,
     senders: Arc<SendersListView>,
     receivers: Arc<ReceiversListView>,
+    request_policy: Arc<RequestPolicyView>,
+    call_history: Arc<CallHistoryListView>,
 }
 
 impl ServerView {
-    pub fn new(server: Arc<Server>, channels_var: Signal<Vec<ChannelId>>) -> Arc<Self> {
+    pub fn new(server: Arc<Server>, model: Arc<ServerModel>) -> Arc<Self> {
         log::trace!("client::ServerView::new");
 
         let senders = SendersListView::new(Arc::clone(&server));
-        let receivers = ReceiversListView::new(Arc::clone(&server), channels_var.clone());
+        let receivers = ReceiversListView::new(Arc::clone(&server), Arc::clone(&model));
+        let request_policy = RequestPolicyView::new(Arc::clone(&server));
+        let call_history = CallHistoryListView::new(&senders, &receivers);
 
         Arc::new(Self {
             server,
-            channels_var,
+            model,
             senders,
             receivers,
+            request_policy,
+            call_history,
         })
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
-        let senders = Arc::clone(&self.senders);
-        let receivers = Arc::clone(&self.receivers);
-
-        template! {
-            (senders.view())
-            (receivers.view())
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
+        let senders_view = self.senders.view(cx);
+        let receivers_view = self.receivers.view(cx);
+        let request_policy_view = self.request_policy.view(cx);
+        let call_history_view = self.call_history.view(cx);
+
+        view! { cx,
+            (senders_view)
+            (receivers_view)
+            (request_policy_view)
+            (call_history_view)
         }
     }
 }
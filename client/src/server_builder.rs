@@ -1,38 +1,30 @@
 use async_std::sync::{Arc, Weak};
-use browser_webrtc::signaling_protocol::ChannelId;
-use browser_webrtc::{NewServerError, ServerEvent};
+use browser_webrtc::{NewServerError, WebSocketState};
 use sycamore::prelude::*;
 
-use crate::{ServerView, ServersListView};
+use crate::{bind_signal, ServerModel, ServerView, ServersListView};
 
 #[derive(Debug)]
 pub struct ServerBuilderView {
-    servers: Arc<ServersListView>,
-    addr: String,
-    server_var: Signal<Option<Result<Arc<ServerView>, NewServerError>>>,
-    channels_var: Signal<Vec<ChannelId>>,
+    servers: Weak<ServersListView>,
+    model: Arc<ServerModel>,
+    server_var: RcSignal<Option<Result<Arc<ServerView>, NewServerError>>>,
 }
 
 impl ServerBuilderView {
-    pub fn new(servers: Arc<ServersListView>, addr: String) -> Arc<Self> {
+    /// Creates a server view connecting to the comma-separated list of addresses,
+    /// the following addresses are used as failover if the previous ones are unavailable.
+    pub fn new(servers: Arc<ServersListView>, addrs: String) -> Arc<Self> {
         use wasm_bindgen_futures::spawn_local;
 
         log::trace!("client::ServerBuilderView::new");
 
-        let addr = if addr.starts_with("ws://") || addr.starts_with("wss://") {
-            addr.to_owned()
-        } else {
-            format!("ws://{}", addr)
-        };
-
-        let server_var = Signal::new(None);
-        let channels_var = Signal::new(Vec::new());
+        let server_var = create_rc_signal(None);
 
         let server = Arc::new(Self {
-            servers,
-            addr: addr.clone(),
+            servers: Arc::downgrade(&servers),
+            model: ServerModel::new(&addrs),
             server_var: server_var.clone(),
-            channels_var: channels_var.clone(),
         });
 
         spawn_local({
@@ -47,95 +39,144 @@ impl ServerBuilderView {
         use browser_webrtc::Server;
         use log::error;
 
-        let addr = self.addr.to_owned();
-        let channels_var = self.channels_var.clone();
-
-        let self_weak = Arc::downgrade(&self);
+        let model_weak = Arc::downgrade(&self.model);
         let server = {
             Server::new(
-                addr,
-                Box::new(move |_, ev| {
-                    let self_weak = Weak::clone(&self_weak);
-                    Box::pin(async move { self_weak.upgrade().unwrap().on_event(ev).await })
+                self.model.addrs().to_vec(),
+                Box::new(move |server, ev| {
+                    let model_weak = Weak::clone(&model_weak);
+                    Box::pin(async move {
+                        if let Some(model) = model_weak.upgrade() {
+                            model.on_event(&server, ev).await
+                        }
+                    })
                 }),
             )
             .await
         };
 
         match server {
-            Ok(server) => Ok(ServerView::new(server, channels_var)),
+            Ok(server) => {
+                use browser_webrtc::ServerTelemetry;
+
+                let is_telemetry_enabled = self
+                    .servers
+                    .upgrade()
+                    .map_or(false, |servers| servers.is_telemetry_enabled());
+                if is_telemetry_enabled {
+                    server.set_telemetry(Some(ServerTelemetry::new(&server)));
+                }
+                self.model.set_websocket_state(server.websocket_state());
+                Ok(ServerView::new(server, Arc::clone(&self.model)))
+            }
             Err(err) => {
                 error!("{}", err);
+                self.model.set_websocket_state(WebSocketState::Closed);
                 Err(err)
             }
         }
     }
 
-    async fn on_event(self: &Arc<Self>, ev: ServerEvent) {
-        use log::{debug, error};
-        match ev {
-            ServerEvent::OpenChannelIdsChanged(ids) => {
-                debug!("Open channel ids: {:?}", &ids);
-                self.channels_var.set(ids)
-            }
-            ServerEvent::Error(err) => error!("{}", err),
-            ev => debug!("{:?}", ev),
-        }
-    }
-
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
+    pub fn view(self: &Arc<Self>, cx: Scope<'_>) -> View<DomNode> {
         let server_var = self.server_var.clone();
-        let addr = self.addr.clone();
+        let model = create_ref(cx, Arc::clone(&self.model));
+        let addrs = model.addrs().join(", ");
+        let active_addr_var = bind_signal(cx, model.active_addr());
+        let active_addr = create_memo(cx, move || {
+            active_addr_var
+                .get()
+                .as_ref()
+                .clone()
+                .unwrap_or_else(|| "none".to_owned())
+        });
+        let connection_state = bind_signal(cx, model.connection_state());
+        let last_error_var = bind_signal(cx, model.last_error());
+        let last_error = create_memo(cx, move || {
+            last_error_var
+                .get()
+                .as_ref()
+                .clone()
+                .unwrap_or_else(|| "none".to_owned())
+        });
+        let peer_counts_var = bind_signal(cx, model.peer_counts());
+        let peer_counts = create_memo(cx, move || {
+            let counts = peer_counts_var.get();
+            format!("{} senders, {} receivers", counts.senders, counts.receivers)
+        });
 
         let on_close_click = {
-            let self_arc = Arc::clone(self);
-            move |_| self_arc.servers.remove_server(&self_arc)
+            let self_weak = Arc::downgrade(self);
+            move |_| {
+                if let Some(self_arc) = self_weak.upgrade() {
+                    if let Some(servers) = self_arc.servers.upgrade() {
+                        servers.remove_server(&self_arc)
+                    }
+                }
+            }
         };
 
-        template! {
+        let server_view = View::new_dyn_scoped(cx, move |cx| match server_var.get().as_ref() {
+            Some(Ok(server)) => server.view(cx),
+            Some(Err(err)) => {
+                let err = err.to_string();
+                view! { cx,
+                    h2 {
+                        "error"
+                    }
+                    textarea(class = "error", readonly = true) {
+                        (err)
+                    }
+                }
+            }
+            None => view! { cx,
+                h2(class = "loading") {
+                    "connecting..."
+                }
+            },
+        });
+
+        view! { cx,
             div(class = "component") {
                 h1 {
-                    ("Server")
+                    "Server"
                 }
                 button(on:click = on_close_click, class = "close") {
-                    ("close")
+                    "close"
                 }
                 div(class = "monospace") {
-                    ("address: ")
-                    (addr)
+                    "address: "
+                    (addrs)
                 }
-                ({
-                    let server = server_var.get();
-
-                    match server.as_ref() {
-                        Some(Ok(server)) => {
-                            server.view()
-                        },
-                        Some(Err(err)) => {
-                            let err = err.to_string();
-                            template! {
-                                h2() {
-                                    ("error")
-                                }
-                                textarea(class = "error", readonly = true) {
-                                    (err)
-                                }
-                            }
-                        },
-                        None => {
-                            template! {
-                                h2(class = "loading") {
-                                    ("loading...")
-                                }
-                            }
-                        }
-                    }
-                })
+                div(class = "monospace") {
+                    "active: "
+                    (active_addr.get())
+                }
+                div(class = "monospace") {
+                    "state: "
+                    (connection_state.get())
+                }
+                div(class = "monospace") {
+                    "last error: "
+                    (last_error.get())
+                }
+                div(class = "monospace") {
+                    "peers: "
+                    (peer_counts.get())
+                }
+                (server_view)
             }
         }
     }
 }
 
+impl PartialEq for ServerBuilderView {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self, other)
+    }
+}
+
+impl Eq for ServerBuilderView {}
+
 impl Drop for ServerBuilderView {
     fn drop(&mut self) {
         log::trace!("client::ServerBuilderView::drop");
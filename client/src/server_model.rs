@@ -0,0 +1,332 @@
+use core::time::Duration;
+use std::collections::HashMap;
+
+use async_std::sync::Arc;
+use browser_webrtc::signaling_protocol::{ChannelId, ChannelStatus, IntendedMedia};
+use browser_webrtc::{MediaSnapshot, PeerCounts, Server, ServerEvent, WebSocketState};
+
+use crate::{ui_language, Observable};
+
+/// Duration of the "new" and "closed" channel list highlight.
+const CHANNEL_TRANSITION_DURATION: Duration = Duration::from_millis(1500);
+
+/// Channel preview shown in the open channels list.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ChannelPreview {
+    pub image_url: Option<String>,
+    pub caption: String,
+    /// Media the sender captures when the channel is joined.
+    pub intended_media: Option<IntendedMedia>,
+    /// Language tags of the sender captions.
+    pub caption_languages: Vec<String>,
+    pub status: Option<ChannelStatus>,
+    /// Planned start time since the UNIX epoch.
+    pub scheduled_start: Option<Duration>,
+}
+
+impl ChannelPreview {
+    /// Applies the preview published by the channel sender,
+    /// the previous image is kept if the new preview has no thumbnail.
+    fn update(&mut self, preview: browser_webrtc::signaling_protocol::ChannelPreview) {
+        self.caption = preview.caption;
+        self.intended_media = preview.intended_media;
+        self.caption_languages = preview.caption_languages;
+        if let Some(thumbnail) = preview.thumbnail {
+            self.image_url = Some(MediaSnapshot::from_jpeg(thumbnail).data_url());
+        }
+    }
+}
+
+/// Open channels list item state, new and closed items are highlighted for a while.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChannelListState {
+    New,
+    Open,
+    Closed,
+}
+
+/// Server connection state and its open channels list.
+#[derive(Debug)]
+pub struct ServerModel {
+    addrs: Vec<String>,
+    active_addr: Observable<Option<String>>,
+    connection_state: Observable<String>,
+    /// Latest error reported by the server, described in the browser language.
+    last_error: Observable<Option<String>>,
+    channels: Observable<Vec<ChannelId>>,
+    channel_previews: Observable<HashMap<ChannelId, ChannelPreview>>,
+    channel_states: Observable<HashMap<ChannelId, ChannelListState>>,
+    peer_counts: Observable<PeerCounts>,
+}
+
+impl ServerModel {
+    /// Creates a model of the comma-separated list of addresses,
+    /// the following addresses are used as failover if the previous ones are unavailable.
+    pub fn new(addrs: &str) -> Arc<Self> {
+        let addrs = addrs
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(|addr| {
+                if addr.starts_with("ws://") || addr.starts_with("wss://") {
+                    addr.to_owned()
+                } else {
+                    format!("ws://{}", addr)
+                }
+            })
+            .collect();
+
+        Arc::new(Self {
+            addrs,
+            active_addr: Observable::new(None),
+            connection_state: Observable::new(websocket_state_name(WebSocketState::Connecting)),
+            last_error: Observable::new(None),
+            channels: Observable::new(Vec::new()),
+            channel_previews: Observable::new(HashMap::new()),
+            channel_states: Observable::new(HashMap::new()),
+            peer_counts: Observable::new(PeerCounts::default()),
+        })
+    }
+
+    pub fn addrs(&self) -> &[String] {
+        &self.addrs
+    }
+
+    pub fn active_addr(&self) -> &Observable<Option<String>> {
+        &self.active_addr
+    }
+
+    pub fn connection_state(&self) -> &Observable<String> {
+        &self.connection_state
+    }
+
+    pub fn last_error(&self) -> &Observable<Option<String>> {
+        &self.last_error
+    }
+
+    /// Listed channels sorted by id, closed channels are kept during their list transition.
+    pub fn channels(&self) -> &Observable<Vec<ChannelId>> {
+        &self.channels
+    }
+
+    pub fn channel_previews(&self) -> &Observable<HashMap<ChannelId, ChannelPreview>> {
+        &self.channel_previews
+    }
+
+    pub fn channel_states(&self) -> &Observable<HashMap<ChannelId, ChannelListState>> {
+        &self.channel_states
+    }
+
+    /// Numbers of the live senders and receivers of the server.
+    pub fn peer_counts(&self) -> &Observable<PeerCounts> {
+        &self.peer_counts
+    }
+
+    pub fn set_websocket_state(&self, state: WebSocketState) {
+        self.connection_state.set(websocket_state_name(state))
+    }
+
+    pub async fn on_event(self: &Arc<Self>, server: &Server, ev: ServerEvent) {
+        use browser_webrtc::signaling_protocol::CatalogedError;
+        use log::{debug, error, info, warn};
+
+        match ev {
+            ServerEvent::EndpointFailed { url, error } => {
+                warn!("Server {} is unavailable: {}", url, error)
+            }
+            ServerEvent::EndpointActive { url, .. } => {
+                info!("Connected to server {}", url);
+                self.active_addr.set(Some(url))
+            }
+            ServerEvent::WebSocketStateChanged(state) => self.set_websocket_state(state),
+            ServerEvent::WebSocketClosed {
+                code,
+                reason,
+                was_clean,
+            } => {
+                if was_clean {
+                    info!("Server connection closed with code {}: {}", code, reason)
+                } else {
+                    warn!("Server connection lost with code {}: {}", code, reason)
+                }
+                self.connection_state
+                    .set(format!("closed with code {}: {}", code, reason))
+            }
+            ServerEvent::ServerShutdown => {
+                warn!("Server is shutting down");
+                self.connection_state.set("server shutdown".to_owned())
+            }
+            ServerEvent::ConnectionLost { silence } => {
+                warn!("Server connection lost, nothing received for {:?}", silence);
+                self.connection_state.set("connection lost".to_owned())
+            }
+            ServerEvent::Reconnecting { attempt, delay } => self
+                .connection_state
+                .set(format!("reconnecting in {:?}, attempt {}", delay, attempt)),
+            ServerEvent::Reconnected {
+                url,
+                resumed_sessions,
+                ..
+            } => {
+                info!(
+                    "Reconnected to server {}, {} sessions resumed",
+                    url, resumed_sessions
+                );
+                self.active_addr.set(Some(url))
+            }
+            ServerEvent::ReconnectFailed { attempts } => {
+                error!("Server reconnection failed after {} attempts", attempts);
+                self.connection_state
+                    .set(format!("reconnection failed after {} attempts", attempts))
+            }
+            ServerEvent::OpenChannelsChanged(channels) => {
+                let ids: Vec<_> = channels
+                    .iter()
+                    .map(|channel| channel.channel_id.clone())
+                    .collect();
+                debug!("Open channel ids: {:?}", &ids);
+
+                self.channel_previews.modify(|previews| {
+                    previews.retain(|channel_id, _| ids.contains(channel_id));
+                    for channel in channels {
+                        let preview = previews.entry(channel.channel_id).or_default();
+                        preview.status = Some(channel.status);
+                        preview.scheduled_start = channel.scheduled_start;
+                        if let Some(channel_preview) = channel.preview {
+                            preview.update(channel_preview);
+                        }
+                    }
+                });
+            }
+            ServerEvent::OpenChannelsDiff { opened, closed } => {
+                self.on_open_channels_diff(opened, closed)
+            }
+            ServerEvent::ChannelPreviewChanged {
+                channel_id,
+                preview,
+            } => self.channel_previews.modify(|previews| {
+                if let Some(channel_preview) = previews.get_mut(&channel_id) {
+                    channel_preview.update(preview);
+                }
+            }),
+            ServerEvent::ChannelSnapshot {
+                channel_id,
+                snapshot,
+            } => self.channel_previews.modify(|previews| {
+                previews.entry(channel_id).or_default().image_url = Some(snapshot.data_url());
+            }),
+            ServerEvent::PeerCountsChanged(counts) => {
+                // The channel list is not needed while a channel is received.
+                let result = if counts.receivers > 0 {
+                    server.pause_channel_list()
+                } else {
+                    server.resume_channel_list()
+                };
+                if let Err(err) = result {
+                    error!("{}", err);
+                }
+                self.peer_counts.set(counts)
+            }
+            ServerEvent::PeerRegistrationFailed { peer_id, reason } => {
+                let description = reason.describe(ui_language());
+                warn!("Peer {:?} registration failed: {}", peer_id, description);
+                self.last_error.set(Some(description))
+            }
+            ServerEvent::IncomingRequest { request_id, from } => {
+                info!("Channel request {:?} from {:?}", request_id, from)
+            }
+            ServerEvent::IncomingRequestAutoAccepted { request_id, from } => {
+                info!(
+                    "Channel request {:?} from {:?} is auto-accepted",
+                    request_id, from
+                )
+            }
+            ServerEvent::IncomingRequestDeclined {
+                request_id,
+                from,
+                reason,
+            } => info!(
+                "Channel request {:?} from {:?} is declined: {:?}",
+                request_id, from, reason
+            ),
+            ServerEvent::ChannelRequestDeclined { request_id, reason } => {
+                info!("Channel request {:?} is declined: {:?}", request_id, reason)
+            }
+            ServerEvent::ChannelRequestFailed { request_id, reason } => {
+                let description = reason.describe(ui_language());
+                warn!("Channel request {:?} failed: {}", request_id, description);
+                self.last_error.set(Some(description))
+            }
+            ServerEvent::Error(err) => error!("{}", err),
+            ev => debug!("{:?}", ev),
+        }
+    }
+
+    /// Marks the opened and closed channels for the list transition,
+    /// channels are kept sorted so that the list order does not depend on the server.
+    fn on_open_channels_diff(self: &Arc<Self>, opened: Vec<ChannelId>, closed: Vec<ChannelId>) {
+        use browser_webrtc::sleep;
+        use wasm_bindgen_futures::spawn_local;
+
+        self.channel_states.modify(|states| {
+            for channel_id in &opened {
+                let _: Option<_> = states.insert(channel_id.clone(), ChannelListState::New);
+            }
+            for channel_id in &closed {
+                let _: Option<_> = states.insert(channel_id.clone(), ChannelListState::Closed);
+            }
+        });
+
+        self.channels.modify(|channels| {
+            for channel_id in &opened {
+                if !channels.contains(channel_id) {
+                    channels.push(channel_id.clone());
+                }
+            }
+            channels.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        });
+
+        let self_weak = Arc::downgrade(self);
+        spawn_local(async move {
+            sleep(CHANNEL_TRANSITION_DURATION).await;
+            if let Some(self_arc) = self_weak.upgrade() {
+                self_arc.settle_channel_states(opened, closed)
+            }
+        });
+    }
+
+    /// Ends the list transition unless the channels have changed their state again.
+    fn settle_channel_states(&self, opened: Vec<ChannelId>, closed: Vec<ChannelId>) {
+        let mut settled_closed = Vec::new();
+        self.channel_states.modify(|states| {
+            for channel_id in opened {
+                if let Some(state @ ChannelListState::New) = states.get_mut(&channel_id) {
+                    *state = ChannelListState::Open;
+                }
+            }
+            settled_closed = closed
+                .into_iter()
+                .filter(|channel_id| states.get(channel_id) == Some(&ChannelListState::Closed))
+                .collect();
+            for channel_id in &settled_closed {
+                let _: Option<_> = states.remove(channel_id);
+            }
+        });
+
+        if !settled_closed.is_empty() {
+            self.channels.modify(|channels| {
+                channels.retain(|channel_id| !settled_closed.contains(channel_id))
+            });
+        }
+    }
+}
+
+fn websocket_state_name(state: WebSocketState) -> String {
+    match state {
+        WebSocketState::Connecting => "connecting",
+        WebSocketState::Open => "open",
+        WebSocketState::Closing => "closing",
+        WebSocketState::Closed => "closed",
+    }
+    .to_owned()
+}
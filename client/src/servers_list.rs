@@ -1,5 +1,3 @@
-use core::cell::RefCell;
-
 use async_std::sync::Arc;
 use sycamore::prelude::*;
 
@@ -7,8 +5,11 @@ use crate::ServerBuilderView;
 
 #[derive(Debug)]
 pub struct ServersListView {
-    addr_var: Signal<String>,
-    servers_var: Signal<RefCell<Vec<Arc<ServerBuilderView>>>>,
+    addr_var: RcSignal<String>,
+    servers_var: RcSignal<Vec<Arc<ServerBuilderView>>>,
+    are_notifications_enabled_var: RcSignal<bool>,
+    is_telemetry_enabled_var: RcSignal<bool>,
+    ice_servers_health_var: RcSignal<Vec<String>>,
 }
 
 impl ServersListView {
@@ -17,61 +18,145 @@ impl ServersListView {
 
         use crate::default_server_address;
 
-        let addr_var = Signal::new(default_server_address());
-        let servers_var = Signal::new(RefCell::new(Vec::new()));
+        let addr_var = create_rc_signal(default_server_address());
+        let servers_var = create_rc_signal(Vec::new());
 
         Arc::new(Self {
             addr_var,
             servers_var,
+            are_notifications_enabled_var: create_rc_signal(false),
+            is_telemetry_enabled_var: create_rc_signal(false),
+            ice_servers_health_var: create_rc_signal(Vec::new()),
         })
     }
 
+    /// Checks the ICE servers used by the senders and receivers before a call.
+    async fn check_ice_servers(self: Arc<Self>) {
+        use browser_webrtc::{
+            check_ice_servers, default_rtc_configuration, IceServerKind, RtcConfigurationExt,
+            DEFAULT_ICE_SERVERS_CHECK_TIMEOUT,
+        };
+
+        self.ice_servers_health_var
+            .set(vec!["checking ICE servers...".to_owned()]);
+        let rtc_configuration = default_rtc_configuration().with_google_stun_server();
+        let health = check_ice_servers(&rtc_configuration, DEFAULT_ICE_SERVERS_CHECK_TIMEOUT).await;
+        let lines = health
+            .iter()
+            .map(|server| {
+                let kind = match server.kind {
+                    IceServerKind::Stun => "STUN",
+                    IceServerKind::Turn => "TURN",
+                };
+                match (&server.error, server.response_time()) {
+                    (Some(err), _) => format!("{} {} error: {}", kind, server.url, err),
+                    (None, Some(time)) => {
+                        format!("{} {} ok in {} ms", kind, server.url, time.as_millis())
+                    }
+                    (None, None) => format!("{} {} unreachable", kind, server.url),
+                }
+            })
+            .collect();
+        self.ice_servers_health_var.set(lines);
+    }
+
     pub fn add_server(self: &Arc<Self>) {
-        use crate::SignalVecPush;
         let server = ServerBuilderView::new(Arc::clone(self), self.addr_var.get().as_ref().clone());
-        self.servers_var.push(server);
+        self.servers_var.modify().push(server);
+    }
+
+    /// Returns `true` if the call setup milestones of new servers are reported to them.
+    pub fn is_telemetry_enabled(&self) -> bool {
+        *self.is_telemetry_enabled_var.get()
     }
 
     pub fn remove_server(self: &Arc<Self>, server: &Arc<ServerBuilderView>) {
-        use crate::SignalVecRemoveByPtrEq;
-        self.servers_var.remove_by_ptr_eq(server);
+        self.servers_var
+            .modify()
+            .retain(|other| !Arc::ptr_eq(server, other));
     }
 
-    pub fn view(self: &Arc<Self>) -> Template<DomNode> {
-        let addr_var = self.addr_var.clone();
+    pub fn view<'a>(self: &Arc<Self>, cx: Scope<'a>) -> View<DomNode> {
+        let addr_var: &'a Signal<String> = create_ref(cx, self.addr_var.clone());
+        let are_notifications_enabled_var: &'a Signal<bool> =
+            create_ref(cx, self.are_notifications_enabled_var.clone());
+
+        let is_telemetry_enabled_var: &'a Signal<bool> =
+            create_ref(cx, self.is_telemetry_enabled_var.clone());
+
+        let are_notifications_enabled_rc_var = self.are_notifications_enabled_var.clone();
+        create_effect(cx, move || {
+            use crate::set_notifications_enabled;
+            use wasm_bindgen_futures::spawn_local;
+
+            let is_enabled = *are_notifications_enabled_var.get();
+            let are_notifications_enabled_var = are_notifications_enabled_rc_var.clone();
+            spawn_local(async move {
+                if !set_notifications_enabled(is_enabled).await {
+                    are_notifications_enabled_var.set(false);
+                }
+            });
+        });
 
         let on_add_server_click = {
             let self_arc = Arc::clone(self);
-            move |_| Arc::clone(&self_arc).add_server()
+            move |_| self_arc.add_server()
+        };
+
+        let on_check_ice_servers_click = {
+            let self_arc = Arc::clone(self);
+            move |_| {
+                use wasm_bindgen_futures::spawn_local;
+
+                spawn_local(Arc::clone(&self_arc).check_ice_servers())
+            }
         };
+        let ice_servers_health_var = self.ice_servers_health_var.clone();
+        let ice_servers_health =
+            create_memo(cx, move || ice_servers_health_var.get().as_ref().clone());
 
         let servers_var = self.servers_var.clone();
+        let servers = create_memo(cx, move || servers_var.get().as_ref().clone());
 
-        template! {
+        view! { cx,
             div(class = "component") {
-                h1() {
-                    ("Servers")
+                h1 {
+                    "Servers"
                 }
-                div() {
-                    label() {
-                        ("address: ")
-                        input(type = "text", bind:value = addr_var.clone())
+                div {
+                    label(title = "Comma-separated list, the following addresses are used as failover") {
+                        "address: "
+                        input(type = "text", bind:value = addr_var)
                     }
                 }
                 button(on:click = on_add_server_click) {
-                    ("Join server")
+                    "Join server"
+                }
+                label(title = "Notify about joined receivers and messages while the page is hidden") {
+                    input(type = "checkbox", bind:checked = are_notifications_enabled_var)
+                    "Notifications"
+                }
+                label(title = "Report call setup times of joined servers to them") {
+                    input(type = "checkbox", bind:checked = is_telemetry_enabled_var)
+                    "Telemetry"
+                }
+                button(on:click = on_check_ice_servers_click) {
+                    "Check ICE servers"
                 }
-                div() {
-                    ({
-                        Template::new_fragment(
-                            servers_var
-                                .get()
-                                .borrow()
-                                .iter()
-                                .map(|server| server.view())
-                                .collect(),
-                        )
-                    })
+                Indexed(
+                    iterable = ice_servers_health,
+                    view = |cx, line| view! { cx,
+                        div(class = "monospace") {
+                            (line)
+                        }
+                    },
+                )
+                div {
+                    Keyed(
+                        iterable = servers,
+                        view = |cx, server| server.view(cx),
+                        key = |server| Arc::as_ptr(server) as usize,
+                    )
                 }
             }
         }
@@ -0,0 +1,39 @@
+use browser_webrtc::{SessionUsage, TrafficBytes};
+
+/// Renders the total bytes a session cost, including the signaling.
+pub fn describe_usage(usage: SessionUsage) -> String {
+    describe_traffic(usage.total())
+}
+
+/// Describes the usage by the traffic kind.
+pub fn describe_usage_details(usage: SessionUsage) -> String {
+    format!(
+        "media: {}, data: {}, signaling: {}",
+        describe_traffic(usage.media),
+        describe_traffic(usage.data),
+        describe_traffic(usage.signaling),
+    )
+}
+
+fn describe_traffic(traffic: TrafficBytes) -> String {
+    format!(
+        "↑{} ↓{}",
+        describe_bytes(traffic.sent),
+        describe_bytes(traffic.received)
+    )
+}
+
+fn describe_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use clap::{AppSettings, Clap};
+
+/// Time to collect the mDNS responses of the signaling servers.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Interval of the keepalive pings, well below the default server idle timeout.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Clap, Clone, Debug)]
+#[clap(about = "Headless channel sender publishing camera frames captured with GStreamer")]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct IotSenderOptions {
+    /// Signaling server WebSocket URL
+    #[clap(short, long, default_value = "ws://127.0.0.1:9010")]
+    pub server: String,
+    /// Connect to the first signaling server found on the local network via mDNS
+    /// instead of the server URL
+    #[clap(long)]
+    pub discover: bool,
+    /// Channel to open
+    #[clap(short, long, default_value = "camera")]
+    pub channel: String,
+    /// Channel secret required from the receivers
+    #[clap(long)]
+    pub secret: Option<String>,
+    /// GStreamer source elements, for example `videotestsrc` or `rtspsrc location=URL ! decodebin`
+    #[clap(long, default_value = "v4l2src device=/dev/video0")]
+    pub source: String,
+    /// Frame width in pixels
+    #[clap(long, default_value = "640")]
+    pub width: u32,
+    /// Frame height in pixels
+    #[clap(long, default_value = "480")]
+    pub height: u32,
+    /// Frames per second, the relayed frames are limited by the server relay throughput caps
+    #[clap(long, default_value = "10")]
+    pub fps: u32,
+    /// JPEG quality from 0 to 100
+    #[clap(long, default_value = "70")]
+    pub quality: u32,
+}
+
+impl IotSenderOptions {
+    /// Pipeline scaling the source frames and encoding them as JPEG images to stdout.
+    pub fn pipeline(&self) -> String {
+        format!(
+            "{} ! videoconvert ! videoscale ! videorate \
+             ! video/x-raw,width={},height={},framerate={}/1 \
+             ! jpegenc quality={} ! fdsink fd=1",
+            self.source, self.width, self.height, self.fps, self.quality
+        )
+    }
+}
+
+/// Publishes the captured frames until the pipeline ends or the process is interrupted.
+pub async fn app() -> anyhow::Result<()> {
+    use crate::{FrameCapture, Publisher};
+    use anyhow::anyhow;
+    use log::{info, warn};
+    use signaling_protocol::{discover_servers, ChannelId, ChannelSecret, ServerSenderMessage};
+    use tokio::signal::ctrl_c;
+    use tokio::task::spawn_blocking;
+    use tokio::time::{interval, MissedTickBehavior};
+
+    env_logger::init();
+    let mut opts = IotSenderOptions::parse();
+    if opts.discover {
+        let servers = spawn_blocking(|| discover_servers(DISCOVERY_TIMEOUT)).await??;
+        let server = servers
+            .first()
+            .ok_or_else(|| anyhow!("no signaling servers found on the local network"))?;
+        info!("discovered {:?} at {}", server.instance, server.addr);
+        opts.server = server.url();
+    }
+
+    let mut publisher = Publisher::open(
+        &opts.server,
+        ChannelId(opts.channel.clone()),
+        opts.secret.clone().map(ChannelSecret),
+    )
+    .await?;
+    info!("channel {:?} is open on {}", opts.channel, opts.server);
+    let mut capture = FrameCapture::spawn(&opts)?;
+
+    let mut keepalive = interval(KEEPALIVE_INTERVAL);
+    keepalive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let result = loop {
+        tokio::select! {
+            frame = capture.next_frame() => match frame {
+                Ok(Some(frame)) => publisher.send_frame(frame).await?,
+                Ok(None) => break Err(anyhow!("capture pipeline ended")),
+                Err(err) => break Err(err.into()),
+            },
+            message = publisher.recv() => match message? {
+                ServerSenderMessage::ReceiverJoined { receiver_info } => {
+                    info!("receiver joined: {:?}", receiver_info);
+                }
+                ServerSenderMessage::ReceiverLeft(reason) => info!("receiver left: {:?}", reason),
+                ServerSenderMessage::Throttled(notice) => {
+                    warn!("frames are throttled by the server: {:?}", notice);
+                }
+                message => log::debug!("server message: {:?}", message),
+            },
+            _ = keepalive.tick() => publisher.ping().await?,
+            result = ctrl_c() => break result.map_err(Into::into),
+        }
+    };
+
+    capture.stop().await;
+    publisher.close().await;
+    result
+}
@@ -0,0 +1,170 @@
+use std::io;
+use std::process::Stdio;
+
+use tokio::process::{Child, ChildStdout};
+
+use crate::IotSenderOptions;
+
+/// Frames not ended within this size are not JPEG frames,
+/// the server rejects the relayed data of 4 MiB and more anyway.
+const MAX_FRAME_LEN: usize = 4 * 1024 * 1024 - 1024;
+
+const JPEG_SOI: [u8; 2] = [0xff, 0xd8];
+const JPEG_EOI: [u8; 2] = [0xff, 0xd9];
+
+/// Captures the camera frames with a `gst-launch-1.0` pipeline
+/// that writes them to its stdout as concatenated JPEG images.
+#[derive(Debug)]
+pub struct FrameCapture {
+    child: Child,
+    stdout: ChildStdout,
+    buf: Vec<u8>,
+}
+
+impl FrameCapture {
+    pub fn spawn(opts: &IotSenderOptions) -> io::Result<Self> {
+        use log::info;
+        use tokio::process::Command;
+
+        let pipeline = opts.pipeline();
+        info!("capturing frames with: gst-launch-1.0 {}", pipeline);
+        // The pipeline description is parsed from the arguments joined with spaces.
+        let mut child = Command::new("gst-launch-1.0")
+            .arg("-q")
+            .args(pipeline.split_whitespace())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("pipeline stdout is not piped"))?;
+
+        Ok(Self {
+            child,
+            stdout,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Returns the next JPEG frame or `None` once the pipeline ends.
+    ///
+    /// Cancel safe, the data read so far is kept for the next call.
+    pub async fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        use tokio::io::AsyncReadExt;
+
+        loop {
+            if let Some(frame) = split_frame(&mut self.buf)? {
+                return Ok(Some(frame));
+            }
+            self.buf.reserve(64 * 1024);
+            if self.stdout.read_buf(&mut self.buf).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    pub async fn stop(mut self) {
+        let _: io::Result<()> = self.child.kill().await;
+    }
+}
+
+/// Takes the first complete JPEG image from the buffer.
+///
+/// The end of image marker never occurs inside the entropy-coded data
+/// and the encoder writes no embedded thumbnails, so the first one ends the image.
+fn split_frame(buf: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+    let start = match find(buf, &JPEG_SOI, 0) {
+        Some(start) => start,
+        None => {
+            // A marker may be split between the reads.
+            let keep = usize::from(buf.last() == Some(&JPEG_SOI[0]));
+            let rest = buf.split_off(buf.len() - keep);
+            *buf = rest;
+            return Ok(None);
+        }
+    };
+    if start > 0 {
+        let rest = buf.split_off(start);
+        *buf = rest;
+    }
+    match find(buf, &JPEG_EOI, JPEG_SOI.len()) {
+        Some(end) => {
+            let rest = buf.split_off(end + JPEG_EOI.len());
+            Ok(Some(core::mem::replace(buf, rest)))
+        }
+        None if buf.len() > MAX_FRAME_LEN => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "pipeline output is not a JPEG frame stream",
+        )),
+        None => Ok(None),
+    }
+}
+
+fn find(buf: &[u8], marker: &[u8; 2], from: usize) -> Option<usize> {
+    buf.get(from..)?
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .map(|pos| from + pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{split_frame, MAX_FRAME_LEN};
+
+    const FRAME: [u8; 6] = [0xff, 0xd8, 0x01, 0x02, 0xff, 0xd9];
+
+    #[test]
+    fn complete_frame_is_split() {
+        let mut buf = FRAME.to_vec();
+        assert_eq!(split_frame(&mut buf).unwrap(), Some(FRAME.to_vec()));
+        assert!(buf.is_empty());
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn frame_split_between_reads_is_joined() {
+        // Both markers are split between the reads.
+        let mut buf = FRAME[..1].to_vec();
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&FRAME[1..5]);
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&FRAME[5..]);
+        assert_eq!(split_frame(&mut buf).unwrap(), Some(FRAME.to_vec()));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn frames_of_one_read_are_split_in_order() {
+        let second = [0xff, 0xd8, 0x03, 0xff, 0xd9];
+        let mut buf = [&FRAME[..], &second[..], &FRAME[..2]].concat();
+        assert_eq!(split_frame(&mut buf).unwrap(), Some(FRAME.to_vec()));
+        assert_eq!(split_frame(&mut buf).unwrap(), Some(second.to_vec()));
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+        assert_eq!(buf, FRAME[..2]);
+    }
+
+    #[test]
+    fn data_before_start_of_image_is_skipped() {
+        let mut buf = [&[0x00, 0xd9, 0xff, 0x42][..], &FRAME[..]].concat();
+        assert_eq!(split_frame(&mut buf).unwrap(), Some(FRAME.to_vec()));
+        assert!(buf.is_empty());
+
+        let mut buf = vec![0x00, 0x01, 0x02];
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn unended_frame_over_size_cap_is_error() {
+        let mut buf = FRAME[..2].to_vec();
+        buf.resize(MAX_FRAME_LEN, 0);
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+        buf.push(0);
+        let err = split_frame(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,24 @@
+#![warn(
+    clippy::all,
+    rust_2018_idioms,
+    missing_copy_implementations,
+    missing_debug_implementations,
+    single_use_lifetimes,
+    trivial_casts,
+    unused_import_braces,
+    unused_qualifications,
+    unused_results
+)]
+
+mod app;
+mod capture;
+mod publisher;
+
+use app::{app, IotSenderOptions};
+use capture::FrameCapture;
+use publisher::Publisher;
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    app().await
+}
@@ -0,0 +1,141 @@
+use futures::{SinkExt, StreamExt};
+use signaling_protocol::{
+    ChannelId, ChannelSecret, ClientMessage, ClientSenderMessage, CloseReason, FrameError,
+    ServerMessage, ServerSenderMessage, SessionSenderId, Topic, WireCodec, JPEG_FRAME_TOPIC,
+};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// Codec of the sender, servers accept it without negotiation.
+const CODEC: WireCodec = WireCodec::DEFAULT;
+
+/// Channel sender relaying the frames through the server to the joined receivers.
+///
+/// Browsers join the channel as usual, subscribe to `JPEG_FRAME_TOPIC`
+/// and get the frames as `ReceiverEvent::TopicData`,
+/// there is no native WebRTC stack to send them peer-to-peer.
+#[derive(Debug)]
+pub struct Publisher {
+    websocket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    sender_id: SessionSenderId,
+}
+
+impl Publisher {
+    /// Connects to the server and opens a client-server channel.
+    pub async fn open(
+        url: &str,
+        channel_id: ChannelId,
+        secret: Option<ChannelSecret>,
+    ) -> Result<Self, PublisherError> {
+        use signaling_protocol::NetworkMode;
+        use tokio_tungstenite::connect_async;
+
+        let (websocket, _) = connect_async(url).await?;
+        let mut publisher = Self {
+            websocket,
+            sender_id: SessionSenderId(random_session_id()),
+        };
+        publisher
+            .send(ClientSenderMessage::OpenChannel {
+                channel_id,
+                network_mode: NetworkMode::ClientServer,
+                claim_token: None,
+                secret,
+                resume_token: None,
+            })
+            .await?;
+        loop {
+            match publisher.recv().await? {
+                ServerSenderMessage::OpenChannelSuccess => return Ok(publisher),
+                ServerSenderMessage::Error(err) => {
+                    return Err(PublisherError::OpenChannelError(err))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub async fn send_frame(&mut self, frame: Vec<u8>) -> Result<(), PublisherError> {
+        self.send(ClientSenderMessage::SendTopicData {
+            topic: Topic(JPEG_FRAME_TOPIC.to_owned()),
+            data: frame,
+        })
+        .await
+    }
+
+    /// Keeps the connection alive while the pipeline produces no frames.
+    pub async fn ping(&mut self) -> Result<(), PublisherError> {
+        self.send_raw(&ClientMessage::Ping).await
+    }
+
+    /// Receives the next message addressed to the sender, cancel safe.
+    pub async fn recv(&mut self) -> Result<ServerSenderMessage, PublisherError> {
+        loop {
+            let data = match self.websocket.next().await {
+                Some(Ok(Message::Binary(data))) => data,
+                Some(Ok(Message::Close(_))) | None => return Err(PublisherError::ConnectionClosed),
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(err.into()),
+            };
+            match CODEC.decode_frame(&data, u32::MAX) {
+                Ok(ServerMessage::SenderMessage { sender_id, message })
+                    if sender_id == self.sender_id =>
+                {
+                    return Ok(message)
+                }
+                Ok(ServerMessage::ServerShutdown) => return Err(PublisherError::ConnectionClosed),
+                Ok(_) => {}
+                // Messages of the newer protocol revisions are skipped.
+                Err(err) if err.is_unknown_type() => {}
+                Err(err) => return Err(PublisherError::DecodeError(err)),
+            }
+        }
+    }
+
+    pub async fn close(mut self) {
+        let _: Result<(), _> = self
+            .send(ClientSenderMessage::CloseChannel(CloseReason::LocalClosed))
+            .await;
+        let _: Result<(), _> = self.websocket.close(None).await;
+    }
+
+    async fn send(&mut self, message: ClientSenderMessage) -> Result<(), PublisherError> {
+        self.send_raw(&ClientMessage::SenderMessage {
+            sender_id: self.sender_id,
+            message,
+        })
+        .await
+    }
+
+    async fn send_raw(&mut self, message: &ClientMessage) -> Result<(), PublisherError> {
+        let data = CODEC
+            .encode_frame(message)
+            .map_err(PublisherError::EncodeError)?;
+        self.websocket.send(Message::Binary(data)).await?;
+        Ok(())
+    }
+}
+
+/// Session ids are chosen by the clients, so they should not collide
+/// with the ones of the previous runs still draining on the server.
+fn random_session_id() -> u64 {
+    let mut bytes = [0; 8];
+    getrandom::getrandom(&mut bytes).expect("OS random source is unavailable");
+    u64::from_le_bytes(bytes)
+}
+
+#[derive(Error, Debug)]
+pub enum PublisherError {
+    #[error("WebSocket error: {0}")]
+    WebSocketError(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error("message encode error: {0}")]
+    EncodeError(FrameError),
+    #[error("server message decode error: {0}")]
+    DecodeError(FrameError),
+    #[error("channel open error: {0:?}")]
+    OpenChannelError(signaling_protocol::ServerSenderErrorMessage),
+    #[error("connection is closed by the server")]
+    ConnectionClosed,
+}
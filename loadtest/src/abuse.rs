@@ -0,0 +1,112 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use signaling_protocol::{AckId, ChannelId, ClientMessage, FrameHeader};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{Connection, LoadStats, LoadTestOptions, Rng};
+
+/// Number of valid messages sent at once by a flood.
+const FLOOD_LEN: usize = 1000;
+
+/// Size of the oversized binary messages.
+const OVERSIZED_LEN: usize = 1 << 20;
+
+/// Delay between the abuse bursts of a connection.
+const BURST_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Sends malformed, oversized and flooding traffic until the deadline,
+/// reconnecting when the server closes the connection.
+///
+/// Disconnects are expected, the server closes sessions sending invalid messages,
+/// the server must keep serving the other scenarios meanwhile.
+pub async fn run_abuser(
+    opts: Arc<LoadTestOptions>,
+    stats: Arc<LoadStats>,
+    mut rng: Rng,
+    deadline: Instant,
+) {
+    use tokio::time::sleep;
+
+    while Instant::now() < deadline {
+        let mut connection = match Connection::connect(&opts.server, &stats).await {
+            Some(connection) => connection,
+            None => {
+                sleep(BURST_INTERVAL).await;
+                continue;
+            }
+        };
+        while Instant::now() < deadline {
+            if !send_burst(&mut connection, &stats, &mut rng).await {
+                break;
+            }
+            // Drains the server responses, they are not checked.
+            let drain_deadline = (Instant::now() + BURST_INTERVAL).min(deadline);
+            loop {
+                match connection.recv(drain_deadline, &stats).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(_) => {
+                        stats.record_abuser_disconnect();
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Sends a random kind of abusive traffic, returns `false` if the connection is closed.
+async fn send_burst(connection: &mut Connection, stats: &LoadStats, rng: &mut Rng) -> bool {
+    let messages: Vec<Message> = match rng.below(6) {
+        0 => {
+            let len = 1 + rng.below(512);
+            vec![Message::Binary(rng.bytes(len))]
+        }
+        1 => vec![Message::Text("not a signaling message".to_owned())],
+        2 => vec![Message::Binary(rng.bytes(OVERSIZED_LEN))],
+        3 => {
+            // Valid frame header of an unknown message type declaring an oversized payload.
+            let mut frame = FrameHeader::new(u8::MAX, u32::MAX).to_bytes().to_vec();
+            frame.extend(rng.bytes(64));
+            vec![Message::Binary(frame)]
+        }
+        4 => {
+            let message = ClientMessage::RequestChannelSnapshot {
+                channel_id: ChannelId("loadtest-missing".to_owned()),
+                credential: None,
+            };
+            return flood(connection, stats, &message).await;
+        }
+        _ => {
+            let message = ClientMessage::WithAck {
+                ack_id: AckId(0),
+                message: Box::new(ClientMessage::WithAck {
+                    ack_id: AckId(1),
+                    message: Box::new(ClientMessage::ReportMilestones(Vec::new())),
+                }),
+            };
+            stats.record_abusive();
+            return connection.send(&message, stats).await;
+        }
+    };
+    for message in messages {
+        stats.record_abusive();
+        if !connection.send_raw(message, stats).await {
+            stats.record_abuser_disconnect();
+            return false;
+        }
+    }
+    true
+}
+
+async fn flood(connection: &mut Connection, stats: &LoadStats, message: &ClientMessage) -> bool {
+    for _ in 0..FLOOD_LEN {
+        stats.record_abusive();
+        if !connection.send(message, stats).await {
+            stats.record_abuser_disconnect();
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::{AppSettings, Clap};
+
+/// Time to collect the mDNS responses of the signaling servers.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clap, Clone, Debug)]
+#[clap(about = "Load and abuse simulation against a running signaling server")]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct LoadTestOptions {
+    /// Signaling server WebSocket URL
+    #[clap(short, long, default_value = "ws://127.0.0.1:9010")]
+    pub server: String,
+    /// Connect to the first signaling server found on the local network via mDNS
+    /// instead of the server URL
+    #[clap(long)]
+    pub discover: bool,
+    /// Number of sender and receiver connection pairs relaying binary data
+    #[clap(long, default_value = "500")]
+    pub relay_pairs: usize,
+    /// Number of connections measuring the relay latency with loopback channels
+    #[clap(long, default_value = "50")]
+    pub echoers: usize,
+    /// Number of connections repeatedly opening and closing channels
+    #[clap(long, default_value = "200")]
+    pub churners: usize,
+    /// Number of connections sending malformed and flooding traffic
+    #[clap(long, default_value = "50")]
+    pub abusers: usize,
+    /// Test duration in seconds
+    #[clap(short, long, default_value = "30")]
+    pub duration: u64,
+    /// Interval between the relayed messages of a pair in milliseconds
+    #[clap(long, default_value = "100")]
+    pub relay_interval: u64,
+    /// Size of the relayed messages in bytes, at least the timestamp size
+    #[clap(long, default_value = "256")]
+    pub payload_size: usize,
+    /// Seed of the malformed traffic generator
+    #[clap(long, default_value = "1")]
+    pub seed: u64,
+}
+
+impl LoadTestOptions {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.duration)
+    }
+
+    pub fn relay_interval(&self) -> Duration {
+        Duration::from_millis(self.relay_interval)
+    }
+}
+
+/// Runs all scenarios concurrently until the test duration elapses and prints the report.
+pub async fn app() -> anyhow::Result<()> {
+    use crate::{run_abuser, run_churner, run_echoer, run_relay_pair, LoadStats, Rng};
+    use anyhow::anyhow;
+    use futures::future::join_all;
+    use signaling_protocol::discover_servers;
+    use tokio::spawn;
+    use tokio::task::spawn_blocking;
+
+    let mut opts = LoadTestOptions::parse();
+    if opts.discover {
+        let servers = spawn_blocking(|| discover_servers(DISCOVERY_TIMEOUT)).await??;
+        let server = servers
+            .first()
+            .ok_or_else(|| anyhow!("no signaling servers found on the local network"))?;
+        println!("discovered {:?} at {}", server.instance, server.addr);
+        opts.server = server.url();
+    }
+    let opts = Arc::new(opts);
+    let stats = Arc::new(LoadStats::default());
+    let start = Instant::now();
+    let deadline = start + opts.duration();
+
+    let relays = (0..opts.relay_pairs).map(|index| {
+        spawn(run_relay_pair(
+            Arc::clone(&opts),
+            Arc::clone(&stats),
+            index,
+            start,
+            deadline,
+        ))
+    });
+    let echoers = (0..opts.echoers).map(|index| {
+        spawn(run_echoer(
+            Arc::clone(&opts),
+            Arc::clone(&stats),
+            index,
+            start,
+            deadline,
+        ))
+    });
+    let churners = (0..opts.churners).map(|index| {
+        spawn(run_churner(
+            Arc::clone(&opts),
+            Arc::clone(&stats),
+            index,
+            deadline,
+        ))
+    });
+    let abusers = (0..opts.abusers).map(|index| {
+        let rng = Rng::new(opts.seed.wrapping_add(index as u64));
+        spawn(run_abuser(
+            Arc::clone(&opts),
+            Arc::clone(&stats),
+            rng,
+            deadline,
+        ))
+    });
+    let tasks: Vec<_> = relays
+        .chain(echoers)
+        .chain(churners)
+        .chain(abusers)
+        .collect();
+    for result in join_all(tasks).await {
+        if let Err(err) = result {
+            stats.record_error(format!("scenario task failed: {}", err));
+        }
+    }
+
+    println!("{}", stats.report(start.elapsed()));
+    Ok(())
+}
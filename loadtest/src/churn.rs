@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use signaling_protocol::{
+    ChannelId, ClientMessage, ClientSenderMessage, CloseReason, NetworkMode, ServerMessage,
+    ServerSenderMessage, SessionSenderId,
+};
+
+use crate::{Connection, LoadStats, LoadTestOptions};
+
+/// Time to wait for the channel open confirmation.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Opens and closes channels in a loop until the deadline,
+/// every change is broadcast to all connected clients in the open channel list.
+pub async fn run_churner(
+    opts: Arc<LoadTestOptions>,
+    stats: Arc<LoadStats>,
+    index: usize,
+    deadline: Instant,
+) {
+    let mut connection = match Connection::connect(&opts.server, &stats).await {
+        Some(connection) => connection,
+        None => return,
+    };
+
+    let mut cycle = 0_u64;
+    while Instant::now() < deadline {
+        let sender_id = SessionSenderId(cycle);
+        let open = ClientMessage::SenderMessage {
+            sender_id,
+            message: ClientSenderMessage::OpenChannel {
+                channel_id: ChannelId(format!("loadtest-churn-{}-{}", index, cycle)),
+                network_mode: NetworkMode::PeerToPeer,
+                claim_token: None,
+                secret: None,
+                resume_token: None,
+            },
+        };
+        if !connection.send(&open, &stats).await {
+            stats.record_error("churner disconnected".to_owned());
+            return;
+        }
+        let open_timeout = OPEN_TIMEOUT.min(deadline.saturating_duration_since(Instant::now()));
+        let opened = connection
+            .recv_until(open_timeout, &stats, |message| {
+                matches!(
+                    message,
+                    ServerMessage::SenderMessage {
+                        sender_id: id,
+                        message: ServerSenderMessage::OpenChannelSuccess
+                            | ServerSenderMessage::Error(_),
+                    } if *id == sender_id
+                )
+            })
+            .await;
+        match opened {
+            Ok(Some(ServerMessage::SenderMessage {
+                message: ServerSenderMessage::OpenChannelSuccess,
+                ..
+            })) => {}
+            Ok(Some(message)) => stats.record_error(format!("channel open failed: {:?}", message)),
+            Ok(None) if Instant::now() >= deadline => break,
+            Ok(None) => stats.record_error("channel open timed out".to_owned()),
+            Err(_) => {
+                stats.record_error("churner disconnected".to_owned());
+                return;
+            }
+        }
+
+        let close = ClientMessage::SenderMessage {
+            sender_id,
+            message: ClientSenderMessage::CloseChannel(CloseReason::LocalClosed),
+        };
+        if !connection.send(&close, &stats).await {
+            stats.record_error("churner disconnected".to_owned());
+            return;
+        }
+        stats.record_churn_cycle();
+        cycle += 1;
+    }
+    connection.close().await;
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use signaling_protocol::{ClientMessage, ServerMessage, WireCodec};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::LoadStats;
+
+/// Codec used by the simulated clients, servers accept it without negotiation.
+const CODEC: WireCodec = WireCodec::DEFAULT;
+
+/// Signaling server connection of a simulated client.
+#[derive(Debug)]
+pub struct Connection {
+    websocket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Connection {
+    /// Connects to the server, failures are recorded in the stats.
+    pub async fn connect(url: &str, stats: &LoadStats) -> Option<Self> {
+        use tokio_tungstenite::connect_async;
+
+        match connect_async(url).await {
+            Ok((websocket, _)) => {
+                stats.record_connection();
+                Some(Self { websocket })
+            }
+            Err(err) => {
+                stats.record_error(format!("connection failed: {}", err));
+                None
+            }
+        }
+    }
+
+    pub async fn send(&mut self, message: &ClientMessage, stats: &LoadStats) -> bool {
+        match CODEC.encode_frame(message) {
+            Ok(data) => self.send_raw(Message::Binary(data), stats).await,
+            Err(err) => {
+                stats.record_error(format!("message encode failed: {}", err));
+                false
+            }
+        }
+    }
+
+    /// Sends the message as is, returns `false` if the connection is closed.
+    pub async fn send_raw(&mut self, message: Message, stats: &LoadStats) -> bool {
+        match self.websocket.send(message).await {
+            Ok(()) => {
+                stats.record_sent();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Receives the next server message until the deadline,
+    /// returns `Err` if the connection is closed by the server.
+    pub async fn recv(
+        &mut self,
+        deadline: Instant,
+        stats: &LoadStats,
+    ) -> Result<Option<ServerMessage>, ConnectionClosed> {
+        use tokio::time::timeout_at;
+
+        loop {
+            let message = match timeout_at(deadline.into(), self.websocket.next()).await {
+                Ok(Some(Ok(message))) => message,
+                Ok(Some(Err(_))) | Ok(None) => return Err(ConnectionClosed),
+                Err(_) => return Ok(None),
+            };
+            match message {
+                Message::Binary(data) => match CODEC.decode_frame(&data, u32::MAX) {
+                    Ok(message) => return Ok(Some(message)),
+                    Err(err) => {
+                        stats.record_error(format!("server message decode failed: {}", err))
+                    }
+                },
+                Message::Close(_) => return Err(ConnectionClosed),
+                _ => {}
+            }
+        }
+    }
+
+    /// Receives messages until the predicate matches one of them or the timeout elapses.
+    pub async fn recv_until<F>(
+        &mut self,
+        timeout: Duration,
+        stats: &LoadStats,
+        mut predicate: F,
+    ) -> Result<Option<ServerMessage>, ConnectionClosed>
+    where
+        F: FnMut(&ServerMessage) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        while let Some(message) = self.recv(deadline, stats).await? {
+            if predicate(&message) {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn close(mut self) {
+        let _: Result<(), _> = self.websocket.close(None).await;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionClosed;
+
+/// Xorshift generator of the malformed traffic, reproducible with the same seed.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift never leaves the zero state.
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a number in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use signaling_protocol::{
+    ChannelId, ClientMessage, ClientSenderMessage, CloseReason, NetworkMode, ServerMessage,
+    ServerSenderMessage, SessionSenderId,
+};
+
+use crate::{Connection, LoadStats, LoadTestOptions};
+
+/// Time to wait for the channel open confirmation.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Opens a loopback channel and sends timestamped binary data through the server
+/// until the deadline, recording the round trip latencies of the echoed data.
+///
+/// Needs no receivers, so the relay path latency is measured with a single connection.
+pub async fn run_echoer(
+    opts: Arc<LoadTestOptions>,
+    stats: Arc<LoadStats>,
+    index: usize,
+    start: Instant,
+    deadline: Instant,
+) {
+    use core::convert::TryInto;
+    use tokio::time::{interval, MissedTickBehavior};
+
+    let sender_id = SessionSenderId(index as u64);
+    let mut connection = match Connection::connect(&opts.server, &stats).await {
+        Some(connection) => connection,
+        None => return,
+    };
+
+    let open = ClientMessage::SenderMessage {
+        sender_id,
+        message: ClientSenderMessage::OpenChannel {
+            channel_id: ChannelId(format!("loadtest-echo-{}", index)),
+            network_mode: NetworkMode::Loopback,
+            claim_token: None,
+            secret: None,
+            resume_token: None,
+        },
+    };
+    let open_timeout = OPEN_TIMEOUT.min(deadline.saturating_duration_since(Instant::now()));
+    let is_opened = connection.send(&open, &stats).await
+        && matches!(
+            connection
+                .recv_until(open_timeout, &stats, |message| matches!(
+                    message,
+                    ServerMessage::SenderMessage {
+                        message: ServerSenderMessage::OpenChannelSuccess,
+                        ..
+                    }
+                ))
+                .await,
+            Ok(Some(_))
+        );
+    if !is_opened {
+        // Setups interrupted by the test end are not failures.
+        if Instant::now() < deadline {
+            stats.record_error("echo channel open failed".to_owned());
+        }
+        return;
+    }
+
+    let mut ticks = interval(opts.relay_interval());
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    while Instant::now() < deadline {
+        let tick_deadline = Instant::now() + opts.relay_interval();
+        tokio::select! {
+            _ = ticks.tick() => {
+                let mut data = (start.elapsed().as_nanos() as u64).to_le_bytes().to_vec();
+                data.resize(opts.payload_size.max(data.len()), 0);
+                let message = ClientMessage::SenderMessage {
+                    sender_id,
+                    message: ClientSenderMessage::SendBinaryData(data),
+                };
+                if !connection.send(&message, &stats).await {
+                    stats.record_error("echoer disconnected".to_owned());
+                    return;
+                }
+            }
+            result = connection.recv(tick_deadline.min(deadline), &stats) => match result {
+                Ok(Some(ServerMessage::SenderMessage {
+                    message: ServerSenderMessage::BinaryDataEcho(data),
+                    ..
+                })) => match data.get(..8).and_then(|bytes| bytes.try_into().ok()) {
+                    Some(bytes) => {
+                        let sent_at = Duration::from_nanos(u64::from_le_bytes(bytes));
+                        stats.record_echoed(start.elapsed().saturating_sub(sent_at));
+                    }
+                    None => stats.record_error("echoed data is truncated".to_owned()),
+                },
+                Ok(_) => {}
+                Err(_) => {
+                    stats.record_error("echoer disconnected".to_owned());
+                    return;
+                }
+            }
+        }
+    }
+
+    let close = ClientMessage::SenderMessage {
+        sender_id,
+        message: ClientSenderMessage::CloseChannel(CloseReason::LocalClosed),
+    };
+    let _: bool = connection.send(&close, &stats).await;
+    connection.close().await;
+}
@@ -0,0 +1,32 @@
+#![warn(
+    clippy::all,
+    rust_2018_idioms,
+    missing_copy_implementations,
+    missing_debug_implementations,
+    single_use_lifetimes,
+    trivial_casts,
+    unused_import_braces,
+    unused_qualifications,
+    unused_results
+)]
+
+mod abuse;
+mod app;
+mod churn;
+mod connection;
+mod echo;
+mod relay;
+mod report;
+
+use abuse::run_abuser;
+use app::{app, LoadTestOptions};
+use churn::run_churner;
+use connection::{Connection, Rng};
+use echo::run_echoer;
+use relay::run_relay_pair;
+use report::LoadStats;
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    app().await
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use signaling_protocol::{
+    ChannelId, ClientMessage, ClientReceiverMessage, ClientSenderMessage, CloseReason, NetworkMode,
+    ServerMessage, ServerReceiverMessage, ServerSenderMessage, SessionReceiverId, SessionSenderId,
+};
+
+use crate::{Connection, LoadStats, LoadTestOptions};
+
+/// Time to wait for the channel open and join confirmations.
+const SETUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Opens a channel, joins it from another connection and relays timestamped binary data
+/// through the server until the deadline, recording the relay latencies.
+pub async fn run_relay_pair(
+    opts: Arc<LoadTestOptions>,
+    stats: Arc<LoadStats>,
+    index: usize,
+    start: Instant,
+    deadline: Instant,
+) {
+    let sender_id = SessionSenderId(index as u64);
+    let receiver_id = SessionReceiverId(index as u64);
+    let channel_id = ChannelId(format!("loadtest-relay-{}", index));
+
+    let mut sender = match Connection::connect(&opts.server, &stats).await {
+        Some(sender) => sender,
+        None => return,
+    };
+    let mut receiver = match Connection::connect(&opts.server, &stats).await {
+        Some(receiver) => receiver,
+        None => return,
+    };
+
+    let open = ClientMessage::SenderMessage {
+        sender_id,
+        message: ClientSenderMessage::OpenChannel {
+            channel_id: channel_id.clone(),
+            network_mode: NetworkMode::PeerToPeer,
+            claim_token: None,
+            secret: None,
+            resume_token: None,
+        },
+    };
+    let join = ClientMessage::ReceiverMessage {
+        receiver_id,
+        message: ClientReceiverMessage::JoinChannel {
+            channel_id,
+            credential: None,
+        },
+    };
+    let setup_timeout = SETUP_TIMEOUT.min(deadline.saturating_duration_since(Instant::now()));
+    let is_opened = sender.send(&open, &stats).await
+        && matches!(
+            sender
+                .recv_until(setup_timeout, &stats, |message| matches!(
+                    message,
+                    ServerMessage::SenderMessage {
+                        message: ServerSenderMessage::OpenChannelSuccess,
+                        ..
+                    }
+                ))
+                .await,
+            Ok(Some(_))
+        );
+    if !is_opened {
+        // Setups interrupted by the test end are not failures.
+        if Instant::now() < deadline {
+            stats.record_error("relay channel open failed".to_owned());
+        }
+        return;
+    }
+    let is_joined = receiver.send(&join, &stats).await
+        && matches!(
+            receiver
+                .recv_until(setup_timeout, &stats, |message| matches!(
+                    message,
+                    ServerMessage::ReceiverMessage {
+                        message: ServerReceiverMessage::JoinChannelSuccess,
+                        ..
+                    }
+                ))
+                .await,
+            Ok(Some(_))
+        );
+    if !is_joined {
+        if Instant::now() < deadline {
+            stats.record_error("relay channel join failed".to_owned());
+        }
+        return;
+    }
+
+    let sending = send_timestamps(&opts, &stats, sender, sender_id, start, deadline);
+    let receiving = receive_timestamps(&stats, receiver, start, deadline);
+    let (sender, ()) = futures::join!(sending, receiving);
+    if let Some(mut sender) = sender {
+        let close = ClientMessage::SenderMessage {
+            sender_id,
+            message: ClientSenderMessage::CloseChannel(CloseReason::LocalClosed),
+        };
+        let _: bool = sender.send(&close, &stats).await;
+        sender.close().await;
+    }
+}
+
+/// Sends the time since the test start in nanoseconds padded to the payload size,
+/// the server messages to the sender are drained meanwhile.
+async fn send_timestamps(
+    opts: &LoadTestOptions,
+    stats: &LoadStats,
+    mut sender: Connection,
+    sender_id: SessionSenderId,
+    start: Instant,
+    deadline: Instant,
+) -> Option<Connection> {
+    use tokio::time::{interval, MissedTickBehavior};
+
+    let mut ticks = interval(opts.relay_interval());
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    while Instant::now() < deadline {
+        let tick_deadline = Instant::now() + opts.relay_interval();
+        tokio::select! {
+            _ = ticks.tick() => {
+                let mut data = (start.elapsed().as_nanos() as u64).to_le_bytes().to_vec();
+                data.resize(opts.payload_size.max(data.len()), 0);
+                let message = ClientMessage::SenderMessage {
+                    sender_id,
+                    message: ClientSenderMessage::SendBinaryData(data),
+                };
+                if !sender.send(&message, stats).await {
+                    stats.record_error("relay sender disconnected".to_owned());
+                    return None;
+                }
+            }
+            result = sender.recv(tick_deadline.min(deadline), stats) => {
+                if result.is_err() {
+                    stats.record_error("relay sender disconnected".to_owned());
+                    return None;
+                }
+            }
+        }
+    }
+    Some(sender)
+}
+
+async fn receive_timestamps(
+    stats: &LoadStats,
+    mut receiver: Connection,
+    start: Instant,
+    deadline: Instant,
+) {
+    use core::convert::TryInto;
+
+    loop {
+        match receiver.recv(deadline, stats).await {
+            Ok(Some(ServerMessage::ReceiverMessage {
+                message: ServerReceiverMessage::BinaryData(data),
+                ..
+            })) => match data.get(..8).and_then(|bytes| bytes.try_into().ok()) {
+                Some(bytes) => {
+                    let sent_at = Duration::from_nanos(u64::from_le_bytes(bytes));
+                    stats.record_relayed(start.elapsed().saturating_sub(sent_at));
+                }
+                None => stats.record_error("relayed data is truncated".to_owned()),
+            },
+            Ok(Some(ServerMessage::ReceiverMessage {
+                message: ServerReceiverMessage::Error(err),
+                ..
+            })) => stats.record_error(format!("relay receiver error: {}", err)),
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(_) => {
+                stats.record_error("relay receiver disconnected".to_owned());
+                return;
+            }
+        }
+    }
+    receiver.close().await;
+}
@@ -0,0 +1,177 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Counters shared by all simulated clients.
+#[derive(Debug, Default)]
+pub struct LoadStats {
+    connections: AtomicU64,
+    sent: AtomicU64,
+    relayed: AtomicU64,
+    echoed: AtomicU64,
+    churn_cycles: AtomicU64,
+    abusive: AtomicU64,
+    abuser_disconnects: AtomicU64,
+    latencies: Mutex<Vec<Duration>>,
+    echo_latencies: Mutex<Vec<Duration>>,
+    errors: Mutex<BTreeMap<String, u64>>,
+}
+
+impl LoadStats {
+    pub fn record_connection(&self) {
+        let _: u64 = self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self) {
+        let _: u64 = self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_relayed(&self, latency: Duration) {
+        let _: u64 = self.relayed.fetch_add(1, Ordering::Relaxed);
+        self.latencies.lock().unwrap().push(latency);
+    }
+
+    /// Round trip of the data echoed back by a loopback channel.
+    pub fn record_echoed(&self, latency: Duration) {
+        let _: u64 = self.echoed.fetch_add(1, Ordering::Relaxed);
+        self.echo_latencies.lock().unwrap().push(latency);
+    }
+
+    pub fn record_churn_cycle(&self) {
+        let _: u64 = self.churn_cycles.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_abusive(&self) {
+        let _: u64 = self.abusive.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Expected for the abusive connections, they are not reported as errors.
+    pub fn record_abuser_disconnect(&self) {
+        let _: u64 = self.abuser_disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Unexpected failure, errors with the same description are counted together.
+    pub fn record_error(&self, description: String) {
+        *self.errors.lock().unwrap().entry(description).or_insert(0) += 1;
+    }
+
+    pub fn report(&self, elapsed: Duration) -> LoadReport {
+        LoadReport {
+            elapsed,
+            connections: self.connections.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            relayed: self.relayed.load(Ordering::Relaxed),
+            echoed: self.echoed.load(Ordering::Relaxed),
+            churn_cycles: self.churn_cycles.load(Ordering::Relaxed),
+            abusive: self.abusive.load(Ordering::Relaxed),
+            abuser_disconnects: self.abuser_disconnects.load(Ordering::Relaxed),
+            relay_latency: LatencySummary::new(self.latencies.lock().unwrap().clone()),
+            echo_latency: LatencySummary::new(self.echo_latencies.lock().unwrap().clone()),
+            errors: self.errors.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Percentiles of the recorded latencies, `None` if nothing was recorded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatencySummary {
+    pub p50: Option<Duration>,
+    pub p90: Option<Duration>,
+    pub p99: Option<Duration>,
+    pub max: Option<Duration>,
+}
+
+impl LatencySummary {
+    fn new(mut latencies: Vec<Duration>) -> Self {
+        latencies.sort_unstable();
+        let percentile = |percent: usize| {
+            latencies
+                .len()
+                .checked_sub(1)
+                .map(|last| latencies[last * percent / 100])
+        };
+        Self {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: latencies.last().copied(),
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+        for (percentile, latency) in [
+            ("p50", self.p50),
+            ("p90", self.p90),
+            ("p99", self.p99),
+            ("max", self.max),
+        ] {
+            let label = format!("{} latency {}:", name, percentile);
+            match latency {
+                Some(latency) => writeln!(f, "{:<21}{:.2?}", label, latency)?,
+                None => writeln!(f, "{:<21}-", label)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Summary of a finished load test.
+#[derive(Clone, Debug)]
+pub struct LoadReport {
+    pub elapsed: Duration,
+    pub connections: u64,
+    pub sent: u64,
+    pub relayed: u64,
+    pub echoed: u64,
+    pub churn_cycles: u64,
+    pub abusive: u64,
+    pub abuser_disconnects: u64,
+    pub relay_latency: LatencySummary,
+    pub echo_latency: LatencySummary,
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        writeln!(f, "duration:            {:.1?}", self.elapsed)?;
+        writeln!(f, "connections:         {}", self.connections)?;
+        writeln!(
+            f,
+            "messages sent:       {} ({:.0}/s)",
+            self.sent,
+            self.sent as f64 / secs
+        )?;
+        writeln!(
+            f,
+            "messages relayed:    {} ({:.0}/s)",
+            self.relayed,
+            self.relayed as f64 / secs
+        )?;
+        self.relay_latency.write(f, "relay")?;
+        writeln!(
+            f,
+            "messages echoed:     {} ({:.0}/s)",
+            self.echoed,
+            self.echoed as f64 / secs
+        )?;
+        self.echo_latency.write(f, "echo")?;
+        writeln!(f, "channel churn:       {} cycles", self.churn_cycles)?;
+        writeln!(
+            f,
+            "abusive messages:    {} ({} disconnects by the server)",
+            self.abusive, self.abuser_disconnects
+        )?;
+        if self.errors.is_empty() {
+            write!(f, "errors:              none")
+        } else {
+            writeln!(f, "errors:")?;
+            for (description, count) in &self.errors {
+                writeln!(f, "  {} x {}", count, description)?;
+            }
+            Ok(())
+        }
+    }
+}
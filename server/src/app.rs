@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
 use clap::{AppSettings, Clap};
 
+const MIB: u64 = 1024 * 1024;
+
 #[derive(Clap)]
 #[clap(
     version = env!("CARGO_PKG_VERSION"),
@@ -14,15 +18,94 @@ struct Options {
     /// Port number
     #[clap(short, long, default_value = "9010")]
     port: String,
+    /// Directory to record per channel signaling transcripts to as JSONL files,
+    /// the peer IP addresses, including the SDP and ICE candidate ones, are stored hashed
+    #[clap(long)]
+    transcripts_dir: Option<PathBuf>,
+    /// Directory to store the channel recordings uploaded by the senders to,
+    /// the recording is disabled if not set
+    #[clap(long)]
+    recordings_dir: Option<PathBuf>,
+    /// Maximum size of a single recording in MiB
+    #[clap(long, default_value = "1024")]
+    max_recording_size: u64,
+    /// Maximum size of all the recordings stored during the server run in MiB
+    #[clap(long, default_value = "10240")]
+    max_recordings_size: u64,
+    /// Directory of the built client to serve over HTTP on the same port
+    #[clap(long)]
+    static_dir: Option<PathBuf>,
+    /// Language of the error descriptions in the log: en or ru
+    #[clap(long, default_value = "en")]
+    language: String,
+    /// Seconds without any message, including keepalive pings, before a client is dropped,
+    /// 0 to keep silent clients connected
+    #[clap(long, default_value = "45")]
+    idle_timeout: u64,
+    /// Instance name to advertise the server under on the local network via mDNS,
+    /// for the native clients discovering the servers
+    #[clap(long)]
+    mdns_instance: Option<String>,
 }
 
 pub async fn app() -> anyhow::Result<()> {
-    use crate::Server;
+    use crate::{MdnsResponder, RecordingStore, Server};
+    use anyhow::anyhow;
+    use core::time::Duration;
+    use futures::future;
+    use log::error;
+    use signaling_protocol::{Language, MdnsAdvertisement};
+    use std::sync::Arc;
+    use tokio::signal::ctrl_c;
 
     env_logger::init();
     let opts: Options = Options::parse();
     let addr = format!("{}:{}", opts.address, opts.port);
-    let server = Server::new(addr).await?;
-    server.run().await;
+    let language = Language::from_tag(&opts.language)
+        .ok_or_else(|| anyhow!("unsupported language: {}", opts.language))?;
+    let idle_timeout =
+        Some(Duration::from_secs(opts.idle_timeout)).filter(|timeout| !timeout.is_zero());
+    let max_recording_size = opts.max_recording_size.saturating_mul(MIB);
+    let max_recordings_size = opts.max_recordings_size.saturating_mul(MIB);
+    let recording_store = opts
+        .recordings_dir
+        .map(|dir| RecordingStore::new(dir, max_recording_size, max_recordings_size));
+    let mdns_responder = match opts.mdns_instance {
+        Some(instance) => {
+            let port = &opts.port;
+            let port = port
+                .parse()
+                .map_err(|_| anyhow!("invalid port: {}", port))?;
+            Some(MdnsResponder::bind(MdnsAdvertisement::new(
+                instance, port,
+            )?)?)
+        }
+        None => None,
+    };
+    let server = Server::new(
+        addr,
+        opts.transcripts_dir,
+        recording_store,
+        opts.static_dir,
+        language,
+        idle_timeout,
+    )
+    .await?;
+    let mdns_responder = async {
+        match mdns_responder {
+            Some(mdns_responder) => mdns_responder.run().await,
+            None => future::pending().await,
+        }
+    };
+    tokio::select! {
+        () = Arc::clone(&server).run() => {}
+        () = mdns_responder => {}
+        result = ctrl_c() => {
+            if let Err(err) = result {
+                error!("signal handler error: {}", err);
+            }
+            server.shutdown().await;
+        }
+    }
     Ok(())
 }
@@ -1,21 +1,33 @@
-use std::sync::Weak;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::AtomicU32;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use signaling_protocol::{
-    ChannelId, IceCandidate, ServerReceiverMessage, ServerSenderMessage, SessionDescription,
-    SessionReceiverId, SessionSenderId,
+    ChannelId, ChannelInfo, ChannelPreview, ChannelSecret, ChannelStatus, CloseReason,
+    IceCandidate, NackReason, PeerCapabilities, PeerNetwork, PublicKey, QualityRequest,
+    ReceiverInfo, ResumeToken, ServerReceiverErrorMessage, ServerReceiverMessage,
+    ServerSenderMessage, SessionDescription, SessionReceiverId, SessionSenderId, ThrottleNotice,
+    Topic,
 };
 use tokio::sync::RwLock;
 
-use crate::SocketSender;
+use crate::{ChannelTranscript, RelayBudget, RelayThrottle, SocketSender};
 
 #[derive(Debug)]
 pub struct Channel {
-    pub channel_id: Weak<ChannelId>,
+    /// Changed when the channel is renamed, the server channel map owns the id.
+    pub channel_id: RwLock<Weak<ChannelId>>,
     pub sender: ChannelSender,
     pub kind: ChannelKind,
+    /// Receivers join the channel only with the same credential if it is set.
+    pub secret: Option<ChannelSecret>,
+    /// A reconnected sender takes the channel over with the same token.
+    pub resume_token: Option<ResumeToken>,
+    pub transcript: Option<ChannelTranscript>,
 }
 
-#[allow(dead_code)] // TODO: ClientServer implementation
 #[derive(Debug)]
 pub enum ChannelKind {
     PeerToPeer {
@@ -23,6 +35,10 @@ pub enum ChannelKind {
     },
     ClientServer {
         receivers: RwLock<Vec<Weak<ChannelReceiver>>>,
+        relay_budget: RelayBudget,
+        relay_throttle: RelayThrottle,
+        /// The sender binary data is also echoed back to the sender.
+        is_loopback: bool,
     },
 }
 
@@ -32,6 +48,14 @@ pub struct ChannelSender {
     pub session_sender_id: SessionSenderId,
     pub session_description: RwLock<Option<SessionDescription>>,
     pub ice_candidates: RwLock<ChannelIceCandidates>,
+    pub snapshot: RwLock<Option<Vec<u8>>>,
+    pub preview: RwLock<Option<ChannelPreview>>,
+    pub public_key: RwLock<Option<PublicKey>>,
+    pub capabilities: RwLock<Option<PeerCapabilities>>,
+    pub scheduled_start: RwLock<Option<Duration>>,
+    pub addr: SocketAddr,
+    /// The peer network is sent to the sender and the receiver on join.
+    pub is_peer_network_requested: RwLock<bool>,
 }
 
 #[derive(Debug)]
@@ -41,6 +65,13 @@ pub struct ChannelReceiver {
     pub session_receiver_id: SessionReceiverId,
     pub session_description: RwLock<Option<SessionDescription>>,
     pub ice_candidates: RwLock<ChannelIceCandidates>,
+    pub topics: RwLock<HashSet<Topic>>,
+    /// Relay cap of the client-server channel data sent to this receiver.
+    pub relay_budget: RelayBudget,
+    /// Incremented with every offer relayed to the receiver,
+    /// so the answer watchdog of a previous offer does not release it.
+    pub offer_generation: AtomicU32,
+    pub addr: SocketAddr,
 }
 
 #[derive(Debug)]
@@ -49,29 +80,244 @@ pub struct ChannelIceCandidates {
     pub all_sent: bool,
 }
 
+impl Channel {
+    pub async fn is_receiver_attached(&self, channel_receiver: &ChannelReceiver) -> bool {
+        match &self.kind {
+            ChannelKind::PeerToPeer { receiver } => receiver
+                .read()
+                .await
+                .as_ref()
+                .is_some_and(|receiver| receiver.as_ptr() == channel_receiver),
+            ChannelKind::ClientServer { receivers, .. } => receivers
+                .read()
+                .await
+                .iter()
+                .any(|receiver| receiver.as_ptr() == channel_receiver),
+        }
+    }
+
+    pub fn accepts_credential(&self, credential: Option<&ChannelSecret>) -> bool {
+        match &self.secret {
+            Some(secret) => credential == Some(secret),
+            None => true,
+        }
+    }
+
+    pub fn accepts_resume_token(&self, resume_token: Option<&ResumeToken>) -> bool {
+        self.resume_token.is_some() && self.resume_token.as_ref() == resume_token
+    }
+
+    /// Peer-to-peer channels are listed as open until a receiver joins them.
+    pub async fn is_open(&self) -> bool {
+        match &self.kind {
+            ChannelKind::PeerToPeer { receiver } => receiver.read().await.is_none(),
+            ChannelKind::ClientServer { .. } => true,
+        }
+    }
+
+    /// Scheduled channels are live since their sender sends the first offer.
+    /// Channels with a secret are listed without their preview.
+    pub async fn info(&self, channel_id: ChannelId) -> ChannelInfo {
+        let scheduled_start = *self.sender.scheduled_start.read().await;
+        let is_negotiated = self.sender.session_description.read().await.is_some();
+        let status = if scheduled_start.is_some() && !is_negotiated {
+            ChannelStatus::Scheduled
+        } else {
+            ChannelStatus::Live
+        };
+        ChannelInfo {
+            channel_id,
+            preview: self.public_preview().await,
+            status,
+            scheduled_start,
+        }
+    }
+
+    /// Returns the preview to list, `None` if the channel has a secret.
+    pub async fn public_preview(&self) -> Option<ChannelPreview> {
+        match &self.secret {
+            Some(_) => None,
+            None => self.sender.preview.read().await.clone(),
+        }
+    }
+
+    pub async fn joined_receivers(&self) -> Vec<Arc<ChannelReceiver>> {
+        match &self.kind {
+            ChannelKind::PeerToPeer { receiver } => receiver
+                .read()
+                .await
+                .as_ref()
+                .and_then(Weak::upgrade)
+                .into_iter()
+                .collect(),
+            ChannelKind::ClientServer { receivers, .. } => receivers
+                .read()
+                .await
+                .iter()
+                .filter_map(Weak::upgrade)
+                .collect(),
+        }
+    }
+
+    /// Detaches the joined receivers, the channel is no longer relayed to them.
+    pub async fn take_receivers(&self) -> Vec<Arc<ChannelReceiver>> {
+        use core::mem::take;
+
+        match &self.kind {
+            ChannelKind::PeerToPeer { receiver } => receiver
+                .write()
+                .await
+                .take()
+                .and_then(|receiver| receiver.upgrade())
+                .into_iter()
+                .collect(),
+            ChannelKind::ClientServer { receivers, .. } => take(&mut *receivers.write().await)
+                .iter()
+                .filter_map(Weak::upgrade)
+                .collect(),
+        }
+    }
+
+    /// Notifies the joined receivers that the channel is closed.
+    pub async fn send_channel_closed(&self, reason: CloseReason) {
+        for receiver in self.joined_receivers().await {
+            receiver.send_channel_closed(reason).await;
+        }
+    }
+
+    /// Notifies the joined receivers that the channel is renamed.
+    pub async fn send_channel_renamed(&self, channel_id: &ChannelId) {
+        for receiver in self.joined_receivers().await {
+            receiver.send_channel_renamed(channel_id.clone()).await;
+        }
+    }
+
+    /// Sends the server view of the sender and the receiver source addresses to both of them.
+    pub async fn send_peer_network(&self, receiver: &ChannelReceiver) {
+        let peer_network = PeerNetwork {
+            is_same_network: is_same_network(self.sender.addr.ip(), receiver.addr.ip()),
+        };
+        self.sender.send_peer_network(peer_network).await;
+        receiver.send_peer_network(peer_network).await;
+    }
+
+    /// Returns the listing of the closed channel if it had a scheduled start.
+    pub async fn ended_info(&self, channel_id: ChannelId) -> Option<ChannelInfo> {
+        let info = self.info(channel_id).await;
+        info.scheduled_start.map(|_| ChannelInfo {
+            status: ChannelStatus::Ended,
+            ..info
+        })
+    }
+}
+
 impl ChannelSender {
-    pub async fn send_answer(&self, sdp: SessionDescription) {
+    pub async fn send_peer_network(&self, peer_network: PeerNetwork) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_sender_message(
+                    self.session_sender_id,
+                    ServerSenderMessage::PeerNetwork(peer_network),
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_receiver_joined(&self, receiver_info: ReceiverInfo) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_sender_message(
+                    self.session_sender_id,
+                    ServerSenderMessage::ReceiverJoined { receiver_info },
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_receiver_left(&self, reason: CloseReason) {
         if let Some(socket_sender) = self.socket_sender.upgrade() {
             socket_sender
                 .send_sender_message(
                     self.session_sender_id,
-                    ServerSenderMessage::ChannelAnswer(sdp),
+                    ServerSenderMessage::ReceiverLeft(reason),
                 )
                 .await;
         }
     }
 
-    pub async fn send_ice_candidate(&self, ice: IceCandidate) {
+    pub async fn send_throttled(&self, notice: ThrottleNotice) {
         if let Some(socket_sender) = self.socket_sender.upgrade() {
             socket_sender
                 .send_sender_message(
                     self.session_sender_id,
-                    ServerSenderMessage::IceCandidate(ice),
+                    ServerSenderMessage::Throttled(notice),
                 )
                 .await;
         }
     }
 
+    pub async fn send_binary_data_echo(&self, data: Vec<u8>) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_sender_message(
+                    self.session_sender_id,
+                    ServerSenderMessage::BinaryDataEcho(data),
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_offer_relayed(&self) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_sender_message(self.session_sender_id, ServerSenderMessage::OfferRelayed)
+                .await;
+        }
+    }
+
+    pub async fn send_answer(&self, sdp: SessionDescription) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_sender_message(
+                self.session_sender_id,
+                ServerSenderMessage::ChannelAnswer(sdp),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
+    /// Relays the renegotiation offer of the receiver.
+    pub async fn send_offer(&self, sdp: SessionDescription) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_sender_message(
+                self.session_sender_id,
+                ServerSenderMessage::ChannelOffer(sdp),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
+    pub async fn send_ice_candidate(&self, ice: IceCandidate) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_sender_message(
+                self.session_sender_id,
+                ServerSenderMessage::IceCandidate(ice),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
     pub async fn send_all_ice_candidate_sent(&self) {
         if let Some(socket_sender) = self.socket_sender.upgrade() {
             socket_sender
@@ -82,31 +328,149 @@ impl ChannelSender {
                 .await;
         }
     }
+
+    pub async fn send_peer_public_key(&self, public_key: PublicKey) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_sender_message(
+                self.session_sender_id,
+                ServerSenderMessage::PeerPublicKey(public_key),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
+    pub async fn send_peer_capabilities(
+        &self,
+        capabilities: PeerCapabilities,
+    ) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_sender_message(
+                self.session_sender_id,
+                ServerSenderMessage::PeerCapabilities(capabilities),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
+    pub async fn send_quality_request(&self, request: QualityRequest) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_sender_message(
+                    self.session_sender_id,
+                    ServerSenderMessage::QualityRequested(request),
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_ice_candidates_request(&self) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_sender_message(
+                    self.session_sender_id,
+                    ServerSenderMessage::IceCandidatesRequested,
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_ice_restart_request(&self) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_sender_message(
+                    self.session_sender_id,
+                    ServerSenderMessage::IceRestartRequested,
+                )
+                .await;
+        }
+    }
 }
 
 impl ChannelReceiver {
-    pub async fn send_offer(&self, sdp: SessionDescription) {
+    /// Returns the channel if the receiver is still attached to it,
+    /// a receiver released by the answer watchdog no longer reaches the sender.
+    pub async fn attached_channel(&self) -> Result<Arc<Channel>, NackReason> {
+        let channel = self.channel.upgrade().ok_or(NackReason::PeerDisconnected)?;
+        if channel.is_receiver_attached(self).await {
+            Ok(channel)
+        } else {
+            Err(NackReason::SessionIsNotExist)
+        }
+    }
+
+    pub async fn send_join_channel_success(&self) {
         if let Some(socket_sender) = self.socket_sender.upgrade() {
             socket_sender
                 .send_receiver_message(
                     self.session_receiver_id,
-                    ServerReceiverMessage::ChannelOffer(sdp),
+                    ServerReceiverMessage::JoinChannelSuccess,
                 )
                 .await;
         }
     }
 
-    pub async fn send_ice_candidate(&self, ice: IceCandidate) {
+    pub async fn send_peer_network(&self, peer_network: PeerNetwork) {
         if let Some(socket_sender) = self.socket_sender.upgrade() {
             socket_sender
                 .send_receiver_message(
                     self.session_receiver_id,
-                    ServerReceiverMessage::IceCandidate(ice),
+                    ServerReceiverMessage::PeerNetwork(peer_network),
                 )
                 .await;
         }
     }
 
+    pub async fn send_offer(&self, sdp: SessionDescription) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_receiver_message(
+                self.session_receiver_id,
+                ServerReceiverMessage::ChannelOffer(sdp),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
+    /// Relays the sender answer to the renegotiation offer of the receiver.
+    pub async fn send_answer(&self, sdp: SessionDescription) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_receiver_message(
+                self.session_receiver_id,
+                ServerReceiverMessage::ChannelAnswer(sdp),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
+    pub async fn send_ice_candidate(&self, ice: IceCandidate) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_receiver_message(
+                self.session_receiver_id,
+                ServerReceiverMessage::IceCandidate(ice),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
     pub async fn send_all_ice_candidate_sent(&self) {
         if let Some(socket_sender) = self.socket_sender.upgrade() {
             socket_sender
@@ -118,6 +482,37 @@ impl ChannelReceiver {
         }
     }
 
+    pub async fn send_peer_public_key(&self, public_key: PublicKey) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_receiver_message(
+                self.session_receiver_id,
+                ServerReceiverMessage::PeerPublicKey(public_key),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
+    pub async fn send_peer_capabilities(
+        &self,
+        capabilities: PeerCapabilities,
+    ) -> Result<(), NackReason> {
+        let socket_sender = self
+            .socket_sender
+            .upgrade()
+            .ok_or(NackReason::PeerDisconnected)?;
+        socket_sender
+            .try_send_receiver_message(
+                self.session_receiver_id,
+                ServerReceiverMessage::PeerCapabilities(capabilities),
+            )
+            .await
+            .map_err(|err| NackReason::PeerSendFailed(err.to_string()))
+    }
+
     pub async fn send_offer_and_ice_candidates(
         &self,
         sdp: Option<&SessionDescription>,
@@ -151,6 +546,58 @@ impl ChannelReceiver {
         }
     }
 
+    pub async fn send_snapshot(&self, snapshot: Vec<u8>) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_receiver_message(
+                    self.session_receiver_id,
+                    ServerReceiverMessage::ChannelSnapshot(snapshot),
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_channel_closed(&self, reason: CloseReason) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_receiver_message(
+                    self.session_receiver_id,
+                    ServerReceiverMessage::ChannelClosed(reason),
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_channel_renamed(&self, channel_id: ChannelId) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_receiver_message(
+                    self.session_receiver_id,
+                    ServerReceiverMessage::ChannelRenamed(channel_id),
+                )
+                .await;
+        }
+    }
+
+    pub async fn send_error(&self, err: ServerReceiverErrorMessage) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_receiver_error(self.session_receiver_id, err)
+                .await;
+        }
+    }
+
+    pub async fn send_ice_candidates_request(&self) {
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_receiver_message(
+                    self.session_receiver_id,
+                    ServerReceiverMessage::IceCandidatesRequested,
+                )
+                .await;
+        }
+    }
+
     pub async fn send_binary_data(&self, data: Vec<u8>) {
         if let Some(socket_sender) = self.socket_sender.upgrade() {
             socket_sender
@@ -161,6 +608,28 @@ impl ChannelReceiver {
                 .await;
         }
     }
+
+    pub async fn is_subscribed(&self, topic: &Topic) -> bool {
+        self.topics.read().await.contains(topic)
+    }
+
+    /// Sends the data if the receiver is subscribed to its topic.
+    pub async fn send_topic_data(&self, topic: &Topic, data: &[u8]) {
+        if !self.is_subscribed(topic).await {
+            return;
+        }
+        if let Some(socket_sender) = self.socket_sender.upgrade() {
+            socket_sender
+                .send_receiver_message(
+                    self.session_receiver_id,
+                    ServerReceiverMessage::TopicData {
+                        topic: topic.clone(),
+                        data: data.to_vec(),
+                    },
+                )
+                .await;
+        }
+    }
 }
 
 impl ChannelIceCandidates {
@@ -171,3 +640,24 @@ impl ChannelIceCandidates {
         }
     }
 }
+
+/// Peers connecting from the same address are behind the same NAT or on the same host,
+/// peers connecting from private addresses reach the server on their LAN.
+pub fn is_same_network(sender: IpAddr, receiver: IpAddr) -> bool {
+    fn is_private(addr: IpAddr) -> bool {
+        match addr {
+            IpAddr::V4(addr) => addr.is_private() || addr.is_loopback() || addr.is_link_local(),
+            IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+                Some(addr) => is_private(IpAddr::V4(addr)),
+                None => {
+                    // Unique local `fc00::/7` and link-local `fe80::/10` addresses.
+                    addr.is_loopback()
+                        || addr.segments()[0] & 0xfe00 == 0xfc00
+                        || addr.segments()[0] & 0xffc0 == 0xfe80
+                }
+            },
+        }
+    }
+
+    sender == receiver || is_private(sender) && is_private(receiver)
+}
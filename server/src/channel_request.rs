@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use signaling_protocol::{ChannelRequestErrorMessage, ChannelRequestId, PeerId};
+use tokio::sync::Mutex;
+
+use crate::SocketSender;
+
+/// Time for the requested client to accept or decline a channel request.
+pub const CHANNEL_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Clients registered by peer id and the channel requests addressed to them,
+/// see `ClientMessage::RequestChannel`.
+#[derive(Debug, Default)]
+pub struct ChannelRequests {
+    peers: Mutex<HashMap<PeerId, Weak<SocketSender>>>,
+    requests: Mutex<HashMap<ChannelRequestId, ChannelRequest>>,
+    next_request_id: AtomicU64,
+}
+
+#[derive(Debug)]
+struct ChannelRequest {
+    requester: Weak<SocketSender>,
+    /// Id chosen by the requester, the map key is the id seen by the requested client.
+    requester_request_id: ChannelRequestId,
+    target: Weak<SocketSender>,
+    expires_at: Instant,
+}
+
+/// Pending channel request delivered to the requested client.
+#[derive(Debug)]
+pub struct IncomingChannelRequest {
+    pub target: Arc<SocketSender>,
+    pub request_id: ChannelRequestId,
+    pub from: Option<PeerId>,
+}
+
+impl ChannelRequests {
+    /// Registers the peer id, ids of disconnected clients can be taken over.
+    pub async fn register_peer(
+        &self,
+        peer_id: PeerId,
+        socket_sender: &Arc<SocketSender>,
+    ) -> Result<(), ChannelRequestErrorMessage> {
+        let mut peers = self.peers.lock().await;
+        peers.retain(|_, peer| peer.strong_count() > 0);
+        match peers.get(&peer_id).and_then(Weak::upgrade) {
+            Some(peer) if !Arc::ptr_eq(&peer, socket_sender) => {
+                Err(ChannelRequestErrorMessage::PeerIdIsAlreadyUsed)
+            }
+            _ => {
+                let _: Option<_> = peers.insert(peer_id, Arc::downgrade(socket_sender));
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes the peer ids and the requests of the disconnected client.
+    pub async fn unregister_client(&self, socket_sender: &SocketSender) {
+        let is_other = |peer: &Weak<SocketSender>| !core::ptr::eq(peer.as_ptr(), socket_sender);
+        self.peers.lock().await.retain(|_, peer| is_other(peer));
+        self.requests
+            .lock()
+            .await
+            .retain(|_, request| is_other(&request.requester) && is_other(&request.target));
+    }
+
+    /// Records the request addressed to the registered peer,
+    /// returns the requested client with the request id it should see.
+    pub async fn request(
+        &self,
+        requester: &Arc<SocketSender>,
+        requester_request_id: ChannelRequestId,
+        peer_id: &PeerId,
+    ) -> Result<IncomingChannelRequest, ChannelRequestErrorMessage> {
+        let peers = self.peers.lock().await;
+        let target = peers
+            .get(peer_id)
+            .and_then(Weak::upgrade)
+            .ok_or(ChannelRequestErrorMessage::PeerIsNotFound)?;
+        let from = peers
+            .iter()
+            .find(|(_, peer)| core::ptr::eq(peer.as_ptr(), Arc::as_ptr(requester)))
+            .map(|(peer_id, _)| peer_id.clone());
+        drop(peers);
+
+        let request_id = ChannelRequestId(self.next_request_id.fetch_add(1, Ordering::Relaxed));
+        let mut requests = self.requests.lock().await;
+        remove_expired(&mut requests);
+        let _: Option<_> = requests.insert(
+            request_id,
+            ChannelRequest {
+                requester: Arc::downgrade(requester),
+                requester_request_id,
+                target: Arc::downgrade(&target),
+                expires_at: Instant::now() + CHANNEL_REQUEST_TIMEOUT,
+            },
+        );
+        Ok(IncomingChannelRequest {
+            target,
+            request_id,
+            from,
+        })
+    }
+
+    /// Consumes the request answered by the requested client,
+    /// returns the connected requester with its own request id.
+    pub async fn answer(
+        &self,
+        target: &SocketSender,
+        request_id: ChannelRequestId,
+    ) -> Result<(Arc<SocketSender>, ChannelRequestId), ChannelRequestErrorMessage> {
+        use std::collections::hash_map::Entry;
+
+        let mut requests = self.requests.lock().await;
+        remove_expired(&mut requests);
+        match requests.entry(request_id) {
+            Entry::Occupied(entry) if core::ptr::eq(entry.get().target.as_ptr(), target) => {
+                let request = entry.remove();
+                let requester = request
+                    .requester
+                    .upgrade()
+                    .ok_or(ChannelRequestErrorMessage::RequestIsNotFound)?;
+                Ok((requester, request.requester_request_id))
+            }
+            _ => Err(ChannelRequestErrorMessage::RequestIsNotFound),
+        }
+    }
+}
+
+fn remove_expired(requests: &mut HashMap<ChannelRequestId, ChannelRequest>) {
+    let now = Instant::now();
+    requests.retain(|_, request| request.expires_at > now);
+}
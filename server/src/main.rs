@@ -12,17 +12,35 @@
 
 mod app;
 mod channel;
+mod channel_request;
+mod mdns_responder;
+mod recording;
+mod relay_limits;
+mod reservation;
 mod server;
 mod server_data;
+mod session_stats;
 mod socket;
 mod socket_sender;
+mod socket_stats;
+mod static_files;
+mod transcript;
 
 use app::app;
 use channel::{Channel, ChannelIceCandidates, ChannelKind, ChannelReceiver, ChannelSender};
+use channel_request::ChannelRequests;
+use mdns_responder::MdnsResponder;
+use recording::{ChannelRecording, RecordingStore};
+use relay_limits::{RelayBudget, RelayStats, RelayThrottle};
+use reservation::ChannelReservations;
 use server::Server;
 use server_data::ServerData;
+use session_stats::{SessionRole, SessionStats};
 use socket::{Socket, SocketId};
-use socket_sender::SocketSender;
+use socket_sender::{SocketSender, WebSocketSink};
+use socket_stats::{SessionEnd, SocketStats};
+use static_files::StaticFiles;
+use transcript::{ChannelTranscript, TranscriptEvent, TranscriptRecorder};
 
 #[tokio::main]
 pub async fn main() -> anyhow::Result<()> {
@@ -0,0 +1,76 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+
+use signaling_protocol::MdnsAdvertisement;
+use tokio::net::UdpSocket;
+
+/// Advertises the server on the local network, so that the native clients
+/// find it with `signaling_protocol::discover_servers`.
+#[derive(Debug)]
+pub struct MdnsResponder {
+    advertisement: MdnsAdvertisement,
+    socket: UdpSocket,
+}
+
+impl MdnsResponder {
+    /// Shares the mDNS port with the other responders of the host.
+    pub fn bind(advertisement: MdnsAdvertisement) -> io::Result<Self> {
+        use signaling_protocol::{MDNS_ADDR, MDNS_PORT};
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+        socket.join_multicast_v4(&MDNS_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+        socket.set_multicast_loop_v4(true)?;
+        let socket = UdpSocket::from_std(socket.into())?;
+
+        Ok(Self {
+            advertisement,
+            socket,
+        })
+    }
+
+    pub async fn run(self) {
+        use log::{debug, error, info};
+        use signaling_protocol::{is_mdns_query, MDNS_ADDR, MDNS_PORT};
+
+        let multicast_addr = SocketAddr::from((MDNS_ADDR, MDNS_PORT));
+        let response = self.advertisement.response();
+        info!(
+            "advertising {:?} on port {} via mDNS",
+            self.advertisement.instance(),
+            self.advertisement.port()
+        );
+        if let Err(err) = self.socket.send_to(&response, multicast_addr).await {
+            error!("mDNS announcement error: {}", err);
+        }
+
+        let mut buf = [0; 9000];
+        loop {
+            let (len, source) = match self.socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(err) => {
+                    error!("mDNS receive error: {}", err);
+                    continue;
+                }
+            };
+            if !is_mdns_query(&buf[..len]) {
+                continue;
+            }
+            // Queries from other ports than the mDNS one are answered directly (RFC 6762).
+            let addr = if source.port() == MDNS_PORT {
+                multicast_addr
+            } else {
+                source
+            };
+            debug!("mDNS query from {}", source);
+            if let Err(err) = self.socket.send_to(&response, addr).await {
+                error!("mDNS response to {} error: {}", addr, err);
+            }
+        }
+    }
+}
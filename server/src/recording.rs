@@ -0,0 +1,140 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use signaling_protocol::{ChannelId, RecordingErrorMessage};
+use tokio::fs::File;
+
+/// Stores the `MediaRecorder` chunks uploaded by the channel senders in the directory,
+/// one file per recording.
+#[derive(Debug)]
+pub struct RecordingStore {
+    dir: PathBuf,
+    max_recording_size: u64,
+    max_total_size: u64,
+    /// Bytes stored by all the recordings of this server run,
+    /// the files stored by the previous runs are not counted.
+    total_size: Arc<AtomicU64>,
+}
+
+/// Recording of a single channel, started and stopped by its sender.
+#[derive(Debug)]
+pub struct ChannelRecording {
+    file: File,
+    path: PathBuf,
+    size: u64,
+    max_size: u64,
+    max_total_size: u64,
+    total_size: Arc<AtomicU64>,
+}
+
+impl RecordingStore {
+    pub fn new(dir: PathBuf, max_recording_size: u64, max_total_size: u64) -> Self {
+        Self {
+            dir,
+            max_recording_size,
+            max_total_size,
+            total_size: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub async fn create(
+        &self,
+        channel_id: &ChannelId,
+        mime_type: &str,
+    ) -> Result<ChannelRecording, RecordingErrorMessage> {
+        use crate::transcript::create_channel_file;
+        use log::error;
+
+        if self.total_size.load(Ordering::Relaxed) >= self.max_total_size {
+            return Err(RecordingErrorMessage::RecordingQuotaIsExceeded);
+        }
+        match create_channel_file(&self.dir, channel_id, file_extension(mime_type)).await {
+            Ok((path, file)) => Ok(ChannelRecording {
+                file,
+                path,
+                size: 0,
+                max_size: self.max_recording_size,
+                max_total_size: self.max_total_size,
+                total_size: Arc::clone(&self.total_size),
+            }),
+            Err(err) => {
+                error!(
+                    "channel {:?} recording create error in {}: {}",
+                    channel_id,
+                    self.dir.display(),
+                    err
+                );
+                Err(RecordingErrorMessage::RecordingStorageFailed)
+            }
+        }
+    }
+}
+
+impl ChannelRecording {
+    /// Appends the chunk, the chunks exceeding the quotas are not stored.
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), RecordingErrorMessage> {
+        use log::error;
+        use tokio::io::AsyncWriteExt;
+
+        let len = chunk.len() as u64;
+        if self.size + len > self.max_size {
+            return Err(RecordingErrorMessage::RecordingQuotaIsExceeded);
+        }
+        let max_total_size = self.max_total_size;
+        let reserved =
+            self.total_size
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total_size| {
+                    Some(total_size + len).filter(|&total_size| total_size <= max_total_size)
+                });
+        if reserved.is_err() {
+            return Err(RecordingErrorMessage::RecordingQuotaIsExceeded);
+        }
+        match self.file.write_all(chunk).await {
+            Ok(()) => {
+                self.size += len;
+                Ok(())
+            }
+            Err(err) => {
+                let _: u64 = self.total_size.fetch_sub(len, Ordering::Relaxed);
+                error!("recording {} write error: {}", self.path.display(), err);
+                Err(RecordingErrorMessage::RecordingStorageFailed)
+            }
+        }
+    }
+
+    /// Flushes the stored chunks, returns the recording size.
+    pub async fn finish(mut self) -> u64 {
+        use log::{error, info};
+        use tokio::io::AsyncWriteExt;
+
+        if let Err(err) = self.file.flush().await {
+            error!("recording {} flush error: {}", self.path.display(), err);
+        }
+        info!(
+            "recording {} finished, {} bytes",
+            self.path.display(),
+            self.size
+        );
+        self.size
+    }
+}
+
+/// File extension of the container, e.g. `webm` for `video/webm;codecs=vp8,opus`.
+fn file_extension(mime_type: &str) -> &str {
+    let subtype = mime_type
+        .split(';')
+        .next()
+        .and_then(|essence| essence.split('/').nth(1))
+        .unwrap_or_default()
+        .trim();
+    match subtype {
+        "x-matroska" => "mkv",
+        "webm" | "mp4" | "ogg" => subtype,
+        _ => "bin",
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use signaling_protocol::{ThrottleNotice, ThrottleScope};
+use tokio::sync::Mutex;
+
+/// Bytes per second relayed by a client-server channel, counted once for each target receiver.
+pub const CHANNEL_RELAY_RATE: u64 = 8 * 1024 * 1024;
+
+/// Bytes a client-server channel can relay at once after being idle.
+pub const CHANNEL_RELAY_BURST: u64 = 16 * 1024 * 1024;
+
+/// Bytes per second relayed to a single client-server channel receiver.
+pub const RECEIVER_RELAY_RATE: u64 = 1024 * 1024;
+
+/// Bytes a single receiver can be relayed at once after being idle.
+pub const RECEIVER_RELAY_BURST: u64 = 2 * 1024 * 1024;
+
+/// Minimum time between the throttle notices sent to a sender.
+const THROTTLE_NOTICE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Token bucket of relayed bytes refilled at the rate up to the burst allowance.
+///
+/// Data larger than the burst allowance is never relayed.
+#[derive(Debug)]
+pub struct RelayBudget {
+    rate: u64,
+    burst: u64,
+    state: Mutex<RelayBudgetState>,
+}
+
+#[derive(Debug)]
+struct RelayBudgetState {
+    available: u64,
+    updated_at: Instant,
+}
+
+/// Dropped data of a channel, reported to its sender at most once per notice interval.
+#[derive(Debug, Default)]
+pub struct RelayThrottle {
+    state: Mutex<RelayThrottleState>,
+}
+
+#[derive(Debug, Default)]
+struct RelayThrottleState {
+    pending: Option<ThrottleNotice>,
+    notified_at: Option<Instant>,
+}
+
+/// Server-wide totals of the client-server relay.
+#[derive(Debug, Default)]
+pub struct RelayStats {
+    relayed_bytes: AtomicU64,
+    channel_dropped_bytes: AtomicU64,
+    receiver_dropped_bytes: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RelayTotals {
+    pub relayed_bytes: u64,
+    pub channel_dropped_bytes: u64,
+    pub receiver_dropped_bytes: u64,
+}
+
+impl RelayBudget {
+    pub fn new(rate: u64, burst: u64) -> Self {
+        Self {
+            rate,
+            burst,
+            state: Mutex::new(RelayBudgetState {
+                available: burst,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn channel() -> Self {
+        Self::new(CHANNEL_RELAY_RATE, CHANNEL_RELAY_BURST)
+    }
+
+    pub fn receiver() -> Self {
+        Self::new(RECEIVER_RELAY_RATE, RECEIVER_RELAY_BURST)
+    }
+
+    /// Takes the bytes from the budget,
+    /// returns the time until they could be taken if the budget is exceeded.
+    pub async fn take(&self, bytes: u64) -> Result<(), Duration> {
+        let mut state = self.state.lock().await;
+        self.refill(&mut state, Instant::now());
+        if state.available >= bytes {
+            state.available -= bytes;
+            Ok(())
+        } else {
+            let missing = bytes.min(self.burst) - state.available;
+            Err(Duration::from_secs_f64(missing as f64 / self.rate as f64))
+        }
+    }
+
+    /// Returns the bytes of the burst allowance used at the moment.
+    pub async fn used(&self) -> u64 {
+        let mut state = self.state.lock().await;
+        self.refill(&mut state, Instant::now());
+        self.burst - state.available
+    }
+
+    pub fn burst(&self) -> u64 {
+        self.burst
+    }
+
+    fn refill(&self, state: &mut RelayBudgetState, now: Instant) {
+        let elapsed = now.saturating_duration_since(state.updated_at);
+        let refilled = (elapsed.as_secs_f64() * self.rate as f64) as u64;
+        // Short intervals are accumulated until they refill at least a byte.
+        if refilled > 0 {
+            state.available = state.available.saturating_add(refilled).min(self.burst);
+            state.updated_at = now;
+        }
+    }
+}
+
+impl RelayThrottle {
+    /// Records the dropped data,
+    /// returns the notice to send if the previous one was sent long enough ago.
+    pub async fn record(
+        &self,
+        scope: ThrottleScope,
+        dropped_bytes: u64,
+        retry_after: Duration,
+    ) -> Option<ThrottleNotice> {
+        let now = Instant::now();
+        let mut state = self.state.lock().await;
+        let notice = match state.pending.take() {
+            Some(pending) => ThrottleNotice {
+                scope: match (pending.scope, scope) {
+                    (ThrottleScope::Receivers, ThrottleScope::Receivers) => {
+                        ThrottleScope::Receivers
+                    }
+                    _ => ThrottleScope::Channel,
+                },
+                dropped_bytes: pending.dropped_bytes.saturating_add(dropped_bytes),
+                retry_after: pending.retry_after.max(retry_after),
+            },
+            None => ThrottleNotice {
+                scope,
+                dropped_bytes,
+                retry_after,
+            },
+        };
+        match state.notified_at {
+            Some(notified_at) if now.duration_since(notified_at) < THROTTLE_NOTICE_INTERVAL => {
+                state.pending = Some(notice);
+                None
+            }
+            _ => {
+                state.notified_at = Some(now);
+                Some(notice)
+            }
+        }
+    }
+}
+
+impl RelayStats {
+    pub fn record_relayed(&self, bytes: u64) {
+        let _: u64 = self.relayed_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, scope: ThrottleScope, bytes: u64) {
+        let counter = match scope {
+            ThrottleScope::Channel => &self.channel_dropped_bytes,
+            ThrottleScope::Receivers => &self.receiver_dropped_bytes,
+            // Only decoded from the newer peers, the relay never drops data with it.
+            ThrottleScope::Unknown => return,
+        };
+        let _: u64 = counter.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn totals(&self) -> RelayTotals {
+        RelayTotals {
+            relayed_bytes: self.relayed_bytes.load(Ordering::Relaxed),
+            channel_dropped_bytes: self.channel_dropped_bytes.load(Ordering::Relaxed),
+            receiver_dropped_bytes: self.receiver_dropped_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
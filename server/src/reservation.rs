@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use signaling_protocol::{ChannelId, ClaimToken, ReservationErrorMessage};
+use tokio::sync::Mutex;
+
+use crate::SocketId;
+
+/// Time for the reserving client to open the reserved channel.
+pub const RESERVATION_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Reservations a single client may hold, so it cannot squat arbitrary channel ids.
+pub const MAX_CLIENT_RESERVATIONS: usize = 8;
+
+/// Reservations of all clients, also bounds the expired reservation cleanup.
+const MAX_RESERVATIONS: usize = 10_000;
+
+/// Channel ids reserved ahead of opening, see `ClientMessage::ReserveChannel`.
+#[derive(Debug, Default)]
+pub struct ChannelReservations {
+    reservations: Mutex<HashMap<ChannelId, Reservation>>,
+}
+
+#[derive(Debug)]
+struct Reservation {
+    claim_token: ClaimToken,
+    expires_at: Instant,
+    /// The reservation is dropped when the reserving client disconnects.
+    socket_id: SocketId,
+}
+
+impl ChannelReservations {
+    /// Reserves the channel id, the caller checks that the channel is not open.
+    pub async fn reserve(
+        &self,
+        channel_id: ChannelId,
+        socket_id: SocketId,
+    ) -> Result<ClaimToken, ReservationErrorMessage> {
+        use std::collections::hash_map::Entry;
+
+        let mut reservations = self.reservations.lock().await;
+        remove_expired(&mut reservations);
+        let client_reservations = reservations
+            .values()
+            .filter(|reservation| reservation.socket_id == socket_id)
+            .count();
+        if client_reservations >= MAX_CLIENT_RESERVATIONS || reservations.len() >= MAX_RESERVATIONS
+        {
+            return Err(ReservationErrorMessage::ReservationLimitIsReached);
+        }
+        match reservations.entry(channel_id) {
+            Entry::Occupied(_) => Err(ReservationErrorMessage::ChannelIsAlreadyReserved),
+            Entry::Vacant(entry) => {
+                let claim_token = new_claim_token();
+                let _: &mut _ = entry.insert(Reservation {
+                    claim_token: claim_token.clone(),
+                    expires_at: Instant::now() + RESERVATION_DURATION,
+                    socket_id,
+                });
+                Ok(claim_token)
+            }
+        }
+    }
+
+    /// Returns `false` if the channel id is reserved with another token,
+    /// a matching reservation is consumed.
+    pub async fn claim(&self, channel_id: &ChannelId, claim_token: Option<&ClaimToken>) -> bool {
+        let mut reservations = self.reservations.lock().await;
+        remove_expired(&mut reservations);
+        match reservations.get(channel_id) {
+            None => true,
+            Some(reservation) if Some(&reservation.claim_token) == claim_token => {
+                drop(reservations.remove(channel_id));
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Drops the reservations of the disconnected client.
+    pub async fn release(&self, socket_id: SocketId) {
+        self.reservations
+            .lock()
+            .await
+            .retain(|_, reservation| reservation.socket_id != socket_id);
+    }
+}
+
+fn remove_expired(reservations: &mut HashMap<ChannelId, Reservation>) {
+    let now = Instant::now();
+    reservations.retain(|_, reservation| reservation.expires_at > now);
+}
+
+/// Returns 16 bytes from the OS random source as hex,
+/// the token is the only proof of the channel reservation.
+fn new_claim_token() -> ClaimToken {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).expect("OS random source is unavailable");
+    ClaimToken(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
@@ -1,50 +1,173 @@
 use core::sync::atomic::AtomicU32;
+use core::time::Duration;
 use std::io;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use log::info;
+use signaling_protocol::Language;
 use thiserror::Error;
 use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch};
 
-use crate::ServerData;
+use crate::{RecordingStore, ServerData, StaticFiles, TranscriptRecorder};
 
 #[derive(Debug)]
 pub struct Server {
     listener: TcpListener,
     data: Arc<ServerData>,
+    static_files: Option<Arc<StaticFiles>>,
     next_socket_id: AtomicU32,
+    /// Language of the errors reported to the clients as written to the log.
+    log_language: Language,
+    /// Clients that send nothing for this time are dropped, `None` to keep them.
+    idle_timeout: Option<Duration>,
+    shutdown_sender: watch::Sender<bool>,
+    /// Kept so that the shutdown flag is stored even if no connection task is running.
+    shutdown_receiver: watch::Receiver<bool>,
+    /// Cloned into every connection task and dropped on shutdown,
+    /// so that the receiver is closed once all the tasks are finished.
+    task_guard: Mutex<Option<mpsc::Sender<()>>>,
+    tasks_finished: tokio::sync::Mutex<mpsc::Receiver<()>>,
 }
 
 impl Server {
-    pub async fn new<Address: AsRef<str>>(addr: Address) -> Result<Arc<Self>, NewServerError> {
+    pub async fn new<Address: AsRef<str>>(
+        addr: Address,
+        transcripts_dir: Option<PathBuf>,
+        recording_store: Option<RecordingStore>,
+        static_dir: Option<PathBuf>,
+        log_language: Language,
+        idle_timeout: Option<Duration>,
+    ) -> Result<Arc<Self>, NewServerError> {
         let listener = TcpListener::bind(addr.as_ref()).await?;
         info!("started on address: {}", addr.as_ref());
-        let data = Arc::new(ServerData::new());
+        if let Some(transcripts_dir) = &transcripts_dir {
+            info!(
+                "recording signaling transcripts to: {}",
+                transcripts_dir.display()
+            );
+        }
+        if let Some(recording_store) = &recording_store {
+            info!("storing recordings to: {}", recording_store.dir().display());
+        }
+        if let Some(static_dir) = &static_dir {
+            info!("serving static files from: {}", static_dir.display());
+        }
+        let transcript_recorder = transcripts_dir.map(TranscriptRecorder::new);
+        let data = Arc::new(ServerData::new(transcript_recorder, recording_store));
+        let static_files = static_dir.map(|static_dir| Arc::new(StaticFiles::new(static_dir)));
         let next_socket_id = AtomicU32::new(0);
+        let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+        let (task_guard, tasks_finished) = mpsc::channel(1);
 
         Ok(Arc::new(Self {
             listener,
             data,
+            static_files,
             next_socket_id,
+            log_language,
+            idle_timeout,
+            shutdown_sender,
+            shutdown_receiver,
+            task_guard: Mutex::new(Some(task_guard)),
+            tasks_finished: tokio::sync::Mutex::new(tasks_finished),
         }))
     }
 
+    /// Stops accepting new connections and closes the client sessions
+    /// with `ServerMessage::ServerShutdown` followed by a WebSocket close frame.
+    ///
+    /// Resolves when all the connection tasks are finished.
+    pub async fn shutdown(&self) {
+        info!("shutting down");
+        let _: Result<(), _> = self.shutdown_sender.send(true);
+        drop(self.task_guard.lock().unwrap().take());
+        let _: Option<()> = self.tasks_finished.lock().await.recv().await;
+        info!("all sessions are closed");
+    }
+
     pub async fn run(self: Arc<Self>) {
         use crate::{Socket, SocketId};
         use core::sync::atomic::Ordering;
+        use log::{debug, info};
         use tokio::spawn;
-        use tokio::task::JoinHandle;
 
-        while let Ok((stream, addr)) = self.listener.accept().await {
+        let mut shutdown = self.shutdown_receiver.clone();
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+            let (stream, addr) = tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(_) => break,
+                },
+                _ = shutdown.changed() => continue,
+            };
+            let task_guard = match self.task_guard.lock().unwrap().clone() {
+                Some(task_guard) => task_guard,
+                None => break,
+            };
+            let session_shutdown = self.shutdown_receiver.clone();
             let data = Arc::clone(&self.data);
+            let log_language = self.log_language;
+            let idle_timeout = self.idle_timeout;
+            let static_files = self.static_files.clone();
             let socket_id = SocketId(self.next_socket_id.fetch_add(1, Ordering::Relaxed));
-            let _: JoinHandle<()> = spawn(async move {
-                let session = Socket::new(socket_id, Arc::clone(&data), stream, addr)
-                    .await
-                    .unwrap();
-                Socket::run(session).await;
-                data.update_open_channel_ids().await;
-            });
+            drop(spawn(async move {
+                let _task_guard = task_guard;
+                let handshake = async {
+                    if let Some(static_files) = static_files {
+                        match StaticFiles::is_websocket_request(&stream).await {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                if let Err(err) = static_files.serve(stream).await {
+                                    debug!("static file request from {} error: {}", addr, err);
+                                }
+                                return None;
+                            }
+                            Err(err) => {
+                                debug!("request from {} error: {}", addr, err);
+                                return None;
+                            }
+                        }
+                    }
+                    match Socket::new(socket_id, Arc::clone(&data), stream, addr, log_language)
+                        .await
+                    {
+                        Ok(session) => Some(session),
+                        Err(err) => {
+                            data.socket_stats().record_handshake_failure();
+                            info!("session handshake failed: {}, {}", addr, err);
+                            None
+                        }
+                    }
+                };
+                // A client that connected but has not completed its request
+                // does not see the shutdown in its session yet.
+                let session = tokio::select! {
+                    session = handshake => session,
+                    () = shutdown_signaled(session_shutdown.clone()) => None,
+                };
+                let (session, socket_receiver) = match session {
+                    Some(session) => session,
+                    None => return,
+                };
+                session
+                    .run(socket_receiver, session_shutdown, idle_timeout)
+                    .await;
+                data.update_open_channels().await;
+            }));
+        }
+    }
+}
+
+/// Resolves once the shutdown is signaled or the server is dropped.
+async fn shutdown_signaled(mut shutdown: watch::Receiver<bool>) {
+    while !*shutdown.borrow() {
+        if shutdown.changed().await.is_err() {
+            return;
         }
     }
 }
@@ -54,3 +177,123 @@ pub enum NewServerError {
     #[error("TcpListener bind error: {0}")]
     BindTcpListenerError(#[from] io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+    use signaling_protocol::{ClientMessage, Language, ServerMessage, WireCodec};
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+    use tokio_tungstenite::tungstenite::protocol::Message;
+
+    use super::Server;
+
+    #[tokio::test]
+    async fn shutdown_notifies_and_closes_connected_clients() {
+        let server = Server::new("127.0.0.1:0", None, None, None, Language::English, None)
+            .await
+            .unwrap();
+        let addr = server.listener.local_addr().unwrap();
+        let _server_task = tokio::spawn(Arc::clone(&server).run());
+
+        let url = format!("ws://{}", addr);
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        // The session is registered once the first channel list is received.
+        let message = client.next().await.unwrap().unwrap();
+        assert!(matches!(message, Message::Binary(_)));
+
+        server.shutdown().await;
+
+        let mut messages = Vec::new();
+        while let Some(Ok(message)) = client.next().await {
+            messages.push(message);
+        }
+        let shutdown: ServerMessage = match &messages[..] {
+            [Message::Binary(data), Message::Close(Some(frame))] => {
+                assert_eq!(frame.code, CloseCode::Away);
+                WireCodec::DEFAULT
+                    .decode_frame(&data[..], u32::MAX)
+                    .unwrap()
+            }
+            _ => panic!("unexpected messages: {:?}", messages),
+        };
+        assert_eq!(shutdown, ServerMessage::ServerShutdown);
+    }
+
+    #[tokio::test]
+    async fn shutdown_does_not_wait_for_idle_connections() {
+        use tokio::net::TcpStream;
+        use tokio::time::{sleep, timeout};
+
+        let static_dir = std::env::temp_dir().join(format!("static-{}", std::process::id()));
+        for static_dir in [None, Some(static_dir)] {
+            let server = Server::new(
+                "127.0.0.1:0",
+                None,
+                None,
+                static_dir,
+                Language::English,
+                None,
+            )
+            .await
+            .unwrap();
+            let addr = server.listener.local_addr().unwrap();
+            let _server_task = tokio::spawn(Arc::clone(&server).run());
+
+            // Connected without sending the request head.
+            let _stream = TcpStream::connect(addr).await.unwrap();
+            sleep(Duration::from_millis(50)).await;
+
+            timeout(Duration::from_secs(1), server.shutdown())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn pings_are_answered_and_silent_clients_are_dropped() {
+        use futures::SinkExt;
+        use tokio::time::timeout;
+
+        let idle_timeout = Duration::from_millis(200);
+        let server = Server::new(
+            "127.0.0.1:0",
+            None,
+            None,
+            None,
+            Language::English,
+            Some(idle_timeout),
+        )
+        .await
+        .unwrap();
+        let addr = server.listener.local_addr().unwrap();
+        let _server_task = tokio::spawn(Arc::clone(&server).run());
+
+        let url = format!("ws://{}", addr);
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let ping = WireCodec::DEFAULT
+            .encode_frame(&ClientMessage::Ping)
+            .unwrap();
+        client.send(Message::Binary(ping)).await.unwrap();
+        loop {
+            match client.next().await.unwrap().unwrap() {
+                Message::Binary(data) => {
+                    let message: ServerMessage =
+                        WireCodec::DEFAULT.decode_frame(&data, u32::MAX).unwrap();
+                    if message == ServerMessage::Pong {
+                        break;
+                    }
+                }
+                message => panic!("unexpected message: {:?}", message),
+            }
+        }
+
+        let dropped = timeout(idle_timeout * 10, async {
+            while let Some(Ok(_)) = client.next().await {}
+        })
+        .await;
+        assert!(dropped.is_ok());
+    }
+}
@@ -1,22 +1,50 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Weak};
 
-use signaling_protocol::ChannelId;
+use signaling_protocol::{ChannelId, ChannelInfo};
 use tokio::sync::RwLock;
 
-use crate::{Channel, SocketId, SocketSender};
+use crate::{
+    Channel, ChannelRequests, ChannelReservations, RecordingStore, RelayStats, SessionStats,
+    SocketId, SocketSender, SocketStats, TranscriptRecorder,
+};
+
+/// Maximum number of the ended scheduled channels listed to clients.
+const MAX_ENDED_CHANNELS: usize = 16;
 
 #[derive(Debug)]
 pub struct ServerData {
     channels: RwLock<HashMap<Arc<ChannelId>, Weak<Channel>>>,
     senders: RwLock<HashMap<SocketId, Weak<SocketSender>>>,
+    transcript_recorder: Option<TranscriptRecorder>,
+    recording_store: Option<RecordingStore>,
+    session_stats: SessionStats,
+    relay_stats: RelayStats,
+    socket_stats: SocketStats,
+    reservations: ChannelReservations,
+    channel_requests: ChannelRequests,
+    ended_channels: RwLock<VecDeque<ChannelInfo>>,
 }
 
 impl ServerData {
-    pub fn new() -> Self {
+    pub fn new(
+        transcript_recorder: Option<TranscriptRecorder>,
+        recording_store: Option<RecordingStore>,
+    ) -> Self {
         let channels = RwLock::new(HashMap::new());
         let senders = RwLock::new(HashMap::new());
-        Self { channels, senders }
+        Self {
+            channels,
+            senders,
+            transcript_recorder,
+            recording_store,
+            session_stats: SessionStats::default(),
+            relay_stats: RelayStats::default(),
+            socket_stats: SocketStats::default(),
+            reservations: ChannelReservations::default(),
+            channel_requests: ChannelRequests::default(),
+            ended_channels: RwLock::new(VecDeque::new()),
+        }
     }
 
     pub fn channels(&self) -> &RwLock<HashMap<Arc<ChannelId>, Weak<Channel>>> {
@@ -27,6 +55,35 @@ impl ServerData {
         &self.senders
     }
 
+    pub fn transcript_recorder(&self) -> Option<&TranscriptRecorder> {
+        self.transcript_recorder.as_ref()
+    }
+
+    /// `None` if the server recording is disabled.
+    pub fn recording_store(&self) -> Option<&RecordingStore> {
+        self.recording_store.as_ref()
+    }
+
+    pub fn session_stats(&self) -> &SessionStats {
+        &self.session_stats
+    }
+
+    pub fn relay_stats(&self) -> &RelayStats {
+        &self.relay_stats
+    }
+
+    pub fn socket_stats(&self) -> &SocketStats {
+        &self.socket_stats
+    }
+
+    pub fn reservations(&self) -> &ChannelReservations {
+        &self.reservations
+    }
+
+    pub fn channel_requests(&self) -> &ChannelRequests {
+        &self.channel_requests
+    }
+
     pub async fn remove_channels<T: AsRef<ChannelId>, I: IntoIterator<Item = T>>(&self, iter: I) {
         let mut channels = self.channels.write().await;
         for channel_id in iter.into_iter() {
@@ -34,35 +91,91 @@ impl ServerData {
         }
     }
 
-    pub async fn update_open_channel_ids(&self) {
-        use crate::ChannelKind;
+    /// Keeps the closed scheduled channel listed as ended, the oldest ones are dropped.
+    pub async fn add_ended_channel(&self, channel_info: ChannelInfo) {
+        let mut ended_channels = self.ended_channels.write().await;
+        ended_channels.retain(|ended| ended.channel_id != channel_info.channel_id);
+        if ended_channels.len() >= MAX_ENDED_CHANNELS {
+            drop(ended_channels.pop_front());
+        }
+        ended_channels.push_back(channel_info);
+    }
+
+    /// Removes the ended listing of a reopened channel.
+    pub async fn remove_ended_channel(&self, channel_id: &ChannelId) {
+        self.ended_channels
+            .write()
+            .await
+            .retain(|ended| &ended.channel_id != channel_id);
+    }
+
+    /// Sends the open channel list to the clients which did not pause its updates.
+    pub async fn update_open_channels(&self) {
         use signaling_protocol::ServerMessage;
 
-        let channels = self.channels.read().await;
-        let mut channel_ids = Vec::new();
-        for (channel_id, channel) in channels.iter() {
-            if let Some(channel) = channel.upgrade() {
-                match &channel.kind {
-                    ChannelKind::PeerToPeer { receiver } => {
-                        if receiver.read().await.is_none() {
-                            channel_ids.push(channel_id.as_ref().to_owned())
-                        }
-                    }
-                    ChannelKind::ClientServer { .. } => {
-                        channel_ids.push(channel_id.as_ref().to_owned())
-                    }
+        let channel_infos = self.open_channel_infos().await;
+        let senders = self.senders.read().await;
+        for sender in senders.values() {
+            if let Some(sender) = sender.upgrade() {
+                if !sender.is_channel_list_paused() {
+                    sender
+                        .send(ServerMessage::OpenChannelsChanged(channel_infos.clone()))
+                        .await;
                 }
             }
         }
-        drop(channels);
+    }
+
+    /// Sends the full open channel list to a single client, e.g. when it resumes the updates.
+    pub async fn send_open_channels(&self, sender: &SocketSender) {
+        use signaling_protocol::ServerMessage;
+
+        let channel_infos = self.open_channel_infos().await;
+        sender
+            .send(ServerMessage::OpenChannelsChanged(channel_infos))
+            .await;
+    }
+
+    /// Sends the republished preview of a listed channel to the clients,
+    /// unlike `update_open_channels` the other channels are not resent.
+    pub async fn update_channel_preview(&self, channel: &Channel) {
+        use signaling_protocol::ServerMessage;
 
+        let channel_id = match channel.channel_id.read().await.upgrade() {
+            Some(channel_id) => channel_id.as_ref().to_owned(),
+            None => return,
+        };
+        let preview = match channel.public_preview().await {
+            Some(preview) if channel.is_open().await => preview,
+            _ => return,
+        };
         let senders = self.senders.read().await;
         for sender in senders.values() {
             if let Some(sender) = sender.upgrade() {
-                sender
-                    .send(ServerMessage::OpenChannelIdsChanged(channel_ids.clone()))
-                    .await;
+                if !sender.is_channel_list_paused() {
+                    sender
+                        .send(ServerMessage::ChannelPreviewChanged {
+                            channel_id: channel_id.clone(),
+                            preview: preview.clone(),
+                        })
+                        .await;
+                }
             }
         }
     }
+
+    async fn open_channel_infos(&self) -> Vec<ChannelInfo> {
+        let channels = self.channels.read().await;
+        let mut channel_infos = Vec::new();
+        for (channel_id, channel) in channels.iter() {
+            if let Some(channel) = channel.upgrade() {
+                if channel.is_open().await {
+                    channel_infos.push(channel.info(channel_id.as_ref().to_owned()).await)
+                }
+            }
+        }
+        drop(channels);
+        channel_infos.extend(self.ended_channels.read().await.iter().cloned());
+        channel_infos
+    }
 }
@@ -0,0 +1,101 @@
+use core::time::Duration;
+use std::collections::BTreeMap;
+
+use signaling_protocol::{CallMilestone, MilestoneReport, SessionSummary};
+use tokio::sync::Mutex;
+
+/// Aggregates the session summaries reported by the clients that opted in.
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    totals: Mutex<SessionTotals>,
+    milestones: Mutex<BTreeMap<CallMilestone, MilestoneTotals>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SessionRole {
+    Sender,
+    Receiver,
+}
+
+/// Totals of the reported summaries.
+///
+/// Both peers of a connection report their own traffic,
+/// so the bytes sent by senders are also counted as bytes received by receivers.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SessionTotals {
+    pub sender_sessions: u64,
+    pub receiver_sessions: u64,
+    pub duration: Duration,
+    pub state_durations: BTreeMap<String, Duration>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub frames_sent: u64,
+    pub frames_received: u64,
+}
+
+/// Totals of the reported call setup milestones,
+/// the setup success rate is the ratio of `IceConnected` to `OfferSent` counts.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MilestoneTotals {
+    pub count: u64,
+    pub total_elapsed: Duration,
+    pub max_elapsed: Duration,
+}
+
+impl MilestoneTotals {
+    pub fn average_elapsed(&self) -> Duration {
+        use core::convert::TryFrom;
+
+        match u32::try_from(self.count) {
+            Ok(0) => Duration::ZERO,
+            Ok(count) => self.total_elapsed / count,
+            Err(_) => Duration::ZERO,
+        }
+    }
+}
+
+impl SessionStats {
+    pub async fn record(&self, role: SessionRole, summary: &SessionSummary) -> SessionTotals {
+        let mut totals = self.totals.lock().await;
+        match role {
+            SessionRole::Sender => totals.sender_sessions += 1,
+            SessionRole::Receiver => totals.receiver_sessions += 1,
+        }
+        totals.duration += summary.duration;
+        for state_duration in &summary.state_durations {
+            *totals
+                .state_durations
+                .entry(state_duration.state.clone())
+                .or_default() += state_duration.duration;
+        }
+        totals.bytes_sent += summary.bytes_sent;
+        totals.bytes_received += summary.bytes_received;
+        totals.frames_sent += summary.frames_sent;
+        totals.frames_received += summary.frames_received;
+        totals.clone()
+    }
+
+    pub async fn record_milestones(
+        &self,
+        reports: &[MilestoneReport],
+    ) -> BTreeMap<CallMilestone, MilestoneTotals> {
+        let mut milestones = self.milestones.lock().await;
+        for report in reports {
+            let totals = milestones.entry(report.milestone).or_default();
+            totals.count += 1;
+            totals.total_elapsed += report.elapsed;
+            totals.max_elapsed = totals.max_elapsed.max(report.elapsed);
+        }
+        milestones.clone()
+    }
+
+    #[cfg(test)]
+    pub async fn totals(&self) -> SessionTotals {
+        self.totals.lock().await.clone()
+    }
+
+    #[cfg(test)]
+    pub async fn milestone_totals(&self) -> BTreeMap<CallMilestone, MilestoneTotals> {
+        self.milestones.lock().await.clone()
+    }
+}
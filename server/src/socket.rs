@@ -1,31 +1,60 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
-use futures::stream::SplitStream;
+use futures::stream::{SplitStream, Stream};
 use signaling_protocol::{
-    ChannelId, ClientReceiverMessage, ClientSenderMessage, IceCandidate, NetworkMode,
-    ServerReceiverErrorMessage, ServerSenderErrorMessage, SessionDescription, SessionReceiverId,
-    SessionSenderId,
+    ChannelId, ChannelPreview, ChannelRequestDeclineReason, ChannelRequestId, ChannelSecret,
+    ClaimToken, ClientMessage, ClientReceiverMessage, ClientSenderMessage, CloseReason,
+    IceCandidate, Language, MilestoneReport, NackReason, NetworkMode, PeerCapabilities, PeerId,
+    PublicKey, QualityRequest, ResumeToken, ServerMessage, ServerReceiverErrorMessage,
+    ServerSenderErrorMessage, ServerSenderMessage, SessionDescription, SessionReceiverId,
+    SessionSenderId, SessionSummary, ThrottleNotice, Topic, WireCodec,
 };
 use thiserror::Error;
 use tokio::net::TcpStream;
+use tokio::sync::watch;
 use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::tungstenite::Error as WebSocketError;
 use tokio_tungstenite::WebSocketStream;
 
-use crate::{Channel, ChannelReceiver, ServerData, SocketSender};
+use crate::{
+    Channel, ChannelReceiver, ChannelRecording, ChannelTranscript, RelayBudget, ServerData,
+    SessionEnd, SessionRole, SocketSender, TranscriptEvent,
+};
+
+/// Time for a joined receiver to answer the relayed offer
+/// before its half-open channel slot is released.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client frames with longer payloads are rejected without decoding them,
+/// the longest messages are the channel snapshots and the relayed binary data.
+const MAX_CLIENT_FRAME_PAYLOAD_LEN: u32 = 4 * 1024 * 1024;
+
+/// Joins and snapshot requests with a wrong channel credential a session may make before it is closed,
+/// so the channel secrets cannot be guessed over a single connection.
+const MAX_FAILED_JOIN_ATTEMPTS: u32 = 5;
+
+const SEC_WEBSOCKET_PROTOCOL: &str = "Sec-WebSocket-Protocol";
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct SocketId(pub u32);
 
+/// Incoming half of the client WebSocket.
+pub type SocketReceiver = SplitStream<WebSocketStream<TcpStream>>;
+
 #[derive(Debug)]
 pub struct Socket {
     socket_id: SocketId,
     server_data: Arc<ServerData>,
     socket_sender: Arc<SocketSender>,
-    socket_receiver: SplitStream<WebSocketStream<TcpStream>>,
     channel_senders: HashMap<SessionSenderId, Arc<Channel>>,
     channel_receivers: HashMap<SessionReceiverId, Arc<ChannelReceiver>>,
+    /// Recordings started by the channel senders of the session.
+    recordings: HashMap<SessionSenderId, ChannelRecording>,
+    /// Joins and snapshot requests rejected for a wrong channel credential.
+    failed_join_attempts: u32,
     addr: SocketAddr,
 }
 
@@ -35,16 +64,46 @@ impl Socket {
         server_data: Arc<ServerData>,
         stream: TcpStream,
         addr: SocketAddr,
-    ) -> Result<Self, NewSessionError> {
+        log_language: Language,
+    ) -> Result<(Self, SocketReceiver), NewSessionError> {
+        use crate::WebSocketSink;
         use futures::StreamExt;
         use log::info;
-        use tokio_tungstenite::accept_async;
+        use tokio_tungstenite::accept_hdr_async;
+        use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+        use tokio_tungstenite::tungstenite::http::HeaderValue;
 
-        let websocket = accept_async(stream).await.unwrap();
+        let mut codec = WireCodec::DEFAULT;
+        #[allow(clippy::result_large_err)] // error response type is defined by tungstenite
+        let callback = |request: &Request, mut response: Response| {
+            if let Some(negotiated) = negotiate_codec(request) {
+                codec = negotiated;
+                let _: Option<_> = response.headers_mut().insert(
+                    SEC_WEBSOCKET_PROTOCOL,
+                    HeaderValue::from_static(negotiated.subprotocol()),
+                );
+            }
+            Ok(response)
+        };
+        let websocket = accept_hdr_async(stream, callback).await?;
         let (socket_sender, socket_receiver) = websocket.split();
-        let socket_sender = Arc::new(SocketSender::new(socket_sender));
-        info!("new session: {}", addr);
+        let socket_sender = Arc::new(
+            SocketSender::new(WebSocketSink::new(socket_sender), codec)
+                .with_log_language(log_language),
+        );
+        info!("new session: {}, codec: {}", addr, codec.name());
 
+        let socket = Self::with_sender(socket_id, server_data, socket_sender, addr).await;
+        Ok((socket, socket_receiver))
+    }
+
+    /// Registers the session with an already connected sender.
+    pub async fn with_sender(
+        socket_id: SocketId,
+        server_data: Arc<ServerData>,
+        socket_sender: Arc<SocketSender>,
+        addr: SocketAddr,
+    ) -> Self {
         let prev_sender = server_data
             .senders()
             .write()
@@ -52,103 +111,389 @@ impl Socket {
             .insert(socket_id, Arc::downgrade(&socket_sender));
         assert!(prev_sender.is_none());
 
-        server_data.update_open_channel_ids().await;
+        server_data.update_open_channels().await;
 
-        Ok(Self {
+        Self {
             socket_id,
             server_data,
             socket_sender,
-            socket_receiver,
             channel_senders: HashMap::new(),
             channel_receivers: HashMap::new(),
+            recordings: HashMap::new(),
+            failed_join_attempts: 0,
             addr,
-        })
+        }
+    }
+
+    /// Handles the client messages until the client disconnects or the server shuts down.
+    ///
+    /// Clients that send nothing, including keepalive pings, for the idle timeout
+    /// are dropped as disconnected. The session is cleared however it ends,
+    /// including the WebSocket errors and the panics of the message handlers.
+    pub async fn run<S>(
+        mut self,
+        socket_receiver: S,
+        shutdown: watch::Receiver<bool>,
+        idle_timeout: Option<Duration>,
+    ) where
+        S: Stream<Item = Result<Message, WebSocketError>> + Unpin,
+    {
+        use core::panic::AssertUnwindSafe;
+        use futures::FutureExt;
+        use log::{log, Level};
+
+        let end = AssertUnwindSafe(self.receive_messages(socket_receiver, shutdown, idle_timeout))
+            .catch_unwind()
+            .await
+            .unwrap_or(SessionEnd::HandlerPanicked);
+        let socket_stats = self.server_data.socket_stats();
+        socket_stats.record_end(end);
+        let level = match end {
+            SessionEnd::HandlerPanicked => Level::Error,
+            _ => Level::Info,
+        };
+        log!(
+            level,
+            "session ended: {}, end: {}, totals: {:?}",
+            self.addr,
+            end.name(),
+            socket_stats.totals()
+        );
+        self.clear(end.close_reason()).await;
     }
 
-    pub async fn run(mut self) {
-        use bincode::deserialize;
+    async fn receive_messages<S>(
+        &mut self,
+        mut socket_receiver: S,
+        mut shutdown: watch::Receiver<bool>,
+        idle_timeout: Option<Duration>,
+    ) -> SessionEnd
+    where
+        S: Stream<Item = Result<Message, WebSocketError>> + Unpin,
+    {
         use futures::stream::StreamExt;
         use log::{debug, error, info};
-        use signaling_protocol::ClientMessage;
+        use tokio::time::{sleep_until, Instant};
 
+        let mut idle_deadline = idle_timeout.map(|timeout| Instant::now() + timeout);
         loop {
-            let message = self.socket_receiver.next().await.unwrap().unwrap();
+            if *shutdown.borrow() {
+                self.socket_sender.send(ServerMessage::ServerShutdown).await;
+                self.socket_sender.close("server shutdown").await;
+                return SessionEnd::ServerShutdown;
+            }
+            let message = tokio::select! {
+                message = socket_receiver.next() => match message {
+                    Some(Ok(message)) => message,
+                    Some(Err(err)) => {
+                        info!("session receive error: {}, {}", self.addr, err);
+                        return SessionEnd::from_websocket_error(&err);
+                    }
+                    None => return SessionEnd::Disconnected,
+                },
+                changed = shutdown.changed() => {
+                    // The server is dropped without a shutdown.
+                    if changed.is_err() {
+                        return SessionEnd::Disconnected;
+                    }
+                    continue;
+                }
+                () = sleep_until(idle_deadline.unwrap_or_else(Instant::now)),
+                    if idle_deadline.is_some() =>
+                {
+                    return SessionEnd::TimedOut;
+                }
+            };
+            idle_deadline = idle_timeout.map(|timeout| Instant::now() + timeout);
             match message {
                 Message::Binary(data) => {
-                    let message: Result<ClientMessage, _> = deserialize(&data[..]);
+                    let message: Result<ClientMessage, _> = self
+                        .socket_sender
+                        .codec()
+                        .decode_frame(&data[..], MAX_CLIENT_FRAME_PAYLOAD_LEN);
                     debug!("client message: {}, {:?}", self.addr, message);
                     match message {
-                        Ok(ClientMessage::SenderMessage { sender_id, message }) => match message {
-                            ClientSenderMessage::OpenChannel {
-                                channel_id,
-                                network_mode,
-                            } => self.open_channel(sender_id, channel_id, network_mode).await,
-                            ClientSenderMessage::CloseChannel => {
-                                self.close_channel(sender_id).await
-                            }
-                            ClientSenderMessage::SendOffer(sdp) => {
-                                self.send_offer(sender_id, sdp).await
-                            }
-                            ClientSenderMessage::IceCandidate(ice_candidate) => {
-                                self.sender_ice_candidate(sender_id, ice_candidate).await
-                            }
-                            ClientSenderMessage::AllIceCandidatesSent => {
-                                self.sender_all_ice_candidate_sent(sender_id).await
-                            }
-                            ClientSenderMessage::SendBinaryData(data) => {
-                                self.send_binary_data(sender_id, data).await
-                            }
-                        },
-                        Ok(ClientMessage::ReceiverMessage {
-                            receiver_id,
-                            message,
-                        }) => match message {
-                            ClientReceiverMessage::JoinChannel { channel_id } => {
-                                self.join_channel(receiver_id, channel_id).await
-                            }
-                            ClientReceiverMessage::ExitChannel => {
-                                self.exit_channel(receiver_id).await
-                            }
-                            ClientReceiverMessage::SendAnswer(sdp) => {
-                                self.send_answer(receiver_id, sdp).await
-                            }
-                            ClientReceiverMessage::IceCandidate(ice_candidate) => {
-                                self.receiver_ice_candidate(receiver_id, ice_candidate)
-                                    .await
-                            }
-                            ClientReceiverMessage::AllIceCandidatesSent => {
-                                self.receiver_all_ice_candidate_sent(receiver_id).await
-                            }
-                        },
+                        Ok(ClientMessage::WithAck { ack_id, message }) => {
+                            let reply = match self.handle_message(*message).await {
+                                Ok(()) => ServerMessage::Ack(ack_id),
+                                Err(reason) => ServerMessage::Nack { ack_id, reason },
+                            };
+                            self.socket_sender.send(reply).await;
+                        }
+                        Ok(message) => {
+                            let _: Result<(), NackReason> = self.handle_message(message).await;
+                        }
+                        Err(err) if err.is_unknown_type() => {
+                            debug!("ClientMessage of a newer protocol is skipped: {}", err);
+                        }
                         Err(err) => {
                             error!("ClientMessage deserialization error {}", err);
                         }
                     }
+                    if self.failed_join_attempts >= MAX_FAILED_JOIN_ATTEMPTS {
+                        info!("too many failed join attempts: {}", self.addr);
+                        self.socket_sender
+                            .close("too many failed join attempts")
+                            .await;
+                        return SessionEnd::ProtocolViolation;
+                    }
                 }
-                Message::Close(_) => {
-                    info!("session closed: {}", self.addr);
-                    break;
-                }
+                Message::Close(_) => return SessionEnd::Closed,
+                // WebSocket pings are answered by tungstenite, they only keep the session alive.
+                Message::Ping(_) | Message::Pong(_) => {}
                 _ => {
-                    info!(
-                        "invalid client message: {:?}, session closed: {}",
-                        message, self.addr
-                    );
-                    break;
+                    info!("invalid client message: {}, {:?}", self.addr, message);
+                    return SessionEnd::ProtocolViolation;
+                }
+            }
+        }
+    }
+
+    /// Handles the client message,
+    /// returns an error if the message could not be delivered to the peer.
+    async fn handle_message(&mut self, message: ClientMessage) -> Result<(), NackReason> {
+        self.record_transcript(&message).await;
+        match message {
+            ClientMessage::SenderMessage { sender_id, message } => match message {
+                ClientSenderMessage::OpenChannel {
+                    channel_id,
+                    network_mode,
+                    claim_token,
+                    secret,
+                    resume_token,
+                } => {
+                    self.open_channel(
+                        sender_id,
+                        channel_id,
+                        network_mode,
+                        claim_token,
+                        secret,
+                        resume_token,
+                    )
+                    .await;
+                    Ok(())
+                }
+                ClientSenderMessage::CloseChannel(reason) => {
+                    self.close_channel(sender_id, reason).await;
+                    Ok(())
+                }
+                ClientSenderMessage::RenameChannel(channel_id) => {
+                    self.rename_channel(sender_id, channel_id).await;
+                    Ok(())
+                }
+                ClientSenderMessage::SendOffer(sdp) => self.send_offer(sender_id, sdp).await,
+                ClientSenderMessage::SendIceRestartOffer(sdp) => {
+                    self.send_ice_restart_offer(sender_id, sdp).await
+                }
+                ClientSenderMessage::IceCandidate(ice_candidate) => {
+                    self.sender_ice_candidate(sender_id, ice_candidate).await
+                }
+                ClientSenderMessage::AllIceCandidatesSent => {
+                    self.sender_all_ice_candidate_sent(sender_id).await;
+                    Ok(())
+                }
+                ClientSenderMessage::RequestIceCandidates => {
+                    self.request_receiver_ice_candidates(sender_id).await;
+                    Ok(())
+                }
+                ClientSenderMessage::SendBinaryData(data) => {
+                    self.send_binary_data(sender_id, data).await;
+                    Ok(())
+                }
+                ClientSenderMessage::SendTopicData { topic, data } => {
+                    self.send_topic_data(sender_id, topic, data).await;
+                    Ok(())
+                }
+                ClientSenderMessage::SendSnapshot(snapshot) => {
+                    self.send_snapshot(sender_id, snapshot).await;
+                    Ok(())
+                }
+                ClientSenderMessage::PublishPreview(preview) => {
+                    self.publish_preview(sender_id, preview).await;
+                    Ok(())
+                }
+                ClientSenderMessage::ScheduleStart(scheduled_start) => {
+                    self.schedule_start(sender_id, scheduled_start).await;
+                    Ok(())
+                }
+                ClientSenderMessage::PublishPublicKey(public_key) => {
+                    self.publish_sender_public_key(sender_id, public_key).await
+                }
+                ClientSenderMessage::ReportSessionSummary(summary) => {
+                    self.report_session_summary(SessionRole::Sender, summary)
+                        .await;
+                    Ok(())
+                }
+                ClientSenderMessage::PublishCapabilities(capabilities) => {
+                    self.publish_sender_capabilities(sender_id, capabilities)
+                        .await
+                }
+                ClientSenderMessage::RequestPeerNetwork => {
+                    self.request_peer_network(sender_id).await
+                }
+                ClientSenderMessage::SendAnswer(sdp) => {
+                    self.send_renegotiation_answer(sender_id, sdp).await
+                }
+                ClientSenderMessage::StartRecording { mime_type } => {
+                    self.start_recording(sender_id, mime_type).await;
+                    Ok(())
+                }
+                ClientSenderMessage::SendRecordingChunk(chunk) => {
+                    self.send_recording_chunk(sender_id, chunk).await;
+                    Ok(())
+                }
+                ClientSenderMessage::StopRecording => {
+                    self.stop_recording(sender_id).await;
+                    Ok(())
+                }
+            },
+            ClientMessage::ReceiverMessage {
+                receiver_id,
+                message,
+            } => match message {
+                ClientReceiverMessage::JoinChannel {
+                    channel_id,
+                    credential,
+                } => {
+                    self.join_channel(receiver_id, channel_id, credential).await;
+                    Ok(())
+                }
+                ClientReceiverMessage::ExitChannel(reason) => {
+                    self.exit_channel(receiver_id, reason).await;
+                    Ok(())
+                }
+                ClientReceiverMessage::SendAnswer(sdp) => self.send_answer(receiver_id, sdp).await,
+                ClientReceiverMessage::IceCandidate(ice_candidate) => {
+                    self.receiver_ice_candidate(receiver_id, ice_candidate)
+                        .await
+                }
+                ClientReceiverMessage::AllIceCandidatesSent => {
+                    self.receiver_all_ice_candidate_sent(receiver_id).await;
+                    Ok(())
                 }
+                ClientReceiverMessage::RequestIceCandidates => {
+                    self.request_sender_ice_candidates(receiver_id).await;
+                    Ok(())
+                }
+                ClientReceiverMessage::RequestQuality(request) => {
+                    self.request_quality(receiver_id, request).await;
+                    Ok(())
+                }
+                ClientReceiverMessage::RequestIceRestart => {
+                    self.request_ice_restart(receiver_id).await;
+                    Ok(())
+                }
+                ClientReceiverMessage::SubscribeTopic(topic) => {
+                    self.subscribe_topic(receiver_id, topic).await;
+                    Ok(())
+                }
+                ClientReceiverMessage::UnsubscribeTopic(topic) => {
+                    self.unsubscribe_topic(receiver_id, topic).await;
+                    Ok(())
+                }
+                ClientReceiverMessage::PublishPublicKey(public_key) => {
+                    self.publish_receiver_public_key(receiver_id, public_key)
+                        .await
+                }
+                ClientReceiverMessage::ReportSessionSummary(summary) => {
+                    self.report_session_summary(SessionRole::Receiver, summary)
+                        .await;
+                    Ok(())
+                }
+                ClientReceiverMessage::PublishCapabilities(capabilities) => {
+                    self.publish_receiver_capabilities(receiver_id, capabilities)
+                        .await
+                }
+                ClientReceiverMessage::SendOffer(sdp) => {
+                    self.send_renegotiation_offer(receiver_id, sdp).await
+                }
+            },
+            ClientMessage::RequestChannelSnapshot {
+                channel_id,
+                credential,
+            } => {
+                self.request_channel_snapshot(channel_id, credential).await;
+                Ok(())
+            }
+            ClientMessage::ReserveChannel { channel_id } => {
+                self.reserve_channel(channel_id).await;
+                Ok(())
+            }
+            ClientMessage::ReportMilestones(reports) => {
+                self.report_milestones(reports).await;
+                Ok(())
+            }
+            ClientMessage::PauseChannelList => {
+                self.socket_sender.set_channel_list_paused(true);
+                Ok(())
+            }
+            ClientMessage::ResumeChannelList => {
+                self.socket_sender.set_channel_list_paused(false);
+                self.server_data
+                    .send_open_channels(&self.socket_sender)
+                    .await;
+                Ok(())
             }
+            ClientMessage::RegisterPeer(peer_id) => {
+                self.register_peer(peer_id).await;
+                Ok(())
+            }
+            ClientMessage::RequestChannel {
+                request_id,
+                peer_id,
+            } => {
+                self.request_channel(request_id, peer_id).await;
+                Ok(())
+            }
+            ClientMessage::AcceptChannelRequest {
+                request_id,
+                channel_id,
+            } => {
+                self.answer_channel_request(request_id, Ok(channel_id))
+                    .await;
+                Ok(())
+            }
+            ClientMessage::DeclineChannelRequest { request_id, reason } => {
+                self.answer_channel_request(request_id, Err(reason)).await;
+                Ok(())
+            }
+            ClientMessage::Ping => {
+                self.socket_sender.send(ServerMessage::Pong).await;
+                Ok(())
+            }
+            ClientMessage::WithAck { .. } => Err(NackReason::NestedAck),
         }
-        self.clear().await;
     }
 
-    pub async fn clear(mut self) {
+    /// Unregisters the session, its peers are notified with the reason.
+    pub async fn clear(mut self, reason: CloseReason) {
         use core::mem::take;
 
+        // Peers of a disconnected client are notified as if it closed its sessions.
+        for recording in take(&mut self.recordings).into_values() {
+            let _: u64 = recording.finish().await;
+        }
         let senders = take(&mut self.channel_senders);
-        let channel_ids = senders
-            .into_iter()
-            .filter_map(|(_, channel)| channel.channel_id.upgrade());
+        for channel in senders.values() {
+            channel.send_channel_closed(reason).await;
+            end_scheduled_channel(&self.server_data, channel).await;
+        }
+        for receiver in take(&mut self.channel_receivers).values() {
+            notify_receiver_left(receiver, reason).await;
+        }
+        let mut channel_ids = Vec::new();
+        for channel in senders.values() {
+            channel_ids.extend(channel.channel_id.read().await.upgrade());
+        }
         self.server_data.remove_channels(channel_ids).await;
+        self.server_data
+            .reservations()
+            .release(self.socket_id)
+            .await;
+        self.server_data
+            .channel_requests()
+            .unregister_client(&self.socket_sender)
+            .await;
 
         let prev_sender = self
             .server_data
@@ -164,8 +509,11 @@ impl Socket {
         session_sender_id: SessionSenderId,
         channel_id: ChannelId,
         network_mode: NetworkMode,
+        claim_token: Option<ClaimToken>,
+        secret: Option<ChannelSecret>,
+        resume_token: Option<ResumeToken>,
     ) {
-        use crate::{ChannelIceCandidates, ChannelKind, ChannelSender};
+        use crate::{ChannelIceCandidates, ChannelKind, ChannelSender, RelayThrottle};
         use std::collections::hash_map::Entry;
         use tokio::sync::RwLock;
 
@@ -182,10 +530,29 @@ impl Socket {
             Entry::Vacant(entry) => entry,
         };
 
+        // The transcript file is created before the channel list is locked,
+        // so the other channel operations do not wait for the disk.
+        let transcript = create_transcript(&self.server_data, &channel_id).await;
         let channel_id = Arc::new(channel_id);
         let mut channels = self.server_data.channels().write().await;
+        // The channel of a reconnected sender is taken over from its stale connection,
+        // which may still be open until it times out.
+        let prev_channel = match channels.get(&channel_id).and_then(Weak::upgrade) {
+            Some(channel)
+                if channel.accepts_resume_token(resume_token.as_ref())
+                    && channel.sender.socket_sender.as_ptr()
+                        != Arc::as_ptr(&self.socket_sender) =>
+            {
+                // The stale connection no longer owns the channel id once its key is removed.
+                let _: Option<_> = channels.remove(&channel_id);
+                Some(channel)
+            }
+            _ => None,
+        };
         let server_channel_entry = match channels.entry(Arc::clone(&channel_id)) {
             Entry::Occupied(_) => {
+                drop(channels);
+                discard_transcript(transcript).await;
                 self.socket_sender
                     .send_sender_error(
                         session_sender_id,
@@ -198,41 +565,95 @@ impl Socket {
             }
             Entry::Vacant(entry) => entry,
         };
-
-        let channel = match network_mode {
-            NetworkMode::PeerToPeer => Channel {
-                channel_id: Arc::downgrade(&channel_id),
-                sender: ChannelSender {
-                    socket_sender: Arc::downgrade(&self.socket_sender),
+        // The reservation was claimed when the channel was opened first.
+        let is_claimed = prev_channel.is_some()
+            || self
+                .server_data
+                .reservations()
+                .claim(&channel_id, claim_token.as_ref())
+                .await;
+        if !is_claimed {
+            drop(channels);
+            discard_transcript(transcript).await;
+            self.socket_sender
+                .send_sender_error(
                     session_sender_id,
-                    session_description: RwLock::new(None),
-                    ice_candidates: RwLock::new(ChannelIceCandidates::new()),
-                },
-                kind: ChannelKind::PeerToPeer {
-                    receiver: RwLock::new(None),
-                },
+                    ServerSenderErrorMessage::ChannelIsReserved(channel_id.as_ref().to_owned()),
+                )
+                .await;
+            return;
+        }
+
+        let kind = match network_mode {
+            NetworkMode::PeerToPeer => ChannelKind::PeerToPeer {
+                receiver: RwLock::new(None),
             },
-            NetworkMode::ClientServer => {
-                log::error!("not implemented"); // TODO
-                return;
-            }
+            NetworkMode::ClientServer | NetworkMode::Loopback => ChannelKind::ClientServer {
+                receivers: RwLock::new(Vec::new()),
+                relay_budget: RelayBudget::channel(),
+                relay_throttle: RelayThrottle::default(),
+                is_loopback: network_mode == NetworkMode::Loopback,
+            },
+        };
+        let channel = Channel {
+            channel_id: RwLock::new(Arc::downgrade(&channel_id)),
+            sender: ChannelSender {
+                socket_sender: Arc::downgrade(&self.socket_sender),
+                session_sender_id,
+                session_description: RwLock::new(None),
+                ice_candidates: RwLock::new(ChannelIceCandidates::new()),
+                snapshot: RwLock::new(None),
+                preview: RwLock::new(None),
+                public_key: RwLock::new(None),
+                capabilities: RwLock::new(None),
+                scheduled_start: RwLock::new(None),
+                addr: self.addr,
+                is_peer_network_requested: RwLock::new(false),
+            },
+            kind,
+            secret,
+            resume_token,
+            transcript,
         };
 
         let channel = Arc::new(channel);
         let _: &mut _ = server_channel_entry.insert(Arc::downgrade(&channel));
-        let _: &mut _ = session_channel_entry.insert(channel);
+        let _: &mut _ = session_channel_entry.insert(Arc::clone(&channel));
         drop(channels);
+        if let Some(prev_channel) = prev_channel {
+            for receiver in prev_channel.take_receivers().await {
+                receiver
+                    .send_channel_closed(CloseReason::RemoteClosed)
+                    .await;
+            }
+            if let Some(socket_sender) = prev_channel.sender.socket_sender.upgrade() {
+                socket_sender.close("channel resumed").await;
+            }
+        }
+        if let Some(transcript) = &channel.transcript {
+            transcript
+                .record(Some(self.addr), TranscriptEvent::ChannelOpened)
+                .await;
+        }
+        self.server_data.remove_ended_channel(&channel_id).await;
 
-        self.server_data.update_open_channel_ids().await;
+        self.socket_sender
+            .send_sender_message(session_sender_id, ServerSenderMessage::OpenChannelSuccess)
+            .await;
+        self.server_data.update_open_channels().await;
     }
 
     pub async fn join_channel(
         &mut self,
         session_receiver_id: SessionReceiverId,
         channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
     ) {
         use crate::{ChannelIceCandidates, ChannelKind};
+        use signaling_protocol::ReceiverInfo;
         use std::collections::hash_map::Entry;
+        use std::collections::HashSet;
+        use std::sync::atomic::AtomicU32;
         use tokio::sync::RwLock;
 
         let session_channel_entry = match self.channel_receivers.entry(session_receiver_id) {
@@ -267,6 +688,18 @@ impl Socket {
                 return;
             }
         };
+        if !channel.accepts_credential(credential.as_ref()) {
+            self.failed_join_attempts += 1;
+            self.socket_sender
+                .send_receiver_error(
+                    session_receiver_id,
+                    ServerReceiverErrorMessage::InvalidChannelCredentials(
+                        channel_id.as_ref().to_owned(),
+                    ),
+                )
+                .await;
+            return;
+        }
 
         let channel_receiver = Arc::new(ChannelReceiver {
             channel: Arc::downgrade(&channel),
@@ -274,6 +707,10 @@ impl Socket {
             session_receiver_id,
             session_description: RwLock::new(None),
             ice_candidates: RwLock::new(ChannelIceCandidates::new()),
+            topics: RwLock::new(HashSet::new()),
+            relay_budget: RelayBudget::receiver(),
+            offer_generation: AtomicU32::new(0),
+            addr: self.addr,
         });
 
         let session_description = channel.sender.session_description.read().await;
@@ -282,7 +719,11 @@ impl Socket {
         match &channel.kind {
             ChannelKind::PeerToPeer { receiver } => {
                 let mut receiver = receiver.write().await;
-                if let Some(_) = receiver.as_ref().and_then(|receiver| receiver.upgrade()) {
+                if receiver
+                    .as_ref()
+                    .and_then(|receiver| receiver.upgrade())
+                    .is_some()
+                {
                     self.socket_sender
                         .send_receiver_error(
                             session_receiver_id,
@@ -294,13 +735,60 @@ impl Socket {
                     return;
                 }
                 let _: Option<_> = receiver.replace(Arc::downgrade(&channel_receiver));
+                channel_receiver.send_join_channel_success().await;
+                if let Some(transcript) = &channel.transcript {
+                    transcript
+                        .record(Some(self.addr), TranscriptEvent::ReceiverJoined)
+                        .await;
+                }
+                channel
+                    .sender
+                    .send_receiver_joined(ReceiverInfo {
+                        connection_id: self.socket_id.0,
+                    })
+                    .await;
+                // The key is sent first, so that the receiver can open the sealed offer.
+                if let Some(public_key) = channel.sender.public_key.read().await.clone() {
+                    let _: Result<(), _> = channel_receiver.send_peer_public_key(public_key).await;
+                }
+                if let Some(capabilities) = *channel.sender.capabilities.read().await {
+                    let _: Result<(), _> =
+                        channel_receiver.send_peer_capabilities(capabilities).await;
+                }
+                if *channel.sender.is_peer_network_requested.read().await {
+                    channel.send_peer_network(&channel_receiver).await;
+                }
                 channel_receiver
                     .send_offer_and_ice_candidates(session_description.as_ref(), &ice_candidates)
-                    .await
+                    .await;
+                if session_description.is_some() {
+                    channel.sender.send_offer_relayed().await;
+                    watch_answer(&self.server_data, &channel, &channel_receiver);
+                }
+                if let Some(snapshot) = channel.sender.snapshot.read().await.clone() {
+                    channel_receiver.send_snapshot(snapshot).await;
+                }
             }
-            ChannelKind::ClientServer { .. } => {
-                log::error!("not implemented"); // TODO
-                return;
+            ChannelKind::ClientServer { receivers, .. } => {
+                let mut receivers = receivers.write().await;
+                receivers.retain(|receiver| receiver.strong_count() > 0);
+                receivers.push(Arc::downgrade(&channel_receiver));
+                channel_receiver.send_join_channel_success().await;
+                if let Some(transcript) = &channel.transcript {
+                    transcript
+                        .record(Some(self.addr), TranscriptEvent::ReceiverJoined)
+                        .await;
+                }
+                channel
+                    .sender
+                    .send_receiver_joined(ReceiverInfo {
+                        connection_id: self.socket_id.0,
+                    })
+                    .await;
+                // Only the data is relayed by the server, so no offer is replayed.
+                if let Some(snapshot) = channel.sender.snapshot.read().await.clone() {
+                    channel_receiver.send_snapshot(snapshot).await;
+                }
             }
         }
 
@@ -312,12 +800,42 @@ impl Socket {
 
         match &channel.kind {
             ChannelKind::PeerToPeer { .. } => {
-                self.server_data.update_open_channel_ids().await;
+                self.server_data.update_open_channels().await;
             }
             ChannelKind::ClientServer { .. } => {}
         }
     }
 
+    async fn record_transcript(&self, message: &ClientMessage) {
+        use crate::transcript::{is_signaling_receiver_message, is_signaling_sender_message};
+
+        let (channel, event) = match message {
+            ClientMessage::SenderMessage { sender_id, message }
+                if is_signaling_sender_message(message) =>
+            {
+                let channel = self.channel_senders.get(sender_id).map(Arc::clone);
+                (channel, TranscriptEvent::SenderMessage(message))
+            }
+            ClientMessage::ReceiverMessage {
+                receiver_id,
+                message,
+            } if is_signaling_receiver_message(message) => {
+                let channel = self
+                    .channel_receivers
+                    .get(receiver_id)
+                    .and_then(|receiver| receiver.channel.upgrade());
+                (channel, TranscriptEvent::ReceiverMessage(message))
+            }
+            _ => return,
+        };
+        if let Some(transcript) = channel
+            .as_ref()
+            .and_then(|channel| channel.transcript.as_ref())
+        {
+            transcript.record(Some(self.addr), event).await;
+        }
+    }
+
     pub async fn get_channel(&mut self, sender_id: SessionSenderId) -> Option<&Arc<Channel>> {
         match self.channel_senders.get(&sender_id) {
             Some(channel) => Some(channel),
@@ -351,11 +869,16 @@ impl Socket {
         }
     }
 
-    pub async fn close_channel(&mut self, sender_id: SessionSenderId) {
+    pub async fn close_channel(&mut self, sender_id: SessionSenderId, reason: CloseReason) {
         let channel = self.channel_senders.remove(&sender_id);
-        if channel.is_some() {
+        if let Some(channel) = channel {
+            if let Some(recording) = self.recordings.remove(&sender_id) {
+                let _: u64 = recording.finish().await;
+            }
+            channel.send_channel_closed(reason.to_remote()).await;
+            end_scheduled_channel(&self.server_data, &channel).await;
             drop(channel);
-            self.server_data.update_open_channel_ids().await;
+            self.server_data.update_open_channels().await;
         } else {
             self.socket_sender
                 .send_sender_error(
@@ -366,11 +889,82 @@ impl Socket {
         }
     }
 
-    pub async fn exit_channel(&mut self, receiver_id: SessionReceiverId) {
+    /// Moves the channel to the new id if it is not used or reserved,
+    /// the joined receivers and the channel list subscribers are notified.
+    pub async fn rename_channel(&mut self, sender_id: SessionSenderId, channel_id: ChannelId) {
+        use std::collections::hash_map::Entry;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => Arc::clone(channel),
+            None => return,
+        };
+
+        let new_channel_id = Arc::new(channel_id);
+        let mut channels = self.server_data.channels().write().await;
+        let server_channel_entry = match channels.entry(Arc::clone(&new_channel_id)) {
+            Entry::Occupied(_) => {
+                self.socket_sender
+                    .send_sender_message(
+                        sender_id,
+                        ServerSenderMessage::RenameChannelFailed(
+                            ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(
+                                new_channel_id.as_ref().to_owned(),
+                            ),
+                        ),
+                    )
+                    .await;
+                return;
+            }
+            Entry::Vacant(entry) => entry,
+        };
+        let is_claimed = self
+            .server_data
+            .reservations()
+            .claim(&new_channel_id, None)
+            .await;
+        if !is_claimed {
+            self.socket_sender
+                .send_sender_message(
+                    sender_id,
+                    ServerSenderMessage::RenameChannelFailed(
+                        ServerSenderErrorMessage::ChannelIsReserved(
+                            new_channel_id.as_ref().to_owned(),
+                        ),
+                    ),
+                )
+                .await;
+            return;
+        }
+
+        // The channel map and the channel id are updated under the same lock,
+        // so the channel is never listed under both ids.
+        let _: &mut _ = server_channel_entry.insert(Arc::downgrade(&channel));
+        let mut channel_id = channel.channel_id.write().await;
+        if let Some(old_channel_id) = channel_id.upgrade() {
+            drop(channels.remove(&old_channel_id));
+        }
+        *channel_id = Arc::downgrade(&new_channel_id);
+        drop(channel_id);
+        drop(channels);
+        self.server_data.remove_ended_channel(&new_channel_id).await;
+
+        self.socket_sender
+            .send_sender_message(
+                sender_id,
+                ServerSenderMessage::ChannelRenamed(new_channel_id.as_ref().to_owned()),
+            )
+            .await;
+        channel.send_channel_renamed(&new_channel_id).await;
+        self.server_data.update_open_channels().await;
+    }
+
+    pub async fn exit_channel(&mut self, receiver_id: SessionReceiverId, reason: CloseReason) {
         let receiver = self.channel_receivers.remove(&receiver_id);
         // TODO: reopen channel for join: set receiver from Some(Weak(null)) to None
         // TODO: or close channel when receiver disconnected
-        if receiver.is_none() {
+        if let Some(receiver) = receiver {
+            notify_receiver_left(&receiver, reason.to_remote()).await;
+        } else {
             self.socket_sender
                 .send_receiver_error(
                     receiver_id,
@@ -380,38 +974,92 @@ impl Socket {
         }
     }
 
-    pub async fn send_offer(&mut self, sender_id: SessionSenderId, sdp: SessionDescription) {
+    pub async fn send_offer(
+        &mut self,
+        sender_id: SessionSenderId,
+        sdp: SessionDescription,
+    ) -> Result<(), NackReason> {
         use crate::ChannelKind;
 
         let channel = match self.get_channel(sender_id).await {
-            Some(channel) => channel,
-            None => return,
+            Some(channel) => Arc::clone(channel),
+            None => return Err(NackReason::SessionIsNotExist),
         };
 
         let mut var = channel.sender.session_description.write().await;
-        let _: Option<_> = var.replace(sdp.clone());
+        let prev_sdp = var.replace(sdp.clone());
         drop(var);
 
+        let is_scheduled = channel.sender.scheduled_start.read().await.is_some();
+        if prev_sdp.is_none() && is_scheduled {
+            // The scheduled channel goes live.
+            self.server_data.update_open_channels().await;
+        }
+
         match &channel.kind {
             ChannelKind::PeerToPeer { receiver } => {
                 let receiver = receiver.read().await;
                 let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
                 if let Some(receiver) = receiver {
-                    receiver.send_offer(sdp).await;
+                    // The previous answer belongs to the previous negotiation.
+                    *receiver.session_description.write().await = None;
+                    receiver.send_offer(sdp).await?;
+                    channel.sender.send_offer_relayed().await;
+                    watch_answer(&self.server_data, &channel, &receiver);
                 }
+                Ok(())
             }
             ChannelKind::ClientServer { .. } => {
                 log::error!("not implemented"); // TODO
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn send_ice_restart_offer(
+        &mut self,
+        sender_id: SessionSenderId,
+        sdp: SessionDescription,
+    ) -> Result<(), NackReason> {
+        use crate::{ChannelIceCandidates, ChannelKind};
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => channel,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        // Candidates gathered before the restart belong to the previous ICE session,
+        // so they must not be replayed to receivers joining later.
+        *channel.sender.ice_candidates.write().await = ChannelIceCandidates::new();
+        match &channel.kind {
+            ChannelKind::PeerToPeer { receiver } => {
+                let receiver = receiver.read().await;
+                let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
+                if let Some(receiver) = receiver {
+                    *receiver.ice_candidates.write().await = ChannelIceCandidates::new();
+                }
+            }
+            ChannelKind::ClientServer { receivers, .. } => {
+                let receivers = receivers.read().await;
+                for receiver in receivers.iter().filter_map(|receiver| receiver.upgrade()) {
+                    *receiver.ice_candidates.write().await = ChannelIceCandidates::new();
+                }
             }
         }
+
+        self.send_offer(sender_id, sdp).await
     }
 
-    pub async fn send_answer(&mut self, receiver_id: SessionReceiverId, sdp: SessionDescription) {
+    pub async fn send_answer(
+        &mut self,
+        receiver_id: SessionReceiverId,
+        sdp: SessionDescription,
+    ) -> Result<(), NackReason> {
         use crate::ChannelKind;
 
         let receiver = match self.get_receiver(receiver_id).await {
             Some(receiver) => receiver,
-            None => return,
+            None => return Err(NackReason::SessionIsNotExist),
         };
 
         let mut var = receiver.session_description.write().await;
@@ -420,16 +1068,79 @@ impl Socket {
 
         let channel = match receiver.channel.upgrade() {
             Some(channel) => channel,
-            None => return,
+            None => return Err(NackReason::PeerDisconnected),
         };
 
         match &channel.kind {
             ChannelKind::PeerToPeer { .. } => {
-                channel.sender.send_answer(sdp).await;
+                // The slot of a receiver which answered too late is already released.
+                if channel.is_receiver_attached(receiver).await {
+                    channel.sender.send_answer(sdp).await
+                } else {
+                    Err(NackReason::SessionIsNotExist)
+                }
             }
             ChannelKind::ClientServer { .. } => {
                 log::error!("not implemented"); // TODO
+                Ok(())
+            }
+        }
+    }
+
+    /// Relays the receiver offer, it is not cached since only the joined receiver offers.
+    pub async fn send_renegotiation_offer(
+        &mut self,
+        receiver_id: SessionReceiverId,
+        sdp: SessionDescription,
+    ) -> Result<(), NackReason> {
+        use crate::ChannelKind;
+
+        let receiver = match self.get_receiver(receiver_id).await {
+            Some(receiver) => receiver,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        let channel = match receiver.channel.upgrade() {
+            Some(channel) => channel,
+            None => return Err(NackReason::PeerDisconnected),
+        };
+
+        match &channel.kind {
+            ChannelKind::PeerToPeer { .. } => {
+                if channel.is_receiver_attached(receiver).await {
+                    channel.sender.send_offer(sdp).await
+                } else {
+                    Err(NackReason::SessionIsNotExist)
+                }
+            }
+            // Media is not negotiated with the relayed receivers.
+            ChannelKind::ClientServer { .. } => Err(NackReason::SessionIsNotExist),
+        }
+    }
+
+    pub async fn send_renegotiation_answer(
+        &mut self,
+        sender_id: SessionSenderId,
+        sdp: SessionDescription,
+    ) -> Result<(), NackReason> {
+        use crate::ChannelKind;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => channel,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        match &channel.kind {
+            ChannelKind::PeerToPeer { receiver } => {
+                let receiver = receiver.read().await;
+                let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
+                match receiver {
+                    Some(receiver) => receiver.send_answer(sdp).await,
+                    None => Err(NackReason::PeerDisconnected),
+                }
             }
+            // Media is not negotiated with the relayed receivers.
+            ChannelKind::ClientServer { .. } => Err(NackReason::SessionIsNotExist),
         }
     }
 
@@ -437,12 +1148,12 @@ impl Socket {
         &mut self,
         sender_id: SessionSenderId,
         ice_candidate: IceCandidate,
-    ) {
+    ) -> Result<(), NackReason> {
         use crate::ChannelKind;
 
         let channel = match self.get_channel(sender_id).await {
             Some(channel) => channel,
-            None => return,
+            None => return Err(NackReason::SessionIsNotExist),
         };
 
         let mut var = channel.sender.ice_candidates.write().await;
@@ -454,12 +1165,15 @@ impl Socket {
             ChannelKind::PeerToPeer { receiver } => {
                 let receiver = receiver.read().await;
                 let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
-                if let Some(receiver) = receiver {
-                    receiver.send_ice_candidate(ice_candidate).await;
+                match receiver {
+                    Some(receiver) => receiver.send_ice_candidate(ice_candidate).await,
+                    // Candidates are stored and sent to the receiver on join.
+                    None => Ok(()),
                 }
             }
             ChannelKind::ClientServer { .. } => {
                 log::error!("not implemented"); // TODO
+                Ok(())
             }
         }
     }
@@ -468,12 +1182,12 @@ impl Socket {
         &mut self,
         receiver_id: SessionReceiverId,
         ice_candidate: IceCandidate,
-    ) {
+    ) -> Result<(), NackReason> {
         use crate::ChannelKind;
 
         let receiver = match self.get_receiver(receiver_id).await {
             Some(receiver) => receiver,
-            None => return,
+            None => return Err(NackReason::SessionIsNotExist),
         };
 
         let mut var = receiver.ice_candidates.write().await;
@@ -481,17 +1195,18 @@ impl Socket {
         var.all_sent = false;
         drop(var);
 
-        let channel = match receiver.channel.upgrade() {
-            Some(channel) => channel,
-            None => return,
+        let channel = match receiver.attached_channel().await {
+            Ok(channel) => channel,
+            Err(reason) => return Err(reason),
         };
 
         match &channel.kind {
             ChannelKind::PeerToPeer { .. } => {
-                channel.sender.send_ice_candidate(ice_candidate).await;
+                channel.sender.send_ice_candidate(ice_candidate).await
             }
             ChannelKind::ClientServer { .. } => {
                 log::error!("not implemented"); // TODO
+                Ok(())
             }
         }
     }
@@ -534,9 +1249,9 @@ impl Socket {
         var.all_sent = true;
         drop(var);
 
-        let channel = match receiver.channel.upgrade() {
-            Some(channel) => channel,
-            None => return,
+        let channel = match receiver.attached_channel().await {
+            Ok(channel) => channel,
+            Err(_) => return,
         };
 
         match &channel.kind {
@@ -549,7 +1264,8 @@ impl Socket {
         }
     }
 
-    pub async fn send_binary_data(&mut self, sender_id: SessionSenderId, data: Vec<u8>) {
+    /// Relays the sender request to re-send the receiver candidates.
+    pub async fn request_receiver_ice_candidates(&mut self, sender_id: SessionSenderId) {
         use crate::ChannelKind;
 
         let channel = match self.get_channel(sender_id).await {
@@ -557,6 +1273,77 @@ impl Socket {
             None => return,
         };
 
+        match &channel.kind {
+            ChannelKind::PeerToPeer { receiver } => {
+                let receiver = receiver.read().await;
+                let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
+                if let Some(receiver) = receiver {
+                    receiver.send_ice_candidates_request().await;
+                }
+            }
+            ChannelKind::ClientServer { .. } => {
+                log::error!("not implemented"); // TODO
+            }
+        }
+    }
+
+    /// Relays the receiver request to re-send the sender candidates.
+    pub async fn request_sender_ice_candidates(&mut self, receiver_id: SessionReceiverId) {
+        let receiver = match self.get_receiver(receiver_id).await {
+            Some(receiver) => receiver,
+            None => return,
+        };
+
+        let channel = match receiver.attached_channel().await {
+            Ok(channel) => channel,
+            Err(_) => return,
+        };
+
+        channel.sender.send_ice_candidates_request().await;
+    }
+
+    pub async fn request_quality(
+        &mut self,
+        receiver_id: SessionReceiverId,
+        request: QualityRequest,
+    ) {
+        let receiver = match self.get_receiver(receiver_id).await {
+            Some(receiver) => receiver,
+            None => return,
+        };
+
+        let channel = match receiver.attached_channel().await {
+            Ok(channel) => channel,
+            Err(_) => return,
+        };
+
+        // Quality requests are relayed in both modes,
+        // the sender decides how to adjust encodings of its connection.
+        channel.sender.send_quality_request(request).await;
+    }
+
+    pub async fn request_ice_restart(&mut self, receiver_id: SessionReceiverId) {
+        let receiver = match self.get_receiver(receiver_id).await {
+            Some(receiver) => receiver,
+            None => return,
+        };
+
+        let channel = match receiver.attached_channel().await {
+            Ok(channel) => channel,
+            Err(_) => return,
+        };
+
+        channel.sender.send_ice_restart_request().await;
+    }
+
+    pub async fn send_binary_data(&mut self, sender_id: SessionSenderId, data: Vec<u8>) {
+        use crate::ChannelKind;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => Arc::clone(channel),
+            None => return,
+        };
+
         match &channel.kind {
             ChannelKind::PeerToPeer { receiver } => {
                 let receiver = receiver.read().await;
@@ -565,12 +1352,2753 @@ impl Socket {
                     receiver.send_binary_data(data).await;
                 }
             }
+            ChannelKind::ClientServer {
+                receivers,
+                is_loopback,
+                ..
+            } => {
+                let receivers = receivers
+                    .read()
+                    .await
+                    .iter()
+                    .filter_map(|receiver| receiver.upgrade())
+                    .collect();
+                for receiver in self.admit_relay(&channel, receivers, data.len()).await {
+                    receiver.send_binary_data(data.clone()).await;
+                }
+                if *is_loopback {
+                    self.echo_binary_data(&channel, data).await;
+                }
+            }
+        }
+    }
+
+    /// Echoes the data back to the loopback channel sender,
+    /// the echo takes the channel relay budget like the data relayed to a receiver.
+    async fn echo_binary_data(&self, channel: &Channel, data: Vec<u8>) {
+        use crate::ChannelKind;
+        use signaling_protocol::ThrottleScope;
+
+        let (relay_budget, relay_throttle) = match &channel.kind {
+            ChannelKind::ClientServer {
+                relay_budget,
+                relay_throttle,
+                ..
+            } => (relay_budget, relay_throttle),
+            ChannelKind::PeerToPeer { .. } => return,
+        };
+        let relay_stats = self.server_data.relay_stats();
+        let len = data.len() as u64;
+        match relay_budget.take(len).await {
+            Ok(()) => {
+                relay_stats.record_relayed(len);
+                channel.sender.send_binary_data_echo(data).await;
+            }
+            Err(retry_after) => {
+                relay_stats.record_dropped(ThrottleScope::Channel, len);
+                let notice = relay_throttle
+                    .record(ThrottleScope::Channel, len, retry_after)
+                    .await;
+                self.notify_throttled(channel, relay_budget, notice).await;
+            }
+        }
+    }
+
+    /// Relays the data only to the channel receivers subscribed to its topic.
+    pub async fn send_topic_data(
+        &mut self,
+        sender_id: SessionSenderId,
+        topic: Topic,
+        data: Vec<u8>,
+    ) {
+        use crate::ChannelKind;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => Arc::clone(channel),
+            None => return,
+        };
+
+        match &channel.kind {
+            ChannelKind::PeerToPeer { receiver } => {
+                let receiver = receiver.read().await;
+                let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
+                if let Some(receiver) = receiver {
+                    receiver.send_topic_data(&topic, &data).await;
+                }
+            }
+            ChannelKind::ClientServer { receivers, .. } => {
+                let mut subscribed = Vec::new();
+                for receiver in receivers.read().await.iter().filter_map(Weak::upgrade) {
+                    if receiver.is_subscribed(&topic).await {
+                        subscribed.push(receiver);
+                    }
+                }
+                for receiver in self.admit_relay(&channel, subscribed, data.len()).await {
+                    receiver.send_topic_data(&topic, &data).await;
+                }
+            }
+        }
+    }
+
+    /// Filters the client-server channel receivers of the relayed data
+    /// by the channel and the receiver relay throughput caps,
+    /// the sender is notified of the dropped data.
+    async fn admit_relay(
+        &self,
+        channel: &Channel,
+        receivers: Vec<Arc<ChannelReceiver>>,
+        len: usize,
+    ) -> Vec<Arc<ChannelReceiver>> {
+        use crate::ChannelKind;
+        use signaling_protocol::ThrottleScope;
+
+        let (relay_budget, relay_throttle) = match &channel.kind {
+            ChannelKind::ClientServer {
+                relay_budget,
+                relay_throttle,
+                ..
+            } => (relay_budget, relay_throttle),
+            ChannelKind::PeerToPeer { .. } => return receivers,
+        };
+        let relay_stats = self.server_data.relay_stats();
+        let len = len as u64;
+
+        let total = len.saturating_mul(receivers.len() as u64);
+        if let Err(retry_after) = relay_budget.take(total).await {
+            relay_stats.record_dropped(ThrottleScope::Channel, total);
+            let notice = relay_throttle
+                .record(ThrottleScope::Channel, total, retry_after)
+                .await;
+            self.notify_throttled(channel, relay_budget, notice).await;
+            return Vec::new();
+        }
+
+        let mut admitted = Vec::with_capacity(receivers.len());
+        let mut dropped_bytes = 0;
+        let mut retry_after = Duration::ZERO;
+        for receiver in receivers {
+            match receiver.relay_budget.take(len).await {
+                Ok(()) => admitted.push(receiver),
+                Err(receiver_retry_after) => {
+                    dropped_bytes += len;
+                    retry_after = retry_after.max(receiver_retry_after);
+                }
+            }
+        }
+        relay_stats.record_relayed(len.saturating_mul(admitted.len() as u64));
+        if dropped_bytes > 0 {
+            relay_stats.record_dropped(ThrottleScope::Receivers, dropped_bytes);
+            let notice = relay_throttle
+                .record(ThrottleScope::Receivers, dropped_bytes, retry_after)
+                .await;
+            self.notify_throttled(channel, relay_budget, notice).await;
+        }
+        admitted
+    }
+
+    async fn notify_throttled(
+        &self,
+        channel: &Channel,
+        relay_budget: &RelayBudget,
+        notice: Option<ThrottleNotice>,
+    ) {
+        if let Some(notice) = notice {
+            log::warn!(
+                "relay throttled: {}, {:?}; channel relay used {} of {} bytes; totals: {:?}",
+                self.addr,
+                notice,
+                relay_budget.used().await,
+                relay_budget.burst(),
+                self.server_data.relay_stats().totals()
+            );
+            channel.sender.send_throttled(notice).await;
+        }
+    }
+
+    pub async fn subscribe_topic(&mut self, receiver_id: SessionReceiverId, topic: Topic) {
+        if let Some(receiver) = self.get_receiver(receiver_id).await {
+            let _: bool = receiver.topics.write().await.insert(topic);
+        }
+    }
+
+    pub async fn unsubscribe_topic(&mut self, receiver_id: SessionReceiverId, topic: Topic) {
+        if let Some(receiver) = self.get_receiver(receiver_id).await {
+            let _: bool = receiver.topics.write().await.remove(&topic);
+        }
+    }
+
+    pub async fn send_snapshot(&mut self, sender_id: SessionSenderId, snapshot: Vec<u8>) {
+        use crate::ChannelKind;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        let mut var = channel.sender.snapshot.write().await;
+        let _: Option<_> = var.replace(snapshot.clone());
+        drop(var);
+
+        match &channel.kind {
+            // Peer-to-peer receiver gets the media directly,
+            // so the snapshot is only stored for channel previews.
+            ChannelKind::PeerToPeer { .. } => {}
+            ChannelKind::ClientServer { receivers, .. } => {
+                let receivers = receivers.read().await;
+                for receiver in receivers.iter().filter_map(|receiver| receiver.upgrade()) {
+                    receiver.send_snapshot(snapshot.clone()).await;
+                }
+            }
+        }
+    }
+
+    /// Oversized thumbnails are dropped, the rest of the preview is still published.
+    pub async fn publish_preview(
+        &mut self,
+        sender_id: SessionSenderId,
+        mut preview: ChannelPreview,
+    ) {
+        use signaling_protocol::MAX_PREVIEW_THUMBNAIL_SIZE;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => Arc::clone(channel),
+            None => return,
+        };
+
+        let thumbnail_size = preview.thumbnail.as_ref().map_or(0, Vec::len);
+        if thumbnail_size > MAX_PREVIEW_THUMBNAIL_SIZE {
+            log::warn!(
+                "preview thumbnail dropped: {}, {} bytes exceed the limit of {} bytes",
+                self.addr,
+                thumbnail_size,
+                MAX_PREVIEW_THUMBNAIL_SIZE
+            );
+            preview.thumbnail = None;
+        }
+
+        let mut var = channel.sender.preview.write().await;
+        let _: Option<_> = var.replace(preview);
+        drop(var);
+
+        self.server_data.update_channel_preview(&channel).await;
+    }
+
+    pub async fn schedule_start(
+        &mut self,
+        sender_id: SessionSenderId,
+        scheduled_start: Option<Duration>,
+    ) {
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        *channel.sender.scheduled_start.write().await = scheduled_start;
+
+        self.server_data.update_open_channels().await;
+    }
+
+    /// Adds the summary to the aggregate statistics and logs the updated totals.
+    pub async fn report_session_summary(&mut self, role: SessionRole, summary: SessionSummary) {
+        let totals = self
+            .server_data
+            .session_stats()
+            .record(role, &summary)
+            .await;
+        log::info!(
+            "session summary: {}, {:?}, {:?}; totals: {:?}",
+            self.addr,
+            role,
+            summary,
+            totals
+        );
+    }
+
+    pub async fn report_milestones(&mut self, reports: Vec<MilestoneReport>) {
+        let totals = self
+            .server_data
+            .session_stats()
+            .record_milestones(&reports)
+            .await;
+        let averages: Vec<_> = totals
+            .iter()
+            .map(|(milestone, totals)| (milestone, totals.count, totals.average_elapsed()))
+            .collect();
+        log::info!(
+            "call milestones: {}, {:?}; counts and average times: {:?}",
+            self.addr,
+            reports,
+            averages
+        );
+    }
+
+    pub async fn publish_sender_public_key(
+        &mut self,
+        sender_id: SessionSenderId,
+        public_key: PublicKey,
+    ) -> Result<(), NackReason> {
+        use crate::ChannelKind;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => channel,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        let mut var = channel.sender.public_key.write().await;
+        let _: Option<_> = var.replace(public_key.clone());
+        drop(var);
+
+        match &channel.kind {
+            ChannelKind::PeerToPeer { receiver } => {
+                let receiver = receiver.read().await;
+                let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
+                match receiver {
+                    Some(receiver) => receiver.send_peer_public_key(public_key).await,
+                    // The key is stored and sent to the receiver on join.
+                    None => Ok(()),
+                }
+            }
             ChannelKind::ClientServer { .. } => {
                 log::error!("not implemented"); // TODO
+                Ok(())
             }
         }
     }
-}
 
-#[derive(Error, Debug)]
-pub enum NewSessionError {}
+    pub async fn publish_receiver_public_key(
+        &mut self,
+        receiver_id: SessionReceiverId,
+        public_key: PublicKey,
+    ) -> Result<(), NackReason> {
+        let receiver = match self.get_receiver(receiver_id).await {
+            Some(receiver) => receiver,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        let channel = match receiver.attached_channel().await {
+            Ok(channel) => channel,
+            Err(reason) => return Err(reason),
+        };
+
+        channel.sender.send_peer_public_key(public_key).await
+    }
+
+    pub async fn publish_sender_capabilities(
+        &mut self,
+        sender_id: SessionSenderId,
+        capabilities: PeerCapabilities,
+    ) -> Result<(), NackReason> {
+        use crate::ChannelKind;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => channel,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        let _: Option<_> = channel
+            .sender
+            .capabilities
+            .write()
+            .await
+            .replace(capabilities);
+
+        match &channel.kind {
+            ChannelKind::PeerToPeer { receiver } => {
+                let receiver = receiver.read().await;
+                let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
+                match receiver {
+                    Some(receiver) => receiver.send_peer_capabilities(capabilities).await,
+                    // The capabilities are stored and sent to the receiver on join.
+                    None => Ok(()),
+                }
+            }
+            // Candidates are not exchanged with the relayed receivers.
+            ChannelKind::ClientServer { .. } => Ok(()),
+        }
+    }
+
+    pub async fn publish_receiver_capabilities(
+        &mut self,
+        receiver_id: SessionReceiverId,
+        capabilities: PeerCapabilities,
+    ) -> Result<(), NackReason> {
+        let receiver = match self.get_receiver(receiver_id).await {
+            Some(receiver) => receiver,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        let channel = match receiver.attached_channel().await {
+            Ok(channel) => channel,
+            Err(reason) => return Err(reason),
+        };
+
+        channel.sender.send_peer_capabilities(capabilities).await
+    }
+
+    pub async fn request_peer_network(
+        &mut self,
+        sender_id: SessionSenderId,
+    ) -> Result<(), NackReason> {
+        use crate::ChannelKind;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => channel,
+            None => return Err(NackReason::SessionIsNotExist),
+        };
+
+        *channel.sender.is_peer_network_requested.write().await = true;
+
+        match &channel.kind {
+            ChannelKind::PeerToPeer { receiver } => {
+                let receiver = receiver.read().await;
+                let receiver = receiver.as_ref().and_then(|receiver| receiver.upgrade());
+                // Otherwise the peer network is sent on join.
+                if let Some(receiver) = receiver {
+                    channel.send_peer_network(&receiver).await;
+                }
+                Ok(())
+            }
+            // Candidates are not exchanged with the relayed receivers.
+            ChannelKind::ClientServer { .. } => Ok(()),
+        }
+    }
+
+    /// Starts storing the recording chunks of the channel sender on the server.
+    pub async fn start_recording(&mut self, sender_id: SessionSenderId, mime_type: String) {
+        use signaling_protocol::RecordingErrorMessage;
+
+        let channel = match self.get_channel(sender_id).await {
+            Some(channel) => Arc::clone(channel),
+            None => return,
+        };
+
+        let result = match self.server_data.recording_store() {
+            None => Err(RecordingErrorMessage::RecordingIsDisabled),
+            Some(_) if self.recordings.contains_key(&sender_id) => {
+                Err(RecordingErrorMessage::RecordingIsAlreadyStarted)
+            }
+            Some(recording_store) => match channel.channel_id.read().await.upgrade() {
+                Some(channel_id) => recording_store.create(&channel_id, &mime_type).await,
+                None => Err(RecordingErrorMessage::RecordingStorageFailed),
+            },
+        };
+        let message = match result {
+            Ok(recording) => {
+                let _: Option<_> = self.recordings.insert(sender_id, recording);
+                ServerSenderMessage::RecordingStarted
+            }
+            Err(err) => ServerSenderMessage::RecordingFailed(err),
+        };
+        self.socket_sender
+            .send_sender_message(sender_id, message)
+            .await;
+    }
+
+    /// Appends the chunk to the started recording,
+    /// the recording is stopped if the chunk could not be stored.
+    pub async fn send_recording_chunk(&mut self, sender_id: SessionSenderId, chunk: Vec<u8>) {
+        use signaling_protocol::RecordingErrorMessage;
+
+        if self.get_channel(sender_id).await.is_none() {
+            return;
+        }
+
+        let result = match self.recordings.get_mut(&sender_id) {
+            Some(recording) => recording.write(&chunk).await,
+            None => Err(RecordingErrorMessage::RecordingIsNotStarted),
+        };
+        if let Err(err) = result {
+            if let Some(recording) = self.recordings.remove(&sender_id) {
+                let _: u64 = recording.finish().await;
+            }
+            self.socket_sender
+                .send_sender_message(sender_id, ServerSenderMessage::RecordingFailed(err))
+                .await;
+        }
+    }
+
+    pub async fn stop_recording(&mut self, sender_id: SessionSenderId) {
+        use signaling_protocol::RecordingErrorMessage;
+
+        if self.get_channel(sender_id).await.is_none() {
+            return;
+        }
+
+        let message = match self.recordings.remove(&sender_id) {
+            Some(recording) => ServerSenderMessage::RecordingStopped {
+                size: recording.finish().await,
+            },
+            None => {
+                ServerSenderMessage::RecordingFailed(RecordingErrorMessage::RecordingIsNotStarted)
+            }
+        };
+        self.socket_sender
+            .send_sender_message(sender_id, message)
+            .await;
+    }
+
+    /// Reserves the channel id if it is not open or reserved yet.
+    pub async fn reserve_channel(&mut self, channel_id: ChannelId) {
+        use crate::reservation::RESERVATION_DURATION;
+        use signaling_protocol::ReservationErrorMessage;
+
+        let channels = self.server_data.channels().read().await;
+        let is_open = channels
+            .get(&channel_id)
+            .and_then(|channel| channel.upgrade())
+            .is_some();
+        let result = if is_open {
+            Err(ReservationErrorMessage::ChannelIsAlreadyOpen)
+        } else {
+            self.server_data
+                .reservations()
+                .reserve(channel_id.clone(), self.socket_id)
+                .await
+        };
+        drop(channels);
+
+        let message = match result {
+            Ok(claim_token) => ServerMessage::ChannelReserved {
+                channel_id,
+                claim_token,
+                valid_for: RESERVATION_DURATION,
+            },
+            Err(reason) => ServerMessage::ChannelReservationFailed { channel_id, reason },
+        };
+        self.socket_sender.send(message).await;
+    }
+
+    pub async fn register_peer(&mut self, peer_id: PeerId) {
+        let result = self
+            .server_data
+            .channel_requests()
+            .register_peer(peer_id.clone(), &self.socket_sender)
+            .await;
+        if let Err(reason) = result {
+            self.socket_sender
+                .send(ServerMessage::PeerRegistrationFailed { peer_id, reason })
+                .await;
+        }
+    }
+
+    /// Relays the channel request to the registered peer,
+    /// the requester is notified if the peer is not found.
+    pub async fn request_channel(&mut self, request_id: ChannelRequestId, peer_id: PeerId) {
+        let result = self
+            .server_data
+            .channel_requests()
+            .request(&self.socket_sender, request_id, &peer_id)
+            .await;
+        match result {
+            Ok(request) => {
+                request
+                    .target
+                    .send(ServerMessage::ChannelRequested {
+                        request_id: request.request_id,
+                        from: request.from,
+                    })
+                    .await
+            }
+            Err(reason) => {
+                self.socket_sender
+                    .send(ServerMessage::ChannelRequestFailed { request_id, reason })
+                    .await
+            }
+        }
+    }
+
+    /// Relays the answer to the requester, the accepted channel must be opened by this client.
+    pub async fn answer_channel_request(
+        &mut self,
+        request_id: ChannelRequestId,
+        answer: Result<ChannelId, ChannelRequestDeclineReason>,
+    ) {
+        use signaling_protocol::ChannelRequestErrorMessage;
+
+        if let Ok(channel_id) = &answer {
+            let mut is_opened = false;
+            for channel in self.channel_senders.values() {
+                let opened_id = channel.channel_id.read().await.upgrade();
+                is_opened |= opened_id.as_deref() == Some(channel_id);
+            }
+            if !is_opened {
+                self.socket_sender
+                    .send(ServerMessage::ChannelRequestFailed {
+                        request_id,
+                        reason: ChannelRequestErrorMessage::ChannelIsNotOpened,
+                    })
+                    .await;
+                return;
+            }
+        }
+
+        let result = self
+            .server_data
+            .channel_requests()
+            .answer(&self.socket_sender, request_id)
+            .await;
+        match result {
+            Ok((requester, request_id)) => {
+                let message = match answer {
+                    Ok(channel_id) => ServerMessage::ChannelRequestAccepted {
+                        request_id,
+                        channel_id,
+                    },
+                    Err(reason) => ServerMessage::ChannelRequestDeclined { request_id, reason },
+                };
+                requester.send(message).await
+            }
+            Err(reason) => {
+                self.socket_sender
+                    .send(ServerMessage::ChannelRequestFailed { request_id, reason })
+                    .await
+            }
+        }
+    }
+
+    /// Channels with a secret send the snapshot only with the matching credential,
+    /// a wrong one counts as a failed join attempt.
+    pub async fn request_channel_snapshot(
+        &mut self,
+        channel_id: ChannelId,
+        credential: Option<ChannelSecret>,
+    ) {
+        let channels = self.server_data.channels().read().await;
+        let channel = channels
+            .get(&channel_id)
+            .and_then(|channel| channel.upgrade());
+        drop(channels);
+
+        let snapshot = match channel {
+            Some(channel) if channel.accepts_credential(credential.as_ref()) => {
+                channel.sender.snapshot.read().await.clone()
+            }
+            Some(_) => {
+                // Listed channels are requested without a credential, it is not a guess.
+                if credential.is_some() {
+                    self.failed_join_attempts += 1;
+                }
+                None
+            }
+            None => None,
+        };
+        if let Some(snapshot) = snapshot {
+            self.socket_sender
+                .send(ServerMessage::ChannelSnapshot {
+                    channel_id,
+                    snapshot,
+                })
+                .await;
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum NewSessionError {
+    #[error("WebSocket handshake error: {0}")]
+    HandshakeError(#[from] WebSocketError),
+}
+
+/// Picks the first codec offered by the client that is supported by the server,
+/// clients that do not offer any subprotocol use the default codec.
+fn negotiate_codec(
+    request: &tokio_tungstenite::tungstenite::handshake::server::Request,
+) -> Option<WireCodec> {
+    request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|subprotocol| WireCodec::from_subprotocol(subprotocol.trim()))
+}
+
+async fn end_scheduled_channel(server_data: &ServerData, channel: &Channel) {
+    let channel_id = channel.channel_id.read().await.upgrade();
+    if let Some(channel_id) = channel_id {
+        if let Some(channel_info) = channel.ended_info(channel_id.as_ref().to_owned()).await {
+            server_data.add_ended_channel(channel_info).await;
+        }
+    }
+}
+
+/// Notifies the channel sender if the receiver is still attached to the channel.
+async fn notify_receiver_left(channel_receiver: &ChannelReceiver, reason: CloseReason) {
+    if let Some(channel) = channel_receiver.channel.upgrade() {
+        if channel.is_receiver_attached(channel_receiver).await {
+            channel.sender.send_receiver_left(reason).await;
+        }
+    }
+}
+
+async fn create_transcript(
+    server_data: &ServerData,
+    channel_id: &ChannelId,
+) -> Option<ChannelTranscript> {
+    use log::error;
+
+    let transcript_recorder = server_data.transcript_recorder()?;
+    match transcript_recorder.create(channel_id).await {
+        Ok(transcript) => Some(transcript),
+        Err(err) => {
+            error!("channel {:?} transcript create error: {}", channel_id, err);
+            None
+        }
+    }
+}
+
+/// Removes the transcript of a channel that failed to open.
+async fn discard_transcript(transcript: Option<ChannelTranscript>) {
+    if let Some(transcript) = transcript {
+        transcript.discard().await;
+    }
+}
+
+fn watch_answer(
+    server_data: &Arc<ServerData>,
+    channel: &Arc<Channel>,
+    channel_receiver: &Arc<ChannelReceiver>,
+) {
+    use core::sync::atomic::Ordering;
+    use tokio::spawn;
+    use tokio::time::sleep;
+
+    let generation = channel_receiver
+        .offer_generation
+        .fetch_add(1, Ordering::Relaxed)
+        .wrapping_add(1);
+    let server_data = Arc::clone(server_data);
+    let channel = Arc::downgrade(channel);
+    let channel_receiver = Arc::downgrade(channel_receiver);
+    drop(spawn(async move {
+        sleep(ANSWER_TIMEOUT).await;
+        if let (Some(channel), Some(channel_receiver)) =
+            (channel.upgrade(), channel_receiver.upgrade())
+        {
+            release_unanswered_receiver(&server_data, &channel, &channel_receiver, generation)
+                .await;
+        }
+    }));
+}
+
+/// Releases the receiver slot if the offer of the generation is still unanswered,
+/// the receiver re-offered since then is watched by the newer offer timer.
+async fn release_unanswered_receiver(
+    server_data: &ServerData,
+    channel: &Channel,
+    channel_receiver: &ChannelReceiver,
+    generation: u32,
+) {
+    use crate::ChannelKind;
+    use core::sync::atomic::Ordering;
+
+    if channel_receiver.offer_generation.load(Ordering::Relaxed) != generation
+        || channel_receiver.session_description.read().await.is_some()
+        || !channel.is_receiver_attached(channel_receiver).await
+    {
+        return;
+    }
+
+    match &channel.kind {
+        ChannelKind::PeerToPeer { receiver } => {
+            *receiver.write().await = None;
+        }
+        // Client-server receivers are answered by the server itself,
+        // `watch_answer` is only scheduled for the peer-to-peer receivers.
+        ChannelKind::ClientServer { .. } => return,
+    }
+
+    if let Some(transcript) = &channel.transcript {
+        transcript
+            .record(None, TranscriptEvent::NegotiationTimedOut)
+            .await;
+    }
+    channel
+        .sender
+        .send_receiver_left(CloseReason::Timeout)
+        .await;
+    let channel_id = channel.channel_id.read().await.upgrade();
+    if let Some(channel_id) = channel_id {
+        log::info!("channel {:?} offer was not answered in time", channel_id);
+        channel_receiver
+            .send_error(ServerReceiverErrorMessage::NegotiationTimedOut(
+                channel_id.as_ref().to_owned(),
+            ))
+            .await;
+    }
+    server_data.update_open_channels().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::future::BoxFuture;
+    use signaling_protocol::{
+        ChannelPreview, ChannelSecret, ChannelStatus, ClaimToken, ClientMessage,
+        ClientReceiverMessage, ClientSenderMessage, CloseReason, IceCandidate, NetworkMode,
+        ResumeToken, ServerMessage, ServerReceiverErrorMessage, ServerReceiverMessage,
+        ServerSenderErrorMessage, ServerSenderMessage, SessionDescription, SessionReceiverId,
+        SessionSenderId, WireCodec,
+    };
+    use tokio_tungstenite::tungstenite::protocol::Message;
+    use tokio_tungstenite::tungstenite::Error as WebSocketError;
+
+    use super::{Socket, SocketId};
+    use crate::socket_sender::MessageSink;
+    use crate::{ServerData, SocketSender};
+
+    const CODEC: WireCodec = WireCodec::DEFAULT;
+
+    /// Records messages sent to the client instead of writing them to a WebSocket.
+    #[derive(Debug, Default)]
+    struct RecordingSink(Arc<Mutex<Vec<Message>>>);
+
+    impl MessageSink for RecordingSink {
+        fn send_message(&self, message: Message) -> BoxFuture<'_, Result<(), WebSocketError>> {
+            self.0.lock().unwrap().push(message);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestClient {
+        socket: Socket,
+        messages: Arc<Mutex<Vec<Message>>>,
+    }
+
+    impl TestClient {
+        async fn connect(server_data: &Arc<ServerData>, socket_id: u32) -> Self {
+            let sink = RecordingSink::default();
+            let messages = Arc::clone(&sink.0);
+            let socket_sender = Arc::new(SocketSender::new(sink, CODEC));
+            let socket = Socket::with_sender(
+                SocketId(socket_id),
+                Arc::clone(server_data),
+                socket_sender,
+                "127.0.0.1:9010".parse().unwrap(),
+            )
+            .await;
+            Self { socket, messages }
+        }
+
+        async fn send(&mut self, message: ClientMessage) {
+            let _: Result<(), _> = self.socket.handle_message(message).await;
+        }
+
+        async fn send_sender(&mut self, sender_id: u64, message: ClientSenderMessage) {
+            self.send(ClientMessage::SenderMessage {
+                sender_id: SessionSenderId(sender_id),
+                message,
+            })
+            .await
+        }
+
+        async fn send_receiver(&mut self, receiver_id: u64, message: ClientReceiverMessage) {
+            self.send(ClientMessage::ReceiverMessage {
+                receiver_id: SessionReceiverId(receiver_id),
+                message,
+            })
+            .await
+        }
+
+        async fn open_channel(&mut self, sender_id: u64, name: &str) {
+            self.open_claimed_channel(sender_id, name, None).await
+        }
+
+        async fn open_claimed_channel(
+            &mut self,
+            sender_id: u64,
+            name: &str,
+            claim_token: Option<ClaimToken>,
+        ) {
+            self.send_sender(
+                sender_id,
+                ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id(name),
+                    network_mode: NetworkMode::PeerToPeer,
+                    claim_token,
+                    secret: None,
+                    resume_token: None,
+                },
+            )
+            .await
+        }
+
+        async fn open_protected_channel(&mut self, sender_id: u64, name: &str, secret: &str) {
+            self.send_sender(
+                sender_id,
+                ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id(name),
+                    network_mode: NetworkMode::PeerToPeer,
+                    claim_token: None,
+                    secret: Some(ChannelSecret(secret.to_owned())),
+                    resume_token: None,
+                },
+            )
+            .await
+        }
+
+        async fn open_resumable_channel(&mut self, sender_id: u64, name: &str, token: &str) {
+            self.send_sender(
+                sender_id,
+                ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id(name),
+                    network_mode: NetworkMode::PeerToPeer,
+                    claim_token: None,
+                    secret: None,
+                    resume_token: Some(ResumeToken(token.to_owned())),
+                },
+            )
+            .await
+        }
+
+        async fn open_client_server_channel(&mut self, sender_id: u64, name: &str) {
+            self.send_sender(
+                sender_id,
+                ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id(name),
+                    network_mode: NetworkMode::ClientServer,
+                    claim_token: None,
+                    secret: None,
+                    resume_token: None,
+                },
+            )
+            .await
+        }
+
+        async fn join_channel(&mut self, receiver_id: u64, name: &str) {
+            self.join_protected_channel(receiver_id, name, None).await
+        }
+
+        async fn join_protected_channel(
+            &mut self,
+            receiver_id: u64,
+            name: &str,
+            credential: Option<&str>,
+        ) {
+            self.send_receiver(
+                receiver_id,
+                ClientReceiverMessage::JoinChannel {
+                    channel_id: channel_id(name),
+                    credential: credential.map(|credential| ChannelSecret(credential.to_owned())),
+                },
+            )
+            .await
+        }
+
+        async fn disconnect(self) {
+            self.socket.clear(CloseReason::RemoteClosed).await
+        }
+
+        /// Takes the recorded messages except the open channel list broadcasts.
+        fn take_messages(&self) -> Vec<ServerMessage> {
+            self.messages
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|message| match message {
+                    Message::Binary(data) => CODEC.decode_frame(&data, u32::MAX).unwrap(),
+                    message => panic!("unexpected message: {:?}", message),
+                })
+                .filter(|message| !matches!(message, ServerMessage::OpenChannelsChanged(_)))
+                .collect()
+        }
+
+        fn take_open_channels(&self) -> Vec<Vec<String>> {
+            self.messages
+                .lock()
+                .unwrap()
+                .drain(..)
+                .filter_map(|message| match message {
+                    Message::Binary(data) => match CODEC.decode_frame(&data, u32::MAX).unwrap() {
+                        ServerMessage::OpenChannelsChanged(channels) => Some(
+                            channels
+                                .into_iter()
+                                .map(|channel| channel.channel_id.0)
+                                .collect(),
+                        ),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn take_channel_statuses(&self) -> Vec<Vec<(String, ChannelStatus)>> {
+            self.messages
+                .lock()
+                .unwrap()
+                .drain(..)
+                .filter_map(|message| match message {
+                    Message::Binary(data) => match CODEC.decode_frame(&data, u32::MAX).unwrap() {
+                        ServerMessage::OpenChannelsChanged(channels) => Some(
+                            channels
+                                .into_iter()
+                                .map(|channel| (channel.channel_id.0, channel.status))
+                                .collect(),
+                        ),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn take_channel_previews(&self) -> Vec<Vec<(String, Option<ChannelPreview>)>> {
+            self.messages
+                .lock()
+                .unwrap()
+                .drain(..)
+                .filter_map(|message| match message {
+                    Message::Binary(data) => match CODEC.decode_frame(&data, u32::MAX).unwrap() {
+                        ServerMessage::OpenChannelsChanged(channels) => Some(
+                            channels
+                                .into_iter()
+                                .map(|channel| (channel.channel_id.0, channel.preview))
+                                .collect(),
+                        ),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    fn channel_id(name: &str) -> signaling_protocol::ChannelId {
+        signaling_protocol::ChannelId(name.to_owned())
+    }
+
+    fn open_channel_success(sender_id: u64) -> ServerMessage {
+        ServerMessage::SenderMessage {
+            sender_id: SessionSenderId(sender_id),
+            message: ServerSenderMessage::OpenChannelSuccess,
+        }
+    }
+
+    fn sender_error(sender_id: u64, err: ServerSenderErrorMessage) -> ServerMessage {
+        ServerMessage::SenderMessage {
+            sender_id: SessionSenderId(sender_id),
+            message: ServerSenderMessage::Error(err),
+        }
+    }
+
+    fn receiver_joined(sender_id: u64, connection_id: u32) -> ServerMessage {
+        use signaling_protocol::ReceiverInfo;
+
+        ServerMessage::SenderMessage {
+            sender_id: SessionSenderId(sender_id),
+            message: ServerSenderMessage::ReceiverJoined {
+                receiver_info: ReceiverInfo { connection_id },
+            },
+        }
+    }
+
+    fn receiver_message(receiver_id: u64, message: ServerReceiverMessage) -> ServerMessage {
+        ServerMessage::ReceiverMessage {
+            receiver_id: SessionReceiverId(receiver_id),
+            message,
+        }
+    }
+
+    fn join_channel_success(receiver_id: u64) -> ServerMessage {
+        receiver_message(receiver_id, ServerReceiverMessage::JoinChannelSuccess)
+    }
+
+    fn receiver_error(receiver_id: u64, err: ServerReceiverErrorMessage) -> ServerMessage {
+        receiver_message(receiver_id, ServerReceiverMessage::Error(err))
+    }
+
+    fn ice_candidate(candidate: &str) -> IceCandidate {
+        IceCandidate {
+            candidate: candidate.to_owned(),
+            sdp_mid: Some("0".to_owned()),
+            sdp_m_line_index: Some(0),
+        }
+    }
+
+    fn server_data() -> Arc<ServerData> {
+        Arc::new(ServerData::new(None, None))
+    }
+
+    #[tokio::test]
+    async fn open_channel_is_listed_to_all_clients() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let receiver = TestClient::connect(&server_data, 1).await;
+        let _: Vec<_> = sender.take_open_channels();
+        let _: Vec<_> = receiver.take_open_channels();
+
+        sender.open_channel(1, "a").await;
+
+        assert_eq!(sender.take_open_channels(), vec![vec!["a".to_owned()]]);
+        assert_eq!(receiver.take_open_channels(), vec![vec!["a".to_owned()]]);
+    }
+
+    #[tokio::test]
+    async fn paused_channel_list_is_resumed_with_full_list() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut observer = TestClient::connect(&server_data, 1).await;
+        let _: Vec<_> = observer.take_open_channels();
+
+        observer.send(ClientMessage::PauseChannelList).await;
+        sender.open_channel(1, "a").await;
+        sender.open_channel(2, "b").await;
+        assert_eq!(observer.take_open_channels(), Vec::<Vec<String>>::new());
+
+        observer.send(ClientMessage::ResumeChannelList).await;
+        let mut open_channels = observer.take_open_channels();
+        open_channels
+            .iter_mut()
+            .for_each(|channels| channels.sort());
+        assert_eq!(open_channels, vec![vec!["a".to_owned(), "b".to_owned()]]);
+
+        sender.open_channel(3, "c").await;
+        assert_eq!(observer.take_open_channels().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn open_channel_with_used_channel_id_fails() {
+        let server_data = server_data();
+        let mut first = TestClient::connect(&server_data, 0).await;
+        let mut second = TestClient::connect(&server_data, 1).await;
+
+        first.open_channel(1, "a").await;
+        second.open_channel(2, "a").await;
+
+        assert_eq!(first.take_messages(), vec![open_channel_success(1)]);
+        assert_eq!(
+            second.take_messages(),
+            vec![sender_error(
+                2,
+                ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(channel_id("a"))
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn reconnected_sender_takes_channel_over_with_resume_token() {
+        let server_data = server_data();
+        let mut stale = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let mut other = TestClient::connect(&server_data, 2).await;
+        let mut reconnected = TestClient::connect(&server_data, 3).await;
+
+        stale.open_resumable_channel(1, "a", "token").await;
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = receiver.take_messages();
+        let _: Vec<_> = stale.take_messages();
+        other.open_resumable_channel(1, "a", "other").await;
+        reconnected.open_resumable_channel(1, "a", "token").await;
+
+        assert_eq!(
+            other.take_messages(),
+            vec![sender_error(
+                1,
+                ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(channel_id("a"))
+            )]
+        );
+        assert_eq!(reconnected.take_messages(), vec![open_channel_success(1)]);
+        assert_eq!(
+            receiver.take_messages(),
+            vec![receiver_message(
+                1,
+                ServerReceiverMessage::ChannelClosed(CloseReason::RemoteClosed)
+            )]
+        );
+        assert!(stale
+            .messages
+            .lock()
+            .unwrap()
+            .drain(..)
+            .any(|message| matches!(message, Message::Close(_))));
+
+        // The stale connection timing out later leaves the resumed channel open.
+        stale.disconnect().await;
+        receiver.join_channel(2, "a").await;
+        assert_eq!(receiver.take_messages(), vec![join_channel_success(2)]);
+    }
+
+    #[tokio::test]
+    async fn open_channel_with_used_sender_id_fails() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+
+        sender.open_channel(1, "a").await;
+        sender.open_channel(1, "b").await;
+
+        assert_eq!(
+            sender.take_messages(),
+            vec![
+                open_channel_success(1),
+                sender_error(1, ServerSenderErrorMessage::SessionSenderIdIsAlreadyUsed),
+            ]
+        );
+        assert!(server_data
+            .channels()
+            .read()
+            .await
+            .get(&channel_id("b"))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn reserved_channel_is_opened_with_claim_token_only() {
+        use signaling_protocol::ReservationErrorMessage;
+
+        let server_data = server_data();
+        let mut owner = TestClient::connect(&server_data, 0).await;
+        let mut other = TestClient::connect(&server_data, 1).await;
+        let reserve = ClientMessage::ReserveChannel {
+            channel_id: channel_id("a"),
+        };
+
+        owner.send(reserve.clone()).await;
+        let claim_token = match owner.take_messages().as_slice() {
+            [ServerMessage::ChannelReserved { claim_token, .. }] => claim_token.clone(),
+            messages => panic!("unexpected messages: {:?}", messages),
+        };
+        other.send(reserve.clone()).await;
+        other.open_claimed_channel(1, "a", None).await;
+        other
+            .open_claimed_channel(2, "a", Some(ClaimToken("guess".to_owned())))
+            .await;
+        assert_eq!(
+            other.take_messages(),
+            vec![
+                ServerMessage::ChannelReservationFailed {
+                    channel_id: channel_id("a"),
+                    reason: ReservationErrorMessage::ChannelIsAlreadyReserved,
+                },
+                sender_error(
+                    1,
+                    ServerSenderErrorMessage::ChannelIsReserved(channel_id("a"))
+                ),
+                sender_error(
+                    2,
+                    ServerSenderErrorMessage::ChannelIsReserved(channel_id("a"))
+                ),
+            ]
+        );
+
+        owner.open_claimed_channel(1, "a", Some(claim_token)).await;
+        other.send(reserve).await;
+        assert_eq!(owner.take_messages(), vec![open_channel_success(1)]);
+        assert_eq!(
+            other.take_messages(),
+            vec![ServerMessage::ChannelReservationFailed {
+                channel_id: channel_id("a"),
+                reason: ReservationErrorMessage::ChannelIsAlreadyOpen,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn reservations_are_limited_and_released_on_disconnect() {
+        use signaling_protocol::ReservationErrorMessage;
+
+        let reserve = |name: &str| ClientMessage::ReserveChannel {
+            channel_id: channel_id(name),
+        };
+        let server_data = server_data();
+        let mut owner = TestClient::connect(&server_data, 0).await;
+        let mut other = TestClient::connect(&server_data, 1).await;
+
+        for index in 0..crate::reservation::MAX_CLIENT_RESERVATIONS {
+            owner.send(reserve(&index.to_string())).await;
+        }
+        owner.send(reserve("extra")).await;
+        other.send(reserve("extra")).await;
+        assert_eq!(
+            owner.take_messages().pop(),
+            Some(ServerMessage::ChannelReservationFailed {
+                channel_id: channel_id("extra"),
+                reason: ReservationErrorMessage::ReservationLimitIsReached,
+            })
+        );
+        assert!(matches!(
+            other.take_messages().as_slice(),
+            [ServerMessage::ChannelReserved { .. }]
+        ));
+
+        owner.disconnect().await;
+        other.open_channel(1, "0").await;
+        assert_eq!(other.take_messages(), vec![open_channel_success(1)]);
+    }
+
+    #[tokio::test]
+    async fn join_missing_channel_fails() {
+        let server_data = server_data();
+        let mut receiver = TestClient::connect(&server_data, 0).await;
+
+        receiver.join_channel(1, "a").await;
+
+        assert_eq!(
+            receiver.take_messages(),
+            vec![receiver_error(
+                1,
+                ServerReceiverErrorMessage::ChannelIsNotExist(channel_id("a"))
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn join_occupied_channel_fails() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut first = TestClient::connect(&server_data, 1).await;
+        let mut second = TestClient::connect(&server_data, 2).await;
+
+        sender.open_channel(1, "a").await;
+        first.join_channel(1, "a").await;
+        let _: Vec<_> = second.take_open_channels();
+        second.join_channel(2, "a").await;
+
+        assert_eq!(first.take_messages(), vec![join_channel_success(1)]);
+        assert_eq!(
+            second.take_messages(),
+            vec![receiver_error(
+                2,
+                ServerReceiverErrorMessage::ChannelIsAlreadyOccupied(channel_id("a"))
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn join_protected_channel_requires_credential() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+
+        sender.open_protected_channel(1, "a", "secret").await;
+        receiver.join_channel(1, "a").await;
+        receiver.join_protected_channel(2, "a", Some("wrong")).await;
+        receiver
+            .join_protected_channel(3, "a", Some("secret"))
+            .await;
+
+        let rejected = |receiver_id| {
+            receiver_error(
+                receiver_id,
+                ServerReceiverErrorMessage::InvalidChannelCredentials(channel_id("a")),
+            )
+        };
+        assert_eq!(
+            receiver.take_messages(),
+            vec![rejected(1), rejected(2), join_channel_success(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn session_guessing_channel_credentials_is_closed() {
+        use futures::stream;
+        use tokio::sync::watch;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let receiver = TestClient::connect(&server_data, 1).await;
+        let (_shutdown_sender, shutdown) = watch::channel(false);
+        let join_channel = |receiver_id| {
+            let message = ClientMessage::ReceiverMessage {
+                receiver_id: SessionReceiverId(receiver_id),
+                message: ClientReceiverMessage::JoinChannel {
+                    channel_id: channel_id("a"),
+                    credential: Some(ChannelSecret(format!("guess-{}", receiver_id))),
+                },
+            };
+            Message::Binary(CODEC.encode_frame(&message).unwrap())
+        };
+
+        sender.open_protected_channel(1, "a", "secret").await;
+        receiver
+            .socket
+            .run(
+                stream::iter((1..=10).map(join_channel).map(Ok)),
+                shutdown,
+                None,
+            )
+            .await;
+
+        let messages = receiver.messages.lock().unwrap();
+        let rejections = messages
+            .iter()
+            .filter(|message| match message {
+                Message::Binary(data) => matches!(
+                    CODEC.decode_frame(data, u32::MAX).unwrap(),
+                    ServerMessage::ReceiverMessage {
+                        message: ServerReceiverMessage::Error(
+                            ServerReceiverErrorMessage::InvalidChannelCredentials(_)
+                        ),
+                        ..
+                    }
+                ),
+                _ => false,
+            })
+            .count();
+        assert_eq!(rejections, super::MAX_FAILED_JOIN_ATTEMPTS as usize);
+        assert!(matches!(messages.last(), Some(Message::Close(_))));
+        assert_eq!(server_data.socket_stats().totals().protocol_violations, 1);
+    }
+
+    #[tokio::test]
+    async fn occupied_channel_is_not_listed() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+
+        sender.open_channel(1, "a").await;
+        let _: Vec<_> = receiver.take_open_channels();
+        receiver.join_channel(1, "a").await;
+
+        assert_eq!(receiver.take_open_channels(), vec![Vec::<String>::new()]);
+    }
+
+    #[tokio::test]
+    async fn preview_updates_are_sent_per_channel_without_oversized_thumbnails() {
+        use signaling_protocol::MAX_PREVIEW_THUMBNAIL_SIZE;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let observer = TestClient::connect(&server_data, 1).await;
+        sender.open_channel(1, "a").await;
+        let _: Vec<_> = sender.take_messages();
+        let _: Vec<_> = observer.take_messages();
+
+        let preview = |thumbnail_size| ChannelPreview {
+            thumbnail: Some(vec![0; thumbnail_size]),
+            caption: "caption".to_owned(),
+            intended_media: None,
+            caption_languages: Vec::new(),
+        };
+        let preview_changed = |preview| ServerMessage::ChannelPreviewChanged {
+            channel_id: channel_id("a"),
+            preview,
+        };
+
+        sender
+            .send_sender(1, ClientSenderMessage::PublishPreview(preview(16)))
+            .await;
+        assert_eq!(observer.take_messages(), vec![preview_changed(preview(16))]);
+
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::PublishPreview(preview(MAX_PREVIEW_THUMBNAIL_SIZE + 1)),
+            )
+            .await;
+        let without_thumbnail = ChannelPreview {
+            thumbnail: None,
+            ..preview(0)
+        };
+        assert_eq!(
+            observer.take_messages(),
+            vec![preview_changed(without_thumbnail)]
+        );
+
+        sender
+            .send_sender(1, ClientSenderMessage::PublishPreview(preview(16)))
+            .await;
+        assert_eq!(observer.take_open_channels(), Vec::<Vec<String>>::new());
+    }
+
+    #[tokio::test]
+    async fn secret_channel_previews_are_not_listed() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let observer = TestClient::connect(&server_data, 1).await;
+        sender.open_protected_channel(1, "a", "secret").await;
+        sender.open_channel(2, "b").await;
+        let _: Vec<_> = sender.take_messages();
+        let _: Vec<_> = observer.take_messages();
+
+        let preview = ChannelPreview {
+            thumbnail: Some(vec![0; 16]),
+            caption: "caption".to_owned(),
+            intended_media: None,
+            caption_languages: Vec::new(),
+        };
+        sender
+            .send_sender(1, ClientSenderMessage::PublishPreview(preview.clone()))
+            .await;
+        sender
+            .send_sender(2, ClientSenderMessage::PublishPreview(preview.clone()))
+            .await;
+        assert_eq!(
+            observer.take_messages(),
+            vec![ServerMessage::ChannelPreviewChanged {
+                channel_id: channel_id("b"),
+                preview: preview.clone(),
+            }]
+        );
+
+        sender.open_channel(3, "c").await;
+        let mut listed = observer.take_channel_previews().concat();
+        listed.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        assert_eq!(
+            listed,
+            vec![
+                ("a".to_owned(), None),
+                ("b".to_owned(), Some(preview)),
+                ("c".to_owned(), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn secret_channel_snapshot_requires_credential() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let request_snapshot = |credential: Option<&str>| ClientMessage::RequestChannelSnapshot {
+            channel_id: channel_id("a"),
+            credential: credential.map(|credential| ChannelSecret(credential.to_owned())),
+        };
+
+        sender.open_protected_channel(1, "a", "secret").await;
+        sender
+            .send_sender(1, ClientSenderMessage::SendSnapshot(vec![1, 2, 3]))
+            .await;
+        let _: Vec<_> = receiver.take_messages();
+
+        receiver.send(request_snapshot(None)).await;
+        receiver.send(request_snapshot(Some("wrong"))).await;
+        assert_eq!(receiver.take_messages(), vec![]);
+        assert_eq!(receiver.socket.failed_join_attempts, 1);
+
+        receiver.send(request_snapshot(Some("secret"))).await;
+        assert_eq!(
+            receiver.take_messages(),
+            vec![ServerMessage::ChannelSnapshot {
+                channel_id: channel_id("a"),
+                snapshot: vec![1, 2, 3],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_offer_and_candidates_are_replayed_on_join() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let sdp = SessionDescription("offer".to_owned());
+
+        sender.open_channel(1, "a").await;
+        sender
+            .send_sender(1, ClientSenderMessage::SendOffer(sdp.clone()))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::IceCandidate(ice_candidate("c1")))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::IceCandidate(ice_candidate("c2")))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::AllIceCandidatesSent)
+            .await;
+        assert_eq!(sender.take_messages(), vec![open_channel_success(1)]);
+
+        receiver.join_channel(1, "a").await;
+
+        assert_eq!(
+            receiver.take_messages(),
+            vec![
+                join_channel_success(1),
+                receiver_message(1, ServerReceiverMessage::ChannelOffer(sdp)),
+                receiver_message(1, ServerReceiverMessage::IceCandidate(ice_candidate("c1"))),
+                receiver_message(1, ServerReceiverMessage::IceCandidate(ice_candidate("c2"))),
+                receiver_message(1, ServerReceiverMessage::AllIceCandidatesSent),
+            ]
+        );
+        assert_eq!(
+            sender.take_messages(),
+            vec![
+                receiver_joined(1, 1),
+                ServerMessage::SenderMessage {
+                    sender_id: SessionSenderId(1),
+                    message: ServerSenderMessage::OfferRelayed,
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scheduled_channel_goes_live_on_first_offer_and_ends_on_close() {
+        use core::time::Duration;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let observer = TestClient::connect(&server_data, 1).await;
+        let sdp = SessionDescription("offer".to_owned());
+        let scheduled_start = Some(Duration::from_secs(1_700_000_000));
+
+        sender.open_channel(1, "a").await;
+        sender
+            .send_sender(1, ClientSenderMessage::ScheduleStart(scheduled_start))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::SendOffer(sdp.clone()))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::SendOffer(sdp))
+            .await;
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::CloseChannel(CloseReason::LocalClosed),
+            )
+            .await;
+
+        let _: Vec<_> = sender.take_messages();
+        let channel = |status| vec![("a".to_owned(), status)];
+        assert_eq!(
+            observer.take_channel_statuses(),
+            vec![
+                vec![],
+                channel(ChannelStatus::Live),
+                channel(ChannelStatus::Scheduled),
+                channel(ChannelStatus::Live),
+                channel(ChannelStatus::Ended),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn renamed_channel_is_relisted_and_receivers_are_notified() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let mut late_receiver = TestClient::connect(&server_data, 2).await;
+
+        sender.open_client_server_channel(1, "a").await;
+        sender.open_channel(2, "c").await;
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = sender.take_messages();
+        let _: Vec<_> = receiver.take_messages();
+
+        sender
+            .send_sender(1, ClientSenderMessage::RenameChannel(channel_id("c")))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::RenameChannel(channel_id("b")))
+            .await;
+        let sender_message = |message| ServerMessage::SenderMessage {
+            sender_id: SessionSenderId(1),
+            message,
+        };
+        assert_eq!(
+            sender.take_messages(),
+            vec![
+                sender_message(ServerSenderMessage::RenameChannelFailed(
+                    ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(channel_id("c"))
+                )),
+                sender_message(ServerSenderMessage::ChannelRenamed(channel_id("b"))),
+            ]
+        );
+        assert_eq!(
+            receiver.take_messages(),
+            vec![receiver_message(
+                1,
+                ServerReceiverMessage::ChannelRenamed(channel_id("b"))
+            )]
+        );
+        let mut open_channels = late_receiver.take_open_channels().pop().unwrap();
+        open_channels.sort();
+        assert_eq!(open_channels, vec!["b".to_owned(), "c".to_owned()]);
+
+        late_receiver.join_channel(1, "a").await;
+        late_receiver.join_channel(2, "b").await;
+        assert_eq!(
+            late_receiver.take_messages(),
+            vec![
+                receiver_error(
+                    1,
+                    ServerReceiverErrorMessage::ChannelIsNotExist(channel_id("a"))
+                ),
+                join_channel_success(2),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn close_reason_is_relayed_to_joined_receiver() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+
+        sender.open_channel(1, "a").await;
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = receiver.take_messages();
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::CloseChannel(CloseReason::LocalClosed),
+            )
+            .await;
+
+        assert_eq!(
+            receiver.take_messages(),
+            vec![receiver_message(
+                1,
+                ServerReceiverMessage::ChannelClosed(CloseReason::RemoteClosed)
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn receiver_exit_and_disconnect_are_reported_to_sender() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+
+        sender.open_channel(1, "a").await;
+        receiver.join_channel(1, "a").await;
+        receiver
+            .send_receiver(1, ClientReceiverMessage::ExitChannel(CloseReason::Timeout))
+            .await;
+        receiver.join_channel(2, "a").await;
+        receiver.disconnect().await;
+
+        let receiver_left = |reason| ServerMessage::SenderMessage {
+            sender_id: SessionSenderId(1),
+            message: ServerSenderMessage::ReceiverLeft(reason),
+        };
+        assert_eq!(
+            sender.take_messages(),
+            vec![
+                open_channel_success(1),
+                receiver_joined(1, 1),
+                receiver_left(CloseReason::Timeout),
+                receiver_joined(1, 1),
+                receiver_left(CloseReason::RemoteClosed),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn session_is_cleared_on_receive_errors() {
+        use crate::socket_stats::SocketTotals;
+        use futures::channel::mpsc;
+        use futures::stream;
+        use std::io::ErrorKind;
+        use tokio::sync::watch;
+        use tokio::task::yield_now;
+
+        let server_data = server_data();
+        let sender = TestClient::connect(&server_data, 0).await;
+        let invalid = TestClient::connect(&server_data, 1).await;
+        let mut receiver = TestClient::connect(&server_data, 2).await;
+        let (_shutdown_sender, shutdown) = watch::channel(false);
+        let open_channel = |sender_id, name| {
+            let message = ClientMessage::SenderMessage {
+                sender_id: SessionSenderId(sender_id),
+                message: ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id(name),
+                    network_mode: NetworkMode::PeerToPeer,
+                    claim_token: None,
+                    secret: None,
+                    resume_token: None,
+                },
+            };
+            Message::Binary(CODEC.encode_frame(&message).unwrap())
+        };
+
+        let (messages, socket_receiver) = mpsc::unbounded();
+        messages.unbounded_send(Ok(open_channel(1, "a"))).unwrap();
+        let peer = async {
+            while server_data.channels().read().await.is_empty() {
+                let () = yield_now().await;
+            }
+            receiver.join_channel(1, "a").await;
+            let reset = WebSocketError::Io(ErrorKind::ConnectionReset.into());
+            messages.unbounded_send(Err(reset)).unwrap();
+        };
+        let ((), ()) = tokio::join!(
+            sender.socket.run(socket_receiver, shutdown.clone(), None),
+            peer
+        );
+        invalid
+            .socket
+            .run(
+                stream::iter(vec![
+                    Ok(open_channel(2, "b")),
+                    Ok(Message::Text("open".to_owned())),
+                ]),
+                shutdown,
+                None,
+            )
+            .await;
+
+        assert!(server_data.channels().read().await.is_empty());
+        assert_eq!(
+            receiver.take_messages().pop(),
+            Some(receiver_message(
+                1,
+                ServerReceiverMessage::ChannelClosed(CloseReason::RemoteClosed)
+            ))
+        );
+        assert_eq!(
+            server_data.socket_stats().totals(),
+            SocketTotals {
+                disconnected: 1,
+                protocol_violations: 1,
+                ..SocketTotals::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_answer_timer_does_not_release_reoffered_receiver() {
+        use super::release_unanswered_receiver;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let offer = || ClientSenderMessage::SendOffer(SessionDescription("offer".to_owned()));
+
+        sender.open_channel(1, "a").await;
+        sender.send_sender(1, offer()).await;
+        receiver.join_channel(1, "a").await;
+        sender.send_sender(1, offer()).await;
+        let _: Vec<_> = sender.take_messages();
+        let _: Vec<_> = receiver.take_messages();
+        let channel = Arc::clone(&sender.socket.channel_senders[&SessionSenderId(1)]);
+        let channel_receiver =
+            Arc::clone(&receiver.socket.channel_receivers[&SessionReceiverId(1)]);
+
+        // The timer of the offer relayed on join fires after the re-offer.
+        release_unanswered_receiver(&server_data, &channel, &channel_receiver, 1).await;
+        assert_eq!(sender.take_messages(), vec![]);
+        assert!(channel.is_receiver_attached(&channel_receiver).await);
+
+        release_unanswered_receiver(&server_data, &channel, &channel_receiver, 2).await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ServerSenderMessage::ReceiverLeft(CloseReason::Timeout),
+            }]
+        );
+
+        // The released receiver no longer reaches the sender.
+        receiver
+            .send_receiver(
+                1,
+                ClientReceiverMessage::IceCandidate(ice_candidate("candidate:1")),
+            )
+            .await;
+        receiver
+            .send_receiver(1, ClientReceiverMessage::AllIceCandidatesSent)
+            .await;
+        receiver
+            .send_receiver(1, ClientReceiverMessage::RequestIceRestart)
+            .await;
+        assert_eq!(sender.take_messages(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn receiver_join_is_reported_to_sender_before_offer() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 7).await;
+
+        sender.open_channel(1, "a").await;
+        let _: Vec<_> = sender.take_messages();
+        receiver.join_channel(1, "a").await;
+
+        assert_eq!(sender.take_messages(), vec![receiver_joined(1, 7)]);
+    }
+
+    #[tokio::test]
+    async fn sender_public_key_is_replayed_before_offer_on_join() {
+        use signaling_protocol::PublicKey;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let sdp = SessionDescription("sealed:offer".to_owned());
+        let public_key = PublicKey(vec![1, 2, 3]);
+
+        sender.open_channel(1, "a").await;
+        sender
+            .send_sender(1, ClientSenderMessage::PublishPublicKey(public_key.clone()))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::SendOffer(sdp.clone()))
+            .await;
+        receiver.join_channel(1, "a").await;
+
+        assert_eq!(
+            receiver.take_messages(),
+            vec![
+                join_channel_success(1),
+                receiver_message(1, ServerReceiverMessage::PeerPublicKey(public_key)),
+                receiver_message(1, ServerReceiverMessage::ChannelOffer(sdp)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn peer_capabilities_are_exchanged_on_join() {
+        use signaling_protocol::PeerCapabilities;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let capabilities = PeerCapabilities { is_on_lan: true };
+
+        sender.open_channel(1, "a").await;
+        sender
+            .send_sender(1, ClientSenderMessage::PublishCapabilities(capabilities))
+            .await;
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = sender.take_messages();
+
+        assert_eq!(
+            receiver.take_messages(),
+            vec![
+                join_channel_success(1),
+                receiver_message(1, ServerReceiverMessage::PeerCapabilities(capabilities)),
+            ]
+        );
+
+        receiver
+            .send_receiver(1, ClientReceiverMessage::PublishCapabilities(capabilities))
+            .await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ServerSenderMessage::PeerCapabilities(capabilities),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn peer_network_is_sent_to_both_peers_on_join() {
+        use signaling_protocol::PeerNetwork;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let peer_network = PeerNetwork {
+            is_same_network: true,
+        };
+
+        sender.open_channel(1, "a").await;
+        sender
+            .send_sender(1, ClientSenderMessage::RequestPeerNetwork)
+            .await;
+        let _: Vec<_> = sender.take_messages();
+        receiver.join_channel(1, "a").await;
+
+        assert_eq!(
+            receiver.take_messages(),
+            vec![
+                join_channel_success(1),
+                receiver_message(1, ServerReceiverMessage::PeerNetwork(peer_network)),
+            ]
+        );
+        assert!(sender
+            .take_messages()
+            .contains(&ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ServerSenderMessage::PeerNetwork(peer_network),
+            }));
+    }
+
+    #[tokio::test]
+    async fn receiver_renegotiation_offer_is_relayed_to_sender() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let offer = SessionDescription("receiver offer".to_owned());
+        let answer = SessionDescription("sender answer".to_owned());
+
+        sender.open_channel(1, "a").await;
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = sender.take_messages();
+        let _: Vec<_> = receiver.take_messages();
+
+        receiver
+            .send_receiver(1, ClientReceiverMessage::SendOffer(offer.clone()))
+            .await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ServerSenderMessage::ChannelOffer(offer),
+            }]
+        );
+
+        sender
+            .send_sender(1, ClientSenderMessage::SendAnswer(answer.clone()))
+            .await;
+        assert_eq!(
+            receiver.take_messages(),
+            vec![receiver_message(
+                1,
+                ServerReceiverMessage::ChannelAnswer(answer)
+            )]
+        );
+    }
+
+    #[test]
+    fn peers_on_private_or_same_addresses_are_on_same_network() {
+        use crate::channel::is_same_network;
+
+        let addr = |addr: &str| addr.parse().unwrap();
+        assert!(is_same_network(addr("203.0.113.1"), addr("203.0.113.1")));
+        assert!(is_same_network(addr("192.168.1.2"), addr("10.0.0.3")));
+        assert!(is_same_network(addr("::ffff:192.168.1.2"), addr("fd00::1")));
+        assert!(!is_same_network(addr("203.0.113.1"), addr("203.0.113.2")));
+        assert!(!is_same_network(addr("192.168.1.2"), addr("203.0.113.1")));
+        assert!(!is_same_network(addr("2001:db8::1"), addr("fe80::1")));
+    }
+
+    #[tokio::test]
+    async fn ice_restart_offer_drops_cached_candidates() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let sdp = SessionDescription("restart".to_owned());
+
+        sender.open_channel(1, "a").await;
+        sender
+            .send_sender(1, ClientSenderMessage::IceCandidate(ice_candidate("old")))
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::SendIceRestartOffer(sdp.clone()))
+            .await;
+        receiver.join_channel(1, "a").await;
+
+        assert_eq!(
+            receiver.take_messages(),
+            vec![
+                join_channel_success(1),
+                receiver_message(1, ServerReceiverMessage::ChannelOffer(sdp)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ice_candidates_requests_are_relayed_to_the_peer() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+
+        sender.open_channel(1, "a").await;
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = sender.take_messages();
+        let _: Vec<_> = receiver.take_messages();
+
+        receiver
+            .send_receiver(1, ClientReceiverMessage::RequestIceCandidates)
+            .await;
+        sender
+            .send_sender(1, ClientSenderMessage::RequestIceCandidates)
+            .await;
+
+        assert_eq!(
+            sender.take_messages(),
+            vec![ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ServerSenderMessage::IceCandidatesRequested,
+            }]
+        );
+        assert_eq!(
+            receiver.take_messages(),
+            vec![receiver_message(
+                1,
+                ServerReceiverMessage::IceCandidatesRequested
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn topic_data_is_relayed_to_subscribed_receivers_only() {
+        use signaling_protocol::Topic;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut first = TestClient::connect(&server_data, 1).await;
+        let mut second = TestClient::connect(&server_data, 2).await;
+        let topic = |name: &str| Topic(name.to_owned());
+
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id("a"),
+                    network_mode: NetworkMode::ClientServer,
+                    claim_token: None,
+                    secret: None,
+                    resume_token: None,
+                },
+            )
+            .await;
+        first.join_channel(1, "a").await;
+        second.join_channel(2, "a").await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![
+                open_channel_success(1),
+                receiver_joined(1, 1),
+                receiver_joined(1, 2)
+            ]
+        );
+        assert_eq!(first.take_messages(), vec![join_channel_success(1)]);
+        assert_eq!(second.take_messages(), vec![join_channel_success(2)]);
+
+        first
+            .send_receiver(1, ClientReceiverMessage::SubscribeTopic(topic("chat")))
+            .await;
+        second
+            .send_receiver(2, ClientReceiverMessage::SubscribeTopic(topic("chat")))
+            .await;
+        second
+            .send_receiver(2, ClientReceiverMessage::SubscribeTopic(topic("telemetry")))
+            .await;
+        second
+            .send_receiver(2, ClientReceiverMessage::UnsubscribeTopic(topic("chat")))
+            .await;
+        for (name, data) in [("chat", 1), ("telemetry", 2), ("unknown", 3)] {
+            sender
+                .send_sender(
+                    1,
+                    ClientSenderMessage::SendTopicData {
+                        topic: topic(name),
+                        data: vec![data],
+                    },
+                )
+                .await;
+        }
+
+        let topic_data = |receiver_id, name: &str, data| {
+            receiver_message(
+                receiver_id,
+                ServerReceiverMessage::TopicData {
+                    topic: topic(name),
+                    data: vec![data],
+                },
+            )
+        };
+        assert_eq!(first.take_messages(), vec![topic_data(1, "chat", 1)]);
+        assert_eq!(second.take_messages(), vec![topic_data(2, "telemetry", 2)]);
+    }
+
+    #[tokio::test]
+    async fn topic_data_reaches_receiver_resubscribed_after_reconnect() {
+        use signaling_protocol::Topic;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut stale = TestClient::connect(&server_data, 1).await;
+        let mut reconnected = TestClient::connect(&server_data, 2).await;
+        let topic = Topic("jpeg-frame".to_owned());
+        let send_frame = |data| ClientSenderMessage::SendTopicData {
+            topic: topic.clone(),
+            data: vec![data],
+        };
+
+        sender.open_client_server_channel(1, "a").await;
+        stale.join_channel(1, "a").await;
+        stale
+            .send_receiver(1, ClientReceiverMessage::SubscribeTopic(topic.clone()))
+            .await;
+        sender.send_sender(1, send_frame(1)).await;
+        stale.disconnect().await;
+
+        // The rejoined receiver subscribes again, the new session keeps no topics.
+        reconnected.join_channel(1, "a").await;
+        reconnected
+            .send_receiver(1, ClientReceiverMessage::SubscribeTopic(topic.clone()))
+            .await;
+        let _: Vec<_> = reconnected.take_messages();
+        sender.send_sender(1, send_frame(2)).await;
+
+        assert_eq!(
+            reconnected.take_messages(),
+            vec![receiver_message(
+                1,
+                ServerReceiverMessage::TopicData {
+                    topic: topic.clone(),
+                    data: vec![2],
+                },
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn receiver_relay_cap_drops_data_and_notifies_sender_once() {
+        use crate::relay_limits::{RelayTotals, RECEIVER_RELAY_BURST};
+        use signaling_protocol::{ThrottleNotice, ThrottleScope};
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        sender.open_client_server_channel(1, "a").await;
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = sender.take_messages();
+        let _: Vec<_> = receiver.take_messages();
+
+        let len = RECEIVER_RELAY_BURST / 2;
+        for _ in 0..4 {
+            sender
+                .send_sender(
+                    1,
+                    ClientSenderMessage::SendBinaryData(vec![0; len as usize]),
+                )
+                .await;
+        }
+
+        assert_eq!(receiver.take_messages().len(), 2);
+        match sender.take_messages().as_slice() {
+            [ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message:
+                    ServerSenderMessage::Throttled(ThrottleNotice {
+                        scope: ThrottleScope::Receivers,
+                        dropped_bytes,
+                        retry_after,
+                    }),
+            }] => {
+                assert_eq!(*dropped_bytes, len);
+                assert!(!retry_after.is_zero());
+            }
+            messages => panic!("unexpected messages: {:?}", messages),
+        }
+        assert_eq!(
+            server_data.relay_stats().totals(),
+            RelayTotals {
+                relayed_bytes: 2 * len,
+                channel_dropped_bytes: 0,
+                receiver_dropped_bytes: 2 * len,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn channel_relay_cap_drops_data_for_all_receivers() {
+        use crate::relay_limits::{CHANNEL_RELAY_BURST, RECEIVER_RELAY_BURST};
+        use signaling_protocol::{ThrottleNotice, ThrottleScope};
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        sender.open_client_server_channel(1, "a").await;
+        let receiver_count = CHANNEL_RELAY_BURST / RECEIVER_RELAY_BURST + 1;
+        let mut receivers = Vec::new();
+        for id in 1..=receiver_count {
+            let mut receiver = TestClient::connect(&server_data, id as u32).await;
+            receiver.join_channel(id, "a").await;
+            let _: Vec<_> = receiver.take_messages();
+            receivers.push(receiver);
+        }
+        let _: Vec<_> = sender.take_messages();
+
+        let data = vec![0; RECEIVER_RELAY_BURST as usize];
+        sender
+            .send_sender(1, ClientSenderMessage::SendBinaryData(data))
+            .await;
+
+        for receiver in &receivers {
+            assert_eq!(receiver.take_messages(), vec![]);
+        }
+        match sender.take_messages().as_slice() {
+            [ServerMessage::SenderMessage {
+                message:
+                    ServerSenderMessage::Throttled(ThrottleNotice {
+                        scope: ThrottleScope::Channel,
+                        dropped_bytes,
+                        ..
+                    }),
+                ..
+            }] => assert_eq!(*dropped_bytes, receiver_count * RECEIVER_RELAY_BURST),
+            messages => panic!("unexpected messages: {:?}", messages),
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback_channel_echoes_data_to_sender_and_relays_it() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id("a"),
+                    network_mode: NetworkMode::Loopback,
+                    claim_token: None,
+                    secret: None,
+                    resume_token: None,
+                },
+            )
+            .await;
+        let _: Vec<_> = sender.take_messages();
+        sender
+            .send_sender(1, ClientSenderMessage::SendBinaryData(vec![1]))
+            .await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ServerSenderMessage::BinaryDataEcho(vec![1]),
+            }]
+        );
+
+        receiver.join_channel(1, "a").await;
+        let _: Vec<_> = receiver.take_messages();
+        let _: Vec<_> = sender.take_messages();
+        sender
+            .send_sender(1, ClientSenderMessage::SendBinaryData(vec![2]))
+            .await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![ServerMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ServerSenderMessage::BinaryDataEcho(vec![2]),
+            }]
+        );
+        assert_eq!(
+            receiver.take_messages(),
+            vec![ServerMessage::ReceiverMessage {
+                receiver_id: SessionReceiverId(1),
+                message: ServerReceiverMessage::BinaryData(vec![2]),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn channel_request_is_relayed_to_registered_peer_and_answered() {
+        use signaling_protocol::{
+            ChannelRequestDeclineReason, ChannelRequestErrorMessage, ChannelRequestId, PeerId,
+        };
+
+        let server_data = server_data();
+        let mut callee = TestClient::connect(&server_data, 0).await;
+        let mut caller = TestClient::connect(&server_data, 1).await;
+        callee
+            .send(ClientMessage::RegisterPeer(PeerId("callee".to_owned())))
+            .await;
+        caller
+            .send(ClientMessage::RegisterPeer(PeerId("caller".to_owned())))
+            .await;
+
+        caller
+            .send(ClientMessage::RequestChannel {
+                request_id: ChannelRequestId(7),
+                peer_id: PeerId("nobody".to_owned()),
+            })
+            .await;
+        assert_eq!(
+            caller.take_messages(),
+            vec![ServerMessage::ChannelRequestFailed {
+                request_id: ChannelRequestId(7),
+                reason: ChannelRequestErrorMessage::PeerIsNotFound,
+            }]
+        );
+
+        caller
+            .send(ClientMessage::RequestChannel {
+                request_id: ChannelRequestId(8),
+                peer_id: PeerId("callee".to_owned()),
+            })
+            .await;
+        let request_id = match callee.take_messages().as_slice() {
+            [ServerMessage::ChannelRequested { request_id, from }] => {
+                assert_eq!(from, &Some(PeerId("caller".to_owned())));
+                *request_id
+            }
+            messages => panic!("unexpected messages: {:?}", messages),
+        };
+
+        callee
+            .send(ClientMessage::AcceptChannelRequest {
+                request_id,
+                channel_id: channel_id("call"),
+            })
+            .await;
+        assert_eq!(
+            callee.take_messages(),
+            vec![ServerMessage::ChannelRequestFailed {
+                request_id,
+                reason: ChannelRequestErrorMessage::ChannelIsNotOpened,
+            }]
+        );
+
+        callee.open_channel(1, "call").await;
+        let _: Vec<_> = callee.take_messages();
+        callee
+            .send(ClientMessage::AcceptChannelRequest {
+                request_id,
+                channel_id: channel_id("call"),
+            })
+            .await;
+        assert_eq!(
+            caller.take_messages(),
+            vec![ServerMessage::ChannelRequestAccepted {
+                request_id: ChannelRequestId(8),
+                channel_id: channel_id("call"),
+            }]
+        );
+
+        callee
+            .send(ClientMessage::DeclineChannelRequest {
+                request_id,
+                reason: ChannelRequestDeclineReason::Declined,
+            })
+            .await;
+        assert_eq!(
+            callee.take_messages(),
+            vec![ServerMessage::ChannelRequestFailed {
+                request_id,
+                reason: ChannelRequestErrorMessage::RequestIsNotFound,
+            }]
+        );
+
+        caller
+            .send(ClientMessage::RequestChannel {
+                request_id: ChannelRequestId(9),
+                peer_id: PeerId("callee".to_owned()),
+            })
+            .await;
+        let request_id = match callee.take_messages().as_slice() {
+            [ServerMessage::ChannelRequested { request_id, .. }] => *request_id,
+            messages => panic!("unexpected messages: {:?}", messages),
+        };
+        callee
+            .send(ClientMessage::DeclineChannelRequest {
+                request_id,
+                reason: ChannelRequestDeclineReason::DoNotDisturb,
+            })
+            .await;
+        assert_eq!(
+            caller.take_messages(),
+            vec![ServerMessage::ChannelRequestDeclined {
+                request_id: ChannelRequestId(9),
+                reason: ChannelRequestDeclineReason::DoNotDisturb,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn sender_disconnect_removes_its_channels() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+
+        sender.open_channel(1, "a").await;
+        sender.disconnect().await;
+        server_data.update_open_channels().await;
+
+        assert!(server_data.channels().read().await.is_empty());
+        assert!(server_data
+            .senders()
+            .read()
+            .await
+            .get(&SocketId(0))
+            .is_none());
+        assert_eq!(
+            receiver.take_open_channels().last(),
+            Some(&Vec::<String>::new())
+        );
+
+        receiver.join_channel(1, "a").await;
+        assert_eq!(
+            receiver.take_messages(),
+            vec![receiver_error(
+                1,
+                ServerReceiverErrorMessage::ChannelIsNotExist(channel_id("a"))
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn receiver_disconnect_frees_channel_slot() {
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut first = TestClient::connect(&server_data, 1).await;
+        let mut second = TestClient::connect(&server_data, 2).await;
+
+        sender.open_channel(1, "a").await;
+        first.join_channel(1, "a").await;
+        first.disconnect().await;
+        second.join_channel(2, "a").await;
+
+        assert_eq!(second.take_messages(), vec![join_channel_success(2)]);
+    }
+
+    #[tokio::test]
+    async fn message_of_unknown_session_is_nacked() {
+        use signaling_protocol::NackReason;
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+
+        let result = sender
+            .socket
+            .handle_message(ClientMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ClientSenderMessage::SendOffer(SessionDescription("offer".to_owned())),
+            })
+            .await;
+
+        assert_eq!(result, Err(NackReason::SessionIsNotExist));
+        assert_eq!(
+            sender.take_messages(),
+            vec![sender_error(
+                1,
+                ServerSenderErrorMessage::SessionSenderIdIsNotExist
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn session_summaries_are_aggregated() {
+        use core::time::Duration;
+        use signaling_protocol::{SessionSummary, StateDuration};
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        let summary = |state: &str, secs| SessionSummary {
+            duration: Duration::from_secs(secs),
+            state_durations: vec![StateDuration {
+                state: state.to_owned(),
+                duration: Duration::from_secs(secs),
+            }],
+            bytes_sent: 100,
+            bytes_received: 10,
+            frames_sent: 5,
+            frames_received: 1,
+        };
+
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::ReportSessionSummary(summary("Connected", 3)),
+            )
+            .await;
+        receiver
+            .send_receiver(
+                1,
+                ClientReceiverMessage::ReportSessionSummary(summary("Connected", 2)),
+            )
+            .await;
+        receiver
+            .send_receiver(
+                2,
+                ClientReceiverMessage::ReportSessionSummary(summary("Failed", 1)),
+            )
+            .await;
+
+        let totals = server_data.session_stats().totals().await;
+        assert_eq!(totals.sender_sessions, 1);
+        assert_eq!(totals.receiver_sessions, 2);
+        assert_eq!(totals.duration, Duration::from_secs(6));
+        assert_eq!(
+            totals.state_durations.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("Connected".to_owned(), Duration::from_secs(5)),
+                ("Failed".to_owned(), Duration::from_secs(1)),
+            ]
+        );
+        assert_eq!(totals.bytes_sent, 300);
+        assert_eq!(totals.frames_received, 3);
+    }
+
+    #[tokio::test]
+    async fn call_milestones_are_aggregated() {
+        use core::time::Duration;
+        use signaling_protocol::{CallMilestone, MilestoneReport};
+
+        let server_data = server_data();
+        let mut client = TestClient::connect(&server_data, 0).await;
+        let report = |milestone, millis| MilestoneReport {
+            milestone,
+            elapsed: Duration::from_millis(millis),
+        };
+
+        client
+            .send(ClientMessage::ReportMilestones(vec![
+                report(CallMilestone::OfferSent, 100),
+                report(CallMilestone::IceConnected, 900),
+            ]))
+            .await;
+        client
+            .send(ClientMessage::ReportMilestones(vec![
+                report(CallMilestone::OfferSent, 300),
+                report(CallMilestone::SetupFailed, 5000),
+            ]))
+            .await;
+
+        let totals = server_data.session_stats().milestone_totals().await;
+        let offer_sent = totals[&CallMilestone::OfferSent];
+        assert_eq!(offer_sent.count, 2);
+        assert_eq!(offer_sent.average_elapsed(), Duration::from_millis(200));
+        assert_eq!(offer_sent.max_elapsed, Duration::from_millis(300));
+        assert_eq!(totals[&CallMilestone::IceConnected].count, 1);
+        assert_eq!(totals[&CallMilestone::SetupFailed].count, 1);
+        assert!(client.take_messages().is_empty());
+    }
+
+    #[tokio::test]
+    async fn transcripts_store_hashed_peer_ips_only() {
+        use crate::TranscriptRecorder;
+        use std::fs::{read_dir, read_to_string, remove_dir_all};
+
+        let dir = std::env::temp_dir().join(format!("transcripts-{}", std::process::id()));
+        let server_data = Arc::new(ServerData::new(
+            Some(TranscriptRecorder::new(dir.clone())),
+            None,
+        ));
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut other_sender = TestClient::connect(&server_data, 1).await;
+        sender.open_channel(1, "a").await;
+        other_sender.open_channel(1, "a").await;
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::SendOffer(SessionDescription(
+                    "v=0\r\nc=IN IP4 203.0.113.7\r\n".to_owned(),
+                )),
+            )
+            .await;
+        sender
+            .send_sender(
+                1,
+                ClientSenderMessage::IceCandidate(ice_candidate(
+                    "candidate:1 1 udp 2122260223 2001:db8::7 54321 typ host",
+                )),
+            )
+            .await;
+
+        // The transcript of the channel that failed to open is removed.
+        let paths: Vec<_> = read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(paths.len(), 1);
+        let transcript = read_to_string(&paths[0]).unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        assert_eq!(transcript.lines().count(), 3);
+        assert!(transcript.contains("c=IN IP4 "));
+        assert!(transcript.contains("candidate:1 1 udp 2122260223 "));
+        for ip in ["127.0.0.1", "203.0.113.7", "2001:db8::7"] {
+            assert!(!transcript.contains(ip), "{} is recorded", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn transcripts_store_no_channel_secrets() {
+        use crate::TranscriptRecorder;
+        use std::fs::{read_dir, read_to_string, remove_dir_all};
+
+        let dir = std::env::temp_dir().join(format!("secret-transcripts-{}", std::process::id()));
+        let server_data = Arc::new(ServerData::new(
+            Some(TranscriptRecorder::new(dir.clone())),
+            None,
+        ));
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        let mut receiver = TestClient::connect(&server_data, 1).await;
+        sender.open_protected_channel(1, "a", "hunter2").await;
+        receiver.join_protected_channel(1, "a", Some("guess")).await;
+        receiver
+            .join_protected_channel(2, "a", Some("hunter2"))
+            .await;
+
+        let paths: Vec<_> = read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(paths.len(), 1);
+        let transcript = read_to_string(&paths[0]).unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        for secret in ["hunter2", "guess"] {
+            assert!(!transcript.contains(secret), "{} is recorded", secret);
+        }
+    }
+
+    #[tokio::test]
+    async fn recordings_are_stored_within_quotas() {
+        use crate::RecordingStore;
+        use signaling_protocol::RecordingErrorMessage;
+
+        let recording_message = |message| ServerMessage::SenderMessage {
+            sender_id: SessionSenderId(0),
+            message,
+        };
+        let recording_failed = |err| recording_message(ServerSenderMessage::RecordingFailed(err));
+        let start = || ClientSenderMessage::StartRecording {
+            mime_type: "video/webm;codecs=vp8".to_owned(),
+        };
+
+        let server_data = server_data();
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        sender.open_channel(0, "recorded").await;
+        let _: Vec<_> = sender.take_messages();
+        sender.send_sender(0, start()).await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![recording_failed(RecordingErrorMessage::RecordingIsDisabled)]
+        );
+
+        let dir = std::env::temp_dir().join(format!("recordings-{}", std::process::id()));
+        let recording_store = RecordingStore::new(dir.clone(), 8, 12);
+        let server_data = Arc::new(ServerData::new(None, Some(recording_store)));
+        let mut sender = TestClient::connect(&server_data, 0).await;
+        sender.open_channel(0, "recorded").await;
+        let _: Vec<_> = sender.take_messages();
+
+        sender
+            .send_sender(0, ClientSenderMessage::SendRecordingChunk(vec![0; 4]))
+            .await;
+        sender.send_sender(0, start()).await;
+        sender.send_sender(0, start()).await;
+        sender
+            .send_sender(0, ClientSenderMessage::SendRecordingChunk(vec![1; 5]))
+            .await;
+        sender
+            .send_sender(0, ClientSenderMessage::StopRecording)
+            .await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![
+                recording_failed(RecordingErrorMessage::RecordingIsNotStarted),
+                recording_message(ServerSenderMessage::RecordingStarted),
+                recording_failed(RecordingErrorMessage::RecordingIsAlreadyStarted),
+                recording_message(ServerSenderMessage::RecordingStopped { size: 5 }),
+            ]
+        );
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "webm");
+        assert_eq!(std::fs::read(&files[0]).unwrap(), vec![1; 5]);
+
+        // Only 7 bytes of the total quota are left for the second recording.
+        sender.send_sender(0, start()).await;
+        sender
+            .send_sender(0, ClientSenderMessage::SendRecordingChunk(vec![2; 8]))
+            .await;
+        sender
+            .send_sender(0, ClientSenderMessage::StopRecording)
+            .await;
+        assert_eq!(
+            sender.take_messages(),
+            vec![
+                recording_message(ServerSenderMessage::RecordingStarted),
+                recording_failed(RecordingErrorMessage::RecordingQuotaIsExceeded),
+                recording_failed(RecordingErrorMessage::RecordingIsNotStarted),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
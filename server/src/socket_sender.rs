@@ -1,41 +1,116 @@
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use futures::future::BoxFuture;
 use futures::stream::SplitSink;
 use signaling_protocol::{
-    ServerMessage, ServerReceiverErrorMessage, ServerReceiverMessage, ServerSenderErrorMessage,
-    ServerSenderMessage, SessionReceiverId, SessionSenderId,
+    FrameError, Language, ServerMessage, ServerReceiverErrorMessage, ServerReceiverMessage,
+    ServerSenderErrorMessage, ServerSenderMessage, SessionReceiverId, SessionSenderId, WireCodec,
 };
+use thiserror::Error;
 use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::tungstenite::Error as WebSocketError;
 use tokio_tungstenite::WebSocketStream;
 
+/// Outgoing half of the client connection,
+/// abstracted so that socket handlers can be run without a real WebSocket.
+pub trait MessageSink: Debug + Send + Sync {
+    fn send_message(&self, message: Message) -> BoxFuture<'_, Result<(), WebSocketError>>;
+}
+
 #[derive(Debug)]
-pub struct SocketSender(Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>);
+pub struct WebSocketSink(Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>);
 
-impl SocketSender {
+impl WebSocketSink {
     pub fn new(sender: SplitSink<WebSocketStream<TcpStream>, Message>) -> Self {
         Self(Mutex::new(sender))
     }
+}
 
-    pub async fn send(&self, message: ServerMessage) {
-        use bincode::serialize;
+impl MessageSink for WebSocketSink {
+    fn send_message(&self, message: Message) -> BoxFuture<'_, Result<(), WebSocketError>> {
         use futures::SinkExt;
-        use log::error;
 
-        let message: Result<Vec<u8>, _> = serialize(&message);
-        let message = match message {
-            Ok(message) => message,
-            Err(err) => {
-                error!("send message serialization error: {}", err);
-                return;
-            }
-        };
+        Box::pin(async move { self.0.lock().await.send(message).await })
+    }
+}
+
+#[derive(Debug)]
+pub struct SocketSender {
+    sink: Box<dyn MessageSink>,
+    codec: WireCodec,
+    log_language: Language,
+    is_channel_list_paused: AtomicBool,
+}
+
+impl SocketSender {
+    pub fn new<S: 'static + MessageSink>(sink: S, codec: WireCodec) -> Self {
+        Self {
+            sink: Box::new(sink),
+            codec,
+            log_language: Language::default(),
+            is_channel_list_paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Language of the errors reported to the client as written to the log.
+    pub fn with_log_language(self, log_language: Language) -> Self {
+        Self {
+            log_language,
+            ..self
+        }
+    }
+
+    /// Codec negotiated with the client during the WebSocket handshake.
+    pub fn codec(&self) -> WireCodec {
+        self.codec
+    }
+
+    /// Whether the client paused the open channel list updates,
+    /// see `ClientMessage::PauseChannelList`.
+    pub fn is_channel_list_paused(&self) -> bool {
+        self.is_channel_list_paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_channel_list_paused(&self, is_paused: bool) {
+        self.is_channel_list_paused
+            .store(is_paused, Ordering::Relaxed);
+    }
+
+    pub async fn send(&self, message: ServerMessage) {
+        use log::error;
 
-        match self.0.lock().await.send(Message::Binary(message)).await {
+        match self.try_send(message).await {
             Ok(()) => {}
-            Err(err) => {
-                error!("send message error: {}", err);
-                return;
-            }
+            Err(err) => error!("{}", err),
+        }
+    }
+
+    pub async fn try_send(&self, message: ServerMessage) -> Result<(), SocketSendError> {
+        use log::info;
+
+        if let Some(description) = message.describe_error(self.log_language) {
+            info!("error reported to client: {}", description);
+        }
+        let message = self.codec.encode_frame(&message)?;
+        self.sink.send_message(Message::Binary(message)).await?;
+        Ok(())
+    }
+
+    /// Closes the WebSocket with a close frame, the client is expected to close its side.
+    pub async fn close(&self, reason: &str) {
+        use log::error;
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+        let frame = CloseFrame {
+            code: CloseCode::Away,
+            reason: reason.to_owned().into(),
+        };
+        if let Err(err) = self.sink.send_message(Message::Close(Some(frame))).await {
+            error!("close message error: {}", err);
         }
     }
 
@@ -48,6 +123,27 @@ impl SocketSender {
             .await
     }
 
+    pub async fn try_send_sender_message(
+        &self,
+        sender_id: SessionSenderId,
+        message: ServerSenderMessage,
+    ) -> Result<(), SocketSendError> {
+        self.try_send(ServerMessage::SenderMessage { sender_id, message })
+            .await
+    }
+
+    pub async fn try_send_receiver_message(
+        &self,
+        receiver_id: SessionReceiverId,
+        message: ServerReceiverMessage,
+    ) -> Result<(), SocketSendError> {
+        self.try_send(ServerMessage::ReceiverMessage {
+            receiver_id,
+            message,
+        })
+        .await
+    }
+
     pub async fn send_receiver_message(
         &self,
         receiver_id: SessionReceiverId,
@@ -78,3 +174,11 @@ impl SocketSender {
             .await
     }
 }
+
+#[derive(Error, Debug)]
+pub enum SocketSendError {
+    #[error("send message serialization error: {0}")]
+    SerializationFailed(#[from] FrameError),
+    #[error("send message error: {0}")]
+    SendFailed(#[from] WebSocketError),
+}
@@ -0,0 +1,125 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use signaling_protocol::CloseReason;
+use tokio_tungstenite::tungstenite::Error as WebSocketError;
+
+/// How a client session ended, logged and counted by `SocketStats`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SessionEnd {
+    /// The client closed the WebSocket with a close frame.
+    Closed,
+    /// The connection is gone without a close frame, e.g. reset by the client.
+    Disconnected,
+    TimedOut,
+    ServerShutdown,
+    /// The connection failed for other reasons than the client disconnect.
+    TransportError,
+    /// The client sent an invalid WebSocket frame or a non-binary message,
+    /// or kept joining channels with wrong credentials.
+    ProtocolViolation,
+    /// A message handler panicked, the session is still cleared.
+    HandlerPanicked,
+}
+
+impl SessionEnd {
+    pub fn from_websocket_error(err: &WebSocketError) -> Self {
+        use std::io::ErrorKind;
+
+        match err {
+            WebSocketError::ConnectionClosed | WebSocketError::AlreadyClosed => Self::Disconnected,
+            WebSocketError::Io(err) => match err.kind() {
+                ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof => Self::Disconnected,
+                _ => Self::TransportError,
+            },
+            WebSocketError::Protocol(_) | WebSocketError::Capacity(_) | WebSocketError::Utf8 => {
+                Self::ProtocolViolation
+            }
+            _ => Self::TransportError,
+        }
+    }
+
+    /// Reason the peers of the session are notified with.
+    pub fn close_reason(self) -> CloseReason {
+        match self {
+            Self::TimedOut => CloseReason::Timeout,
+            Self::ServerShutdown => CloseReason::ServerShutdown,
+            Self::Closed
+            | Self::Disconnected
+            | Self::TransportError
+            | Self::ProtocolViolation
+            | Self::HandlerPanicked => CloseReason::RemoteClosed,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Disconnected => "disconnected",
+            Self::TimedOut => "timed_out",
+            Self::ServerShutdown => "server_shutdown",
+            Self::TransportError => "transport_error",
+            Self::ProtocolViolation => "protocol_violation",
+            Self::HandlerPanicked => "handler_panicked",
+        }
+    }
+}
+
+/// Counts the failed handshakes and the ended sessions by how they ended.
+#[derive(Debug, Default)]
+pub struct SocketStats {
+    handshake_failures: AtomicU64,
+    closed: AtomicU64,
+    disconnected: AtomicU64,
+    timed_out: AtomicU64,
+    server_shutdown: AtomicU64,
+    transport_errors: AtomicU64,
+    protocol_violations: AtomicU64,
+    handler_panics: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SocketTotals {
+    pub handshake_failures: u64,
+    pub closed: u64,
+    pub disconnected: u64,
+    pub timed_out: u64,
+    pub server_shutdown: u64,
+    pub transport_errors: u64,
+    pub protocol_violations: u64,
+    pub handler_panics: u64,
+}
+
+impl SocketStats {
+    pub fn record_handshake_failure(&self) {
+        let _: u64 = self.handshake_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_end(&self, end: SessionEnd) {
+        let counter = match end {
+            SessionEnd::Closed => &self.closed,
+            SessionEnd::Disconnected => &self.disconnected,
+            SessionEnd::TimedOut => &self.timed_out,
+            SessionEnd::ServerShutdown => &self.server_shutdown,
+            SessionEnd::TransportError => &self.transport_errors,
+            SessionEnd::ProtocolViolation => &self.protocol_violations,
+            SessionEnd::HandlerPanicked => &self.handler_panics,
+        };
+        let _: u64 = counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn totals(&self) -> SocketTotals {
+        SocketTotals {
+            handshake_failures: self.handshake_failures.load(Ordering::Relaxed),
+            closed: self.closed.load(Ordering::Relaxed),
+            disconnected: self.disconnected.load(Ordering::Relaxed),
+            timed_out: self.timed_out.load(Ordering::Relaxed),
+            server_shutdown: self.server_shutdown.load(Ordering::Relaxed),
+            transport_errors: self.transport_errors.load(Ordering::Relaxed),
+            protocol_violations: self.protocol_violations.load(Ordering::Relaxed),
+            handler_panics: self.handler_panics.load(Ordering::Relaxed),
+        }
+    }
+}
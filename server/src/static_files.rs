@@ -0,0 +1,176 @@
+use core::time::Duration;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use tokio::net::TcpStream;
+
+/// Maximum size of the HTTP request head.
+const MAX_REQUEST_HEAD_LEN: usize = 8192;
+
+/// Interval between the request head checks if it is not received at once.
+const REQUEST_HEAD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Maximum time to receive the HTTP request head.
+const REQUEST_HEAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the client files over plain HTTP on the signaling server port.
+#[derive(Debug)]
+pub struct StaticFiles {
+    root: PathBuf,
+}
+
+impl StaticFiles {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Peeks the request head without consuming it,
+    /// so that WebSocket upgrade requests are still accepted by the signaling socket.
+    pub async fn is_websocket_request(stream: &TcpStream) -> io::Result<bool> {
+        let head = peek_request_head(stream).await?;
+        Ok(head.lines().skip(1).any(|line| {
+            let mut parts = line.splitn(2, ':');
+            let name = parts.next().unwrap_or_default().trim();
+            let value = parts.next().unwrap_or_default().trim();
+            name.eq_ignore_ascii_case("upgrade") && value.eq_ignore_ascii_case("websocket")
+        }))
+    }
+
+    pub async fn serve(&self, mut stream: TcpStream) -> io::Result<()> {
+        use log::debug;
+        use tokio::fs;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let head = peek_request_head(&stream).await?;
+        let mut head_buf = vec![0; head.len()];
+        let _: usize = stream.read_exact(&mut head_buf).await?;
+
+        let (method, path) = match parse_request_line(&head) {
+            Some(request_line) => request_line,
+            None => return write_status(&mut stream, "400 Bad Request").await,
+        };
+        if method != "GET" && method != "HEAD" {
+            return write_status(&mut stream, "405 Method Not Allowed").await;
+        }
+        let file_path = match resolve_path(&self.root, path) {
+            Some(file_path) => file_path,
+            None => return write_status(&mut stream, "404 Not Found").await,
+        };
+        let body = match fs::read(&file_path).await {
+            Ok(body) => body,
+            Err(err) => {
+                debug!("static file {} error: {}", file_path.display(), err);
+                return write_status(&mut stream, "404 Not Found").await;
+            }
+        };
+
+        let response_head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            content_type(&file_path),
+            body.len()
+        );
+        stream.write_all(response_head.as_bytes()).await?;
+        if method == "GET" {
+            stream.write_all(&body).await?;
+        }
+        stream.shutdown().await
+    }
+}
+
+/// Waits for the whole request head, which usually comes in a single packet.
+async fn peek_request_head(stream: &TcpStream) -> io::Result<String> {
+    use tokio::time::{sleep, timeout_at, Instant};
+
+    let deadline = Instant::now() + REQUEST_HEAD_TIMEOUT;
+    let mut buf = vec![0; MAX_REQUEST_HEAD_LEN];
+    loop {
+        // The peek waits for the first data, a client sending nothing is dropped at the deadline.
+        let len = timeout_at(deadline, stream.peek(&mut buf))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no HTTP request head"))??;
+        let head = &buf[..len];
+        if let Some(end) = head.windows(4).position(|window| window == b"\r\n\r\n") {
+            return Ok(String::from_utf8_lossy(&head[..end + 4]).into_owned());
+        }
+        if len == 0 || len == buf.len() || Instant::now() >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete HTTP request head",
+            ));
+        }
+        sleep(REQUEST_HEAD_POLL_INTERVAL).await;
+    }
+}
+
+fn parse_request_line(head: &str) -> Option<(&str, &str)> {
+    let mut parts = head.lines().next()?.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let path = target.split(['?', '#']).next()?;
+    Some((method, path))
+}
+
+/// Maps the request path to a file in the root directory, paths leaving the root are rejected.
+fn resolve_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path.trim_start_matches('/'));
+    if path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    if path.as_os_str().is_empty() {
+        Some(root.join("index.html"))
+    } else {
+        Some(root.join(path))
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "application/javascript",
+        Some("wasm") => "application/wasm",
+        Some("css") => "text/css",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+async fn write_status(stream: &mut TcpStream, status: &str) -> io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_request_line, resolve_path};
+
+    #[test]
+    fn request_paths_are_resolved_inside_the_root() {
+        let root = Path::new("/srv/static");
+        assert_eq!(resolve_path(root, "/"), Some(root.join("index.html")));
+        assert_eq!(
+            resolve_path(root, "/client-1234_bg.wasm"),
+            Some(root.join("client-1234_bg.wasm"))
+        );
+        assert_eq!(resolve_path(root, "/../secret"), None);
+        assert_eq!(resolve_path(root, "/assets/../../secret"), None);
+        assert_eq!(
+            parse_request_line("GET /index.html?v=1 HTTP/1.1\r\n\r\n"),
+            Some(("GET", "/index.html"))
+        );
+    }
+}
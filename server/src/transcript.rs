@@ -0,0 +1,296 @@
+use std::collections::hash_map::RandomState;
+use std::io;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use signaling_protocol::{ChannelId, ClientReceiverMessage, ClientSenderMessage};
+use tokio::fs::File;
+use tokio::sync::Mutex;
+
+/// Delimiters of the SDP and ICE candidate tokens the IP addresses are looked for in.
+const TOKEN_DELIMITERS: [char; 3] = [' ', '\r', '\n'];
+
+/// Message fields holding channel secrets and tokens, their values are not stored.
+const REDACTED_FIELDS: [&str; 4] = ["secret", "credential", "claim_token", "resume_token"];
+
+/// Creates per channel signaling transcripts in the directory as JSONL files.
+#[derive(Debug)]
+pub struct TranscriptRecorder {
+    dir: PathBuf,
+    addr_hasher: RandomState,
+}
+
+/// Signaling transcript of a single channel, one JSON object per line.
+#[derive(Debug)]
+pub struct ChannelTranscript {
+    file: Mutex<File>,
+    path: PathBuf,
+    addr_hasher: RandomState,
+}
+
+#[derive(Debug)]
+pub enum TranscriptEvent<'a> {
+    ChannelOpened,
+    ReceiverJoined,
+    SenderMessage(&'a ClientSenderMessage),
+    ReceiverMessage(&'a ClientReceiverMessage),
+    NegotiationTimedOut,
+}
+
+impl TranscriptRecorder {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            addr_hasher: RandomState::new(),
+        }
+    }
+
+    pub async fn create(&self, channel_id: &ChannelId) -> io::Result<ChannelTranscript> {
+        let (path, file) = create_channel_file(&self.dir, channel_id, "jsonl").await?;
+        Ok(ChannelTranscript {
+            file: Mutex::new(file),
+            path,
+            addr_hasher: self.addr_hasher.clone(),
+        })
+    }
+}
+
+impl ChannelTranscript {
+    pub async fn record(&self, addr: Option<SocketAddr>, event: TranscriptEvent<'_>) {
+        use log::error;
+        use serde_json::{json, Value};
+        use tokio::io::AsyncWriteExt;
+
+        let (kind, mut message) = match event {
+            TranscriptEvent::ChannelOpened => ("channel_opened", Value::Null),
+            TranscriptEvent::ReceiverJoined => ("receiver_joined", Value::Null),
+            TranscriptEvent::SenderMessage(message) => ("sender_message", json!(message)),
+            TranscriptEvent::ReceiverMessage(message) => ("receiver_message", json!(message)),
+            TranscriptEvent::NegotiationTimedOut => ("negotiation_timed_out", Value::Null),
+        };
+        redact_secrets(&mut message);
+        self.hash_message_ips(&mut message);
+        let peer = addr.map(|addr| self.hash_ip(addr.ip()));
+        let mut line = json!({
+            "timestamp_ms": timestamp_ms(),
+            "peer": peer,
+            "event": kind,
+            "message": message,
+        })
+        .to_string();
+        line.push('\n');
+
+        // Every line is flushed, so the transcript is complete up to the last event
+        // even if the server stops without closing the channel.
+        let mut file = self.file.lock().await;
+        let result = match file.write_all(line.as_bytes()).await {
+            Ok(()) => file.flush().await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            error!("transcript write error: {}", err);
+        }
+    }
+
+    /// Removes the transcript file, e.g. if the channel failed to open.
+    pub async fn discard(self) {
+        use log::error;
+        use tokio::fs::remove_file;
+
+        drop(self.file);
+        if let Err(err) = remove_file(&self.path).await {
+            error!("transcript {} remove error: {}", self.path.display(), err);
+        }
+    }
+
+    /// Peer addresses are only stored hashed with the per server run random key,
+    /// so peers can be distinguished within the transcripts but not identified.
+    fn hash_ip(&self, ip: IpAddr) -> String {
+        use core::hash::BuildHasher;
+
+        format!("{:016x}", self.addr_hasher.hash_one(ip))
+    }
+
+    /// Hashes the IP addresses in the message strings,
+    /// e.g. the `c=` lines and the candidates of the SDP and the ICE candidates.
+    fn hash_message_ips(&self, value: &mut serde_json::Value) {
+        use serde_json::Value;
+
+        match value {
+            Value::String(text) => {
+                let mut hashed = String::with_capacity(text.len());
+                for token in text.split_inclusive(TOKEN_DELIMITERS) {
+                    let word = token.trim_end_matches(TOKEN_DELIMITERS);
+                    match word.parse() {
+                        Ok(ip) => {
+                            hashed.push_str(&self.hash_ip(ip));
+                            hashed.push_str(&token[word.len()..]);
+                        }
+                        Err(_) => hashed.push_str(token),
+                    }
+                }
+                *text = hashed;
+            }
+            Value::Array(values) => values
+                .iter_mut()
+                .for_each(|value| self.hash_message_ips(value)),
+            Value::Object(values) => values
+                .values_mut()
+                .for_each(|value| self.hash_message_ips(value)),
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+}
+
+/// Replaces the set secret and token fields of the message with a placeholder.
+fn redact_secrets(value: &mut serde_json::Value) {
+    use serde_json::Value;
+
+    match value {
+        Value::Array(values) => values.iter_mut().for_each(redact_secrets),
+        Value::Object(values) => {
+            for (name, value) in values.iter_mut() {
+                if REDACTED_FIELDS.contains(&name.as_str()) {
+                    if !value.is_null() {
+                        *value = Value::from("<redacted>");
+                    }
+                } else {
+                    redact_secrets(value);
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+/// Creates a new file for the channel in the directory,
+/// named with the creation time and numbered if the channel already has a file of that time.
+pub async fn create_channel_file(
+    dir: &Path,
+    channel_id: &ChannelId,
+    extension: &str,
+) -> io::Result<(PathBuf, File)> {
+    use tokio::fs::{create_dir_all, OpenOptions};
+
+    create_dir_all(dir).await?;
+    let stem = format!(
+        "{}-{}",
+        timestamp_ms(),
+        channel_id
+            .0
+            .chars()
+            .map(|ch| match ch {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => ch,
+                _ => '_',
+            })
+            .collect::<String>()
+    );
+    let mut index = 0_u32;
+    loop {
+        let path = match index {
+            0 => dir.join(format!("{}.{}", stem, extension)),
+            _ => dir.join(format!("{}-{}.{}", stem, index, extension)),
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => index += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Returns whether the message is a part of the connection negotiation.
+pub fn is_signaling_sender_message(message: &ClientSenderMessage) -> bool {
+    match message {
+        ClientSenderMessage::OpenChannel { .. }
+        | ClientSenderMessage::CloseChannel(_)
+        | ClientSenderMessage::SendOffer(_)
+        | ClientSenderMessage::SendIceRestartOffer(_)
+        | ClientSenderMessage::IceCandidate(_)
+        | ClientSenderMessage::AllIceCandidatesSent
+        | ClientSenderMessage::RequestIceCandidates
+        | ClientSenderMessage::PublishPublicKey(_)
+        | ClientSenderMessage::PublishCapabilities(_)
+        | ClientSenderMessage::RequestPeerNetwork
+        | ClientSenderMessage::SendAnswer(_) => true,
+        ClientSenderMessage::SendBinaryData(_)
+        | ClientSenderMessage::SendTopicData { .. }
+        | ClientSenderMessage::SendSnapshot(_)
+        | ClientSenderMessage::PublishPreview(_)
+        | ClientSenderMessage::ScheduleStart(_)
+        | ClientSenderMessage::RenameChannel(_)
+        | ClientSenderMessage::ReportSessionSummary(_)
+        | ClientSenderMessage::StartRecording { .. }
+        | ClientSenderMessage::SendRecordingChunk(_)
+        | ClientSenderMessage::StopRecording => false,
+    }
+}
+
+/// Returns whether the message is a part of the connection negotiation.
+pub fn is_signaling_receiver_message(message: &ClientReceiverMessage) -> bool {
+    match message {
+        ClientReceiverMessage::JoinChannel { .. }
+        | ClientReceiverMessage::ExitChannel(_)
+        | ClientReceiverMessage::SendAnswer(_)
+        | ClientReceiverMessage::IceCandidate(_)
+        | ClientReceiverMessage::AllIceCandidatesSent
+        | ClientReceiverMessage::RequestIceCandidates
+        | ClientReceiverMessage::RequestIceRestart
+        | ClientReceiverMessage::PublishPublicKey(_)
+        | ClientReceiverMessage::PublishCapabilities(_)
+        | ClientReceiverMessage::SendOffer(_) => true,
+        ClientReceiverMessage::RequestQuality(_)
+        | ClientReceiverMessage::SubscribeTopic(_)
+        | ClientReceiverMessage::UnsubscribeTopic(_)
+        | ClientReceiverMessage::ReportSessionSummary(_) => false,
+    }
+}
+
+fn timestamp_ms() -> u128 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use signaling_protocol::{
+        ChannelId, ChannelSecret, ClientSenderMessage, NetworkMode, ResumeToken,
+    };
+
+    use super::redact_secrets;
+
+    #[test]
+    fn channel_secrets_are_redacted() {
+        let mut message = json!(ClientSenderMessage::OpenChannel {
+            channel_id: ChannelId("a".to_owned()),
+            network_mode: NetworkMode::PeerToPeer,
+            claim_token: None,
+            secret: Some(ChannelSecret("hunter2".to_owned())),
+            resume_token: Some(ResumeToken("token".to_owned())),
+        });
+        redact_secrets(&mut message);
+        assert_eq!(
+            message,
+            json!({
+                "OpenChannel": {
+                    "channel_id": "a",
+                    "network_mode": "PeerToPeer",
+                    "claim_token": null,
+                    "secret": "<redacted>",
+                    "resume_token": "<redacted>",
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use thiserror::Error;
+
+use crate::{
+    AckId, ClientMessage, ClientReceiverMessage, ClientSenderMessage, CloseReason, FrameError,
+    NackReason, ServerMessage, ServerReceiverErrorMessage, ServerReceiverMessage,
+    ServerSenderErrorMessage, ServerSenderMessage, SessionReceiverId, SessionSenderId, WireCodec,
+};
+
+/// Signaling client state machine without any IO, for runtimes other than tokio or wasm,
+/// e.g. embedded devices or game engines.
+///
+/// The embedder feeds the received WebSocket binary frames to `handle_incoming`,
+/// sends the frames returned by `poll_transmit` and handles the events returned by
+/// `poll_event`. The client tracks the session states and the acknowledged messages,
+/// timeouts and reconnects are left to the embedder.
+#[derive(Debug)]
+pub struct SignalingClient {
+    codec: WireCodec,
+    sessions: HashMap<SessionKey, SessionState>,
+    next_ack_id: u32,
+    pending_acks: HashSet<AckId>,
+    transmits: VecDeque<Vec<u8>>,
+    events: VecDeque<SignalingEvent>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SessionKey {
+    Sender(SessionSenderId),
+    Receiver(SessionReceiverId),
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SessionState {
+    /// The channel open or join is sent and not confirmed yet.
+    Pending,
+    Active,
+    /// The channel open or join is rejected by the server, the session is removed.
+    Rejected,
+    /// The session is closed locally or by the server, the session is removed.
+    Closed(CloseReason),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SignalingEvent {
+    /// Emitted before the server message which changed the state.
+    SessionStateChanged {
+        session: SessionKey,
+        state: SessionState,
+    },
+    /// Server message, except for the acknowledgements reported separately.
+    Message(ServerMessage),
+    Delivered(AckId),
+    DeliveryFailed {
+        ack_id: AckId,
+        reason: NackReason,
+    },
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum SignalingClientError {
+    #[error(transparent)]
+    FrameError(#[from] FrameError),
+    #[error("session {0:?} is already used")]
+    SessionIsAlreadyUsed(SessionKey),
+    #[error("session {0:?} is not exist")]
+    SessionIsNotExist(SessionKey),
+    #[error("acknowledged message can not be nested")]
+    NestedAck,
+}
+
+impl SignalingClient {
+    pub fn new(codec: WireCodec) -> Self {
+        Self {
+            codec,
+            sessions: HashMap::new(),
+            next_ack_id: 0,
+            pending_acks: HashSet::new(),
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn codec(&self) -> WireCodec {
+        self.codec
+    }
+
+    pub fn session_state(&self, session: SessionKey) -> Option<SessionState> {
+        self.sessions.get(&session).copied()
+    }
+
+    /// Queues the message for transmission after checking it against the session states,
+    /// rejected messages are not queued.
+    pub fn send(&mut self, message: ClientMessage) -> Result<(), SignalingClientError> {
+        let data = self.codec.encode_frame(&message)?;
+        self.apply_client_message(&message)?;
+        self.transmits.push_back(data);
+        Ok(())
+    }
+
+    /// Queues the message which delivery to the peer is confirmed by the server,
+    /// the result is reported in `SignalingEvent::Delivered` or `DeliveryFailed`.
+    pub fn send_acked(&mut self, message: ClientMessage) -> Result<AckId, SignalingClientError> {
+        let ack_id = AckId(self.next_ack_id);
+        self.send(ClientMessage::WithAck {
+            ack_id,
+            message: Box::new(message),
+        })?;
+        self.next_ack_id = self.next_ack_id.wrapping_add(1);
+        let _: bool = self.pending_acks.insert(ack_id);
+        Ok(ack_id)
+    }
+
+    pub fn send_sender_message(
+        &mut self,
+        sender_id: SessionSenderId,
+        message: ClientSenderMessage,
+    ) -> Result<(), SignalingClientError> {
+        self.send(ClientMessage::SenderMessage { sender_id, message })
+    }
+
+    pub fn send_receiver_message(
+        &mut self,
+        receiver_id: SessionReceiverId,
+        message: ClientReceiverMessage,
+    ) -> Result<(), SignalingClientError> {
+        self.send(ClientMessage::ReceiverMessage {
+            receiver_id,
+            message,
+        })
+    }
+
+    /// Handles a received binary frame,
+    /// a frame that can not be decoded does not change the client state.
+    pub fn handle_incoming(&mut self, data: &[u8]) -> Result<(), SignalingClientError> {
+        let message = self.codec.decode_frame(data, u32::MAX)?;
+        self.handle_message(message);
+        Ok(())
+    }
+
+    /// Returns the next frame to send.
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<SignalingEvent> {
+        self.events.pop_front()
+    }
+
+    /// Closes all sessions after the connection is lost,
+    /// the queued frames and the awaited acknowledgements are dropped.
+    pub fn handle_disconnected(&mut self) {
+        self.transmits.clear();
+        for ack_id in self.pending_acks.drain() {
+            self.events.push_back(SignalingEvent::DeliveryFailed {
+                ack_id,
+                reason: NackReason::PeerDisconnected,
+            });
+        }
+        let sessions: Vec<_> = self.sessions.keys().copied().collect();
+        for session in sessions {
+            self.set_state(session, SessionState::Closed(CloseReason::RemoteClosed));
+        }
+    }
+
+    fn apply_client_message(
+        &mut self,
+        message: &ClientMessage,
+    ) -> Result<(), SignalingClientError> {
+        match message {
+            ClientMessage::SenderMessage { sender_id, message } => {
+                let session = SessionKey::Sender(*sender_id);
+                match message {
+                    ClientSenderMessage::OpenChannel { .. } => self.add_session(session),
+                    ClientSenderMessage::CloseChannel(reason) => {
+                        self.check_session(session)?;
+                        self.set_state(session, SessionState::Closed(*reason));
+                        Ok(())
+                    }
+                    _ => self.check_session(session),
+                }
+            }
+            ClientMessage::ReceiverMessage {
+                receiver_id,
+                message,
+            } => {
+                let session = SessionKey::Receiver(*receiver_id);
+                match message {
+                    ClientReceiverMessage::JoinChannel { .. } => self.add_session(session),
+                    ClientReceiverMessage::ExitChannel(reason) => {
+                        self.check_session(session)?;
+                        self.set_state(session, SessionState::Closed(*reason));
+                        Ok(())
+                    }
+                    _ => self.check_session(session),
+                }
+            }
+            ClientMessage::WithAck { message, .. } => match message.as_ref() {
+                ClientMessage::WithAck { .. } => Err(SignalingClientError::NestedAck),
+                message => self.apply_client_message(message),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn add_session(&mut self, session: SessionKey) -> Result<(), SignalingClientError> {
+        if self.sessions.contains_key(&session) {
+            return Err(SignalingClientError::SessionIsAlreadyUsed(session));
+        }
+        let _: Option<_> = self.sessions.insert(session, SessionState::Pending);
+        Ok(())
+    }
+
+    fn check_session(&self, session: SessionKey) -> Result<(), SignalingClientError> {
+        if self.sessions.contains_key(&session) {
+            Ok(())
+        } else {
+            Err(SignalingClientError::SessionIsNotExist(session))
+        }
+    }
+
+    fn handle_message(&mut self, message: ServerMessage) {
+        match &message {
+            ServerMessage::SenderMessage { sender_id, message } => {
+                let session = SessionKey::Sender(*sender_id);
+                match message {
+                    ServerSenderMessage::OpenChannelSuccess => {
+                        self.update_pending(session, SessionState::Active)
+                    }
+                    ServerSenderMessage::Error(
+                        ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(_)
+                        | ServerSenderErrorMessage::ChannelIsReserved(_),
+                    ) => self.update_pending(session, SessionState::Rejected),
+                    _ => {}
+                }
+            }
+            ServerMessage::ReceiverMessage {
+                receiver_id,
+                message,
+            } => {
+                let session = SessionKey::Receiver(*receiver_id);
+                match message {
+                    ServerReceiverMessage::JoinChannelSuccess => {
+                        self.update_pending(session, SessionState::Active)
+                    }
+                    ServerReceiverMessage::Error(
+                        ServerReceiverErrorMessage::ChannelIsNotExist(_)
+                        | ServerReceiverErrorMessage::ChannelIsAlreadyOccupied(_)
+                        | ServerReceiverErrorMessage::InvalidChannelCredentials(_),
+                    ) => self.update_pending(session, SessionState::Rejected),
+                    ServerReceiverMessage::Error(
+                        ServerReceiverErrorMessage::NegotiationTimedOut(_),
+                    ) => self.update_known(session, SessionState::Closed(CloseReason::Timeout)),
+                    ServerReceiverMessage::ChannelClosed(reason) => {
+                        self.update_known(session, SessionState::Closed(*reason))
+                    }
+                    _ => {}
+                }
+            }
+            ServerMessage::Ack(ack_id) if self.pending_acks.remove(ack_id) => {
+                self.events.push_back(SignalingEvent::Delivered(*ack_id));
+                return;
+            }
+            ServerMessage::Nack { ack_id, reason } if self.pending_acks.remove(ack_id) => {
+                self.events.push_back(SignalingEvent::DeliveryFailed {
+                    ack_id: *ack_id,
+                    reason: reason.clone(),
+                });
+                return;
+            }
+            _ => {}
+        }
+        // Unknown acknowledgements are passed as is, so protocol violations can be detected.
+        self.events.push_back(SignalingEvent::Message(message));
+    }
+
+    fn update_pending(&mut self, session: SessionKey, state: SessionState) {
+        if self.sessions.get(&session) == Some(&SessionState::Pending) {
+            self.set_state(session, state);
+        }
+    }
+
+    fn update_known(&mut self, session: SessionKey, state: SessionState) {
+        if self.sessions.contains_key(&session) {
+            self.set_state(session, state);
+        }
+    }
+
+    /// Rejected and closed sessions are removed, so their ids can be reused.
+    fn set_state(&mut self, session: SessionKey, state: SessionState) {
+        match state {
+            SessionState::Pending | SessionState::Active => {
+                let _: Option<_> = self.sessions.insert(session, state);
+            }
+            SessionState::Rejected | SessionState::Closed(_) => {
+                let _: Option<_> = self.sessions.remove(&session);
+            }
+        }
+        self.events
+            .push_back(SignalingEvent::SessionStateChanged { session, state });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionKey, SessionState, SignalingClient, SignalingClientError, SignalingEvent};
+    use crate::{
+        AckId, ChannelId, ClientMessage, ClientReceiverMessage, ClientSenderMessage, CloseReason,
+        NackReason, NetworkMode, ServerMessage, ServerReceiverErrorMessage, ServerReceiverMessage,
+        ServerSenderMessage, SessionReceiverId, SessionSenderId, WireCodec,
+    };
+
+    const CODEC: WireCodec = WireCodec::DEFAULT;
+
+    fn receive(client: &mut SignalingClient, message: &ServerMessage) {
+        client
+            .handle_incoming(&CODEC.encode_frame(message).unwrap())
+            .unwrap();
+    }
+
+    fn events(client: &mut SignalingClient) -> Vec<SignalingEvent> {
+        core::iter::from_fn(|| client.poll_event()).collect()
+    }
+
+    fn open_channel() -> ClientSenderMessage {
+        ClientSenderMessage::OpenChannel {
+            channel_id: ChannelId("a".to_owned()),
+            network_mode: NetworkMode::PeerToPeer,
+            claim_token: None,
+            secret: None,
+            resume_token: None,
+        }
+    }
+
+    #[test]
+    fn sender_session_is_opened_and_closed() {
+        let mut client = SignalingClient::new(CODEC);
+        let sender = SessionKey::Sender(SessionSenderId(1));
+
+        client
+            .send_sender_message(SessionSenderId(1), open_channel())
+            .unwrap();
+        let transmit: ClientMessage = CODEC
+            .decode_frame(&client.poll_transmit().unwrap(), u32::MAX)
+            .unwrap();
+        assert_eq!(
+            transmit,
+            ClientMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: open_channel(),
+            }
+        );
+        assert_eq!(client.session_state(sender), Some(SessionState::Pending));
+        assert!(matches!(
+            client.send_sender_message(SessionSenderId(1), open_channel()),
+            Err(SignalingClientError::SessionIsAlreadyUsed(_))
+        ));
+
+        let success = ServerMessage::SenderMessage {
+            sender_id: SessionSenderId(1),
+            message: ServerSenderMessage::OpenChannelSuccess,
+        };
+        receive(&mut client, &success);
+        assert_eq!(
+            events(&mut client),
+            vec![
+                SignalingEvent::SessionStateChanged {
+                    session: sender,
+                    state: SessionState::Active,
+                },
+                SignalingEvent::Message(success),
+            ]
+        );
+
+        client
+            .send_sender_message(
+                SessionSenderId(1),
+                ClientSenderMessage::CloseChannel(CloseReason::LocalClosed),
+            )
+            .unwrap();
+        assert_eq!(client.session_state(sender), None);
+        assert!(matches!(
+            client.send_sender_message(
+                SessionSenderId(1),
+                ClientSenderMessage::AllIceCandidatesSent
+            ),
+            Err(SignalingClientError::SessionIsNotExist(_))
+        ));
+    }
+
+    #[test]
+    fn rejected_join_removes_receiver_session() {
+        let mut client = SignalingClient::new(CODEC);
+        let channel_id = ChannelId("a".to_owned());
+
+        client
+            .send_receiver_message(
+                SessionReceiverId(1),
+                ClientReceiverMessage::JoinChannel {
+                    channel_id: channel_id.clone(),
+                    credential: None,
+                },
+            )
+            .unwrap();
+        receive(
+            &mut client,
+            &ServerMessage::ReceiverMessage {
+                receiver_id: SessionReceiverId(1),
+                message: ServerReceiverMessage::Error(
+                    ServerReceiverErrorMessage::ChannelIsNotExist(channel_id),
+                ),
+            },
+        );
+
+        assert!(matches!(
+            events(&mut client).first(),
+            Some(SignalingEvent::SessionStateChanged {
+                state: SessionState::Rejected,
+                ..
+            })
+        ));
+        assert_eq!(
+            client.session_state(SessionKey::Receiver(SessionReceiverId(1))),
+            None
+        );
+    }
+
+    #[test]
+    fn acknowledgements_are_matched_to_sent_messages() {
+        let mut client = SignalingClient::new(CODEC);
+        client
+            .send_sender_message(SessionSenderId(1), open_channel())
+            .unwrap();
+
+        let message = ClientMessage::SenderMessage {
+            sender_id: SessionSenderId(1),
+            message: ClientSenderMessage::AllIceCandidatesSent,
+        };
+        let first = client.send_acked(message.clone()).unwrap();
+        let second = client.send_acked(message).unwrap();
+        receive(&mut client, &ServerMessage::Ack(first));
+        receive(
+            &mut client,
+            &ServerMessage::Nack {
+                ack_id: second,
+                reason: NackReason::PeerDisconnected,
+            },
+        );
+        receive(&mut client, &ServerMessage::Ack(AckId(100)));
+
+        assert_eq!(
+            events(&mut client),
+            vec![
+                SignalingEvent::Delivered(first),
+                SignalingEvent::DeliveryFailed {
+                    ack_id: second,
+                    reason: NackReason::PeerDisconnected,
+                },
+                SignalingEvent::Message(ServerMessage::Ack(AckId(100))),
+            ]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+/// Serialization format of the signaling messages sent over the WebSocket.
+pub trait Codec {
+    /// Codec identifier used in the handshake.
+    const WIRE_CODEC: WireCodec;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError>;
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError>;
+}
+
+/// Codec selected at runtime, negotiated as a WebSocket subprotocol.
+///
+/// Peers that do not offer any subprotocol are assumed to use `Bincode`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WireCodec {
+    Bincode,
+    Json,
+    Postcard,
+    Cbor,
+}
+
+impl WireCodec {
+    /// Codec used by peers that do not negotiate one.
+    pub const DEFAULT: Self = Self::Bincode;
+
+    /// All codecs enabled by the crate features, in order of preference.
+    pub fn supported() -> Vec<Self> {
+        [Self::Postcard, Self::Cbor, Self::Bincode, Self::Json]
+            .iter()
+            .copied()
+            .filter(|codec| codec.is_supported())
+            .collect()
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Bincode => cfg!(feature = "bincode"),
+            Self::Json => cfg!(feature = "json"),
+            Self::Postcard => cfg!(feature = "postcard"),
+            Self::Cbor => cfg!(feature = "cbor"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bincode => "bincode",
+            Self::Json => "json",
+            Self::Postcard => "postcard",
+            Self::Cbor => "cbor",
+        }
+    }
+
+    /// WebSocket subprotocol name of the codec.
+    pub fn subprotocol(self) -> &'static str {
+        match self {
+            Self::Bincode => "signaling.bincode",
+            Self::Json => "signaling.json",
+            Self::Postcard => "signaling.postcard",
+            Self::Cbor => "signaling.cbor",
+        }
+    }
+
+    /// Returns the codec of the WebSocket subprotocol name
+    /// or `None` if it is unknown or not enabled.
+    pub fn from_subprotocol(subprotocol: &str) -> Option<Self> {
+        [Self::Bincode, Self::Json, Self::Postcard, Self::Cbor]
+            .iter()
+            .copied()
+            .find(|codec| codec.subprotocol() == subprotocol && codec.is_supported())
+    }
+
+    #[allow(unused_variables)] // with all codecs disabled
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            #[cfg(feature = "bincode")]
+            Self::Bincode => Bincode::encode(value),
+            #[cfg(feature = "json")]
+            Self::Json => Json::encode(value),
+            #[cfg(feature = "postcard")]
+            Self::Postcard => Postcard::encode(value),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => Cbor::encode(value),
+            #[allow(unreachable_patterns)]
+            _ => Err(CodecError::Unsupported(self)),
+        }
+    }
+
+    #[allow(unused_variables)] // with all codecs disabled
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> Result<T, CodecError> {
+        match self {
+            #[cfg(feature = "bincode")]
+            Self::Bincode => Bincode::decode(data),
+            #[cfg(feature = "json")]
+            Self::Json => Json::decode(data),
+            #[cfg(feature = "postcard")]
+            Self::Postcard => Postcard::decode(data),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => Cbor::decode(data),
+            #[allow(unreachable_patterns)]
+            _ => Err(CodecError::Unsupported(self)),
+        }
+    }
+}
+
+impl Default for WireCodec {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(feature = "bincode")]
+#[derive(Clone, Copy, Debug)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Codec for Bincode {
+    const WIRE_CODEC: WireCodec = WireCodec::Bincode;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+        bincode::serialize(value).map_err(|err| CodecError::encode(Self::WIRE_CODEC, err))
+    }
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError> {
+        bincode::deserialize(data).map_err(|err| CodecError::decode(Self::WIRE_CODEC, err))
+    }
+}
+
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    const WIRE_CODEC: WireCodec = WireCodec::Json;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(|err| CodecError::encode(Self::WIRE_CODEC, err))
+    }
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(data).map_err(|err| CodecError::decode(Self::WIRE_CODEC, err))
+    }
+}
+
+#[cfg(feature = "postcard")]
+#[derive(Clone, Copy, Debug)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Codec for Postcard {
+    const WIRE_CODEC: WireCodec = WireCodec::Postcard;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+        postcard::to_allocvec(value).map_err(|err| CodecError::encode(Self::WIRE_CODEC, err))
+    }
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError> {
+        postcard::from_bytes(data).map_err(|err| CodecError::decode(Self::WIRE_CODEC, err))
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    const WIRE_CODEC: WireCodec = WireCodec::Cbor;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(value, &mut data)
+            .map_err(|err| CodecError::encode(Self::WIRE_CODEC, err))?;
+        Ok(data)
+    }
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError> {
+        ciborium::de::from_reader(data).map_err(|err| CodecError::decode(Self::WIRE_CODEC, err))
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum CodecError {
+    #[error("{} encoding error: {}", .0.name(), .1)]
+    EncodeFailed(WireCodec, String),
+    #[error("{} decoding error: {}", .0.name(), .1)]
+    DecodeFailed(WireCodec, String),
+    #[error("codec `{}` is not enabled", .0.name())]
+    Unsupported(WireCodec),
+}
+
+impl CodecError {
+    #[allow(dead_code)]
+    fn encode(codec: WireCodec, err: impl core::fmt::Display) -> Self {
+        Self::EncodeFailed(codec, err.to_string())
+    }
+
+    #[allow(dead_code)]
+    fn decode(codec: WireCodec, err: impl core::fmt::Display) -> Self {
+        Self::DecodeFailed(codec, err.to_string())
+    }
+}
@@ -0,0 +1,427 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use thiserror::Error;
+
+use crate::FrameHeader;
+
+/// DNS-SD service type of the signaling servers advertised on the local network.
+pub const MDNS_SERVICE_TYPE: &str = "_webrtc-signaling._tcp.local";
+
+pub const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const MDNS_PORT: u16 = 5353;
+
+/// Time to live of the advertised records in seconds.
+const RECORD_TTL: u32 = 120;
+
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+/// Class bit of the questions asking for a unicast response
+/// and of the records replacing the cached ones (RFC 6762).
+const CLASS_TOP_BIT: u16 = 0x8000;
+const FLAGS_RESPONSE: u16 = 0x8400;
+const HEADER_LEN: usize = 12;
+
+/// Signaling server found by `parse_mdns_response` or `discover_servers`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DiscoveredServer {
+    pub instance: String,
+    /// The response source address with the advertised port,
+    /// so the host name of the server is never resolved.
+    pub addr: SocketAddr,
+}
+
+impl DiscoveredServer {
+    /// WebSocket URL of the server.
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+}
+
+/// Advertisement of a signaling server answering the mDNS queries of the clients.
+///
+/// The socket IO is left to the server, see `is_mdns_query` and `MdnsAdvertisement::response`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MdnsAdvertisement {
+    instance: String,
+    port: u16,
+}
+
+impl MdnsAdvertisement {
+    pub fn new(instance: String, port: u16) -> Result<Self, MdnsError> {
+        if instance.is_empty() || instance.len() > 63 {
+            return Err(MdnsError::InvalidInstanceName(instance));
+        }
+        Ok(Self { instance, port })
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Response with the PTR, SRV and TXT records of the server,
+    /// also sent unsolicited when the server starts.
+    pub fn response(&self) -> Vec<u8> {
+        let mut packet = header(FLAGS_RESPONSE, 0, 3);
+
+        let mut ptr = Vec::new();
+        write_name(&mut ptr, &[&self.instance], MDNS_SERVICE_TYPE);
+        write_record(
+            &mut packet,
+            &[],
+            MDNS_SERVICE_TYPE,
+            TYPE_PTR,
+            CLASS_IN,
+            &ptr,
+        );
+
+        let mut srv = Vec::new();
+        srv.extend_from_slice(&0_u16.to_be_bytes());
+        srv.extend_from_slice(&0_u16.to_be_bytes());
+        srv.extend_from_slice(&self.port.to_be_bytes());
+        write_name(&mut srv, &[&self.instance], "local");
+        write_record(
+            &mut packet,
+            &[&self.instance],
+            MDNS_SERVICE_TYPE,
+            TYPE_SRV,
+            CLASS_IN | CLASS_TOP_BIT,
+            &srv,
+        );
+
+        let version = format!("version={}", FrameHeader::VERSION);
+        let mut txt = vec![version.len() as u8];
+        txt.extend_from_slice(version.as_bytes());
+        write_record(
+            &mut packet,
+            &[&self.instance],
+            MDNS_SERVICE_TYPE,
+            TYPE_TXT,
+            CLASS_IN | CLASS_TOP_BIT,
+            &txt,
+        );
+
+        packet
+    }
+}
+
+/// Query for the signaling servers, sent to `MDNS_ADDR:MDNS_PORT`.
+///
+/// The responders answer the queries sent from other ports than `MDNS_PORT`
+/// directly to their source address.
+pub fn mdns_query() -> Vec<u8> {
+    let mut packet = header(0, 1, 0);
+    write_name(&mut packet, &[], MDNS_SERVICE_TYPE);
+    packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    packet.extend_from_slice(&(CLASS_IN | CLASS_TOP_BIT).to_be_bytes());
+    packet
+}
+
+/// Returns `true` if the packet is a query for the signaling servers.
+pub fn is_mdns_query(packet: &[u8]) -> bool {
+    let mut reader = Reader::new(packet);
+    let (flags, question_count) = match reader.header() {
+        Ok((flags, question_count, _)) => (flags, question_count),
+        Err(_) => return false,
+    };
+    if flags & 0x8000 != 0 {
+        return false;
+    }
+    (0..question_count).any(|_| match reader.question() {
+        Ok((name, typ)) => {
+            name.eq_ignore_ascii_case(MDNS_SERVICE_TYPE) && (typ == TYPE_PTR || typ == 255)
+        }
+        Err(_) => false,
+    })
+}
+
+/// Returns the signaling servers advertised in the response received from the source address.
+pub fn parse_mdns_response(
+    packet: &[u8],
+    source: IpAddr,
+) -> Result<Vec<DiscoveredServer>, MdnsError> {
+    let mut reader = Reader::new(packet);
+    let (flags, question_count, record_count) = reader.header()?;
+    if flags & 0x8000 == 0 {
+        return Ok(Vec::new());
+    }
+    for _ in 0..question_count {
+        let _: (String, u16) = reader.question()?;
+    }
+
+    let mut instances = Vec::new();
+    let mut ports = Vec::new();
+    for _ in 0..record_count {
+        let name = reader.name()?;
+        let typ = reader.u16()?;
+        let _class = reader.u16()?;
+        let _ttl = reader.u32()?;
+        let len = usize::from(reader.u16()?);
+        let data_end = reader.pos + len;
+        if data_end > packet.len() {
+            return Err(MdnsError::Truncated);
+        }
+        match typ {
+            TYPE_PTR if name.eq_ignore_ascii_case(MDNS_SERVICE_TYPE) => {
+                instances.push(reader.name()?);
+            }
+            TYPE_SRV => {
+                let _priority = reader.u16()?;
+                let _weight = reader.u16()?;
+                ports.push((name, reader.u16()?));
+            }
+            _ => {}
+        }
+        reader.pos = data_end;
+    }
+
+    let suffix = format!(".{}", MDNS_SERVICE_TYPE);
+    Ok(instances
+        .into_iter()
+        .filter_map(|instance_name| {
+            let port = ports
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&instance_name))?
+                .1;
+            let instance = instance_name
+                .get(..instance_name.len().checked_sub(suffix.len())?)?
+                .to_owned();
+            Some(DiscoveredServer {
+                instance,
+                addr: SocketAddr::new(source, port),
+            })
+        })
+        .collect())
+}
+
+/// Sends the query and collects the responses until the timeout,
+/// for the native clients connecting to a server on the same network.
+#[cfg(feature = "mdns")]
+pub fn discover_servers(
+    timeout: core::time::Duration,
+) -> Result<Vec<DiscoveredServer>, std::io::Error> {
+    use std::io::ErrorKind;
+    use std::net::UdpSocket;
+    use std::time::Instant;
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    let _: usize = socket.send_to(&mdns_query(), (MDNS_ADDR, MDNS_PORT))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut servers = Vec::new();
+    let mut buf = [0; 9000];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(servers);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let (len, source) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(servers)
+            }
+            Err(err) => return Err(err),
+        };
+        // Malformed responses of other responders are skipped.
+        for server in parse_mdns_response(&buf[..len], source.ip()).unwrap_or_default() {
+            if !servers.contains(&server) {
+                servers.push(server);
+            }
+        }
+    }
+}
+
+fn header(flags: u16, question_count: u16, answer_count: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(512);
+    packet.extend_from_slice(&0_u16.to_be_bytes());
+    packet.extend_from_slice(&flags.to_be_bytes());
+    packet.extend_from_slice(&question_count.to_be_bytes());
+    packet.extend_from_slice(&answer_count.to_be_bytes());
+    packet.extend_from_slice(&[0; 4]);
+    packet
+}
+
+/// Writes the labels followed by the dot-separated name, names are not compressed.
+fn write_name(packet: &mut Vec<u8>, labels: &[&str], name: &str) {
+    for label in labels.iter().copied().chain(name.split('.')) {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+}
+
+fn write_record(
+    packet: &mut Vec<u8>,
+    labels: &[&str],
+    name: &str,
+    typ: u16,
+    class: u16,
+    data: &[u8],
+) {
+    write_name(packet, labels, name);
+    packet.extend_from_slice(&typ.to_be_bytes());
+    packet.extend_from_slice(&class.to_be_bytes());
+    packet.extend_from_slice(&RECORD_TTL.to_be_bytes());
+    packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+}
+
+struct Reader<'a> {
+    packet: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(packet: &'a [u8]) -> Self {
+        Self { packet, pos: 0 }
+    }
+
+    /// Returns the flags, the question count and the count of all records.
+    fn header(&mut self) -> Result<(u16, u16, u32), MdnsError> {
+        if self.packet.len() < HEADER_LEN {
+            return Err(MdnsError::Truncated);
+        }
+        let _id = self.u16()?;
+        let flags = self.u16()?;
+        let question_count = self.u16()?;
+        let mut record_count = 0;
+        for _ in 0..3 {
+            record_count += u32::from(self.u16()?);
+        }
+        Ok((flags, question_count, record_count))
+    }
+
+    fn question(&mut self) -> Result<(String, u16), MdnsError> {
+        let name = self.name()?;
+        let typ = self.u16()?;
+        let _class = self.u16()?;
+        Ok((name, typ))
+    }
+
+    fn u16(&mut self) -> Result<u16, MdnsError> {
+        let bytes = self
+            .packet
+            .get(self.pos..self.pos + 2)
+            .ok_or(MdnsError::Truncated)?;
+        self.pos += 2;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, MdnsError> {
+        Ok(u32::from(self.u16()?) << 16 | u32::from(self.u16()?))
+    }
+
+    /// Reads the dot-separated name, following the compression pointers.
+    fn name(&mut self) -> Result<String, MdnsError> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut pos = self.pos;
+        let mut end = None;
+        // Every pointer leads backwards, so a valid name has fewer jumps than bytes.
+        for _ in 0..self.packet.len() {
+            let len = *self.packet.get(pos).ok_or(MdnsError::Truncated)?;
+            match len {
+                0 => {
+                    self.pos = end.unwrap_or(pos + 1);
+                    return Ok(labels.join("."));
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    let low = *self.packet.get(pos + 1).ok_or(MdnsError::Truncated)?;
+                    let target = usize::from(len & 0x3f) << 8 | usize::from(low);
+                    if target >= pos {
+                        return Err(MdnsError::InvalidName);
+                    }
+                    end = end.or(Some(pos + 2));
+                    pos = target;
+                }
+                len if len & 0xc0 == 0 => {
+                    let label = self
+                        .packet
+                        .get(pos + 1..pos + 1 + usize::from(len))
+                        .ok_or(MdnsError::Truncated)?;
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    pos += 1 + usize::from(len);
+                }
+                _ => return Err(MdnsError::InvalidName),
+            }
+        }
+        Err(MdnsError::InvalidName)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Error, Hash, PartialEq)]
+pub enum MdnsError {
+    #[error("mDNS instance name {0:?} must be from 1 to 63 bytes long")]
+    InvalidInstanceName(String),
+    #[error("mDNS packet is truncated")]
+    Truncated,
+    #[error("mDNS packet has an invalid name")]
+    InvalidName,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advertised_servers_are_discovered() {
+        let advertisement = MdnsAdvertisement::new("Kiosk 1.lobby".to_owned(), 9010).unwrap();
+        let source = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+
+        assert!(is_mdns_query(&mdns_query()));
+        assert!(!is_mdns_query(&advertisement.response()));
+        assert_eq!(
+            parse_mdns_response(&advertisement.response(), source).unwrap(),
+            vec![DiscoveredServer {
+                instance: "Kiosk 1.lobby".to_owned(),
+                addr: SocketAddr::new(source, 9010),
+            }]
+        );
+        assert_eq!(
+            parse_mdns_response(&mdns_query(), source).unwrap(),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn compressed_names_are_followed() {
+        // Response of another responder, the instance name points to the service type.
+        let mut packet = header(FLAGS_RESPONSE, 0, 2);
+        let service_type_pos = packet.len();
+        write_name(&mut packet, &[], MDNS_SERVICE_TYPE);
+        packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet.extend_from_slice(&RECORD_TTL.to_be_bytes());
+        packet.extend_from_slice(&8_u16.to_be_bytes());
+        let instance_pos = packet.len();
+        packet.extend_from_slice(&[5, b'd', b'e', b'v', b'i', b'c']);
+        packet.extend_from_slice(&[0xc0, service_type_pos as u8]);
+        packet.extend_from_slice(&[0xc0, instance_pos as u8]);
+        packet.extend_from_slice(&TYPE_SRV.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet.extend_from_slice(&RECORD_TTL.to_be_bytes());
+        packet.extend_from_slice(&8_u16.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0, 0x23, 0x32, 0xc0, instance_pos as u8]);
+
+        let source = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(
+            parse_mdns_response(&packet, source).unwrap(),
+            vec![DiscoveredServer {
+                instance: "devic".to_owned(),
+                addr: SocketAddr::new(source, 0x2332),
+            }]
+        );
+
+        let mut looped = packet.clone();
+        looped[instance_pos + 7] = instance_pos as u8 + 6;
+        assert_eq!(
+            parse_mdns_response(&looped, source),
+            Err(MdnsError::InvalidName)
+        );
+    }
+}
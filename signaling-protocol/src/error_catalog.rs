@@ -0,0 +1,446 @@
+use core::fmt;
+
+use crate::{
+    ChannelId, ChannelRequestErrorMessage, RecordingErrorMessage, ReservationErrorMessage,
+    ServerMessage, ServerReceiverErrorMessage, ServerReceiverMessage, ServerSenderErrorMessage,
+    ServerSenderMessage,
+};
+
+/// Language of the error descriptions shown to users and written to the server log.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Language {
+    #[default]
+    English,
+    Russian,
+}
+
+impl Language {
+    pub const ALL: &'static [Self] = &[Self::English, Self::Russian];
+
+    /// Language of a BCP 47 tag such as `ru-RU`, only its primary subtag is matched.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|language| language.tag().eq_ignore_ascii_case(primary))
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::Russian => "ru",
+        }
+    }
+}
+
+/// Stable code of an error reported by the server, see `CatalogedError`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorCode {
+    SenderIdIsAlreadyUsed,
+    SenderIdIsNotExist,
+    ChannelIdIsAlreadyUsed,
+    ChannelIsReserved,
+    ReceiverIdIsAlreadyUsed,
+    ReceiverIdIsNotExist,
+    ChannelIsNotExist,
+    ChannelIsAlreadyOccupied,
+    NegotiationTimedOut,
+    InvalidChannelCredentials,
+    ChannelIsAlreadyOpen,
+    ChannelIsAlreadyReserved,
+    ReservationLimitIsReached,
+    PeerIdIsAlreadyUsed,
+    PeerIsNotFound,
+    RequestIsNotFound,
+    ChannelIsNotOpened,
+    RecordingIsDisabled,
+    RecordingIsAlreadyStarted,
+    RecordingIsNotStarted,
+    RecordingQuotaIsExceeded,
+    RecordingStorageFailed,
+    /// Error added by a newer protocol revision.
+    Unknown,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [Self] = &[
+        Self::SenderIdIsAlreadyUsed,
+        Self::SenderIdIsNotExist,
+        Self::ChannelIdIsAlreadyUsed,
+        Self::ChannelIsReserved,
+        Self::ReceiverIdIsAlreadyUsed,
+        Self::ReceiverIdIsNotExist,
+        Self::ChannelIsNotExist,
+        Self::ChannelIsAlreadyOccupied,
+        Self::NegotiationTimedOut,
+        Self::InvalidChannelCredentials,
+        Self::ChannelIsAlreadyOpen,
+        Self::ChannelIsAlreadyReserved,
+        Self::ReservationLimitIsReached,
+        Self::PeerIdIsAlreadyUsed,
+        Self::PeerIsNotFound,
+        Self::RequestIsNotFound,
+        Self::ChannelIsNotOpened,
+        Self::RecordingIsDisabled,
+        Self::RecordingIsAlreadyStarted,
+        Self::RecordingIsNotStarted,
+        Self::RecordingQuotaIsExceeded,
+        Self::RecordingStorageFailed,
+        Self::Unknown,
+    ];
+
+    /// Code to search for in the logs and the documentation, it never changes.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SenderIdIsAlreadyUsed => "E101",
+            Self::SenderIdIsNotExist => "E102",
+            Self::ChannelIdIsAlreadyUsed => "E103",
+            Self::ChannelIsReserved => "E104",
+            Self::ReceiverIdIsAlreadyUsed => "E201",
+            Self::ReceiverIdIsNotExist => "E202",
+            Self::ChannelIsNotExist => "E203",
+            Self::ChannelIsAlreadyOccupied => "E204",
+            Self::NegotiationTimedOut => "E205",
+            Self::InvalidChannelCredentials => "E206",
+            Self::ChannelIsAlreadyOpen => "E301",
+            Self::ChannelIsAlreadyReserved => "E302",
+            Self::ReservationLimitIsReached => "E303",
+            Self::PeerIdIsAlreadyUsed => "E401",
+            Self::PeerIsNotFound => "E402",
+            Self::RequestIsNotFound => "E403",
+            Self::ChannelIsNotOpened => "E404",
+            Self::RecordingIsDisabled => "E501",
+            Self::RecordingIsAlreadyStarted => "E502",
+            Self::RecordingIsNotStarted => "E503",
+            Self::RecordingQuotaIsExceeded => "E504",
+            Self::RecordingStorageFailed => "E505",
+            Self::Unknown => "E000",
+        }
+    }
+
+    /// What went wrong.
+    pub fn description(self, language: Language) -> &'static str {
+        use Language::{English, Russian};
+
+        match (self, language) {
+            (Self::SenderIdIsAlreadyUsed, English) => "sender session id is already used",
+            (Self::SenderIdIsAlreadyUsed, Russian) => {
+                "идентификатор сессии отправителя уже используется"
+            }
+            (Self::SenderIdIsNotExist, English) => "sender session is not found",
+            (Self::SenderIdIsNotExist, Russian) => "сессия отправителя не найдена",
+            (Self::ChannelIdIsAlreadyUsed, English) => "channel name already in use",
+            (Self::ChannelIdIsAlreadyUsed, Russian) => "имя канала уже занято",
+            (Self::ChannelIsReserved, English) => "channel name is reserved",
+            (Self::ChannelIsReserved, Russian) => "имя канала зарезервировано",
+            (Self::ReceiverIdIsAlreadyUsed, English) => "receiver session id is already used",
+            (Self::ReceiverIdIsAlreadyUsed, Russian) => {
+                "идентификатор сессии получателя уже используется"
+            }
+            (Self::ReceiverIdIsNotExist, English) => "receiver session is not found",
+            (Self::ReceiverIdIsNotExist, Russian) => "сессия получателя не найдена",
+            (Self::ChannelIsNotExist, English) => "channel is not open",
+            (Self::ChannelIsNotExist, Russian) => "канал не открыт",
+            (Self::ChannelIsAlreadyOccupied, English) => "channel already has a receiver",
+            (Self::ChannelIsAlreadyOccupied, Russian) => "у канала уже есть получатель",
+            (Self::NegotiationTimedOut, English) => "channel offer was not answered in time",
+            (Self::NegotiationTimedOut, Russian) => "на предложение канала не ответили вовремя",
+            (Self::InvalidChannelCredentials, English) => "channel password is wrong",
+            (Self::InvalidChannelCredentials, Russian) => "неверный пароль канала",
+            (Self::ChannelIsAlreadyOpen, English) => "channel is already open",
+            (Self::ChannelIsAlreadyOpen, Russian) => "канал уже открыт",
+            (Self::ChannelIsAlreadyReserved, English) => "channel name is already reserved",
+            (Self::ChannelIsAlreadyReserved, Russian) => "имя канала уже зарезервировано",
+            (Self::ReservationLimitIsReached, English) => "too many channel names are reserved",
+            (Self::ReservationLimitIsReached, Russian) => {
+                "зарезервировано слишком много имён каналов"
+            }
+            (Self::PeerIdIsAlreadyUsed, English) => "peer id is registered by another client",
+            (Self::PeerIdIsAlreadyUsed, Russian) => {
+                "идентификатор пира зарегистрирован другим клиентом"
+            }
+            (Self::PeerIsNotFound, English) => "peer is not registered or disconnected",
+            (Self::PeerIsNotFound, Russian) => "пир не зарегистрирован или отключён",
+            (Self::RequestIsNotFound, English) => "channel request is expired or already answered",
+            (Self::RequestIsNotFound, Russian) => "запрос канала истёк или уже обработан",
+            (Self::ChannelIsNotOpened, English) => "accepted channel is not open",
+            (Self::ChannelIsNotOpened, Russian) => "принятый канал не открыт",
+            (Self::RecordingIsDisabled, English) => "server recording is disabled",
+            (Self::RecordingIsDisabled, Russian) => "запись на сервере отключена",
+            (Self::RecordingIsAlreadyStarted, English) => "recording is already started",
+            (Self::RecordingIsAlreadyStarted, Russian) => "запись уже начата",
+            (Self::RecordingIsNotStarted, English) => "recording is not started",
+            (Self::RecordingIsNotStarted, Russian) => "запись не начата",
+            (Self::RecordingQuotaIsExceeded, English) => "recording storage quota is exceeded",
+            (Self::RecordingQuotaIsExceeded, Russian) => "превышена квота хранилища записей",
+            (Self::RecordingStorageFailed, English) => "recording could not be stored",
+            (Self::RecordingStorageFailed, Russian) => "запись не удалось сохранить",
+            (Self::Unknown, English) => "server reported an unknown error",
+            (Self::Unknown, Russian) => "сервер сообщил о неизвестной ошибке",
+        }
+    }
+
+    /// Description followed by the hint and the code,
+    /// e.g. ``channel name already in use: `room` — pick another [E103]``.
+    pub fn describe(self, channel_id: Option<&ChannelId>, language: Language) -> String {
+        let subject = match channel_id {
+            Some(channel_id) => format!(": `{}`", channel_id.0),
+            None => String::new(),
+        };
+        format!(
+            "{}{} — {} [{}]",
+            self.description(language),
+            subject,
+            self.hint(language),
+            self.as_str()
+        )
+    }
+
+    /// What the user can do about it.
+    pub fn hint(self, language: Language) -> &'static str {
+        use Language::{English, Russian};
+
+        match (self, language) {
+            (Self::SenderIdIsAlreadyUsed, English) | (Self::ReceiverIdIsAlreadyUsed, English) => {
+                "reload the page"
+            }
+            (Self::SenderIdIsAlreadyUsed, Russian) | (Self::ReceiverIdIsAlreadyUsed, Russian) => {
+                "перезагрузите страницу"
+            }
+            (Self::SenderIdIsNotExist, English) => "open the channel again",
+            (Self::SenderIdIsNotExist, Russian) => "откройте канал заново",
+            (Self::ChannelIdIsAlreadyUsed, English)
+            | (Self::ChannelIsAlreadyOpen, English)
+            | (Self::ChannelIsAlreadyReserved, English) => "pick another",
+            (Self::ChannelIdIsAlreadyUsed, Russian)
+            | (Self::ChannelIsAlreadyOpen, Russian)
+            | (Self::ChannelIsAlreadyReserved, Russian) => "выберите другое",
+            (Self::ReservationLimitIsReached, English) => {
+                "open the reserved channels or try again later"
+            }
+            (Self::ReservationLimitIsReached, Russian) => {
+                "откройте зарезервированные каналы или повторите позже"
+            }
+            (Self::ChannelIsReserved, English) => "use its claim token or pick another",
+            (Self::ChannelIsReserved, Russian) => "используйте его токен или выберите другое",
+            (Self::ReceiverIdIsNotExist, English) | (Self::NegotiationTimedOut, English) => {
+                "join the channel again"
+            }
+            (Self::ReceiverIdIsNotExist, Russian) | (Self::NegotiationTimedOut, Russian) => {
+                "подключитесь к каналу заново"
+            }
+            (Self::ChannelIsNotExist, English) => {
+                "check the name or wait for the sender to open it"
+            }
+            (Self::ChannelIsNotExist, Russian) => {
+                "проверьте имя или дождитесь, пока отправитель его откроет"
+            }
+            (Self::InvalidChannelCredentials, English) => "ask the sender for the password",
+            (Self::InvalidChannelCredentials, Russian) => "узнайте пароль у отправителя",
+            (Self::ChannelIsAlreadyOccupied, English) => "ask the sender for another channel",
+            (Self::ChannelIsAlreadyOccupied, Russian) => {
+                "попросите отправителя открыть другой канал"
+            }
+            (Self::PeerIdIsAlreadyUsed, English) => "pick another peer id",
+            (Self::PeerIdIsAlreadyUsed, Russian) => "выберите другой идентификатор",
+            (Self::PeerIsNotFound, English) => "check the peer id or try again later",
+            (Self::PeerIsNotFound, Russian) => "проверьте идентификатор или повторите позже",
+            (Self::RequestIsNotFound, English) => "ask the peer to send a new request",
+            (Self::RequestIsNotFound, Russian) => "попросите пира отправить новый запрос",
+            (Self::ChannelIsNotOpened, English) => "open the channel before accepting",
+            (Self::ChannelIsNotOpened, Russian) => "откройте канал до принятия запроса",
+            (Self::RecordingIsDisabled, English) => "ask the server operator to enable it",
+            (Self::RecordingIsDisabled, Russian) => "попросите администратора сервера её включить",
+            (Self::RecordingIsAlreadyStarted, English) => "stop the current recording first",
+            (Self::RecordingIsAlreadyStarted, Russian) => "сначала остановите текущую запись",
+            (Self::RecordingIsNotStarted, English) | (Self::RecordingStorageFailed, English) => {
+                "start the recording again"
+            }
+            (Self::RecordingIsNotStarted, Russian) | (Self::RecordingStorageFailed, Russian) => {
+                "начните запись заново"
+            }
+            (Self::RecordingQuotaIsExceeded, English) => "ask the server operator to free space",
+            (Self::RecordingQuotaIsExceeded, Russian) => {
+                "попросите администратора сервера освободить место"
+            }
+            (Self::Unknown, English) => "update the application",
+            (Self::Unknown, Russian) => "обновите приложение",
+        }
+    }
+}
+
+/// Error reported by the server with a catalog entry,
+/// so that it is shown the same way by the server logs and the client UI.
+pub trait CatalogedError {
+    fn error_code(&self) -> ErrorCode;
+
+    /// Channel the error is about, if it is reported.
+    fn channel_id(&self) -> Option<&ChannelId> {
+        None
+    }
+
+    /// See `ErrorCode::describe`.
+    fn describe(&self, language: Language) -> String {
+        self.error_code().describe(self.channel_id(), language)
+    }
+}
+
+/// Errors are displayed in English, see `CatalogedError::describe` for other languages.
+macro_rules! impl_display_with_catalog {
+    ($($ty:ty),*) => {$(
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.describe(Language::English))
+            }
+        }
+
+        impl std::error::Error for $ty {}
+    )*};
+}
+
+impl_display_with_catalog!(
+    ServerSenderErrorMessage,
+    ServerReceiverErrorMessage,
+    ReservationErrorMessage,
+    ChannelRequestErrorMessage,
+    RecordingErrorMessage
+);
+
+impl CatalogedError for ServerSenderErrorMessage {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::SessionSenderIdIsAlreadyUsed => ErrorCode::SenderIdIsAlreadyUsed,
+            Self::SessionSenderIdIsNotExist => ErrorCode::SenderIdIsNotExist,
+            Self::ChannelIdIsAlreadyUsed(_) => ErrorCode::ChannelIdIsAlreadyUsed,
+            Self::ChannelIsReserved(_) => ErrorCode::ChannelIsReserved,
+        }
+    }
+
+    fn channel_id(&self) -> Option<&ChannelId> {
+        match self {
+            Self::SessionSenderIdIsAlreadyUsed | Self::SessionSenderIdIsNotExist => None,
+            Self::ChannelIdIsAlreadyUsed(channel_id) | Self::ChannelIsReserved(channel_id) => {
+                Some(channel_id)
+            }
+        }
+    }
+}
+
+impl CatalogedError for ServerReceiverErrorMessage {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::SessionReceiverIdIsAlreadyUsed => ErrorCode::ReceiverIdIsAlreadyUsed,
+            Self::SessionReceiverIdIsNotExist => ErrorCode::ReceiverIdIsNotExist,
+            Self::ChannelIsNotExist(_) => ErrorCode::ChannelIsNotExist,
+            Self::ChannelIsAlreadyOccupied(_) => ErrorCode::ChannelIsAlreadyOccupied,
+            Self::NegotiationTimedOut(_) => ErrorCode::NegotiationTimedOut,
+            Self::InvalidChannelCredentials(_) => ErrorCode::InvalidChannelCredentials,
+        }
+    }
+
+    fn channel_id(&self) -> Option<&ChannelId> {
+        match self {
+            Self::SessionReceiverIdIsAlreadyUsed | Self::SessionReceiverIdIsNotExist => None,
+            Self::ChannelIsNotExist(channel_id)
+            | Self::ChannelIsAlreadyOccupied(channel_id)
+            | Self::NegotiationTimedOut(channel_id)
+            | Self::InvalidChannelCredentials(channel_id) => Some(channel_id),
+        }
+    }
+}
+
+impl CatalogedError for ReservationErrorMessage {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::ChannelIsAlreadyOpen => ErrorCode::ChannelIsAlreadyOpen,
+            Self::ChannelIsAlreadyReserved => ErrorCode::ChannelIsAlreadyReserved,
+            Self::ReservationLimitIsReached => ErrorCode::ReservationLimitIsReached,
+            Self::Unknown => ErrorCode::Unknown,
+        }
+    }
+}
+
+impl CatalogedError for ChannelRequestErrorMessage {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::PeerIdIsAlreadyUsed => ErrorCode::PeerIdIsAlreadyUsed,
+            Self::PeerIsNotFound => ErrorCode::PeerIsNotFound,
+            Self::RequestIsNotFound => ErrorCode::RequestIsNotFound,
+            Self::ChannelIsNotOpened => ErrorCode::ChannelIsNotOpened,
+            Self::Unknown => ErrorCode::Unknown,
+        }
+    }
+}
+
+impl CatalogedError for RecordingErrorMessage {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::RecordingIsDisabled => ErrorCode::RecordingIsDisabled,
+            Self::RecordingIsAlreadyStarted => ErrorCode::RecordingIsAlreadyStarted,
+            Self::RecordingIsNotStarted => ErrorCode::RecordingIsNotStarted,
+            Self::RecordingQuotaIsExceeded => ErrorCode::RecordingQuotaIsExceeded,
+            Self::RecordingStorageFailed => ErrorCode::RecordingStorageFailed,
+            Self::Unknown => ErrorCode::Unknown,
+        }
+    }
+}
+
+impl ServerMessage {
+    /// Description of the error reported by the message, `None` for other messages.
+    pub fn describe_error(&self, language: Language) -> Option<String> {
+        match self {
+            Self::ChannelReservationFailed { channel_id, reason } => {
+                Some(reason.error_code().describe(Some(channel_id), language))
+            }
+            Self::PeerRegistrationFailed { reason, .. }
+            | Self::ChannelRequestFailed { reason, .. } => Some(reason.describe(language)),
+            Self::SenderMessage {
+                message: ServerSenderMessage::Error(err),
+                ..
+            }
+            | Self::SenderMessage {
+                message: ServerSenderMessage::RenameChannelFailed(err),
+                ..
+            } => Some(err.describe(language)),
+            Self::SenderMessage {
+                message: ServerSenderMessage::RecordingFailed(err),
+                ..
+            } => Some(err.describe(language)),
+            Self::ReceiverMessage {
+                message: ServerReceiverMessage::Error(err),
+                ..
+            } => Some(err.describe(language)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{CatalogedError, ErrorCode, Language};
+    use crate::{ChannelId, ServerSenderErrorMessage};
+
+    #[test]
+    fn catalog_codes_are_unique_and_described_in_every_language() {
+        let codes: HashSet<_> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+        for &code in ErrorCode::ALL {
+            for &language in Language::ALL {
+                assert!(!code.description(language).is_empty());
+                assert!(!code.hint(language).is_empty());
+            }
+        }
+
+        let err = ServerSenderErrorMessage::ChannelIdIsAlreadyUsed(ChannelId("room".to_owned()));
+        assert_eq!(
+            err.describe(Language::English),
+            "channel name already in use: `room` — pick another [E103]"
+        );
+        assert_eq!(err.to_string(), err.describe(Language::English));
+        assert_eq!(Language::from_tag("ru-RU"), Some(Language::Russian));
+        assert_eq!(Language::from_tag("de"), None);
+    }
+}
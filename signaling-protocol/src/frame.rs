@@ -0,0 +1,246 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{ClientMessage, CodecError, ServerMessage, WireCodec};
+
+/// Header of every signaling WebSocket binary frame, followed by the encoded message.
+///
+/// The header is checked before the payload is decoded, so that oversized frames
+/// and frames of unknown message types are rejected without deserializing them,
+/// and a frame length is known from its first `FrameHeader::LEN` bytes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FrameHeader {
+    pub version: u8,
+    /// See `FramedMessage::type_id`.
+    pub type_id: u8,
+    pub payload_len: u32,
+}
+
+/// Message sent in a frame with its type id in the header.
+pub trait FramedMessage: Serialize + DeserializeOwned {
+    /// Number of the known type ids, they are numbered from `0`.
+    const TYPE_COUNT: u8;
+
+    /// Type id of the top-level message variant.
+    fn type_id(&self) -> u8;
+}
+
+impl FrameHeader {
+    pub const MAGIC: [u8; 2] = *b"SG";
+    pub const VERSION: u8 = 1;
+    /// Magic, version, type id and big-endian payload length.
+    pub const LEN: usize = 8;
+
+    pub fn new(type_id: u8, payload_len: u32) -> Self {
+        Self {
+            version: Self::VERSION,
+            type_id,
+            payload_len,
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::LEN] {
+        let len = self.payload_len.to_be_bytes();
+        [
+            Self::MAGIC[0],
+            Self::MAGIC[1],
+            self.version,
+            self.type_id,
+            len[0],
+            len[1],
+            len[2],
+            len[3],
+        ]
+    }
+
+    /// Parses the header from the first `FrameHeader::LEN` bytes of a frame,
+    /// the rest of the frame is not required.
+    pub fn parse(data: &[u8]) -> Result<Self, FrameError> {
+        if data.len() < Self::LEN {
+            return Err(FrameError::IncompleteHeader(data.len()));
+        }
+        if data[0..2] != Self::MAGIC {
+            return Err(FrameError::InvalidMagic([data[0], data[1]]));
+        }
+        if data[2] != Self::VERSION {
+            return Err(FrameError::UnsupportedVersion(data[2]));
+        }
+        Ok(Self {
+            version: data[2],
+            type_id: data[3],
+            payload_len: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        })
+    }
+
+    /// Length of the whole frame including the header.
+    pub fn frame_len(self) -> usize {
+        Self::LEN + self.payload_len as usize
+    }
+}
+
+impl WireCodec {
+    pub fn encode_frame<T: FramedMessage>(self, message: &T) -> Result<Vec<u8>, FrameError> {
+        use core::convert::TryFrom;
+
+        let payload = self.encode(message)?;
+        let payload_len = u32::try_from(payload.len())
+            .map_err(|_| FrameError::PayloadIsTooLarge(payload.len(), u32::MAX))?;
+        let header = FrameHeader::new(message.type_id(), payload_len);
+        let mut data = Vec::with_capacity(header.frame_len());
+        data.extend_from_slice(&header.to_bytes());
+        data.extend_from_slice(&payload);
+        Ok(data)
+    }
+
+    /// Decodes the frame if its header is valid and its payload is not longer
+    /// than `max_payload_len`.
+    pub fn decode_frame<T: FramedMessage>(
+        self,
+        data: &[u8],
+        max_payload_len: u32,
+    ) -> Result<T, FrameError> {
+        let header = FrameHeader::parse(data)?;
+        if header.type_id >= T::TYPE_COUNT {
+            return Err(FrameError::UnknownType(header.type_id));
+        }
+        if header.payload_len > max_payload_len {
+            return Err(FrameError::PayloadIsTooLarge(
+                header.payload_len as usize,
+                max_payload_len,
+            ));
+        }
+        if data.len() != header.frame_len() {
+            return Err(FrameError::LengthMismatch(
+                header.payload_len,
+                data.len() - FrameHeader::LEN,
+            ));
+        }
+        let message: T = self.decode(&data[FrameHeader::LEN..])?;
+        if message.type_id() != header.type_id {
+            return Err(FrameError::TypeMismatch(header.type_id, message.type_id()));
+        }
+        Ok(message)
+    }
+}
+
+impl FramedMessage for ClientMessage {
+    const TYPE_COUNT: u8 = 13;
+
+    fn type_id(&self) -> u8 {
+        match self {
+            Self::SenderMessage { .. } => 0,
+            Self::ReceiverMessage { .. } => 1,
+            Self::RequestChannelSnapshot { .. } => 2,
+            Self::ReserveChannel { .. } => 3,
+            Self::ReportMilestones(_) => 4,
+            Self::PauseChannelList => 5,
+            Self::ResumeChannelList => 6,
+            Self::RegisterPeer(_) => 7,
+            Self::RequestChannel { .. } => 8,
+            Self::AcceptChannelRequest { .. } => 9,
+            Self::DeclineChannelRequest { .. } => 10,
+            Self::WithAck { .. } => 11,
+            Self::Ping => 12,
+        }
+    }
+}
+
+impl FramedMessage for ServerMessage {
+    const TYPE_COUNT: u8 = 16;
+
+    fn type_id(&self) -> u8 {
+        match self {
+            Self::OpenChannelsChanged(_) => 0,
+            Self::ChannelSnapshot { .. } => 1,
+            Self::ChannelReserved { .. } => 2,
+            Self::ChannelReservationFailed { .. } => 3,
+            Self::PeerRegistrationFailed { .. } => 4,
+            Self::ChannelRequested { .. } => 5,
+            Self::ChannelRequestAccepted { .. } => 6,
+            Self::ChannelRequestDeclined { .. } => 7,
+            Self::ChannelRequestFailed { .. } => 8,
+            Self::ServerShutdown => 9,
+            Self::SenderMessage { .. } => 10,
+            Self::ReceiverMessage { .. } => 11,
+            Self::Ack(_) => 12,
+            Self::Nack { .. } => 13,
+            Self::Pong => 14,
+            Self::ChannelPreviewChanged { .. } => 15,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum FrameError {
+    #[error("frame header is incomplete, {0} bytes received")]
+    IncompleteHeader(usize),
+    #[error("frame magic {0:?} is invalid")]
+    InvalidMagic([u8; 2]),
+    #[error("frame version {0} is not supported")]
+    UnsupportedVersion(u8),
+    #[error("frame message type {0} is unknown")]
+    UnknownType(u8),
+    #[error("frame payload of {0} bytes exceeds the limit of {1} bytes")]
+    PayloadIsTooLarge(usize, u32),
+    #[error("frame payload length {0} does not match the received {1} bytes")]
+    LengthMismatch(u32, usize),
+    #[error("frame message type {0} does not match the decoded type {1}")]
+    TypeMismatch(u8, u8),
+    #[error(transparent)]
+    CodecError(#[from] CodecError),
+}
+
+impl FrameError {
+    /// Returns `true` if the frame is well-formed but its message type
+    /// is added by a newer protocol revision, such frames are skipped by the older peers.
+    pub fn is_unknown_type(&self) -> bool {
+        matches!(self, Self::UnknownType(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameError, FrameHeader};
+    use crate::{ChannelId, ClientMessage, ServerMessage, WireCodec};
+
+    const CODEC: WireCodec = WireCodec::DEFAULT;
+
+    #[test]
+    fn frames_are_checked_before_decoding() {
+        let message = ClientMessage::ReserveChannel {
+            channel_id: ChannelId("room".to_owned()),
+        };
+        let data = CODEC.encode_frame(&message).unwrap();
+        let header = FrameHeader::parse(&data[..FrameHeader::LEN]).unwrap();
+        assert_eq!(header.type_id, 3);
+        assert_eq!(header.frame_len(), data.len());
+        assert_eq!(
+            CODEC.decode_frame::<ClientMessage>(&data, 1024).unwrap(),
+            message
+        );
+
+        assert!(matches!(
+            CODEC.decode_frame::<ClientMessage>(&data, 4),
+            Err(FrameError::PayloadIsTooLarge(_, 4))
+        ));
+        let mut unknown = data.clone();
+        unknown[3] = 200;
+        assert!(matches!(
+            CODEC.decode_frame::<ClientMessage>(&unknown, 1024),
+            Err(FrameError::UnknownType(200))
+        ));
+        assert!(matches!(
+            CODEC.decode_frame::<ClientMessage>(&data[..data.len() - 1], 1024),
+            Err(FrameError::LengthMismatch(_, _))
+        ));
+        assert!(matches!(
+            CODEC.decode_frame::<ServerMessage>(&data, 1024),
+            Err(FrameError::TypeMismatch(_, _)) | Err(FrameError::CodecError(_))
+        ));
+        assert!(matches!(
+            FrameHeader::parse(b"GET / HTTP/1.1"),
+            Err(FrameError::InvalidMagic(_))
+        ));
+    }
+}
@@ -1,3 +1,19 @@
+//! Signaling messages exchanged by the server and the clients.
+//!
+//! # Protocol evolution
+//!
+//! New protocol revisions stay readable by the peers of the older ones:
+//!
+//! - new message types get new frame type ids, see `FramedMessage`,
+//!   and the frames of unknown types are skipped, see `FrameError::is_unknown_type`;
+//! - new variants of the fieldless enums are decoded as their `Unknown` variant;
+//! - new struct and variant fields are `Option` or `#[serde(default)]`
+//!   and unknown fields are ignored, this holds for the self-describing codecs
+//!   (`WireCodec::Json` and `WireCodec::Cbor`) only, the binary codecs encode fields
+//!   by position and require the peers to use the same revision of the changed messages.
+//!
+//! Frames captured from the previous revisions are decoded by the `tests/compat.rs` tests.
+
 #![warn(
     clippy::all,
     rust_2018_idioms,
@@ -10,14 +26,37 @@
     unused_results
 )]
 
+mod client;
+mod codec;
+mod discovery;
+mod error_catalog;
+mod frame;
+
+use core::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub use client::*;
+pub use codec::*;
+pub use discovery::*;
+pub use error_catalog::*;
+pub use frame::*;
+
+/// Randomly generated by the client, so that sessions of a reloaded page
+/// do not collide with its previous sessions still draining on the server.
+///
+/// Widened from `u32` sequential ids, so clients and servers built before that
+/// are not wire compatible and must be updated together.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
-pub struct SessionSenderId(pub u32);
+pub struct SessionSenderId(pub u64);
 
+/// Randomly generated by the client, see `SessionSenderId`.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
-pub struct SessionReceiverId(pub u32);
+pub struct SessionReceiverId(pub u64);
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct AckId(pub u32);
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct ChannelId(pub String);
@@ -25,6 +64,116 @@ pub struct ChannelId(pub String);
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct SessionDescription(pub String);
 
+/// Secret returned on a channel reservation, required to open the reserved channel.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ClaimToken(pub String);
+
+/// Random secret chosen by the channel sender when it opens the channel.
+///
+/// A sender reconnected to the server opens the channel again with the same token,
+/// the channel is then taken over from its previous, possibly half-open, connection.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ResumeToken(pub String);
+
+/// Password chosen by the channel sender, receivers join the channel only with the same one.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ChannelSecret(pub String);
+
+/// Name under which a client can be asked to open a channel, e.g. its identity key fingerprint.
+///
+/// The server does not verify it, the peers verify each other with signed session descriptions.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct PeerId(pub String);
+
+/// Channel request id chosen by the requesting client,
+/// the requested client sees another id assigned by the server.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ChannelRequestId(pub u64);
+
+/// Routing key of the binary data relayed by the server.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Topic(pub String);
+
+/// Topic of the JPEG video frames relayed by the headless senders, e.g. `iot-sender`.
+pub const JPEG_FRAME_TOPIC: &str = "video/jpeg";
+
+/// Public key of a peer used for sealed signaling.
+///
+/// When both peers exchanged their keys, `SessionDescription` and `IceCandidate::candidate`
+/// payloads are sealed end-to-end and start with `SEALED_PAYLOAD_PREFIX`,
+/// so the server relays them without being able to read or tamper with them.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct PublicKey(pub Vec<u8>);
+
+/// Network properties a peer advertises to the other peer of the channel,
+/// so that both sides can tune the candidates they send.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct PeerCapabilities {
+    /// The peer is deployed on a LAN, mDNS host candidates are not needed
+    /// between two such peers.
+    pub is_on_lan: bool,
+}
+
+/// The signaling server view of the source addresses of both peers of a channel.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct PeerNetwork {
+    /// Both peers connect from the same address or from private addresses,
+    /// so they are likely reachable with their host candidates only.
+    pub is_same_network: bool,
+}
+
+/// Prefix of the sealed signaling payloads.
+pub const SEALED_PAYLOAD_PREFIX: &str = "sealed:";
+
+/// Prefix of the session descriptions signed with a long-lived peer identity key.
+///
+/// Signed descriptions are formatted as `signed:<key>:<signature>:<sdp>`
+/// with the hex-encoded identity public key and signature,
+/// they are sealed after signing when sealed signaling is used.
+pub const SIGNED_PAYLOAD_PREFIX: &str = "signed:";
+
+/// Per-connection session metrics, optionally reported on close
+/// for operator-side aggregate statistics.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SessionSummary {
+    /// Time since the peer connection was created.
+    pub duration: Duration,
+    /// Time spent in each ICE connection state, in order of first occurrence.
+    pub state_durations: Vec<StateDuration>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub frames_sent: u64,
+    pub frames_received: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct StateDuration {
+    pub state: String,
+    pub duration: Duration,
+}
+
+/// Call setup milestone reported by the clients that opted in to telemetry.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum CallMilestone {
+    SignalingConnected,
+    OfferSent,
+    AnswerReceived,
+    IceConnected,
+    FirstFrameDecoded,
+    /// ICE failed before the connection was established.
+    SetupFailed,
+    /// Added by a newer protocol revision, see the crate documentation.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct MilestoneReport {
+    pub milestone: CallMilestone,
+    /// Time since the signaling connection or the peer connection was started.
+    pub elapsed: Duration,
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct IceCandidate {
     pub candidate: String,
@@ -32,10 +181,142 @@ pub struct IceCandidate {
     pub sdp_m_line_index: Option<u16>,
 }
 
+/// Open channel as listed to all connected clients.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ChannelInfo {
+    pub channel_id: ChannelId,
+    pub preview: Option<ChannelPreview>,
+    pub status: ChannelStatus,
+    /// Planned start time since the UNIX epoch, set by `ClientSenderMessage::ScheduleStart`.
+    pub scheduled_start: Option<Duration>,
+}
+
+/// Broadcast state of a listed channel.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ChannelStatus {
+    /// The channel has a scheduled start and its sender has not started the negotiation yet.
+    Scheduled,
+    Live,
+    /// The scheduled channel was closed, it is listed for a while for the upcoming broadcasts UIs.
+    Ended,
+    /// Added by a newer protocol revision, see the crate documentation.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Receiver that joined a channel, as reported to the channel sender.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ReceiverInfo {
+    /// Server-assigned id of the receiver connection, distinguishes rejoining receivers.
+    pub connection_id: u32,
+}
+
+/// Maximum size of `ChannelPreview::thumbnail` in bytes,
+/// the previews are sent to every client listing the open channels.
+pub const MAX_PREVIEW_THUMBNAIL_SIZE: usize = 32 * 1024;
+
+/// Preview of what the channel is broadcasting, periodically published by the channel sender.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ChannelPreview {
+    /// Small JPEG-encoded image of the current channel video frame,
+    /// the server drops the thumbnails larger than `MAX_PREVIEW_THUMBNAIL_SIZE`.
+    pub thumbnail: Option<Vec<u8>>,
+    pub caption: String,
+    /// Media the sender will capture when a receiver joins, set while the capture is deferred.
+    pub intended_media: Option<IntendedMedia>,
+    /// BCP 47 language tags of the captions the sender publishes to the joined receivers.
+    #[serde(default)]
+    pub caption_languages: Vec<String>,
+}
+
+/// Media kinds advertised by a sender before its local media is captured.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct IntendedMedia {
+    pub video: bool,
+    pub audio: bool,
+}
+
+/// Video quality requested by a receiver, e.g. on a limited bandwidth or a small viewport.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum QualityRequest {
+    LowerLayer,
+    HigherLayer,
+    /// Target bitrate in bits per second, `None` removes the limit.
+    TargetBitrate(Option<u32>),
+}
+
+/// Why the requested client declined a channel request, relayed to the requester.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ChannelRequestDeclineReason {
+    /// Declined by the user.
+    Declined,
+    /// Declined automatically because the user does not want to be disturbed.
+    DoNotDisturb,
+    /// Not answered before the ring timeout.
+    NoAnswer,
+    /// Added by a newer protocol revision, see the crate documentation.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Why a session ended, sent on close and relayed to the remote peer.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum CloseReason {
+    /// Closed by the local application.
+    LocalClosed,
+    /// Closed by the remote peer.
+    RemoteClosed,
+    /// Removed from the channel, e.g. by the channel sender or the server operator.
+    Kicked,
+    ServerShutdown,
+    /// Negotiation or session timed out.
+    Timeout,
+    /// Closed because of an error, codes are application-defined
+    /// except for `CloseReason::PROTOCOL_VIOLATION`.
+    Error(u16),
+}
+
+impl CloseReason {
+    /// The remote peer violated the signaling protocol.
+    pub const PROTOCOL_VIOLATION: Self = Self::Error(1);
+
+    /// Returns the reason as seen by the remote peer of the closed session.
+    pub fn to_remote(self) -> Self {
+        match self {
+            Self::LocalClosed => Self::RemoteClosed,
+            reason => reason,
+        }
+    }
+}
+
+/// Binary data of a client-server channel dropped by the server relay throughput caps.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ThrottleNotice {
+    pub scope: ThrottleScope,
+    /// Bytes dropped since the previous notice, counted once for each receiver.
+    pub dropped_bytes: u64,
+    /// Time until the exceeded burst allowance is refilled enough to relay the dropped data.
+    pub retry_after: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ThrottleScope {
+    /// The channel cap is exceeded, the data is dropped for all receivers.
+    Channel,
+    /// The cap of some receivers is exceeded, the data is dropped only for them.
+    Receivers,
+    /// Added by a newer protocol revision, see the crate documentation.
+    #[serde(other)]
+    Unknown,
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum NetworkMode {
     PeerToPeer,
     ClientServer,
+    /// Client-server channel which also echoes the sender binary data back to the sender,
+    /// e.g. for a single-client self-test and latency tests of the server relay path.
+    Loopback,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -48,6 +329,51 @@ pub enum ClientMessage {
         receiver_id: SessionReceiverId,
         message: ClientReceiverMessage,
     },
+    /// Requests the latest poster frame of the channel, e.g. for a channel list thumbnail.
+    RequestChannelSnapshot {
+        channel_id: ChannelId,
+        /// Required if the channel has a secret, as for `JoinChannel`.
+        #[serde(default)]
+        credential: Option<ChannelSecret>,
+    },
+    /// Reserves the channel id before the channel is opened, e.g. to share a join link
+    /// before the capture is started.
+    ReserveChannel { channel_id: ChannelId },
+    /// Call setup milestones batched by the clients that opted in to telemetry.
+    ReportMilestones(Vec<MilestoneReport>),
+    /// Stops the `ServerMessage::OpenChannelsChanged` updates of this client,
+    /// e.g. while a call is active and the channel list is not shown.
+    PauseChannelList,
+    /// Resumes the open channel list updates, the full list is sent at once.
+    ResumeChannelList,
+    /// Makes the client reachable by channel requests addressed to the peer id
+    /// until it disconnects.
+    RegisterPeer(PeerId),
+    /// Asks the registered peer to open a channel toward this client,
+    /// the outcome is reported with the same request id.
+    RequestChannel {
+        request_id: ChannelRequestId,
+        peer_id: PeerId,
+    },
+    /// Accepts the incoming channel request after opening the channel,
+    /// the requesting client is told to join it.
+    AcceptChannelRequest {
+        request_id: ChannelRequestId,
+        channel_id: ChannelId,
+    },
+    DeclineChannelRequest {
+        request_id: ChannelRequestId,
+        reason: ChannelRequestDeclineReason,
+    },
+    /// Message which delivery to the peer is confirmed with `ServerMessage::Ack`
+    /// or rejected with `ServerMessage::Nack`.
+    WithAck {
+        ack_id: AckId,
+        message: Box<ClientMessage>,
+    },
+    /// Keepalive answered with `ServerMessage::Pong`,
+    /// the server drops the clients that send nothing for its idle timeout.
+    Ping,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -55,26 +381,134 @@ pub enum ClientSenderMessage {
     OpenChannel {
         channel_id: ChannelId,
         network_mode: NetworkMode,
+        /// Required if the channel id is reserved.
+        claim_token: Option<ClaimToken>,
+        /// Required from the receivers to join the channel if set.
+        secret: Option<ChannelSecret>,
+        /// Lets the sender open the channel again after a reconnect, see `ResumeToken`.
+        #[serde(default)]
+        resume_token: Option<ResumeToken>,
     },
-    CloseChannel,
+    /// Closes the channel, the reason is relayed to the joined receivers.
+    CloseChannel(CloseReason),
+    /// Moves the open channel to a new id,
+    /// e.g. when the display name is derived from the evolving content.
+    /// The joined receivers and the open channel list are updated.
+    RenameChannel(ChannelId),
     SendOffer(SessionDescription),
+    /// Offer with new ICE credentials, previously sent candidates are no longer valid.
+    SendIceRestartOffer(SessionDescription),
     IceCandidate(IceCandidate),
     AllIceCandidatesSent,
+    /// Asks the receiver to re-send its candidates after they repeatedly failed to be added.
+    RequestIceCandidates,
     SendBinaryData(Vec<u8>),
+    /// Binary data relayed only to the receivers subscribed to the topic.
+    SendTopicData {
+        topic: Topic,
+        data: Vec<u8>,
+    },
+    /// Encoded image of the current channel video frame.
+    SendSnapshot(Vec<u8>),
+    PublishPreview(ChannelPreview),
+    /// Sets the planned start time since the UNIX epoch, the channel is listed as scheduled
+    /// until the sender sends its first offer.
+    ScheduleStart(Option<Duration>),
+    /// Enables sealed signaling, the key is relayed to the joined receiver.
+    PublishPublicKey(PublicKey),
+    /// Sent before `CloseChannel` by the senders that opted in to reporting.
+    ReportSessionSummary(SessionSummary),
+    /// The capabilities are stored and relayed to the joined receiver.
+    PublishCapabilities(PeerCapabilities),
+    /// Asks the server to compare the source addresses of the sender and the receiver,
+    /// the result is sent to both of them once the receiver is joined.
+    RequestPeerNetwork,
+    /// Answer to a renegotiation offer of the receiver.
+    SendAnswer(SessionDescription),
+    /// Starts the server-side recording of the channel,
+    /// the chunks are stored in the order they are sent until `StopRecording`.
+    StartRecording {
+        /// Container of the chunks, e.g. `video/webm;codecs=vp8,opus`.
+        mime_type: String,
+    },
+    /// `MediaRecorder` chunk of the started recording.
+    SendRecordingChunk(Vec<u8>),
+    StopRecording,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum ClientReceiverMessage {
-    JoinChannel { channel_id: ChannelId },
-    ExitChannel,
+    JoinChannel {
+        channel_id: ChannelId,
+        /// Checked against the channel secret, ignored if the channel has none.
+        credential: Option<ChannelSecret>,
+    },
+    /// Exits the channel, the reason is relayed to the channel sender.
+    ExitChannel(CloseReason),
     SendAnswer(SessionDescription),
     IceCandidate(IceCandidate),
     AllIceCandidatesSent,
+    /// Asks the sender to re-send its candidates after they repeatedly failed to be added.
+    RequestIceCandidates,
+    RequestQuality(QualityRequest),
+    RequestIceRestart,
+    /// Starts relaying of the sender data tagged with the topic.
+    SubscribeTopic(Topic),
+    UnsubscribeTopic(Topic),
+    /// Response to the sender public key, the key is relayed to the sender.
+    PublishPublicKey(PublicKey),
+    /// Sent before `ExitChannel` by the receivers that opted in to reporting.
+    ReportSessionSummary(SessionSummary),
+    /// The capabilities are relayed to the sender.
+    PublishCapabilities(PeerCapabilities),
+    /// Renegotiation offer of a connected receiver, e.g. when it adds a media stream.
+    ///
+    /// The receiver is the polite peer: it rolls back its offer if the sender
+    /// offers at the same time, while the sender ignores the colliding receiver offer.
+    SendOffer(SessionDescription),
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum ServerMessage {
-    OpenChannelIdsChanged(Vec<ChannelId>),
+    OpenChannelsChanged(Vec<ChannelInfo>),
+    ChannelSnapshot {
+        channel_id: ChannelId,
+        snapshot: Vec<u8>,
+    },
+    ChannelReserved {
+        channel_id: ChannelId,
+        claim_token: ClaimToken,
+        /// The reservation expires if the channel is not opened in this time.
+        valid_for: Duration,
+    },
+    ChannelReservationFailed {
+        channel_id: ChannelId,
+        reason: ReservationErrorMessage,
+    },
+    PeerRegistrationFailed {
+        peer_id: PeerId,
+        reason: ChannelRequestErrorMessage,
+    },
+    /// Another client asks this registered client to open a channel toward it,
+    /// `from` is the peer id registered by the requesting client if any.
+    ChannelRequested {
+        request_id: ChannelRequestId,
+        from: Option<PeerId>,
+    },
+    ChannelRequestAccepted {
+        request_id: ChannelRequestId,
+        channel_id: ChannelId,
+    },
+    ChannelRequestDeclined {
+        request_id: ChannelRequestId,
+        reason: ChannelRequestDeclineReason,
+    },
+    ChannelRequestFailed {
+        request_id: ChannelRequestId,
+        reason: ChannelRequestErrorMessage,
+    },
+    /// The server stops, the WebSocket is closed right after this message.
+    ServerShutdown,
     SenderMessage {
         sender_id: SessionSenderId,
         message: ServerSenderMessage,
@@ -83,15 +517,62 @@ pub enum ServerMessage {
         receiver_id: SessionReceiverId,
         message: ServerReceiverMessage,
     },
+    Ack(AckId),
+    Nack {
+        ack_id: AckId,
+        reason: NackReason,
+    },
+    Pong,
+    /// The preview of an open channel is republished by its sender,
+    /// the open channel list is not resent for the preview updates.
+    ChannelPreviewChanged {
+        channel_id: ChannelId,
+        preview: ChannelPreview,
+    },
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum ServerSenderMessage {
     OpenChannelSuccess,
+    /// A receiver joined the channel, sent before its answer is awaited.
+    ReceiverJoined {
+        receiver_info: ReceiverInfo,
+    },
+    /// The offer was relayed to a joined receiver and its answer is awaited.
+    OfferRelayed,
     ChannelAnswer(SessionDescription),
     IceCandidate(IceCandidate),
     AllIceCandidatesSent,
+    QualityRequested(QualityRequest),
+    IceRestartRequested,
+    /// The receiver asks to re-send the candidates of the current ICE session.
+    IceCandidatesRequested,
+    PeerPublicKey(PublicKey),
+    /// The receiver exited the channel, disconnected or did not answer in time.
+    ReceiverLeft(CloseReason),
+    ChannelRenamed(ChannelId),
+    /// The channel keeps its id, the new id is already used or reserved.
+    RenameChannelFailed(ServerSenderErrorMessage),
+    /// The relayed binary data exceeds the relay throughput caps and is partially dropped,
+    /// sent at most once a second while the sender keeps exceeding them.
+    Throttled(ThrottleNotice),
+    /// The sender binary data echoed back by a loopback channel.
+    BinaryDataEcho(Vec<u8>),
     Error(ServerSenderErrorMessage),
+    PeerCapabilities(PeerCapabilities),
+    /// Sent on join if requested by the sender.
+    PeerNetwork(PeerNetwork),
+    /// Renegotiation offer of the receiver, see `ClientReceiverMessage::SendOffer`.
+    ChannelOffer(SessionDescription),
+    RecordingStarted,
+    /// The recording is stopped by the sender, the stored chunks are kept.
+    RecordingStopped {
+        /// Stored bytes.
+        size: u64,
+    },
+    /// The recording could not be started or is stopped by the server,
+    /// the chunks stored before are kept.
+    RecordingFailed(RecordingErrorMessage),
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -100,30 +581,93 @@ pub enum ServerReceiverMessage {
     ChannelOffer(SessionDescription),
     IceCandidate(IceCandidate),
     AllIceCandidatesSent,
+    /// The sender asks to re-send the candidates of the current ICE session.
+    IceCandidatesRequested,
     BinaryData(Vec<u8>),
+    TopicData {
+        topic: Topic,
+        data: Vec<u8>,
+    },
+    /// The latest channel snapshot, sent on join before the media is connected.
+    ChannelSnapshot(Vec<u8>),
+    /// The sender public key, sent on join before the offer.
+    PeerPublicKey(PublicKey),
+    /// The channel was closed by its sender or because the sender disconnected.
+    ChannelClosed(CloseReason),
+    /// The channel is renamed by its sender, the session is not affected.
+    ChannelRenamed(ChannelId),
     Error(ServerReceiverErrorMessage),
+    /// The sender capabilities, sent on join before the offer.
+    PeerCapabilities(PeerCapabilities),
+    /// Sent on join before the offer if requested by the sender.
+    PeerNetwork(PeerNetwork),
+    /// Answer of the sender to the receiver renegotiation offer.
+    ChannelAnswer(SessionDescription),
 }
 
 #[allow(missing_copy_implementations)]
-#[derive(Clone, Debug, Deserialize, Eq, Error, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum ServerSenderErrorMessage {
-    #[error("session sender id `{}` is already used", 0.0)]
     SessionSenderIdIsAlreadyUsed,
-    #[error("session sender id `{}` is not exist", 0.0)]
     SessionSenderIdIsNotExist,
-    #[error("channel `{}` is already used", 0.0)]
     ChannelIdIsAlreadyUsed(ChannelId),
+    ChannelIsReserved(ChannelId),
 }
 
 #[allow(missing_copy_implementations)]
-#[derive(Clone, Debug, Deserialize, Eq, Error, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum ServerReceiverErrorMessage {
-    #[error("session receiver id `{}` is already used", 0.0)]
     SessionReceiverIdIsAlreadyUsed,
-    #[error("session receiver id `{}` is not exist", 0.0)]
     SessionReceiverIdIsNotExist,
-    #[error("channel `{}` is not exist", 0.0)]
     ChannelIsNotExist(ChannelId),
-    #[error("channel `{}` is already occupied", 0.0)]
     ChannelIsAlreadyOccupied(ChannelId),
+    NegotiationTimedOut(ChannelId),
+    /// The channel has a secret and the join credential does not match it.
+    InvalidChannelCredentials(ChannelId),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ReservationErrorMessage {
+    ChannelIsAlreadyOpen,
+    ChannelIsAlreadyReserved,
+    /// The client or the whole server holds the maximum number of reservations.
+    ReservationLimitIsReached,
+    /// Added by a newer protocol revision, see the crate documentation.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ChannelRequestErrorMessage {
+    PeerIdIsAlreadyUsed,
+    PeerIsNotFound,
+    RequestIsNotFound,
+    ChannelIsNotOpened,
+    /// Added by a newer protocol revision, see the crate documentation.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum RecordingErrorMessage {
+    RecordingIsDisabled,
+    RecordingIsAlreadyStarted,
+    RecordingIsNotStarted,
+    RecordingQuotaIsExceeded,
+    RecordingStorageFailed,
+    /// Added by a newer protocol revision, see the crate documentation.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Error, Hash, PartialEq, Serialize)]
+pub enum NackReason {
+    #[error("session is not exist")]
+    SessionIsNotExist,
+    #[error("peer is disconnected")]
+    PeerDisconnected,
+    #[error("message send to peer failed: {0}")]
+    PeerSendFailed(String),
+    #[error("acknowledged message can not be nested")]
+    NestedAck,
 }
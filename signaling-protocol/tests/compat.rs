@@ -0,0 +1,293 @@
+//! Frames of the older and the newer protocol revisions are still decoded,
+//! see the protocol evolution rules in the crate documentation.
+
+use std::fmt::Debug;
+use std::path::Path;
+
+use serde::Serialize;
+use signaling_protocol::{
+    ChannelId, ChannelInfo, ChannelSecret, ChannelStatus, ClientMessage, ClientReceiverMessage,
+    ClientSenderMessage, FrameHeader, FramedMessage, NetworkMode, ReservationErrorMessage,
+    ServerMessage, ServerReceiverErrorMessage, ServerReceiverMessage, SessionReceiverId,
+    SessionSenderId, WireCodec,
+};
+
+/// Frames captured from the revision that added the keepalive pings,
+/// the last one before the channel secrets.
+const KEEPALIVE_REVISION: &str = "tests/frames/keepalive";
+
+const CODECS: [WireCodec; 4] = [
+    WireCodec::Bincode,
+    WireCodec::Json,
+    WireCodec::Postcard,
+    WireCodec::Cbor,
+];
+
+const SELF_DESCRIBING_CODECS: [WireCodec; 2] = [WireCodec::Json, WireCodec::Cbor];
+
+fn channel_id() -> ChannelId {
+    ChannelId("room".to_owned())
+}
+
+/// Checks the frames captured with every codec, the frames of the codecs
+/// not enabled by the crate features are skipped.
+fn assert_captured<T: FramedMessage + Debug + PartialEq>(revision: &str, name: &str, expected: T) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(revision);
+    let mut captured = 0;
+    for codec in CODECS.iter().copied() {
+        let path = dir.join(format!("{}.{}", name, codec.name()));
+        let frame = match std::fs::read(&path) {
+            Ok(frame) => frame,
+            Err(_) => continue,
+        };
+        captured += 1;
+        if codec.is_supported() {
+            let message: T = codec
+                .decode_frame(&frame, u32::MAX)
+                .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+            assert_eq!(message, expected, "{}", path.display());
+        }
+    }
+    assert!(captured > 0, "no frames captured for {}", name);
+}
+
+/// Frame of a message encoded as a newer protocol revision would encode it.
+fn newer_frame<T: Serialize>(codec: WireCodec, type_id: u8, message: &T) -> Vec<u8> {
+    let payload = codec.encode(message).unwrap();
+    let mut frame = FrameHeader::new(type_id, payload.len() as u32)
+        .to_bytes()
+        .to_vec();
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+#[test]
+fn keepalive_revision_frames_are_decoded() {
+    assert_captured(
+        KEEPALIVE_REVISION,
+        "client_open_channel",
+        ClientMessage::SenderMessage {
+            sender_id: SessionSenderId(1),
+            message: ClientSenderMessage::OpenChannel {
+                channel_id: channel_id(),
+                network_mode: NetworkMode::PeerToPeer,
+                claim_token: None,
+                secret: None,
+                resume_token: None,
+            },
+        },
+    );
+    assert_captured(
+        KEEPALIVE_REVISION,
+        "client_join_channel",
+        ClientMessage::ReceiverMessage {
+            receiver_id: SessionReceiverId(2),
+            message: ClientReceiverMessage::JoinChannel {
+                channel_id: channel_id(),
+                credential: None,
+            },
+        },
+    );
+    assert_captured(KEEPALIVE_REVISION, "client_ping", ClientMessage::Ping);
+    assert_captured(
+        KEEPALIVE_REVISION,
+        "server_open_channels_changed",
+        ServerMessage::OpenChannelsChanged(vec![ChannelInfo {
+            channel_id: channel_id(),
+            preview: None,
+            status: ChannelStatus::Live,
+            scheduled_start: None,
+        }]),
+    );
+    assert_captured(
+        KEEPALIVE_REVISION,
+        "server_channel_is_not_exist",
+        ServerMessage::ReceiverMessage {
+            receiver_id: SessionReceiverId(2),
+            message: ServerReceiverMessage::Error(ServerReceiverErrorMessage::ChannelIsNotExist(
+                channel_id(),
+            )),
+        },
+    );
+    assert_captured(
+        KEEPALIVE_REVISION,
+        "server_channel_reservation_failed",
+        ServerMessage::ChannelReservationFailed {
+            channel_id: channel_id(),
+            reason: ReservationErrorMessage::ChannelIsAlreadyOpen,
+        },
+    );
+}
+
+#[test]
+fn newer_message_types_are_skipped() {
+    for codec in CODECS.iter().copied().filter(|codec| codec.is_supported()) {
+        let frame = newer_frame(codec, ClientMessage::TYPE_COUNT, &());
+        let err = codec
+            .decode_frame::<ClientMessage>(&frame, u32::MAX)
+            .unwrap_err();
+        assert!(err.is_unknown_type(), "{}: {}", codec.name(), err);
+
+        let frame = newer_frame(codec, ServerMessage::TYPE_COUNT, &());
+        let err = codec
+            .decode_frame::<ServerMessage>(&frame, u32::MAX)
+            .unwrap_err();
+        assert!(err.is_unknown_type(), "{}: {}", codec.name(), err);
+    }
+}
+
+#[test]
+fn newer_fieldless_enum_variants_are_decoded_as_unknown() {
+    // Variants are encoded by name or by position, the preceding ones are never encoded here.
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum NewerServerMessage {
+        OpenChannelsChanged,
+        ChannelSnapshot,
+        ChannelReserved,
+        ChannelReservationFailed {
+            channel_id: ChannelId,
+            reason: NewerReservationErrorMessage,
+        },
+    }
+
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum NewerReservationErrorMessage {
+        ChannelIsAlreadyOpen,
+        ChannelIsAlreadyReserved,
+        ReservationLimitIsReached,
+        ChannelIdIsTooLong,
+    }
+
+    let newer = NewerServerMessage::ChannelReservationFailed {
+        channel_id: channel_id(),
+        reason: NewerReservationErrorMessage::ChannelIdIsTooLong,
+    };
+    for codec in CODECS.iter().copied().filter(|codec| codec.is_supported()) {
+        let frame = newer_frame(codec, 3, &newer);
+        assert_eq!(
+            codec
+                .decode_frame::<ServerMessage>(&frame, u32::MAX)
+                .unwrap(),
+            ServerMessage::ChannelReservationFailed {
+                channel_id: channel_id(),
+                reason: ReservationErrorMessage::Unknown,
+            },
+            "{}",
+            codec.name()
+        );
+    }
+}
+
+#[test]
+fn newer_fields_are_ignored_by_self_describing_codecs() {
+    #[derive(Serialize)]
+    enum NewerClientMessage {
+        SenderMessage {
+            sender_id: SessionSenderId,
+            message: NewerClientSenderMessage,
+        },
+    }
+
+    #[derive(Serialize)]
+    enum NewerClientSenderMessage {
+        OpenChannel {
+            channel_id: ChannelId,
+            network_mode: NetworkMode,
+            secret: Option<ChannelSecret>,
+            max_receivers: u32,
+        },
+    }
+
+    let newer = NewerClientMessage::SenderMessage {
+        sender_id: SessionSenderId(1),
+        message: NewerClientSenderMessage::OpenChannel {
+            channel_id: channel_id(),
+            network_mode: NetworkMode::ClientServer,
+            secret: Some(ChannelSecret("secret".to_owned())),
+            max_receivers: 4,
+        },
+    };
+    for codec in SELF_DESCRIBING_CODECS
+        .iter()
+        .copied()
+        .filter(|codec| codec.is_supported())
+    {
+        let frame = newer_frame(codec, 0, &newer);
+        assert_eq!(
+            codec
+                .decode_frame::<ClientMessage>(&frame, u32::MAX)
+                .unwrap(),
+            ClientMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ClientSenderMessage::OpenChannel {
+                    channel_id: channel_id(),
+                    network_mode: NetworkMode::ClientServer,
+                    claim_token: None,
+                    secret: Some(ChannelSecret("secret".to_owned())),
+                    resume_token: None,
+                },
+            },
+            "{}",
+            codec.name()
+        );
+    }
+}
+
+#[test]
+fn older_previews_without_caption_languages_are_decoded() {
+    use signaling_protocol::ChannelPreview;
+
+    #[derive(Serialize)]
+    enum OlderClientMessage {
+        SenderMessage {
+            sender_id: SessionSenderId,
+            message: OlderClientSenderMessage,
+        },
+    }
+
+    #[derive(Serialize)]
+    enum OlderClientSenderMessage {
+        PublishPreview(OlderChannelPreview),
+    }
+
+    #[derive(Serialize)]
+    struct OlderChannelPreview {
+        thumbnail: Option<Vec<u8>>,
+        caption: String,
+        intended_media: Option<()>,
+    }
+
+    let older = OlderClientMessage::SenderMessage {
+        sender_id: SessionSenderId(1),
+        message: OlderClientSenderMessage::PublishPreview(OlderChannelPreview {
+            thumbnail: None,
+            caption: "lecture".to_owned(),
+            intended_media: None,
+        }),
+    };
+    for codec in SELF_DESCRIBING_CODECS
+        .iter()
+        .copied()
+        .filter(|codec| codec.is_supported())
+    {
+        let frame = newer_frame(codec, 0, &older);
+        assert_eq!(
+            codec
+                .decode_frame::<ClientMessage>(&frame, u32::MAX)
+                .unwrap(),
+            ClientMessage::SenderMessage {
+                sender_id: SessionSenderId(1),
+                message: ClientSenderMessage::PublishPreview(ChannelPreview {
+                    thumbnail: None,
+                    caption: "lecture".to_owned(),
+                    intended_media: None,
+                    caption_languages: Vec::new(),
+                }),
+            },
+            "{}",
+            codec.name()
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+use std::collections::VecDeque;
+
+use signaling_protocol::IceCandidate;
+
+/// Delay before a failed remote candidate is added again,
+/// e.g. when it arrived before the remote description was applied.
+pub const ICE_CANDIDATE_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// Number of retries before the candidates are requested from the peer again.
+pub const MAX_ICE_CANDIDATE_RETRIES: u32 = 4;
+
+/// Buffers the remote candidates received before the remote description,
+/// the engines reject such candidates.
+#[derive(Debug, Default)]
+pub struct RemoteCandidates {
+    has_remote_description: Cell<bool>,
+    buffered: RefCell<Vec<IceCandidate>>,
+}
+
+impl RemoteCandidates {
+    /// Returns the candidate if it can be added right away, buffers it otherwise.
+    pub fn on_received(&self, ice_candidate: IceCandidate) -> Option<IceCandidate> {
+        if self.has_remote_description.get() {
+            Some(ice_candidate)
+        } else {
+            self.buffered.borrow_mut().push(ice_candidate);
+            None
+        }
+    }
+
+    /// Returns the buffered candidates in the received order.
+    pub fn on_remote_description(&self) -> Vec<IceCandidate> {
+        self.has_remote_description.set(true);
+        self.buffered.replace(Vec::new())
+    }
+
+    pub fn has_remote_description(&self) -> bool {
+        self.has_remote_description.get()
+    }
+
+    /// Drops the buffered candidates, called when the connection is closed.
+    pub fn clear(&self) {
+        self.has_remote_description.set(false);
+        self.buffered.borrow_mut().clear();
+    }
+}
+
+/// Locally gathered candidate or the end of the gathering.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LocalCandidate {
+    IceCandidate(IceCandidate),
+    AllSent,
+}
+
+/// Buffers the local candidates gathered before the server confirmed the channel,
+/// the server drops the candidates of the channels it does not know yet.
+#[derive(Debug, Default)]
+pub struct LocalCandidates {
+    is_confirmed: Cell<bool>,
+    buffered: RefCell<VecDeque<LocalCandidate>>,
+}
+
+impl LocalCandidates {
+    /// Returns the candidate if it can be sent right away, buffers it otherwise.
+    pub fn on_gathered(&self, candidate: LocalCandidate) -> Option<LocalCandidate> {
+        if self.is_confirmed.get() {
+            Some(candidate)
+        } else {
+            self.buffered.borrow_mut().push_back(candidate);
+            None
+        }
+    }
+
+    /// Returns the next buffered candidate to be sent once the channel is confirmed.
+    ///
+    /// The candidates gathered while the buffered ones are being sent are buffered too,
+    /// so the channel is confirmed only when the buffer is drained, which keeps the order.
+    pub fn release_next(&self) -> Option<LocalCandidate> {
+        let candidate = self.buffered.borrow_mut().pop_front();
+        if candidate.is_none() {
+            self.is_confirmed.set(true);
+        }
+        candidate
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.is_confirmed.get()
+    }
+
+    /// Buffers the candidates again until the channel is confirmed,
+    /// called when it is opened or joined again.
+    pub fn embargo(&self) {
+        self.is_confirmed.set(false);
+    }
+}
+
+/// IP family of the local candidates to be tried first.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum IpFamilyPreference {
+    /// The priorities assigned by the engine are kept.
+    #[default]
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+/// Prioritization of the local candidates sent to the remote peer,
+/// e.g. for the controlled network deployments with a known working IP family.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CandidatePreference {
+    pub ip_family: IpFamilyPreference,
+    /// The peer is deployed on a LAN, its mDNS host candidates are not sent
+    /// to the remote peers deployed on a LAN too, the resolution of them only delays
+    /// the connection when the private addresses are reachable.
+    pub is_on_lan: bool,
+    /// Only the host candidates are sent, e.g. for the LAN-only deployments
+    /// without STUN and TURN servers.
+    pub is_host_only: bool,
+}
+
+impl CandidatePreference {
+    /// Returns the candidate with the priority adjusted to the preferred IP family
+    /// or `None` if it is not sent to the remote peer.
+    pub fn apply(
+        &self,
+        ice_candidate: IceCandidate,
+        is_remote_on_lan: bool,
+    ) -> Option<IceCandidate> {
+        // `candidate:<foundation> <component> <transport> <priority> <address> <port> typ <type>`
+        let fields: Vec<&str> = ice_candidate.candidate.split(' ').collect();
+        if self.is_host_only {
+            let typ = fields.iter().skip_while(|field| **field != "typ").nth(1);
+            if typ.is_some_and(|typ| *typ != "host") {
+                return None;
+            }
+        }
+        let (priority, address) = match (fields.get(3), fields.get(4)) {
+            (Some(priority), Some(address)) => match priority.parse::<u32>() {
+                Ok(priority) => (priority, *address),
+                Err(_) => return Some(ice_candidate),
+            },
+            _ => return Some(ice_candidate),
+        };
+
+        if address.ends_with(".local") {
+            return if self.is_on_lan && is_remote_on_lan {
+                None
+            } else {
+                Some(ice_candidate)
+            };
+        }
+
+        let is_preferred = match self.ip_family {
+            IpFamilyPreference::Any => return Some(ice_candidate),
+            IpFamilyPreference::Ipv4 => !address.contains(':'),
+            IpFamilyPreference::Ipv6 => address.contains(':'),
+        };
+        // The local preference is in the middle 16 bits of the priority (RFC 8445),
+        // its upper bit puts the preferred family first and the engine order is kept within it.
+        let local_preference =
+            ((priority >> 8) & 0xffff) >> 1 | if is_preferred { 0x8000 } else { 0 };
+        let priority = (priority & 0xff00_00ff) | (local_preference << 8);
+        let priority = priority.to_string();
+
+        let mut fields = fields;
+        fields[3] = &priority;
+        Some(IceCandidate {
+            candidate: fields.join(" "),
+            ..ice_candidate
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IceCandidateRecoveryAction {
+    /// Ask the peer to re-send its candidates.
+    RequestResend,
+    /// The candidate was received before the re-send request, its copy is awaited.
+    AwaitResend,
+    /// The candidate failed even after the re-send request.
+    Fail,
+}
+
+/// Tracks the remote candidates that could not be added after all retries.
+///
+/// Candidates are re-requested from the peer once per remote description,
+/// so a persistently invalid candidate fails instead of being requested in a loop.
+#[derive(Debug, Default)]
+pub struct IceCandidateRecovery {
+    received_count: Cell<u32>,
+    resend_requested_at: Cell<Option<u32>>,
+}
+
+impl IceCandidateRecovery {
+    /// Returns the sequence number of the received candidate.
+    pub fn on_received(&self) -> u32 {
+        let sequence = self.received_count.get().wrapping_add(1);
+        self.received_count.set(sequence);
+        sequence
+    }
+
+    pub fn on_retries_exhausted(&self, sequence: u32) -> IceCandidateRecoveryAction {
+        match self.resend_requested_at.get() {
+            None => {
+                self.resend_requested_at
+                    .set(Some(self.received_count.get()));
+                IceCandidateRecoveryAction::RequestResend
+            }
+            Some(requested_at) if sequence <= requested_at => {
+                IceCandidateRecoveryAction::AwaitResend
+            }
+            Some(_) => IceCandidateRecoveryAction::Fail,
+        }
+    }
+
+    /// Allows a new re-send request, called when a new remote description is applied.
+    pub fn reset(&self) {
+        self.resend_requested_at.set(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(index: u16) -> IceCandidate {
+        IceCandidate {
+            candidate: format!("candidate:{}", index),
+            sdp_mid: None,
+            sdp_m_line_index: Some(index),
+        }
+    }
+
+    #[test]
+    fn candidates_are_buffered_until_remote_description() {
+        let candidates = RemoteCandidates::default();
+
+        assert_eq!(candidates.on_received(candidate(0)), None);
+        assert_eq!(candidates.on_received(candidate(1)), None);
+        assert_eq!(
+            candidates.on_remote_description(),
+            vec![candidate(0), candidate(1)]
+        );
+        assert_eq!(candidates.on_received(candidate(2)), Some(candidate(2)));
+        assert_eq!(candidates.on_remote_description(), vec![]);
+    }
+
+    #[test]
+    fn local_candidates_are_buffered_until_confirmed() {
+        let candidates = LocalCandidates::default();
+
+        assert_eq!(
+            candidates.on_gathered(LocalCandidate::IceCandidate(candidate(0))),
+            None
+        );
+        assert_eq!(
+            candidates.release_next(),
+            Some(LocalCandidate::IceCandidate(candidate(0)))
+        );
+        assert!(!candidates.is_confirmed());
+        assert_eq!(candidates.on_gathered(LocalCandidate::AllSent), None);
+        assert_eq!(candidates.release_next(), Some(LocalCandidate::AllSent));
+        assert_eq!(candidates.release_next(), None);
+        assert!(candidates.is_confirmed());
+        assert_eq!(
+            candidates.on_gathered(LocalCandidate::IceCandidate(candidate(1))),
+            Some(LocalCandidate::IceCandidate(candidate(1)))
+        );
+
+        candidates.embargo();
+        assert_eq!(
+            candidates.on_gathered(LocalCandidate::IceCandidate(candidate(2))),
+            None
+        );
+    }
+
+    fn gathered(address: &str, priority: u32) -> IceCandidate {
+        IceCandidate {
+            candidate: format!("candidate:1 1 udp {} {} 9 typ host", priority, address),
+            sdp_mid: Some("0".to_owned()),
+            sdp_m_line_index: Some(0),
+        }
+    }
+
+    #[test]
+    fn preferred_ip_family_is_prioritized() {
+        let preference = CandidatePreference {
+            ip_family: IpFamilyPreference::Ipv4,
+            is_on_lan: false,
+            is_host_only: false,
+        };
+        let priority = (126 << 24) | (0xff7f << 8) | 255;
+
+        assert_eq!(
+            preference.apply(gathered("10.0.0.1", priority), false),
+            Some(gathered("10.0.0.1", (126 << 24) | (0xffbf << 8) | 255))
+        );
+        assert_eq!(
+            preference.apply(gathered("fd00::1", priority), false),
+            Some(gathered("fd00::1", (126 << 24) | (0x7fbf << 8) | 255))
+        );
+        assert_eq!(
+            CandidatePreference::default().apply(gathered("fd00::1", priority), false),
+            Some(gathered("fd00::1", priority))
+        );
+    }
+
+    #[test]
+    fn mdns_candidates_are_suppressed_between_lan_peers() {
+        let preference = CandidatePreference {
+            ip_family: IpFamilyPreference::Ipv6,
+            is_on_lan: true,
+            is_host_only: false,
+        };
+        let mdns = gathered("4b2c9f1a-0d3e-4c6b-9a5f-2e7d8c1b0a9f.local", 1);
+
+        assert_eq!(preference.apply(mdns.clone(), true), None);
+        assert_eq!(preference.apply(mdns.clone(), false), Some(mdns));
+    }
+
+    #[test]
+    fn only_host_candidates_are_sent_if_host_only() {
+        let preference = CandidatePreference {
+            is_host_only: true,
+            ..CandidatePreference::default()
+        };
+        let host = gathered("192.168.1.2", 1);
+        let srflx = IceCandidate {
+            candidate: "candidate:2 1 udp 1 203.0.113.1 9 typ srflx raddr 0.0.0.0 rport 0"
+                .to_owned(),
+            ..host.clone()
+        };
+
+        assert_eq!(preference.apply(host.clone(), false), Some(host));
+        assert_eq!(preference.apply(srflx.clone(), false), None);
+        assert_eq!(
+            CandidatePreference::default().apply(srflx.clone(), false),
+            Some(srflx)
+        );
+    }
+
+    #[test]
+    fn resend_is_requested_once_per_remote_description() {
+        let recovery = IceCandidateRecovery::default();
+
+        let first = recovery.on_received();
+        let second = recovery.on_received();
+        assert_eq!(
+            recovery.on_retries_exhausted(first),
+            IceCandidateRecoveryAction::RequestResend
+        );
+        assert_eq!(
+            recovery.on_retries_exhausted(second),
+            IceCandidateRecoveryAction::AwaitResend
+        );
+
+        let resent = recovery.on_received();
+        assert_eq!(
+            recovery.on_retries_exhausted(resent),
+            IceCandidateRecoveryAction::Fail
+        );
+
+        recovery.reset();
+        assert_eq!(
+            recovery.on_retries_exhausted(resent),
+            IceCandidateRecoveryAction::RequestResend
+        );
+    }
+}
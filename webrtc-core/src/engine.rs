@@ -0,0 +1,31 @@
+use core::fmt::Debug;
+
+use signaling_protocol::{IceCandidate, SessionDescription};
+
+use crate::SdpType;
+
+/// Peer connection implementation, e.g. the browser `RTCPeerConnection`.
+///
+/// Engines are driven from a single thread, so the returned futures are not required to be `Send`.
+#[allow(async_fn_in_trait)]
+pub trait RtcEngine {
+    type Error: Debug;
+
+    async fn create_offer(&self, is_ice_restart: bool) -> Result<SessionDescription, Self::Error>;
+
+    async fn create_answer(&self) -> Result<SessionDescription, Self::Error>;
+
+    async fn set_local_description(
+        &self,
+        sdp_type: SdpType,
+        sdp: &SessionDescription,
+    ) -> Result<(), Self::Error>;
+
+    async fn set_remote_description(
+        &self,
+        sdp_type: SdpType,
+        sdp: &SessionDescription,
+    ) -> Result<(), Self::Error>;
+
+    async fn add_ice_candidate(&self, ice_candidate: &IceCandidate) -> Result<(), Self::Error>;
+}
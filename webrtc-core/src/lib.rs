@@ -0,0 +1,38 @@
+#![warn(
+    clippy::all,
+    rust_2018_idioms,
+    missing_copy_implementations,
+    missing_debug_implementations,
+    single_use_lifetimes,
+    trivial_casts,
+    unused_import_braces,
+    unused_qualifications,
+    unused_results
+)]
+
+//! Platform-agnostic negotiation logic shared by the browser and native clients.
+//!
+//! The RTC engine and the signaling transport are abstracted by traits,
+//! so the message sequencing and candidate buffering are implemented and tested once.
+
+mod candidates;
+mod engine;
+mod negotiation;
+mod sdp;
+mod teardown;
+mod transport;
+
+pub use candidates::{
+    CandidatePreference, IceCandidateRecovery, IceCandidateRecoveryAction, IpFamilyPreference,
+    LocalCandidate, LocalCandidates, RemoteCandidates, ICE_CANDIDATE_RETRY_INTERVAL,
+    MAX_ICE_CANDIDATE_RETRIES,
+};
+pub use engine::RtcEngine;
+pub use negotiation::{
+    Negotiation, NegotiationError, NegotiationRole, NegotiationState, NegotiationStep, Negotiator,
+    NegotiatorError, OfferCollision, PerfectNegotiation,
+};
+pub use sdp::{sdp_ice_candidates, SdpType};
+pub use signaling_protocol;
+pub use teardown::SessionTeardown;
+pub use transport::SignalingTransport;
@@ -0,0 +1,672 @@
+use core::cell::Cell;
+use core::fmt::Debug;
+
+use signaling_protocol::{IceCandidate, SessionDescription};
+use thiserror::Error;
+
+use crate::{RemoteCandidates, RtcEngine, SdpType, SignalingTransport};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NegotiationState {
+    Stable,
+    HaveLocalOffer,
+    HaveRemoteOffer,
+    Closed,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NegotiationStep {
+    LocalOffer,
+    RemoteOffer,
+    LocalAnswer,
+    RemoteAnswer,
+    /// The pending local offer is discarded in favor of the remote one.
+    LocalRollback,
+}
+
+/// Role of the peer when both peers send their offers at once (glare),
+/// the polite peer rolls back its offer, the impolite peer ignores the remote offer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NegotiationRole {
+    Polite,
+    Impolite,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OfferCollision {
+    /// No local offer is pending, the remote offer is answered.
+    None,
+    /// The local offer is rolled back, then the remote offer is answered.
+    Rollback,
+    /// The remote offer is dropped, the remote peer answers the local offer instead.
+    Ignore,
+}
+
+/// Offer/answer state machine without any IO.
+///
+/// Steps are validated before the engine is called and applied after it succeeds,
+/// so a failed engine call leaves the state unchanged.
+#[derive(Debug)]
+pub struct Negotiation {
+    state: Cell<NegotiationState>,
+    remote_candidates: RemoteCandidates,
+}
+
+impl Negotiation {
+    pub fn new() -> Self {
+        Self {
+            state: Cell::new(NegotiationState::Stable),
+            remote_candidates: RemoteCandidates::default(),
+        }
+    }
+
+    pub fn state(&self) -> NegotiationState {
+        self.state.get()
+    }
+
+    pub fn remote_candidates(&self) -> &RemoteCandidates {
+        &self.remote_candidates
+    }
+
+    /// Returns the state the step leads to without applying it.
+    pub fn next_state(&self, step: NegotiationStep) -> Result<NegotiationState, NegotiationError> {
+        use NegotiationState as State;
+        use NegotiationStep as Step;
+
+        let state = self.state.get();
+        match (state, step) {
+            (State::Closed, _) => Err(NegotiationError::Closed),
+            // A pending offer may be replaced, e.g. by an ICE restart offer.
+            (State::Stable, Step::LocalOffer) | (State::HaveLocalOffer, Step::LocalOffer) => {
+                Ok(State::HaveLocalOffer)
+            }
+            (State::Stable, Step::RemoteOffer) | (State::HaveRemoteOffer, Step::RemoteOffer) => {
+                Ok(State::HaveRemoteOffer)
+            }
+            (State::HaveRemoteOffer, Step::LocalAnswer)
+            | (State::HaveLocalOffer, Step::RemoteAnswer)
+            | (State::HaveLocalOffer, Step::LocalRollback) => Ok(State::Stable),
+            (state, step) => Err(NegotiationError::UnexpectedStep { state, step }),
+        }
+    }
+
+    pub fn apply(&self, step: NegotiationStep) -> Result<NegotiationState, NegotiationError> {
+        let state = self.next_state(step)?;
+        self.state.set(state);
+        Ok(state)
+    }
+
+    pub fn close(&self) {
+        self.state.set(NegotiationState::Closed);
+        self.remote_candidates.clear();
+    }
+}
+
+impl Default for Negotiation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Perfect negotiation pattern, lets both peers renegotiate at any time,
+/// e.g. when each of them adds a media stream after the connection is established.
+#[derive(Debug)]
+pub struct PerfectNegotiation {
+    role: NegotiationRole,
+    is_making_offer: Cell<bool>,
+    is_ignoring_offer: Cell<bool>,
+}
+
+impl PerfectNegotiation {
+    pub fn new(role: NegotiationRole) -> Self {
+        Self {
+            role,
+            is_making_offer: Cell::new(false),
+            is_ignoring_offer: Cell::new(false),
+        }
+    }
+
+    pub fn role(&self) -> NegotiationRole {
+        self.role
+    }
+
+    /// Set while the local offer is created and until it is set as the local description,
+    /// the offers received meanwhile collide with it.
+    pub fn set_making_offer(&self, is_making_offer: bool) {
+        self.is_making_offer.set(is_making_offer)
+    }
+
+    pub fn is_making_offer(&self) -> bool {
+        self.is_making_offer.get()
+    }
+
+    /// Returns how the remote offer received in the given state is handled.
+    pub fn on_remote_offer(&self, state: NegotiationState) -> OfferCollision {
+        let is_colliding = self.is_making_offer.get() || state == NegotiationState::HaveLocalOffer;
+        let collision = match (is_colliding, self.role) {
+            (false, _) => OfferCollision::None,
+            (true, NegotiationRole::Polite) => OfferCollision::Rollback,
+            (true, NegotiationRole::Impolite) => OfferCollision::Ignore,
+        };
+        self.is_ignoring_offer
+            .set(collision == OfferCollision::Ignore);
+        collision
+    }
+
+    /// Returns `true` since the last remote offer is ignored,
+    /// the failures to add its candidates are expected then.
+    pub fn is_ignoring_offer(&self) -> bool {
+        self.is_ignoring_offer.get()
+    }
+}
+
+/// Drives the negotiation with the given engine and transport.
+#[derive(Debug)]
+pub struct Negotiator<E, T> {
+    engine: E,
+    transport: T,
+    negotiation: Negotiation,
+    /// Colliding offers are rejected without it.
+    perfect_negotiation: Option<PerfectNegotiation>,
+}
+
+impl<E: RtcEngine, T: SignalingTransport> Negotiator<E, T> {
+    pub fn new(engine: E, transport: T) -> Self {
+        Self {
+            engine,
+            transport,
+            negotiation: Negotiation::new(),
+            perfect_negotiation: None,
+        }
+    }
+
+    /// Negotiator that resolves the colliding offers as the peer of the given role.
+    pub fn with_role(engine: E, transport: T, role: NegotiationRole) -> Self {
+        Self {
+            perfect_negotiation: Some(PerfectNegotiation::new(role)),
+            ..Self::new(engine, transport)
+        }
+    }
+
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn state(&self) -> NegotiationState {
+        self.negotiation.state()
+    }
+
+    pub async fn send_offer(
+        &self,
+        is_ice_restart: bool,
+    ) -> Result<(), NegotiatorError<E::Error, T::Error>> {
+        let _: NegotiationState = self.negotiation.next_state(NegotiationStep::LocalOffer)?;
+        if let Some(perfect_negotiation) = &self.perfect_negotiation {
+            perfect_negotiation.set_making_offer(true);
+        }
+        let result = self.set_local_offer(is_ice_restart).await;
+        if let Some(perfect_negotiation) = &self.perfect_negotiation {
+            perfect_negotiation.set_making_offer(false);
+        }
+        let sdp = result?;
+        self.transport
+            .send_offer(sdp, is_ice_restart)
+            .await
+            .map_err(NegotiatorError::Transport)
+    }
+
+    pub async fn on_remote_offer(
+        &self,
+        sdp: SessionDescription,
+    ) -> Result<(), NegotiatorError<E::Error, T::Error>> {
+        if let Some(perfect_negotiation) = &self.perfect_negotiation {
+            match perfect_negotiation.on_remote_offer(self.negotiation.state()) {
+                OfferCollision::None => {}
+                OfferCollision::Rollback => self.rollback().await?,
+                OfferCollision::Ignore => return Ok(()),
+            }
+        }
+        let _: NegotiationState = self.negotiation.next_state(NegotiationStep::RemoteOffer)?;
+        self.engine
+            .set_remote_description(SdpType::Offer, &sdp)
+            .await
+            .map_err(NegotiatorError::Engine)?;
+        let _: NegotiationState = self.negotiation.apply(NegotiationStep::RemoteOffer)?;
+        self.add_buffered_ice_candidates().await?;
+
+        let sdp = self
+            .engine
+            .create_answer()
+            .await
+            .map_err(NegotiatorError::Engine)?;
+        self.engine
+            .set_local_description(SdpType::Answer, &sdp)
+            .await
+            .map_err(NegotiatorError::Engine)?;
+        let _: NegotiationState = self.negotiation.apply(NegotiationStep::LocalAnswer)?;
+        self.transport
+            .send_answer(sdp)
+            .await
+            .map_err(NegotiatorError::Transport)
+    }
+
+    pub async fn on_remote_answer(
+        &self,
+        sdp: SessionDescription,
+    ) -> Result<(), NegotiatorError<E::Error, T::Error>> {
+        let _: NegotiationState = self.negotiation.next_state(NegotiationStep::RemoteAnswer)?;
+        self.engine
+            .set_remote_description(SdpType::Answer, &sdp)
+            .await
+            .map_err(NegotiatorError::Engine)?;
+        let _: NegotiationState = self.negotiation.apply(NegotiationStep::RemoteAnswer)?;
+        self.add_buffered_ice_candidates().await
+    }
+
+    /// Adds the remote candidate, it is buffered until the remote description is applied.
+    pub async fn on_remote_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<(), NegotiatorError<E::Error, T::Error>> {
+        if self.negotiation.state() == NegotiationState::Closed {
+            return Err(NegotiationError::Closed.into());
+        }
+        match self
+            .negotiation
+            .remote_candidates()
+            .on_received(ice_candidate)
+        {
+            Some(ice_candidate) => self
+                .engine
+                .add_ice_candidate(&ice_candidate)
+                .await
+                .map_err(NegotiatorError::Engine),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn on_local_ice_candidate(
+        &self,
+        ice_candidate: IceCandidate,
+    ) -> Result<(), NegotiatorError<E::Error, T::Error>> {
+        if self.negotiation.state() == NegotiationState::Closed {
+            return Err(NegotiationError::Closed.into());
+        }
+        self.transport
+            .send_ice_candidate(ice_candidate)
+            .await
+            .map_err(NegotiatorError::Transport)
+    }
+
+    pub fn close(&self) {
+        self.negotiation.close()
+    }
+
+    async fn set_local_offer(
+        &self,
+        is_ice_restart: bool,
+    ) -> Result<SessionDescription, NegotiatorError<E::Error, T::Error>> {
+        let sdp = self
+            .engine
+            .create_offer(is_ice_restart)
+            .await
+            .map_err(NegotiatorError::Engine)?;
+        self.engine
+            .set_local_description(SdpType::Offer, &sdp)
+            .await
+            .map_err(NegotiatorError::Engine)?;
+        let _: NegotiationState = self.negotiation.apply(NegotiationStep::LocalOffer)?;
+        Ok(sdp)
+    }
+
+    async fn rollback(&self) -> Result<(), NegotiatorError<E::Error, T::Error>> {
+        let _: NegotiationState = self
+            .negotiation
+            .next_state(NegotiationStep::LocalRollback)?;
+        self.engine
+            .set_local_description(SdpType::Rollback, &SessionDescription(String::new()))
+            .await
+            .map_err(NegotiatorError::Engine)?;
+        let _: NegotiationState = self.negotiation.apply(NegotiationStep::LocalRollback)?;
+        Ok(())
+    }
+
+    async fn add_buffered_ice_candidates(&self) -> Result<(), NegotiatorError<E::Error, T::Error>> {
+        for ice_candidate in self.negotiation.remote_candidates().on_remote_description() {
+            self.engine
+                .add_ice_candidate(&ice_candidate)
+                .await
+                .map_err(NegotiatorError::Engine)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+pub enum NegotiationError {
+    #[error("{step:?} is unexpected in the {state:?} negotiation state")]
+    UnexpectedStep {
+        state: NegotiationState,
+        step: NegotiationStep,
+    },
+    #[error("negotiation is closed")]
+    Closed,
+}
+
+#[derive(Debug, Error)]
+pub enum NegotiatorError<E: Debug, T: Debug> {
+    #[error(transparent)]
+    Negotiation(#[from] NegotiationError),
+    #[error("rtc engine error: {0:?}")]
+    Engine(E),
+    #[error("signaling transport error: {0:?}")]
+    Transport(T),
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use core::future::Future;
+
+    use super::*;
+
+    /// Polls the future once, test engines and transports complete immediately.
+    fn ready<F: Future>(future: F) -> F::Output {
+        use core::pin::pin;
+        use core::task::{Context, Poll, Waker};
+
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future is not ready"),
+        }
+    }
+
+    fn candidate(index: u16) -> IceCandidate {
+        IceCandidate {
+            candidate: format!("candidate:{}", index),
+            sdp_mid: None,
+            sdp_m_line_index: Some(index),
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct TestEngine {
+        calls: RefCell<Vec<String>>,
+        fail_remote_description: Cell<bool>,
+    }
+
+    impl TestEngine {
+        fn call(&self, call: String) {
+            self.calls.borrow_mut().push(call)
+        }
+
+        fn take_calls(&self) -> Vec<String> {
+            self.calls.replace(Vec::new())
+        }
+    }
+
+    impl RtcEngine for TestEngine {
+        type Error = &'static str;
+
+        async fn create_offer(
+            &self,
+            is_ice_restart: bool,
+        ) -> Result<SessionDescription, Self::Error> {
+            self.call(format!("create_offer {}", is_ice_restart));
+            Ok(SessionDescription("offer".to_owned()))
+        }
+
+        async fn create_answer(&self) -> Result<SessionDescription, Self::Error> {
+            self.call("create_answer".to_owned());
+            Ok(SessionDescription("answer".to_owned()))
+        }
+
+        async fn set_local_description(
+            &self,
+            sdp_type: SdpType,
+            sdp: &SessionDescription,
+        ) -> Result<(), Self::Error> {
+            self.call(format!("set_local {:?} {}", sdp_type, sdp.0));
+            Ok(())
+        }
+
+        async fn set_remote_description(
+            &self,
+            sdp_type: SdpType,
+            sdp: &SessionDescription,
+        ) -> Result<(), Self::Error> {
+            if self.fail_remote_description.get() {
+                return Err("invalid sdp");
+            }
+            self.call(format!("set_remote {:?} {}", sdp_type, sdp.0));
+            Ok(())
+        }
+
+        async fn add_ice_candidate(&self, ice_candidate: &IceCandidate) -> Result<(), Self::Error> {
+            self.call(format!("add {}", ice_candidate.candidate));
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct TestTransport {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl TestTransport {
+        fn take_sent(&self) -> Vec<String> {
+            self.sent.replace(Vec::new())
+        }
+    }
+
+    impl SignalingTransport for TestTransport {
+        type Error = &'static str;
+
+        async fn send_offer(
+            &self,
+            sdp: SessionDescription,
+            is_ice_restart: bool,
+        ) -> Result<(), Self::Error> {
+            self.sent
+                .borrow_mut()
+                .push(format!("offer {} {}", sdp.0, is_ice_restart));
+            Ok(())
+        }
+
+        async fn send_answer(&self, sdp: SessionDescription) -> Result<(), Self::Error> {
+            self.sent.borrow_mut().push(format!("answer {}", sdp.0));
+            Ok(())
+        }
+
+        async fn send_ice_candidate(&self, ice_candidate: IceCandidate) -> Result<(), Self::Error> {
+            self.sent
+                .borrow_mut()
+                .push(format!("candidate {}", ice_candidate.candidate));
+            Ok(())
+        }
+    }
+
+    fn negotiator() -> Negotiator<TestEngine, TestTransport> {
+        Negotiator::new(TestEngine::default(), TestTransport::default())
+    }
+
+    #[test]
+    fn offerer_adds_buffered_candidates_after_answer() {
+        let negotiator = negotiator();
+
+        ready(negotiator.send_offer(false)).unwrap();
+        assert_eq!(negotiator.state(), NegotiationState::HaveLocalOffer);
+        ready(negotiator.on_remote_ice_candidate(candidate(0))).unwrap();
+        assert_eq!(
+            negotiator.engine().take_calls(),
+            vec!["create_offer false", "set_local Offer offer"]
+        );
+        assert_eq!(
+            negotiator.transport().take_sent(),
+            vec!["offer offer false"]
+        );
+
+        ready(negotiator.on_remote_answer(SessionDescription("answer".to_owned()))).unwrap();
+        ready(negotiator.on_remote_ice_candidate(candidate(1))).unwrap();
+        assert_eq!(negotiator.state(), NegotiationState::Stable);
+        assert_eq!(
+            negotiator.engine().take_calls(),
+            vec![
+                "set_remote Answer answer",
+                "add candidate:0",
+                "add candidate:1"
+            ]
+        );
+    }
+
+    #[test]
+    fn answerer_applies_offer_before_candidates_and_answer() {
+        let negotiator = negotiator();
+
+        ready(negotiator.on_remote_ice_candidate(candidate(0))).unwrap();
+        ready(negotiator.on_remote_offer(SessionDescription("offer".to_owned()))).unwrap();
+        ready(negotiator.on_local_ice_candidate(candidate(1))).unwrap();
+
+        assert_eq!(negotiator.state(), NegotiationState::Stable);
+        assert_eq!(
+            negotiator.engine().take_calls(),
+            vec![
+                "set_remote Offer offer",
+                "add candidate:0",
+                "create_answer",
+                "set_local Answer answer"
+            ]
+        );
+        assert_eq!(
+            negotiator.transport().take_sent(),
+            vec!["answer answer", "candidate candidate:1"]
+        );
+    }
+
+    #[test]
+    fn unexpected_messages_are_rejected() {
+        let negotiator = negotiator();
+
+        assert!(matches!(
+            ready(negotiator.on_remote_answer(SessionDescription("answer".to_owned()))),
+            Err(NegotiatorError::Negotiation(
+                NegotiationError::UnexpectedStep {
+                    state: NegotiationState::Stable,
+                    step: NegotiationStep::RemoteAnswer,
+                }
+            ))
+        ));
+
+        ready(negotiator.send_offer(false)).unwrap();
+        assert!(matches!(
+            ready(negotiator.on_remote_offer(SessionDescription("offer".to_owned()))),
+            Err(NegotiatorError::Negotiation(
+                NegotiationError::UnexpectedStep {
+                    state: NegotiationState::HaveLocalOffer,
+                    step: NegotiationStep::RemoteOffer,
+                }
+            ))
+        ));
+
+        negotiator.close();
+        assert!(matches!(
+            ready(negotiator.send_offer(true)),
+            Err(NegotiatorError::Negotiation(NegotiationError::Closed))
+        ));
+    }
+
+    #[test]
+    fn polite_peer_rolls_back_colliding_offer() {
+        let negotiator = Negotiator::with_role(
+            TestEngine::default(),
+            TestTransport::default(),
+            NegotiationRole::Polite,
+        );
+
+        ready(negotiator.send_offer(false)).unwrap();
+        let _: Vec<_> = negotiator.engine().take_calls();
+        ready(negotiator.on_remote_offer(SessionDescription("remote".to_owned()))).unwrap();
+
+        assert_eq!(negotiator.state(), NegotiationState::Stable);
+        assert_eq!(
+            negotiator.engine().take_calls(),
+            vec![
+                "set_local Rollback ",
+                "set_remote Offer remote",
+                "create_answer",
+                "set_local Answer answer"
+            ]
+        );
+    }
+
+    #[test]
+    fn impolite_peer_ignores_colliding_offer() {
+        let negotiator = Negotiator::with_role(
+            TestEngine::default(),
+            TestTransport::default(),
+            NegotiationRole::Impolite,
+        );
+
+        ready(negotiator.send_offer(false)).unwrap();
+        let _: Vec<_> = negotiator.engine().take_calls();
+        let _: Vec<_> = negotiator.transport().take_sent();
+        ready(negotiator.on_remote_offer(SessionDescription("remote".to_owned()))).unwrap();
+
+        assert_eq!(negotiator.state(), NegotiationState::HaveLocalOffer);
+        assert!(negotiator.engine().take_calls().is_empty());
+        assert!(negotiator.transport().take_sent().is_empty());
+
+        ready(negotiator.on_remote_answer(SessionDescription("answer".to_owned()))).unwrap();
+        ready(negotiator.on_remote_offer(SessionDescription("remote".to_owned()))).unwrap();
+        assert_eq!(negotiator.transport().take_sent(), vec!["answer answer"]);
+    }
+
+    #[test]
+    fn offer_collision_depends_on_role() {
+        let polite = PerfectNegotiation::new(NegotiationRole::Polite);
+        let impolite = PerfectNegotiation::new(NegotiationRole::Impolite);
+
+        assert_eq!(
+            polite.on_remote_offer(NegotiationState::Stable),
+            OfferCollision::None
+        );
+        assert_eq!(
+            impolite.on_remote_offer(NegotiationState::HaveLocalOffer),
+            OfferCollision::Ignore
+        );
+        assert!(impolite.is_ignoring_offer());
+
+        polite.set_making_offer(true);
+        impolite.set_making_offer(true);
+        assert_eq!(
+            polite.on_remote_offer(NegotiationState::Stable),
+            OfferCollision::Rollback
+        );
+        assert_eq!(
+            impolite.on_remote_offer(NegotiationState::Stable),
+            OfferCollision::Ignore
+        );
+        assert!(!polite.is_ignoring_offer());
+    }
+
+    #[test]
+    fn failed_engine_call_keeps_state() {
+        let negotiator = negotiator();
+
+        ready(negotiator.send_offer(false)).unwrap();
+        negotiator.engine().fail_remote_description.set(true);
+        assert!(matches!(
+            ready(negotiator.on_remote_answer(SessionDescription("answer".to_owned()))),
+            Err(NegotiatorError::Engine("invalid sdp"))
+        ));
+        assert_eq!(negotiator.state(), NegotiationState::HaveLocalOffer);
+
+        negotiator.engine().fail_remote_description.set(false);
+        ready(negotiator.on_remote_answer(SessionDescription("answer".to_owned()))).unwrap();
+        assert_eq!(negotiator.state(), NegotiationState::Stable);
+    }
+}
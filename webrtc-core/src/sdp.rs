@@ -0,0 +1,67 @@
+use core::convert::TryFrom;
+
+use signaling_protocol::IceCandidate;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SdpType {
+    Offer,
+    Answer,
+    /// Discards the pending local offer, the description itself is ignored.
+    Rollback,
+}
+
+/// Returns the candidates listed in the session description media sections.
+pub fn sdp_ice_candidates(sdp: &str) -> Vec<IceCandidate> {
+    // The first section is the session description, media sections start with `m=`.
+    let mut candidates = Vec::new();
+    for (sdp_m_line_index, section) in sdp.split("\nm=").skip(1).enumerate() {
+        let lines = || section.lines().map(str::trim);
+        let sdp_mid = lines()
+            .find_map(|line| line.strip_prefix("a=mid:"))
+            .map(str::to_owned);
+        candidates.extend(
+            lines()
+                .filter_map(|line| line.strip_prefix("a="))
+                .filter(|line| line.starts_with("candidate:"))
+                .map(|candidate| IceCandidate {
+                    candidate: candidate.to_owned(),
+                    sdp_mid: sdp_mid.clone(),
+                    sdp_m_line_index: u16::try_from(sdp_m_line_index).ok(),
+                }),
+        );
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_are_read_from_media_sections() {
+        let sdp = "v=0\r\n\
+            a=candidate:0 1 udp 1 10.0.0.1 1000 typ host\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=mid:0\r\n\
+            a=candidate:1 1 udp 1 10.0.0.1 1001 typ host\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=mid:data\r\n\
+            a=candidate:2 1 udp 1 10.0.0.1 1002 typ host\r\n";
+
+        assert_eq!(
+            sdp_ice_candidates(sdp),
+            vec![
+                IceCandidate {
+                    candidate: "candidate:1 1 udp 1 10.0.0.1 1001 typ host".to_owned(),
+                    sdp_mid: Some("0".to_owned()),
+                    sdp_m_line_index: Some(0),
+                },
+                IceCandidate {
+                    candidate: "candidate:2 1 udp 1 10.0.0.1 1002 typ host".to_owned(),
+                    sdp_mid: Some("data".to_owned()),
+                    sdp_m_line_index: Some(1),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use signaling_protocol::CloseReason;
+
+const NO_REASON: u32 = 0;
+const ERROR_REASON_FLAG: u32 = 0x1_0000;
+
+/// Close state of a signaling session shared by its close paths.
+///
+/// The session may be closed explicitly, by the remote peer, by a policy
+/// or on drop, possibly while another close path is still running,
+/// only the first close is effective and the close message is sent at most once.
+#[derive(Debug, Default)]
+pub struct SessionTeardown {
+    reason: AtomicU32,
+    is_close_message_sent: AtomicBool,
+}
+
+impl SessionTeardown {
+    /// Closes the session, returns `false` if it was already closed,
+    /// the reason of the first close is kept in that case.
+    pub fn close(&self, reason: CloseReason) -> bool {
+        self.reason
+            .compare_exchange(
+                NO_REASON,
+                encode_reason(reason),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    /// Why the session was closed, `None` while it is open.
+    pub fn reason(&self) -> Option<CloseReason> {
+        decode_reason(self.reason.load(Ordering::Acquire))
+    }
+
+    /// Returns the reason to send in the close message once,
+    /// the session is closed as `CloseReason::LocalClosed` if it is still open.
+    ///
+    /// Returns `None` if the message was already sent or skipped,
+    /// or if the transport is no longer open, the server releases
+    /// the session of a closed transport by itself.
+    pub fn take_close_message(&self, is_transport_open: bool) -> Option<CloseReason> {
+        let _: bool = self.close(CloseReason::LocalClosed);
+        if self.is_close_message_sent.swap(true, Ordering::AcqRel) || !is_transport_open {
+            return None;
+        }
+        self.reason()
+    }
+
+    /// Marks the close message as not needed, e.g. when the server rejected the session.
+    pub fn skip_close_message(&self) {
+        self.is_close_message_sent.store(true, Ordering::Release);
+    }
+}
+
+fn encode_reason(reason: CloseReason) -> u32 {
+    match reason {
+        CloseReason::LocalClosed => 1,
+        CloseReason::RemoteClosed => 2,
+        CloseReason::Kicked => 3,
+        CloseReason::ServerShutdown => 4,
+        CloseReason::Timeout => 5,
+        CloseReason::Error(code) => ERROR_REASON_FLAG | u32::from(code),
+    }
+}
+
+fn decode_reason(value: u32) -> Option<CloseReason> {
+    match value {
+        NO_REASON => None,
+        1 => Some(CloseReason::LocalClosed),
+        2 => Some(CloseReason::RemoteClosed),
+        3 => Some(CloseReason::Kicked),
+        4 => Some(CloseReason::ServerShutdown),
+        5 => Some(CloseReason::Timeout),
+        value => Some(CloseReason::Error((value & !ERROR_REASON_FLAG) as u16)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn close_then_drop_sends_close_message_once() {
+        let teardown = SessionTeardown::default();
+
+        assert!(teardown.close(CloseReason::LocalClosed));
+        assert_eq!(
+            teardown.take_close_message(true),
+            Some(CloseReason::LocalClosed)
+        );
+        // Dropped after the explicit close.
+        assert_eq!(teardown.take_close_message(true), None);
+        assert_eq!(teardown.reason(), Some(CloseReason::LocalClosed));
+    }
+
+    #[test]
+    fn first_close_reason_is_kept() {
+        let teardown = SessionTeardown::default();
+
+        assert!(teardown.close(CloseReason::Error(300)));
+        assert!(!teardown.close(CloseReason::RemoteClosed));
+        assert_eq!(
+            teardown.take_close_message(true),
+            Some(CloseReason::Error(300))
+        );
+    }
+
+    #[test]
+    fn close_message_is_not_sent_over_closed_transport_or_after_skip() {
+        let teardown = SessionTeardown::default();
+        assert_eq!(teardown.take_close_message(false), None);
+        assert_eq!(teardown.take_close_message(true), None);
+        assert!(teardown.is_closed());
+
+        let teardown = SessionTeardown::default();
+        teardown.skip_close_message();
+        assert!(!teardown.is_closed());
+        assert_eq!(teardown.take_close_message(true), None);
+    }
+
+    #[test]
+    fn racing_close_paths_close_once() {
+        let teardown = Arc::new(SessionTeardown::default());
+        let reasons = [
+            CloseReason::LocalClosed,
+            CloseReason::RemoteClosed,
+            CloseReason::Timeout,
+            CloseReason::PROTOCOL_VIOLATION,
+        ];
+
+        let results: Vec<_> = reasons
+            .iter()
+            .map(|&reason| {
+                let teardown = Arc::clone(&teardown);
+                thread::spawn(move || {
+                    let is_closed = teardown.close(reason);
+                    (is_closed, teardown.take_close_message(true))
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        let winners: Vec<_> = reasons
+            .iter()
+            .zip(&results)
+            .filter(|(_, (is_closed, _))| *is_closed)
+            .map(|(&reason, _)| reason)
+            .collect();
+        let messages: Vec<_> = results.iter().filter_map(|(_, message)| *message).collect();
+        assert_eq!(winners.len(), 1);
+        assert_eq!(messages, winners);
+        assert_eq!(teardown.reason(), Some(winners[0]));
+    }
+}
@@ -0,0 +1,19 @@
+use core::fmt::Debug;
+
+use signaling_protocol::{IceCandidate, SessionDescription};
+
+/// Delivers the negotiation messages to the peer, e.g. through the signaling server.
+#[allow(async_fn_in_trait)]
+pub trait SignalingTransport {
+    type Error: Debug;
+
+    async fn send_offer(
+        &self,
+        sdp: SessionDescription,
+        is_ice_restart: bool,
+    ) -> Result<(), Self::Error>;
+
+    async fn send_answer(&self, sdp: SessionDescription) -> Result<(), Self::Error>;
+
+    async fn send_ice_candidate(&self, ice_candidate: IceCandidate) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,35 @@
+use clap::{AppSettings, Clap};
+
+use crate::{BuildClientOptions, DistOptions, E2eOptions, ServeDevOptions};
+
+#[derive(Clap)]
+#[clap(about = "Build and test automation of the project")]
+#[clap(setting = AppSettings::ColoredHelp)]
+struct Options {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Clap)]
+enum Command {
+    /// Builds the client into `client/dist`
+    BuildClient(BuildClientOptions),
+    /// Serves the client and runs the signaling server, rebuilding both on changes
+    ServeDev(ServeDevOptions),
+    /// Runs the end-to-end test of the signaling server and two headless browser clients
+    RunE2e(E2eOptions),
+    /// Builds a deployable bundle of the server and the client it serves
+    Dist(DistOptions),
+}
+
+pub async fn app() -> anyhow::Result<()> {
+    use crate::{build_client, dist, run_e2e, serve_dev};
+
+    let opts: Options = Options::parse();
+    match opts.command {
+        Command::BuildClient(opts) => build_client(opts).await,
+        Command::ServeDev(opts) => serve_dev(opts).await,
+        Command::RunE2e(opts) => run_e2e(opts).await,
+        Command::Dist(opts) => dist(opts).await,
+    }
+}
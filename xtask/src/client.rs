@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use clap::Clap;
+
+/// Client sources and its local dependencies rebuilt by `serve-dev` on change.
+pub const CLIENT_WATCH_DIRS: &[&str] = &[
+    ".",
+    "../browser-webrtc",
+    "../signaling-protocol",
+    "../webrtc-core",
+];
+
+/// Server address expression that points to the host the page is loaded from.
+const SAME_ORIGIN_SERVER_ADDRESS: &str =
+    "(window.location.protocol === \"https:\" ? \"wss://\" : \"ws://\") + window.location.host";
+
+#[derive(Clap)]
+pub struct BuildClientOptions {
+    /// Default signaling server address, the address the page is loaded from if not specified
+    #[clap(long)]
+    server_address: Option<String>,
+    /// Builds without optimizations
+    #[clap(long)]
+    debug: bool,
+}
+
+pub async fn build_client(opts: BuildClientOptions) -> anyhow::Result<()> {
+    use crate::project_root;
+
+    let client_dir = project_root().join("client");
+    write_server_address(&client_dir, opts.server_address.as_deref()).await?;
+    trunk_build(&client_dir, !opts.debug).await
+}
+
+/// Writes the script inlined into the client page that sets its default signaling server address.
+pub async fn write_server_address(
+    client_dir: &Path,
+    server_address: Option<&str>,
+) -> anyhow::Result<()> {
+    use tokio::fs;
+
+    let expression = match server_address {
+        Some(server_address) => format!("{:?}", server_address),
+        None => SAME_ORIGIN_SERVER_ADDRESS.to_owned(),
+    };
+    fs::create_dir_all(client_dir.join("target")).await?;
+    fs::write(
+        client_dir.join("target/server_address.js"),
+        format!("window.server_address = {};\n", expression),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Builds the client into the `client/dist` directory.
+pub async fn trunk_build(client_dir: &Path, is_release: bool) -> anyhow::Result<()> {
+    use crate::run;
+    use tokio::process::Command;
+
+    let mut command = Command::new("trunk");
+    let _ = command
+        .args(&["build", "-d", "dist"])
+        .current_dir(client_dir);
+    if is_release {
+        let _ = command.arg("--release");
+    }
+    run(&mut command).await
+}
@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use clap::Clap;
+
+/// Bundle startup script, the bundle directory is resolved from the script path.
+const RUN_SCRIPT: &str = r#"#!/bin/sh
+DIR="$(cd "$(dirname "$0")" && pwd)"
+exec "$DIR/server" --static-dir "$DIR/static" "$@"
+"#;
+
+#[derive(Clap)]
+pub struct DistOptions {
+    /// Output directory, replaced if exists
+    #[clap(short, long, default_value = "dist")]
+    output: String,
+}
+
+/// Builds a deployable bundle of the signaling server serving the client on the same port.
+pub async fn dist(opts: DistOptions) -> anyhow::Result<()> {
+    use crate::{project_root, run, trunk_build, write_server_address};
+    use tokio::fs;
+    use tokio::process::Command;
+
+    let root = project_root();
+    let output = root.join(&opts.output);
+    let client_dir = root.join("client");
+    let server_dir = root.join("server");
+
+    write_server_address(&client_dir, None).await?;
+    trunk_build(&client_dir, true).await?;
+    run(Command::new("cargo")
+        .args(&["build", "--release"])
+        .current_dir(&server_dir))
+    .await?;
+
+    if fs::metadata(&output).await.is_ok() {
+        fs::remove_dir_all(&output).await?;
+    }
+    fs::create_dir_all(&output).await?;
+    copy_dir(&client_dir.join("dist"), &output.join("static")).await?;
+    let server_file_name = format!("server{}", std::env::consts::EXE_SUFFIX);
+    let _: u64 = fs::copy(
+        server_dir.join("target/release").join(&server_file_name),
+        output.join(&server_file_name),
+    )
+    .await?;
+    write_run_script(&output.join("run.sh")).await?;
+
+    println!("bundle is written to {}", output.display());
+    Ok(())
+}
+
+async fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    use tokio::fs;
+
+    fs::create_dir_all(to).await?;
+    let mut entries = fs::read_dir(from).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if entry.file_type().await?.is_dir() {
+            Box::pin(copy_dir(&path, &to.join(entry.file_name()))).await?;
+        } else {
+            let _: u64 = fs::copy(&path, to.join(entry.file_name())).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn write_run_script(path: &Path) -> anyhow::Result<()> {
+    use tokio::fs;
+
+    fs::write(path, RUN_SCRIPT).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+    Ok(())
+}
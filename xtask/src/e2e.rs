@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use clap::Clap;
+use fantoccini::elements::Element;
+use fantoccini::{Client, Locator};
+
+/// Timeout of the server and client builds.
+const BUILD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Timeout of every awaited page state.
+const STEP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Text sent from the sender to the receiver through the WebRTC data channel.
+const SENDER_MESSAGE: &str = "hello from the e2e sender";
+
+#[derive(Clap)]
+pub struct E2eOptions {
+    /// Signaling server port
+    #[clap(long, default_value = "9011")]
+    server_port: u16,
+    /// Client development server port
+    #[clap(long, default_value = "8081")]
+    client_port: u16,
+    /// URL of an already running WebDriver, `chromedriver` is started if not specified
+    #[clap(long)]
+    webdriver: Option<String>,
+    /// Path to the chromedriver executable
+    #[clap(long, default_value = "chromedriver")]
+    chromedriver: String,
+    /// ChromeDriver port
+    #[clap(long, default_value = "4444")]
+    chromedriver_port: u16,
+    /// Shows the browser windows
+    #[clap(long)]
+    headed: bool,
+}
+
+/// Starts the signaling server and the client, opens a channel in one browser,
+/// joins it in another one and checks that the data flows both ways.
+pub async fn run_e2e(opts: E2eOptions) -> anyhow::Result<()> {
+    use crate::{project_root, run, spawn, trunk_build, wait_for_port, write_server_address};
+    use tokio::process::Command;
+
+    let root = project_root();
+
+    run(Command::new("cargo")
+        .args(&["build", "--release"])
+        .current_dir(root.join("server")))
+    .await?;
+    let _server = spawn(
+        Command::new(root.join("server/target/release/server")).args(&[
+            "-a",
+            "127.0.0.1",
+            "-p",
+            &opts.server_port.to_string(),
+        ]),
+    )?;
+    wait_for_port(opts.server_port, STEP_TIMEOUT).await?;
+
+    let client_dir = root.join("client");
+    write_server_address(
+        &client_dir,
+        Some(&format!("ws://127.0.0.1:{}", opts.server_port)),
+    )
+    .await?;
+    trunk_build(&client_dir, true).await?;
+    let _client = spawn(
+        Command::new("trunk")
+            .args(&["serve", "--release", "-d", "dist", "--no-autoreload"])
+            .args(&["--port", &opts.client_port.to_string()])
+            .current_dir(&client_dir),
+    )?;
+    wait_for_port(opts.client_port, BUILD_TIMEOUT).await?;
+
+    let (webdriver, _chromedriver) = match opts.webdriver.clone() {
+        Some(webdriver) => (webdriver, None),
+        None => {
+            let chromedriver = spawn(
+                Command::new(&opts.chromedriver).arg(format!("--port={}", opts.chromedriver_port)),
+            )?;
+            wait_for_port(opts.chromedriver_port, STEP_TIMEOUT).await?;
+            (
+                format!("http://127.0.0.1:{}", opts.chromedriver_port),
+                Some(chromedriver),
+            )
+        }
+    };
+
+    let client_url = format!("http://127.0.0.1:{}", opts.client_port);
+    let sender = connect_browser(&webdriver, opts.headed).await?;
+    let receiver = match connect_browser(&webdriver, opts.headed).await {
+        Ok(receiver) => receiver,
+        Err(err) => {
+            let _ = sender.close().await;
+            return Err(err);
+        }
+    };
+
+    let result = run_scenario(&sender, &receiver, &client_url).await;
+    let _ = sender.close().await;
+    let _ = receiver.close().await;
+    result?;
+
+    println!("e2e test passed");
+    Ok(())
+}
+
+async fn connect_browser(webdriver: &str, is_headed: bool) -> anyhow::Result<Client> {
+    use fantoccini::ClientBuilder;
+    use serde_json::{json, Map};
+
+    let mut args = vec![
+        "--no-sandbox",
+        "--use-fake-device-for-media-stream",
+        "--use-fake-ui-for-media-stream",
+        "--autoplay-policy=no-user-gesture-required",
+    ];
+    if !is_headed {
+        args.push("--headless");
+    }
+    let mut capabilities = Map::new();
+    let _ = capabilities.insert("goog:chromeOptions".to_owned(), json!({ "args": args }));
+
+    Ok(ClientBuilder::native()
+        .capabilities(capabilities)
+        .connect(webdriver)
+        .await?)
+}
+
+async fn run_scenario(sender: &Client, receiver: &Client, client_url: &str) -> anyhow::Result<()> {
+    for browser in [sender, receiver] {
+        browser.goto(client_url).await?;
+        click(browser, "//button[normalize-space()='Join server']").await?;
+        let _ = wait_for(browser, "//div[normalize-space()='state: open']").await?;
+    }
+
+    let channel_name = wait_for(sender, "//label[contains(., 'channel name:')]/input")
+        .await?
+        .prop("value")
+        .await?
+        .unwrap_or_default();
+    click(sender, "//button[normalize-space()='Test devices']").await?;
+    let _ = wait_for(sender, "//*[contains(text(), 'devices are working')]").await?;
+    click(sender, "//button[normalize-space()='Open channel']").await?;
+
+    click(
+        receiver,
+        &format!(
+            "//button[normalize-space()='Join channel: {}']",
+            channel_name
+        ),
+    )
+    .await?;
+    let _ = wait_for(
+        sender,
+        "//div[normalize-space()='receiver presence: connected']",
+    )
+    .await?;
+    let _ = wait_for(receiver, "//div[@class='video']/video").await?;
+
+    // Sender to receiver, through the WebRTC data channel.
+    wait_for(
+        sender,
+        "//label[div='WebRtc DataChannel']/textarea[not(@readonly)]",
+    )
+    .await?
+    .send_keys(SENDER_MESSAGE)
+    .await?;
+    let _ = wait_for(
+        receiver,
+        &format!(
+            "//label[div='WebRtc DataChannel']/textarea[contains(., '{}')]",
+            SENDER_MESSAGE
+        ),
+    )
+    .await?;
+
+    // Receiver to sender, through the peer state control channel.
+    click(receiver, "//label[normalize-space()='Away']/input").await?;
+    let _ = wait_for(sender, "//div[normalize-space()='receiver: away']").await?;
+
+    Ok(())
+}
+
+async fn wait_for(browser: &Client, xpath: &str) -> anyhow::Result<Element> {
+    use anyhow::Context;
+
+    browser
+        .wait()
+        .at_most(STEP_TIMEOUT)
+        .for_element(Locator::XPath(xpath))
+        .await
+        .with_context(|| format!("element {} is not found", xpath))
+}
+
+async fn click(browser: &Client, xpath: &str) -> anyhow::Result<()> {
+    wait_for(browser, xpath).await?.click().await?;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+#![warn(
+    clippy::all,
+    rust_2018_idioms,
+    missing_copy_implementations,
+    missing_debug_implementations,
+    single_use_lifetimes,
+    trivial_casts,
+    unused_import_braces,
+    unused_qualifications,
+    unused_results
+)]
+
+mod app;
+mod client;
+mod dist;
+mod e2e;
+mod process;
+mod serve_dev;
+
+use app::app;
+use client::{
+    build_client, trunk_build, write_server_address, BuildClientOptions, CLIENT_WATCH_DIRS,
+};
+use dist::{dist, DistOptions};
+use e2e::{run_e2e, E2eOptions};
+use process::{project_root, run, spawn, wait_for_port};
+use serve_dev::{serve_dev, ServeDevOptions};
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    app().await
+}
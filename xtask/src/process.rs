@@ -0,0 +1,52 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use tokio::process::{Child, Command};
+
+/// Interval between the port availability checks.
+const PORT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub fn project_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_owned()
+}
+
+/// Runs the command to completion and fails if it exits unsuccessfully.
+pub async fn run(command: &mut Command) -> anyhow::Result<()> {
+    let status = command
+        .status()
+        .await
+        .with_context(|| format!("failed to run {:?}", command))?;
+    if !status.success() {
+        bail!("{:?} exited with {}", command, status);
+    }
+    Ok(())
+}
+
+/// Spawns the long-running command, it is killed when the returned child is dropped.
+pub fn spawn(command: &mut Command) -> anyhow::Result<Child> {
+    command
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("failed to spawn {:?}", command))
+}
+
+/// Waits until the local port accepts connections.
+pub async fn wait_for_port(port: u16, timeout: Duration) -> anyhow::Result<()> {
+    use tokio::net::TcpStream;
+    use tokio::time::{sleep, Instant};
+
+    let deadline = Instant::now() + timeout;
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        if Instant::now() >= deadline {
+            bail!("port {} is not opened in {:?}", port, timeout);
+        }
+        sleep(PORT_POLL_INTERVAL).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use clap::Clap;
+
+#[derive(Clap)]
+pub struct ServeDevOptions {
+    /// Signaling server IP address to bind, also used by the client to connect
+    #[clap(short, long, default_value = "127.0.0.1")]
+    address: String,
+    /// Signaling server port
+    #[clap(short, long, default_value = "9010")]
+    port: u16,
+    /// Client development server port
+    #[clap(long, default_value = "8080")]
+    client_port: u16,
+}
+
+/// Serves the client and runs the signaling server, rebuilding both on source changes.
+pub async fn serve_dev(opts: ServeDevOptions) -> anyhow::Result<()> {
+    use crate::{project_root, spawn, write_server_address, CLIENT_WATCH_DIRS};
+    use anyhow::bail;
+    use tokio::process::Command;
+
+    let root = project_root();
+    let client_dir = root.join("client");
+    write_server_address(
+        &client_dir,
+        Some(&format!("ws://{}:{}", opts.address, opts.port)),
+    )
+    .await?;
+
+    let mut client_command = Command::new("trunk");
+    let _ = client_command
+        .args(&["serve", "--release", "-d", "dist"])
+        .args(&["--port", &opts.client_port.to_string()])
+        .current_dir(&client_dir);
+    for dir in CLIENT_WATCH_DIRS {
+        let _ = client_command.args(&["-w", dir]);
+    }
+    let mut client = spawn(&mut client_command)?;
+
+    let mut server = spawn(
+        Command::new("cargo")
+            .args(&["watch", "-s"])
+            .arg(format!("cargo run -- -a {} -p {}", opts.address, opts.port))
+            .env("RUST_LOG", "debug")
+            .current_dir(root.join("server")),
+    )?;
+
+    tokio::select! {
+        status = client.wait() => bail!("client development server exited with {}", status?),
+        status = server.wait() => bail!("server watcher exited with {}", status?),
+    }
+}